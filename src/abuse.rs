@@ -0,0 +1,399 @@
+// abuse heuristics with automatic throttling and suspension
+
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Mutex, Once, PoisonError};
+use std::time::{Duration, Instant};
+
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum::http::{request::Parts, Extensions, HeaderMap};
+use regex::Regex;
+use serde::Serialize;
+use tracing::{error, warn};
+
+use crate::auth::ApiKeyName;
+use crate::config;
+
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+const INJECTION_PATTERNS: &[&str] = &[
+    r"ignore (all |any )?(previous|prior|above) (instructions|prompts?)",
+    r"disregard (the |your )?(system|previous) (prompt|instructions)",
+    r"(reveal|print|show|repeat) (me )?(your|the) (system prompt|instructions|preamble)",
+    r"you are now (dan|an? unrestricted)",
+    r"jailbreak",
+    r"developer mode",
+];
+
+// asks for work that has nothing to do with planning a conference visit
+const OFF_TOPIC_PATTERNS: &[&str] = &[
+    r"\bwrite (me )?(a |an |some )?(poem|essay|story|song|lyrics|cover letter|code|script|program|function)\b",
+    r"\b(do|solve|finish) (my|this|the) (homework|assignment|exam|equation)\b",
+    r"\b(generate|create|make) (a |an )?(password|malware|virus|exploit|phishing)",
+    r"\bhow (do i|to) (hack|ddos|phish|crack)\b",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AbuseSignal {
+    PromptInjection,
+    Nonsense,
+    OffTopic,
+    // flagged by the moderation api, see objective.rs
+    Moderation,
+}
+
+impl AbuseSignal {
+    // the `reason` of an objective_rejected error
+    pub fn reason(self) -> &'static str {
+        match self {
+            AbuseSignal::PromptInjection => "prompt_injection",
+            AbuseSignal::Nonsense => "nonsense",
+            AbuseSignal::OffTopic => "off_topic",
+            AbuseSignal::Moderation => "moderation",
+        }
+    }
+}
+
+pub enum Verdict {
+    Allow,
+    // this request is refused, the client is not blocked yet
+    Reject(AbuseSignal),
+    Throttled(Duration),
+    Suspended,
+}
+
+#[derive(Default)]
+struct ClientRecord {
+    requests: VecDeque<Instant>,
+    strikes: VecDeque<(Instant, AbuseSignal)>,
+    throttled_until: Option<Instant>,
+    suspended: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FlaggedClient {
+    pub client: String,
+    pub status: &'static str,
+    pub strikes: usize,
+    pub signals: Vec<AbuseSignal>,
+    pub throttled_for_secs: Option<u64>,
+}
+
+pub struct AbuseMonitor {
+    clients: Mutex<HashMap<String, ClientRecord>>,
+    injection_patterns: Vec<Regex>,
+    off_topic_patterns: Vec<Regex>,
+    burst_limit: usize,
+    burst_window: Duration,
+    strike_window: Duration,
+    throttle_after: usize,
+    suspend_after: usize,
+    throttle_duration: Duration,
+}
+
+impl AbuseMonitor {
+    pub fn from_env() -> Self {
+        let limits = &config::get().abuse;
+        Self {
+            clients: Mutex::new(HashMap::new()),
+            injection_patterns: compile(INJECTION_PATTERNS),
+            off_topic_patterns: compile(OFF_TOPIC_PATTERNS),
+            burst_limit: limits.burst_limit,
+            burst_window: limits.burst_window,
+            strike_window: limits.strike_window,
+            throttle_after: limits.throttle_after,
+            suspend_after: limits.suspend_after,
+            throttle_duration: limits.throttle_duration,
+        }
+    }
+
+    // record the request and decide whether to serve it
+    pub fn check(&self, client: &str, objective: &str) -> Verdict {
+        let now = Instant::now();
+        let signal = self.classify_objective(objective);
+
+        let mut clients = self.clients.lock().unwrap_or_else(PoisonError::into_inner);
+        if clients.len() > MAX_TRACKED_CLIENTS {
+            self.prune(&mut clients, now);
+        }
+        let record = clients.entry(client.to_string()).or_default();
+
+        if record.suspended {
+            return Verdict::Suspended;
+        }
+        if let Some(until) = record.throttled_until {
+            if until > now {
+                return Verdict::Throttled(until - now);
+            }
+            record.throttled_until = None;
+        }
+
+        record.requests.push_back(now);
+        while record
+            .requests
+            .front()
+            .is_some_and(|t| now.duration_since(*t) > self.burst_window)
+        {
+            record.requests.pop_front();
+        }
+
+        if let Some(signal) = signal {
+            return self.strike(client, record, signal, now);
+        }
+        // a burst is throttled without a strike, a legitimate batch gets there too.
+        // the refused request does not count towards the window
+        if record.requests.len() > self.burst_limit {
+            record.requests.pop_back();
+            let oldest = record.requests.front().copied().unwrap_or(now);
+            return Verdict::Throttled(
+                self.burst_window
+                    .saturating_sub(now.duration_since(oldest))
+                    .max(Duration::from_secs(1)),
+            );
+        }
+        Verdict::Allow
+    }
+
+    // a signal found after check() let the request through, e.g. by the moderation api
+    pub fn flag(&self, client: &str, signal: AbuseSignal) -> Verdict {
+        let now = Instant::now();
+        let mut clients = self.clients.lock().unwrap_or_else(PoisonError::into_inner);
+        let record = clients.entry(client.to_string()).or_default();
+        self.strike(client, record, signal, now)
+    }
+
+    fn strike(
+        &self,
+        client: &str,
+        record: &mut ClientRecord,
+        signal: AbuseSignal,
+        now: Instant,
+    ) -> Verdict {
+        record.strikes.push_back((now, signal));
+        while record
+            .strikes
+            .front()
+            .is_some_and(|(t, _)| now.duration_since(*t) > self.strike_window)
+        {
+            record.strikes.pop_front();
+        }
+
+        let strikes = record.strikes.len();
+        warn!(
+            client = %client,
+            signal = ?signal,
+            strikes = strikes as u64,
+            "Abuse signal detected"
+        );
+
+        if strikes >= self.suspend_after {
+            warn!(client = %client, "Suspending client after repeated abuse");
+            record.suspended = true;
+            return Verdict::Suspended;
+        }
+        if strikes >= self.throttle_after {
+            warn!(client = %client, "Throttling client after repeated abuse");
+            record.throttled_until = Some(now + self.throttle_duration);
+            return Verdict::Throttled(self.throttle_duration);
+        }
+
+        Verdict::Reject(signal)
+    }
+
+    fn classify_objective(&self, objective: &str) -> Option<AbuseSignal> {
+        if self
+            .injection_patterns
+            .iter()
+            .any(|p| p.is_match(objective))
+        {
+            return Some(AbuseSignal::PromptInjection);
+        }
+        if looks_like_nonsense(objective) {
+            return Some(AbuseSignal::Nonsense);
+        }
+        if self
+            .off_topic_patterns
+            .iter()
+            .any(|p| p.is_match(objective))
+        {
+            return Some(AbuseSignal::OffTopic);
+        }
+        None
+    }
+
+    // drop clients with nothing recent and no sanctions
+    fn prune(&self, clients: &mut HashMap<String, ClientRecord>, now: Instant) {
+        clients.retain(|_, record| {
+            record.suspended
+                || record.throttled_until.is_some_and(|t| t > now)
+                || record
+                    .strikes
+                    .back()
+                    .is_some_and(|(t, _)| now.duration_since(*t) <= self.strike_window)
+                || record
+                    .requests
+                    .back()
+                    .is_some_and(|t| now.duration_since(*t) <= self.burst_window)
+        });
+    }
+
+    // clients with strikes, for admin review
+    pub fn flagged(&self) -> Vec<FlaggedClient> {
+        let now = Instant::now();
+        let clients = self.clients.lock().unwrap_or_else(PoisonError::into_inner);
+
+        clients
+            .iter()
+            .filter(|(_, record)| record.suspended || !record.strikes.is_empty())
+            .map(|(client, record)| {
+                let throttled_for = record
+                    .throttled_until
+                    .filter(|until| *until > now)
+                    .map(|until| (until - now).as_secs());
+                let status = if record.suspended {
+                    "suspended"
+                } else if throttled_for.is_some() {
+                    "throttled"
+                } else {
+                    "watch"
+                };
+
+                FlaggedClient {
+                    client: client.clone(),
+                    status,
+                    strikes: record.strikes.len(),
+                    signals: record.strikes.iter().map(|(_, s)| *s).collect(),
+                    throttled_for_secs: throttled_for,
+                }
+            })
+            .collect()
+    }
+
+    // clear sanctions and strikes, returns false for unknown clients
+    pub fn lift(&self, client: &str) -> bool {
+        let mut clients = self.clients.lock().unwrap_or_else(PoisonError::into_inner);
+        match clients.get_mut(client) {
+            Some(record) => {
+                record.suspended = false;
+                record.throttled_until = None;
+                record.strikes.clear();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn compile(patterns: &[&str]) -> Vec<Regex> {
+    patterns
+        .iter()
+        .filter_map(|p| Regex::new(&format!("(?i){}", p)).ok())
+        .collect()
+}
+
+// keyboard mashing, repeated characters, or almost no letters
+fn looks_like_nonsense(objective: &str) -> bool {
+    let text = objective.trim();
+    let total = text.chars().count();
+    if total < 8 {
+        return false;
+    }
+
+    let letters = text.chars().filter(|c| c.is_alphabetic()).count();
+    if (letters as f32) / (total as f32) < 0.5 {
+        return true;
+    }
+
+    let mut longest_run = 1;
+    let mut run = 1;
+    let chars: Vec<char> = text.chars().collect();
+    for pair in chars.windows(2) {
+        if pair[0] == pair[1] {
+            run += 1;
+            longest_run = longest_run.max(run);
+        } else {
+            run = 1;
+        }
+    }
+    if longest_run >= 6 {
+        return true;
+    }
+
+    // real words have vowels, mashed keys mostly do not. only said of latin words,
+    // scripts like arabic or hindi leave vowels unwritten and cjk has none to count
+    let words: Vec<&str> = text
+        .split_whitespace()
+        .filter(|w| w.chars().count() > 3 && w.chars().all(is_latin))
+        .collect();
+    let vowelless = words
+        .iter()
+        .filter(|w| !w.chars().flat_map(char::to_lowercase).any(is_latin_vowel))
+        .count();
+    !words.is_empty() && vowelless * 2 > words.len()
+}
+
+// ascii, latin-1 and latin extended letters, with punctuation and digits around them
+fn is_latin(c: char) -> bool {
+    !c.is_alphabetic() || c.is_ascii() || ('\u{00c0}'..='\u{024f}').contains(&c)
+}
+
+fn is_latin_vowel(c: char) -> bool {
+    "aeiouyàáâãäåæèéêëìíîïòóôõöøœùúûüýÿąęįųāēīōūăőű".contains(c)
+}
+
+// who strikes and rate limits are kept for: the name of the api key that authenticated
+// the request, otherwise the client address. a presented but unchecked key is not used,
+// the caller could rotate it, and keys of one issuer share their first characters
+pub fn client_identity(headers: &HeaderMap, extensions: &Extensions) -> String {
+    if let Some(name) = extensions.get::<ApiKeyName>() {
+        return format!("key:{}", name.0);
+    }
+    let peer = extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    match client_ip(headers, peer, config::get().trusted_proxy_hops) {
+        Some(ip) => format!("ip:{}", ip),
+        None => {
+            // served without router::serve, every keyless client shares one bucket
+            static NO_PEER: Once = Once::new();
+            NO_PEER.call_once(|| {
+                error!("No client address for keyless requests, they are all counted as one client")
+            });
+            "anonymous".to_string()
+        }
+    }
+}
+
+// the left of X-Forwarded-For is whatever the client sent, only the entries our own
+// proxies appended on the right can be trusted
+fn client_ip(headers: &HeaderMap, peer: Option<IpAddr>, trusted_hops: usize) -> Option<IpAddr> {
+    if trusted_hops == 0 {
+        return peer;
+    }
+    let forwarded: Vec<IpAddr> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|hop| hop.trim().parse().ok())
+        .collect();
+    // fewer hops than proxies, the request did not come through them
+    match forwarded.len().checked_sub(trusted_hops) {
+        Some(index) => Some(forwarded[index]),
+        None => peer,
+    }
+}
+
+// the handlers' side of client_identity
+#[derive(Debug, Clone)]
+pub struct ClientId(pub String);
+
+impl<S: Send + Sync> FromRequestParts<S> for ClientId {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(ClientId(client_identity(&parts.headers, &parts.extensions)))
+    }
+}
@@ -0,0 +1,333 @@
+// admin endpoints, guarded by ADMIN_TOKEN
+
+use std::convert::Infallible;
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Json,
+};
+use futures::StreamExt;
+use serde_json::{json, Map, Value};
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{info, warn};
+
+use crate::auth;
+use crate::catalog_sync;
+use crate::config;
+use crate::copy;
+use crate::error::{self, AppError};
+use crate::feedback::{self, FeedbackQuery};
+use crate::ingest::{self, IngestEvent};
+use crate::pipeline::{generate_plan, GeneratedPlan};
+use crate::replay::{ReplayMode, ReplayReport, ReplayRequest};
+use crate::reply::elapsed_ms;
+use crate::settings;
+use crate::state::AppState;
+use crate::telemetry;
+use crate::usage::UsageReportQuery;
+
+// bearer token check, admin routes are closed when no token is configured
+pub fn require_admin(headers: &HeaderMap) -> Result<(), Response> {
+    let expected = match &config::get().admin_token {
+        Some(token) => token,
+        None => {
+            warn!("Admin request rejected: ADMIN_TOKEN not configured");
+            return Err(error::respond(
+                StatusCode::FORBIDDEN,
+                error::envelope("admin_disabled", "admin endpoints are disabled"),
+            ));
+        }
+    };
+
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    if auth::secret_matches(provided, expected) {
+        Ok(())
+    } else {
+        Err(error::respond(
+            StatusCode::UNAUTHORIZED,
+            error::envelope("invalid_admin_token", "invalid admin token"),
+        ))
+    }
+}
+
+// clients flagged by the abuse heuristics
+pub async fn list_abuse_handler(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection;
+    }
+
+    Json(state.abuse.flagged()).into_response()
+}
+
+// clear throttling/suspension for a client
+pub async fn lift_abuse_handler(
+    State(state): State<AppState>,
+    Path(client): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection;
+    }
+
+    if state.abuse.lift(&client) {
+        info!("Admin lifted abuse sanctions for {}", client);
+        Json(json!({ "client": client, "status": "active" })).into_response()
+    } else {
+        AppError::NotFound(copy::error("error.unknown_client", &[])).into_response()
+    }
+}
+
+// token spend and estimated cost grouped by api key (default), ?by=model|provider
+// or one cost tag, e.g. ?tag=team&since=2025-06-01T00:00:00Z
+pub async fn usage_report_handler(
+    State(state): State<AppState>,
+    Query(query): Query<UsageReportQuery>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection;
+    }
+
+    match state.usage.report(&query) {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to build usage report: {}", e);
+            AppError::Internal(copy::error("error.usage_report_failed", &[])).into_response()
+        }
+    }
+}
+
+// plan ratings and the issues attendees raise most, with what the planner is told
+pub async fn feedback_report_handler(
+    State(state): State<AppState>,
+    Query(query): Query<FeedbackQuery>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection;
+    }
+
+    let profile = match state.conferences.resolve(query.conference.as_deref()) {
+        Ok(profile) => profile.clone(),
+        Err(e) => return AppError::Validation(e).into_response(),
+    };
+    match feedback::summary(&state, &profile, &query) {
+        Ok(summary) => Json(summary).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to build feedback report: {}", e);
+            AppError::Internal(copy::error("error.feedback_report_failed", &[])).into_response()
+        }
+    }
+}
+
+// the runtime settings in use, which of them differ from the environment, and the
+// prompt templates with where each came from
+pub async fn get_config_handler(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection;
+    }
+
+    match settings::view(&state.plans) {
+        Ok(view) => Json(view).into_response(),
+        Err(e) => AppError::from(e).into_response(),
+    }
+}
+
+// only the settings in the body change, e.g. {"plan_temperature": 0.4}. applied to
+// the next request and kept across restarts
+pub async fn update_config_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(changes): Json<Map<String, Value>>,
+) -> Response {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection;
+    }
+
+    match settings::update(&state.plans, changes).await {
+        Ok(_) => match settings::view(&state.plans) {
+            Ok(view) => Json(view).into_response(),
+            Err(e) => AppError::from(e).into_response(),
+        },
+        Err(e) => {
+            warn!("Rejected runtime settings: {}", e);
+            AppError::from(e).into_response()
+        }
+    }
+}
+
+// drop one override and go back to the environment value
+pub async fn reset_config_handler(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection;
+    }
+
+    match settings::reset(&state.plans, &name).await {
+        Ok(_) => match settings::view(&state.plans) {
+            Ok(view) => Json(view).into_response(),
+            Err(e) => AppError::from(e).into_response(),
+        },
+        Err(e) => AppError::from(e).into_response(),
+    }
+}
+
+// shares the catalog sync lease, so a scheduled sync waits for it
+const INGEST_LEASE: Duration = Duration::from_secs(30 * 60);
+
+// full catalog pull and rebuild of everything derived from it, e.g. right before
+// doors open. progress comes as server-sent events, the last one is the report
+pub async fn ingest_handler(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection;
+    }
+
+    let Some(lease) = state
+        .job_locks
+        .try_acquire("catalog-sync", INGEST_LEASE)
+        .await
+    else {
+        return error::respond(
+            StatusCode::CONFLICT,
+            error::envelope("conflict", "a catalog sync or ingestion is already running"),
+        );
+    };
+
+    info!("Admin requested catalog ingestion");
+    let (sender, receiver) = tokio::sync::mpsc::channel(64);
+    let watcher = state.catalog_watcher.clone();
+    // runs to the end even if the admin disconnects. the catalog was just pulled, so
+    // the scheduled sync is held off for an interval, or not at all when it is disabled
+    tokio::spawn(async move {
+        ingest::run_ingestion(&watcher, sender).await;
+        match catalog_sync::get_sync_interval() {
+            Some(interval) => lease.hold_for(interval).await,
+            None => lease.release().await,
+        }
+    });
+
+    let events = ReceiverStream::new(receiver).map(|event: IngestEvent| {
+        Ok::<_, Infallible>(
+            Event::default()
+                .event(event.name())
+                .json_data(&event)
+                .unwrap_or_else(|_| Event::default().event("error").data("serialization failed")),
+        )
+    });
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+// refresh the offline catalog snapshot from upstream
+#[cfg(feature = "offline-catalog")]
+pub async fn sync_catalog_handler(headers: HeaderMap) -> Response {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection;
+    }
+
+    info!("Admin requested offline catalog sync");
+    match crate::catalog::sync_offline_catalog().await {
+        Ok(count) => Json(json!({ "synced_sources": count })).into_response(),
+        Err(e) => {
+            tracing::error!("Offline catalog sync failed: {}", e);
+            AppError::Upstream(e.to_string()).into_response()
+        }
+    }
+}
+
+// re-runs a logged plan request, found by its X-Request-Id or plan id, with the
+// current configuration or the given overrides, and diffs the result against the
+// plan it produced. dry runs store nothing
+pub async fn admin_replay_handler(
+    State(state): State<AppState>,
+    Path(request_id): Path<String>,
+    headers: HeaderMap,
+    request: Option<Json<ReplayRequest>>,
+) -> Response {
+    if let Err(rejection) = require_admin(&headers) {
+        return rejection;
+    }
+    let started = Instant::now();
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+
+    let original = match state.plans.logged(&request_id) {
+        Ok(mut plans) if plans.len() == 1 => plans.remove(0),
+        Ok(plans) if plans.is_empty() => {
+            return AppError::NotFound(copy::error("error.replay_not_found", &[])).into_response()
+        }
+        // a batch, each of its plans is replayed by plan id
+        Ok(plans) => {
+            let plan_ids = plans.into_iter().map(|plan| plan.id).collect();
+            return error::respond(
+                StatusCode::CONFLICT,
+                error::envelope(
+                    "conflict",
+                    "the request produced several plans, replay one by its plan id",
+                )
+                .with_plan_ids(plan_ids),
+            );
+        }
+        Err(e) => {
+            tracing::error!("Failed to load request {} for replay: {}", request_id, e);
+            return AppError::Internal(copy::error("error.replay_load_failed", &[]))
+                .into_response();
+        }
+    };
+    let payload = match request.plan_request(&original) {
+        Ok(payload) => payload,
+        Err(e) => {
+            return AppError::Validation(e).into_response();
+        }
+    };
+
+    info!("Admin replaying plan {} as {:?}", original.id, request.mode);
+    let GeneratedPlan {
+        outcome,
+        conference,
+        usage,
+    } = match generate_plan(&state, payload.clone(), started).await {
+        Ok(generated) => generated,
+        Err(reply) => return reply.into_response(),
+    };
+    let plan_id = match conference {
+        Some(conference) if request.mode == ReplayMode::Live && outcome.next_action.is_none() => {
+            let request_id = telemetry::current_request_id();
+            match state
+                .plans
+                .insert(&payload, &conference, &outcome, request_id.as_deref())
+            {
+                Ok(plan_id) => Some(plan_id),
+                Err(e) => {
+                    tracing::error!("Failed to store replayed plan: {}", e);
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+
+    Json(ReplayReport::new(
+        request.mode,
+        payload,
+        original,
+        outcome,
+        plan_id,
+        usage,
+        elapsed_ms(started),
+    ))
+    .into_response()
+}
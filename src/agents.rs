@@ -0,0 +1,495 @@
+// agent builders shared by the api handlers
+
+use std::sync::Arc;
+
+use rig::agent::AgentBuilder;
+use rig::completion::CompletionModel;
+use rig::prelude::*;
+use rig::tool::{Tool, ToolSetBuilder};
+use tracing::warn;
+
+use crate::build_agent;
+use crate::citations::SourceCollector;
+use crate::conference::ConferenceProfile;
+use crate::config;
+use crate::health::{self, CapabilityMatrix, ToolHealth};
+use crate::language;
+use crate::llm::{AnyAgent, LlmClient};
+use crate::mock::MockAgentBuilder;
+use crate::prompts::{self, PromptKind};
+use crate::tone::{self, Tone};
+use crate::tools::{
+    AssessTimeliness, DetectScheduleConflicts, EstimateTravelTime, ExportItineraryToIcal,
+    FilterByTrack, FindPartners, GetParisWeather, LookupSpeaker, QueryVivatechAPI,
+    QueryVivatechBatch, SuggestNetworkingTargets,
+};
+use crate::validation::Validated;
+
+// tools that depend on an upstream, with the capability as the attendee would call it
+pub const MONITORED_TOOLS: &[(&str, &str)] = &[(
+    QueryVivatechAPI::NAME,
+    "live Vivatech session and partner search",
+)];
+
+// how the planning agent generates, from the configured defaults and request overrides
+#[derive(Debug, Clone)]
+pub struct GenerationParams {
+    pub model: String,
+    pub temperature: f64,
+    pub max_tokens: u64,
+    // e.g. "fr" or "Spanish", the model follows the objective's language when unset
+    pub language: Option<String>,
+    pub tone: Option<Tone>,
+    // write sessions as placeholders that are resolved when the plan is read
+    pub live_placeholders: bool,
+    // one line per session other attendees pick most, empty to leave popularity out
+    pub popular_sessions: String,
+    // one line per top complaint in plan feedback, empty to leave feedback out
+    pub feedback_notes: String,
+}
+
+impl GenerationParams {
+    // configured defaults for `model`
+    pub fn new(model: &str) -> Self {
+        Self {
+            model: model.to_string(),
+            temperature: config::runtime().plan_temperature,
+            max_tokens: config::runtime().plan_max_tokens,
+            language: None,
+            tone: None,
+            live_placeholders: false,
+            popular_sessions: String::new(),
+            feedback_notes: String::new(),
+        }
+    }
+
+    // request overrides on top of the defaults, out of range values are rejected
+    pub fn resolve(
+        model: &str,
+        temperature: Option<f64>,
+        max_tokens: Option<u64>,
+        language: Option<&str>,
+    ) -> Result<Self, String> {
+        let mut params = Self::new(model);
+
+        if let Some(temperature) = temperature {
+            if !(0.0..=2.0).contains(&temperature) {
+                return Err(format!(
+                    "temperature must be between 0 and 2, got {}",
+                    temperature
+                ));
+            }
+            params.temperature = temperature;
+        }
+
+        if let Some(max_tokens) = max_tokens {
+            let limit = config::get().plan_max_tokens_limit;
+            if max_tokens == 0 || max_tokens > limit {
+                return Err(format!(
+                    "max_tokens must be between 1 and {}, got {}",
+                    limit, max_tokens
+                ));
+            }
+            params.max_tokens = max_tokens;
+        }
+
+        if let Some(language) = language.map(str::trim).filter(|l| !l.is_empty()) {
+            // a language tag or name, not free text smuggled into the preamble
+            let valid = language.chars().count() <= 35
+                && language
+                    .chars()
+                    .all(|c| c.is_alphabetic() || c == '-' || c == ' ');
+            if !valid {
+                return Err(format!("unsupported language '{}'", language));
+            }
+            params.language = Some(language.to_string());
+        }
+
+        Ok(params)
+    }
+
+    pub fn with_tone(mut self, tone: Option<Tone>) -> Self {
+        self.tone = tone;
+        self
+    }
+
+    pub fn with_live_placeholders(mut self, live_placeholders: bool) -> Self {
+        self.live_placeholders = live_placeholders;
+        self
+    }
+
+    pub fn with_popular_sessions(mut self, popular_sessions: String) -> Self {
+        self.popular_sessions = popular_sessions;
+        self
+    }
+
+    pub fn with_feedback_notes(mut self, feedback_notes: String) -> Self {
+        self.feedback_notes = feedback_notes;
+        self
+    }
+
+    // the same settings for a fallback model
+    pub fn for_model(&self, model: &str) -> Self {
+        Self {
+            model: model.to_string(),
+            ..self.clone()
+        }
+    }
+}
+
+// build agent with conference context
+pub fn build_planning_agent(
+    client: &LlmClient,
+    profile: &ConferenceProfile,
+    user_context: &str,
+    collector: &SourceCollector,
+    health: &Arc<CapabilityMatrix>,
+    params: &GenerationParams,
+) -> AnyAgent {
+    let current_date = language::format_date(profile.current_date(), params.language.as_deref());
+    let preamble = prompts::text(PromptKind::Preamble);
+    let mut instructions = prompts::render(
+        &preamble,
+        &[
+            ("conference", &profile.name),
+            ("current_date", &current_date),
+            ("user_profile", user_context),
+        ],
+    );
+    // templates that place the profile themselves don't get it twice
+    if !prompts::uses(&preamble, "user_profile") {
+        append_user_context(&mut instructions, user_context);
+    }
+    append_capability_notice(&mut instructions, health);
+    append_popular_sessions(&mut instructions, &params.popular_sessions);
+    append_feedback_notes(&mut instructions, &params.feedback_notes);
+    append_language(&mut instructions, params.language.as_deref());
+    append_placeholder_instructions(&mut instructions, params.live_placeholders);
+    tone::append_tone(&mut instructions, params.tone);
+
+    build_agent!(client, &params.model, |builder| {
+        let builder = builder
+            .preamble(&instructions)
+            .max_tokens(params.max_tokens)
+            .temperature(params.temperature);
+        planning_tools(builder, profile, collector, health).build()
+    })
+}
+
+// what the planner's tools can be added to: a provider's agent builder, the mock's,
+// or the tool set a streamed run calls itself
+pub trait ToolSink: Sized {
+    fn with_tool<T: Tool + 'static>(self, tool: T) -> Self;
+}
+
+impl<M: CompletionModel> ToolSink for AgentBuilder<M> {
+    fn with_tool<T: Tool + 'static>(self, tool: T) -> Self {
+        self.tool(tool)
+    }
+}
+
+impl ToolSink for MockAgentBuilder {
+    fn with_tool<T: Tool + 'static>(self, tool: T) -> Self {
+        self.tool(tool)
+    }
+}
+
+impl ToolSink for ToolSetBuilder {
+    fn with_tool<T: Tool + 'static>(self, tool: T) -> Self {
+        self.static_tool(tool)
+    }
+}
+
+// the planner's tools, the same for agent runs and streamed ones
+pub fn planning_tools<S: ToolSink>(
+    mut tools: S,
+    profile: &ConferenceProfile,
+    collector: &SourceCollector,
+    health: &Arc<CapabilityMatrix>,
+) -> S {
+    if let Some(search) = search_tool(profile, collector, health) {
+        tools = tools.with_tool(search);
+    }
+    if let Some(batch) = batch_search_tool(profile, collector, health) {
+        tools = tools.with_tool(batch);
+    }
+    if let Some(speaker) = speaker_tool(profile, collector, health) {
+        tools = tools.with_tool(speaker);
+    }
+    if let Some(partners) = partners_tool(profile, collector, health) {
+        tools = tools.with_tool(partners);
+    }
+    if let Some(networking) = networking_tool(profile, collector, health) {
+        tools = tools.with_tool(networking);
+    }
+    if let Some(tracks) = track_filter_tool(profile, collector, health) {
+        tools = tools.with_tool(tracks);
+    }
+    if let Some(weather) = GetParisWeather::for_conference(profile) {
+        tools = tools.with_tool(Validated::new(weather));
+    }
+    tools
+        .with_tool(Validated::new(AssessTimeliness::for_conference(profile)))
+        .with_tool(Validated::new(EstimateTravelTime::for_conference(profile)))
+        .with_tool(Validated::new(DetectScheduleConflicts::for_conference(
+            profile,
+        )))
+        .with_tool(Validated::new(ExportItineraryToIcal::for_conference(
+            profile,
+        )))
+}
+
+// build agent that picks one thing to do in the next hour
+pub fn build_next_action_agent(
+    client: &LlmClient,
+    profile: &ConferenceProfile,
+    user_context: &str,
+    collector: &SourceCollector,
+    health: &Arc<CapabilityMatrix>,
+    params: &GenerationParams,
+) -> AnyAgent {
+    let mut instructions = format!(
+        "You are a helpful assistant for {} attendees who need to know what to do right now. \
+        Current date: {}.\n\n\
+        For every request:\n\
+        1. Use the query_vivatech_api tool to find sessions or partners matching the request, \
+        or query_vivatech_api_batch with one query per topic when it covers several\n\
+        2. Pass the results to the assess_event_timeliness tool to rank them by urgency\n\
+        3. Pick the single best action for the next hour, preferring Immediate over Soon over Normal\n\n\
+        Respond with ONLY a JSON object, no prose, with these fields:\n\
+        {{\"action\": what to do, \"location\": where (stage, hall or booth), \
+        \"reason\": why it matters for the user, \"source_id\": id of the source used, \
+        \"urgency\": \"Immediate\" | \"Soon\" | \"Normal\", \"starts_at\": start time or null}}",
+        profile.name,
+        profile.current_date().format("%B %-d, %Y")
+    );
+    append_user_context(&mut instructions, user_context);
+    append_capability_notice(&mut instructions, health);
+
+    build_agent!(client, &params.model, |builder| {
+        let mut builder = builder
+            .preamble(&instructions)
+            .max_tokens(params.max_tokens)
+            .temperature(params.temperature);
+        if let Some(search) = search_tool(profile, collector, health) {
+            builder = builder.tool(search);
+        }
+        if let Some(batch) = batch_search_tool(profile, collector, health) {
+            builder = builder.tool(batch);
+        }
+        builder
+            .tool(Validated::new(AssessTimeliness::for_conference(profile)))
+            .build()
+    })
+}
+
+// live search, left out while its circuit is open
+pub fn search_tool(
+    profile: &ConferenceProfile,
+    collector: &SourceCollector,
+    health: &Arc<CapabilityMatrix>,
+) -> Option<Validated<QueryVivatechAPI>> {
+    if health.status(QueryVivatechAPI::NAME) == ToolHealth::Down {
+        warn!("Search tool is down, building agent without it");
+        return None;
+    }
+
+    Some(Validated::new(
+        QueryVivatechAPI::for_conference(profile)
+            .with_collector(collector.clone())
+            .with_health(health.clone()),
+    ))
+}
+
+// the same search fanned out over several queries, down with it
+pub fn batch_search_tool(
+    profile: &ConferenceProfile,
+    collector: &SourceCollector,
+    health: &Arc<CapabilityMatrix>,
+) -> Option<Validated<QueryVivatechBatch>> {
+    if health.status(QueryVivatechAPI::NAME) == ToolHealth::Down {
+        return None;
+    }
+
+    Some(Validated::new(QueryVivatechBatch::new(
+        QueryVivatechAPI::for_conference(profile)
+            .with_collector(collector.clone())
+            .with_health(health.clone()),
+    )))
+}
+
+// speaker lookups run through the same search, down with it
+pub fn speaker_tool(
+    profile: &ConferenceProfile,
+    collector: &SourceCollector,
+    health: &Arc<CapabilityMatrix>,
+) -> Option<Validated<LookupSpeaker>> {
+    if health.status(QueryVivatechAPI::NAME) == ToolHealth::Down {
+        return None;
+    }
+
+    Some(Validated::new(LookupSpeaker::new(
+        QueryVivatechAPI::for_conference(profile)
+            .with_collector(collector.clone())
+            .with_health(health.clone()),
+        profile,
+    )))
+}
+
+// exhibitor search runs through the same search, down with it
+pub fn partners_tool(
+    profile: &ConferenceProfile,
+    collector: &SourceCollector,
+    health: &Arc<CapabilityMatrix>,
+) -> Option<Validated<FindPartners>> {
+    if health.status(QueryVivatechAPI::NAME) == ToolHealth::Down {
+        return None;
+    }
+
+    Some(Validated::new(FindPartners::new(
+        QueryVivatechAPI::for_conference(profile)
+            .with_collector(collector.clone())
+            .with_health(health.clone()),
+        profile,
+    )))
+}
+
+// networking shortlists run through the same search, down with it
+pub fn networking_tool(
+    profile: &ConferenceProfile,
+    collector: &SourceCollector,
+    health: &Arc<CapabilityMatrix>,
+) -> Option<Validated<SuggestNetworkingTargets>> {
+    if health.status(QueryVivatechAPI::NAME) == ToolHealth::Down {
+        return None;
+    }
+
+    Some(Validated::new(SuggestNetworkingTargets::new(
+        QueryVivatechAPI::for_conference(profile)
+            .with_collector(collector.clone())
+            .with_health(health.clone()),
+        profile,
+    )))
+}
+
+// track filtering runs through the same search, down with it
+pub fn track_filter_tool(
+    profile: &ConferenceProfile,
+    collector: &SourceCollector,
+    health: &Arc<CapabilityMatrix>,
+) -> Option<Validated<FilterByTrack>> {
+    if health.status(QueryVivatechAPI::NAME) == ToolHealth::Down {
+        return None;
+    }
+
+    Some(Validated::new(FilterByTrack::new(
+        QueryVivatechAPI::for_conference(profile)
+            .with_collector(collector.clone())
+            .with_health(health.clone()),
+        profile,
+    )))
+}
+
+// build agent that turns attendance data into highlights and follow-ups
+pub fn build_recap_agent(client: &LlmClient, model: &str) -> AnyAgent {
+    const RECAP_INSTRUCTIONS: &str = "\
+        You write post-conference recaps for attendees. \
+        From the sessions, feedback and contacts you are given, respond with ONLY a JSON object:\n\
+        {\"highlights\": [3 to 5 key takeaways], \
+        \"follow_up_actions\": [concrete next steps, e.g. who to contact about what]}\n\
+        Only use information present in the input.";
+
+    build_agent!(client, model, |builder| builder
+        .preamble(RECAP_INSTRUCTIONS)
+        .max_tokens(1024)
+        .temperature(0.3)
+        .build())
+}
+
+// build agent that justifies one plan step from the stored sources only
+pub fn build_explain_agent(client: &LlmClient, model: &str) -> AnyAgent {
+    const EXPLAIN_INSTRUCTIONS: &str = "\
+        You explain why a conference plan recommended a specific step. \
+        Use only the attendee objective, the plan and the sources you are given. \
+        In 2 to 4 sentences, say how the step serves the objective and cite the supporting \
+        source ids in square brackets, e.g. [session-12]. \
+        If the sources do not support the step, say so plainly instead of guessing.";
+
+    build_agent!(client, model, |builder| builder
+        .preamble(EXPLAIN_INSTRUCTIONS)
+        .max_tokens(400)
+        .temperature(0.2)
+        .build())
+}
+
+// plain agent without tools, for smoke tests and the polish stage
+pub fn build_text_agent(
+    client: &LlmClient,
+    instructions: &str,
+    params: &GenerationParams,
+) -> AnyAgent {
+    build_agent!(client, &params.model, |builder| builder
+        .preamble(instructions)
+        .max_tokens(params.max_tokens)
+        .temperature(params.temperature)
+        .build())
+}
+
+// tell the model what it can't rely on right now
+fn append_capability_notice(instructions: &mut String, health: &CapabilityMatrix) {
+    let notice = health::capability_notice(health, MONITORED_TOOLS);
+    if !notice.is_empty() {
+        instructions.push_str("\n\nService status:\n");
+        instructions.push_str(&notice);
+    }
+}
+
+pub fn append_language(instructions: &mut String, language: Option<&str>) {
+    if let Some(language) = language {
+        instructions.push_str(&format!(
+            "\n\nWrite the final answer in {}, keeping session titles and speaker names as listed. \
+            Write dates, days and times the way its readers expect, e.g. weekday and month names \
+            in that language and a 24-hour clock where that is the custom.",
+            language
+        ));
+    }
+}
+
+pub fn append_placeholder_instructions(instructions: &mut String, live_placeholders: bool) {
+    if live_placeholders {
+        instructions.push_str(
+            "\n\nThe plan is stored and re-read as the schedule changes. \
+            Instead of copying a session's title and time, write {{session:<source id>}}, \
+            e.g. {{session:session-12}}. For \"whatever is next\" recommendations, \
+            write {{next:<search terms>}}, e.g. {{next:keynote Hall 1}}. \
+            Keep every placeholder exactly as written.",
+        );
+    }
+}
+
+// a prior, not a ranking: popular rooms fill up, which is a reason to arrive early
+fn append_popular_sessions(instructions: &mut String, popular_sessions: &str) {
+    if !popular_sessions.is_empty() {
+        instructions.push_str("\n\nSessions other attendees pick most, likely to be crowded:\n");
+        instructions.push_str(popular_sessions);
+        instructions.push_str(
+            "\nPrefer them only when they fit the objective as well as the alternatives, \
+            and tell the attendee to arrive early.",
+        );
+    }
+}
+
+// complaints from attendees' plan ratings, see feedback.rs
+fn append_feedback_notes(instructions: &mut String, feedback_notes: &str) {
+    if !feedback_notes.is_empty() {
+        instructions.push_str("\n\nRecent feedback on generated plans, avoid these problems:\n");
+        instructions.push_str(feedback_notes);
+    }
+}
+
+fn append_user_context(instructions: &mut String, user_context: &str) {
+    if !user_context.is_empty() {
+        instructions.push_str("\n\nAbout this attendee:\n");
+        instructions.push_str(user_context);
+    }
+}
@@ -0,0 +1,250 @@
+// api key authentication for the planner routes, with a rate limit per key
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tracing::{error, info, warn};
+
+use crate::config;
+use crate::error;
+use crate::state::AppState;
+use crate::usage;
+
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+// one entry of API_KEYS
+#[derive(Clone, Deserialize)]
+struct ApiKeyConfig {
+    name: String,
+    key: String,
+    // requests per minute, falls back to API_KEY_RATE_LIMIT_PER_MINUTE
+    #[serde(default)]
+    rate_limit_per_minute: Option<u32>,
+    // kept in the config but refused, e.g. while a partner is offboarded
+    #[serde(default)]
+    disabled: bool,
+}
+
+struct ApiKey {
+    name: String,
+    rate_limit_per_minute: u32,
+    disabled: bool,
+}
+
+// name of the key that authenticated the request, set as a request extension
+#[derive(Debug, Clone)]
+pub struct ApiKeyName(pub String);
+
+#[derive(Debug, thiserror::Error)]
+pub enum AuthError {
+    #[error("missing API key, send it as a Bearer token or in X-API-Key")]
+    Missing,
+    #[error("invalid API key")]
+    Invalid,
+    #[error("API key '{0}' is disabled")]
+    Disabled(String),
+    #[error("API key '{name}' exceeded {limit} requests per minute")]
+    RateLimited {
+        name: String,
+        limit: u32,
+        retry_after: Duration,
+    },
+}
+
+impl AuthError {
+    fn code(&self) -> &'static str {
+        match self {
+            AuthError::Missing => "missing_api_key",
+            AuthError::Invalid => "invalid_api_key",
+            AuthError::Disabled(_) => "api_key_disabled",
+            AuthError::RateLimited { .. } => "rate_limited",
+        }
+    }
+
+    fn status(&self) -> StatusCode {
+        match self {
+            AuthError::Missing | AuthError::Invalid => StatusCode::UNAUTHORIZED,
+            AuthError::Disabled(_) => StatusCode::FORBIDDEN,
+            AuthError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+        }
+    }
+}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        let body = error::respond(
+            self.status(),
+            error::envelope(self.code(), self.to_string()),
+        );
+        match &self {
+            AuthError::RateLimited { retry_after, .. } => (
+                [(
+                    header::RETRY_AFTER,
+                    retry_after.as_secs().max(1).to_string(),
+                )],
+                body,
+            )
+                .into_response(),
+            AuthError::Missing | AuthError::Invalid => {
+                ([(header::WWW_AUTHENTICATE, "Bearer")], body).into_response()
+            }
+            AuthError::Disabled(_) => body,
+        }
+    }
+}
+
+pub struct ApiKeyAuth {
+    // keyed by the sha-256 of the key, raw keys are not kept
+    keys: HashMap<[u8; 32], ApiKey>,
+    // false only with AUTH_DISABLED=true and no API_KEYS
+    enforced: bool,
+    windows: Mutex<HashMap<String, (Instant, u32)>>,
+}
+
+impl ApiKeyAuth {
+    // unset API_KEYS rejects every key, unless AUTH_DISABLED=true opens the api for
+    // local development
+    pub fn from_env() -> Self {
+        match config::env_var("API_KEYS") {
+            Ok(raw) => Self::from_json(&raw),
+            Err(_) if config::get().auth_disabled => {
+                warn!("AUTH_DISABLED=true, planner routes are open to anyone");
+                Self::with_keys(HashMap::new(), false)
+            }
+            Err(_) => {
+                error!("API_KEYS not configured, every planner request will be rejected");
+                Self::with_keys(HashMap::new(), true)
+            }
+        }
+    }
+
+    // a json array of {"name", "key", "rate_limit_per_minute", "disabled"}
+    pub fn from_json(raw: &str) -> Self {
+        let configs = match serde_json::from_str::<Vec<ApiKeyConfig>>(raw) {
+            Ok(configs) => configs,
+            Err(e) => {
+                // failing closed, a typo must not open the api
+                warn!("Invalid API_KEYS, every key will be rejected: {}", e);
+                return Self::with_keys(HashMap::new(), true);
+            }
+        };
+
+        let default_limit = config::get().api_key_rate_limit_per_minute;
+        let keys = configs
+            .into_iter()
+            .filter(|config| !config.key.is_empty())
+            .map(|config| {
+                (
+                    digest(&config.key),
+                    ApiKey {
+                        name: config.name,
+                        rate_limit_per_minute: config
+                            .rate_limit_per_minute
+                            .unwrap_or(default_limit)
+                            .max(1),
+                        disabled: config.disabled,
+                    },
+                )
+            })
+            .collect::<HashMap<_, _>>();
+        info!("Loaded {} API keys", keys.len());
+        Self::with_keys(keys, true)
+    }
+
+    fn with_keys(keys: HashMap<[u8; 32], ApiKey>, enforced: bool) -> Self {
+        Self {
+            keys,
+            enforced,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_enforced(&self) -> bool {
+        self.enforced
+    }
+
+    pub fn authenticate(&self, headers: &HeaderMap) -> Result<ApiKeyName, AuthError> {
+        let presented = presented_key(headers).ok_or(AuthError::Missing)?;
+        let key = self
+            .keys
+            .get(&digest(presented))
+            .ok_or(AuthError::Invalid)?;
+        if key.disabled {
+            return Err(AuthError::Disabled(key.name.clone()));
+        }
+        self.check_rate(key)?;
+        Ok(ApiKeyName(key.name.clone()))
+    }
+
+    // fixed one-minute windows per key
+    fn check_rate(&self, key: &ApiKey) -> Result<(), AuthError> {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        let (started, count) = windows.entry(key.name.clone()).or_insert((now, 0));
+        if now.duration_since(*started) >= RATE_WINDOW {
+            *started = now;
+            *count = 0;
+        }
+        if *count >= key.rate_limit_per_minute {
+            return Err(AuthError::RateLimited {
+                name: key.name.clone(),
+                limit: key.rate_limit_per_minute,
+                retry_after: RATE_WINDOW.saturating_sub(now.duration_since(*started)),
+            });
+        }
+        *count += 1;
+        Ok(())
+    }
+}
+
+fn presented_key(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or_else(|| headers.get("x-api-key").and_then(|v| v.to_str().ok()))
+        .filter(|key| !key.is_empty())
+}
+
+// digests are compared, so the time taken says nothing about how much of a secret
+// matched. for admin tokens, issuer keys and webhook secrets
+pub fn secret_matches(presented: Option<&str>, expected: &str) -> bool {
+    presented.is_some_and(|presented| digest(presented) == digest(expected))
+}
+
+fn digest(key: &str) -> [u8; 32] {
+    Sha256::digest(key.as_bytes()).into()
+}
+
+pub async fn require_api_key(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if !state.api_keys.is_enforced() {
+        return next.run(request).await;
+    }
+
+    match state.api_keys.authenticate(request.headers()) {
+        Ok(name) => {
+            tracing::Span::current().record("api_key", name.0.as_str());
+            let key = name.0.clone();
+            request.extensions_mut().insert(name);
+            // agent runs of the request are accounted to the key
+            usage::for_api_key(key, next.run(request)).await
+        }
+        Err(e) => {
+            warn!(code = e.code(), "Rejected request: {}", e);
+            e.into_response()
+        }
+    }
+}
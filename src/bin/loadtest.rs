@@ -0,0 +1,23 @@
+// synthetic attendee load against a local instance started with MOCK_MODE=true:
+//   cargo run --release --features loadtest --bin loadtest
+
+use vivaagent::loadtest::{self, LoadTestConfig};
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let config = LoadTestConfig::from_env();
+    println!(
+        "Sending {} synthetic objectives per second to {} for {}s",
+        config.rps,
+        config.base_url,
+        config.duration.as_secs()
+    );
+
+    match loadtest::run(&config).await {
+        Ok(report) => println!("{}", report),
+        Err(e) => {
+            eprintln!("Load test failed: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,42 @@
+// mcp server over stdio, for agent hosts that launch the tools themselves:
+//   cargo run --release --features mcp-stdio --bin mcp
+// one json-rpc message per line on stdin, replies on stdout
+
+use tokio::io::{self, AsyncBufReadExt, AsyncWriteExt, BufReader};
+use vivaagent::conference::ConferenceRegistry;
+use vivaagent::mcp::McpServer;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    // DEFAULT_CONFERENCE picks the conference, as for the api
+    let conferences = ConferenceRegistry::from_env();
+    let server = McpServer::for_conference(conferences.default_profile());
+
+    let mut lines = BufReader::new(io::stdin()).lines();
+    let mut stdout = io::stdout();
+    loop {
+        let line = match lines.next_line().await {
+            Ok(Some(line)) => line,
+            // the host closed stdin
+            Ok(None) => break,
+            Err(e) => {
+                eprintln!("Failed to read from stdin: {}", e);
+                std::process::exit(1);
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some(reply) = server.handle_message(&line).await else {
+            continue;
+        };
+        let written = async {
+            stdout.write_all(format!("{}\n", reply).as_bytes()).await?;
+            stdout.flush().await
+        };
+        if let Err(e) = written.await {
+            eprintln!("Failed to write to stdout: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
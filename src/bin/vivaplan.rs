@@ -0,0 +1,109 @@
+// plans from the command line with the library planner, for scripts and for trying
+// prompt changes without a deploy:
+//   cargo run --release --features cli --bin vivaplan -- "AI keynotes on Thursday"
+//   echo "climate startups" | cargo run --features cli --bin vivaplan -- --mock --format json
+
+use std::io::{self, IsTerminal, Read};
+use std::process;
+
+use clap::{Parser, ValueEnum};
+use vivaagent::planner::{Plan, Planner, PlannerConfig};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+    Md,
+    Json,
+}
+
+#[derive(Debug, Parser)]
+#[command(name = "vivaplan", version, about = "Generate a conference plan")]
+struct Args {
+    /// What the attendee wants from the day, read from stdin when absent
+    objective: Vec<String>,
+    /// Model to plan with, e.g. gpt-4o or anthropic:claude-3-5-sonnet-latest
+    #[arg(long)]
+    model: Option<String>,
+    /// md prints the plan, json adds its sources and model
+    #[arg(long, value_enum, default_value = "md")]
+    format: Format,
+    /// Answer with the fake model and the fixture catalog, no keys needed
+    #[arg(long)]
+    mock: bool,
+}
+
+fn read_objective(args: &Args) -> Result<String, String> {
+    let objective = if args.objective.is_empty() {
+        if io::stdin().is_terminal() {
+            return Err("give an objective as arguments or on stdin".to_string());
+        }
+        let mut input = String::new();
+        io::stdin()
+            .read_to_string(&mut input)
+            .map_err(|e| format!("failed to read stdin: {}", e))?;
+        input
+    } else {
+        args.objective.join(" ")
+    };
+    let objective = objective.trim();
+    if objective.is_empty() {
+        return Err("the objective is empty".to_string());
+    }
+    Ok(objective.to_string())
+}
+
+fn print_plan(plan: &Plan, format: Format) -> Result<(), String> {
+    match format {
+        Format::Md => println!("{}", plan.body),
+        Format::Json => {
+            let json = serde_json::to_string_pretty(plan)
+                .map_err(|e| format!("failed to encode the plan: {}", e))?;
+            println!("{}", json);
+        }
+    }
+    Ok(())
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let args = Args::parse();
+    // before anything reads the configuration
+    if args.mock {
+        std::env::set_var("MOCK_MODE", "true");
+    }
+
+    let objective = match read_objective(&args) {
+        Ok(objective) => objective,
+        Err(e) => {
+            eprintln!("vivaplan: {}", e);
+            process::exit(2);
+        }
+    };
+    let planner = match Planner::new(PlannerConfig {
+        model: args.model.clone(),
+        ..PlannerConfig::default()
+    }) {
+        Ok(planner) => planner,
+        Err(e) => {
+            eprintln!("vivaplan: {}", e);
+            process::exit(2);
+        }
+    };
+
+    let plan = match planner.plan(&objective).await {
+        Ok(plan) => plan,
+        Err(e) => {
+            eprintln!("vivaplan: {}", e);
+            process::exit(1);
+        }
+    };
+    if let Some(requested) = &plan.fallback_from {
+        eprintln!(
+            "vivaplan: {} was unavailable, planned with {}",
+            requested, plan.model
+        );
+    }
+    if let Err(e) = print_plan(&plan, args.format) {
+        eprintln!("vivaplan: {}", e);
+        process::exit(1);
+    }
+}
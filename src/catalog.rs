@@ -0,0 +1,201 @@
+// offline sqlite snapshot of the session/partner catalog
+
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+use chrono::Utc;
+use rusqlite::{params, params_from_iter, Connection};
+use tracing::{info, warn};
+
+use crate::catalog_sync::pull_catalog;
+use crate::config;
+use crate::mock::{FixtureCatalog, SessionCatalog};
+use crate::models::VivatechSource;
+
+static CATALOG: OnceLock<OfflineCatalog> = OnceLock::new();
+
+#[derive(Debug, thiserror::Error)]
+pub enum CatalogError {
+    #[error("Offline catalog error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("Offline catalog sync failed: {0}")]
+    Upstream(String),
+    #[error("Offline catalog search task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+}
+
+// snapshot location with fallback
+fn get_catalog_path() -> String {
+    config::env_var("OFFLINE_CATALOG_PATH").unwrap_or_else(|_| "data/catalog.sqlite".to_string())
+}
+
+pub struct OfflineCatalog {
+    conn: Mutex<Connection>,
+}
+
+impl OfflineCatalog {
+    pub fn open(path: &str) -> Result<Self, CatalogError> {
+        if let Some(parent) = std::path::Path::new(path).parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS sources (
+                id TEXT PRIMARY KEY,
+                source_table TEXT NOT NULL DEFAULT '',
+                text_chunk TEXT NOT NULL,
+                synced_at TEXT NOT NULL
+            );",
+        )?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    pub fn count(&self) -> Result<usize, CatalogError> {
+        let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM sources", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    // naive term matching, good enough to keep the planner useful offline.
+    // blocking, call it through search_offline_catalog from async code
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<VivatechSource>, CatalogError> {
+        let terms: Vec<String> = query
+            .split_whitespace()
+            .map(|t| t.to_lowercase())
+            .filter(|t| t.len() > 2)
+            .collect();
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // sqlite only hands back rows containing at least one term
+        let filter = (1..=terms.len())
+            .map(|i| format!("lower(text_chunk) LIKE ?{} ESCAPE '\\'", i))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        let patterns = terms.iter().map(|t| format!("%{}%", escape_like(t)));
+
+        let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut statement = conn.prepare(&format!(
+            "SELECT id, source_table, text_chunk FROM sources WHERE {}",
+            filter
+        ))?;
+        let rows = statement.query_map(params_from_iter(patterns), |row| {
+            Ok(VivatechSource {
+                id: row.get(0)?,
+                source_table: row.get(1)?,
+                score: 0.0,
+                text_chunk: row.get(2)?,
+                facets: None,
+                session: None,
+                partner: None,
+            })
+        })?;
+
+        let mut matches = Vec::new();
+        for row in rows {
+            let mut source = row?;
+            let text = source.text_chunk.to_lowercase();
+            let hits = terms.iter().filter(|t| text.contains(t.as_str())).count();
+            if hits > 0 {
+                source.score = hits as f32 / terms.len() as f32;
+                matches.push(source);
+            }
+        }
+
+        matches.sort_by(|a, b| b.score.total_cmp(&a.score));
+        matches.truncate(limit);
+        Ok(matches)
+    }
+
+    // swap the whole snapshot in one transaction
+    pub fn replace_all(&self, sources: &[VivatechSource]) -> Result<usize, CatalogError> {
+        let mut conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        let synced_at = Utc::now().to_rfc3339();
+
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM sources", [])?;
+        for source in sources {
+            tx.execute(
+                "INSERT OR REPLACE INTO sources (id, source_table, text_chunk, synced_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![source.id, source.source_table, source.text_chunk, synced_at],
+            )?;
+        }
+        tx.commit()?;
+
+        Ok(sources.len())
+    }
+}
+
+// terms are matched literally, not as LIKE wildcards
+fn escape_like(term: &str) -> String {
+    term.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+// open the snapshot at startup, failures only disable the fallback.
+// an empty snapshot is seeded from the bundled fixture until the first sync
+pub fn init_offline_catalog() {
+    let path = get_catalog_path();
+    match OfflineCatalog::open(&path) {
+        Ok(catalog) => {
+            let mut count = catalog.count().unwrap_or(0);
+            if count == 0 {
+                match catalog.replace_all(&FixtureCatalog::bundled().all()) {
+                    Ok(seeded) => {
+                        info!(
+                            "Seeded empty offline catalog with {} bundled sources",
+                            seeded
+                        );
+                        count = seeded;
+                    }
+                    Err(e) => warn!("Could not seed offline catalog: {}", e),
+                }
+            }
+            info!("Offline catalog opened at {} with {} sources", path, count);
+            let _ = CATALOG.set(catalog);
+        }
+        Err(e) => warn!("Offline catalog unavailable at {}: {}", path, e),
+    }
+}
+
+pub fn offline_catalog() -> Option<&'static OfflineCatalog> {
+    CATALOG.get()
+}
+
+// runs the sqlite search on the blocking pool so it never stalls the runtime
+pub async fn search_offline_catalog(
+    query: &str,
+) -> Option<Result<Vec<VivatechSource>, CatalogError>> {
+    let catalog = offline_catalog()?;
+    let query = query.to_string();
+    let limit = config::get().offline_search_limit;
+    let searched = tokio::task::spawn_blocking(move || catalog.search(&query, limit)).await;
+    Some(searched.unwrap_or_else(|e| Err(e.into())))
+}
+
+// pull the catalog through the seed queries and replace the snapshot
+pub async fn sync_offline_catalog() -> Result<usize, CatalogError> {
+    let catalog = offline_catalog()
+        .ok_or_else(|| CatalogError::Upstream("offline catalog is not initialized".to_string()))?;
+
+    let sources = pull_catalog()
+        .await
+        .map_err(|e| CatalogError::Upstream(e.to_string()))?;
+
+    // never wipe a good snapshot with nothing
+    if sources.is_empty() {
+        return Err(CatalogError::Upstream(
+            "upstream returned no sources".to_string(),
+        ));
+    }
+
+    let stored = catalog.replace_all(&sources)?;
+    info!("Offline catalog synced with {} sources", stored);
+    Ok(stored)
+}
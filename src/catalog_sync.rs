@@ -0,0 +1,385 @@
+// periodic catalog sync with change detection
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::json;
+use tokio::sync::broadcast;
+use tracing::{error, info, warn};
+
+use crate::config;
+use crate::http;
+use crate::locks::JobLocks;
+use crate::mock;
+use crate::models::VivatechSource;
+use crate::tools::{
+    extract_dates_from_text, get_vivatech_api_url, make_api_request, parse_query_response,
+    VivatechApiError,
+};
+
+const MAX_RECORDED_CHANGES: usize = 500;
+const CHANGE_CHANNEL_CAPACITY: usize = 256;
+// pulls in a row that must agree before a shrunk catalog or a vanished session is believed
+const CONFIRMING_PULLS: u32 = 3;
+
+// sync interval, 0 disables the background job
+pub(crate) fn get_sync_interval() -> Option<Duration> {
+    let seconds = config::env_var("CATALOG_SYNC_INTERVAL_SECONDS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(900);
+    (seconds > 0).then(|| Duration::from_secs(seconds))
+}
+
+// broad queries used to pull the catalog, the upstream has no list endpoint
+pub(crate) fn get_seed_queries() -> Vec<String> {
+    config::env_var("CATALOG_SEED_QUERIES")
+        .unwrap_or_else(|_| {
+            "AI,startups,keynote,partners,climate,fintech,mobility,health,cybersecurity,sessions"
+                .to_string()
+        })
+        .split(',')
+        .map(|q| q.trim().to_string())
+        .filter(|q| !q.is_empty())
+        .collect()
+}
+
+// pull everything the seed queries surface, deduplicated by id
+pub async fn pull_catalog() -> Result<Vec<VivatechSource>, VivatechApiError> {
+    if let Some(catalog) = mock::mock_catalog() {
+        return Ok(catalog.all());
+    }
+    let client = http::api_client();
+    let api_url = get_vivatech_api_url()?;
+
+    let mut collected: HashMap<String, VivatechSource> = HashMap::new();
+    for query in get_seed_queries() {
+        for source in pull_query(&client, &api_url, &query).await? {
+            collected.entry(source.id.clone()).or_insert(source);
+        }
+    }
+
+    Ok(collected.into_values().collect())
+}
+
+// sources one seed query surfaces
+pub(crate) async fn pull_query(
+    client: &reqwest::Client,
+    api_url: &str,
+    query: &str,
+) -> Result<Vec<VivatechSource>, VivatechApiError> {
+    let request_body = json!({ "query": query });
+    let response = make_api_request(client, api_url, &request_body).await?;
+    let api_response = parse_query_response(response).await?;
+    Ok(api_response.sources)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Moved,
+    Updated,
+    Cancelled,
+    // absent from a pull, which may just be the seed queries missing it
+    Missing,
+    // absent from CONFIRMING_PULLS pulls in a row
+    Removed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogChange {
+    pub kind: ChangeKind,
+    pub source_id: String,
+    pub detected_at: DateTime<Utc>,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+// what recent pulls disagreed with the snapshot about
+#[derive(Default)]
+struct PullHistory {
+    // consecutive pulls much smaller than the snapshot
+    shrunk: u32,
+    // consecutive pulls each missing session was absent from
+    missing: HashMap<String, u32>,
+}
+
+// holds the last pulled snapshot and fans out changes to subscribers
+pub struct CatalogWatcher {
+    snapshot: Mutex<HashMap<String, VivatechSource>>,
+    pulls: Mutex<PullHistory>,
+    history: Mutex<VecDeque<CatalogChange>>,
+    events: broadcast::Sender<CatalogChange>,
+    year: i32,
+}
+
+impl CatalogWatcher {
+    pub fn new(year: i32) -> Self {
+        let (events, _) = broadcast::channel(CHANGE_CHANNEL_CAPACITY);
+        Self {
+            snapshot: Mutex::new(HashMap::new()),
+            pulls: Mutex::new(PullHistory::default()),
+            history: Mutex::new(VecDeque::new()),
+            events,
+            year,
+        }
+    }
+
+    // re-planning and notification subsystems listen here
+    pub fn subscribe(&self) -> broadcast::Receiver<CatalogChange> {
+        self.events.subscribe()
+    }
+
+    // read access to the last pull, empty until the first sync
+    pub fn with_snapshot<T>(&self, f: impl FnOnce(&HashMap<String, VivatechSource>) -> T) -> T {
+        let snapshot = self.snapshot.lock().unwrap_or_else(PoisonError::into_inner);
+        f(&snapshot)
+    }
+
+    pub fn recent_changes(&self, limit: usize) -> Vec<CatalogChange> {
+        let history = self.history.lock().unwrap_or_else(PoisonError::into_inner);
+        history.iter().rev().take(limit).cloned().collect()
+    }
+
+    // diff a fresh pull against the snapshot, then store it
+    pub fn apply(&self, pulled: Vec<VivatechSource>) -> Vec<CatalogChange> {
+        let mut fresh: HashMap<String, VivatechSource> = pulled
+            .into_iter()
+            .map(|source| (source.id.clone(), source))
+            .collect();
+
+        let changes = {
+            let mut snapshot = self.snapshot.lock().unwrap_or_else(PoisonError::into_inner);
+            let mut pulls = self.pulls.lock().unwrap_or_else(PoisonError::into_inner);
+
+            // first pull only establishes the baseline
+            if snapshot.is_empty() {
+                info!("Catalog baseline established with {} sources", fresh.len());
+                *snapshot = fresh;
+                *pulls = PullHistory::default();
+                return Vec::new();
+            }
+
+            // a much smaller pull is more likely an upstream hiccup than mass cancellations,
+            // unless the next pulls are just as small
+            if fresh.len() < snapshot.len() / 2 {
+                pulls.shrunk += 1;
+                if pulls.shrunk < CONFIRMING_PULLS {
+                    warn!(
+                        "Catalog pull shrank from {} to {} sources, skipping diff ({}/{})",
+                        snapshot.len(),
+                        fresh.len(),
+                        pulls.shrunk,
+                        CONFIRMING_PULLS
+                    );
+                    return Vec::new();
+                }
+                warn!(
+                    "Catalog pull shrank from {} to {} sources {} times in a row, taking it as the new baseline",
+                    snapshot.len(),
+                    fresh.len(),
+                    pulls.shrunk
+                );
+                *snapshot = fresh;
+                *pulls = PullHistory::default();
+                return Vec::new();
+            }
+            pulls.shrunk = 0;
+
+            // a missing session keeps its last version in the snapshot until enough pulls
+            // agree it is gone, it is reported when first missed and when removed
+            pulls.missing.retain(|id, _| !fresh.contains_key(id));
+            let mut changes = Vec::new();
+            for change in diff_catalogs(&snapshot, &fresh, self.year) {
+                if change.kind != ChangeKind::Missing {
+                    changes.push(change);
+                    continue;
+                }
+                let misses = pulls.missing.entry(change.source_id.clone()).or_insert(0);
+                *misses += 1;
+                if *misses >= CONFIRMING_PULLS {
+                    pulls.missing.remove(&change.source_id);
+                    changes.push(CatalogChange {
+                        kind: ChangeKind::Removed,
+                        ..change
+                    });
+                    continue;
+                }
+                if let Some(previous) = snapshot.get(&change.source_id) {
+                    fresh.insert(change.source_id.clone(), previous.clone());
+                }
+                if *misses == 1 {
+                    changes.push(change);
+                }
+            }
+            *snapshot = fresh;
+            changes
+        };
+
+        if !changes.is_empty() {
+            let mut history = self.history.lock().unwrap_or_else(PoisonError::into_inner);
+            for change in &changes {
+                history.push_back(change.clone());
+                if history.len() > MAX_RECORDED_CHANGES {
+                    history.pop_front();
+                }
+                // no subscribers is fine
+                let _ = self.events.send(change.clone());
+            }
+        }
+
+        changes
+    }
+}
+
+// classify differences between two snapshots
+pub fn diff_catalogs(
+    old: &HashMap<String, VivatechSource>,
+    new: &HashMap<String, VivatechSource>,
+    year: i32,
+) -> Vec<CatalogChange> {
+    let detected_at = Utc::now();
+    let mut changes = Vec::new();
+
+    for (id, source) in new {
+        let kind = match old.get(id) {
+            None => Some(ChangeKind::Added),
+            Some(previous) if previous.text_chunk == source.text_chunk => None,
+            Some(_) if mentions_cancellation(&source.text_chunk) => Some(ChangeKind::Cancelled),
+            Some(previous) if schedule_changed(&previous.text_chunk, &source.text_chunk, year) => {
+                Some(ChangeKind::Moved)
+            }
+            Some(_) => Some(ChangeKind::Updated),
+        };
+
+        if let Some(kind) = kind {
+            changes.push(CatalogChange {
+                kind,
+                source_id: id.clone(),
+                detected_at,
+                before: old.get(id).map(|s| s.text_chunk.clone()),
+                after: Some(source.text_chunk.clone()),
+            });
+        }
+    }
+
+    // a search-based pull cannot tell a cancelled session from one no seed query surfaced
+    for (id, previous) in old {
+        if !new.contains_key(id) {
+            changes.push(CatalogChange {
+                kind: ChangeKind::Missing,
+                source_id: id.clone(),
+                detected_at,
+                before: Some(previous.text_chunk.clone()),
+                after: None,
+            });
+        }
+    }
+
+    changes
+}
+
+fn mentions_cancellation(text: &str) -> bool {
+    let lower = text.to_lowercase();
+    ["cancelled", "canceled", "annulé", "annulée"]
+        .iter()
+        .any(|word| lower.contains(word))
+}
+
+// a different date or start time means the session moved
+fn schedule_changed(before: &str, after: &str, year: i32) -> bool {
+    extract_dates_from_text(before, year) != extract_dates_from_text(after, year)
+        || extract_first_time(before) != extract_first_time(after)
+}
+
+fn extract_first_time(text: &str) -> Option<String> {
+    let pattern = Regex::new(r"\b(\d{1,2})[:h](\d{2})\b").ok()?;
+    let captures = pattern.captures(text)?;
+    Some(format!(
+        "{}:{}",
+        captures.get(1)?.as_str(),
+        captures.get(2)?.as_str()
+    ))
+}
+
+// one pull + diff, also refreshes the offline snapshot and session facets when enabled
+pub async fn sync_once(watcher: &CatalogWatcher) -> Result<Vec<CatalogChange>, VivatechApiError> {
+    let sources = pull_catalog().await?;
+
+    #[cfg(feature = "offline-catalog")]
+    if !sources.is_empty() {
+        if let Some(catalog) = crate::catalog::offline_catalog() {
+            if let Err(e) = catalog.replace_all(&sources) {
+                warn!("Failed to refresh offline catalog during sync: {}", e);
+            }
+        }
+    }
+
+    if let Some(enricher) = crate::enrichment::session_enricher() {
+        let tagged = enricher.enrich(&sources).await;
+        if tagged > 0 {
+            info!("Session enrichment tagged {} sources", tagged);
+        }
+    }
+
+    let changes = watcher.apply(sources);
+    if !changes.is_empty() {
+        if let Some(cache) = crate::query_cache::query_cache() {
+            cache.clear();
+        }
+        if let Some(cache) = crate::plan_cache::plan_cache() {
+            cache.clear();
+        }
+    }
+    Ok(changes)
+}
+
+pub fn spawn_catalog_sync(watcher: Arc<CatalogWatcher>, locks: Arc<JobLocks>) {
+    let Some(interval) = get_sync_interval() else {
+        info!("Catalog sync disabled via CATALOG_SYNC_INTERVAL_SECONDS");
+        return;
+    };
+
+    // log every change so operators see schedule shifts as they happen
+    let mut changes = watcher.subscribe();
+    tokio::spawn(async move {
+        loop {
+            match changes.recv().await {
+                Ok(change) => info!(
+                    kind = ?change.kind,
+                    source_id = %change.source_id,
+                    "Catalog change detected"
+                ),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("Catalog change log skipped {} events", skipped)
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+
+    info!("Starting catalog sync every {}s", interval.as_secs());
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            // with several instances only the lease holder pulls, diffs and enriches. the
+            // lease lapses at the end of the interval rather than when the sync ends, so an
+            // instance whose ticker runs late cannot pull again within the same tick
+            let Some(_lease) = locks.try_acquire("catalog-sync", interval).await else {
+                info!("Catalog sync skipped, another instance holds the lock");
+                continue;
+            };
+            match sync_once(&watcher).await {
+                Ok(changes) if changes.is_empty() => info!("Catalog sync found no changes"),
+                Ok(changes) => info!("Catalog sync recorded {} changes", changes.len()),
+                Err(e) => error!("Catalog sync failed: {}", e),
+            }
+        }
+    });
+}
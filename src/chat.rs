@@ -0,0 +1,369 @@
+// multi-turn chat over POST /chat and the websocket, with the session history
+// fed back to the agent and older turns summarized off the request path
+use std::time::Instant;
+
+use axum::{
+    extract::{
+        ws::{WebSocket, WebSocketUpgrade},
+        Path, Query, State,
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use tracing::info;
+
+use crate::abuse::ClientId;
+use crate::agents::{build_planning_agent, build_text_agent, GenerationParams};
+use crate::citations::SourceCollector;
+use crate::error::AppError;
+use crate::llm::LlmClient;
+use crate::pipeline::{
+    abuse_rejection, initialize_llm, resolve_tone, resolve_user_context, streaming_tools,
+};
+use crate::planner::{chat_with_retry, prompt_with_retry};
+use crate::reply::{elapsed_ms, plan_error};
+use crate::sessions::{self, ChatRequest, ChatResponse};
+use crate::state::AppState;
+use crate::{copy, streaming, usage, ws};
+
+// refine a plan over several messages, prior turns are fed back as chat history
+pub async fn chat_handler(
+    State(state): State<AppState>,
+    ClientId(client): ClientId,
+    Json(request): Json<ChatRequest>,
+) -> Response {
+    let started = Instant::now();
+    if let Err(e) = request.validate() {
+        return plan_error(AppError::Validation(e), started).into_response();
+    }
+
+    if let Some(rejection) = abuse_rejection(state.abuse.check(&client, &request.message)) {
+        return rejection.into_response();
+    }
+
+    let (llm_client, choice) = match initialize_llm(None, started) {
+        Ok(llm) => llm,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    let profile = match state.conferences.resolve(request.conference.as_deref()) {
+        Ok(profile) => profile.clone(),
+        Err(e) => return plan_error(AppError::Validation(e), started).into_response(),
+    };
+    let history = state.conversations.history(&request.session_id).await;
+    let mut user_context = resolve_user_context(&state, request.user_id.as_deref(), &profile);
+    user_context.push_str(&history.memory_block());
+    let tone = resolve_tone(&state, request.tone, request.user_id.as_deref());
+    info!(
+        "Chat message for session {} with {} prior messages{}",
+        request.session_id,
+        history.messages.len(),
+        if history.memory.is_some() {
+            " and a summary"
+        } else {
+            ""
+        }
+    );
+
+    let collector = SourceCollector::default();
+    let planner_agent = build_planning_agent(
+        &llm_client,
+        &profile,
+        &user_context,
+        &collector,
+        &state.health,
+        &GenerationParams::new(&choice.model).with_tone(tone),
+    );
+    let attribution = usage::Attribution::new(state.usage.clone(), request.cost_tags.clone());
+    let reply = usage::attributed(
+        Some(attribution.clone()),
+        chat_with_retry(
+            &planner_agent,
+            &request.message,
+            &history.messages,
+            &state.health,
+        ),
+    )
+    .await;
+    match reply {
+        Ok(reply) => {
+            let turns = state
+                .conversations
+                .record(
+                    &request.session_id,
+                    &request.message,
+                    &reply,
+                    collector.sources(),
+                )
+                .await;
+            keep_exchange(&state, &request, &reply);
+            // read before the summary run below adds to it
+            let spent = attribution.spent();
+            if let Some(compaction) = state
+                .conversations
+                .pending_compaction(&request.session_id)
+                .await
+            {
+                spawn_chat_compaction(
+                    state.clone(),
+                    llm_client,
+                    choice.model,
+                    compaction,
+                    attribution,
+                );
+            }
+            Json(ChatResponse {
+                session_id: request.session_id,
+                reply,
+                turns,
+                usage: spent,
+                elapsed_ms: elapsed_ms(started),
+            })
+            .into_response()
+        }
+        Err(e) => plan_error(
+            AppError::llm(
+                copy::error("error.chat_failed", &[("error", e.message.as_str())]),
+                e.retry_after,
+            ),
+            started,
+        )
+        .into_response(),
+    }
+}
+
+// exchanges of known users outlive the session for their exports, a store
+// failure only costs the export
+fn keep_exchange(state: &AppState, request: &ChatRequest, reply: &str) {
+    let Some(user_id) = request.user_id.as_deref() else {
+        return;
+    };
+    if let Err(e) =
+        state
+            .plans
+            .record_exchange(user_id, &request.session_id, &request.message, reply)
+    {
+        tracing::error!("Failed to keep chat exchange for {}: {}", user_id, e);
+    }
+}
+
+// summarize older turns off the request path, the next message sees the result
+fn spawn_chat_compaction(
+    state: AppState,
+    llm_client: LlmClient,
+    model: String,
+    compaction: sessions::Compaction,
+    attribution: usage::Attribution,
+) {
+    tokio::spawn(usage::attributed(Some(attribution), async move {
+        // a short, factual summary whatever the chat's own settings
+        let params = GenerationParams {
+            max_tokens: 512,
+            temperature: 0.2,
+            ..GenerationParams::new(&model)
+        };
+        let summarizer = build_text_agent(&llm_client, sessions::SUMMARY_INSTRUCTIONS, &params);
+        let memory = match prompt_with_retry(&summarizer, compaction.prompt(), &state.health).await
+        {
+            Ok(memory) => Some(memory),
+            Err(e) => {
+                tracing::warn!("Chat summary failed, keeping full history: {}", e);
+                None
+            }
+        };
+        if state
+            .conversations
+            .finish_compaction(compaction, memory)
+            .await
+        {
+            info!("Summarized older chat turns into session memory");
+        }
+    }));
+}
+
+// chat over a websocket, tokens, tool calls and the final plan are pushed as they happen
+pub async fn ws_handler(
+    State(state): State<AppState>,
+    Query(query): Query<ws::WsQuery>,
+    ClientId(client): ClientId,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    let session_id = match query.session_id {
+        Some(session_id) if (1..=128).contains(&session_id.trim().len()) => session_id,
+        Some(_) => {
+            return plan_error(
+                AppError::Validation(copy::error("error.invalid_session_id", &[])),
+                Instant::now(),
+            )
+            .into_response()
+        }
+        None => ws::new_session_id(),
+    };
+    // the socket outlives the request, so the api key is carried over for usage accounting
+    let api_key = usage::current_api_key();
+    upgrade.on_upgrade(move |socket| async move {
+        let session = run_ws_session(socket, state, client, session_id);
+        match api_key {
+            Some(api_key) => usage::for_api_key(api_key, session).await,
+            None => session.await,
+        }
+    })
+}
+
+// one message at a time, frames sent during a run are read after it ends
+async fn run_ws_session(
+    mut socket: WebSocket,
+    state: AppState,
+    client: String,
+    session_id: String,
+) {
+    info!("WebSocket chat session {} opened", session_id);
+    let ready = ws::SessionFrame::Ready {
+        session_id: session_id.clone(),
+    };
+    if ws::send(&mut socket, &ready).await.is_err() {
+        return;
+    }
+
+    while let Some(frame) = ws::next_frame(&mut socket).await {
+        let sent = match frame {
+            Ok(ws::ClientFrame::Message(message)) => {
+                let request = message.into_request(&session_id);
+                ws_chat_turn(&mut socket, &state, &client, request).await
+            }
+            Ok(ws::ClientFrame::Reset) => {
+                state.conversations.clear(&session_id).await;
+                let reset = ws::SessionFrame::Reset {
+                    session_id: session_id.clone(),
+                };
+                ws::send(&mut socket, &reset).await
+            }
+            Err(message) => {
+                let error = streaming::StreamEvent::error(
+                    streaming::StreamErrorCode::InvalidRequest,
+                    message,
+                    false,
+                );
+                ws::send(&mut socket, &error).await
+            }
+        };
+        if sent.is_err() {
+            break;
+        }
+    }
+    info!("WebSocket chat session {} closed", session_id);
+}
+
+// streams one agent run to the socket and remembers the exchange like POST /chat
+async fn ws_chat_turn(
+    socket: &mut WebSocket,
+    state: &AppState,
+    client: &str,
+    request: ChatRequest,
+) -> Result<(), axum::Error> {
+    let started = Instant::now();
+    let invalid = |message: String| {
+        streaming::StreamEvent::error(streaming::StreamErrorCode::InvalidRequest, message, false)
+    };
+    if let Err(e) = request.validate() {
+        return ws::send(socket, &invalid(e)).await;
+    }
+    if let Some(rejection) = abuse_rejection(state.abuse.check(client, &request.message)) {
+        return ws::send(socket, &rejection.into_stream_error()).await;
+    }
+    let (llm_client, choice) = match initialize_llm(None, started) {
+        Ok(llm) => llm,
+        Err(rejection) => return ws::send(socket, &rejection.into_stream_error()).await,
+    };
+    let profile = match state.conferences.resolve(request.conference.as_deref()) {
+        Ok(profile) => profile.clone(),
+        Err(e) => return ws::send(socket, &invalid(e.to_string())).await,
+    };
+
+    let history = state.conversations.history(&request.session_id).await;
+    let mut user_context = resolve_user_context(state, request.user_id.as_deref(), &profile);
+    user_context.push_str(&history.memory_block());
+    let tone = resolve_tone(state, request.tone, request.user_id.as_deref());
+    let collector = SourceCollector::default();
+    let planner_agent = build_planning_agent(
+        &llm_client,
+        &profile,
+        &user_context,
+        &collector,
+        &state.health,
+        &GenerationParams::new(&choice.model).with_tone(tone),
+    );
+    let tools = streaming_tools(&profile, &collector, &state.health);
+
+    let attribution = usage::Attribution::new(state.usage.clone(), request.cost_tags.clone());
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(64);
+    streaming::spawn_agent_run(
+        planner_agent,
+        tools,
+        request.message.clone(),
+        history.messages,
+        state.config.agent_max_turns,
+        attribution.clone(),
+        sender,
+    );
+
+    // a failed send drops the receiver, which stops the run
+    while let Some(event) = receiver.recv().await {
+        if let streaming::StreamEvent::Done { plan, .. } = &event {
+            state
+                .conversations
+                .record(
+                    &request.session_id,
+                    &request.message,
+                    plan,
+                    collector.sources(),
+                )
+                .await;
+            keep_exchange(state, &request, plan);
+            if let Some(compaction) = state
+                .conversations
+                .pending_compaction(&request.session_id)
+                .await
+            {
+                spawn_chat_compaction(
+                    state.clone(),
+                    llm_client.clone(),
+                    choice.model.clone(),
+                    compaction,
+                    attribution.clone(),
+                );
+            }
+        }
+        ws::send(socket, &event).await?;
+    }
+    Ok(())
+}
+
+// the stored exchanges of a chat session, 404 once it expired
+pub async fn get_chat_handler(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+) -> Response {
+    match state.conversations.transcript(&session_id).await {
+        Some(transcript) => Json(transcript).into_response(),
+        None => AppError::NotFound(copy::error(
+            "error.unknown_chat_session",
+            &[("session", session_id.as_str())],
+        ))
+        .into_response(),
+    }
+}
+
+// forget a chat session
+pub async fn clear_chat_handler(
+    State(state): State<AppState>,
+    Path(session_id): Path<String>,
+) -> StatusCode {
+    if state.conversations.clear(&session_id).await {
+        info!("Cleared chat session {}", session_id);
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
@@ -0,0 +1,195 @@
+// align plan steps with the source text that supports them
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::models::VivatechSource;
+
+// minimum word overlap for a sentence to count as support
+const MIN_ALIGNMENT_SCORE: f32 = 0.2;
+
+const STOPWORDS: &[&str] = &[
+    "about", "after", "also", "and", "are", "attend", "because", "from", "have", "into", "its",
+    "that", "the", "their", "there", "these", "this", "those", "what", "when", "where", "which",
+    "while", "will", "with", "your", "you",
+];
+
+// sources returned by the search tools during one request, each session once however
+// many searches returned it
+#[derive(Clone, Default)]
+pub struct SourceCollector(Arc<Mutex<Vec<CollectedSource>>>);
+
+struct CollectedSource {
+    source: VivatechSource,
+    // how many searches returned it
+    hits: u32,
+}
+
+impl SourceCollector {
+    // a source seen before keeps its best score and gains the facets it lacked
+    pub fn extend(&self, sources: &[VivatechSource]) {
+        let mut collected = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        for source in sources {
+            match collected.iter_mut().find(|c| c.source.id == source.id) {
+                Some(existing) => {
+                    existing.hits += 1;
+                    existing.source.score = existing.source.score.max(source.score);
+                    if existing.source.facets.is_none() {
+                        existing.source.facets = source.facets.clone();
+                    }
+                }
+                None => collected.push(CollectedSource {
+                    source: source.clone(),
+                    hits: 1,
+                }),
+            }
+        }
+    }
+
+    // best score first, then the sessions more searches returned, then first seen
+    pub fn sources(&self) -> Vec<VivatechSource> {
+        let mut collected: Vec<(VivatechSource, u32)> = self
+            .0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|c| (c.source.clone(), c.hits))
+            .collect();
+        collected.sort_by(|a, b| b.0.score.total_cmp(&a.0.score).then(b.1.cmp(&a.1)));
+        collected.into_iter().map(|(source, _)| source).collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct Citation {
+    // 1-based index of the plan step
+    pub step: usize,
+    pub source_id: String,
+    pub snippet: String,
+    // char offsets into the source text_chunk, end exclusive
+    pub start: usize,
+    pub end: usize,
+    pub score: f32,
+}
+
+// best supporting sentence for each plan step
+pub fn cite_plan(plan: &str, sources: &[VivatechSource]) -> Vec<Citation> {
+    let sentences: Vec<(&VivatechSource, Sentence)> = sources
+        .iter()
+        .flat_map(|source| {
+            split_sentences(&source.text_chunk)
+                .into_iter()
+                .map(move |sentence| (source, sentence))
+        })
+        .collect();
+
+    plan_steps(plan)
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, step)| {
+            let step_words = content_words(step);
+            if step_words.is_empty() {
+                return None;
+            }
+
+            sentences
+                .iter()
+                .map(|(source, sentence)| {
+                    let score = overlap(&step_words, &content_words(&sentence.text));
+                    (score, source, sentence)
+                })
+                .filter(|(score, _, _)| *score >= MIN_ALIGNMENT_SCORE)
+                .max_by(|a, b| a.0.total_cmp(&b.0))
+                .map(|(score, source, sentence)| Citation {
+                    step: index + 1,
+                    source_id: source.id.clone(),
+                    snippet: sentence.text.clone(),
+                    start: sentence.start,
+                    end: sentence.end,
+                    score,
+                })
+        })
+        .collect()
+}
+
+// numbered/bulleted lines, falling back to paragraphs
+pub fn plan_steps(plan: &str) -> Vec<&str> {
+    let is_step = |line: &&str| {
+        let line = line.trim_start();
+        line.starts_with(['-', '*', '•'])
+            || line
+                .split_once(['.', ')'])
+                .is_some_and(|(n, _)| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+    };
+
+    let steps: Vec<&str> = plan.lines().filter(is_step).collect();
+    if !steps.is_empty() {
+        return steps;
+    }
+
+    plan.split("\n\n")
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+struct Sentence {
+    text: String,
+    start: usize,
+    end: usize,
+}
+
+fn split_sentences(text: &str) -> Vec<Sentence> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut sentences = Vec::new();
+    let mut start = 0;
+
+    for (i, c) in chars.iter().enumerate() {
+        let at_boundary = matches!(c, '.' | '!' | '?' | '\n')
+            && chars.get(i + 1).map_or(true, |next| next.is_whitespace());
+        if at_boundary || i + 1 == chars.len() {
+            push_sentence(&chars, start, i + 1, &mut sentences);
+            start = i + 1;
+        }
+    }
+
+    sentences
+}
+
+// trims whitespace while keeping offsets pointing into the original text
+fn push_sentence(chars: &[char], start: usize, end: usize, sentences: &mut Vec<Sentence>) {
+    let mut start = start;
+    let mut end = end;
+    while start < end && chars[start].is_whitespace() {
+        start += 1;
+    }
+    while end > start && chars[end - 1].is_whitespace() {
+        end -= 1;
+    }
+    if start < end {
+        sentences.push(Sentence {
+            text: chars[start..end].iter().collect(),
+            start,
+            end,
+        });
+    }
+}
+
+fn content_words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() > 2)
+        .map(str::to_lowercase)
+        .filter(|w| !STOPWORDS.contains(&w.as_str()))
+        .collect()
+}
+
+// share of the step's words found in the sentence
+fn overlap(step: &HashSet<String>, sentence: &HashSet<String>) -> f32 {
+    if step.is_empty() {
+        return 0.0;
+    }
+    step.intersection(sentence).count() as f32 / step.len() as f32
+}
@@ -0,0 +1,96 @@
+// in-flight request coalescing for identical objectives
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Mutex, PoisonError};
+
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+pub struct RequestCoalescer<T> {
+    inflight: Mutex<HashMap<String, watch::Receiver<Option<T>>>>,
+}
+
+enum Role<T> {
+    Leader(watch::Sender<Option<T>>),
+    Follower(watch::Receiver<Option<T>>),
+}
+
+// removes the key when the leading run finishes or is cancelled
+struct InflightGuard<'a, T> {
+    inflight: &'a Mutex<HashMap<String, watch::Receiver<Option<T>>>>,
+    key: &'a str,
+}
+
+impl<T> Drop for InflightGuard<'_, T> {
+    fn drop(&mut self) {
+        self.inflight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(self.key);
+    }
+}
+
+impl<T: Clone + Send + Sync> RequestCoalescer<T> {
+    pub fn new() -> Self {
+        Self {
+            inflight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // first caller for a key runs the task, concurrent callers wait for its result
+    pub async fn run<F, Fut>(&self, key: String, task: F) -> T
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let role = {
+            let mut inflight = self.inflight.lock().unwrap_or_else(PoisonError::into_inner);
+            match inflight.get(&key) {
+                Some(receiver) => Role::Follower(receiver.clone()),
+                None => {
+                    let (sender, receiver) = watch::channel(None);
+                    inflight.insert(key.clone(), receiver);
+                    Role::Leader(sender)
+                }
+            }
+        };
+
+        match role {
+            Role::Leader(sender) => {
+                let _guard = InflightGuard {
+                    inflight: &self.inflight,
+                    key: &key,
+                };
+                let result = task().await;
+                let _ = sender.send(Some(result.clone()));
+                result
+            }
+            Role::Follower(mut receiver) => {
+                info!("Coalescing request onto in-flight run for '{}'", key);
+                let shared = match receiver.wait_for(Option::is_some).await {
+                    Ok(value) => (*value).clone(),
+                    Err(_) => None,
+                };
+
+                match shared {
+                    Some(result) => result,
+                    // leader was cancelled before finishing, do the work ourselves
+                    None => {
+                        warn!(
+                            "In-flight run for '{}' was dropped, running request directly",
+                            key
+                        );
+                        task().await
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<T: Clone + Send + Sync> Default for RequestCoalescer<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
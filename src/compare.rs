@@ -0,0 +1,300 @@
+// two plans for alternative objectives side by side: shared sessions, the ones
+// unique to each, and what picking one costs in the other
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::NaiveDateTime;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::citations;
+use crate::models::{GeneratePlanRequest, VivatechSource};
+use crate::placeholders;
+use crate::tools::session_slot;
+
+const MAX_OBJECTIVE_CHARS: usize = 500;
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ComparePlansRequest {
+    // e.g. "focus on AI"
+    pub objective_a: String,
+    // e.g. "focus on climate tech"
+    pub objective_b: String,
+    #[serde(default)]
+    pub conference: Option<String>,
+    #[serde(default)]
+    pub user_id: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub cost_tags: BTreeMap<String, String>,
+}
+
+impl ComparePlansRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        for objective in [&self.objective_a, &self.objective_b] {
+            if objective.trim().is_empty() {
+                return Err("objective_a and objective_b must not be empty".to_string());
+            }
+            if objective.chars().count() > MAX_OBJECTIVE_CHARS {
+                return Err(format!(
+                    "objectives must be at most {} characters",
+                    MAX_OBJECTIVE_CHARS
+                ));
+            }
+        }
+        if self.objective_a.trim().to_lowercase() == self.objective_b.trim().to_lowercase() {
+            return Err("objective_a and objective_b must differ".to_string());
+        }
+        Ok(())
+    }
+
+    // plan mode with citations, the comparison is built from what each plan cites.
+    // both objectives are asked on purpose, so the duplicate check is skipped
+    pub fn plan_request(&self, objective: &str) -> GeneratePlanRequest {
+        GeneratePlanRequest {
+            user_id: self.user_id.clone(),
+            include_citations: true,
+            model: self.model.clone(),
+            language: self.language.clone(),
+            cost_tags: self.cost_tags.clone(),
+            regenerate: true,
+            ..GeneratePlanRequest::simple(objective.to_string(), self.conference.clone())
+        }
+    }
+}
+
+// one side of the comparison
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PlanVariant {
+    pub objective: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plan_id: Option<String>,
+    pub plan: String,
+    pub model: String,
+    // ids of the sources the plan steps cite, in plan order
+    pub sessions: Vec<String>,
+    #[serde(skip)]
+    sources: HashMap<String, VivatechSource>,
+}
+
+impl PlanVariant {
+    pub fn new(
+        objective: String,
+        plan_id: Option<String>,
+        plan: String,
+        model: String,
+        sources: Vec<VivatechSource>,
+    ) -> Self {
+        let mut sessions = Vec::new();
+        for citation in citations::cite_plan(&plan, &sources) {
+            if !sessions.contains(&citation.source_id) {
+                sessions.push(citation.source_id);
+            }
+        }
+        Self {
+            objective,
+            plan_id,
+            plan,
+            model,
+            sessions,
+            sources: sources
+                .into_iter()
+                .map(|source| (source.id.clone(), source))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ComparedSession {
+    pub id: String,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub starts_at: Option<NaiveDateTime>,
+}
+
+// a session only plan a has running at the same time as one only plan b has
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Clash {
+    pub a: String,
+    pub b: String,
+    pub overlap_minutes: i64,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PlanComparison {
+    pub a: PlanVariant,
+    pub b: PlanVariant,
+    pub shared: Vec<ComparedSession>,
+    pub only_a: Vec<ComparedSession>,
+    pub only_b: Vec<ComparedSession>,
+    // shared sessions over all distinct sessions, 1.0 for identical plans
+    pub overlap: f64,
+    pub clashes: Vec<Clash>,
+    // plain sentences for the user, derived from the lists above
+    pub trade_offs: Vec<String>,
+    pub elapsed_ms: u64,
+}
+
+pub fn compare(a: PlanVariant, b: PlanVariant, year: i32, elapsed_ms: u64) -> PlanComparison {
+    let session = |id: &String| {
+        let source = a.sources.get(id).or_else(|| b.sources.get(id));
+        ComparedSession {
+            id: id.clone(),
+            title: source
+                .map(placeholders::summary)
+                .unwrap_or_else(|| id.clone()),
+            starts_at: source
+                .and_then(|source| session_slot(source, year))
+                .map(|slot| slot.start),
+        }
+    };
+    let shared: Vec<ComparedSession> = a
+        .sessions
+        .iter()
+        .filter(|id| b.sessions.contains(id))
+        .map(session)
+        .collect();
+    let only_a: Vec<ComparedSession> = a
+        .sessions
+        .iter()
+        .filter(|id| !b.sessions.contains(id))
+        .map(session)
+        .collect();
+    let only_b: Vec<ComparedSession> = b
+        .sessions
+        .iter()
+        .filter(|id| !a.sessions.contains(id))
+        .map(session)
+        .collect();
+
+    let distinct = shared.len() + only_a.len() + only_b.len();
+    let overlap = if distinct == 0 {
+        0.0
+    } else {
+        shared.len() as f64 / distinct as f64
+    };
+
+    let clashes = find_clashes(&only_a, &a.sources, &only_b, &b.sources, year);
+    let trade_offs = trade_offs(
+        &shared, &only_a, &only_b, &clashes, &a.sources, &b.sources, year,
+    );
+
+    PlanComparison {
+        a,
+        b,
+        shared,
+        only_a,
+        only_b,
+        overlap,
+        clashes,
+        trade_offs,
+        elapsed_ms,
+    }
+}
+
+fn find_clashes(
+    only_a: &[ComparedSession],
+    sources_a: &HashMap<String, VivatechSource>,
+    only_b: &[ComparedSession],
+    sources_b: &HashMap<String, VivatechSource>,
+    year: i32,
+) -> Vec<Clash> {
+    let mut clashes = Vec::new();
+    for first in only_a {
+        let Some(first_slot) = sources_a
+            .get(&first.id)
+            .and_then(|source| session_slot(source, year))
+        else {
+            continue;
+        };
+        for second in only_b {
+            let Some(second_slot) = sources_b
+                .get(&second.id)
+                .and_then(|source| session_slot(source, year))
+            else {
+                continue;
+            };
+            let overlap =
+                first_slot.end.min(second_slot.end) - first_slot.start.max(second_slot.start);
+            if overlap.num_minutes() > 0 {
+                clashes.push(Clash {
+                    a: first.id.clone(),
+                    b: second.id.clone(),
+                    overlap_minutes: overlap.num_minutes(),
+                });
+            }
+        }
+    }
+    clashes
+}
+
+fn trade_offs(
+    shared: &[ComparedSession],
+    only_a: &[ComparedSession],
+    only_b: &[ComparedSession],
+    clashes: &[Clash],
+    sources_a: &HashMap<String, VivatechSource>,
+    sources_b: &HashMap<String, VivatechSource>,
+    year: i32,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    match shared.len() {
+        0 => lines.push("The plans have no sessions in common.".to_string()),
+        n => lines.push(format!(
+            "{} session(s) appear in both plans and are worth keeping either way.",
+            n
+        )),
+    }
+    if !only_a.is_empty() || !only_b.is_empty() {
+        lines.push(format!(
+            "Plan A has {} session(s) plan B skips, plan B has {} plan A skips.",
+            only_a.len(),
+            only_b.len()
+        ));
+    }
+
+    let title = |sessions: &[ComparedSession], id: &str| {
+        sessions
+            .iter()
+            .find(|session| session.id == id)
+            .map(|session| session.title.clone())
+            .unwrap_or_else(|| id.to_string())
+    };
+    for clash in clashes {
+        lines.push(format!(
+            "\"{}\" (A) overlaps \"{}\" (B) by {} minutes, only one of them fits.",
+            title(only_a, &clash.a),
+            title(only_b, &clash.b),
+            clash.overlap_minutes
+        ));
+    }
+
+    // sessions from b that would fit into a without touching any of a's picks
+    let a_slots: Vec<_> = sources_a
+        .values()
+        .filter(|source| shared.iter().chain(only_a).any(|s| s.id == source.id))
+        .filter_map(|source| session_slot(source, year))
+        .collect();
+    for session in only_b {
+        let Some(slot) = sources_b
+            .get(&session.id)
+            .and_then(|source| session_slot(source, year))
+        else {
+            continue;
+        };
+        let busy = a_slots
+            .iter()
+            .any(|other| slot.start < other.end && other.start < slot.end);
+        if !busy {
+            lines.push(format!(
+                "\"{}\" from plan B also fits into plan A.",
+                session.title
+            ));
+        }
+    }
+    lines
+}
@@ -0,0 +1,224 @@
+// conference profiles, selected per request
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::config;
+
+// built-in vivatech 2025 defaults
+const VIVATECH_ID: &str = "vivatech-2025";
+const VIVATECH_YEAR: i32 = 2025;
+const CURRENT_MONTH: u32 = 6; // June
+const CURRENT_DAY: u32 = 11;
+const LAST_DAY: u32 = 14;
+// hall to hall walking times at paris expo porte de versailles
+const VIVATECH_PATHS: &str = include_str!("../resources/vivatech_paths.json");
+
+fn default_timezone() -> String {
+    "Europe/Paris".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VenuePath {
+    pub from: String,
+    pub to: String,
+    pub walking_minutes: u32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct GeoPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VenueGraph {
+    pub name: String,
+    #[serde(default)]
+    pub locations: Vec<String>,
+    #[serde(default)]
+    pub paths: Vec<VenuePath>,
+    // where the venue is, the weather tool is left out without it
+    #[serde(default)]
+    pub coordinates: Option<GeoPoint>,
+}
+
+impl VenueGraph {
+    // the location as the venue names it, "hall1" and "HALL 1" are "Hall 1"
+    pub fn location(&self, name: &str) -> Option<&str> {
+        let key = |name: &str| name.to_lowercase().replace(char::is_whitespace, "");
+        let wanted = key(name);
+        self.locations
+            .iter()
+            .find(|location| key(location) == wanted)
+            .map(String::as_str)
+    }
+
+    // a direct path either way, none within one location
+    pub fn walking_minutes(&self, from: &str, to: &str) -> Option<u32> {
+        if from.eq_ignore_ascii_case(to) {
+            return Some(0);
+        }
+        self.paths
+            .iter()
+            .find(|path| {
+                (path.from.eq_ignore_ascii_case(from) && path.to.eq_ignore_ascii_case(to))
+                    || (path.from.eq_ignore_ascii_case(to) && path.to.eq_ignore_ascii_case(from))
+            })
+            .map(|path| path.walking_minutes)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConferenceProfile {
+    pub id: String,
+    pub name: String,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    // pins "today" for tools, otherwise derived from the dates
+    #[serde(default)]
+    pub reference_date: Option<NaiveDate>,
+    // iana name of the venue timezone
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    #[serde(default)]
+    pub venue: VenueGraph,
+    // falls back to VIVATECH_API_URL
+    #[serde(default)]
+    pub api_url: Option<String>,
+}
+
+impl ConferenceProfile {
+    pub fn vivatech_2025() -> Self {
+        let start_date = NaiveDate::from_ymd_opt(VIVATECH_YEAR, CURRENT_MONTH, CURRENT_DAY)
+            .expect("June 11, 2025 is a valid date");
+        let end_date = NaiveDate::from_ymd_opt(VIVATECH_YEAR, CURRENT_MONTH, LAST_DAY)
+            .expect("June 14, 2025 is a valid date");
+
+        // CONFERENCE_DATE keeps working as the override for the default profile
+        let reference_date = config::get().conference_date.or(Some(start_date));
+
+        Self {
+            id: VIVATECH_ID.to_string(),
+            name: "Vivatech 2025".to_string(),
+            start_date,
+            end_date,
+            reference_date,
+            timezone: default_timezone(),
+            venue: VenueGraph {
+                name: "Paris Expo Porte de Versailles".to_string(),
+                locations: vec![
+                    "Hall 1".to_string(),
+                    "Hall 2".to_string(),
+                    "Hall 3".to_string(),
+                ],
+                paths: serde_json::from_str(VIVATECH_PATHS)
+                    .expect("the bundled venue paths are valid"),
+                coordinates: Some(GeoPoint {
+                    latitude: 48.8323,
+                    longitude: 2.2876,
+                }),
+            },
+            api_url: None,
+        }
+    }
+
+    // date the tools treat as today
+    pub fn current_date(&self) -> NaiveDate {
+        self.now().date_naive()
+    }
+
+    // moment the tools treat as now, in the venue timezone. the date is pinned or
+    // clamped to the conference, the time of day is the venue's actual clock
+    pub fn now(&self) -> DateTime<Tz> {
+        let tz = self.tz();
+        let clock = Utc::now().with_timezone(&tz);
+        let today = match self.reference_date {
+            Some(date) => date,
+            None if clock.date_naive() < self.start_date => self.start_date,
+            None => clock.date_naive().min(self.end_date),
+        };
+        tz.from_local_datetime(&today.and_time(clock.time()))
+            .earliest()
+            .unwrap_or(clock)
+    }
+
+    // an unknown timezone name falls back to the vivatech one
+    pub fn tz(&self) -> Tz {
+        Tz::from_str(&self.timezone).unwrap_or(chrono_tz::Europe::Paris)
+    }
+
+    // year assumed for dates written without one ("June 12")
+    pub fn year(&self) -> i32 {
+        self.start_date.year()
+    }
+
+    pub fn api_url(&self) -> Option<String> {
+        self.api_url
+            .clone()
+            .or_else(|| config::get().vivatech_api_url.clone())
+    }
+}
+
+pub struct ConferenceRegistry {
+    profiles: HashMap<String, ConferenceProfile>,
+    default_id: String,
+}
+
+impl ConferenceRegistry {
+    // CONFERENCE_PROFILES holds a json array of extra profiles
+    pub fn from_env() -> Self {
+        let mut profiles = HashMap::new();
+        let builtin = ConferenceProfile::vivatech_2025();
+        profiles.insert(builtin.id.clone(), builtin);
+
+        if let Ok(raw) = config::env_var("CONFERENCE_PROFILES") {
+            match serde_json::from_str::<Vec<ConferenceProfile>>(&raw) {
+                Ok(extra) => {
+                    for profile in extra {
+                        info!("Loaded conference profile: {}", profile.id);
+                        profiles.insert(profile.id.clone(), profile);
+                    }
+                }
+                Err(e) => warn!("Ignoring invalid CONFERENCE_PROFILES: {}", e),
+            }
+        }
+
+        let default_id = match config::env_var("DEFAULT_CONFERENCE") {
+            Ok(id) if profiles.contains_key(&id) => id,
+            Ok(id) => {
+                warn!(
+                    "DEFAULT_CONFERENCE '{}' is unknown, using {}",
+                    id, VIVATECH_ID
+                );
+                VIVATECH_ID.to_string()
+            }
+            Err(_) => VIVATECH_ID.to_string(),
+        };
+
+        Self {
+            profiles,
+            default_id,
+        }
+    }
+
+    pub fn default_profile(&self) -> &ConferenceProfile {
+        &self.profiles[&self.default_id]
+    }
+
+    // None selects the default, unknown ids are an error
+    pub fn resolve(&self, id: Option<&str>) -> Result<&ConferenceProfile, String> {
+        match id {
+            None => Ok(self.default_profile()),
+            Some(id) => self
+                .profiles
+                .get(id)
+                .ok_or_else(|| format!("Unknown conference '{}'", id)),
+        }
+    }
+}
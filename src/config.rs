@@ -0,0 +1,397 @@
+// settings read on the request path by handlers, tools and the date logic, loaded once
+// right after the secrets are exported. getenv takes the process-wide env lock on every
+// call and is unsound while anything still calls set_var, so requests never read it
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use arc_swap::ArcSwap;
+use chrono::NaiveDate;
+use rig::providers::openai;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::llm::{ModelChoice, Provider};
+use crate::rerank::RerankMode;
+use crate::retry::RetryPolicy;
+use crate::signing::UpstreamSigning;
+
+static CONFIG: OnceLock<AppConfig> = OnceLock::new();
+
+static RUNTIME: OnceLock<ArcSwap<RuntimeSettings>> = OnceLock::new();
+
+tokio::task_local! {
+    // set while a request is served, env reads inside it are flagged in debug builds
+    static SERVING_REQUEST: ();
+}
+
+// no Debug, it holds secrets
+pub struct AppConfig {
+    pub vivatech_api_url: Option<String>,
+    pub api_timeout_seconds: u64,
+    // largest upstream body we are willing to hold in memory
+    pub max_response_bytes: usize,
+    // longest source text chunk handed to the agent, 0 keeps them whole
+    pub source_max_chars: usize,
+    // log and count every way a search response differs from the expected schema
+    pub strict_schema: bool,
+    // shared by the vivatech calls and the llm calls
+    pub retry: RetryPolicy,
+    pub upstream_signing: Vec<UpstreamSigning>,
+    // VIVATECH_SIGNING_*, for upstreams without their own UPSTREAM_SIGNING_KEYS entry
+    pub default_signing: Option<UpstreamSigning>,
+    // CONFERENCE_DATE, pins "today" for the default profile
+    pub conference_date: Option<NaiveDate>,
+    pub mock_mode: bool,
+    // always the mock in MOCK_MODE
+    pub llm_provider: Provider,
+    // LLM_MODEL spec, parsed against the provider when used
+    pub llm_model: Option<String>,
+    // LLM_FALLBACK_MODELS specs, tried in order when the model is unavailable
+    pub llm_fallback_models: Vec<String>,
+    // provider key variables that were set, the keys themselves stay with the clients
+    pub llm_api_keys: Vec<&'static str>,
+    pub draft_model: Option<String>,
+    pub polish_model: Option<String>,
+    pub plan_temperature: f64,
+    pub plan_max_tokens: u64,
+    // largest max_tokens a request may ask for
+    pub plan_max_tokens_limit: u64,
+    // tool round-trips allowed per prompt, e.g. search then assess
+    pub agent_max_turns: usize,
+    pub batch_max_requests: usize,
+    // objectives planned at the same time within one batch
+    pub batch_concurrency: usize,
+    // plans generated at the same time, the rest wait in submission order
+    pub plan_job_concurrency: usize,
+    // queued and running jobs accepted before submissions are turned away
+    pub plan_job_max_pending: usize,
+    // how long finished jobs can still be polled
+    pub plan_job_retention: Duration,
+    pub plan_list_limit: usize,
+    pub plan_footer_enabled: bool,
+    // tell the planner which sessions other attendees pick most
+    pub popularity_prior_enabled: bool,
+    // tell the planner what attendees complain about most in plan feedback
+    pub feedback_prior_enabled: bool,
+    // jaccard score above which two objectives count as the same ask
+    pub duplicate_similarity_threshold: f64,
+    pub duplicate_window_hours: i64,
+    pub copy_default_language: Option<String>,
+    pub admin_token: Option<String>,
+    // planner routes open without API_KEYS, for local development only
+    pub auth_disabled: bool,
+    // requests per minute for keys without their own limit
+    pub api_key_rate_limit_per_minute: u32,
+    // plan runs a client can start back to back, and the rate they refill at
+    pub rate_limit_burst: u32,
+    // 0 disables the limiter
+    pub rate_limit_per_minute: u32,
+    pub abuse: AbuseLimits,
+    pub widget_signing_secret: Option<String>,
+    pub widget_issuer_key: Option<String>,
+    // longest lifetime a minted widget token can have
+    pub widget_token_ttl_seconds: u64,
+    // verifies slack slash commands, /slack/events answers 404 when unset
+    pub slack_signing_secret: Option<String>,
+    // the bot replies with this token, /telegram/webhook answers 404 without both
+    pub telegram_bot_token: Option<String>,
+    // the secret_token given to setWebhook
+    pub telegram_webhook_secret: Option<String>,
+    // e.g. https://planner.example.com, share links are relative without it
+    pub public_base_url: String,
+    // longest allowed share link lifetime, unlimited when unset
+    pub share_max_ttl_seconds: Option<u64>,
+    // proxies in front of the service that append to X-Forwarded-For. the client address
+    // is the entry that many hops from the right, 0 trusts only the peer address
+    pub trusted_proxy_hops: usize,
+    pub readiness_timeout: Duration,
+    pub offline_search_limit: usize,
+    pub rag_search_limit: usize,
+    // cosine similarity below which a semantic hit is dropped
+    pub rag_min_score: f64,
+    pub openai_api_key: Option<String>,
+    pub rerank_mode: RerankMode,
+    // search results kept per query after reranking
+    pub rerank_top_k: usize,
+    pub rerank_embedding_model: String,
+    // RERANK_MODEL spec, parsed against the provider when used
+    pub rerank_model: Option<String>,
+    // seconds a generated plan is reused for the same request, 0 disables the cache
+    pub plan_cache_ttl_seconds: u64,
+    pub plan_cache_capacity: u64,
+    // seconds a search result is reused, 0 disables the cache
+    pub query_cache_ttl_seconds: u64,
+    // distinct queries kept before the least used are evicted
+    pub query_cache_capacity: u64,
+    // longer objectives are refused before they reach the model
+    pub objective_max_chars: usize,
+    // screen objectives with openai's moderation api, needs OPENAI_API_KEY
+    pub objective_moderation_enabled: bool,
+    // open-meteo compatible forecast endpoint for the weather tool
+    pub weather_api_url: String,
+    pub weather_enabled: bool,
+    // google distance matrix compatible endpoint for hotel to venue travel times
+    pub routing_api_url: String,
+    // without it the travel time tool only knows the venue's halls
+    pub routing_api_key: Option<String>,
+}
+
+// ABUSE_*, see abuse.rs
+#[derive(Debug, Clone)]
+pub struct AbuseLimits {
+    // requests per burst window before a client is throttled, without a strike
+    pub burst_limit: usize,
+    pub burst_window: Duration,
+    // how long strikes count
+    pub strike_window: Duration,
+    pub throttle_after: usize,
+    pub suspend_after: usize,
+    pub throttle_duration: Duration,
+}
+
+impl AbuseLimits {
+    fn from_env() -> Self {
+        let seconds = |name, default| Duration::from_secs(parsed(name).unwrap_or(default));
+        Self {
+            burst_limit: parsed("ABUSE_BURST_LIMIT").unwrap_or(20),
+            burst_window: seconds("ABUSE_BURST_WINDOW_SECONDS", 60),
+            strike_window: seconds("ABUSE_STRIKE_WINDOW_SECONDS", 3600),
+            throttle_after: parsed("ABUSE_THROTTLE_AFTER_STRIKES").unwrap_or(3),
+            suspend_after: parsed("ABUSE_SUSPEND_AFTER_STRIKES").unwrap_or(6),
+            throttle_duration: seconds("ABUSE_THROTTLE_SECONDS", 600),
+        }
+    }
+}
+
+impl AppConfig {
+    pub fn from_env() -> Self {
+        let mock_mode = env_var("MOCK_MODE")
+            .map(|v| matches!(v.to_lowercase().as_str(), "true" | "1" | "yes" | "on"))
+            .unwrap_or(false);
+        let llm_provider = if mock_mode {
+            Provider::Mock
+        } else {
+            parsed("LLM_PROVIDER").unwrap_or(Provider::OpenAI)
+        };
+        let llm_api_keys = [Provider::OpenAI, Provider::Anthropic, Provider::Gemini]
+            .into_iter()
+            .filter_map(Provider::api_key_var)
+            .filter(|key| env_var(key).is_ok())
+            .collect();
+
+        Self {
+            vivatech_api_url: env_var("VIVATECH_API_URL").ok(),
+            api_timeout_seconds: parsed("API_TIMEOUT_SECONDS").unwrap_or(30),
+            max_response_bytes: parsed("VIVATECH_MAX_RESPONSE_BYTES").unwrap_or(10 * 1024 * 1024),
+            source_max_chars: parsed("SOURCE_MAX_CHARS").unwrap_or(1500),
+            strict_schema: parsed("VIVATECH_STRICT_SCHEMA").unwrap_or(false),
+            retry: RetryPolicy::from_env(),
+            upstream_signing: UpstreamSigning::configured(),
+            default_signing: UpstreamSigning::from_default_env(),
+            conference_date: env_var("CONFERENCE_DATE")
+                .ok()
+                .and_then(|s| NaiveDate::parse_from_str(&s, "%Y-%m-%d").ok()),
+            mock_mode,
+            llm_provider,
+            llm_model: env_var("LLM_MODEL").ok(),
+            llm_fallback_models: env_var("LLM_FALLBACK_MODELS")
+                .unwrap_or_else(|_| "openai:gpt-4o-mini".to_string())
+                .split(',')
+                .map(|spec| spec.trim().to_string())
+                .filter(|spec| !spec.is_empty())
+                .collect(),
+            llm_api_keys,
+            draft_model: env_var("DRAFT_MODEL").ok(),
+            polish_model: env_var("POLISH_MODEL").ok(),
+            plan_temperature: parsed("PLAN_TEMPERATURE").unwrap_or(0.7),
+            plan_max_tokens: parsed("PLAN_MAX_TOKENS").unwrap_or(2048),
+            plan_max_tokens_limit: parsed("PLAN_MAX_TOKENS_LIMIT").unwrap_or(8192),
+            agent_max_turns: parsed("AGENT_MAX_TURNS").unwrap_or(5),
+            batch_max_requests: parsed("BATCH_MAX_REQUESTS").unwrap_or(20),
+            batch_concurrency: parsed("BATCH_CONCURRENCY").unwrap_or(4).max(1),
+            plan_job_concurrency: parsed("PLAN_JOB_CONCURRENCY").unwrap_or(2).max(1),
+            plan_job_max_pending: parsed("PLAN_JOB_MAX_PENDING").unwrap_or(100),
+            plan_job_retention: Duration::from_secs(
+                parsed("PLAN_JOB_RETENTION_SECS").unwrap_or(3600),
+            ),
+            plan_list_limit: parsed("PLAN_LIST_LIMIT").unwrap_or(50).max(1),
+            plan_footer_enabled: parsed("PLAN_FOOTER_ENABLED").unwrap_or(true),
+            popularity_prior_enabled: parsed("POPULARITY_PRIOR_ENABLED").unwrap_or(false),
+            feedback_prior_enabled: parsed("FEEDBACK_PRIOR_ENABLED").unwrap_or(false),
+            duplicate_similarity_threshold: parsed("DUPLICATE_SIMILARITY_THRESHOLD").unwrap_or(0.8),
+            duplicate_window_hours: parsed("DUPLICATE_WINDOW_HOURS").unwrap_or(72),
+            copy_default_language: env_var("COPY_DEFAULT_LANGUAGE").ok(),
+            admin_token: non_empty("ADMIN_TOKEN"),
+            auth_disabled: parsed("AUTH_DISABLED").unwrap_or(false),
+            api_key_rate_limit_per_minute: parsed("API_KEY_RATE_LIMIT_PER_MINUTE")
+                .unwrap_or(60)
+                .max(1),
+            rate_limit_burst: parsed("RATE_LIMIT_BURST").unwrap_or(20),
+            rate_limit_per_minute: parsed("RATE_LIMIT_PER_MINUTE").unwrap_or(30),
+            abuse: AbuseLimits::from_env(),
+            widget_signing_secret: non_empty("WIDGET_SIGNING_SECRET"),
+            widget_issuer_key: non_empty("WIDGET_ISSUER_KEY"),
+            widget_token_ttl_seconds: parsed("WIDGET_TOKEN_TTL_SECONDS").unwrap_or(900).max(1),
+            slack_signing_secret: non_empty("SLACK_SIGNING_SECRET"),
+            telegram_bot_token: non_empty("TELEGRAM_BOT_TOKEN"),
+            telegram_webhook_secret: non_empty("TELEGRAM_WEBHOOK_SECRET"),
+            public_base_url: env_var("PUBLIC_BASE_URL").unwrap_or_default(),
+            share_max_ttl_seconds: parsed("SHARE_MAX_TTL_SECONDS"),
+            trusted_proxy_hops: parsed("TRUSTED_PROXY_HOPS").unwrap_or(0),
+            readiness_timeout: Duration::from_secs(
+                parsed("READINESS_TIMEOUT_SECONDS").unwrap_or(3),
+            ),
+            offline_search_limit: parsed("OFFLINE_CATALOG_SEARCH_LIMIT").unwrap_or(10),
+            rag_search_limit: parsed("RAG_SEARCH_LIMIT").unwrap_or(5).max(1),
+            rag_min_score: parsed("RAG_MIN_SCORE").unwrap_or(0.3),
+            openai_api_key: non_empty("OPENAI_API_KEY"),
+            rerank_mode: env_var("RERANK_MODE")
+                .map(|mode| {
+                    mode.parse().unwrap_or_else(|e| {
+                        warn!("{}, reranking stays off", e);
+                        RerankMode::Off
+                    })
+                })
+                .unwrap_or(RerankMode::Off),
+            rerank_top_k: parsed("RERANK_TOP_K").unwrap_or(8).max(1),
+            rerank_embedding_model: env_var("RERANK_EMBEDDING_MODEL")
+                .unwrap_or_else(|_| openai::TEXT_EMBEDDING_3_SMALL.to_string()),
+            rerank_model: env_var("RERANK_MODEL").ok(),
+            plan_cache_ttl_seconds: parsed("PLAN_CACHE_TTL_SECONDS").unwrap_or(0),
+            plan_cache_capacity: parsed("PLAN_CACHE_CAPACITY").unwrap_or(500),
+            query_cache_ttl_seconds: parsed("QUERY_CACHE_TTL_SECONDS").unwrap_or(300),
+            query_cache_capacity: parsed("QUERY_CACHE_CAPACITY").unwrap_or(1000),
+            objective_max_chars: parsed("OBJECTIVE_MAX_CHARS").unwrap_or(2000).max(1),
+            objective_moderation_enabled: parsed("OBJECTIVE_MODERATION_ENABLED").unwrap_or(false),
+            weather_api_url: non_empty("WEATHER_API_URL")
+                .unwrap_or_else(|| "https://api.open-meteo.com/v1/forecast".to_string()),
+            weather_enabled: parsed("WEATHER_ENABLED").unwrap_or(true),
+            routing_api_url: non_empty("ROUTING_API_URL").unwrap_or_else(|| {
+                "https://maps.googleapis.com/maps/api/distancematrix/json".to_string()
+            }),
+            routing_api_key: non_empty("ROUTING_API_KEY"),
+        }
+    }
+
+    pub fn has_api_key(&self, provider: Provider) -> bool {
+        provider
+            .api_key_var()
+            .map_or(true, |key| self.llm_api_keys.contains(&key))
+    }
+}
+
+// loaded on first use, main touches it as soon as the secrets are exported
+pub fn get() -> &'static AppConfig {
+    CONFIG.get_or_init(AppConfig::from_env)
+}
+
+// what admins can change while the service runs, see /admin/config. starts from the
+// AppConfig values, and every change swaps in a whole new copy
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct RuntimeSettings {
+    // model spec like LLM_MODEL, e.g. "anthropic:claude-3-5-sonnet-latest"
+    pub llm_model: Option<String>,
+    pub plan_temperature: f64,
+    pub plan_max_tokens: u64,
+    // 0 disables the cache, any change starts it empty
+    pub plan_cache_ttl_seconds: u64,
+    pub query_cache_ttl_seconds: u64,
+    pub plan_footer_enabled: bool,
+    pub popularity_prior_enabled: bool,
+    pub feedback_prior_enabled: bool,
+    pub objective_moderation_enabled: bool,
+    // prompt templates by name, over the secrets, file and table ones
+    #[serde(default)]
+    pub prompt_templates: BTreeMap<String, String>,
+}
+
+impl RuntimeSettings {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            llm_model: config.llm_model.clone(),
+            plan_temperature: config.plan_temperature,
+            plan_max_tokens: config.plan_max_tokens,
+            plan_cache_ttl_seconds: config.plan_cache_ttl_seconds,
+            query_cache_ttl_seconds: config.query_cache_ttl_seconds,
+            plan_footer_enabled: config.plan_footer_enabled,
+            popularity_prior_enabled: config.popularity_prior_enabled,
+            feedback_prior_enabled: config.feedback_prior_enabled,
+            objective_moderation_enabled: config.objective_moderation_enabled,
+            prompt_templates: BTreeMap::new(),
+        }
+    }
+
+    // the limits requests are held to, and a model whose provider is set up
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=2.0).contains(&self.plan_temperature) {
+            return Err(format!(
+                "plan_temperature must be between 0 and 2, got {}",
+                self.plan_temperature
+            ));
+        }
+        let limit = get().plan_max_tokens_limit;
+        if self.plan_max_tokens == 0 || self.plan_max_tokens > limit {
+            return Err(format!(
+                "plan_max_tokens must be between 1 and {}, got {}",
+                limit, self.plan_max_tokens
+            ));
+        }
+        if let Some(spec) = &self.llm_model {
+            let choice = ModelChoice::parse(spec, get().llm_provider)?;
+            if !get().has_api_key(choice.provider) {
+                return Err(format!(
+                    "llm_model {} needs the {} api key",
+                    choice,
+                    choice.provider.name()
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn runtime_settings() -> &'static ArcSwap<RuntimeSettings> {
+    RUNTIME.get_or_init(|| ArcSwap::from_pointee(RuntimeSettings::from_config(get())))
+}
+
+// the settings as of now, a later change doesn't affect the copy already taken
+pub fn runtime() -> Arc<RuntimeSettings> {
+    runtime_settings().load_full()
+}
+
+// seen by every request that starts after it
+pub fn set_runtime(settings: RuntimeSettings) {
+    runtime_settings().store(Arc::new(settings));
+}
+
+// std::env::var for every read outside this module, so the ones still happening
+// while a request is served show up in debug builds instead of going unnoticed
+pub fn env_var(name: &str) -> Result<String, std::env::VarError> {
+    #[cfg(debug_assertions)]
+    if SERVING_REQUEST.try_with(|_| ()).is_ok() {
+        tracing::error!(
+            var = name,
+            "Environment read while serving a request, load it into AppConfig instead"
+        );
+        metrics::counter!("env_reads_in_request_total", "var" => name.to_string()).increment(1);
+    }
+    std::env::var(name)
+}
+
+// marks `future` as serving a request for env_var. tasks it spawns are not covered
+pub async fn serving_request<F: Future>(future: F) -> F::Output {
+    SERVING_REQUEST.scope((), future).await
+}
+
+fn parsed<T: FromStr>(name: &str) -> Option<T> {
+    env_var(name).ok().and_then(|s| s.parse::<T>().ok())
+}
+
+fn non_empty(name: &str) -> Option<String> {
+    env_var(name).ok().filter(|value| !value.is_empty())
+}
@@ -0,0 +1,309 @@
+// where chat sessions are kept between messages: in memory, or in redis or postgres so
+// they survive a redeploy and are shared by every instance. each save restarts the ttl
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use tokio_postgres::NoTls;
+use tracing::{info, warn};
+
+use crate::config;
+use crate::models::VivatechSource;
+
+const KEY_PREFIX: &str = "vivaagent:chat:";
+
+const CREATE_TABLE: &str = "
+    CREATE TABLE IF NOT EXISTS chat_sessions (
+        session_id TEXT PRIMARY KEY,
+        conversation TEXT NOT NULL,
+        expires_at TIMESTAMPTZ NOT NULL
+    )";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConversationStoreError {
+    #[error("redis error: {0}")]
+    Redis(#[from] redis::RedisError),
+    #[error("postgres error: {0}")]
+    Postgres(#[from] tokio_postgres::Error),
+    #[error("unreadable conversation: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+// a session as it is stored, see sessions.rs for how it is trimmed and summarized
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StoredConversation {
+    // summary of exchanges no longer kept verbatim
+    #[serde(default)]
+    pub memory: Option<String>,
+    #[serde(default)]
+    pub exchanges: Vec<Exchange>,
+    // bumped whenever exchanges are dropped from the front
+    #[serde(default)]
+    pub revision: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Exchange {
+    pub message: String,
+    // the plan the assistant answered with
+    pub reply: String,
+    // what the search tools returned for this reply
+    #[serde(default)]
+    pub sources: Vec<VivatechSource>,
+    pub at: DateTime<Utc>,
+}
+
+#[async_trait]
+pub trait ConversationStore: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // None for unknown and expired sessions
+    async fn load(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<StoredConversation>, ConversationStoreError>;
+
+    async fn save(
+        &self,
+        session_id: &str,
+        conversation: &StoredConversation,
+        ttl: Duration,
+    ) -> Result<(), ConversationStoreError>;
+
+    // false when there was nothing to delete
+    async fn delete(&self, session_id: &str) -> Result<bool, ConversationStoreError>;
+}
+
+// CONVERSATION_STORE picks the backend, memory when unset or when the backend
+// can't be set up
+pub fn from_env() -> Arc<dyn ConversationStore> {
+    let backend = config::env_var("CONVERSATION_STORE").unwrap_or_default();
+    let store: Arc<dyn ConversationStore> = match backend.trim().to_lowercase().as_str() {
+        "" | "memory" => Arc::new(MemoryStore::default()),
+        "redis" => match config::env_var("REDIS_URL").map(redis::Client::open) {
+            Ok(Ok(client)) => Arc::new(RedisStore { client }),
+            Ok(Err(e)) => {
+                warn!("Invalid REDIS_URL, chat sessions stay in memory: {}", e);
+                Arc::new(MemoryStore::default())
+            }
+            Err(_) => {
+                warn!("REDIS_URL is unset, chat sessions stay in memory");
+                Arc::new(MemoryStore::default())
+            }
+        },
+        "postgres" => match config::env_var("CONVERSATION_DATABASE_URL") {
+            Ok(url) if !url.is_empty() => Arc::new(PostgresStore::new(url)),
+            _ => {
+                warn!("CONVERSATION_DATABASE_URL is unset, chat sessions stay in memory");
+                Arc::new(MemoryStore::default())
+            }
+        },
+        other => {
+            warn!(
+                "Unknown CONVERSATION_STORE '{}', expected memory, redis or postgres",
+                other
+            );
+            Arc::new(MemoryStore::default())
+        }
+    };
+    info!("Chat sessions are kept in {}", store.name());
+    store
+}
+
+// one instance only, lost on restart
+#[derive(Default)]
+pub struct MemoryStore {
+    conversations: Mutex<HashMap<String, (StoredConversation, Instant)>>,
+}
+
+#[async_trait]
+impl ConversationStore for MemoryStore {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    async fn load(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<StoredConversation>, ConversationStoreError> {
+        let conversations = self
+            .conversations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        Ok(conversations
+            .get(session_id)
+            .filter(|(_, expires_at)| *expires_at > Instant::now())
+            .map(|(conversation, _)| conversation.clone()))
+    }
+
+    async fn save(
+        &self,
+        session_id: &str,
+        conversation: &StoredConversation,
+        ttl: Duration,
+    ) -> Result<(), ConversationStoreError> {
+        let now = Instant::now();
+        let mut conversations = self
+            .conversations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        conversations.retain(|_, (_, expires_at)| *expires_at > now);
+        conversations.insert(session_id.to_string(), (conversation.clone(), now + ttl));
+        Ok(())
+    }
+
+    async fn delete(&self, session_id: &str) -> Result<bool, ConversationStoreError> {
+        let mut conversations = self
+            .conversations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        Ok(conversations
+            .remove(session_id)
+            .is_some_and(|(_, expires_at)| expires_at > Instant::now()))
+    }
+}
+
+// one json value per session, expired by redis itself
+pub struct RedisStore {
+    client: redis::Client,
+}
+
+#[async_trait]
+impl ConversationStore for RedisStore {
+    fn name(&self) -> &'static str {
+        "redis"
+    }
+
+    async fn load(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<StoredConversation>, ConversationStoreError> {
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let stored: Option<String> = conn.get(format!("{}{}", KEY_PREFIX, session_id)).await?;
+        Ok(stored.map(|json| serde_json::from_str(&json)).transpose()?)
+    }
+
+    async fn save(
+        &self,
+        session_id: &str,
+        conversation: &StoredConversation,
+        ttl: Duration,
+    ) -> Result<(), ConversationStoreError> {
+        let json = serde_json::to_string(conversation)?;
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let _: () = conn
+            .pset_ex(
+                format!("{}{}", KEY_PREFIX, session_id),
+                json,
+                ttl.as_millis().max(1) as u64,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn delete(&self, session_id: &str) -> Result<bool, ConversationStoreError> {
+        let mut conn = self.client.get_multiplexed_async_connection().await?;
+        let deleted: u64 = conn.del(format!("{}{}", KEY_PREFIX, session_id)).await?;
+        Ok(deleted > 0)
+    }
+}
+
+// a chat_sessions table, created on first use. expired rows are skipped on read
+// and purged on write
+pub struct PostgresStore {
+    url: String,
+    client: tokio::sync::Mutex<Option<Arc<tokio_postgres::Client>>>,
+}
+
+impl PostgresStore {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            client: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    // reconnects after the connection dropped, e.g. a database restart
+    async fn client(&self) -> Result<Arc<tokio_postgres::Client>, ConversationStoreError> {
+        let mut client = self.client.lock().await;
+        if let Some(connected) = client.as_ref().filter(|connected| !connected.is_closed()) {
+            return Ok(connected.clone());
+        }
+        let (connected, connection) = tokio_postgres::connect(&self.url, NoTls).await?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                warn!("Conversation database connection closed: {}", e);
+            }
+        });
+        connected.batch_execute(CREATE_TABLE).await?;
+        let connected = Arc::new(connected);
+        *client = Some(connected.clone());
+        Ok(connected)
+    }
+}
+
+#[async_trait]
+impl ConversationStore for PostgresStore {
+    fn name(&self) -> &'static str {
+        "postgres"
+    }
+
+    async fn load(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<StoredConversation>, ConversationStoreError> {
+        let row = self
+            .client()
+            .await?
+            .query_opt(
+                "SELECT conversation FROM chat_sessions
+                 WHERE session_id = $1 AND expires_at > now()",
+                &[&session_id],
+            )
+            .await?;
+        let stored: Option<String> = row.map(|row| row.get(0));
+        Ok(stored.map(|json| serde_json::from_str(&json)).transpose()?)
+    }
+
+    async fn save(
+        &self,
+        session_id: &str,
+        conversation: &StoredConversation,
+        ttl: Duration,
+    ) -> Result<(), ConversationStoreError> {
+        let json = serde_json::to_string(conversation)?;
+        let expires_at = Utc::now()
+            + chrono::Duration::from_std(ttl).unwrap_or_else(|_| chrono::Duration::days(365));
+        let client = self.client().await?;
+        client
+            .execute("DELETE FROM chat_sessions WHERE expires_at <= now()", &[])
+            .await?;
+        client
+            .execute(
+                "INSERT INTO chat_sessions (session_id, conversation, expires_at)
+                 VALUES ($1, $2, $3)
+                 ON CONFLICT (session_id) DO UPDATE
+                 SET conversation = EXCLUDED.conversation, expires_at = EXCLUDED.expires_at",
+                &[&session_id, &json, &expires_at],
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn delete(&self, session_id: &str) -> Result<bool, ConversationStoreError> {
+        let deleted = self
+            .client()
+            .await?
+            .execute(
+                "DELETE FROM chat_sessions WHERE session_id = $1 AND expires_at > now()",
+                &[&session_id],
+            )
+            .await?;
+        Ok(deleted > 0)
+    }
+}
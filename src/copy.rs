@@ -0,0 +1,127 @@
+// user-facing text by language, bundled and overridable per deployment so
+// white-label instances change copy without touching the handlers
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::config;
+
+pub use crate::language::language_code;
+
+const BUNDLED_COPY: &str = include_str!("../resources/copy.json");
+
+static COPY: OnceLock<HashMap<String, HashMap<String, String>>> = OnceLock::new();
+
+// same shape as resources/copy.json, only the keys to change are needed
+fn get_copy_path() -> Option<String> {
+    config::env_var("COPY_PATH")
+        .ok()
+        .filter(|path| !path.is_empty())
+}
+
+// used when a request names no language, and for error messages
+pub fn default_language() -> String {
+    config::get()
+        .copy_default_language
+        .as_deref()
+        .map(language_code)
+        .unwrap_or_else(|| "en".to_string())
+}
+
+fn texts() -> &'static HashMap<String, HashMap<String, String>> {
+    COPY.get_or_init(|| {
+        let mut texts: HashMap<String, HashMap<String, String>> =
+            serde_json::from_str(BUNDLED_COPY).expect("bundled copy is valid json");
+        let Some(path) = get_copy_path() else {
+            return texts;
+        };
+        let overrides = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|raw| {
+                serde_json::from_str::<HashMap<String, HashMap<String, String>>>(&raw)
+                    .map_err(|e| e.to_string())
+            });
+        match overrides {
+            Ok(overrides) => {
+                for (language, entries) in overrides {
+                    texts
+                        .entry(language_code(&language))
+                        .or_default()
+                        .extend(entries);
+                }
+                info!("Loaded copy overrides from {}", path);
+            }
+            Err(e) => warn!("Ignoring COPY_PATH {}: {}", path, e),
+        }
+        texts
+    })
+}
+
+// loads the copy at startup so a broken COPY_PATH shows up in the logs right away
+pub fn init_copy() {
+    let languages = texts().len();
+    info!(
+        "Copy available in {} language(s), default {}",
+        languages,
+        default_language()
+    );
+}
+
+// the text in the requested language, then the default one, then english.
+// an unknown key comes back as is
+pub fn text(key: &str, language: Option<&str>) -> String {
+    let texts = texts();
+    let requested = language.map(language_code);
+    [requested, Some(default_language()), Some("en".to_string())]
+        .into_iter()
+        .flatten()
+        .find_map(|language| texts.get(&language).and_then(|entries| entries.get(key)))
+        .cloned()
+        .unwrap_or_else(|| key.to_string())
+}
+
+// `text` with {name} placeholders filled in
+pub fn render(key: &str, language: Option<&str>, args: &[(&str, &str)]) -> String {
+    args.iter()
+        .fold(text(key, language), |text, (name, value)| {
+            text.replace(&format!("{{{}}}", name), value)
+        })
+}
+
+// error messages follow the deployment's default language
+pub fn error(key: &str, args: &[(&str, &str)]) -> String {
+    render(key, None, args)
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct BrandingQuery {
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub conference: Option<String>,
+}
+
+// what a frontend shows before the first plan
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Branding {
+    pub language: String,
+    pub brand_name: String,
+    pub greeting: String,
+}
+
+pub fn branding(language: Option<&str>, conference: &str) -> Branding {
+    let brand_name = text("brand_name", language);
+    Branding {
+        language: language.map(language_code).unwrap_or_else(default_language),
+        greeting: render(
+            "greeting",
+            language,
+            &[("brand", brand_name.as_str()), ("conference", conference)],
+        ),
+        brand_name,
+    }
+}
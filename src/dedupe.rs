@@ -0,0 +1,70 @@
+// spot objectives a user already got a plan for
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+
+use crate::config;
+use crate::plans::PlanSummary;
+
+// filler words that say nothing about what the attendee wants
+const STOPWORDS: &[&str] = &[
+    "the", "and", "for", "with", "about", "that", "this", "what", "which", "should", "can", "are",
+    "want", "like", "some", "any", "please", "find", "show", "give", "plan",
+];
+
+#[derive(Debug)]
+pub struct EarlierPlan {
+    pub plan: PlanSummary,
+    pub similarity: f64,
+}
+
+// most similar recent plan for the same conference, if close enough
+pub fn find_earlier_plan(
+    objective: &str,
+    conference: &str,
+    history: Vec<PlanSummary>,
+) -> Option<EarlierPlan> {
+    let config = config::get();
+    let threshold = config.duplicate_similarity_threshold;
+    let cutoff = Utc::now() - chrono::Duration::hours(config.duplicate_window_hours);
+    let words = significant_words(objective);
+
+    history
+        .into_iter()
+        .filter(|plan| plan.conference == conference && plan.created_at >= cutoff)
+        .map(|plan| {
+            let similarity = jaccard(&words, &significant_words(&plan.objective));
+            EarlierPlan { plan, similarity }
+        })
+        .filter(|earlier| earlier.similarity >= threshold)
+        .max_by(|a, b| a.similarity.total_cmp(&b.similarity))
+}
+
+fn significant_words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .map(|word| word.to_lowercase())
+        .filter(|word| word.chars().count() > 2 && !STOPWORDS.contains(&word.as_str()))
+        .collect()
+}
+
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    if a.is_empty() && b.is_empty() {
+        return 0.0;
+    }
+    let shared = a.intersection(b).count();
+    shared as f64 / a.union(b).count() as f64
+}
+
+// "12 minutes", "3 hours", "2 days"
+pub fn describe_age(created_at: DateTime<Utc>) -> String {
+    let age = Utc::now() - created_at;
+    let (amount, unit) = if age.num_days() > 0 {
+        (age.num_days(), "day")
+    } else if age.num_hours() > 0 {
+        (age.num_hours(), "hour")
+    } else {
+        (age.num_minutes().max(1), "minute")
+    };
+    format!("{} {}{}", amount, unit, if amount == 1 { "" } else { "s" })
+}
@@ -0,0 +1,134 @@
+// retrieval-only answers while the llm provider's circuit is open: the objective goes
+// straight to the vivatech search and the hits are listed by urgency, no model involved
+
+use std::sync::Arc;
+
+use rig::tool::Tool;
+
+use crate::conference::ConferenceProfile;
+use crate::copy;
+use crate::health::{CapabilityMatrix, ToolHealth};
+use crate::llm::Provider;
+use crate::models::{ActionUrgency, ListedSession, PlanOutcome, VivatechSource};
+use crate::placeholders;
+use crate::tools::{
+    AssessTimeliness, AssessTimelinessArgs, QueryVivatechAPI, QueryVivatechArgs, SearchOutcome,
+};
+
+// reported as the plan's model so degraded answers stand out in logs and usage
+const RETRIEVAL_ONLY_MODEL: &str = "retrieval-only";
+
+// most sessions listed, a plan rarely fits more
+const MAX_LISTED: usize = 10;
+
+// any provider the request needs being down is enough, e.g. the polish stage,
+// unless a fallback model's provider is still up
+pub fn llm_down(health: &CapabilityMatrix, providers: &[Provider], fallbacks: &[Provider]) -> bool {
+    let down = |provider: &Provider| health.status(provider.circuit()) == ToolHealth::Down;
+    providers.iter().any(down) && fallbacks.iter().all(down)
+}
+
+fn urgency_rank(urgency: ActionUrgency) -> u8 {
+    match urgency {
+        ActionUrgency::Immediate => 0,
+        ActionUrgency::Soon => 1,
+        ActionUrgency::Normal => 2,
+    }
+}
+
+// the search hits for the objective, most urgent first and best matches first within
+// an urgency. a failed search is an Err, there is nothing left to answer with
+pub async fn retrieval_only(
+    profile: &ConferenceProfile,
+    health: &Arc<CapabilityMatrix>,
+    objective: &str,
+    language: Option<&str>,
+) -> Result<PlanOutcome, String> {
+    let search = QueryVivatechAPI::for_conference(profile).with_health(health.clone());
+    let args = QueryVivatechArgs::new(objective);
+    let sources = match search.call(args).await {
+        Ok(SearchOutcome::Sources(sources)) => sources,
+        Ok(SearchOutcome::Empty(_)) => Vec::new(),
+        Ok(SearchOutcome::Degraded { message, .. }) => {
+            return Err(copy::error("error.search_failed", &[("error", &message)]))
+        }
+        Err(e) => {
+            return Err(copy::error(
+                "error.search_failed",
+                &[("error", e.to_string().as_str())],
+            ))
+        }
+    };
+
+    let assessed = AssessTimeliness::for_conference(profile)
+        .call(AssessTimelinessArgs {
+            events: sources.clone(),
+        })
+        .await
+        .unwrap_or_default();
+    let mut listed: Vec<(ListedSession, f32)> = sources
+        .iter()
+        .zip(assessed)
+        .map(|(source, timeliness)| {
+            let session = ListedSession {
+                source_id: source.id.clone(),
+                summary: placeholders::summary(source),
+                urgency: timeliness.urgency,
+                timing: timeliness.description,
+            };
+            (session, source.score)
+        })
+        .collect();
+    listed.sort_by(|(a, a_score), (b, b_score)| {
+        urgency_rank(a.urgency)
+            .cmp(&urgency_rank(b.urgency))
+            .then(b_score.total_cmp(a_score))
+    });
+    let listing: Vec<ListedSession> = listed
+        .into_iter()
+        .take(MAX_LISTED)
+        .map(|(session, _)| session)
+        .collect();
+
+    let body = render(profile, &listing, language);
+    let sources: Vec<VivatechSource> = sources
+        .into_iter()
+        .filter(|source| listing.iter().any(|session| session.source_id == source.id))
+        .collect();
+    Ok(PlanOutcome {
+        body,
+        next_action: None,
+        itinerary: None,
+        sources,
+        model: RETRIEVAL_ONLY_MODEL.to_string(),
+        fallback_from: None,
+        listing: Some(listing),
+    })
+}
+
+fn render(
+    profile: &ConferenceProfile,
+    listing: &[ListedSession],
+    language: Option<&str>,
+) -> String {
+    let mut body = format!(
+        "## {}\n\n{}\n",
+        copy::render(
+            "degraded.heading",
+            language,
+            &[("conference", profile.name.as_str())]
+        ),
+        copy::text("degraded.notice", language)
+    );
+    if listing.is_empty() {
+        body.push('\n');
+        body.push_str(&copy::text("degraded.empty", language));
+        body.push('\n');
+        return body;
+    }
+    for session in listing {
+        body.push_str(&format!("\n- **{}** — {}", session.summary, session.timing));
+    }
+    body.push('\n');
+    body
+}
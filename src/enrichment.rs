@@ -0,0 +1,279 @@
+// derived facets (topics, difficulty, audience) classified once per session at sync time
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::config;
+use crate::llm::{self, LlmClient, ModelChoice};
+use crate::models::VivatechSource;
+
+static ENRICHER: OnceLock<SessionEnricher> = OnceLock::new();
+
+const CLASSIFICATION_INSTRUCTIONS: &str = "\
+    You tag conference sessions and partners with facets from a fixed taxonomy. \
+    Return one entry per input item, using its id exactly as given. \
+    topics: one to three that best describe the content, other only when nothing fits. \
+    difficulty: introductory for general talks, intermediate when some background is assumed, \
+    advanced for deep technical or expert panels. \
+    audience: who would get the most out of it, one to three.";
+
+// the taxonomy is closed so filters see the same values on every sync
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Topic {
+    Ai,
+    Climate,
+    Fintech,
+    Mobility,
+    Health,
+    Cybersecurity,
+    Startups,
+    Retail,
+    Media,
+    Web3,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Difficulty {
+    Introductory,
+    Intermediate,
+    Advanced,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Audience {
+    Founders,
+    Investors,
+    Executives,
+    Developers,
+    Researchers,
+    Policymakers,
+    General,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SessionFacets {
+    pub topics: Vec<Topic>,
+    pub difficulty: Difficulty,
+    pub audience: Vec<Audience>,
+}
+
+// what the classifier returns for one batch
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+struct FacetBatch {
+    sessions: Vec<ClassifiedSession>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+struct ClassifiedSession {
+    id: String,
+    facets: SessionFacets,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EnrichedSession {
+    pub id: String,
+    pub text_chunk: String,
+    pub facets: SessionFacets,
+}
+
+// every field is optional, a session must match all that are set
+#[derive(Debug, Default, Deserialize)]
+pub struct FacetQuery {
+    pub topic: Option<Topic>,
+    pub difficulty: Option<Difficulty>,
+    pub audience: Option<Audience>,
+    pub limit: Option<usize>,
+}
+
+impl FacetQuery {
+    fn matches(&self, facets: &SessionFacets) -> bool {
+        self.topic.map_or(true, |t| facets.topics.contains(&t))
+            && self.difficulty.map_or(true, |d| facets.difficulty == d)
+            && self.audience.map_or(true, |a| facets.audience.contains(&a))
+    }
+}
+
+// off by default, classification spends tokens on every new or edited session
+fn is_enrichment_enabled() -> bool {
+    config::env_var("CATALOG_ENRICHMENT_ENABLED")
+        .ok()
+        .and_then(|s| s.parse::<bool>().ok())
+        .unwrap_or(false)
+}
+
+// "provider:model" or a bare model for LLM_PROVIDER, defaults to a small model
+fn get_enrichment_model() -> Result<ModelChoice, String> {
+    let provider = llm::default_provider();
+    match config::env_var("CATALOG_ENRICHMENT_MODEL") {
+        Ok(spec) => ModelChoice::parse(&spec, provider),
+        Err(_) if provider == llm::Provider::OpenAI => ModelChoice::parse("gpt-4o-mini", provider),
+        Err(_) => Ok(ModelChoice::default_for(provider)),
+    }
+}
+
+// sessions per classification call
+fn get_batch_size() -> usize {
+    config::env_var("CATALOG_ENRICHMENT_BATCH_SIZE")
+        .ok()
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(20)
+        .max(1)
+}
+
+struct CachedFacets {
+    text_hash: u64,
+    session: EnrichedSession,
+}
+
+pub struct SessionEnricher {
+    client: LlmClient,
+    model: ModelChoice,
+    cache: Mutex<HashMap<String, CachedFacets>>,
+}
+
+impl SessionEnricher {
+    pub fn new(client: LlmClient, model: ModelChoice) -> Self {
+        Self {
+            client,
+            model,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // classify sources that are new or whose text changed, returns how many were tagged.
+    // a failed batch is logged and retried on the next sync
+    pub async fn enrich(&self, sources: &[VivatechSource]) -> usize {
+        let pending: Vec<&VivatechSource> = {
+            let cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+            sources
+                .iter()
+                .filter(|source| {
+                    cache.get(&source.id).map_or(true, |cached| {
+                        cached.text_hash != text_hash(&source.text_chunk)
+                    })
+                })
+                .collect()
+        };
+
+        let mut tagged = 0;
+        for batch in pending.chunks(get_batch_size()) {
+            match self.classify(batch).await {
+                Ok(classified) => tagged += self.store(batch, classified),
+                Err(e) => warn!("Session enrichment batch failed: {}", e),
+            }
+        }
+
+        // drop sessions that left the catalog, unless the pull looks like an upstream hiccup
+        let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+        if sources.len() >= cache.len() / 2 {
+            cache.retain(|id, _| sources.iter().any(|source| &source.id == id));
+        }
+
+        tagged
+    }
+
+    async fn classify(&self, batch: &[&VivatechSource]) -> Result<Vec<ClassifiedSession>, String> {
+        let mut input = String::from("Items:\n");
+        for source in batch {
+            input.push_str(&format!("- {}: {}\n", source.id, source.text_chunk));
+        }
+
+        self.client
+            .extract::<FacetBatch>(&self.model.model, CLASSIFICATION_INSTRUCTIONS, &input)
+            .await
+            .map(|result| result.sessions)
+            .map_err(|e| e.to_string())
+    }
+
+    // ids the model made up are ignored, missing ones stay pending
+    fn store(&self, batch: &[&VivatechSource], classified: Vec<ClassifiedSession>) -> usize {
+        let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut stored = 0;
+        for entry in classified {
+            let Some(source) = batch.iter().find(|source| source.id == entry.id) else {
+                continue;
+            };
+            if entry.facets.topics.is_empty() || entry.facets.audience.is_empty() {
+                continue;
+            }
+            cache.insert(
+                entry.id.clone(),
+                CachedFacets {
+                    text_hash: text_hash(&source.text_chunk),
+                    session: EnrichedSession {
+                        id: entry.id,
+                        text_chunk: source.text_chunk.clone(),
+                        facets: entry.facets,
+                    },
+                },
+            );
+            stored += 1;
+        }
+        stored
+    }
+
+    pub fn facets(&self, id: &str) -> Option<SessionFacets> {
+        let cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+        cache.get(id).map(|cached| cached.session.facets.clone())
+    }
+
+    pub fn filter(&self, query: &FacetQuery) -> Vec<EnrichedSession> {
+        let cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut sessions: Vec<EnrichedSession> = cache
+            .values()
+            .filter(|cached| query.matches(&cached.session.facets))
+            .map(|cached| cached.session.clone())
+            .collect();
+        sessions.sort_by(|a, b| a.id.cmp(&b.id));
+        sessions.truncate(query.limit.unwrap_or(50).min(500));
+        sessions
+    }
+}
+
+fn text_hash(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+// set up the classifier at startup, a missing key only disables enrichment
+pub fn init_session_enrichment() {
+    if !is_enrichment_enabled() {
+        info!("Session enrichment disabled via CATALOG_ENRICHMENT_ENABLED");
+        return;
+    }
+
+    let setup = get_enrichment_model().and_then(|model| {
+        LlmClient::from_env(model.provider).map(|client| SessionEnricher::new(client, model))
+    });
+    match setup {
+        Ok(enricher) => {
+            info!("Session enrichment enabled with {}", enricher.model);
+            let _ = ENRICHER.set(enricher);
+        }
+        Err(e) => warn!("Session enrichment unavailable: {}", e),
+    }
+}
+
+pub fn session_enricher() -> Option<&'static SessionEnricher> {
+    ENRICHER.get()
+}
+
+// attach known facets to search results so the agent can weigh them
+pub fn annotate(sources: &mut [VivatechSource]) {
+    if let Some(enricher) = session_enricher() {
+        for source in sources.iter_mut() {
+            source.facets = enricher.facets(&source.id);
+        }
+    }
+}
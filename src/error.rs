@@ -0,0 +1,143 @@
+// what handlers fail with. every error is answered with the same json envelope,
+// {"status": "error", "code", "message", "request_id"}, and a status that says who
+// is at fault, instead of a plain "Error: ..." string
+
+use std::time::Duration;
+
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+use crate::models::ErrorEnvelope;
+use crate::telemetry;
+use crate::tools::VivatechApiError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    // a missing or unusable setting, e.g. no key for the llm provider
+    #[error("{0}")]
+    Config(String),
+    // the vivatech api or another service we call
+    #[error("{0}")]
+    Upstream(String),
+    // the llm provider
+    #[error("{0}")]
+    Llm(String),
+    // the llm provider rate limited this call, answered 429 with its hint
+    #[error("{0}")]
+    LlmRateLimited(String, Duration),
+    #[error("{0}")]
+    Validation(String),
+    #[error("{0}")]
+    NotFound(String),
+    // a full queue or an open circuit, worth retrying later
+    #[error("{0}")]
+    Unavailable(String),
+    // the resource is not in a state to answer, e.g. an unfinished job
+    #[error("{0}")]
+    Conflict(String),
+    // our own storage
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl AppError {
+    // a failed agent run, 429 when the provider's back-off hint came with it
+    pub fn llm(message: String, retry_after: Option<Duration>) -> Self {
+        match retry_after {
+            Some(delay) => AppError::LlmRateLimited(message, delay),
+            None => AppError::Llm(message),
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::Config(_) | AppError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Upstream(_) => StatusCode::BAD_GATEWAY,
+            AppError::Llm(_) => StatusCode::BAD_GATEWAY,
+            AppError::LlmRateLimited(..) => StatusCode::TOO_MANY_REQUESTS,
+            AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Config(_) => "config_error",
+            AppError::Upstream(_) => "upstream_error",
+            AppError::Llm(_) | AppError::LlmRateLimited(..) => "llm_error",
+            AppError::Validation(_) => "validation_error",
+            AppError::NotFound(_) => "not_found",
+            AppError::Unavailable(_) => "unavailable",
+            AppError::Conflict(_) => "conflict",
+            AppError::Internal(_) => "internal_error",
+        }
+    }
+
+    // upstream back-off hints are passed on to our own clients
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            AppError::LlmRateLimited(_, delay) => Some(*delay),
+            _ => None,
+        }
+    }
+
+    pub fn envelope(&self) -> ErrorEnvelope {
+        envelope(self.code(), self.to_string())
+    }
+}
+
+impl From<VivatechApiError> for AppError {
+    fn from(e: VivatechApiError) -> Self {
+        AppError::Upstream(e.to_string())
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let response = respond(self.status(), self.envelope());
+        match self.retry_after() {
+            Some(delay) => (
+                [(header::RETRY_AFTER, delay.as_secs().max(1).to_string())],
+                response,
+            )
+                .into_response(),
+            None => response,
+        }
+    }
+}
+
+// with the id of the request being handled, the one in its X-Request-Id header
+pub fn envelope(code: &str, message: impl Into<String>) -> ErrorEnvelope {
+    ErrorEnvelope {
+        code: code.to_string(),
+        message: message.into(),
+        request_id: telemetry::current_request_id(),
+        reason: None,
+        plan_ids: None,
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    status: &'static str,
+    #[serde(flatten)]
+    error: ErrorEnvelope,
+}
+
+// also used by the errors that pick their own status and code, e.g. auth and webhooks
+pub fn respond(status: StatusCode, error: ErrorEnvelope) -> Response {
+    (
+        status,
+        Json(ErrorBody {
+            status: "error",
+            error,
+        }),
+    )
+        .into_response()
+}
@@ -0,0 +1,239 @@
+// a user's chats, plans and session feedback in one zip, export.json for machines
+// and export.md for people, for data portability requests and personal records
+
+use std::io::{Cursor, Write};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::copy;
+use crate::error::AppError;
+use crate::plans::{PlanStoreError, StoredExchange, StoredFeedback, StoredPlan};
+use crate::profiles::UserProfile;
+use crate::state::AppState;
+use crate::usage;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExportError {
+    #[error("Export encoding error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Export archive error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("Export archive error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ExportQuery {
+    // for the markdown headings
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportedExchange {
+    pub message: String,
+    pub reply: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// exchanges of one chat session, oldest first
+#[derive(Debug, Serialize)]
+pub struct ExportedSession {
+    pub session_id: String,
+    pub exchanges: Vec<ExportedExchange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConversationExport {
+    pub user_id: String,
+    pub exported_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<UserProfile>,
+    pub sessions: Vec<ExportedSession>,
+    pub plans: Vec<StoredPlan>,
+    pub feedback: Vec<StoredFeedback>,
+}
+
+impl ConversationExport {
+    pub fn collect(state: &AppState, user_id: &str) -> Result<Self, PlanStoreError> {
+        let owner = usage::current_api_key();
+        Ok(Self {
+            user_id: user_id.to_string(),
+            exported_at: Utc::now(),
+            profile: state.profiles.get(user_id),
+            sessions: group_sessions(state.plans.exchanges_for_user(user_id)?),
+            plans: state.plans.all_for_user(user_id, owner.as_deref())?,
+            feedback: state.plans.feedback_for_user(user_id)?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.profile.is_none()
+            && self.sessions.is_empty()
+            && self.plans.is_empty()
+            && self.feedback.is_empty()
+    }
+
+    // user ids are client supplied, only the safe characters make it into the name
+    pub fn file_name(&self) -> String {
+        let user: String = self
+            .user_id
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+            .take(64)
+            .collect();
+        format!(
+            "conversations-{}-{}.zip",
+            if user.is_empty() { "user" } else { &user },
+            self.exported_at.format("%Y%m%d")
+        )
+    }
+
+    pub fn to_archive(&self, language: Option<&str>) -> Result<Vec<u8>, ExportError> {
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+        archive.start_file("export.json", options)?;
+        archive.write_all(&serde_json::to_vec_pretty(self)?)?;
+        archive.start_file("export.md", options)?;
+        archive.write_all(self.to_markdown(language).as_bytes())?;
+        Ok(archive.finish()?.into_inner())
+    }
+
+    // the profile is only in export.json, it is settings rather than history
+    pub fn to_markdown(&self, language: Option<&str>) -> String {
+        let heading = |key: &str| copy::text(key, language);
+        let exported = self.exported_at.format("%Y-%m-%d %H:%M UTC").to_string();
+        let mut markdown = format!(
+            "# {}\n\n{}\n",
+            copy::render("export.title", language, &[("user", self.user_id.as_str())]),
+            copy::render("export.exported", language, &[("exported", &exported)])
+        );
+
+        markdown.push_str(&format!("\n## {}\n", heading("export.sessions")));
+        if self.sessions.is_empty() {
+            markdown.push_str(&format!("\n{}\n", heading("export.empty")));
+        }
+        for session in &self.sessions {
+            markdown.push_str(&format!("\n### {}\n", session.session_id));
+            for exchange in &session.exchanges {
+                markdown.push_str(&format!(
+                    "\n_{}_\n\n**{}** {}\n\n**{}** {}\n",
+                    exchange.created_at.format("%Y-%m-%d %H:%M"),
+                    heading("export.you"),
+                    exchange.message.trim(),
+                    heading("export.assistant"),
+                    exchange.reply.trim()
+                ));
+            }
+        }
+
+        markdown.push_str(&format!("\n## {}\n", heading("export.plans")));
+        if self.plans.is_empty() {
+            markdown.push_str(&format!("\n{}\n", heading("export.empty")));
+        }
+        for plan in &self.plans {
+            markdown.push_str(&format!(
+                "\n### {}\n\n_{} · {} · {}_\n\n{}\n",
+                plan.objective.trim(),
+                plan.created_at.format("%Y-%m-%d %H:%M"),
+                plan.conference,
+                plan.id,
+                plan.plan.trim()
+            ));
+        }
+
+        markdown.push_str(&format!("\n## {}\n\n", heading("export.feedback")));
+        if self.feedback.is_empty() {
+            markdown.push_str(&format!("{}\n", heading("export.empty")));
+        }
+        for entry in &self.feedback {
+            markdown.push_str(&format!("- **{}**", entry.session_id));
+            if let Some(rating) = entry.rating {
+                markdown.push_str(&format!(" {}/5", rating));
+            }
+            if let Some(comment) = &entry.comment {
+                markdown.push_str(&format!(" - {}", comment.trim()));
+            }
+            markdown.push('\n');
+        }
+
+        markdown
+    }
+}
+
+// sessions in the order they started, the user may have come back to an older one
+fn group_sessions(exchanges: Vec<StoredExchange>) -> Vec<ExportedSession> {
+    let mut sessions: Vec<ExportedSession> = Vec::new();
+    for exchange in exchanges {
+        let exported = ExportedExchange {
+            message: exchange.message,
+            reply: exchange.reply,
+            created_at: exchange.created_at,
+        };
+        match sessions
+            .iter_mut()
+            .find(|session| session.session_id == exchange.session_id)
+        {
+            Some(session) => session.exchanges.push(exported),
+            None => sessions.push(ExportedSession {
+                session_id: exchange.session_id,
+                exchanges: vec![exported],
+            }),
+        }
+    }
+    sessions
+}
+
+// everything stored about a user in one zip, for data portability requests
+pub async fn export_conversations_handler(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    Query(query): Query<ExportQuery>,
+) -> Response {
+    let export = match ConversationExport::collect(&state, &user_id) {
+        Ok(export) => export,
+        Err(e) => {
+            tracing::error!("Failed to collect export for {}: {}", user_id, e);
+            return AppError::Internal(copy::error("error.export_failed", &[])).into_response();
+        }
+    };
+    if export.is_empty() {
+        return AppError::NotFound(copy::error("error.nothing_to_export", &[])).into_response();
+    }
+
+    match export.to_archive(query.language.as_deref()) {
+        Ok(archive) => {
+            info!(
+                "Exported {} sessions, {} plans and {} feedback entries for {}",
+                export.sessions.len(),
+                export.plans.len(),
+                export.feedback.len(),
+                user_id
+            );
+            (
+                [
+                    (header::CONTENT_TYPE, "application/zip".to_string()),
+                    (
+                        header::CONTENT_DISPOSITION,
+                        format!("attachment; filename=\"{}\"", export.file_name()),
+                    ),
+                ],
+                archive,
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to build export for {}: {}", user_id, e);
+            AppError::Internal(copy::error("error.export_failed", &[])).into_response()
+        }
+    }
+}
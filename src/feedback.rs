@@ -0,0 +1,380 @@
+// ratings of whole plans, what attendees complain about most, and optionally those
+// complaints as things for the planner to avoid: an offline improvement loop without
+// retraining anything
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::conference::ConferenceProfile;
+use crate::config;
+use crate::plans::{PlanStoreError, StoredPlan, StoredPlanFeedback};
+use crate::state::AppState;
+
+const MAX_COMMENT_CHARS: usize = 1000;
+const RECENT_COMMENTS: usize = 20;
+// ratings up to this count as complaints
+const LOW_RATING: u8 = 3;
+
+// the planner prior looks at the last two weeks and names the top issues that enough
+// attendees raised, one complaint is an anecdote
+const PRIOR_WINDOW_DAYS: i64 = 14;
+const PRIOR_ISSUES: usize = 3;
+const PRIOR_MIN_REPORTS: u64 = 3;
+
+// what goes wrong with plans, picked by the attendee or read from their comment
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedbackIssue {
+    // no lunch or breathing room
+    NoBreaks,
+    // back-to-back sessions far apart
+    TooTight,
+    OffTopic,
+    Overlaps,
+    // sessions, speakers or times that aren't in the programme
+    MadeUp,
+    WrongLocation,
+    TooLong,
+}
+
+impl FeedbackIssue {
+    pub const ALL: [FeedbackIssue; 7] = [
+        FeedbackIssue::NoBreaks,
+        FeedbackIssue::TooTight,
+        FeedbackIssue::OffTopic,
+        FeedbackIssue::Overlaps,
+        FeedbackIssue::MadeUp,
+        FeedbackIssue::WrongLocation,
+        FeedbackIssue::TooLong,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FeedbackIssue::NoBreaks => "no_breaks",
+            FeedbackIssue::TooTight => "too_tight",
+            FeedbackIssue::OffTopic => "off_topic",
+            FeedbackIssue::Overlaps => "overlaps",
+            FeedbackIssue::MadeUp => "made_up",
+            FeedbackIssue::WrongLocation => "wrong_location",
+            FeedbackIssue::TooLong => "too_long",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|issue| issue.name() == name)
+    }
+
+    // lowercase phrases that give the issue away in a comment, english and french
+    fn keywords(self) -> &'static [&'static str] {
+        match self {
+            FeedbackIssue::NoBreaks => &[
+                "lunch",
+                "no break",
+                "no time to eat",
+                "pause",
+                "déjeuner",
+                "manger",
+            ],
+            FeedbackIssue::TooTight => &[
+                "too tight",
+                "no time to get",
+                "rush",
+                "back to back",
+                "back-to-back",
+                "across the venue",
+                "trop serré",
+            ],
+            FeedbackIssue::OffTopic => &[
+                "irrelevant",
+                "off topic",
+                "off-topic",
+                "not interested",
+                "not what i asked",
+                "hors sujet",
+            ],
+            FeedbackIssue::Overlaps => &["overlap", "clash", "same time", "en même temps"],
+            FeedbackIssue::MadeUp => &[
+                "doesn't exist",
+                "does not exist",
+                "made up",
+                "invented",
+                "not in the programme",
+                "not in the program",
+                "n'existe pas",
+            ],
+            FeedbackIssue::WrongLocation => &[
+                "wrong hall",
+                "wrong stage",
+                "wrong room",
+                "wrong location",
+                "mauvais hall",
+            ],
+            FeedbackIssue::TooLong => &["too long", "too much text", "too verbose", "trop long"],
+        }
+    }
+
+    // what the planner is told while the issue is among the top complaints
+    fn guidance(self) -> &'static str {
+        match self {
+            FeedbackIssue::NoBreaks => {
+                "Attendees say plans ignore lunch and breaks: keep a lunch break around \
+                midday and some free time in long days."
+            }
+            FeedbackIssue::TooTight => {
+                "Attendees say transitions are too tight: leave time to walk between halls \
+                instead of chaining sessions back to back."
+            }
+            FeedbackIssue::OffTopic => {
+                "Attendees say plans include sessions off their objective: only keep \
+                sessions that clearly serve it."
+            }
+            FeedbackIssue::Overlaps => {
+                "Attendees say plans contain overlapping sessions: check for conflicts \
+                before answering."
+            }
+            FeedbackIssue::MadeUp => {
+                "Attendees found sessions or details that are not in the programme: only \
+                use what the tools returned."
+            }
+            FeedbackIssue::WrongLocation => {
+                "Attendees found wrong halls or stages: copy locations exactly from the \
+                sources."
+            }
+            FeedbackIssue::TooLong => {
+                "Attendees find plans too long: keep each step to one or two lines."
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct PlanFeedbackRequest {
+    // 1 to 5
+    pub rating: u8,
+    #[serde(default)]
+    pub comment: Option<String>,
+    #[serde(default)]
+    pub issues: Vec<FeedbackIssue>,
+}
+
+impl PlanFeedbackRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=5).contains(&self.rating) {
+            return Err(format!(
+                "rating must be between 1 and 5, got {}",
+                self.rating
+            ));
+        }
+        if self
+            .comment
+            .as_ref()
+            .is_some_and(|comment| comment.chars().count() > MAX_COMMENT_CHARS)
+        {
+            return Err(format!(
+                "comment must be at most {} characters",
+                MAX_COMMENT_CHARS
+            ));
+        }
+        Ok(())
+    }
+
+    fn comment(&self) -> Option<&str> {
+        self.comment
+            .as_deref()
+            .map(str::trim)
+            .filter(|comment| !comment.is_empty())
+    }
+
+    // the picked issues plus those the comment gives away, each once
+    pub fn issues(&self) -> Vec<FeedbackIssue> {
+        let comment = self.comment().unwrap_or_default().to_lowercase();
+        FeedbackIssue::ALL
+            .into_iter()
+            .filter(|issue| {
+                self.issues.contains(issue)
+                    || issue
+                        .keywords()
+                        .iter()
+                        .any(|keyword| comment.contains(keyword))
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PlanFeedbackResponse {
+    pub plan_id: String,
+    pub rating: u8,
+    pub issues: Vec<FeedbackIssue>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct FeedbackQuery {
+    // the default conference when unset
+    #[serde(default)]
+    pub conference: Option<String>,
+    // only feedback from the last `hours`, all of it when unset
+    #[serde(default)]
+    pub hours: Option<i64>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct IssueCount {
+    pub issue: FeedbackIssue,
+    pub count: u64,
+    // of all rated plans
+    pub share: f64,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct FeedbackComment {
+    pub plan_id: String,
+    pub rating: u8,
+    pub comment: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct FeedbackSummary {
+    pub conference: String,
+    pub ratings: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub average_rating: Option<f64>,
+    // count per rating from 1 to 5
+    pub distribution: BTreeMap<u8, u64>,
+    // most raised first
+    pub issues: Vec<IssueCount>,
+    // comments of low ratings, newest first
+    pub recent_comments: Vec<FeedbackComment>,
+    // what the planner is told now, empty while FEEDBACK_PRIOR_ENABLED is off
+    pub planner_guidance: Vec<String>,
+}
+
+pub fn record(
+    state: &AppState,
+    plan: &StoredPlan,
+    request: &PlanFeedbackRequest,
+) -> Result<Vec<FeedbackIssue>, PlanStoreError> {
+    let issues = request.issues();
+    let names: Vec<String> = issues
+        .iter()
+        .map(|issue| issue.name().to_string())
+        .collect();
+    state
+        .plans
+        .record_plan_feedback(plan, request.rating, request.comment(), &names)?;
+    Ok(issues)
+}
+
+// issues by how often they were raised, most first
+fn issue_counts(feedback: &[StoredPlanFeedback]) -> Vec<(FeedbackIssue, u64)> {
+    let mut counts: BTreeMap<FeedbackIssue, u64> = BTreeMap::new();
+    for entry in feedback {
+        for issue in entry
+            .issues
+            .iter()
+            .filter_map(|name| FeedbackIssue::from_name(name))
+        {
+            *counts.entry(issue).or_default() += 1;
+        }
+    }
+    let mut counts: Vec<(FeedbackIssue, u64)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    counts
+}
+
+pub fn summary(
+    state: &AppState,
+    profile: &ConferenceProfile,
+    query: &FeedbackQuery,
+) -> Result<FeedbackSummary, PlanStoreError> {
+    let since = query
+        .hours
+        .filter(|hours| *hours > 0)
+        .map(|hours| Utc::now() - Duration::hours(hours));
+    let feedback = state.plans.plan_feedback(&profile.id, since)?;
+
+    let ratings = feedback.len() as u64;
+    let mut distribution: BTreeMap<u8, u64> = (1..=5).map(|rating| (rating, 0)).collect();
+    for entry in &feedback {
+        *distribution.entry(entry.rating).or_default() += 1;
+    }
+    let average_rating = (ratings > 0).then(|| {
+        feedback
+            .iter()
+            .map(|entry| f64::from(entry.rating))
+            .sum::<f64>()
+            / ratings as f64
+    });
+    let issues = issue_counts(&feedback)
+        .into_iter()
+        .map(|(issue, count)| IssueCount {
+            issue,
+            count,
+            share: count as f64 / ratings as f64,
+        })
+        .collect();
+    let recent_comments = feedback
+        .iter()
+        .filter(|entry| entry.rating <= LOW_RATING)
+        .filter_map(|entry| {
+            Some(FeedbackComment {
+                plan_id: entry.plan_id.clone(),
+                rating: entry.rating,
+                comment: entry.comment.clone()?,
+                created_at: entry.created_at,
+            })
+        })
+        .take(RECENT_COMMENTS)
+        .collect();
+
+    Ok(FeedbackSummary {
+        conference: profile.id.clone(),
+        ratings,
+        average_rating,
+        distribution,
+        issues,
+        recent_comments,
+        planner_guidance: guidance(state, profile),
+    })
+}
+
+// the top complaints of low ratings in the prior window, as sentences
+fn guidance(state: &AppState, profile: &ConferenceProfile) -> Vec<String> {
+    if !config::runtime().feedback_prior_enabled {
+        return Vec::new();
+    }
+    let since = Utc::now() - Duration::days(PRIOR_WINDOW_DAYS);
+    let feedback = match state.plans.plan_feedback(&profile.id, Some(since)) {
+        Ok(feedback) => feedback,
+        Err(e) => {
+            tracing::warn!("Feedback prior unavailable: {}", e);
+            return Vec::new();
+        }
+    };
+    let complaints: Vec<StoredPlanFeedback> = feedback
+        .into_iter()
+        .filter(|entry| entry.rating <= LOW_RATING)
+        .collect();
+    issue_counts(&complaints)
+        .into_iter()
+        .filter(|(_, count)| *count >= PRIOR_MIN_REPORTS)
+        .take(PRIOR_ISSUES)
+        .map(|(issue, _)| issue.guidance().to_string())
+        .collect()
+}
+
+// one line per top complaint for the planner preamble when FEEDBACK_PRIOR_ENABLED is
+// set, empty otherwise
+pub fn prior(state: &AppState, profile: &ConferenceProfile) -> String {
+    guidance(state, profile)
+        .iter()
+        .map(|line| format!("- {}", line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
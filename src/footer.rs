@@ -0,0 +1,119 @@
+// disclaimer footer appended to plans when they are answered, never part of the model output
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use tracing::warn;
+
+use crate::config;
+use crate::copy;
+use crate::plans::StoredPlan;
+use crate::state::AppState;
+
+const SEPARATOR: &str = "\n\n---\n";
+
+static TEMPLATES: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+// {snapshot}, {timezone} and {conference} are filled in per plan
+const DEFAULT_TEMPLATES: &[(&str, &str)] = &[
+    (
+        "en",
+        "Session data as of {snapshot} ({timezone}). Times and rooms can change, please verify them at the venue.",
+    ),
+    (
+        "fr",
+        "Données du programme au {snapshot} ({timezone}). Les horaires et les salles peuvent changer, vérifiez-les sur place.",
+    ),
+    (
+        "es",
+        "Datos del programa a {snapshot} ({timezone}). Los horarios y las salas pueden cambiar, compruébalos en el recinto.",
+    ),
+    (
+        "de",
+        "Programmdaten vom {snapshot} ({timezone}). Zeiten und Räume können sich ändern, bitte vor Ort prüfen.",
+    ),
+];
+
+// built-in templates, overridden or extended per language by PLAN_FOOTER_TEMPLATES
+fn templates() -> &'static HashMap<String, String> {
+    TEMPLATES.get_or_init(|| {
+        let mut templates: HashMap<String, String> = DEFAULT_TEMPLATES
+            .iter()
+            .map(|(language, template)| (language.to_string(), template.to_string()))
+            .collect();
+        if let Ok(raw) = config::env_var("PLAN_FOOTER_TEMPLATES") {
+            match serde_json::from_str::<HashMap<String, String>>(&raw) {
+                Ok(overrides) => {
+                    for (language, template) in overrides {
+                        templates.insert(language.to_lowercase(), template);
+                    }
+                }
+                Err(e) => warn!("Ignoring invalid PLAN_FOOTER_TEMPLATES: {}", e),
+            }
+        }
+        templates
+    })
+}
+
+// "fr", "fr-FR" and "French" all pick the french template, english otherwise
+fn template_for(language: Option<&str>) -> Option<&'static String> {
+    let templates = templates();
+    language
+        .map(copy::language_code)
+        .and_then(|key| templates.get(&key))
+        .or_else(|| templates.get("en"))
+}
+
+// footer for a plan of `conference` built from data fetched at `snapshot`,
+// None when disabled or the template for the language is empty
+pub fn render(
+    state: &AppState,
+    conference: &str,
+    language: Option<&str>,
+    snapshot: DateTime<Utc>,
+) -> Option<String> {
+    if !config::runtime().plan_footer_enabled {
+        return None;
+    }
+    let template = template_for(language)?;
+    if template.trim().is_empty() {
+        return None;
+    }
+
+    let profile = state.conferences.resolve(Some(conference)).ok();
+    let timezone = profile
+        .and_then(|profile| Tz::from_str(&profile.timezone).ok())
+        .unwrap_or(Tz::UTC);
+    Some(
+        template
+            .replace(
+                "{snapshot}",
+                &snapshot
+                    .with_timezone(&timezone)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string(),
+            )
+            .replace("{timezone}", timezone.name())
+            .replace(
+                "{conference}",
+                profile.map_or(conference, |profile| profile.name.as_str()),
+            ),
+    )
+}
+
+pub fn append(plan: &str, footer: Option<&str>) -> String {
+    match footer {
+        Some(footer) => format!("{}{}{}", plan.trim_end(), SEPARATOR, footer),
+        None => plan.to_string(),
+    }
+}
+
+// stored plans keep the language they were asked in and date from when they were made
+pub fn append_to_stored(state: &AppState, stored: &mut StoredPlan) {
+    let language = stored.request.get("language").and_then(|v| v.as_str());
+    let footer = render(state, &stored.conference, language, stored.created_at);
+    stored.plan = append(&stored.plan, footer.as_deref());
+}
@@ -0,0 +1,72 @@
+// two-stage generation: cheap draft, expensive polish
+
+use crate::config;
+use crate::llm::{self, ModelChoice, Provider};
+use tracing::info;
+
+// rough but stable, good enough for budget comparisons between stages
+const CHARS_PER_TOKEN: usize = 4;
+
+// small model that calls the tools and writes the draft
+pub fn get_draft_model() -> ModelChoice {
+    stage_model(config::get().draft_model.as_deref(), "gpt-4o-mini")
+}
+
+// large model that only sees the draft
+pub fn get_polish_model() -> ModelChoice {
+    stage_model(config::get().polish_model.as_deref(), "gpt-4o")
+}
+
+// "provider:model" or a bare model on the default provider, the defaults only make sense for openai
+fn stage_model(spec: Option<&str>, openai_default: &str) -> ModelChoice {
+    let provider = llm::default_provider();
+    spec.and_then(|spec| ModelChoice::parse(spec, provider).ok())
+        .unwrap_or_else(|| match provider {
+            Provider::OpenAI => ModelChoice {
+                provider,
+                model: openai_default.to_string(),
+            },
+            other => ModelChoice::default_for(other),
+        })
+}
+
+pub fn polish_prompt(objective: &str, draft: &str) -> String {
+    format!(
+        "Attendee objective:\n{}\n\nDraft plan:\n{}\n\nReturn the polished plan.",
+        objective, draft
+    )
+}
+
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+#[derive(Debug, Clone)]
+pub struct StageUsage {
+    pub stage: &'static str,
+    pub model: String,
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+}
+
+impl StageUsage {
+    // tool results are not visible to us, so draft prompt tokens are a lower bound
+    pub fn estimate(stage: &'static str, model: &str, prompt: &str, completion: &str) -> Self {
+        Self {
+            stage,
+            model: model.to_string(),
+            prompt_tokens: estimate_tokens(prompt),
+            completion_tokens: estimate_tokens(completion),
+        }
+    }
+
+    pub fn log(&self) {
+        info!(
+            stage = self.stage,
+            model = %self.model,
+            prompt_tokens = self.prompt_tokens as u64,
+            completion_tokens = self.completion_tokens as u64,
+            "Generation stage completed"
+        );
+    }
+}
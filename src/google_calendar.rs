@@ -0,0 +1,365 @@
+// pushes a stored plan's sessions into the attendee's google calendar. the frontend
+// runs the oauth consent for the calendar.events scope and sends us the access
+// token, which is used for this one sync and never stored
+
+use std::time::Duration;
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use reqwest::{Client, Url};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::conference::ConferenceProfile;
+use crate::error;
+use crate::placeholders;
+use crate::plans::StoredPlan;
+use crate::tools::{self, SessionSlot};
+
+const CALENDAR_API: &str = "https://www.googleapis.com/calendar/v3";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, thiserror::Error)]
+pub enum GoogleCalendarError {
+    #[error("missing google access token")]
+    MissingToken,
+    #[error("google rejected the access token")]
+    Unauthorized,
+    #[error("the access token does not allow editing this calendar")]
+    Forbidden,
+    #[error("unknown calendar '{0}'")]
+    UnknownCalendar(String),
+    #[error("google calendar answered {0}")]
+    Api(StatusCode),
+    #[error("failed to reach google calendar: {0}")]
+    Request(#[from] reqwest::Error),
+}
+
+impl IntoResponse for GoogleCalendarError {
+    fn into_response(self) -> Response {
+        let (status, code) = match self {
+            GoogleCalendarError::MissingToken => (StatusCode::BAD_REQUEST, "validation_error"),
+            GoogleCalendarError::Unauthorized => (StatusCode::UNAUTHORIZED, "invalid_google_token"),
+            GoogleCalendarError::Forbidden => (StatusCode::FORBIDDEN, "calendar_forbidden"),
+            GoogleCalendarError::UnknownCalendar(_) => (StatusCode::NOT_FOUND, "not_found"),
+            GoogleCalendarError::Api(_) | GoogleCalendarError::Request(_) => {
+                (StatusCode::BAD_GATEWAY, "upstream_error")
+            }
+        };
+        error::respond(status, error::envelope(code, self.to_string()))
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct GoogleCalendarSyncRequest {
+    // oauth access token with the calendar.events scope
+    pub access_token: String,
+    #[serde(default = "default_calendar")]
+    pub calendar_id: String,
+    // also add the sessions that overlap the attendee's own events
+    #[serde(default)]
+    pub include_conflicts: bool,
+}
+
+fn default_calendar() -> String {
+    "primary".to_string()
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct GoogleCalendarSyncResponse {
+    pub calendar_id: String,
+    pub created: Vec<SyncedEvent>,
+    // source ids added by an earlier sync of the same plan
+    pub already_synced: Vec<String>,
+    pub conflicts: Vec<CalendarConflict>,
+    // sources without a readable date and time, not added
+    pub unscheduled: Vec<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SyncedEvent {
+    pub source_id: String,
+    pub event_id: String,
+    pub title: String,
+    // venue local time
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub html_link: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct CalendarConflict {
+    pub source_id: String,
+    pub title: String,
+    // summary of the attendee's event, "(busy)" when it has none
+    pub event: String,
+    pub overlap_minutes: i64,
+    // false when the session was left out
+    pub added: bool,
+}
+
+// the parts of a calendar event the sync reads
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CalendarEvent {
+    id: String,
+    #[serde(default)]
+    summary: Option<String>,
+    #[serde(default)]
+    status: Option<String>,
+    // "transparent" events don't block time
+    #[serde(default)]
+    transparency: Option<String>,
+    #[serde(default)]
+    html_link: Option<String>,
+    #[serde(default)]
+    start: Option<EventTime>,
+    #[serde(default)]
+    end: Option<EventTime>,
+}
+
+// all-day events only have a date and are not checked for conflicts
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EventTime {
+    #[serde(default)]
+    date_time: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EventList {
+    #[serde(default)]
+    items: Vec<CalendarEvent>,
+    #[serde(default)]
+    next_page_token: Option<String>,
+}
+
+impl CalendarEvent {
+    fn busy_between(&self) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        if self.status.as_deref() == Some("cancelled")
+            || self.transparency.as_deref() == Some("transparent")
+        {
+            return None;
+        }
+        Some((
+            self.start.as_ref()?.date_time?,
+            self.end.as_ref()?.date_time?,
+        ))
+    }
+}
+
+// a plan session ready to be added
+struct PlannedEvent {
+    slot: SessionSlot,
+    title: String,
+    // deterministic, so syncing a plan twice doesn't add its sessions twice
+    event_id: String,
+    start_utc: DateTime<Utc>,
+    end_utc: DateTime<Utc>,
+}
+
+// google event ids use base32hex, the lowercase hex of a sha256 fits
+fn event_id(plan_id: &str, source_id: &str) -> String {
+    Sha256::digest(format!("{}:{}", plan_id, source_id).as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn to_utc(tz: Tz, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+    tz.from_local_datetime(&local)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+fn calendar_url(calendar_id: &str, path: &[&str]) -> Url {
+    let mut url = Url::parse(CALENDAR_API).expect("the calendar api url is valid");
+    url.path_segments_mut()
+        .expect("https urls have a path")
+        .push("calendars")
+        .push(calendar_id)
+        .extend(path);
+    url
+}
+
+fn check_status(
+    response: &reqwest::Response,
+    calendar_id: &str,
+) -> Result<(), GoogleCalendarError> {
+    match response.status() {
+        status if status.is_success() => Ok(()),
+        StatusCode::UNAUTHORIZED => Err(GoogleCalendarError::Unauthorized),
+        StatusCode::FORBIDDEN => Err(GoogleCalendarError::Forbidden),
+        StatusCode::NOT_FOUND => Err(GoogleCalendarError::UnknownCalendar(
+            calendar_id.to_string(),
+        )),
+        status => Err(GoogleCalendarError::Api(status)),
+    }
+}
+
+// the attendee's events between `from` and `to`, every page
+async fn list_events(
+    client: &Client,
+    request: &GoogleCalendarSyncRequest,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<CalendarEvent>, GoogleCalendarError> {
+    let mut events = Vec::new();
+    let mut page_token: Option<String> = None;
+    loop {
+        let mut url = calendar_url(&request.calendar_id, &["events"]);
+        url.query_pairs_mut()
+            .append_pair("timeMin", &from.to_rfc3339())
+            .append_pair("timeMax", &to.to_rfc3339())
+            .append_pair("singleEvents", "true")
+            .append_pair("maxResults", "250");
+        if let Some(token) = &page_token {
+            url.query_pairs_mut().append_pair("pageToken", token);
+        }
+        let response = client
+            .get(url)
+            .timeout(REQUEST_TIMEOUT)
+            .bearer_auth(&request.access_token)
+            .send()
+            .await?;
+        check_status(&response, &request.calendar_id)?;
+        let page = response.json::<EventList>().await?;
+        events.extend(page.items);
+        match page.next_page_token {
+            Some(token) => page_token = Some(token),
+            None => return Ok(events),
+        }
+    }
+}
+
+// None when an earlier sync already added it
+async fn insert_event(
+    client: &Client,
+    request: &GoogleCalendarSyncRequest,
+    event: &PlannedEvent,
+    tz: Tz,
+    conference: &str,
+) -> Result<Option<CalendarEvent>, GoogleCalendarError> {
+    let body = json!({
+        "id": event.event_id,
+        "summary": event.title,
+        "description": format!("Added from your {} plan", conference),
+        "start": {
+            "dateTime": event.slot.start.format("%Y-%m-%dT%H:%M:%S").to_string(),
+            "timeZone": tz.name(),
+        },
+        "end": {
+            "dateTime": event.slot.end.format("%Y-%m-%dT%H:%M:%S").to_string(),
+            "timeZone": tz.name(),
+        },
+    });
+    let response = client
+        .post(calendar_url(&request.calendar_id, &["events"]))
+        .timeout(REQUEST_TIMEOUT)
+        .bearer_auth(&request.access_token)
+        .json(&body)
+        .send()
+        .await?;
+    if response.status() == StatusCode::CONFLICT {
+        return Ok(None);
+    }
+    check_status(&response, &request.calendar_id)?;
+    Ok(Some(response.json::<CalendarEvent>().await?))
+}
+
+// one event per plan session with a date and start time, in the venue's timezone.
+// sessions overlapping the attendee's own busy events are reported and, unless
+// include_conflicts is set, left out
+pub async fn sync_plan(
+    client: &Client,
+    stored: &StoredPlan,
+    profile: &ConferenceProfile,
+    request: &GoogleCalendarSyncRequest,
+) -> Result<GoogleCalendarSyncResponse, GoogleCalendarError> {
+    if request.access_token.trim().is_empty() {
+        return Err(GoogleCalendarError::MissingToken);
+    }
+    let tz = profile.tz();
+    let mut report = GoogleCalendarSyncResponse {
+        calendar_id: request.calendar_id.clone(),
+        created: Vec::new(),
+        already_synced: Vec::new(),
+        conflicts: Vec::new(),
+        unscheduled: Vec::new(),
+    };
+
+    let mut planned = Vec::new();
+    for source in &stored.sources {
+        let slot = tools::session_slot(source, profile.year());
+        let times = slot
+            .as_ref()
+            .and_then(|slot| Some((to_utc(tz, slot.start)?, to_utc(tz, slot.end)?)));
+        match (slot, times) {
+            (Some(slot), Some((start_utc, end_utc))) => planned.push(PlannedEvent {
+                event_id: event_id(&stored.id, &source.id),
+                title: placeholders::summary(source),
+                slot,
+                start_utc,
+                end_utc,
+            }),
+            _ => report.unscheduled.push(source.id.clone()),
+        }
+    }
+    let (Some(from), Some(to)) = (
+        planned.iter().map(|event| event.start_utc).min(),
+        planned.iter().map(|event| event.end_utc).max(),
+    ) else {
+        return Ok(report);
+    };
+
+    let existing = list_events(client, request, from, to).await?;
+
+    for event in planned {
+        // our own events from an earlier sync are not conflicts
+        let conflict = existing
+            .iter()
+            .filter(|existing| existing.id != event.event_id)
+            .filter_map(|existing| {
+                let (start, end) = existing.busy_between()?;
+                let overlap = (end.min(event.end_utc) - start.max(event.start_utc)).num_minutes();
+                (overlap > 0).then_some((existing, overlap))
+            })
+            .max_by_key(|(_, overlap)| *overlap);
+        if let Some((existing, overlap_minutes)) = conflict {
+            report.conflicts.push(CalendarConflict {
+                source_id: event.slot.source_id.clone(),
+                title: event.title.clone(),
+                event: existing
+                    .summary
+                    .clone()
+                    .unwrap_or_else(|| "(busy)".to_string()),
+                overlap_minutes,
+                added: request.include_conflicts,
+            });
+            if !request.include_conflicts {
+                continue;
+            }
+        }
+
+        match insert_event(client, request, &event, tz, &profile.name).await? {
+            Some(created) => report.created.push(SyncedEvent {
+                source_id: event.slot.source_id,
+                event_id: created.id,
+                title: event.title,
+                start: event.slot.start,
+                end: event.slot.end,
+                html_link: created.html_link,
+            }),
+            None => report.already_synced.push(event.slot.source_id),
+        }
+    }
+    Ok(report)
+}
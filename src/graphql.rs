@@ -0,0 +1,645 @@
+// graphql next to the rest api, for consumers that prefer it. the resolvers go through
+// the functions behind the rest handlers, so plans, stored plans, search and streamed
+// progress behave, fail and are billed the same way
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use async_graphql::http::{playground_source, GraphQLPlaygroundConfig};
+use async_graphql::{
+    Context, Data, Enum, Error, ErrorExtensions, InputObject, Json, Object, Result, Schema,
+    SimpleObject, Subscription, Union,
+};
+use async_graphql_axum::{
+    GraphQLProtocol, GraphQLRequest, GraphQLResponse, GraphQLWebSocket, ALL_WEBSOCKET_PROTOCOLS,
+};
+use axum::{
+    extract::{State, WebSocketUpgrade},
+    http::StatusCode,
+    response::{Html, Response},
+};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use futures::{Stream, StreamExt};
+
+use crate::abuse::ClientId;
+use crate::error;
+use crate::footer;
+use crate::models::{ErrorEnvelope, GeneratePlanRequest, GeneratePlanResponse, VivatechSource};
+use crate::pipeline;
+use crate::plans::StoredPlan;
+use crate::programme::{Partner, Session};
+use crate::ratelimit;
+use crate::reply::PlanReply;
+use crate::state::AppState;
+use crate::streaming::StreamEvent;
+use crate::tools::QueryVivatechArgs;
+use crate::usage;
+
+// deep enough for a plan's sources and their sessions, no more
+const MAX_QUERY_DEPTH: usize = 8;
+// a field counts 1, so this bounds how many aliased fields one document can select
+const MAX_QUERY_COMPLEXITY: usize = 256;
+// planProgress streams one socket may run at once
+const MAX_SOCKET_SUBSCRIPTIONS: usize = 4;
+
+pub type VivaSchema = Schema<QueryRoot, MutationRoot, SubscriptionRoot>;
+
+static SCHEMA: OnceLock<VivaSchema> = OnceLock::new();
+
+// the state and the caller are added to every request, so the schema is built once
+pub fn schema() -> &'static VivaSchema {
+    SCHEMA.get_or_init(|| {
+        Schema::build(QueryRoot, MutationRoot, SubscriptionRoot)
+            .limit_depth(MAX_QUERY_DEPTH)
+            .limit_complexity(MAX_QUERY_COMPLEXITY)
+            .finish()
+    })
+}
+
+// who is asking, for the abuse heuristics and the rate limiter
+struct Client {
+    id: String,
+    // the http request or socket upgrade already took a token, which covers the first
+    // search, plan or subscription it runs. aliases and further ones each take their own
+    prepaid: AtomicBool,
+}
+
+impl Client {
+    fn new(id: String) -> Self {
+        Client {
+            id,
+            prepaid: AtomicBool::new(true),
+        }
+    }
+
+    // one rate limiter token per resolver run, as if it were its own rest request
+    fn charge(&self, state: &AppState, field: &str) -> Result<()> {
+        if self.prepaid.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+        ratelimit::charge(state, &self.id, &format!("/graphql:{}", field)).map_err(|delay| {
+            let seconds = ratelimit::retry_after_seconds(delay);
+            rejected(
+                StatusCode::TOO_MANY_REQUESTS,
+                Some(Duration::from_secs(seconds)),
+                ratelimit::rejection_envelope(seconds),
+            )
+        })
+    }
+}
+
+// planProgress streams open on one socket
+#[derive(Default)]
+struct SocketSubscriptions(Arc<AtomicUsize>);
+
+// held by a running subscription, frees its place when the stream is dropped
+struct SubscriptionSlot(Arc<AtomicUsize>);
+
+impl SocketSubscriptions {
+    fn acquire(&self) -> Result<SubscriptionSlot> {
+        self.0
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |open| {
+                (open < MAX_SOCKET_SUBSCRIPTIONS).then_some(open + 1)
+            })
+            .map_err(|_| {
+                rejected(
+                    StatusCode::TOO_MANY_REQUESTS,
+                    None,
+                    error::envelope(
+                        "too_many_subscriptions",
+                        format!(
+                            "at most {} subscriptions may run on one socket",
+                            MAX_SOCKET_SUBSCRIPTIONS
+                        ),
+                    ),
+                )
+            })?;
+        Ok(SubscriptionSlot(self.0.clone()))
+    }
+}
+
+impl Drop for SubscriptionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+// a rejection of the rest handlers as a graphql error, with the envelope's code
+fn rejected(status: StatusCode, retry_after: Option<Duration>, error: ErrorEnvelope) -> Error {
+    Error::new(error.message.clone()).extend_with(|_, extensions| {
+        extensions.set("code", error.code.clone());
+        extensions.set("status", status.as_u16());
+        if let Some(reason) = &error.reason {
+            extensions.set("reason", reason.clone());
+        }
+        if let Some(request_id) = &error.request_id {
+            extensions.set("requestId", request_id.clone());
+        }
+        if let Some(delay) = retry_after {
+            extensions.set("retryAfterSeconds", delay.as_secs());
+        }
+    })
+}
+
+fn rejection(reply: PlanReply) -> Error {
+    match reply.body {
+        GeneratePlanResponse::Error { error, .. } => {
+            rejected(reply.status, reply.retry_after, error)
+        }
+        _ => Error::new("the request was rejected"),
+    }
+}
+
+// the request fields of POST /generate-plan, validated the same way
+#[derive(InputObject)]
+pub struct PlanInput {
+    pub objective: String,
+    #[graphql(default)]
+    pub mode: PlanMode,
+    // the default conference when unset
+    pub conference: Option<String>,
+    pub user_id: Option<String>,
+    #[graphql(default)]
+    pub strategy: PlanStrategy,
+    // overrides the user's preferred tone
+    pub tone: Option<PlanTone>,
+    // "provider:model"
+    pub model: Option<String>,
+    // 0 to 2, PLAN_TEMPERATURE when unset
+    pub temperature: Option<f64>,
+    // up to PLAN_MAX_TOKENS_LIMIT, PLAN_MAX_TOKENS when unset
+    pub max_tokens: Option<u64>,
+    // detected from the objective when unset
+    pub language: Option<String>,
+    #[graphql(default)]
+    pub include_citations: bool,
+    // e.g. {"team": "sales"}, to group token spend for chargeback
+    pub cost_tags: Option<Json<BTreeMap<String, String>>>,
+    // a new plan even when the user just got one for nearly the same objective
+    #[graphql(default)]
+    pub regenerate: bool,
+    // always run the agent, even when the plan cache holds this objective
+    #[graphql(default)]
+    pub bypass_cache: bool,
+}
+
+impl PlanInput {
+    fn request(self) -> GeneratePlanRequest {
+        GeneratePlanRequest {
+            mode: self.mode.into(),
+            user_id: self.user_id,
+            strategy: self.strategy.into(),
+            tone: self.tone.map(Into::into),
+            model: self.model,
+            temperature: self.temperature,
+            max_tokens: self.max_tokens,
+            language: self.language,
+            include_citations: self.include_citations,
+            cost_tags: self.cost_tags.map(|tags| tags.0).unwrap_or_default(),
+            regenerate: self.regenerate,
+            bypass_cache: self.bypass_cache,
+            ..GeneratePlanRequest::simple(self.objective, self.conference)
+        }
+    }
+}
+
+#[derive(Enum, Clone, Copy, Default, PartialEq, Eq)]
+#[graphql(remote = "crate::models::OutputMode")]
+pub enum PlanMode {
+    #[default]
+    Plan,
+    NextAction,
+    Itinerary,
+}
+
+#[derive(Enum, Clone, Copy, Default, PartialEq, Eq)]
+#[graphql(remote = "crate::models::GenerationStrategy")]
+pub enum PlanStrategy {
+    #[default]
+    Standard,
+    TwoStage,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(remote = "crate::tone::Tone")]
+pub enum PlanTone {
+    ConciseBullet,
+    ExecutiveBrief,
+    EnthusiasticGuide,
+}
+
+#[derive(SimpleObject)]
+pub struct GeneratedPlan {
+    // unset when the plan could not be stored
+    pub plan_id: Option<String>,
+    pub plan: String,
+    pub sources_used: Vec<String>,
+    pub model: String,
+    pub fallback_from: Option<String>,
+    // the llm was unavailable, the plan is a listing of search hits
+    pub degraded: bool,
+    // as in the rest response, for the next_action and itinerary modes
+    pub next_action: Option<Json<serde_json::Value>>,
+    pub itinerary: Option<Json<serde_json::Value>>,
+    // as in the rest response
+    pub citations: Option<Json<serde_json::Value>>,
+    pub elapsed_ms: u64,
+}
+
+// the user recently got a plan for nearly the same objective
+#[derive(SimpleObject)]
+pub struct DuplicatePlan {
+    pub earlier_plan_id: String,
+    pub earlier_objective: String,
+    pub age_seconds: i64,
+    pub similarity: f64,
+    pub hint: String,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Union)]
+pub enum PlanResult {
+    Plan(GeneratedPlan),
+    Duplicate(DuplicatePlan),
+}
+
+#[derive(SimpleObject)]
+pub struct SessionInfo {
+    pub title: String,
+    pub speakers: Vec<String>,
+    pub stage: Option<String>,
+    // venue local time
+    pub start: Option<NaiveDateTime>,
+    pub end: Option<NaiveDateTime>,
+    // e.g. "climate_tech"
+    pub track: Option<String>,
+    pub days: Vec<NaiveDate>,
+}
+
+impl From<Session> for SessionInfo {
+    fn from(session: Session) -> Self {
+        SessionInfo {
+            title: session.title,
+            speakers: session.speakers,
+            stage: session.stage,
+            start: session.start,
+            end: session.end,
+            track: session
+                .track
+                .and_then(|track| serde_json::to_value(track).ok())
+                .and_then(|track| track.as_str().map(str::to_string)),
+            days: session.days,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct PartnerInfo {
+    pub name: String,
+    pub hall: Option<String>,
+    pub booth: Option<String>,
+    pub sector: Option<String>,
+}
+
+impl From<Partner> for PartnerInfo {
+    fn from(partner: Partner) -> Self {
+        PartnerInfo {
+            name: partner.name,
+            hall: partner.hall,
+            booth: partner.booth,
+            sector: partner.sector,
+        }
+    }
+}
+
+pub struct Source {
+    source: VivatechSource,
+    // for dates without one
+    year: i32,
+}
+
+#[Object]
+impl Source {
+    async fn id(&self) -> &str {
+        &self.source.id
+    }
+
+    // "sessions", "partners" or "speakers"
+    async fn source_table(&self) -> &str {
+        &self.source.source_table
+    }
+
+    async fn score(&self) -> f32 {
+        self.source.score
+    }
+
+    async fn text(&self) -> &str {
+        &self.source.text_chunk
+    }
+
+    // unset for partners and speakers
+    async fn session(&self) -> Option<SessionInfo> {
+        let table = self.source.source_table.as_str();
+        (table != "partners" && table != "speakers")
+            .then(|| Session::from_source(&self.source, self.year).into())
+    }
+
+    // unset for everything but partners
+    async fn partner(&self) -> Option<PartnerInfo> {
+        (self.source.source_table == "partners").then(|| Partner::from_source(&self.source).into())
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct Plan {
+    pub id: String,
+    pub user_id: Option<String>,
+    pub conference: String,
+    pub objective: String,
+    pub plan: String,
+    pub model: String,
+    pub created_at: DateTime<Utc>,
+    pub sources: Vec<Source>,
+}
+
+impl Plan {
+    fn new(stored: StoredPlan, year: i32) -> Self {
+        Plan {
+            sources: stored
+                .sources
+                .into_iter()
+                .map(|source| Source { source, year })
+                .collect(),
+            id: stored.id,
+            user_id: stored.user_id,
+            conference: stored.conference,
+            objective: stored.objective,
+            plan: stored.plan,
+            model: stored.model,
+            created_at: stored.created_at,
+        }
+    }
+}
+
+// one event of a streamed run, the fields its kind has are set
+#[derive(SimpleObject)]
+pub struct PlanEvent {
+    // token, tool_call, tool_result, progress, done or error
+    pub kind: String,
+    // the token's text
+    pub text: Option<String>,
+    pub tool: Option<String>,
+    // tool call arguments, progress and error messages
+    pub message: Option<String>,
+    pub ok: Option<bool>,
+    // the whole plan and its id once stored, on done
+    pub plan: Option<String>,
+    pub plan_id: Option<String>,
+    pub code: Option<String>,
+    pub retryable: Option<bool>,
+}
+
+impl PlanEvent {
+    fn of(kind: &str) -> Self {
+        PlanEvent {
+            kind: kind.to_string(),
+            text: None,
+            tool: None,
+            message: None,
+            ok: None,
+            plan: None,
+            plan_id: None,
+            code: None,
+            retryable: None,
+        }
+    }
+}
+
+impl From<StreamEvent> for PlanEvent {
+    fn from(event: StreamEvent) -> Self {
+        match event {
+            StreamEvent::Token { text } => PlanEvent {
+                text: Some(text),
+                ..PlanEvent::of("token")
+            },
+            StreamEvent::ToolCall { name, arguments } => PlanEvent {
+                tool: Some(name),
+                message: Some(arguments),
+                ..PlanEvent::of("tool_call")
+            },
+            StreamEvent::ToolResult { name, ok, .. } => PlanEvent {
+                tool: Some(name),
+                ok: Some(ok),
+                ..PlanEvent::of("tool_result")
+            },
+            StreamEvent::Progress { tool, message } => PlanEvent {
+                tool: Some(tool),
+                message: Some(message),
+                ..PlanEvent::of("progress")
+            },
+            StreamEvent::Done { plan, plan_id } => PlanEvent {
+                plan: Some(plan),
+                plan_id,
+                ..PlanEvent::of("done")
+            },
+            StreamEvent::Error {
+                code,
+                message,
+                retryable,
+                ..
+            } => PlanEvent {
+                code: serde_json::to_value(code)
+                    .ok()
+                    .and_then(|code| code.as_str().map(str::to_string)),
+                message: Some(message),
+                retryable: Some(retryable),
+                ..PlanEvent::of("error")
+            },
+        }
+    }
+}
+
+fn conference_year(state: &AppState, conference: Option<&str>) -> i32 {
+    state
+        .conferences
+        .resolve(conference)
+        .unwrap_or_else(|_| state.conferences.default_profile())
+        .year()
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    // a stored plan, as GET /plans/{id}
+    async fn plan(&self, ctx: &Context<'_>, id: String) -> Result<Plan> {
+        let state = ctx.data::<AppState>()?;
+        let mut stored = pipeline::load_plan(state, &id, Instant::now()).map_err(rejection)?;
+        footer::append_to_stored(state, &mut stored);
+        let year = conference_year(state, Some(&stored.conference));
+        Ok(Plan::new(stored, year))
+    }
+
+    // sessions and partners matching the query, best first
+    async fn sessions(
+        &self,
+        ctx: &Context<'_>,
+        query: String,
+        conference: Option<String>,
+        top_k: Option<usize>,
+        min_score: Option<f32>,
+    ) -> Result<Vec<Source>> {
+        let state = ctx.data::<AppState>()?;
+        let client = ctx.data::<Client>()?;
+        if let Some(reply) = pipeline::abuse_rejection(state.abuse.check(&client.id, &query)) {
+            return Err(rejection(reply));
+        }
+        client.charge(state, "sessions")?;
+        let year = conference_year(state, conference.as_deref());
+        let args = QueryVivatechArgs {
+            query,
+            top_k,
+            min_score,
+        };
+        let sources = pipeline::search_sessions(state, conference.as_deref(), args, Instant::now())
+            .await
+            .map_err(rejection)?;
+        Ok(sources
+            .into_iter()
+            .map(|source| Source { source, year })
+            .collect())
+    }
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    // as POST /generate-plan
+    async fn generate_plan(&self, ctx: &Context<'_>, input: PlanInput) -> Result<PlanResult> {
+        let state = ctx.data::<AppState>()?;
+        let client = ctx.data::<Client>()?;
+        client.charge(state, "generatePlan")?;
+        let reply = pipeline::run_plan_request(state, &client.id, input.request()).await;
+        match reply.body {
+            GeneratePlanResponse::Success {
+                plan_id,
+                plan,
+                next_action,
+                itinerary,
+                citations,
+                degraded,
+                sources_used,
+                model,
+                fallback_from,
+                elapsed_ms,
+                ..
+            } => Ok(PlanResult::Plan(GeneratedPlan {
+                plan_id,
+                plan,
+                sources_used,
+                model,
+                fallback_from,
+                degraded,
+                next_action: next_action
+                    .and_then(|action| serde_json::to_value(action).ok())
+                    .map(Json),
+                itinerary: itinerary
+                    .and_then(|itinerary| serde_json::to_value(itinerary).ok())
+                    .map(Json),
+                citations: citations
+                    .and_then(|citations| serde_json::to_value(citations).ok())
+                    .map(Json),
+                elapsed_ms,
+            })),
+            GeneratePlanResponse::Duplicate {
+                earlier_plan_id,
+                earlier_objective,
+                age_seconds,
+                similarity,
+                hint,
+                elapsed_ms,
+            } => Ok(PlanResult::Duplicate(DuplicatePlan {
+                earlier_plan_id,
+                earlier_objective,
+                age_seconds,
+                similarity,
+                hint,
+                elapsed_ms,
+            })),
+            GeneratePlanResponse::Error { error, .. } => {
+                Err(rejected(reply.status, reply.retry_after, error))
+            }
+        }
+    }
+}
+
+pub struct SubscriptionRoot;
+
+#[Subscription]
+impl SubscriptionRoot {
+    // a new plan's progress, the events of POST /generate-plan/stream
+    async fn plan_progress(
+        &self,
+        ctx: &Context<'_>,
+        input: PlanInput,
+    ) -> Result<impl Stream<Item = PlanEvent>> {
+        let state = ctx.data::<AppState>()?;
+        let client = ctx.data::<Client>()?;
+        let slot = ctx.data::<SocketSubscriptions>()?.acquire()?;
+        client.charge(state, "planProgress")?;
+        let events = pipeline::plan_events(state, &client.id, input.request(), Instant::now())
+            .await
+            .map_err(rejection)?;
+        Ok(events.map(move |event| {
+            let _held = &slot;
+            PlanEvent::from(event)
+        }))
+    }
+}
+
+// queries and mutations over POST, behind the api key like the rest routes
+pub async fn graphql_handler(
+    State(state): State<AppState>,
+    ClientId(client): ClientId,
+    request: GraphQLRequest,
+) -> GraphQLResponse {
+    let request = request.into_inner().data(state).data(Client::new(client));
+    schema().execute(request).await.into()
+}
+
+// subscriptions over a websocket, graphql-transport-ws or the older graphql-ws
+pub async fn graphql_ws_handler(
+    State(state): State<AppState>,
+    ClientId(client): ClientId,
+    protocol: GraphQLProtocol,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    let client = Client::new(client);
+    // subscriptions are billed to the key that opened the socket, as on /ws
+    let api_key = usage::current_api_key();
+    upgrade
+        .protocols(ALL_WEBSOCKET_PROTOCOLS)
+        .on_upgrade(move |socket| async move {
+            let mut data = Data::default();
+            data.insert(state);
+            data.insert(client);
+            data.insert(SocketSubscriptions::default());
+            let session = GraphQLWebSocket::new(socket, schema().clone(), protocol)
+                .with_data(data)
+                .serve();
+            match api_key {
+                Some(api_key) => usage::for_api_key(api_key, session).await,
+                None => session.await,
+            }
+        })
+}
+
+// an in-browser playground, the api key goes in its http headers
+pub async fn playground_handler() -> Html<String> {
+    Html(playground_source(
+        GraphQLPlaygroundConfig::new("/graphql").subscription_endpoint("/graphql/ws"),
+    ))
+}
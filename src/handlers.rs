@@ -0,0 +1,709 @@
+// the rest endpoints for plans, their stored copies and the catalog. each one is a
+// thin layer over the pipeline, the stores on AppState or a catalog helper
+use std::convert::Infallible;
+use std::time::Instant;
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Json,
+};
+use futures::StreamExt;
+use tracing::info;
+
+use crate::abuse::ClientId;
+use crate::agents::{build_explain_agent, build_planning_agent};
+use crate::citations::SourceCollector;
+use crate::compare::{ComparePlansRequest, PlanVariant};
+use crate::error::AppError;
+use crate::feedback::{PlanFeedbackRequest, PlanFeedbackResponse};
+use crate::google_calendar::GoogleCalendarSyncRequest;
+use crate::models::{
+    BatchPlanItem, BatchPlanRequest, CatalogChangesQuery, GeneratePlanRequest,
+    GeneratePlanResponse, IcalExportRequest, PlanOutcome, TemplatePlanRequest,
+};
+use crate::pipeline::{
+    abuse_rejection, generation_params, initialize_llm, load_plan, moderation_rejection,
+    plan_events, resolve_tone, resolve_user_context, run_plan_request,
+};
+use crate::planner::{prompt_with_fallback, prompt_with_retry};
+use crate::plans::{ExplainResponse, PlanListQuery};
+use crate::refine::{RefinePlanRequest, RefinePlanResponse};
+use crate::render::{FormatQuery, PlanDocument};
+use crate::reply::{elapsed_ms, plan_error, PlanReply};
+use crate::state::AppState;
+use crate::{
+    catalog_sync, citations, compare, copy, enrichment, feedback, footer, google_calendar, mcp,
+    popularity, query_cache, refine, render, telemetry, templates, tools, usage,
+};
+
+// main api endpoint
+pub async fn generate_plan_handler(
+    State(state): State<AppState>,
+    ClientId(client): ClientId,
+    Json(payload): Json<GeneratePlanRequest>,
+) -> PlanReply {
+    run_plan_request(&state, &client, payload).await
+}
+
+// mcp over http for agent hosts: one json-rpc message per POST, answered as json.
+// notifications get 202 with no body
+pub async fn mcp_handler(
+    State(state): State<AppState>,
+    Query(query): Query<mcp::McpQuery>,
+    body: String,
+) -> Response {
+    let profile = match state.conferences.resolve(query.conference.as_deref()) {
+        Ok(profile) => profile,
+        Err(e) => return AppError::Validation(e).into_response(),
+    };
+    let server = mcp::McpServer::for_conference(profile).with_health(state.health.clone());
+    match server.handle_message(&body).await {
+        Some(reply) => Json(reply).into_response(),
+        None => StatusCode::ACCEPTED.into_response(),
+    }
+}
+
+// brand name and greeting for white-label frontends, in the requested language
+pub async fn branding_handler(
+    State(state): State<AppState>,
+    Query(query): Query<copy::BrandingQuery>,
+) -> Response {
+    match state.conferences.resolve(query.conference.as_deref()) {
+        Ok(profile) => {
+            Json(copy::branding(query.language.as_deref(), &profile.name)).into_response()
+        }
+        Err(e) => AppError::Validation(e).into_response(),
+    }
+}
+
+// curated templates for common asks
+pub async fn list_templates_handler() -> Json<Vec<templates::ObjectiveTemplate>> {
+    Json(templates::builtin_templates())
+}
+
+// fill a template and run it through the normal planner
+pub async fn generate_plan_from_template_handler(
+    State(state): State<AppState>,
+    ClientId(client): ClientId,
+    Json(payload): Json<TemplatePlanRequest>,
+) -> PlanReply {
+    let started = Instant::now();
+    let Some(template) = templates::find_template(&payload.template_id) else {
+        return plan_error(
+            AppError::NotFound(copy::error(
+                "error.unknown_template",
+                &[("template", payload.template_id.as_str())],
+            )),
+            started,
+        );
+    };
+
+    let objective = match template.render(&payload.parameters) {
+        Ok(objective) => objective,
+        Err(e) => return plan_error(AppError::Validation(e), started),
+    };
+
+    info!("Rendered template {} into objective", template.id);
+    match payload.into_plan_request(objective) {
+        Ok(request) => run_plan_request(&state, &client, request).await,
+        Err(e) => plan_error(AppError::Validation(e), started),
+    }
+}
+
+// several objectives in one call, NDJSON lines as each one finishes when the client accepts it
+pub async fn generate_plans_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    ClientId(client): ClientId,
+    Json(batch): Json<BatchPlanRequest>,
+) -> Response {
+    let max_requests = state.config.batch_max_requests;
+    if batch.requests.is_empty() || batch.requests.len() > max_requests {
+        return plan_error(
+            AppError::Validation(copy::error(
+                "error.batch_size",
+                &[("max", max_requests.to_string().as_str())],
+            )),
+            Instant::now(),
+        )
+        .into_response();
+    }
+
+    // the route charged the first plan
+    let runs = batch.requests.len() as u32 - 1;
+    if let Err(retry_after) = ratelimit::charge_runs(&state, &client, "/generate-plans", runs) {
+        return ratelimit::rejection(retry_after);
+    }
+
+    info!(
+        "Received batch of {} planning requests",
+        batch.requests.len()
+    );
+    let concurrency = state.config.batch_concurrency;
+    let results = futures::stream::iter(batch.requests.into_iter().enumerate())
+        .map(move |(index, request)| {
+            let state = state.clone();
+            let client = client.clone();
+            async move {
+                let reply = run_plan_request(&state, &client, request).await;
+                BatchPlanItem {
+                    index,
+                    http_status: reply.status.as_u16(),
+                    response: reply.body,
+                }
+            }
+        })
+        .buffer_unordered(concurrency);
+
+    if accepts_ndjson(&headers) {
+        let lines = results.map(|item| {
+            serde_json::to_vec(&item).map(|mut line| {
+                line.push(b'\n');
+                line
+            })
+        });
+        return (
+            [(header::CONTENT_TYPE, "application/x-ndjson")],
+            Body::from_stream(lines),
+        )
+            .into_response();
+    }
+
+    let mut items: Vec<BatchPlanItem> = results.collect().await;
+    items.sort_by_key(|item| item.index);
+    Json(items).into_response()
+}
+
+// plans for two alternative objectives generated side by side, answered as one comparison
+pub async fn compare_plans_handler(
+    State(state): State<AppState>,
+    ClientId(client): ClientId,
+    Json(request): Json<ComparePlansRequest>,
+) -> Response {
+    let started = Instant::now();
+    if let Err(e) = request.validate() {
+        return plan_error(AppError::Validation(e), started).into_response();
+    }
+    let year = match state.conferences.resolve(request.conference.as_deref()) {
+        Ok(profile) => profile.year(),
+        Err(e) => return plan_error(AppError::Validation(e), started).into_response(),
+    };
+    // the route charged plan a
+    if let Err(retry_after) = ratelimit::charge(&state, &client, "/compare-plans") {
+        return ratelimit::rejection(retry_after);
+    }
+
+    info!(
+        "Comparing plans for \"{}\" and \"{}\"",
+        request.objective_a, request.objective_b
+    );
+    let (reply_a, reply_b) = tokio::join!(
+        run_plan_request(&state, &client, request.plan_request(&request.objective_a)),
+        run_plan_request(&state, &client, request.plan_request(&request.objective_b)),
+    );
+
+    // either plan failing fails the comparison with that plan's status
+    let a = match compared_plan(&state, &request.objective_a, reply_a) {
+        Ok(variant) => variant,
+        Err(reply) => return reply.into_response(),
+    };
+    let b = match compared_plan(&state, &request.objective_b, reply_b) {
+        Ok(variant) => variant,
+        Err(reply) => return reply.into_response(),
+    };
+    Json(compare::compare(a, b, year, elapsed_ms(started))).into_response()
+}
+
+// the stored plan keeps the sources the comparison needs for titles and slots
+fn compared_plan(
+    state: &AppState,
+    objective: &str,
+    reply: PlanReply,
+) -> Result<PlanVariant, PlanReply> {
+    match reply.body {
+        GeneratePlanResponse::Success {
+            plan_id,
+            plan,
+            model,
+            ..
+        } => {
+            let owner = usage::current_api_key();
+            let sources = match plan_id
+                .as_deref()
+                .map(|id| state.plans.get(id, owner.as_deref()))
+            {
+                Some(Ok(Some(stored))) => stored.sources,
+                Some(Err(e)) => {
+                    tracing::warn!("Comparing without sources, plan lookup failed: {}", e);
+                    Vec::new()
+                }
+                _ => Vec::new(),
+            };
+            Ok(PlanVariant::new(
+                objective.to_string(),
+                plan_id,
+                plan,
+                model,
+                sources,
+            ))
+        }
+        body => Err(PlanReply { body, ..reply }),
+    }
+}
+
+fn accepts_ndjson(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/x-ndjson"))
+}
+
+// same planner, streamed as server-sent events so frontends can render partial output
+pub async fn generate_plan_stream_handler(
+    State(state): State<AppState>,
+    ClientId(client): ClientId,
+    Json(payload): Json<GeneratePlanRequest>,
+) -> Response {
+    let started = Instant::now();
+    info!(
+        "Received streaming planning request for objective: {}",
+        payload.objective
+    );
+    match plan_events(&state, &client, payload, started).await {
+        Ok(events) => Sse::new(events.map(|event| Ok::<_, Infallible>(event.to_sse())))
+            .keep_alive(KeepAlive::default())
+            .into_response(),
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+// a stored plan with its request and sources, or the plan alone in another format
+pub async fn get_plan_handler(
+    State(state): State<AppState>,
+    Path(plan_id): Path<String>,
+    Query(query): Query<FormatQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(renderer) = render::negotiate(query.format.as_deref(), &headers, "json") else {
+        return render::not_acceptable();
+    };
+    match load_plan(&state, &plan_id, Instant::now()) {
+        Ok(mut stored) => {
+            footer::append_to_stored(&state, &mut stored);
+            let data = serde_json::to_value(&stored).unwrap_or_default();
+            render::respond(renderer, &PlanDocument::new(&state, &stored, data))
+        }
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+// the plan as a file to print or email. a browser's Accept header would always pick
+// html here, so only ?format= can ask for something other than pdf
+pub async fn export_plan_handler(
+    State(state): State<AppState>,
+    Path(plan_id): Path<String>,
+    Query(query): Query<FormatQuery>,
+) -> Response {
+    let Some(renderer) = render::by_format(query.format.as_deref().unwrap_or("pdf")) else {
+        return render::not_acceptable();
+    };
+    match load_plan(&state, &plan_id, Instant::now()) {
+        Ok(mut stored) => {
+            footer::append_to_stored(&state, &mut stored);
+            let data = serde_json::to_value(&stored).unwrap_or_default();
+            render::download(
+                renderer,
+                &PlanDocument::new(&state, &stored, data),
+                &stored.id,
+            )
+        }
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+// adds a stored plan's sessions to the attendee's google calendar
+pub async fn google_calendar_sync_handler(
+    State(state): State<AppState>,
+    Path(plan_id): Path<String>,
+    Json(request): Json<GoogleCalendarSyncRequest>,
+) -> Response {
+    let stored = match load_plan(&state, &plan_id, Instant::now()) {
+        Ok(stored) => stored,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let profile = state
+        .conferences
+        .resolve(Some(&stored.conference))
+        .unwrap_or_else(|_| state.conferences.default_profile());
+    match google_calendar::sync_plan(&state.http, &stored, profile, &request).await {
+        Ok(report) => {
+            info!(
+                "Synced plan {} to google calendar: {} added, {} conflicts",
+                plan_id,
+                report.created.len(),
+                report.conflicts.len()
+            );
+            Json(report).into_response()
+        }
+        Err(e) => {
+            tracing::warn!("Google calendar sync of plan {} failed: {}", plan_id, e);
+            e.into_response()
+        }
+    }
+}
+
+// a user's plans, newest first
+pub async fn list_plans_handler(
+    State(state): State<AppState>,
+    Query(query): Query<PlanListQuery>,
+) -> Response {
+    let owner = usage::current_api_key();
+    match state
+        .plans
+        .list_for_user(&query.user, owner.as_deref(), query.limit)
+    {
+        Ok(plans) => Json(plans).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to list plans for {}: {}", query.user, e);
+            plan_error(
+                AppError::Internal(copy::error("error.plans_load_failed", &[])),
+                Instant::now(),
+            )
+            .into_response()
+        }
+    }
+}
+
+// a rating of a stored plan, with the issues read from its comment
+pub async fn plan_feedback_handler(
+    State(state): State<AppState>,
+    Path(plan_id): Path<String>,
+    Json(request): Json<PlanFeedbackRequest>,
+) -> Response {
+    let started = Instant::now();
+    if let Err(e) = request.validate() {
+        return plan_error(AppError::Validation(e), started).into_response();
+    }
+    let stored = match state
+        .plans
+        .get(&plan_id, usage::current_api_key().as_deref())
+    {
+        Ok(Some(stored)) => stored,
+        Ok(None) => {
+            return plan_error(
+                AppError::NotFound(copy::error("error.plan_not_found", &[])),
+                started,
+            )
+            .into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to load plan {} for feedback: {}", plan_id, e);
+            return AppError::Internal(copy::error("error.feedback_unavailable", &[]))
+                .into_response();
+        }
+    };
+    match feedback::record(&state, &stored, &request) {
+        Ok(issues) => {
+            info!(
+                "Plan {} rated {} with {} issues",
+                plan_id,
+                request.rating,
+                issues.len()
+            );
+            (
+                StatusCode::CREATED,
+                Json(PlanFeedbackResponse {
+                    plan_id,
+                    rating: request.rating,
+                    issues,
+                }),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to record feedback on plan {}: {}", plan_id, e);
+            AppError::Internal(copy::error("error.feedback_unavailable", &[])).into_response()
+        }
+    }
+}
+
+// grounded explanation of one recommendation, reuses the stored transcript and sources
+pub async fn explain_step_handler(
+    State(state): State<AppState>,
+    Path((plan_id, step)): Path<(String, usize)>,
+) -> Response {
+    let started = Instant::now();
+    let stored = match load_plan(&state, &plan_id, started) {
+        Ok(stored) => stored,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let Some(step_text) = stored.step(step) else {
+        return plan_error(
+            AppError::NotFound(copy::error(
+                "error.plan_step_not_found",
+                &[
+                    ("plan", plan_id.as_str()),
+                    ("step", step.to_string().as_str()),
+                ],
+            )),
+            started,
+        )
+        .into_response();
+    };
+
+    let (llm_client, choice) = match initialize_llm(None, started) {
+        Ok(llm) => llm,
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    info!("Explaining step {} of plan {}", step, plan_id);
+    let explain_agent = build_explain_agent(&llm_client, &choice.model);
+    let explanation = match prompt_with_retry(
+        &explain_agent,
+        &stored.explain_prompt(step, step_text),
+        &state.health,
+    )
+    .await
+    {
+        Ok(explanation) => explanation,
+        Err(e) => {
+            return plan_error(
+                AppError::llm(
+                    copy::error("error.explain_failed", &[("error", e.message.as_str())]),
+                    e.retry_after,
+                ),
+                started,
+            )
+            .into_response()
+        }
+    };
+
+    let citations = citations::cite_plan(&stored.plan, &stored.sources)
+        .into_iter()
+        .filter(|citation| citation.step == step)
+        .collect();
+    Json(ExplainResponse {
+        plan_id,
+        step,
+        step_text: step_text.to_string(),
+        explanation,
+        citations,
+        elapsed_ms: elapsed_ms(started),
+    })
+    .into_response()
+}
+
+// revises a stored plan with one instruction. the revision is stored as a new plan
+// and answered with what changed, the original stays as it was for its share links
+pub async fn refine_plan_handler(
+    State(state): State<AppState>,
+    Path(plan_id): Path<String>,
+    ClientId(client): ClientId,
+    Json(request): Json<RefinePlanRequest>,
+) -> Response {
+    let started = Instant::now();
+    if let Err(e) = request.validate() {
+        return plan_error(AppError::Validation(e), started).into_response();
+    }
+    if let Some(rejection) = abuse_rejection(state.abuse.check(&client, &request.instruction)) {
+        return rejection.into_response();
+    }
+    if let Some(rejection) = moderation_rejection(&state, &client, &request.instruction).await {
+        return rejection.into_response();
+    }
+    let stored = match load_plan(&state, &plan_id, started) {
+        Ok(stored) => stored,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let profile = state
+        .conferences
+        .resolve(Some(&stored.conference))
+        .unwrap_or_else(|_| state.conferences.default_profile())
+        .clone();
+
+    let payload = request.plan_request(&stored);
+    let (llm_client, choice) = match initialize_llm(payload.model.as_deref(), started) {
+        Ok(llm) => llm,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let tone = resolve_tone(&state, payload.tone, payload.user_id.as_deref());
+    let params = match generation_params(&payload, &choice) {
+        Ok(params) => params
+            .with_tone(tone)
+            .with_feedback_notes(feedback::prior(&state, &profile)),
+        Err(e) => return plan_error(AppError::Validation(e), started).into_response(),
+    };
+    let user_context = resolve_user_context(&state, payload.user_id.as_deref(), &profile);
+
+    info!("Refining plan {}: {}", plan_id, request.instruction);
+    let collector = SourceCollector::default();
+    let attribution = usage::Attribution::new(state.usage.clone(), payload.cost_tags.clone());
+    let answer = usage::attributed(
+        Some(attribution),
+        prompt_with_fallback(
+            &llm_client,
+            &choice,
+            &request.prompt(&stored),
+            &state.health,
+            |client, model| {
+                build_planning_agent(
+                    client,
+                    &profile,
+                    &user_context,
+                    &collector,
+                    &state.health,
+                    &params.for_model(model),
+                )
+            },
+        ),
+    )
+    .await;
+    let answer = match answer {
+        Ok(answer) => answer,
+        Err(e) => {
+            return plan_error(
+                AppError::llm(
+                    copy::error("error.refine_failed", &[("error", e.message.as_str())]),
+                    e.retry_after,
+                ),
+                started,
+            )
+            .into_response()
+        }
+    };
+
+    // the original's sessions stay citable in the revision
+    let mut sources = stored.sources.clone();
+    for source in collector.sources() {
+        if !sources.iter().any(|known| known.id == source.id) {
+            sources.push(source);
+        }
+    }
+    let diff = refine::diff(&stored, &answer.response, &sources, profile.year());
+    let fallback_from = answer.fallback_from(&choice);
+    let outcome = PlanOutcome {
+        body: answer.response,
+        next_action: None,
+        itinerary: None,
+        sources,
+        model: answer.choice.to_string(),
+        fallback_from,
+        listing: None,
+    };
+    let request_id = telemetry::current_request_id();
+    let owner = usage::current_api_key();
+    let refined_id = match state.plans.insert(
+        &payload,
+        &stored.conference,
+        &outcome,
+        request_id.as_deref(),
+        owner.as_deref(),
+    ) {
+        Ok(refined_id) => {
+            popularity::record_plan(&state, &stored.conference, &outcome);
+            Some(refined_id)
+        }
+        Err(e) => {
+            tracing::error!("Failed to store the refined plan: {}", e);
+            None
+        }
+    };
+    info!(
+        "Refined plan {} into {}: {} added, {} removed, {} moved",
+        plan_id,
+        refined_id.as_deref().unwrap_or("(unsaved)"),
+        diff.added.len(),
+        diff.removed.len(),
+        diff.moved.len()
+    );
+    Json(RefinePlanResponse {
+        plan_id: refined_id,
+        refined_from: plan_id,
+        plan: outcome.body,
+        model: outcome.model,
+        fallback_from: outcome.fallback_from,
+        diff,
+        elapsed_ms: elapsed_ms(started),
+    })
+    .into_response()
+}
+
+// picked sessions as an .ics file for google calendar or outlook
+pub async fn export_ical_handler(
+    State(state): State<AppState>,
+    Json(request): Json<IcalExportRequest>,
+) -> Response {
+    if request.sessions.is_empty() {
+        return AppError::Validation(copy::error("error.no_sessions_to_export", &[]))
+            .into_response();
+    }
+
+    let profile = match state.conferences.resolve(request.conference.as_deref()) {
+        Ok(profile) => profile,
+        Err(e) => return AppError::Validation(e).into_response(),
+    };
+
+    match tools::render_ical(&request.sessions, &profile.timezone, &profile.name) {
+        Ok(ics) => {
+            info!("Exported {} sessions to iCal", request.sessions.len());
+            (
+                [
+                    (header::CONTENT_TYPE, "text/calendar; charset=utf-8"),
+                    (
+                        header::CONTENT_DISPOSITION,
+                        "attachment; filename=\"itinerary.ics\"",
+                    ),
+                ],
+                ics,
+            )
+                .into_response()
+        }
+        Err(e) => AppError::Validation(e.to_string()).into_response(),
+    }
+}
+
+// recent adds/moves/cancellations seen by the catalog sync
+pub async fn catalog_changes_handler(
+    State(state): State<AppState>,
+    Query(query): Query<CatalogChangesQuery>,
+) -> Json<Vec<catalog_sync::CatalogChange>> {
+    let limit = query.limit.unwrap_or(50).min(500);
+    Json(state.catalog_watcher.recent_changes(limit))
+}
+
+// hit rate and size of the vivatech search cache
+pub async fn cache_stats_handler() -> Json<query_cache::QueryCacheStats> {
+    Json(query_cache::query_cache_stats())
+}
+
+// enriched sessions filtered by topic, difficulty and audience
+pub async fn catalog_facets_handler(Query(query): Query<enrichment::FacetQuery>) -> Response {
+    match enrichment::session_enricher() {
+        Some(enricher) => Json(enricher.filter(&query)).into_response(),
+        None => AppError::NotFound(copy::error("error.enrichment_disabled", &[])).into_response(),
+    }
+}
+
+// sessions picked most often in plans and favorites, an early warning for crowded rooms
+pub async fn popular_sessions_handler(
+    State(state): State<AppState>,
+    Query(query): Query<popularity::PopularityQuery>,
+) -> Response {
+    let profile = match state.conferences.resolve(query.conference.as_deref()) {
+        Ok(profile) => profile.clone(),
+        Err(e) => return AppError::Validation(e).into_response(),
+    };
+    match popularity::popular(&state, &profile, &query) {
+        Ok(sessions) => Json(sessions).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to load session popularity: {}", e);
+            AppError::Internal(copy::error("error.popularity_unavailable", &[])).into_response()
+        }
+    }
+}
@@ -0,0 +1,146 @@
+// per-tool circuit breakers and the capability matrix derived from them
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::config;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolHealth {
+    Healthy,
+    // recent failures or probing after an outage
+    Degraded,
+    // breaker open, calls fail fast
+    Down,
+}
+
+#[derive(Debug, Default)]
+struct Breaker {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+pub struct CapabilityMatrix {
+    breakers: Mutex<HashMap<&'static str, Breaker>>,
+    failure_threshold: u32,
+    open_for: Duration,
+}
+
+impl CapabilityMatrix {
+    pub fn from_env() -> Self {
+        let failure_threshold = config::env_var("CIRCUIT_FAILURE_THRESHOLD")
+            .ok()
+            .and_then(|s| s.parse::<u32>().ok())
+            .unwrap_or(5)
+            .max(1);
+        let open_secs = config::env_var("CIRCUIT_OPEN_SECONDS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(60);
+
+        Self {
+            breakers: Mutex::new(HashMap::new()),
+            failure_threshold,
+            open_for: Duration::from_secs(open_secs),
+        }
+    }
+
+    // tools without a breaker (pure local ones) are always healthy
+    pub fn status(&self, tool: &str) -> ToolHealth {
+        let breakers = self.breakers.lock().unwrap_or_else(|e| e.into_inner());
+        match breakers.get(tool) {
+            None => ToolHealth::Healthy,
+            Some(breaker) => match breaker.open_until {
+                Some(until) if until > Instant::now() => ToolHealth::Down,
+                // half open, the next call decides
+                Some(_) => ToolHealth::Degraded,
+                None if breaker.consecutive_failures > 0 => ToolHealth::Degraded,
+                None => ToolHealth::Healthy,
+            },
+        }
+    }
+
+    // time left before an open breaker lets a probe through
+    pub fn retry_in(&self, tool: &str) -> Option<Duration> {
+        let breakers = self.breakers.lock().unwrap_or_else(|e| e.into_inner());
+        let until = breakers.get(tool)?.open_until?;
+        until.checked_duration_since(Instant::now())
+    }
+
+    pub fn record_success(&self, tool: &'static str) {
+        let mut breakers = self.breakers.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(breaker) = breakers.remove(tool) {
+            if breaker.open_until.is_some() {
+                info!(tool, "Circuit closed after successful call");
+            }
+        }
+    }
+
+    pub fn record_failure(&self, tool: &'static str) {
+        let mut breakers = self.breakers.lock().unwrap_or_else(|e| e.into_inner());
+        let breaker = breakers.entry(tool).or_default();
+        breaker.consecutive_failures += 1;
+
+        // a failed probe while half open reopens right away
+        let half_open = breaker.open_until.is_some();
+        if half_open || breaker.consecutive_failures >= self.failure_threshold {
+            breaker.open_until = Some(Instant::now() + self.open_for);
+            warn!(
+                tool,
+                failures = breaker.consecutive_failures,
+                open_secs = self.open_for.as_secs(),
+                "Circuit opened"
+            );
+        }
+    }
+
+    pub fn snapshot(&self, tools: &[&'static str]) -> BTreeMap<&'static str, ToolHealth> {
+        tools
+            .iter()
+            .map(|tool| (*tool, self.status(tool)))
+            .collect()
+    }
+}
+
+impl Default for CapabilityMatrix {
+    fn default() -> Self {
+        Self::from_env()
+    }
+}
+
+// preamble block disclosing missing or shaky capabilities, empty when all is well
+pub fn capability_notice(matrix: &CapabilityMatrix, tools: &[(&'static str, &str)]) -> String {
+    let mut down = Vec::new();
+    let mut degraded = Vec::new();
+    for (tool, capability) in tools {
+        match matrix.status(tool) {
+            ToolHealth::Down => down.push(*capability),
+            ToolHealth::Degraded => degraded.push(*capability),
+            ToolHealth::Healthy => {}
+        }
+    }
+
+    let mut notice = String::new();
+    if !down.is_empty() {
+        notice.push_str(&format!(
+            "Currently unavailable: {}. Tell the attendee up front that the plan could not \
+            use these and may be incomplete; never invent sessions to fill the gap.",
+            down.join(", ")
+        ));
+    }
+    if !degraded.is_empty() {
+        if !notice.is_empty() {
+            notice.push('\n');
+        }
+        notice.push_str(&format!(
+            "Currently unreliable: {}. If a call fails, say which part of the plan is affected.",
+            degraded.join(", ")
+        ));
+    }
+    notice
+}
@@ -0,0 +1,30 @@
+// outbound http clients, built once so calls reuse pooled connections instead of
+// opening a new tls connection per request. clones share the pool
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use reqwest::Client;
+
+use crate::config;
+
+static API_CLIENT: OnceLock<Client> = OnceLock::new();
+static CLIENT: OnceLock<Client> = OnceLock::new();
+
+// for the vivatech api, every request is held to API_TIMEOUT_SECONDS
+pub fn api_client() -> Client {
+    API_CLIENT
+        .get_or_init(|| {
+            Client::builder()
+                .timeout(Duration::from_secs(config::get().api_timeout_seconds))
+                .build()
+                .expect("the http client builds")
+        })
+        .clone()
+}
+
+// every other upstream: moderation, slack, telegram, google calendar, the probes.
+// callers with a deadline set it per request
+pub fn client() -> Client {
+    CLIENT.get_or_init(Client::new).clone()
+}
@@ -0,0 +1,341 @@
+// on-demand full catalog ingestion, reporting progress as it goes
+
+use std::collections::HashMap;
+use std::time::Instant;
+
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::catalog_sync::{get_seed_queries, pull_query, CatalogWatcher};
+use crate::http;
+use crate::mock;
+use crate::models::VivatechSource;
+use crate::tools::get_vivatech_api_url;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IngestStage {
+    Pull,
+    Diff,
+    OfflineCatalog,
+    Enrichment,
+    Embeddings,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StageStatus {
+    Started,
+    Done,
+    Failed,
+    // disabled in this build or configuration
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IngestFailure {
+    pub stage: IngestStage,
+    // the seed query, for pull failures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IngestReport {
+    pub queries: usize,
+    pub failed_queries: usize,
+    // distinct sources pulled
+    pub sources: usize,
+    pub changes: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offline_catalog_sources: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enriched_sources: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embedded_sources: Option<usize>,
+    pub failures: Vec<IngestFailure>,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum IngestEvent {
+    Started {
+        queries: usize,
+    },
+    // one seed query answered, `total` is the distinct sources so far
+    Pulled {
+        query: String,
+        sources: usize,
+        total: usize,
+    },
+    Stage {
+        stage: IngestStage,
+        status: StageStatus,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        query: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        count: Option<usize>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    Finished {
+        report: IngestReport,
+    },
+}
+
+impl IngestEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            IngestEvent::Started { .. } => "started",
+            IngestEvent::Pulled { .. } => "pulled",
+            IngestEvent::Stage { .. } => "stage",
+            IngestEvent::Finished { .. } => "finished",
+        }
+    }
+}
+
+// sends progress and keeps the report in step with it. a gone listener does not stop the run
+struct Progress {
+    events: mpsc::Sender<IngestEvent>,
+    report: IngestReport,
+}
+
+impl Progress {
+    async fn send(&self, event: IngestEvent) {
+        let _ = self.events.send(event).await;
+    }
+
+    async fn stage(&self, stage: IngestStage, status: StageStatus, count: Option<usize>) {
+        self.send(IngestEvent::Stage {
+            stage,
+            status,
+            query: None,
+            count,
+            error: None,
+        })
+        .await;
+    }
+
+    async fn failed(&mut self, stage: IngestStage, query: Option<String>, error: String) {
+        warn!("Ingestion {:?} failed: {}", stage, error);
+        self.report.failures.push(IngestFailure {
+            stage,
+            query: query.clone(),
+            error: error.clone(),
+        });
+        self.send(IngestEvent::Stage {
+            stage,
+            status: StageStatus::Failed,
+            query,
+            count: None,
+            error: Some(error),
+        })
+        .await;
+    }
+}
+
+// pulls every seed query, keeping going past failed ones, then refreshes the
+// change snapshot, offline catalog, facets and semantic index from the result
+pub async fn run_ingestion(
+    watcher: &CatalogWatcher,
+    events: mpsc::Sender<IngestEvent>,
+) -> IngestReport {
+    let started = Instant::now();
+    let mut progress = Progress {
+        events,
+        report: IngestReport::default(),
+    };
+
+    let sources = pull_all(&mut progress).await;
+    progress.report.sources = sources.len();
+
+    if sources.is_empty() {
+        // nothing to build from, the current data stays as it is
+        progress
+            .failed(
+                IngestStage::Pull,
+                None,
+                "no sources pulled, nothing was replaced".to_string(),
+            )
+            .await;
+    } else {
+        refresh_offline_catalog(&mut progress, &sources).await;
+        enrich(&mut progress, &sources).await;
+        embed(&mut progress, &sources).await;
+
+        progress
+            .stage(IngestStage::Diff, StageStatus::Started, None)
+            .await;
+        let changes = watcher.apply(sources);
+        if !changes.is_empty() {
+            if let Some(cache) = crate::query_cache::query_cache() {
+                cache.clear();
+            }
+            if let Some(cache) = crate::plan_cache::plan_cache() {
+                cache.clear();
+            }
+        }
+        progress.report.changes = changes.len();
+        progress
+            .stage(IngestStage::Diff, StageStatus::Done, Some(changes.len()))
+            .await;
+    }
+
+    progress.report.elapsed_ms = started.elapsed().as_millis() as u64;
+    let report = progress.report.clone();
+    info!(
+        "Ingestion finished: {} sources, {} changes, {} failures",
+        report.sources,
+        report.changes,
+        report.failures.len()
+    );
+    progress
+        .send(IngestEvent::Finished {
+            report: report.clone(),
+        })
+        .await;
+    report
+}
+
+async fn pull_all(progress: &mut Progress) -> Vec<VivatechSource> {
+    if let Some(catalog) = mock::mock_catalog() {
+        progress.report.queries = 1;
+        progress.send(IngestEvent::Started { queries: 1 }).await;
+        let sources = catalog.all();
+        progress
+            .send(IngestEvent::Pulled {
+                query: "(mock catalog)".to_string(),
+                sources: sources.len(),
+                total: sources.len(),
+            })
+            .await;
+        return sources;
+    }
+
+    let queries = get_seed_queries();
+    progress.report.queries = queries.len();
+    progress
+        .send(IngestEvent::Started {
+            queries: queries.len(),
+        })
+        .await;
+
+    let api_url = match get_vivatech_api_url() {
+        Ok(api_url) => api_url,
+        Err(e) => {
+            progress.report.failed_queries = queries.len();
+            progress
+                .failed(IngestStage::Pull, None, e.to_string())
+                .await;
+            return Vec::new();
+        }
+    };
+
+    let client = http::api_client();
+    let mut collected: HashMap<String, VivatechSource> = HashMap::new();
+    for query in queries {
+        match pull_query(&client, &api_url, &query).await {
+            Ok(sources) => {
+                let count = sources.len();
+                for source in sources {
+                    collected.entry(source.id.clone()).or_insert(source);
+                }
+                progress
+                    .send(IngestEvent::Pulled {
+                        query,
+                        sources: count,
+                        total: collected.len(),
+                    })
+                    .await;
+            }
+            Err(e) => {
+                progress.report.failed_queries += 1;
+                progress
+                    .failed(IngestStage::Pull, Some(query), e.to_string())
+                    .await;
+            }
+        }
+    }
+    collected.into_values().collect()
+}
+
+#[cfg(feature = "offline-catalog")]
+async fn refresh_offline_catalog(progress: &mut Progress, sources: &[VivatechSource]) {
+    let Some(catalog) = crate::catalog::offline_catalog() else {
+        progress
+            .stage(IngestStage::OfflineCatalog, StageStatus::Skipped, None)
+            .await;
+        return;
+    };
+    progress
+        .stage(IngestStage::OfflineCatalog, StageStatus::Started, None)
+        .await;
+    match catalog.replace_all(sources) {
+        Ok(count) => {
+            progress.report.offline_catalog_sources = Some(count);
+            progress
+                .stage(IngestStage::OfflineCatalog, StageStatus::Done, Some(count))
+                .await;
+        }
+        Err(e) => {
+            progress
+                .failed(IngestStage::OfflineCatalog, None, e.to_string())
+                .await
+        }
+    }
+}
+
+#[cfg(not(feature = "offline-catalog"))]
+async fn refresh_offline_catalog(progress: &mut Progress, _sources: &[VivatechSource]) {
+    progress
+        .stage(IngestStage::OfflineCatalog, StageStatus::Skipped, None)
+        .await;
+}
+
+async fn enrich(progress: &mut Progress, sources: &[VivatechSource]) {
+    let Some(enricher) = crate::enrichment::session_enricher() else {
+        progress
+            .stage(IngestStage::Enrichment, StageStatus::Skipped, None)
+            .await;
+        return;
+    };
+    progress
+        .stage(IngestStage::Enrichment, StageStatus::Started, None)
+        .await;
+    // failed batches are logged by the enricher and retried on the next sync
+    let tagged = enricher.enrich(sources).await;
+    progress.report.enriched_sources = Some(tagged);
+    progress
+        .stage(IngestStage::Enrichment, StageStatus::Done, Some(tagged))
+        .await;
+}
+
+#[cfg(feature = "semantic-fallback")]
+async fn embed(progress: &mut Progress, sources: &[VivatechSource]) {
+    progress
+        .stage(IngestStage::Embeddings, StageStatus::Started, None)
+        .await;
+    match crate::rag::rebuild_semantic_index(sources.to_vec()).await {
+        Ok(count) => {
+            progress.report.embedded_sources = Some(count);
+            progress
+                .stage(IngestStage::Embeddings, StageStatus::Done, Some(count))
+                .await;
+        }
+        Err(e) => {
+            progress
+                .failed(IngestStage::Embeddings, None, e.to_string())
+                .await
+        }
+    }
+}
+
+#[cfg(not(feature = "semantic-fallback"))]
+async fn embed(progress: &mut Progress, _sources: &[VivatechSource]) {
+    progress
+        .stage(IngestStage::Embeddings, StageStatus::Skipped, None)
+        .await;
+}
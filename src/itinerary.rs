@@ -0,0 +1,91 @@
+// machine-readable itinerary extracted from a generated plan
+
+use chrono::NaiveDateTime;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::models::{ActionUrgency, VivatechSource};
+
+// ordered time slots, the schema handed to the model is derived from these types
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Plan {
+    pub slots: Vec<TimeSlot>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TimeSlot {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub session_id: String,
+    pub title: String,
+    pub location: String,
+    pub urgency: ActionUrgency,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ItineraryError {
+    #[error("itinerary has no slots")]
+    Empty,
+    #[error("slot {0} has an empty {1}")]
+    MissingField(usize, &'static str),
+    #[error("slot {0} ends before it starts")]
+    EndBeforeStart(usize),
+    #[error("slot {0} starts before the previous slot")]
+    OutOfOrder(usize),
+    #[error("slot {0} overlaps the previous slot")]
+    Overlap(usize),
+    #[error("slot {0} references unknown session '{1}'")]
+    UnknownSession(usize, String),
+}
+
+impl Plan {
+    // slots are numbered from 1 in errors, like plan steps.
+    // session ids are only checked when the agent saw sources
+    pub fn validate(&self, sources: &[VivatechSource]) -> Result<(), ItineraryError> {
+        if self.slots.is_empty() {
+            return Err(ItineraryError::Empty);
+        }
+
+        let mut previous: Option<&TimeSlot> = None;
+        for (index, slot) in self.slots.iter().enumerate() {
+            let number = index + 1;
+            for (field, value) in [
+                ("session_id", &slot.session_id),
+                ("title", &slot.title),
+                ("location", &slot.location),
+            ] {
+                if value.trim().is_empty() {
+                    return Err(ItineraryError::MissingField(number, field));
+                }
+            }
+            if slot.end <= slot.start {
+                return Err(ItineraryError::EndBeforeStart(number));
+            }
+            if !sources.is_empty() && !sources.iter().any(|s| s.id == slot.session_id) {
+                return Err(ItineraryError::UnknownSession(
+                    number,
+                    slot.session_id.clone(),
+                ));
+            }
+            if let Some(previous) = previous {
+                if slot.start < previous.start {
+                    return Err(ItineraryError::OutOfOrder(number));
+                }
+                if slot.start < previous.end {
+                    return Err(ItineraryError::Overlap(number));
+                }
+            }
+            previous = Some(slot);
+        }
+        Ok(())
+    }
+}
+
+// the prose plan plus the sources it was built from, so ids can be copied
+pub fn extraction_input(plan: &str, sources: &[VivatechSource]) -> String {
+    let mut input = format!("Plan:\n{}\n\nSources:\n", plan);
+    for source in sources {
+        input.push_str(&format!("- {}: {}\n", source.id, source.text_chunk));
+    }
+    input
+}
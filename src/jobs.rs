@@ -0,0 +1,403 @@
+// plan generation as background jobs, for objectives that outlast http timeouts
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::json;
+use tokio::sync::Semaphore;
+use tokio::task::AbortHandle;
+use tracing::info;
+
+use crate::abuse::ClientId;
+use crate::error::{self, AppError};
+use crate::models::GeneratePlanRequest;
+use crate::pipeline::run_plan_request;
+use crate::reply::plan_error;
+use crate::state::AppState;
+use crate::{config, copy, ratelimit, telemetry, usage};
+
+#[derive(Debug, thiserror::Error)]
+pub enum JobError {
+    #[error("Too many plan jobs waiting ({0}), try again later")]
+    QueueFull(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Succeeded => "succeeded",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn is_finished(self) -> bool {
+        matches!(
+            self,
+            JobStatus::Succeeded | JobStatus::Failed | JobStatus::Cancelled
+        )
+    }
+}
+
+// what GET /plans/{job_id}/status answers
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct JobView {
+    pub job_id: String,
+    pub status: JobStatus,
+    pub objective: String,
+    pub submitted_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<DateTime<Utc>>,
+    // jobs ahead of this one, while queued
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queue_position: Option<usize>,
+}
+
+// the plan response a job ended with, served as if it came from /generate-plan
+#[derive(Debug, Clone)]
+pub struct JobResult {
+    pub http_status: u16,
+    pub body: serde_json::Value,
+}
+
+struct Job {
+    // api key that submitted it, only that key can poll, fetch or cancel it
+    owner: Option<String>,
+    view: JobView,
+    result: Option<JobResult>,
+    task: Option<AbortHandle>,
+}
+
+pub struct PlanJobs {
+    jobs: Mutex<HashMap<String, Job>>,
+    slots: Arc<Semaphore>,
+    max_pending: usize,
+    retention: Duration,
+}
+
+impl PlanJobs {
+    pub fn new(concurrency: usize, max_pending: usize, retention: Duration) -> Self {
+        Self {
+            jobs: Mutex::new(HashMap::new()),
+            slots: Arc::new(Semaphore::new(concurrency.max(1))),
+            max_pending,
+            retention,
+        }
+    }
+
+    pub fn from_env() -> Self {
+        let config = config::get();
+        Self::new(
+            config.plan_job_concurrency,
+            config.plan_job_max_pending,
+            config.plan_job_retention,
+        )
+    }
+
+    // a worker slot, held for the whole run
+    pub fn slots(&self) -> Arc<Semaphore> {
+        self.slots.clone()
+    }
+
+    // registers a queued job, finished jobs past retention are dropped on the way
+    pub fn submit(&self, objective: &str, owner: Option<&str>) -> Result<String, JobError> {
+        let mut jobs = self.jobs.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Utc::now();
+        jobs.retain(|_, job| match job.view.finished_at {
+            Some(finished) => (now - finished).to_std().unwrap_or_default() < self.retention,
+            None => true,
+        });
+        let pending = jobs
+            .values()
+            .filter(|job| !job.view.status.is_finished())
+            .count();
+        if pending >= self.max_pending {
+            return Err(JobError::QueueFull(pending));
+        }
+
+        let id = new_job_id();
+        jobs.insert(
+            id.clone(),
+            Job {
+                owner: owner.map(str::to_string),
+                view: JobView {
+                    job_id: id.clone(),
+                    status: JobStatus::Queued,
+                    objective: objective.to_string(),
+                    submitted_at: now,
+                    started_at: None,
+                    finished_at: None,
+                    queue_position: None,
+                },
+                result: None,
+                task: None,
+            },
+        );
+        Ok(id)
+    }
+
+    // the task running the job, so cancel can stop it
+    pub fn attach(&self, id: &str, task: AbortHandle) {
+        let mut jobs = self.jobs.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(job) = jobs.get_mut(id) {
+            if job.view.status.is_finished() {
+                task.abort();
+            } else {
+                job.task = Some(task);
+            }
+        }
+    }
+
+    // false if the job was cancelled while it waited
+    pub fn start(&self, id: &str) -> bool {
+        let mut jobs = self.jobs.lock().unwrap_or_else(PoisonError::into_inner);
+        match jobs.get_mut(id) {
+            Some(job) if job.view.status == JobStatus::Queued => {
+                job.view.status = JobStatus::Running;
+                job.view.started_at = Some(Utc::now());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // 2xx replies succeed, anything else is a failed job with the error body as result
+    pub fn finish(&self, id: &str, http_status: u16, body: serde_json::Value) {
+        let mut jobs = self.jobs.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(job) = jobs.get_mut(id) else {
+            return;
+        };
+        if job.view.status.is_finished() {
+            return;
+        }
+        job.view.status = if (200..300).contains(&http_status) {
+            JobStatus::Succeeded
+        } else {
+            JobStatus::Failed
+        };
+        job.view.finished_at = Some(Utc::now());
+        job.result = Some(JobResult { http_status, body });
+        job.task = None;
+        info!("Plan job {} finished as {:?}", id, job.view.status);
+    }
+
+    // None for unknown jobs and for jobs of another owner alike
+    pub fn status(&self, id: &str, owner: Option<&str>) -> Option<JobView> {
+        let jobs = self.jobs.lock().unwrap_or_else(PoisonError::into_inner);
+        let job = jobs.get(id).filter(|job| job.owner.as_deref() == owner)?;
+        let mut view = job.view.clone();
+        if view.status == JobStatus::Queued {
+            view.queue_position = Some(
+                jobs.values()
+                    .filter(|other| {
+                        other.view.status == JobStatus::Queued
+                            && other.view.submitted_at < view.submitted_at
+                    })
+                    .count(),
+            );
+        }
+        Some(view)
+    }
+
+    // None for unknown jobs, the current status while there is no result yet
+    pub fn result(&self, id: &str, owner: Option<&str>) -> Option<Result<JobResult, JobStatus>> {
+        let jobs = self.jobs.lock().unwrap_or_else(PoisonError::into_inner);
+        let job = jobs.get(id).filter(|job| job.owner.as_deref() == owner)?;
+        Some(job.result.clone().ok_or(job.view.status))
+    }
+
+    // stops a queued or running job, finished jobs keep their status
+    pub fn cancel(&self, id: &str, owner: Option<&str>) -> Option<JobStatus> {
+        let mut jobs = self.jobs.lock().unwrap_or_else(PoisonError::into_inner);
+        let job = jobs
+            .get_mut(id)
+            .filter(|job| job.owner.as_deref() == owner)?;
+        if !job.view.status.is_finished() {
+            if let Some(task) = job.task.take() {
+                task.abort();
+            }
+            job.view.status = JobStatus::Cancelled;
+            job.view.finished_at = Some(Utc::now());
+            info!("Plan job {} cancelled", id);
+        }
+        Some(job.view.status)
+    }
+}
+
+// 128 random bits, distinct from plan ids by the prefix
+fn new_job_id() -> String {
+    format!("job-{:032x}", rand::random::<u128>())
+}
+
+impl Default for PlanJobs {
+    fn default() -> Self {
+        Self::from_env()
+    }
+}
+
+// queue a plan for the background workers and answer at once with the job id
+pub async fn submit_plan_job_handler(
+    State(state): State<AppState>,
+    ClientId(client): ClientId,
+    Json(payload): Json<GeneratePlanRequest>,
+) -> Response {
+    // GET /plans shares the route and runs no agent, so the submit charges itself
+    if let Err(retry_after) = ratelimit::charge(&state, &client, "/plans") {
+        return ratelimit::rejection(retry_after);
+    }
+
+    // the task outlives the request, so the api key is carried over for usage
+    // accounting and the request id for the stored plan
+    let api_key = usage::current_api_key();
+    let request_id = telemetry::current_request_id();
+    let job_id = match state
+        .plan_jobs
+        .submit(&payload.objective, api_key.as_deref())
+    {
+        Ok(job_id) => job_id,
+        Err(e) => {
+            tracing::warn!("Rejected plan job: {}", e);
+            return plan_error(AppError::Unavailable(e.to_string()), Instant::now())
+                .into_response();
+        }
+    };
+    info!(
+        "Queued plan job {} for objective: {}",
+        job_id, payload.objective
+    );
+
+    let jobs = state.plan_jobs.clone();
+    let id = job_id.clone();
+    let task_state = state.clone();
+    let run = async move {
+        let Ok(_slot) = jobs.slots().acquire_owned().await else {
+            return;
+        };
+        if !jobs.start(&id) {
+            return;
+        }
+        let reply = run_plan_request(&task_state, &client, payload).await;
+        let body = serde_json::to_value(&reply.body).unwrap_or_else(|e| {
+            let error = error::envelope(
+                "internal_error",
+                format!("Unreadable plan response - {}", e),
+            );
+            json!({
+                "status": "error",
+                "code": error.code,
+                "message": error.message,
+                "request_id": error.request_id,
+            })
+        });
+        jobs.finish(&id, reply.status.as_u16(), body);
+    };
+    let run = telemetry::with_request_id(request_id, run);
+    let task = tokio::spawn(async move {
+        match api_key {
+            Some(api_key) => usage::for_api_key(api_key, run).await,
+            None => run.await,
+        }
+    });
+    state.plan_jobs.attach(&job_id, task.abort_handle());
+
+    (
+        StatusCode::ACCEPTED,
+        [(header::LOCATION, format!("/plans/{}/status", job_id))],
+        Json(json!({
+            "job_id": job_id,
+            "status": JobStatus::Queued,
+            "status_url": format!("/plans/{}/status", job_id),
+            "result_url": format!("/plans/{}/result", job_id),
+        })),
+    )
+        .into_response()
+}
+
+pub async fn plan_job_status_handler(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> Response {
+    match state
+        .plan_jobs
+        .status(&job_id, usage::current_api_key().as_deref())
+    {
+        Some(view) => Json(view).into_response(),
+        None => unknown_job(&job_id),
+    }
+}
+
+// the plan response once the job is done, 409 with the status until then
+pub async fn plan_job_result_handler(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> Response {
+    match state
+        .plan_jobs
+        .result(&job_id, usage::current_api_key().as_deref())
+    {
+        Some(Ok(result)) => (
+            StatusCode::from_u16(result.http_status).unwrap_or(StatusCode::OK),
+            Json(result.body),
+        )
+            .into_response(),
+        Some(Err(status)) => {
+            let key = if status == JobStatus::Cancelled {
+                "error.job_cancelled"
+            } else {
+                "error.job_unfinished"
+            };
+            let error = AppError::Conflict(copy::error(key, &[("job", job_id.as_str())]));
+            error::respond(
+                error.status(),
+                error.envelope().with_reason(status.as_str()),
+            )
+        }
+        None => unknown_job(&job_id),
+    }
+}
+
+pub async fn cancel_plan_job_handler(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> Response {
+    match state
+        .plan_jobs
+        .cancel(&job_id, usage::current_api_key().as_deref())
+    {
+        Some(status) => Json(json!({ "job_id": job_id, "status": status })).into_response(),
+        None => unknown_job(&job_id),
+    }
+}
+
+// finished jobs are forgotten after PLAN_JOB_RETENTION_SECS, other keys' jobs are never seen
+fn unknown_job(job_id: &str) -> Response {
+    plan_error(
+        AppError::NotFound(copy::error("error.unknown_job", &[("job", job_id)])),
+        Instant::now(),
+    )
+    .into_response()
+}
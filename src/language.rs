@@ -0,0 +1,116 @@
+// which language a request is in, and dates written the way its readers expect.
+// attendees who write in french get a french plan without having to ask
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use whatlang::{Detector, Lang};
+
+// names people put in the language field, mapped to language codes
+const LANGUAGE_NAMES: &[(&str, &str)] = &[
+    ("english", "en"),
+    ("french", "fr"),
+    ("français", "fr"),
+    ("francais", "fr"),
+    ("spanish", "es"),
+    ("español", "es"),
+    ("espanol", "es"),
+    ("german", "de"),
+    ("deutsch", "de"),
+];
+
+// what the detector picks between, the languages most attendees write in
+const DETECTED: &[(Lang, &str)] = &[
+    (Lang::Eng, "en"),
+    (Lang::Fra, "fr"),
+    (Lang::Spa, "es"),
+    (Lang::Deu, "de"),
+];
+
+const MONTHS_FR: [&str; 12] = [
+    "janvier",
+    "février",
+    "mars",
+    "avril",
+    "mai",
+    "juin",
+    "juillet",
+    "août",
+    "septembre",
+    "octobre",
+    "novembre",
+    "décembre",
+];
+
+const MONTHS_ES: [&str; 12] = [
+    "enero",
+    "febrero",
+    "marzo",
+    "abril",
+    "mayo",
+    "junio",
+    "julio",
+    "agosto",
+    "septiembre",
+    "octubre",
+    "noviembre",
+    "diciembre",
+];
+
+const MONTHS_DE: [&str; 12] = [
+    "Januar",
+    "Februar",
+    "März",
+    "April",
+    "Mai",
+    "Juni",
+    "Juli",
+    "August",
+    "September",
+    "Oktober",
+    "November",
+    "Dezember",
+];
+
+// "fr", "fr-FR" and "French" all give "fr"
+pub fn language_code(language: &str) -> String {
+    let language = language.trim().to_lowercase();
+    match LANGUAGE_NAMES.iter().find(|(name, _)| *name == language) {
+        Some((_, code)) => code.to_string(),
+        None => language
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_string(),
+    }
+}
+
+// e.g. "fr" for "Planifie ma journée autour de l'IA". None when the text is too
+// short or too mixed to tell, the model then follows the objective on its own
+pub fn detect(text: &str) -> Option<&'static str> {
+    let detector = Detector::with_allowlist(DETECTED.iter().map(|(lang, _)| *lang).collect());
+    let info = detector.detect(text).filter(|info| info.is_reliable())?;
+    DETECTED
+        .iter()
+        .find(|(lang, _)| *lang == info.lang())
+        .map(|(_, code)| *code)
+}
+
+// "June 11, 2025", "11 juin 2025", "11 de junio de 2025" or "11. Juni 2025",
+// english for languages without month names here
+pub fn format_date(date: NaiveDate, language: Option<&str>) -> String {
+    let month = date.month0() as usize;
+    match language.map(language_code).as_deref() {
+        Some("fr") => format!("{} {} {}", date.day(), MONTHS_FR[month], date.year()),
+        Some("es") => format!("{} de {} de {}", date.day(), MONTHS_ES[month], date.year()),
+        Some("de") => format!("{}. {} {}", date.day(), MONTHS_DE[month], date.year()),
+        _ => date.format("%B %-d, %Y").to_string(),
+    }
+}
+
+// the date with a 24-hour time, e.g. "11 juin 2025 14:30"
+pub fn format_datetime(datetime: NaiveDateTime, language: Option<&str>) -> String {
+    format!(
+        "{} {}",
+        format_date(datetime.date(), language),
+        datetime.format("%H:%M")
+    )
+}
@@ -0,0 +1,86 @@
+// agents, tools, domain models, the embeddable planner and the http api itself, shared
+// by the api binary, the tests and services that depend on this crate
+
+pub mod abuse;
+pub mod admin;
+pub mod agents;
+pub mod auth;
+#[cfg(feature = "offline-catalog")]
+pub mod catalog;
+pub mod catalog_sync;
+pub mod chat;
+pub mod citations;
+pub mod coalesce;
+pub mod compare;
+pub mod conference;
+pub mod config;
+pub mod conversation_store;
+pub mod copy;
+pub mod dedupe;
+pub mod degraded;
+pub mod enrichment;
+pub mod error;
+pub mod export;
+pub mod feedback;
+pub mod footer;
+pub mod generation;
+pub mod google_calendar;
+pub mod graphql;
+pub mod handlers;
+pub mod health;
+pub mod http;
+pub mod ingest;
+pub mod itinerary;
+pub mod jobs;
+pub mod language;
+pub mod llm;
+#[cfg(feature = "loadtest")]
+pub mod loadtest;
+pub mod locks;
+pub mod mcp;
+pub mod mock;
+pub mod models;
+pub mod objective;
+pub mod openapi;
+pub mod pipeline;
+pub mod placeholders;
+pub mod plan_cache;
+pub mod planner;
+pub mod plans;
+pub mod popularity;
+pub mod pricing;
+pub mod probes;
+pub mod profiles;
+pub mod programme;
+pub mod progress;
+pub mod prompts;
+pub mod query_cache;
+#[cfg(feature = "semantic-fallback")]
+pub mod rag;
+pub mod ratelimit;
+pub mod recap;
+pub mod refine;
+pub mod render;
+pub mod replay;
+pub mod reply;
+pub mod rerank;
+pub mod retry;
+pub mod router;
+pub mod sanitize;
+pub mod selftest;
+pub mod sessions;
+pub mod settings;
+pub mod share;
+pub mod signing;
+pub mod slack;
+pub mod state;
+pub mod streaming;
+pub mod telegram;
+pub mod telemetry;
+pub mod templates;
+pub mod tone;
+pub mod tools;
+pub mod usage;
+pub mod validation;
+pub mod widget;
+pub mod ws;
@@ -0,0 +1,350 @@
+// llm provider selection, dispatching over the rig providers we support
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+use rig::agent::Agent;
+use rig::completion::{Message, Prompt, PromptError};
+use rig::extractor::ExtractionError;
+use rig::prelude::*;
+use rig::providers::{anthropic, gemini, ollama, openai};
+use schemars::JsonSchema;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::config;
+use crate::mock::{self, MockAgent, MockClient};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    OpenAI,
+    Anthropic,
+    Gemini,
+    // local models for development
+    Ollama,
+    // canned answers without any network, see MOCK_MODE
+    Mock,
+}
+
+impl Provider {
+    pub fn name(self) -> &'static str {
+        match self {
+            Provider::OpenAI => "openai",
+            Provider::Anthropic => "anthropic",
+            Provider::Gemini => "gemini",
+            Provider::Ollama => "ollama",
+            Provider::Mock => "mock",
+        }
+    }
+
+    // the provider's breaker in the capability matrix
+    pub fn circuit(self) -> &'static str {
+        match self {
+            Provider::OpenAI => "llm_openai",
+            Provider::Anthropic => "llm_anthropic",
+            Provider::Gemini => "llm_gemini",
+            Provider::Ollama => "llm_ollama",
+            Provider::Mock => "llm_mock",
+        }
+    }
+
+    pub fn default_model(self) -> &'static str {
+        match self {
+            Provider::OpenAI => openai::GPT_4O,
+            Provider::Anthropic => "claude-3-5-sonnet-latest",
+            Provider::Gemini => "gemini-1.5-pro",
+            Provider::Ollama => "llama3.1",
+            Provider::Mock => "mock-planner",
+        }
+    }
+
+    // ollama and the mock run locally without a key
+    pub fn api_key_var(self) -> Option<&'static str> {
+        match self {
+            Provider::OpenAI => Some("OPENAI_API_KEY"),
+            Provider::Anthropic => Some("ANTHROPIC_API_KEY"),
+            Provider::Gemini => Some("GEMINI_API_KEY"),
+            Provider::Ollama | Provider::Mock => None,
+        }
+    }
+}
+
+impl FromStr for Provider {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "openai" => Ok(Provider::OpenAI),
+            "anthropic" => Ok(Provider::Anthropic),
+            "gemini" => Ok(Provider::Gemini),
+            "ollama" => Ok(Provider::Ollama),
+            "mock" => Ok(Provider::Mock),
+            other => Err(format!(
+                "Unknown LLM provider '{}', expected openai, anthropic, gemini, ollama or mock",
+                other
+            )),
+        }
+    }
+}
+
+// provider plus model, written "anthropic:claude-3-5-haiku-latest"
+// or just the model name for the default provider
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelChoice {
+    pub provider: Provider,
+    pub model: String,
+}
+
+impl ModelChoice {
+    pub fn parse(spec: &str, default_provider: Provider) -> Result<Self, String> {
+        let spec = spec.trim();
+        let (provider, model) = match spec.split_once(':') {
+            Some((provider, model)) if provider.parse::<Provider>().is_ok() => {
+                (provider.parse::<Provider>()?, model.trim())
+            }
+            // ollama tags like "llama3.1:8b" have a colon too
+            _ => (default_provider, spec),
+        };
+
+        if model.is_empty() {
+            return Ok(Self::default_for(provider));
+        }
+        Ok(Self {
+            provider,
+            model: model.to_string(),
+        })
+    }
+
+    pub fn default_for(provider: Provider) -> Self {
+        Self {
+            provider,
+            model: provider.default_model().to_string(),
+        }
+    }
+
+    // LLM_PROVIDER and LLM_MODEL or the model set in /admin/config, openai gpt-4o when unset
+    pub fn from_env() -> Self {
+        let provider = default_provider();
+        config::runtime()
+            .llm_model
+            .as_deref()
+            .and_then(|spec| Self::parse(spec, provider).ok())
+            .unwrap_or_else(|| Self::default_for(provider))
+    }
+
+    // a per-request override, falling back to the configured model
+    pub fn resolve(requested: Option<&str>) -> Result<Self, String> {
+        match requested {
+            Some(spec) => Self::parse(spec, default_provider()),
+            None => Ok(Self::from_env()),
+        }
+    }
+}
+
+impl fmt::Display for ModelChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.provider.name(), self.model)
+    }
+}
+
+// LLM_FALLBACK_MODELS without the chosen model itself and without providers that have
+// no key. empty in MOCK_MODE, where every provider is the mock
+pub fn fallback_chain(choice: &ModelChoice) -> Vec<ModelChoice> {
+    if mock::mock_mode() {
+        return Vec::new();
+    }
+    let mut chain: Vec<ModelChoice> = Vec::new();
+    for spec in &config::get().llm_fallback_models {
+        let Ok(fallback) = ModelChoice::parse(spec, default_provider()) else {
+            continue;
+        };
+        if &fallback != choice
+            && !chain.contains(&fallback)
+            && config::get().has_api_key(fallback.provider)
+        {
+            chain.push(fallback);
+        }
+    }
+    chain
+}
+
+// always the mock in MOCK_MODE
+pub fn default_provider() -> Provider {
+    config::get().llm_provider
+}
+
+// provider clients are built once, they hold the connection pool
+static CLIENTS: OnceLock<Mutex<HashMap<Provider, LlmClient>>> = OnceLock::new();
+
+#[derive(Clone)]
+pub enum LlmClient {
+    OpenAI(openai::Client),
+    Anthropic(anthropic::Client),
+    Gemini(gemini::Client),
+    Ollama(ollama::Client),
+    Mock(MockClient),
+}
+
+impl LlmClient {
+    // MOCK_MODE swaps every provider for the mock, so no request reaches a real model.
+    // the first call for a provider builds its client, later calls share it
+    pub fn from_env(provider: Provider) -> Result<Self, String> {
+        if provider == Provider::Mock || mock::mock_mode() {
+            return Ok(LlmClient::Mock(MockClient::canned()));
+        }
+        if !config::get().has_api_key(provider) {
+            if let Some(key) = provider.api_key_var() {
+                return Err(format!("{} not found in environment", key));
+            }
+        }
+
+        let mut clients = CLIENTS
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let client = clients.entry(provider).or_insert_with(|| match provider {
+            Provider::OpenAI => LlmClient::OpenAI(openai::Client::from_env()),
+            Provider::Anthropic => LlmClient::Anthropic(anthropic::Client::from_env()),
+            Provider::Gemini => LlmClient::Gemini(gemini::Client::from_env()),
+            Provider::Ollama => LlmClient::Ollama(ollama::Client::from_env()),
+            Provider::Mock => LlmClient::Mock(MockClient::canned()),
+        });
+        Ok(client.clone())
+    }
+
+    // structured output through rig's extractor, the schema comes from T's JsonSchema derive
+    pub async fn extract<T>(
+        &self,
+        model: &str,
+        instructions: &str,
+        input: &str,
+    ) -> Result<T, ExtractionError>
+    where
+        T: JsonSchema + DeserializeOwned + Serialize + Send + Sync + 'static,
+    {
+        match self {
+            LlmClient::OpenAI(client) => {
+                let extractor = client.extractor::<T>(model).preamble(instructions).build();
+                extractor.extract(input).await
+            }
+            LlmClient::Anthropic(client) => {
+                let extractor = client.extractor::<T>(model).preamble(instructions).build();
+                extractor.extract(input).await
+            }
+            LlmClient::Gemini(client) => {
+                let extractor = client.extractor::<T>(model).preamble(instructions).build();
+                extractor.extract(input).await
+            }
+            LlmClient::Ollama(client) => {
+                let extractor = client.extractor::<T>(model).preamble(instructions).build();
+                extractor.extract(input).await
+            }
+            LlmClient::Mock(client) => client.extract(instructions, input),
+        }
+    }
+}
+
+// an agent for whichever provider was picked, with the model it was built for
+pub enum AnyAgent {
+    OpenAI(Agent<openai::CompletionModel>, String),
+    Anthropic(Agent<anthropic::completion::CompletionModel>, String),
+    Gemini(Agent<gemini::completion::CompletionModel>, String),
+    Ollama(Agent<ollama::CompletionModel>, String),
+    Mock(MockAgent, String),
+}
+
+impl AnyAgent {
+    pub fn provider(&self) -> Provider {
+        match self {
+            AnyAgent::OpenAI(..) => Provider::OpenAI,
+            AnyAgent::Anthropic(..) => Provider::Anthropic,
+            AnyAgent::Gemini(..) => Provider::Gemini,
+            AnyAgent::Ollama(..) => Provider::Ollama,
+            AnyAgent::Mock(..) => Provider::Mock,
+        }
+    }
+
+    // for pricing, as passed to the builder
+    pub fn model(&self) -> &str {
+        match self {
+            AnyAgent::OpenAI(_, model)
+            | AnyAgent::Anthropic(_, model)
+            | AnyAgent::Gemini(_, model)
+            | AnyAgent::Ollama(_, model)
+            | AnyAgent::Mock(_, model) => model,
+        }
+    }
+
+    // prompt with prior turns, letting the agent run up to max_turns tool round-trips
+    pub async fn chat(
+        &self,
+        prompt: &str,
+        history: &[Message],
+        max_turns: usize,
+    ) -> Result<String, PromptError> {
+        let mut history = history.to_vec();
+        match self {
+            AnyAgent::OpenAI(agent, _) => {
+                agent
+                    .prompt(prompt)
+                    .with_history(&mut history)
+                    .multi_turn(max_turns)
+                    .await
+            }
+            AnyAgent::Anthropic(agent, _) => {
+                agent
+                    .prompt(prompt)
+                    .with_history(&mut history)
+                    .multi_turn(max_turns)
+                    .await
+            }
+            AnyAgent::Gemini(agent, _) => {
+                agent
+                    .prompt(prompt)
+                    .with_history(&mut history)
+                    .multi_turn(max_turns)
+                    .await
+            }
+            AnyAgent::Ollama(agent, _) => {
+                agent
+                    .prompt(prompt)
+                    .with_history(&mut history)
+                    .multi_turn(max_turns)
+                    .await
+            }
+            AnyAgent::Mock(agent, _) => agent.chat(prompt, &history, max_turns).await,
+        }
+    }
+}
+
+// run the same builder code against the provider's AgentBuilder,
+// `$build` is expanded once per provider so it can stay generic by duck typing
+#[macro_export]
+macro_rules! build_agent {
+    ($client:expr, $model:expr, |$builder:ident| $build:expr) => {
+        match $client {
+            $crate::llm::LlmClient::OpenAI(client) => {
+                let $builder = client.agent($model);
+                $crate::llm::AnyAgent::OpenAI($build, ($model).to_string())
+            }
+            $crate::llm::LlmClient::Anthropic(client) => {
+                let $builder = client.agent($model);
+                $crate::llm::AnyAgent::Anthropic($build, ($model).to_string())
+            }
+            $crate::llm::LlmClient::Gemini(client) => {
+                let $builder = client.agent($model);
+                $crate::llm::AnyAgent::Gemini($build, ($model).to_string())
+            }
+            $crate::llm::LlmClient::Ollama(client) => {
+                let $builder = client.agent($model);
+                $crate::llm::AnyAgent::Ollama($build, ($model).to_string())
+            }
+            $crate::llm::LlmClient::Mock(client) => {
+                let $builder = client.agent($model);
+                $crate::llm::AnyAgent::Mock($build, ($model).to_string())
+            }
+        }
+    };
+}
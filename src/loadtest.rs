@@ -0,0 +1,232 @@
+// synthetic attendee load against a running instance, for capacity planning before the
+// event. the instance has to run in MOCK_MODE so no tokens are spent and the vivatech
+// api is never hit, see the loadtest binary
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use reqwest::{Client, StatusCode};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::task::JoinSet;
+use tokio::time::MissedTickBehavior;
+
+use crate::config;
+
+const TOPICS: &[&str] = &[
+    "AI",
+    "generative AI",
+    "climate tech",
+    "fintech",
+    "mobility",
+    "healthtech",
+    "cybersecurity",
+    "quantum computing",
+    "robotics",
+    "startups raising a seed round",
+];
+
+const INTENTS: &[&str] = &[
+    "Find the best sessions about {topic}",
+    "Which {topic} keynotes should I attend",
+    "I want to meet investors interested in {topic}",
+    "Plan my visit around {topic} and networking",
+    "Show me partner booths working on {topic}",
+];
+
+const WHEN: &[&str] = &["", " today", " tomorrow", " on Friday", " this afternoon"];
+
+#[derive(Debug, thiserror::Error)]
+pub enum LoadTestError {
+    #[error("HTTP client error: {0}")]
+    Client(#[from] reqwest::Error),
+    #[error("{0} is not in MOCK_MODE, set LOADTEST_ALLOW_LIVE=true to spend real tokens")]
+    NotMocked(String),
+}
+
+pub struct LoadTestConfig {
+    pub base_url: String,
+    // requests started per second, whether or not earlier ones have answered
+    pub rps: f64,
+    pub duration: Duration,
+    pub api_key: Option<String>,
+    pub request_timeout: Duration,
+    pub allow_live: bool,
+}
+
+impl LoadTestConfig {
+    pub fn from_env() -> Self {
+        let parsed = |name: &str| {
+            config::env_var(name)
+                .ok()
+                .and_then(|s| s.parse::<f64>().ok())
+        };
+        Self {
+            base_url: config::env_var("LOADTEST_URL")
+                .unwrap_or_else(|_| "http://127.0.0.1:8000".to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            rps: parsed("LOADTEST_RPS").unwrap_or(5.0).max(0.1),
+            duration: Duration::from_secs_f64(
+                parsed("LOADTEST_DURATION_SECONDS").unwrap_or(30.0).max(1.0),
+            ),
+            api_key: config::env_var("LOADTEST_API_KEY")
+                .ok()
+                .filter(|key| !key.is_empty()),
+            request_timeout: Duration::from_secs_f64(
+                parsed("LOADTEST_TIMEOUT_SECONDS").unwrap_or(60.0).max(1.0),
+            ),
+            allow_live: config::env_var("LOADTEST_ALLOW_LIVE")
+                .ok()
+                .and_then(|s| s.parse::<bool>().ok())
+                .unwrap_or(false),
+        }
+    }
+}
+
+// e.g. "Which climate tech keynotes should I attend tomorrow"
+pub fn synthetic_objective(rng: &mut impl Rng) -> String {
+    let topic = TOPICS.choose(rng).copied().unwrap_or("AI");
+    let intent = INTENTS.choose(rng).copied().unwrap_or(INTENTS[0]);
+    let when = WHEN.choose(rng).copied().unwrap_or_default();
+    format!("{}{}", intent.replace("{topic}", topic), when)
+}
+
+enum Outcome {
+    Succeeded,
+    // 429 from the rate limiter or the abuse monitor
+    Throttled,
+    Failed,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LoadTestReport {
+    pub sent: usize,
+    pub succeeded: usize,
+    pub throttled: usize,
+    pub failed: usize,
+    pub elapsed_seconds: f64,
+    // completed requests per second over the whole run
+    pub achieved_rps: f64,
+    // latencies of the successful requests, in milliseconds
+    pub p50_ms: u128,
+    pub p90_ms: u128,
+    pub p95_ms: u128,
+    pub p99_ms: u128,
+    pub max_ms: u128,
+}
+
+impl fmt::Display for LoadTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} requests in {:.1}s ({:.2} rps): {} ok, {} throttled, {} failed",
+            self.sent,
+            self.elapsed_seconds,
+            self.achieved_rps,
+            self.succeeded,
+            self.throttled,
+            self.failed
+        )?;
+        write!(
+            f,
+            "latency p50 {}ms, p90 {}ms, p95 {}ms, p99 {}ms, max {}ms",
+            self.p50_ms, self.p90_ms, self.p95_ms, self.p99_ms, self.max_ms
+        )
+    }
+}
+
+// nearest-rank percentile of sorted latencies, zero when there are none
+pub fn percentile(sorted: &[Duration], percent: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = ((percent / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+// refuses to run against an instance that would call real models, unless allowed
+async fn ensure_mocked(client: &Client, config: &LoadTestConfig) -> Result<(), LoadTestError> {
+    if config.allow_live {
+        return Ok(());
+    }
+    let readiness: Value = client
+        .get(format!("{}/readyz", config.base_url))
+        .send()
+        .await?
+        .json()
+        .await?;
+    let mocked = readiness["checks"]["vivatech_api"]["detail"]
+        .as_str()
+        .is_some_and(|detail| detail.starts_with("MOCK_MODE"));
+    if mocked {
+        Ok(())
+    } else {
+        Err(LoadTestError::NotMocked(config.base_url.clone()))
+    }
+}
+
+// open loop: requests start on schedule even when the service falls behind, so a
+// saturated instance shows up as rising latency instead of a lower request rate
+pub async fn run(config: &LoadTestConfig) -> Result<LoadTestReport, LoadTestError> {
+    let client = Client::builder().timeout(config.request_timeout).build()?;
+    ensure_mocked(&client, config).await?;
+
+    let url = format!("{}/generate-plan", config.base_url);
+    let mut ticker = tokio::time::interval(Duration::from_secs_f64(1.0 / config.rps));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Burst);
+    let mut running = JoinSet::new();
+    let started = Instant::now();
+
+    while started.elapsed() < config.duration {
+        ticker.tick().await;
+        let objective = synthetic_objective(&mut rand::thread_rng());
+        let mut request = client.post(&url).json(&json!({ "objective": objective }));
+        if let Some(key) = &config.api_key {
+            request = request.bearer_auth(key);
+        }
+        running.spawn(async move {
+            let sent_at = Instant::now();
+            let outcome = match request.send().await {
+                Ok(response) if response.status().is_success() => Outcome::Succeeded,
+                Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                    Outcome::Throttled
+                }
+                _ => Outcome::Failed,
+            };
+            (outcome, sent_at.elapsed())
+        });
+    }
+
+    let (mut succeeded, mut throttled, mut failed) = (0, 0, 0);
+    let mut latencies = Vec::new();
+    while let Some(finished) = running.join_next().await {
+        match finished {
+            Ok((Outcome::Succeeded, latency)) => {
+                succeeded += 1;
+                latencies.push(latency);
+            }
+            Ok((Outcome::Throttled, _)) => throttled += 1,
+            Ok((Outcome::Failed, _)) | Err(_) => failed += 1,
+        }
+    }
+    let elapsed = started.elapsed().as_secs_f64();
+    latencies.sort();
+
+    let sent = succeeded + throttled + failed;
+    Ok(LoadTestReport {
+        sent,
+        succeeded,
+        throttled,
+        failed,
+        elapsed_seconds: elapsed,
+        achieved_rps: sent as f64 / elapsed,
+        p50_ms: percentile(&latencies, 50.0).as_millis(),
+        p90_ms: percentile(&latencies, 90.0).as_millis(),
+        p95_ms: percentile(&latencies, 95.0).as_millis(),
+        p99_ms: percentile(&latencies, 99.0).as_millis(),
+        max_ms: latencies.last().copied().unwrap_or_default().as_millis(),
+    })
+}
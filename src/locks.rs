@@ -0,0 +1,215 @@
+// leases for background jobs so only one instance runs each tick
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rand::RngCore;
+use redis::{AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
+use tracing::{info, warn};
+
+use crate::config;
+
+// deletes the key only while it still holds our token, so an expired lease
+// taken over by another instance is never released by us
+const RELEASE_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("DEL", KEYS[1])
+end
+return 0
+"#;
+
+// same check before moving the expiry
+const EXPIRE_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("PEXPIRE", KEYS[1], ARGV[2])
+end
+return 0
+"#;
+
+fn get_lock_prefix() -> String {
+    config::env_var("JOB_LOCK_PREFIX").unwrap_or_else(|_| "vivaagent:lock:".to_string())
+}
+
+// token and expiry of the leases taken on this instance
+type LocalLeases = Arc<Mutex<HashMap<String, (String, Instant)>>>;
+
+#[derive(Clone)]
+enum Backend {
+    // single instance, leases only exclude the other tasks of this process
+    Local(LocalLeases),
+    Redis(redis::Client),
+}
+
+pub struct JobLocks {
+    backend: Backend,
+    prefix: String,
+}
+
+impl JobLocks {
+    // redis when REDIS_URL is set, local otherwise
+    pub fn from_env() -> Self {
+        let backend = match config::env_var("REDIS_URL") {
+            Ok(url) if !url.is_empty() => match redis::Client::open(url) {
+                Ok(client) => {
+                    info!("Background job locks use Redis");
+                    Backend::Redis(client)
+                }
+                Err(e) => {
+                    warn!("Invalid REDIS_URL, job locks are local only: {}", e);
+                    Backend::Local(LocalLeases::default())
+                }
+            },
+            _ => Backend::Local(LocalLeases::default()),
+        };
+
+        Self {
+            backend,
+            prefix: get_lock_prefix(),
+        }
+    }
+
+    // None while another instance holds the lease. the lease expires after
+    // `ttl` so a crashed holder cannot block the job for good.
+    // if redis is unreachable the job is skipped rather than run twice
+    pub async fn try_acquire(&self, job: &str, ttl: Duration) -> Option<JobLease> {
+        let key = format!("{}{}", self.prefix, job);
+        let token = new_token();
+        let client = match &self.backend {
+            Backend::Local(leases) => {
+                let now = Instant::now();
+                let mut leases = leases.lock().unwrap_or_else(PoisonError::into_inner);
+                if leases.get(&key).is_some_and(|(_, expires)| *expires > now) {
+                    return None;
+                }
+                leases.insert(key.clone(), (token.clone(), now + ttl));
+                return Some(JobLease {
+                    key,
+                    token,
+                    backend: Some(self.backend.clone()),
+                });
+            }
+            Backend::Redis(client) => client,
+        };
+
+        let options = SetOptions::default()
+            .conditional_set(ExistenceCheck::NX)
+            .with_expiration(SetExpiry::PX(ttl.as_millis().max(1) as u64));
+
+        let acquired: redis::RedisResult<Option<String>> = async {
+            let mut conn = client.get_multiplexed_async_connection().await?;
+            conn.set_options(&key, &token, options).await
+        }
+        .await;
+
+        match acquired {
+            Ok(Some(_)) => Some(JobLease {
+                key,
+                token,
+                backend: Some(self.backend.clone()),
+            }),
+            Ok(None) => None,
+            Err(e) => {
+                warn!("Could not acquire lock {}: {}", key, e);
+                None
+            }
+        }
+    }
+}
+
+// a local lease is released when dropped, a redis one lapses at its ttl
+pub struct JobLease {
+    key: String,
+    token: String,
+    // taken by hold_for and release
+    backend: Option<Backend>,
+}
+
+impl JobLease {
+    // keep the lease for another `ttl` from now and let it lapse then, so the other
+    // instances skip the job until it is due again
+    pub async fn hold_for(mut self, ttl: Duration) {
+        let client = match self.backend.take() {
+            Some(Backend::Redis(client)) => client,
+            Some(Backend::Local(leases)) => {
+                let mut leases = leases.lock().unwrap_or_else(PoisonError::into_inner);
+                if let Some((token, expires)) = leases.get_mut(&self.key) {
+                    if *token == self.token {
+                        *expires = Instant::now() + ttl;
+                    }
+                }
+                return;
+            }
+            None => return,
+        };
+
+        let held: redis::RedisResult<i32> = async {
+            let mut conn = client.get_multiplexed_async_connection().await?;
+            redis::Script::new(EXPIRE_SCRIPT)
+                .key(&self.key)
+                .arg(&self.token)
+                .arg(ttl.as_millis().max(1) as u64)
+                .invoke_async(&mut conn)
+                .await
+        }
+        .await;
+
+        match held {
+            Ok(0) => warn!("Lock {} expired before the job finished", self.key),
+            Ok(_) => {}
+            Err(e) => warn!("Could not extend lock {}: {}", self.key, e),
+        }
+    }
+
+    // free the lease early, otherwise it lapses at its ttl
+    pub async fn release(mut self) {
+        let client = match self.backend.take() {
+            Some(Backend::Redis(client)) => client,
+            Some(Backend::Local(leases)) => {
+                release_local(&leases, &self.key, &self.token);
+                return;
+            }
+            None => return,
+        };
+
+        let released: redis::RedisResult<i32> = async {
+            let mut conn = client.get_multiplexed_async_connection().await?;
+            redis::Script::new(RELEASE_SCRIPT)
+                .key(&self.key)
+                .arg(&self.token)
+                .invoke_async(&mut conn)
+                .await
+        }
+        .await;
+
+        match released {
+            Ok(0) => warn!("Lock {} expired before the job finished", self.key),
+            Ok(_) => {}
+            Err(e) => warn!("Could not release lock {}: {}", self.key, e),
+        }
+    }
+}
+
+impl Drop for JobLease {
+    fn drop(&mut self) {
+        if let Some(Backend::Local(leases)) = self.backend.take() {
+            release_local(&leases, &self.key, &self.token);
+        }
+    }
+}
+
+// only while the entry still holds our token, like RELEASE_SCRIPT
+fn release_local(leases: &LocalLeases, key: &str, token: &str) {
+    let mut leases = leases.lock().unwrap_or_else(PoisonError::into_inner);
+    if leases.get(key).is_some_and(|(held, _)| held == token) {
+        leases.remove(key);
+    }
+}
+
+fn new_token() -> String {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
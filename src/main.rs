@@ -1,121 +1,54 @@
 // vivatech planner api
 
-use axum::{routing::post, Json, Router};
-use rig::prelude::*;
-use rig::{
-    agent::Agent,
-    completion::Prompt,
-    providers::openai,
-};
-use shuttle_axum::ShuttleAxum;
+use std::net::SocketAddr;
+
+use async_trait::async_trait;
+use axum::Router;
 use shuttle_runtime::SecretStore;
 use tracing::info;
 
-mod models;
-mod tools;
-
-use models::GeneratePlanRequest;
-use tools::QueryVivatechAPI;
-
-// main api endpoint
-async fn generate_plan_handler(Json(payload): Json<GeneratePlanRequest>) -> String {
-    info!(
-        "Received planning request for objective: {}",
-        payload.objective
-    );
-
-    let openai_client = match initialize_openai_client() {
-        Ok(client) => client,
-        Err(e) => {
-            tracing::error!("Failed to initialize OpenAI client: {}", e);
-            return format!("Error: Failed to initialize AI service - {}", e);
-        }
-    };
-
-    // simple test mode without tools
-    if payload.objective.contains("test simple") {
-        info!("Running simple agent test without tools");
-        let simple_agent = openai_client
-            .agent(openai::GPT_4O)
-            .preamble("You are a helpful assistant.")
-            .build();
-
-        match simple_agent.prompt(&payload.objective).await {
-            Ok(response) => {
-                info!("Simple agent response successful");
-                return response;
-            }
-            Err(e) => {
-                tracing::error!("Simple agent failed: {}", e);
-                return format!("Error: Simple agent failed - {}", e);
-            }
-        }
-    }
-
-    let planner_agent = build_planning_agent(openai_client);
-    info!("Planning agent initialized successfully");
-
-    let action_plan = execute_planning_task(&planner_agent, &payload.objective).await;
-
-    info!(
-        "Planning task completed, response length: {} chars",
-        action_plan.len()
-    );
-    action_plan
-}
-
-// setup openai client from env
-fn initialize_openai_client() -> Result<openai::Client, String> {
-    match std::env::var("OPENAI_API_KEY") {
-        Ok(_) => {
-            info!("OpenAI API key found in environment");
-            Ok(openai::Client::from_env())
-        }
-        Err(_) => Err("OPENAI_API_KEY not found in environment".to_string()),
-    }
-}
-
-// build agent with vivatech context
-fn build_planning_agent(client: openai::Client) -> Agent<openai::CompletionModel> {
-    const AGENT_INSTRUCTIONS: &str = "\
-        You are a helpful assistant for Vivatech 2025 conference planning. \
-        Current date: June 11, 2025.\n\n\
-        When asked about sessions or events:\n\
-        1. Use the query_vivatech_api tool to search for relevant information\n\
-        2. Format the results in a clear, organized way for the user\n\
-        3. If sessions have dates, note which ones are happening soon";
-
-    client
-        .agent(openai::GPT_4O)
-        .preamble(AGENT_INSTRUCTIONS)
-        .max_tokens(2048)
-        .temperature(0.7)
-        .tool(QueryVivatechAPI)
-        .build()
-}
+#[cfg(feature = "offline-catalog")]
+use vivaagent::catalog;
+#[cfg(feature = "semantic-fallback")]
+use vivaagent::rag;
+use vivaagent::state::AppState;
+use vivaagent::{
+    catalog_sync, config, copy, enrichment, llm, planner, prompts, rerank, router, selftest,
+    settings, telemetry,
+};
 
-// run the agent with user's request
-async fn execute_planning_task(agent: &Agent<openai::CompletionModel>, objective: &str) -> String {
-    info!("Executing planning task for: {}", objective);
+// the router served with the peer address of each connection, which the stock shuttle axum service leaves out
+struct PlannerService(Router);
 
-    match agent.prompt(objective).await {
-        Ok(response) => {
-            info!("Agent successfully generated response");
-            response
-        }
-        Err(e) => {
-            tracing::error!("Agent execution failed: {}", e);
-            format!("Error: Failed to generate plan - {}", e)
-        }
+#[async_trait]
+impl shuttle_runtime::Service for PlannerService {
+    async fn bind(self, addr: SocketAddr) -> Result<(), shuttle_runtime::Error> {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        router::serve(listener, self.0).await?;
+        Ok(())
     }
 }
 
 // shuttle entry point
 #[shuttle_runtime::main]
-async fn axum(#[shuttle_runtime::Secrets] secret_store: SecretStore) -> ShuttleAxum {
+async fn axum(
+    #[shuttle_runtime::Secrets] secret_store: SecretStore,
+) -> Result<PlannerService, shuttle_runtime::Error> {
     info!("Starting Vivatech Strategic Planner API v1.0");
 
     configure_api_keys(&secret_store);
+    // shuttle's proxy fronts every request and appends the caller to X-Forwarded-For
+    if config::env_var("TRUSTED_PROXY_HOPS").is_err() {
+        std::env::set_var("TRUSTED_PROXY_HOPS", "1");
+    }
+    // after the secrets are exported, before anything reads it
+    config::get();
+    if config::get().trusted_proxy_hops == 0 {
+        tracing::error!(
+            "TRUSTED_PROXY_HOPS=0 behind Shuttle's proxy, keyless clients share its address \
+             for rate limits and abuse strikes"
+        );
+    }
 
     if let Err(e) = validate_required_configuration() {
         tracing::error!("Configuration validation failed: {}", e);
@@ -123,8 +56,29 @@ async fn axum(#[shuttle_runtime::Secrets] secret_store: SecretStore) -> ShuttleA
     }
     info!("All required configuration validated");
 
-    let router = build_router();
-    Ok(router.into())
+    if let Err(e) = selftest::run_startup_self_test().await {
+        tracing::error!("Startup self-test failed: {}", e);
+        panic!("Cannot start service with failing dependencies: {}", e);
+    }
+
+    #[cfg(feature = "offline-catalog")]
+    catalog::init_offline_catalog();
+    #[cfg(feature = "semantic-fallback")]
+    rag::init_semantic_fallback();
+
+    enrichment::init_session_enrichment();
+    rerank::init_reranker();
+    copy::init_copy();
+    telemetry::init_metrics();
+    planner::set_usage_recorder(telemetry::record_token_usage);
+
+    let state = AppState::new();
+    // stored admin overrides, before anything reads the settings or the prompts
+    settings::load(&state.plans);
+    prompts::init_prompts().await;
+    catalog_sync::spawn_catalog_sync(state.catalog_watcher.clone(), state.job_locks.clone());
+
+    Ok(PlannerService(router::build_router(state)))
 }
 
 // load secrets into env vars
@@ -133,7 +87,7 @@ fn configure_api_keys(secret_store: &SecretStore) {
         std::env::set_var("OPENAI_API_KEY", api_key);
         info!("OpenAI API key configured from secrets");
     } else {
-        tracing::warn!("OPENAI_API_KEY not found in secrets - API calls will fail");
+        tracing::warn!("OPENAI_API_KEY not found in secrets - OpenAI models unavailable");
     }
 
     if let Some(api_url) = secret_store.get("VIVATECH_API_URL") {
@@ -152,22 +106,150 @@ fn configure_api_keys(secret_store: &SecretStore) {
         std::env::set_var("CONFERENCE_DATE", date);
         info!("Conference date configured from secrets");
     }
-}
 
-// setup http routes
-fn build_router() -> Router {
-    Router::new().route("/generate-plan", post(generate_plan_handler))
+    for key in [
+        "LLM_PROVIDER",
+        "LLM_MODEL",
+        "LLM_FALLBACK_MODELS",
+        "ANTHROPIC_API_KEY",
+        "GEMINI_API_KEY",
+        "OLLAMA_API_BASE_URL",
+        "WIDGET_SIGNING_SECRET",
+        "WIDGET_ISSUER_KEY",
+        "WIDGET_TOKEN_TTL_SECONDS",
+        "WIDGET_ALLOWED_ORIGINS",
+        "STARTUP_SELF_TEST",
+        "STARTUP_SELF_TEST_STRICT",
+        "STARTUP_SELF_TEST_TIMEOUT_SECONDS",
+        "STARTUP_SELF_TEST_QUERY",
+        "RETRY_MAX_ATTEMPTS",
+        "RETRY_BASE_DELAY_MS",
+        "RETRY_MAX_DELAY_SECONDS",
+        "AGENT_MAX_TURNS",
+        "BATCH_MAX_REQUESTS",
+        "BATCH_CONCURRENCY",
+        "READINESS_TIMEOUT_SECONDS",
+        "PLAN_DB_PATH",
+        "PLAN_LIST_LIMIT",
+        "DUPLICATE_SIMILARITY_THRESHOLD",
+        "DUPLICATE_WINDOW_HOURS",
+        "PUBLIC_BASE_URL",
+        "SHARE_MAX_TTL_SECONDS",
+        "CIRCUIT_FAILURE_THRESHOLD",
+        "CIRCUIT_OPEN_SECONDS",
+        "CHAT_SESSION_TTL_SECONDS",
+        "CHAT_HISTORY_LIMIT",
+        "DRAFT_MODEL",
+        "POLISH_MODEL",
+        "CONFERENCE_PROFILES",
+        "DEFAULT_CONFERENCE",
+        "ADMIN_TOKEN",
+        "ABUSE_BURST_LIMIT",
+        "ABUSE_BURST_WINDOW_SECONDS",
+        "ABUSE_STRIKE_WINDOW_SECONDS",
+        "ABUSE_THROTTLE_AFTER_STRIKES",
+        "ABUSE_SUSPEND_AFTER_STRIKES",
+        "ABUSE_THROTTLE_SECONDS",
+        "OBJECTIVE_MAX_CHARS",
+        "OBJECTIVE_MODERATION_ENABLED",
+        "WEATHER_API_URL",
+        "WEATHER_ENABLED",
+        "ROUTING_API_URL",
+        "ROUTING_API_KEY",
+        "OFFLINE_CATALOG_PATH",
+        "CATALOG_SYNC_INTERVAL_SECONDS",
+        "CATALOG_SEED_QUERIES",
+        "OFFLINE_CATALOG_SEARCH_LIMIT",
+        "CATALOG_ENRICHMENT_ENABLED",
+        "CATALOG_ENRICHMENT_MODEL",
+        "CATALOG_ENRICHMENT_BATCH_SIZE",
+        "RERANK_MODE",
+        "RERANK_TOP_K",
+        "RERANK_EMBEDDING_MODEL",
+        "RERANK_MODEL",
+        "QUERY_CACHE_TTL_SECONDS",
+        "QUERY_CACHE_CAPACITY",
+        "PLAN_CACHE_TTL_SECONDS",
+        "PLAN_CACHE_CAPACITY",
+        "VIVATECH_SIGNING_SECRET",
+        "VIVATECH_SIGNING_KEY_ID",
+        "VIVATECH_SIGNING_MAX_SKEW_SECONDS",
+        "UPSTREAM_SIGNING_KEYS",
+        "VIVATECH_MAX_RESPONSE_BYTES",
+        "SOURCE_MAX_CHARS",
+        "API_KEYS",
+        "AUTH_DISABLED",
+        "API_KEY_RATE_LIMIT_PER_MINUTE",
+        "REDIS_URL",
+        "PLAN_TEMPERATURE",
+        "PLAN_MAX_TOKENS",
+        "PLAN_MAX_TOKENS_LIMIT",
+        "USAGE_DB_PATH",
+        "MOCK_MODE",
+        "MOCK_CATALOG_PATH",
+        "JOB_LOCK_PREFIX",
+        "RAG_DUMP_PATH",
+        "RAG_DUMP_URL",
+        "RAG_EMBEDDING_MODEL",
+        "RAG_SEARCH_LIMIT",
+        "RAG_MIN_SCORE",
+        "PLAN_FOOTER_ENABLED",
+        "PLAN_FOOTER_TEMPLATES",
+        "CHAT_SUMMARY_TOKEN_THRESHOLD",
+        "CHAT_SUMMARY_KEEP_EXCHANGES",
+        "CONVERSATION_STORE",
+        "CONVERSATION_DATABASE_URL",
+        "MODEL_PRICING",
+        "PLAN_JOB_CONCURRENCY",
+        "PLAN_JOB_MAX_PENDING",
+        "PLAN_JOB_RETENTION_SECS",
+        "COPY_PATH",
+        "COPY_DEFAULT_LANGUAGE",
+        "RATE_LIMIT_BURST",
+        "RATE_LIMIT_PER_MINUTE",
+        "TRUSTED_PROXY_HOPS",
+        "POPULARITY_PRIOR_ENABLED",
+        "FEEDBACK_PRIOR_ENABLED",
+        "VIVATECH_STRICT_SCHEMA",
+        "SLACK_SIGNING_SECRET",
+        "TELEGRAM_BOT_TOKEN",
+        "TELEGRAM_WEBHOOK_SECRET",
+        "PROMPT_PREAMBLE",
+        "PROMPT_CRITIC",
+        "PROMPT_FORMATTING",
+        "PROMPT_TEMPLATES_PATH",
+        "PROMPT_TEMPLATES_DATABASE_URL",
+        "PROMPT_TEMPLATES_RELOAD_SECONDS",
+    ] {
+        if let Some(value) = secret_store.get(key) {
+            std::env::set_var(key, value);
+            info!("{} configured from secrets", key);
+        }
+    }
 }
 
 // check required env vars at startup
 fn validate_required_configuration() -> Result<(), String> {
-    if std::env::var("OPENAI_API_KEY").is_err() {
-        return Err("Missing required configuration: OPENAI_API_KEY. \
-             Please set it in Secrets.toml"
+    let provider = llm::default_provider();
+    if let Some(key) = provider.api_key_var() {
+        if config::env_var(key).is_err() {
+            return Err(format!(
+                "Missing required configuration: {} for LLM_PROVIDER={}. \
+                 Please set it in Secrets.toml",
+                key,
+                provider.name()
+            ));
+        }
+    }
+
+    // failing closed, an unset secret must not open the api
+    if config::env_var("API_KEYS").is_err() && !config::get().auth_disabled {
+        return Err("Missing required configuration: API_KEYS. \
+             Please set it in Secrets.toml, or AUTH_DISABLED=true for local development"
             .to_string());
     }
 
-    if std::env::var("VIVATECH_API_URL").is_err() {
+    if config::env_var("VIVATECH_API_URL").is_err() {
         return Err("Missing required configuration: VIVATECH_API_URL. \
              Please set it in Secrets.toml"
             .to_string());
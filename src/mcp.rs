@@ -0,0 +1,202 @@
+// model context protocol server for the vivatech tools, so other agent hosts (claude
+// desktop, ide agents) search the catalog with the same code as the planner. json-rpc
+// messages come over stdio from the mcp binary or over http on POST /mcp
+
+use std::sync::Arc;
+
+use rig::tool::Tool;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::conference::ConferenceProfile;
+use crate::health::CapabilityMatrix;
+use crate::tools::{AssessTimeliness, QueryVivatechAPI};
+
+// newest first, a client asking for another version gets the newest
+const PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+const INSTRUCTIONS: &str = "Search the conference catalog with query_vivatech_api, then pass the \
+sessions you want to attend to assess_event_timeliness to see which need action first.";
+
+#[derive(Debug, Default, Deserialize)]
+pub struct McpQuery {
+    // conference the tools search, the default one when absent
+    #[serde(default)]
+    pub conference: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    jsonrpc: String,
+    // absent on notifications
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct CallParams {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+type RpcError = (i64, String);
+
+pub struct McpServer {
+    profile: ConferenceProfile,
+    health: Option<Arc<CapabilityMatrix>>,
+}
+
+impl McpServer {
+    pub fn for_conference(profile: &ConferenceProfile) -> Self {
+        Self {
+            profile: profile.clone(),
+            health: None,
+        }
+    }
+
+    // searches go through the api's circuit breaker
+    pub fn with_health(mut self, health: Arc<CapabilityMatrix>) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    // built for every call, so the venue clock is current on long-lived stdio servers
+    fn search(&self) -> QueryVivatechAPI {
+        let search = QueryVivatechAPI::for_conference(&self.profile);
+        match &self.health {
+            Some(health) => search.with_health(health.clone()),
+            None => search,
+        }
+    }
+
+    fn timeliness(&self) -> AssessTimeliness {
+        AssessTimeliness::for_conference(&self.profile)
+    }
+
+    // the reply to one raw message, None when it needs none
+    pub async fn handle_message(&self, message: &str) -> Option<Value> {
+        match serde_json::from_str(message) {
+            Ok(message) => self.handle(message).await,
+            Err(e) => Some(error_reply(Value::Null, (PARSE_ERROR, e.to_string()))),
+        }
+    }
+
+    // notifications and replies to requests we never send are not answered
+    pub async fn handle(&self, message: Value) -> Option<Value> {
+        if message.get("method").is_none()
+            && (message.get("result").is_some() || message.get("error").is_some())
+        {
+            return None;
+        }
+        let request = match serde_json::from_value::<Request>(message) {
+            Ok(request) if request.jsonrpc == "2.0" => request,
+            _ => {
+                return Some(error_reply(
+                    Value::Null,
+                    (INVALID_REQUEST, "not a json-rpc 2.0 request".to_string()),
+                ))
+            }
+        };
+        let id = request.id?;
+
+        let result = match request.method.as_str() {
+            "initialize" => Ok(initialize(&request.params)),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": self.list_tools().await })),
+            "tools/call" => self.call_tool(request.params).await,
+            method => Err((METHOD_NOT_FOUND, format!("unknown method '{}'", method))),
+        };
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(error) => error_reply(id, error),
+        })
+    }
+
+    // the same definitions the planning agent sees
+    async fn list_tools(&self) -> Vec<Value> {
+        let definitions = [
+            self.search().definition(String::new()).await,
+            self.timeliness().definition(String::new()).await,
+        ];
+        definitions
+            .into_iter()
+            .map(|definition| {
+                json!({
+                    "name": definition.name,
+                    "description": definition.description,
+                    "inputSchema": definition.parameters,
+                })
+            })
+            .collect()
+    }
+
+    async fn call_tool(&self, params: Value) -> Result<Value, RpcError> {
+        let params: CallParams =
+            serde_json::from_value(params).map_err(|e| (INVALID_PARAMS, e.to_string()))?;
+        let arguments = match params.arguments {
+            Value::Null => json!({}),
+            arguments => arguments,
+        };
+        match params.name.as_str() {
+            name if name == QueryVivatechAPI::NAME => run(&self.search(), arguments).await,
+            name if name == AssessTimeliness::NAME => run(&self.timeliness(), arguments).await,
+            name => Err((INVALID_PARAMS, format!("unknown tool '{}'", name))),
+        }
+    }
+}
+
+fn initialize(params: &Value) -> Value {
+    let version = params
+        .get("protocolVersion")
+        .and_then(Value::as_str)
+        .filter(|version| PROTOCOL_VERSIONS.contains(version))
+        .unwrap_or(PROTOCOL_VERSIONS[0]);
+    json!({
+        "protocolVersion": version,
+        "capabilities": { "tools": { "listChanged": false } },
+        "serverInfo": {
+            "name": env!("CARGO_PKG_NAME"),
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "instructions": INSTRUCTIONS,
+    })
+}
+
+// arguments that don't match the schema are a protocol error, a failing tool is a
+// result the host's model can read and react to
+async fn run<T: Tool>(tool: &T, arguments: Value) -> Result<Value, RpcError> {
+    let args: T::Args = serde_json::from_value(arguments).map_err(|e| {
+        (
+            INVALID_PARAMS,
+            format!("invalid arguments for {}: {}", T::NAME, e),
+        )
+    })?;
+    let (text, is_error) = match tool.call(args).await {
+        Ok(output) => match serde_json::to_string(&output) {
+            Ok(text) => (text, false),
+            Err(e) => (format!("failed to encode the result: {}", e), true),
+        },
+        Err(e) => (e.to_string(), true),
+    };
+    Ok(json!({
+        "content": [{ "type": "text", "text": text }],
+        "isError": is_error,
+    }))
+}
+
+fn error_reply(id: Value, (code, message): RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
@@ -0,0 +1,324 @@
+// offline stand-ins for the llm providers and the vivatech api, turned on with MOCK_MODE
+
+use std::sync::{Arc, OnceLock};
+
+use rig::completion::{CompletionError, Message, PromptError};
+use rig::extractor::ExtractionError;
+use rig::tool::{Tool, ToolSet};
+use serde::de::DeserializeOwned;
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::config;
+use crate::models::VivatechSource;
+
+// canned sessions and partners for local development and the tests
+const BUNDLED_CATALOG: &str = include_str!("../fixtures/vivatech_catalog.json");
+const SEARCH_TOOL: &str = "query_vivatech_api";
+const MAX_SEARCH_RESULTS: usize = 5;
+
+static MOCK_CATALOG: OnceLock<Option<Arc<dyn SessionCatalog>>> = OnceLock::new();
+
+// fake llm and canned catalog instead of the real providers and api, no keys needed
+pub fn mock_mode() -> bool {
+    config::get().mock_mode
+}
+
+// where search results come from while mocked
+pub trait SessionCatalog: Send + Sync {
+    fn search(&self, query: &str, limit: usize) -> Vec<VivatechSource>;
+    fn all(&self) -> Vec<VivatechSource>;
+}
+
+pub struct FixtureCatalog {
+    sources: Vec<VivatechSource>,
+}
+
+impl FixtureCatalog {
+    pub fn from_json(raw: &str) -> Result<Self, serde_json::Error> {
+        Ok(Self {
+            sources: serde_json::from_str(raw)?,
+        })
+    }
+
+    // fixtures/vivatech_catalog.json, compiled in
+    pub fn bundled() -> Self {
+        Self::from_json(BUNDLED_CATALOG).expect("bundled catalog fixture is valid json")
+    }
+
+    // MOCK_CATALOG_PATH when set and readable, the bundled fixture otherwise
+    fn from_env() -> Self {
+        let Ok(path) = config::env_var("MOCK_CATALOG_PATH") else {
+            return Self::bundled();
+        };
+        match std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|raw| Self::from_json(&raw).map_err(|e| e.to_string()))
+        {
+            Ok(catalog) => catalog,
+            Err(e) => {
+                warn!("Ignoring MOCK_CATALOG_PATH {}: {}", path, e);
+                Self::bundled()
+            }
+        }
+    }
+}
+
+impl SessionCatalog for FixtureCatalog {
+    // sources sharing the most query words first, ties by id so results are stable
+    fn search(&self, query: &str, limit: usize) -> Vec<VivatechSource> {
+        let terms: Vec<String> = query
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| word.len() >= 3)
+            .map(str::to_lowercase)
+            .collect();
+
+        let mut matches: Vec<(usize, &VivatechSource)> = self
+            .sources
+            .iter()
+            .filter_map(|source| {
+                let text = source.text_chunk.to_lowercase();
+                let hits = terms
+                    .iter()
+                    .filter(|term| text.contains(term.as_str()))
+                    .count();
+                (hits > 0).then_some((hits, source))
+            })
+            .collect();
+        matches.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.id.cmp(&b.1.id)));
+
+        let best = matches.first().map(|(hits, _)| *hits).unwrap_or(1).max(1);
+        matches
+            .into_iter()
+            .take(limit)
+            .map(|(hits, source)| VivatechSource {
+                score: hits as f32 / best as f32,
+                ..source.clone()
+            })
+            .collect()
+    }
+
+    fn all(&self) -> Vec<VivatechSource> {
+        self.sources.clone()
+    }
+}
+
+// the catalog the search tool and the catalog sync use instead of the api, if any
+pub fn mock_catalog() -> Option<Arc<dyn SessionCatalog>> {
+    MOCK_CATALOG
+        .get_or_init(|| {
+            mock_mode().then(|| {
+                info!("MOCK_MODE: Vivatech searches are served from the fixture catalog");
+                Arc::new(FixtureCatalog::from_env()) as Arc<dyn SessionCatalog>
+            })
+        })
+        .clone()
+}
+
+// mocks the vivatech api for this process, e.g. from a test. false if already decided
+pub fn install_mock_catalog(catalog: Arc<dyn SessionCatalog>) -> bool {
+    MOCK_CATALOG.set(Some(catalog)).is_ok()
+}
+
+pub fn search_mock_catalog(query: &str) -> Option<Vec<VivatechSource>> {
+    mock_catalog().map(|catalog| catalog.search(query, MAX_SEARCH_RESULTS))
+}
+
+// what the fake model sees on each turn
+pub struct MockContext<'a> {
+    pub preamble: &'a str,
+    pub prompt: &'a str,
+    // names of the tools the agent was built with
+    pub tools: &'a [String],
+    // raw outputs of the tools called so far in this run
+    pub tool_results: &'a [String],
+    // false on the last turn, a tool call then ends the run with an error
+    pub can_call_tools: bool,
+}
+
+pub enum MockTurn {
+    CallTool {
+        name: String,
+        arguments: serde_json::Value,
+    },
+    Answer(String),
+}
+
+// stands in for the model, tests can script their own
+pub trait MockResponder: Send + Sync {
+    fn next_turn(&self, context: &MockContext<'_>) -> MockTurn;
+
+    // structured output, None fails like a model that returned nothing usable
+    fn extract(&self, _instructions: &str, _input: &str) -> Option<serde_json::Value> {
+        None
+    }
+}
+
+// searches once with the prompt, then lists what it found as a numbered plan.
+// agents without the search tool get the prompt echoed back
+pub struct CannedPlanner;
+
+impl MockResponder for CannedPlanner {
+    fn next_turn(&self, context: &MockContext<'_>) -> MockTurn {
+        let can_search = context.tools.iter().any(|tool| tool == SEARCH_TOOL);
+        if !can_search {
+            return MockTurn::Answer(format!("[mock] {}", context.prompt.trim()));
+        }
+        if context.tool_results.is_empty() && context.can_call_tools {
+            return MockTurn::CallTool {
+                name: SEARCH_TOOL.to_string(),
+                arguments: json!({ "query": context.prompt }),
+            };
+        }
+
+        let sources: Vec<VivatechSource> = context
+            .tool_results
+            .iter()
+            .filter_map(|output| serde_json::from_str::<Vec<VivatechSource>>(output).ok())
+            .flatten()
+            .collect();
+        if sources.is_empty() {
+            return MockTurn::Answer(format!(
+                "No sessions in the mock catalog match \"{}\".",
+                context.prompt.trim()
+            ));
+        }
+
+        let steps = sources
+            .iter()
+            .enumerate()
+            .map(|(index, source)| {
+                let title = source.text_chunk.lines().next().unwrap_or_default();
+                format!("{}. {} [{}]", index + 1, title.trim(), source.id)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        MockTurn::Answer(format!(
+            "Plan for \"{}\":\n{}",
+            context.prompt.trim(),
+            steps
+        ))
+    }
+}
+
+#[derive(Clone)]
+pub struct MockClient {
+    responder: Arc<dyn MockResponder>,
+}
+
+impl MockClient {
+    pub fn new(responder: Arc<dyn MockResponder>) -> Self {
+        Self { responder }
+    }
+
+    pub fn canned() -> Self {
+        Self::new(Arc::new(CannedPlanner))
+    }
+
+    // same shape as rig's client so build_agent! works unchanged
+    pub fn agent(&self, _model: &str) -> MockAgentBuilder {
+        MockAgentBuilder {
+            preamble: String::new(),
+            tools: ToolSet::default(),
+            tool_names: Vec::new(),
+            responder: self.responder.clone(),
+        }
+    }
+
+    pub fn extract<T: DeserializeOwned>(
+        &self,
+        instructions: &str,
+        input: &str,
+    ) -> Result<T, ExtractionError> {
+        let value = self
+            .responder
+            .extract(instructions, input)
+            .ok_or(ExtractionError::NoData)?;
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+pub struct MockAgentBuilder {
+    preamble: String,
+    tools: ToolSet,
+    tool_names: Vec<String>,
+    responder: Arc<dyn MockResponder>,
+}
+
+impl MockAgentBuilder {
+    pub fn preamble(mut self, preamble: &str) -> Self {
+        self.preamble = preamble.to_string();
+        self
+    }
+
+    // sampling settings mean nothing to the fake model
+    pub fn max_tokens(self, _max_tokens: u64) -> Self {
+        self
+    }
+
+    pub fn temperature(self, _temperature: f64) -> Self {
+        self
+    }
+
+    pub fn tool<T: Tool + 'static>(mut self, tool: T) -> Self {
+        self.tool_names.push(T::NAME.to_string());
+        self.tools.add_tool(tool);
+        self
+    }
+
+    pub fn build(self) -> MockAgent {
+        MockAgent {
+            preamble: self.preamble,
+            tools: self.tools,
+            tool_names: self.tool_names,
+            responder: self.responder,
+        }
+    }
+}
+
+// runs the real tools, only the model is fake
+pub struct MockAgent {
+    preamble: String,
+    tools: ToolSet,
+    tool_names: Vec<String>,
+    responder: Arc<dyn MockResponder>,
+}
+
+impl MockAgent {
+    pub async fn chat(
+        &self,
+        prompt: &str,
+        _history: &[Message],
+        max_turns: usize,
+    ) -> Result<String, PromptError> {
+        let mut tool_results = Vec::new();
+        for turn in 0..=max_turns {
+            let context = MockContext {
+                preamble: &self.preamble,
+                prompt,
+                tools: &self.tool_names,
+                tool_results: &tool_results,
+                can_call_tools: turn < max_turns,
+            };
+            match self.responder.next_turn(&context) {
+                MockTurn::Answer(text) => return Ok(text),
+                MockTurn::CallTool { .. } if turn == max_turns => break,
+                MockTurn::CallTool { name, arguments } => {
+                    // errors go back to the model like in a real run
+                    let output = match self.tools.call(&name, arguments.to_string()).await {
+                        Ok(output) => output,
+                        Err(e) => e.to_string(),
+                    };
+                    tool_results.push(output);
+                }
+            }
+        }
+
+        Err(PromptError::CompletionError(
+            CompletionError::ResponseError(format!(
+                "mock model still calling tools after {} turns",
+                max_turns
+            )),
+        ))
+    }
+}
@@ -1,21 +1,26 @@
 // data models for vivatech api
 
-use chrono::NaiveDate;
+use std::collections::{BTreeMap, HashMap};
+
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-// vivatech 2025 defaults
-const VIVATECH_YEAR: i32 = 2025;
-const CURRENT_MONTH: u32 = 6; // June
-const CURRENT_DAY: u32 = 11;
+use crate::citations::Citation;
+use crate::enrichment::{SessionFacets, Topic};
+use crate::itinerary;
+use crate::language;
+use crate::programme::{self, Partner, Session};
+use crate::tone::Tone;
+use crate::tools::ItinerarySession;
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
 pub enum ActionUrgency {
     Immediate,
     Soon,
     Normal,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct VivatechSource {
     pub id: String,
     #[serde(default)]
@@ -23,6 +28,204 @@ pub struct VivatechSource {
     #[serde(default)]
     pub score: f32,
     pub text_chunk: String,
+    // filled from the enrichment cache, never sent by the upstream api
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub facets: Option<SessionFacets>,
+    // structured fields the upstream sends for some records, see programme
+    #[serde(
+        default,
+        deserialize_with = "programme::lenient",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub session: Option<Session>,
+    #[serde(
+        default,
+        deserialize_with = "programme::lenient",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub partner: Option<Partner>,
+}
+
+// vivatech's programme tracks, what filter_by_track narrows sources to. classified
+// facets decide when a source has them, the keywords cover the ones that don't
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Track {
+    Ai,
+    Mobility,
+    ClimateTech,
+    Fintech,
+    Healthtech,
+    Cybersecurity,
+    Startups,
+    Retail,
+    Media,
+    Web3,
+}
+
+impl Track {
+    pub const ALL: [Track; 10] = [
+        Track::Ai,
+        Track::Mobility,
+        Track::ClimateTech,
+        Track::Fintech,
+        Track::Healthtech,
+        Track::Cybersecurity,
+        Track::Startups,
+        Track::Retail,
+        Track::Media,
+        Track::Web3,
+    ];
+
+    // as the programme names it, also the search term for the track
+    pub fn label(self) -> &'static str {
+        match self {
+            Track::Ai => "AI",
+            Track::Mobility => "Mobility",
+            Track::ClimateTech => "Climate Tech",
+            Track::Fintech => "Fintech",
+            Track::Healthtech => "Healthtech",
+            Track::Cybersecurity => "Cybersecurity",
+            Track::Startups => "Startups",
+            Track::Retail => "Retail",
+            Track::Media => "Media & Entertainment",
+            Track::Web3 => "Web3",
+        }
+    }
+
+    pub fn topic(self) -> Topic {
+        match self {
+            Track::Ai => Topic::Ai,
+            Track::Mobility => Topic::Mobility,
+            Track::ClimateTech => Topic::Climate,
+            Track::Fintech => Topic::Fintech,
+            Track::Healthtech => Topic::Health,
+            Track::Cybersecurity => Topic::Cybersecurity,
+            Track::Startups => Topic::Startups,
+            Track::Retail => Topic::Retail,
+            Track::Media => Topic::Media,
+            Track::Web3 => Topic::Web3,
+        }
+    }
+
+    // whole words or phrases, lowercase, english and french
+    pub fn keywords(self) -> &'static [&'static str] {
+        match self {
+            Track::Ai => &[
+                "ai",
+                "ia",
+                "artificial intelligence",
+                "intelligence artificielle",
+                "genai",
+                "generative",
+                "llm",
+                "machine learning",
+                "agents",
+            ],
+            Track::Mobility => &[
+                "mobility",
+                "mobilité",
+                "automotive",
+                "autonomous",
+                "electric vehicles",
+                "ev",
+                "transport",
+                "aerospace",
+            ],
+            Track::ClimateTech => &[
+                "climate",
+                "climat",
+                "sustainability",
+                "sustainable",
+                "durable",
+                "green",
+                "energy",
+                "énergie",
+                "carbon",
+                "net zero",
+            ],
+            Track::Fintech => &[
+                "fintech",
+                "finance",
+                "banking",
+                "banque",
+                "payments",
+                "paiement",
+                "insurtech",
+            ],
+            Track::Healthtech => &[
+                "health",
+                "healthcare",
+                "healthtech",
+                "santé",
+                "medtech",
+                "biotech",
+                "medical",
+            ],
+            Track::Cybersecurity => &[
+                "cybersecurity",
+                "cybersécurité",
+                "security",
+                "sécurité",
+                "privacy",
+            ],
+            Track::Startups => &[
+                "startup",
+                "startups",
+                "founders",
+                "fondateurs",
+                "venture",
+                "investors",
+                "funding",
+                "scale-up",
+            ],
+            Track::Retail => &[
+                "retail",
+                "commerce",
+                "e-commerce",
+                "luxury",
+                "luxe",
+                "consumer",
+            ],
+            Track::Media => &[
+                "media",
+                "médias",
+                "entertainment",
+                "gaming",
+                "creators",
+                "sport",
+                "music",
+            ],
+            Track::Web3 => &[
+                "web3",
+                "blockchain",
+                "crypto",
+                "nft",
+                "metaverse",
+                "tokenization",
+            ],
+        }
+    }
+
+    // the classified topics when the source has facets, keywords in its text otherwise
+    pub fn matches(self, source: &VivatechSource) -> bool {
+        if let Some(facets) = &source.facets {
+            return facets.topics.contains(&self.topic());
+        }
+        let text = format!(
+            " {} ",
+            source
+                .text_chunk
+                .to_lowercase()
+                .split(|c: char| !c.is_alphanumeric() && c != '-')
+                .filter(|word| !word.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        self.keywords()
+            .iter()
+            .any(|keyword| text.contains(&format!(" {} ", keyword)))
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -40,19 +243,540 @@ pub struct VivatechQueryResponse {
     pub metadata: VivatechMetadata,
 }
 
-#[derive(Debug, Deserialize)]
+// the upstream contract, field names with their json types
+const RESPONSE_FIELDS: &[(&str, &str)] = &[
+    ("answer", "string"),
+    ("sources", "array"),
+    ("metadata", "object"),
+];
+const SOURCE_FIELDS: &[(&str, &str)] = &[
+    ("id", "string"),
+    ("source_table", "string"),
+    ("score", "number"),
+    ("text_chunk", "string"),
+];
+// sent for some records only, checked when present
+const OPTIONAL_SOURCE_FIELDS: &[(&str, &str)] = &[("session", "object"), ("partner", "object")];
+const METADATA_FIELDS: &[(&str, &str)] = &[("search_mode", "string"), ("sources_found", "number")];
+
+// one way a response differs from the contract, see VIVATECH_STRICT_SCHEMA
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaDrift {
+    // "unknown_field", "missing_field" or "wrong_type"
+    pub kind: &'static str,
+    // e.g. "sources[3].speaker"
+    pub path: String,
+    // json type the contract has, "none" for unknown fields
+    pub expected: &'static str,
+    pub found: String,
+}
+
+impl SchemaDrift {
+    // the path without array indexes, e.g. "sources[].speaker", for metric labels
+    pub fn field(&self) -> String {
+        let mut field = String::with_capacity(self.path.len());
+        let mut in_index = false;
+        for c in self.path.chars() {
+            match c {
+                '[' => {
+                    in_index = true;
+                    field.push('[');
+                }
+                ']' => {
+                    in_index = false;
+                    field.push(']');
+                }
+                _ if in_index => {}
+                _ => field.push(c),
+            }
+        }
+        field
+    }
+}
+
+impl VivatechQueryResponse {
+    // every difference between a raw response and the fields we parse, including the
+    // ones serde would silently ignore or default
+    pub fn schema_drift(value: &serde_json::Value) -> Vec<SchemaDrift> {
+        let mut drift = Vec::new();
+        check_object(value, "", RESPONSE_FIELDS, &[], &mut drift);
+        if let Some(sources) = value.get("sources").and_then(|v| v.as_array()) {
+            for (i, source) in sources.iter().enumerate() {
+                check_object(
+                    source,
+                    &format!("sources[{}]", i),
+                    SOURCE_FIELDS,
+                    OPTIONAL_SOURCE_FIELDS,
+                    &mut drift,
+                );
+            }
+        }
+        if let Some(metadata) = value.get("metadata").filter(|v| v.is_object()) {
+            check_object(metadata, "metadata", METADATA_FIELDS, &[], &mut drift);
+        }
+        drift
+    }
+}
+
+fn check_object(
+    value: &serde_json::Value,
+    path: &str,
+    fields: &[(&str, &'static str)],
+    optional: &[(&str, &'static str)],
+    drift: &mut Vec<SchemaDrift>,
+) {
+    let join = |name: &str| {
+        if path.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", path, name)
+        }
+    };
+    let Some(object) = value.as_object() else {
+        drift.push(SchemaDrift {
+            kind: "wrong_type",
+            path: if path.is_empty() {
+                "$".to_string()
+            } else {
+                path.to_string()
+            },
+            expected: "object",
+            found: json_type(value).to_string(),
+        });
+        return;
+    };
+
+    for &(name, expected) in fields {
+        match object.get(name) {
+            None => drift.push(SchemaDrift {
+                kind: "missing_field",
+                path: join(name),
+                expected,
+                found: "none".to_string(),
+            }),
+            Some(found) if json_type(found) != expected => drift.push(SchemaDrift {
+                kind: "wrong_type",
+                path: join(name),
+                expected,
+                found: json_type(found).to_string(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for &(name, expected) in optional {
+        match object.get(name) {
+            Some(found) if json_type(found) != expected => drift.push(SchemaDrift {
+                kind: "wrong_type",
+                path: join(name),
+                expected,
+                found: json_type(found).to_string(),
+            }),
+            _ => {}
+        }
+    }
+    for (name, found) in object {
+        if !fields
+            .iter()
+            .chain(optional)
+            .any(|(known, _)| *known == name.as_str())
+        {
+            drift.push(SchemaDrift {
+                kind: "unknown_field",
+                path: join(name),
+                expected: "none",
+                found: json_type(found).to_string(),
+            });
+        }
+    }
+}
+
+fn json_type(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "boolean",
+        serde_json::Value::Number(_) => "number",
+        serde_json::Value::String(_) => "string",
+        serde_json::Value::Array(_) => "array",
+        serde_json::Value::Object(_) => "object",
+    }
+}
+
+// what the planner should return
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputMode {
+    #[default]
+    Plan,
+    NextAction,
+    // plan plus ordered time slots validated against the sources
+    Itinerary,
+}
+
+// how the plan is generated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GenerationStrategy {
+    #[default]
+    Standard,
+    // cheap model drafts with tools, large model polishes
+    TwoStage,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct GeneratePlanRequest {
     pub objective: String,
+    #[serde(default)]
+    pub mode: OutputMode,
+    // conference profile id, defaults to the configured conference
+    #[serde(default)]
+    pub conference: Option<String>,
+    // loads the stored profile for personalization
+    #[serde(default)]
+    pub user_id: Option<String>,
+    #[serde(default)]
+    pub strategy: GenerationStrategy,
+    // return supporting snippets with offsets alongside the plan
+    #[serde(default)]
+    pub include_citations: bool,
+    // overrides the user's preferred tone
+    #[serde(default)]
+    pub tone: Option<Tone>,
+    // "provider:model" for this request only, e.g. to A/B models;
+    // two_stage drafts with DRAFT_MODEL and polishes with this one
+    #[serde(default)]
+    pub model: Option<String>,
+    // 0 to 2, PLAN_TEMPERATURE when unset
+    #[serde(default)]
+    pub temperature: Option<f64>,
+    // up to PLAN_MAX_TOKENS_LIMIT, PLAN_MAX_TOKENS when unset
+    #[serde(default)]
+    pub max_tokens: Option<u64>,
+    // language of the plan, e.g. "fr" or "Spanish"
+    #[serde(default)]
+    pub language: Option<String>,
+    // plan mode only: sessions are written as placeholders resolved against
+    // the current catalog whenever the stored plan is read
+    #[serde(default)]
+    pub live_placeholders: bool,
+    // e.g. {"team": "sales", "kiosk-id": "k12"}, kept with the stored plan and
+    // used to group token spend for chargeback
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub cost_tags: BTreeMap<String, String>,
+    // skip the "did you mean your earlier plan?" check
+    #[serde(default)]
+    pub regenerate: bool,
+    // always run the agent, even when the plan cache holds this objective
+    #[serde(default)]
+    pub bypass_cache: bool,
 }
 
-// get conference date from env or use default
-pub fn get_current_conference_date() -> NaiveDate {
-    if let Ok(date_str) = std::env::var("CONFERENCE_DATE") {
-        if let Ok(date) = NaiveDate::parse_from_str(&date_str, "%Y-%m-%d") {
-            return date;
+impl GeneratePlanRequest {
+    // plan mode with defaults for everything else
+    pub fn simple(objective: String, conference: Option<String>) -> Self {
+        Self {
+            objective,
+            mode: OutputMode::Plan,
+            conference,
+            user_id: None,
+            strategy: GenerationStrategy::Standard,
+            include_citations: false,
+            tone: None,
+            model: None,
+            temperature: None,
+            max_tokens: None,
+            language: None,
+            live_placeholders: false,
+            cost_tags: BTreeMap::new(),
+            regenerate: false,
+            bypass_cache: false,
         }
     }
-    
-    NaiveDate::from_ymd_opt(VIVATECH_YEAR, CURRENT_MONTH, CURRENT_DAY)
-        .expect("June 11, 2025 is a valid date")
+
+    // without an explicit language the plan is written in the objective's, when it
+    // can be told. runs before the cache and coalescing keys are taken
+    pub fn with_detected_language(mut self) -> Self {
+        if self
+            .language
+            .as_deref()
+            .map_or(true, |l| l.trim().is_empty())
+        {
+            self.language = language::detect(&self.objective).map(str::to_string);
+        }
+        self
+    }
+
+    // requests with the same key can share one agent run
+    pub fn coalescing_key(&self) -> String {
+        let objective = self
+            .objective
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+        let conference = self.conference.as_deref().unwrap_or_default();
+        let user_id = self.user_id.as_deref().unwrap_or_default();
+        let model = self.model.as_deref().unwrap_or_default();
+        let language = self.language.as_deref().unwrap_or_default();
+        format!(
+            "{}:{}:{:?}:{:?}:{:?}:{}:{:?}:{:?}:{}:{}:{}",
+            conference,
+            user_id,
+            self.mode,
+            self.strategy,
+            self.tone,
+            model,
+            self.temperature,
+            self.max_tokens,
+            language,
+            self.live_placeholders,
+            objective
+        )
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct TemplatePlanRequest {
+    pub template_id: String,
+    #[serde(default)]
+    pub parameters: HashMap<String, String>,
+    // any other GeneratePlanRequest field (mode, conference, ...)
+    #[serde(flatten)]
+    pub options: serde_json::Map<String, serde_json::Value>,
+}
+
+impl TemplatePlanRequest {
+    pub fn into_plan_request(self, objective: String) -> Result<GeneratePlanRequest, String> {
+        let mut fields = self.options;
+        fields.insert(
+            "objective".to_string(),
+            serde_json::Value::String(objective),
+        );
+        serde_json::from_value(serde_json::Value::Object(fields))
+            .map_err(|e| format!("Invalid plan options: {}", e))
+    }
+}
+
+// result of one agent run, shared between coalesced requests
+#[derive(Debug, Clone)]
+pub struct PlanOutcome {
+    pub body: String,
+    // set in next_action mode, body then holds the action text
+    pub next_action: Option<NextAction>,
+    // set in itinerary mode, body then holds the prose plan
+    pub itinerary: Option<itinerary::Plan>,
+    pub sources: Vec<VivatechSource>,
+    pub model: String,
+    // the requested model, when a fallback model wrote the plan instead
+    pub fallback_from: Option<String>,
+    // set when the llm was unavailable, body then holds a templated listing
+    pub listing: Option<Vec<ListedSession>>,
+}
+
+// a search hit in a retrieval-only answer, see degraded.rs
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ListedSession {
+    pub source_id: String,
+    pub summary: String,
+    pub urgency: ActionUrgency,
+    // what the urgency analyzer made of the date
+    pub timing: String,
+}
+
+// tokens the agent runs of one request used, cost estimated from the model price table
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+    pub estimated_cost_usd: f64,
+}
+
+impl TokenUsage {
+    pub fn add(&mut self, prompt_tokens: u64, completion_tokens: u64, cost_usd: f64) {
+        self.prompt_tokens += prompt_tokens;
+        self.completion_tokens += completion_tokens;
+        self.total_tokens += prompt_tokens + completion_tokens;
+        self.estimated_cost_usd += cost_usd;
+    }
+}
+
+// body of /generate-plan, failures come with a non-2xx status and an ErrorEnvelope
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum GeneratePlanResponse {
+    Success {
+        // for GET /plans/{id}/steps/{n}/explain
+        #[serde(skip_serializing_if = "Option::is_none")]
+        plan_id: Option<String>,
+        plan: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        next_action: Option<NextAction>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        itinerary: Option<itinerary::Plan>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        citations: Option<Vec<Citation>>,
+        // the llm provider was unavailable, plan is a listing of search hits by urgency
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        degraded: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        listing: Option<Vec<ListedSession>>,
+        // ids of the vivatech sources the agent looked at, each once across all its
+        // searches, best score first
+        sources_used: Vec<String>,
+        model: String,
+        // the requested model, when it was unavailable and `model` answered instead
+        #[serde(skip_serializing_if = "Option::is_none")]
+        fallback_from: Option<String>,
+        // unset for requests that shared another request's agent run
+        #[serde(skip_serializing_if = "Option::is_none")]
+        usage: Option<TokenUsage>,
+        elapsed_ms: u64,
+    },
+    // the user recently got a plan for nearly the same objective, nothing was generated
+    Duplicate {
+        earlier_plan_id: String,
+        earlier_objective: String,
+        age_seconds: i64,
+        similarity: f64,
+        hint: String,
+        elapsed_ms: u64,
+    },
+    Error {
+        #[serde(flatten)]
+        error: ErrorEnvelope,
+        elapsed_ms: u64,
+    },
+}
+
+// body of every failed request, the plan response's error variant included
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ErrorEnvelope {
+    // e.g. "validation_error", stable for clients to branch on
+    pub code: String,
+    pub message: String,
+    // X-Request-Id of the failed request, for support and replays
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    // finer cause within the code, e.g. "prompt_injection" for objective_rejected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    // plans to ask for one by one instead, e.g. the plans of a batch to replay
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan_ids: Option<Vec<String>>,
+}
+
+impl ErrorEnvelope {
+    pub fn with_reason(mut self, reason: &str) -> Self {
+        self.reason = Some(reason.to_string());
+        self
+    }
+
+    pub fn with_plan_ids(mut self, plan_ids: Vec<String>) -> Self {
+        self.plan_ids = Some(plan_ids);
+        self
+    }
+}
+
+impl GeneratePlanResponse {
+    pub fn success(
+        outcome: PlanOutcome,
+        plan_id: Option<String>,
+        citations: Option<Vec<Citation>>,
+        elapsed_ms: u64,
+    ) -> Self {
+        GeneratePlanResponse::Success {
+            plan_id,
+            plan: outcome.body,
+            next_action: outcome.next_action,
+            itinerary: outcome.itinerary,
+            citations,
+            degraded: outcome.listing.is_some(),
+            listing: outcome.listing,
+            sources_used: outcome
+                .sources
+                .into_iter()
+                .map(|source| source.id)
+                .collect(),
+            model: outcome.model,
+            fallback_from: outcome.fallback_from,
+            usage: None,
+            elapsed_ms,
+        }
+    }
+
+    pub fn with_usage(mut self, spent: TokenUsage) -> Self {
+        if let GeneratePlanResponse::Success { usage, .. } = &mut self {
+            *usage = (spent.total_tokens > 0).then_some(spent);
+        }
+        self
+    }
+
+    // a cached plan cost no tokens this time
+    pub fn served_from_cache(mut self, elapsed: u64) -> Self {
+        if let GeneratePlanResponse::Success {
+            usage, elapsed_ms, ..
+        } = &mut self
+        {
+            *usage = None;
+            *elapsed_ms = elapsed;
+        }
+        self
+    }
+
+    pub fn error(error: ErrorEnvelope, elapsed_ms: u64) -> Self {
+        GeneratePlanResponse::Error { error, elapsed_ms }
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct BatchPlanRequest {
+    pub requests: Vec<GeneratePlanRequest>,
+}
+
+// one entry of a batch, streamed in completion order so index says which request it answers
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct BatchPlanItem {
+    pub index: usize,
+    pub http_status: u16,
+    #[serde(flatten)]
+    pub response: GeneratePlanResponse,
+}
+
+// single prioritized action for the "what now?" use case
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct NextAction {
+    pub action: String,
+    pub location: String,
+    pub reason: String,
+    pub source_id: String,
+    pub urgency: ActionUrgency,
+    #[serde(default)]
+    pub starts_at: Option<String>,
+}
+
+impl NextAction {
+    // the model sometimes wraps json in a code fence or adds prose around it
+    pub fn from_agent_output(raw: &str) -> Result<Self, String> {
+        let start = raw.find('{');
+        let end = raw.rfind('}');
+        let json = match (start, end) {
+            (Some(start), Some(end)) if start < end => &raw[start..=end],
+            _ => return Err("Agent response did not contain a JSON object".to_string()),
+        };
+
+        serde_json::from_str(json).map_err(|e| format!("Invalid next action from agent: {}", e))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IcalExportRequest {
+    // sets the timezone the session times are read in
+    #[serde(default)]
+    pub conference: Option<String>,
+    pub sessions: Vec<ItinerarySession>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CatalogChangesQuery {
+    pub limit: Option<usize>,
 }
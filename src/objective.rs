@@ -0,0 +1,104 @@
+// checks on a plan request's objective before any tokens are spent. the abuse
+// heuristics screen every objective, this adds length limits and, when enabled, a
+// moderation api call for the malicious asks the heuristics can't see
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde_json::json;
+use tracing::warn;
+
+use crate::config;
+use crate::http;
+
+const MODERATION_URL: &str = "https://api.openai.com/v1/moderations";
+const MODERATION_MODEL: &str = "omni-moderation-latest";
+const MODERATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, thiserror::Error)]
+pub enum ObjectiveError {
+    #[error("the objective is empty")]
+    Empty,
+    #[error("the objective is longer than {0} characters")]
+    TooLong(usize),
+}
+
+impl ObjectiveError {
+    // the `reason` of an invalid_objective error
+    pub fn reason(&self) -> &'static str {
+        match self {
+            ObjectiveError::Empty => "empty",
+            ObjectiveError::TooLong(_) => "too_long",
+        }
+    }
+}
+
+pub fn validate(objective: &str) -> Result<(), ObjectiveError> {
+    let max_chars = config::get().objective_max_chars;
+    if objective.trim().is_empty() {
+        Err(ObjectiveError::Empty)
+    } else if objective.chars().count() > max_chars {
+        Err(ObjectiveError::TooLong(max_chars))
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ModerationResponse {
+    results: Vec<ModerationResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModerationResult {
+    flagged: bool,
+    #[serde(default)]
+    categories: HashMap<String, bool>,
+}
+
+// the flagged categories, e.g. ["harassment", "violence"], None when the objective
+// passed. an unreachable moderation api lets the request through
+pub async fn moderate(objective: &str) -> Option<Vec<String>> {
+    let config = config::get();
+    if !config::runtime().objective_moderation_enabled || config.mock_mode {
+        return None;
+    }
+    let api_key = config.openai_api_key.as_deref()?;
+
+    let moderation = async {
+        http::client()
+            .post(MODERATION_URL)
+            .bearer_auth(api_key)
+            .timeout(MODERATION_TIMEOUT)
+            .json(&json!({ "model": MODERATION_MODEL, "input": objective }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<ModerationResponse>()
+            .await
+    };
+    match moderation.await {
+        Ok(moderation) => {
+            let flagged = moderation
+                .results
+                .into_iter()
+                .find(|result| result.flagged)?;
+            let mut categories: Vec<String> = flagged
+                .categories
+                .into_iter()
+                .filter(|(_, hit)| *hit)
+                .map(|(category, _)| category)
+                .collect();
+            categories.sort();
+            Some(categories)
+        }
+        Err(e) => {
+            warn!(
+                "Objective moderation failed, letting the request through: {}",
+                e
+            );
+            None
+        }
+    }
+}
@@ -0,0 +1,711 @@
+// openapi 3 document for client sdk generators, schemas are derived from the
+// request and response types so they follow the handlers
+
+use std::sync::OnceLock;
+
+use axum::{response::Html, Json};
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::JsonSchema;
+use serde_json::{json, Map, Value};
+
+use crate::compare::{ComparePlansRequest, PlanComparison};
+use crate::copy::Branding;
+use crate::feedback::{PlanFeedbackRequest, PlanFeedbackResponse};
+use crate::google_calendar::{GoogleCalendarSyncRequest, GoogleCalendarSyncResponse};
+use crate::jobs::{JobStatus, JobView};
+use crate::models::{
+    BatchPlanItem, BatchPlanRequest, ErrorEnvelope, GeneratePlanRequest, GeneratePlanResponse,
+    TemplatePlanRequest,
+};
+use crate::plans::{ExplainResponse, PlanSummary, StoredPlan};
+use crate::probes::{ReadinessReport, VersionInfo};
+use crate::profiles::UserProfile;
+use crate::recap::{Recap, RecapRequest};
+use crate::refine::{RefinePlanRequest, RefinePlanResponse};
+use crate::render;
+use crate::sessions::{ChatRequest, ChatResponse, ChatTranscript};
+use crate::share::{ShareRequest, ShareResponse, SharedPlan};
+use crate::streaming::StreamEvent;
+use crate::templates::ObjectiveTemplate;
+use crate::ws::{ClientFrame, SessionFrame};
+
+// swagger ui from a cdn, pointed at /openapi.json
+pub const DOCS_PAGE: &str = include_str!("../resources/docs.html");
+
+static DOCUMENT: OnceLock<Value> = OnceLock::new();
+
+pub fn document() -> &'static Value {
+    DOCUMENT.get_or_init(build_document)
+}
+
+struct Spec {
+    schemas: SchemaGenerator,
+    paths: Map<String, Value>,
+}
+
+impl Spec {
+    // a $ref into components/schemas for named types, inline for the rest
+    fn schema<T: JsonSchema>(&mut self) -> Value {
+        serde_json::to_value(self.schemas.subschema_for::<T>()).unwrap_or_default()
+    }
+
+    fn body<T: JsonSchema>(&mut self) -> Value {
+        json!({ "required": true, "content": json_content(self.schema::<T>()) })
+    }
+
+    fn reply<T: JsonSchema>(&mut self, description: &str) -> Value {
+        json!({ "description": description, "content": json_content(self.schema::<T>()) })
+    }
+
+    // planner failures share the plan response shape with status "error"
+    fn plan_error(&mut self, description: &str) -> Value {
+        self.reply::<GeneratePlanResponse>(description)
+    }
+
+    // every other failure, the same envelope with status "error"
+    fn error(&mut self, description: &str) -> Value {
+        let envelope = self.schema::<ErrorEnvelope>();
+        json!({
+            "description": description,
+            "content": json_content(json!({
+                "allOf": [
+                    envelope,
+                    {
+                        "type": "object",
+                        "required": ["status"],
+                        "properties": { "status": { "type": "string", "enum": ["error"] } },
+                    },
+                ],
+            })),
+        })
+    }
+
+    fn add(&mut self, method: &str, path: &str, operation: Value) {
+        if let Value::Object(operations) = self
+            .paths
+            .entry(path)
+            .or_insert_with(|| Value::Object(Map::new()))
+        {
+            operations.insert(method.to_string(), operation);
+        }
+    }
+}
+
+fn json_content(schema: Value) -> Value {
+    json!({ "application/json": { "schema": schema } })
+}
+
+// a plan in every format render.rs knows, json described by `schema`
+fn rendered_content(schema: Value) -> Value {
+    let mut content = json_content(schema);
+    for media_type in render::media_types() {
+        if media_type.starts_with("text/") {
+            content[media_type] = json!({ "schema": { "type": "string" } });
+        } else if media_type != "application/json" {
+            content[media_type] = json!({ "schema": { "type": "string", "format": "binary" } });
+        }
+    }
+    content
+}
+
+fn format_param(default: &str) -> Value {
+    query_param(
+        "format",
+        &format!(
+            "Response format, the Accept header decides when unset and {} when neither does",
+            default
+        ),
+        false,
+        json!({ "type": "string", "enum": render::formats() }),
+    )
+}
+
+fn path_param(name: &str) -> Value {
+    json!({ "name": name, "in": "path", "required": true, "schema": { "type": "string" } })
+}
+
+fn query_param(name: &str, description: &str, required: bool, schema: Value) -> Value {
+    json!({
+        "name": name,
+        "in": "query",
+        "required": required,
+        "description": description,
+        "schema": schema,
+    })
+}
+
+// probes and public links are reachable without an api key
+fn public(mut operation: Value) -> Value {
+    operation["security"] = json!([]);
+    operation
+}
+
+fn build_document() -> Value {
+    let mut spec = Spec {
+        schemas: SchemaSettings::openapi3().into_generator(),
+        paths: Map::new(),
+    };
+    plan_paths(&mut spec);
+    job_paths(&mut spec);
+    chat_paths(&mut spec);
+    profile_paths(&mut spec);
+    probe_paths(&mut spec);
+
+    let Spec { mut schemas, paths } = spec;
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "VivaTech planner API",
+            "description": env!("CARGO_PKG_DESCRIPTION"),
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": {
+            "schemas": schemas.take_definitions(),
+            "securitySchemes": {
+                "bearer": { "type": "http", "scheme": "bearer" },
+                "api_key": { "type": "apiKey", "in": "header", "name": "X-API-Key" },
+            },
+        },
+        // only enforced once API_KEYS is configured
+        "security": [{ "bearer": [] }, { "api_key": [] }],
+    })
+}
+
+fn plan_paths(spec: &mut Spec) {
+    let operation = json!({
+        "tags": ["plans"],
+        "operationId": "generatePlan",
+        "summary": "Generate a plan for an objective",
+        "requestBody": spec.body::<GeneratePlanRequest>(),
+        "responses": {
+            "200": spec.reply::<GeneratePlanResponse>(
+                "The plan, or a pointer to the user's earlier plan for a near duplicate objective. \
+                X-Cache says whether the plan came from the plan cache"
+            ),
+            "400": spec.plan_error("Invalid request"),
+            "401": spec.error("Missing or unknown API key"),
+            "422": spec.plan_error("Objective empty, too long or refused, see reason"),
+            "429": spec.plan_error("Rate or spend limit reached, see Retry-After"),
+            "default": spec.plan_error("Planning failed"),
+        },
+    });
+    spec.add("post", "/generate-plan", operation);
+
+    let mut batch_content = json_content(spec.schema::<Vec<BatchPlanItem>>());
+    batch_content["application/x-ndjson"] = json!({ "schema": spec.schema::<BatchPlanItem>() });
+    let operation = json!({
+        "tags": ["plans"],
+        "operationId": "generatePlans",
+        "summary": "Generate plans for several objectives",
+        "description": "With `Accept: application/x-ndjson` each result is a line sent as soon \
+            as it finishes, otherwise a JSON array in request order.",
+        "requestBody": spec.body::<BatchPlanRequest>(),
+        "responses": {
+            "200": { "description": "One item per request", "content": batch_content },
+            "400": spec.plan_error("Empty or oversized batch"),
+        },
+    });
+    spec.add("post", "/generate-plans", operation);
+
+    let operation = json!({
+        "tags": ["plans"],
+        "operationId": "comparePlans",
+        "summary": "Generate plans for two objectives and compare them",
+        "requestBody": spec.body::<ComparePlansRequest>(),
+        "responses": {
+            "200": spec.reply::<PlanComparison>(
+                "Both plans with shared sessions, clashes and trade-offs"
+            ),
+            "400": spec.plan_error("Invalid request"),
+            "default": spec.plan_error("Planning failed"),
+        },
+    });
+    spec.add("post", "/compare-plans", operation);
+
+    let operation = json!({
+        "tags": ["plans"],
+        "operationId": "generatePlanStream",
+        "summary": "Generate a plan as server-sent events",
+        "description": "Each event is named after its `type` and carries the event as JSON \
+            data. The stream ends with `done` or `error`.",
+        "requestBody": spec.body::<GeneratePlanRequest>(),
+        "responses": {
+            "200": {
+                "description": "Tokens, tool calls and the final plan",
+                "content": { "text/event-stream": { "schema": spec.schema::<StreamEvent>() } },
+            },
+            "400": spec.plan_error("Invalid request"),
+            "422": spec.plan_error("Objective empty, too long or refused, see reason"),
+            "429": spec.plan_error("Rate or spend limit reached"),
+        },
+    });
+    spec.add("post", "/generate-plan/stream", operation);
+
+    let operation = json!({
+        "tags": ["plans"],
+        "operationId": "generatePlanFromTemplate",
+        "summary": "Generate a plan from a curated objective template",
+        "requestBody": spec.body::<TemplatePlanRequest>(),
+        "responses": {
+            "200": spec.reply::<GeneratePlanResponse>("The plan"),
+            "400": spec.plan_error("Unknown template or missing parameters"),
+            "default": spec.plan_error("Planning failed"),
+        },
+    });
+    spec.add("post", "/generate-plan/from-template", operation);
+
+    let operation = json!({
+        "tags": ["plans"],
+        "operationId": "listTemplates",
+        "summary": "List the objective templates",
+        "responses": {
+            "200": spec.reply::<Vec<ObjectiveTemplate>>("The templates"),
+        },
+    });
+    spec.add("get", "/templates", operation);
+
+    let operation = json!({
+        "tags": ["plans"],
+        "operationId": "getBranding",
+        "summary": "Brand name and greeting for frontends",
+        "parameters": [
+            query_param(
+                "language",
+                "e.g. \"fr\", the deployment default when unset",
+                false,
+                json!({ "type": "string" })
+            ),
+            query_param("conference", "Conference profile id", false, json!({ "type": "string" })),
+        ],
+        "responses": {
+            "200": spec.reply::<Branding>("Localized branding"),
+            "400": spec.error("Unknown conference"),
+        },
+    });
+    spec.add("get", "/branding", operation);
+
+    let operation = json!({
+        "tags": ["stored plans"],
+        "operationId": "listPlans",
+        "summary": "List a user's plans, newest first",
+        "parameters": [
+            query_param("user", "User id", true, json!({ "type": "string" })),
+            query_param(
+                "limit",
+                "At most PLAN_LIST_LIMIT",
+                false,
+                json!({ "type": "integer", "minimum": 1 })
+            ),
+        ],
+        "responses": {
+            "200": spec.reply::<Vec<PlanSummary>>("The plans"),
+            "500": spec.plan_error("Plan store unavailable"),
+        },
+    });
+    spec.add("get", "/plans", operation);
+
+    let operation = json!({
+        "tags": ["stored plans"],
+        "operationId": "getPlan",
+        "summary": "A stored plan with its request and sources",
+        "parameters": [path_param("plan_id"), format_param("json")],
+        "responses": {
+            "200": {
+                "description": "The plan",
+                "content": rendered_content(spec.schema::<StoredPlan>()),
+            },
+            "404": spec.plan_error("Unknown plan"),
+            "406": spec.error("No acceptable format"),
+        },
+    });
+    spec.add("get", "/plans/{plan_id}", operation);
+
+    let operation = json!({
+        "tags": ["stored plans"],
+        "operationId": "sharePlan",
+        "summary": "Create a read-only link to a stored plan",
+        "parameters": [path_param("plan_id")],
+        "requestBody": {
+            "required": false,
+            "content": json_content(spec.schema::<ShareRequest>()),
+        },
+        "responses": {
+            "201": spec.reply::<ShareResponse>("The share link"),
+            "404": spec.plan_error("Unknown plan"),
+        },
+    });
+    spec.add("post", "/plans/{plan_id}/share", operation);
+
+    let operation = json!({
+        "tags": ["stored plans"],
+        "operationId": "refinePlan",
+        "summary": "Revise a stored plan with an instruction",
+        "description": "The revision is stored as a new plan. The diff lists the sessions added, removed and reordered.",
+        "parameters": [path_param("plan_id")],
+        "requestBody": spec.body::<RefinePlanRequest>(),
+        "responses": {
+            "200": spec.reply::<RefinePlanResponse>("The revised plan and what changed"),
+            "400": spec.plan_error("Empty or overlong instruction"),
+            "404": spec.plan_error("Unknown plan"),
+            "422": spec.plan_error("Instruction rejected by abuse protection or moderation"),
+            "502": spec.plan_error("The model failed to revise the plan"),
+        },
+    });
+    spec.add("post", "/plans/{plan_id}/refine", operation);
+
+    let operation = json!({
+        "tags": ["stored plans"],
+        "operationId": "ratePlan",
+        "summary": "Rate a stored plan",
+        "description": "Issues are the picked ones plus those read from the comment.",
+        "parameters": [path_param("plan_id")],
+        "requestBody": spec.body::<PlanFeedbackRequest>(),
+        "responses": {
+            "201": spec.reply::<PlanFeedbackResponse>("The feedback was recorded"),
+            "400": spec.plan_error("Rating out of range or overlong comment"),
+            "404": spec.plan_error("Unknown plan"),
+        },
+    });
+    spec.add("post", "/plans/{plan_id}/feedback", operation);
+
+    let operation = json!({
+        "tags": ["stored plans"],
+        "operationId": "exportPlan",
+        "summary": "A stored plan as a file to print or email",
+        "parameters": [
+            path_param("plan_id"),
+            query_param(
+                "format",
+                "File format, pdf when unset",
+                false,
+                json!({ "type": "string", "enum": render::formats() })
+            ),
+        ],
+        "responses": {
+            "200": {
+                "description": "The plan, as an attachment",
+                "content": rendered_content(spec.schema::<StoredPlan>()),
+            },
+            "404": spec.plan_error("Unknown plan"),
+            "406": spec.error("No acceptable format"),
+        },
+    });
+    spec.add("get", "/plans/{plan_id}/export", operation);
+
+    let operation = json!({
+        "tags": ["stored plans"],
+        "operationId": "syncPlanToGoogleCalendar",
+        "summary": "Add a stored plan's sessions to the attendee's Google Calendar",
+        "parameters": [path_param("plan_id")],
+        "requestBody": spec.body::<GoogleCalendarSyncRequest>(),
+        "responses": {
+            "200": spec.reply::<GoogleCalendarSyncResponse>(
+                "The events added, and the sessions left out for overlapping the attendee's events"
+            ),
+            "401": spec.error("Google rejected the access token"),
+            "403": spec.error("The token may not edit this calendar"),
+            "404": spec.plan_error("Unknown plan or calendar"),
+            "502": spec.error("Google Calendar failed"),
+        },
+    });
+    spec.add("post", "/plans/{plan_id}/sync/google-calendar", operation);
+
+    let operation = json!({
+        "tags": ["stored plans"],
+        "operationId": "explainStep",
+        "summary": "Explain why a plan step was picked",
+        "parameters": [
+            path_param("plan_id"),
+            {
+                "name": "step",
+                "in": "path",
+                "required": true,
+                "description": "1-based step number",
+                "schema": { "type": "integer", "minimum": 1 },
+            },
+        ],
+        "responses": {
+            "200": spec.reply::<ExplainResponse>("The explanation with its citations"),
+            "404": spec.plan_error("Unknown plan or step"),
+        },
+    });
+    spec.add("get", "/plans/{plan_id}/steps/{step}/explain", operation);
+
+    let shared_content = rendered_content(spec.schema::<SharedPlan>());
+    let operation = public(json!({
+        "tags": ["stored plans"],
+        "operationId": "getSharedPlan",
+        "summary": "Public view of a shared plan",
+        "parameters": [
+            path_param("token"),
+            format_param("html"),
+        ],
+        "responses": {
+            "200": { "description": "The shared plan", "content": shared_content },
+            "404": spec.error("Unknown or expired link"),
+            "406": spec.error("No acceptable format"),
+        },
+    }));
+    spec.add("get", "/shared/{token}", operation);
+}
+
+fn job_paths(spec: &mut Spec) {
+    let status = spec.schema::<JobStatus>();
+    let operation = json!({
+        "tags": ["jobs"],
+        "operationId": "submitPlanJob",
+        "summary": "Queue a plan for background generation",
+        "description": "Poll the status URL, then fetch the result once the job finished.",
+        "requestBody": spec.body::<GeneratePlanRequest>(),
+        "responses": {
+            "202": {
+                "description": "The job was queued, Location points to its status",
+                "content": json_content(json!({
+                    "type": "object",
+                    "required": ["job_id", "status", "status_url", "result_url"],
+                    "properties": {
+                        "job_id": { "type": "string" },
+                        "status": status,
+                        "status_url": { "type": "string" },
+                        "result_url": { "type": "string" },
+                    },
+                })),
+            },
+            "503": spec.plan_error("Too many jobs waiting"),
+        },
+    });
+    spec.add("post", "/plans", operation);
+
+    let operation = json!({
+        "tags": ["jobs"],
+        "operationId": "getPlanJobStatus",
+        "summary": "Status of a plan job",
+        "parameters": [path_param("plan_id")],
+        "responses": {
+            "200": spec.reply::<JobView>("The job"),
+            "404": spec.plan_error("Unknown or expired job"),
+        },
+    });
+    spec.add("get", "/plans/{plan_id}/status", operation);
+
+    let operation = json!({
+        "tags": ["jobs"],
+        "operationId": "getPlanJobResult",
+        "summary": "The plan response a job ended with",
+        "description": "Answered with the status code /generate-plan would have used.",
+        "parameters": [path_param("plan_id")],
+        "responses": {
+            "200": spec.reply::<GeneratePlanResponse>("The plan"),
+            "404": spec.plan_error("Unknown or expired job"),
+            "409": spec.error("The job has not finished or was cancelled, `reason` is its status"),
+        },
+    });
+    spec.add("get", "/plans/{plan_id}/result", operation);
+
+    let operation = json!({
+        "tags": ["jobs"],
+        "operationId": "cancelPlanJob",
+        "summary": "Cancel a queued or running job",
+        "parameters": [path_param("plan_id")],
+        "responses": {
+            "200": {
+                "description": "The job's status after cancelling",
+                "content": json_content(json!({
+                    "type": "object",
+                    "required": ["job_id", "status"],
+                    "properties": {
+                        "job_id": { "type": "string" },
+                        "status": status,
+                    },
+                })),
+            },
+            "404": spec.plan_error("Unknown or expired job"),
+        },
+    });
+    spec.add("post", "/plans/{plan_id}/cancel", operation);
+}
+
+fn chat_paths(spec: &mut Spec) {
+    let operation = json!({
+        "tags": ["chat"],
+        "operationId": "chat",
+        "summary": "Send a message in a chat session",
+        "requestBody": spec.body::<ChatRequest>(),
+        "responses": {
+            "200": spec.reply::<ChatResponse>("The assistant's reply"),
+            "400": spec.error("Invalid request"),
+        },
+    });
+    spec.add("post", "/chat", operation);
+
+    let operation = json!({
+        "tags": ["chat"],
+        "operationId": "getChat",
+        "summary": "Read the stored exchanges of a chat session",
+        "parameters": [path_param("session_id")],
+        "responses": {
+            "200": spec.reply::<ChatTranscript>("The session's memory and exchanges"),
+            "404": spec.error("Unknown or expired session"),
+        },
+    });
+    spec.add("get", "/chat/{session_id}", operation);
+
+    let operation = json!({
+        "tags": ["chat"],
+        "operationId": "clearChat",
+        "summary": "Forget a chat session",
+        "parameters": [path_param("session_id")],
+        "responses": {
+            "204": { "description": "The session was cleared" },
+            "404": { "description": "Unknown session" },
+        },
+    });
+    spec.add("delete", "/chat/{session_id}", operation);
+
+    // websocket frames can't be described in openapi 3, the schemas are listed for sdks
+    let client_frame = spec.schema::<ClientFrame>();
+    let session_frame = spec.schema::<SessionFrame>();
+    let stream_event = spec.schema::<StreamEvent>();
+    let operation = json!({
+        "tags": ["chat"],
+        "operationId": "chatWebSocket",
+        "summary": "Chat over a WebSocket",
+        "description": "JSON text frames both ways. The client sends ClientFrame, the server \
+            answers with SessionFrame and StreamEvent frames.",
+        "parameters": [
+            query_param(
+                "session_id",
+                "Resumes a session, a new one is started when unset",
+                false,
+                json!({ "type": "string" })
+            ),
+        ],
+        "responses": {
+            "101": { "description": "Switching to the WebSocket protocol" },
+        },
+        "x-websocket-frames": {
+            "client": client_frame,
+            "server": { "oneOf": [session_frame, stream_event] },
+        },
+    });
+    spec.add("get", "/ws", operation);
+}
+
+fn profile_paths(spec: &mut Spec) {
+    let operation = json!({
+        "tags": ["profiles"],
+        "operationId": "upsertProfile",
+        "summary": "Create or replace a user profile",
+        "requestBody": spec.body::<UserProfile>(),
+        "responses": {
+            "200": spec.reply::<UserProfile>("The stored profile"),
+            "400": spec.error("Invalid profile"),
+        },
+    });
+    spec.add("post", "/profiles", operation);
+
+    let operation = json!({
+        "tags": ["profiles"],
+        "operationId": "getProfile",
+        "summary": "A user profile",
+        "parameters": [path_param("user_id")],
+        "responses": {
+            "200": spec.reply::<UserProfile>("The profile"),
+            "404": spec.error("Unknown user"),
+        },
+    });
+    spec.add("get", "/profiles/{user_id}", operation);
+
+    let operation = json!({
+        "tags": ["profiles"],
+        "operationId": "createRecap",
+        "summary": "Post-conference recap for a user, from their stored plans, favorites and feedback",
+        "parameters": [path_param("user_id"), format_param("json")],
+        "requestBody": {
+            "required": false,
+            "content": json_content(spec.schema::<RecapRequest>()),
+        },
+        "responses": {
+            "200": {
+                "description": "The recap",
+                "content": rendered_content(spec.schema::<Recap>()),
+            },
+            "400": spec.error("Unknown conference"),
+            "404": spec.error("Nothing to recap"),
+            "406": spec.error("No acceptable format"),
+        },
+    });
+    spec.add("post", "/users/{user_id}/recap", operation);
+
+    let operation = json!({
+        "tags": ["profiles"],
+        "operationId": "exportConversations",
+        "summary": "A user's chats, plans and feedback as a zip of export.json and export.md",
+        "parameters": [
+            path_param("user_id"),
+            query_param(
+                "language",
+                "Language of the markdown headings",
+                false,
+                json!({ "type": "string" }),
+            ),
+        ],
+        "responses": {
+            "200": {
+                "description": "The archive",
+                "content": {
+                    "application/zip": { "schema": { "type": "string", "format": "binary" } },
+                },
+            },
+            "404": spec.error("Nothing stored for the user"),
+        },
+    });
+    spec.add("get", "/users/{user_id}/conversations/export", operation);
+}
+
+fn probe_paths(spec: &mut Spec) {
+    let operation = public(json!({
+        "tags": ["probes"],
+        "operationId": "healthz",
+        "summary": "Liveness",
+        "responses": {
+            "200": {
+                "description": "The process is serving",
+                "content": { "text/plain": { "schema": { "type": "string" } } },
+            },
+        },
+    }));
+    spec.add("get", "/healthz", operation);
+
+    let operation = public(json!({
+        "tags": ["probes"],
+        "operationId": "readyz",
+        "summary": "Readiness",
+        "responses": {
+            "200": spec.reply::<ReadinessReport>("Every check passed"),
+            "503": spec.reply::<ReadinessReport>("A check failed"),
+        },
+    }));
+    spec.add("get", "/readyz", operation);
+
+    let operation = public(json!({
+        "tags": ["probes"],
+        "operationId": "version",
+        "summary": "Build information",
+        "responses": {
+            "200": spec.reply::<VersionInfo>("The running build"),
+        },
+    }));
+    spec.add("get", "/version", operation);
+}
+
+// openapi 3 document, for sdk generators
+pub async fn openapi_handler() -> Json<&'static Value> {
+    Json(document())
+}
+
+// swagger ui over /openapi.json
+pub async fn docs_handler() -> Html<&'static str> {
+    Html(DOCS_PAGE)
+}
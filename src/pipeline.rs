@@ -0,0 +1,1070 @@
+// the plan pipeline behind every entry point: admission, the agent run with its
+// fallbacks and stages, storing and answering. the rest handlers, jobs, graphql and
+// the bots all go through run_plan_request or plan_events
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::http::StatusCode;
+use futures::StreamExt;
+use rig::tool::{Tool, ToolSet};
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::info;
+
+use crate::agents::{
+    self, build_next_action_agent, build_planning_agent, build_text_agent, GenerationParams,
+};
+use crate::citations::SourceCollector;
+use crate::conference::ConferenceProfile;
+use crate::error::AppError;
+use crate::generation::StageUsage;
+use crate::health::CapabilityMatrix;
+use crate::llm::{AnyAgent, LlmClient, ModelChoice, Provider};
+use crate::models::{
+    self, GeneratePlanRequest, GeneratePlanResponse, GenerationStrategy, NextAction, OutputMode,
+    PlanOutcome, TokenUsage,
+};
+use crate::plan_cache::{CacheStatus, CachedPlan};
+use crate::planner::{
+    initialize_llm_client, prompt_with_fallback, prompt_with_retry, AgentFailure, FallbackAnswer,
+};
+use crate::plans::StoredPlan;
+use crate::prompts::PromptKind;
+use crate::reply::{elapsed_ms, plan_error, plan_success, PlanReply};
+use crate::state::AppState;
+use crate::tone::Tone;
+use crate::tools::{QueryVivatechAPI, QueryVivatechArgs, SearchOutcome};
+use crate::{
+    abuse, citations, copy, dedupe, degraded, error, feedback, footer, generation, itinerary,
+    language, llm, objective, placeholders, plan_cache, popularity, profiles, prompts, streaming,
+    telemetry, tone, usage,
+};
+
+pub async fn run_plan_request(
+    state: &AppState,
+    client: &str,
+    payload: GeneratePlanRequest,
+) -> PlanReply {
+    let started = Instant::now();
+    info!(
+        "Received planning request for objective: {}",
+        payload.objective
+    );
+    let payload = match admit_request(state, client, payload, started).await {
+        Ok(payload) => payload,
+        Err(rejection) => return rejection,
+    };
+    let owner = PlanOwner::current();
+
+    // identical settings and objectives share an agent run for PLAN_CACHE_TTL_SECONDS
+    let cache = plan_cache::plan_cache().filter(|_| plan_cache::is_cacheable(&payload));
+    if let Some(cached) = cache.as_ref().and_then(|cache| cache.get(&payload)) {
+        info!("Answering from the plan cache");
+        let generated = GeneratedPlan {
+            outcome: cached.outcome,
+            conference: cached.conference,
+            usage: TokenUsage::default(),
+        };
+        let reply =
+            finish_plan(state, &payload, generated, &owner, started).with_cache(CacheStatus::Hit);
+        return PlanReply {
+            body: reply.body.served_from_cache(elapsed_ms(started)),
+            ..reply
+        };
+    }
+
+    let request = payload.clone();
+    let generated = match generate_plan(state, payload, started).await {
+        Ok(generated) => generated,
+        Err(reply) => return reply,
+    };
+    match cache {
+        Some(cache) => {
+            let cached = CachedPlan {
+                outcome: generated.outcome.clone(),
+                conference: generated.conference.clone(),
+            };
+            cache.insert(&request, &cached);
+            finish_plan(state, &request, generated, &owner, started).with_cache(CacheStatus::Miss)
+        }
+        None => finish_plan(state, &request, generated, &owner, started),
+    }
+}
+
+// the checks every plan request passes before anything runs, streamed or not.
+// answers with the request in its detected language
+async fn admit_request(
+    state: &AppState,
+    client: &str,
+    payload: GeneratePlanRequest,
+    started: Instant,
+) -> Result<GeneratePlanRequest, PlanReply> {
+    if let Some(rejection) = invalid_objective(&payload.objective) {
+        return Err(rejection);
+    }
+    if let Some(rejection) = abuse_rejection(state.abuse.check(client, &payload.objective)) {
+        return Err(rejection);
+    }
+    if let Some(rejection) = moderation_rejection(state, client, &payload.objective).await {
+        return Err(rejection);
+    }
+    if let Err(e) = usage::validate_cost_tags(&payload.cost_tags) {
+        tracing::warn!("Rejected planning request: {}", e);
+        return Err(plan_error(AppError::Validation(e), started));
+    }
+    Ok(payload.with_detected_language())
+}
+
+// who a plan is stored for, read while the request is handled since a streamed
+// plan is finished after its handler returned
+#[derive(Debug, Clone, Default)]
+pub struct PlanOwner {
+    pub api_key: Option<String>,
+    pub request_id: Option<String>,
+}
+
+impl PlanOwner {
+    pub fn current() -> Self {
+        PlanOwner {
+            api_key: usage::current_api_key(),
+            request_id: telemetry::current_request_id(),
+        }
+    }
+}
+
+// stores an agent run for the caller and answers it: placeholders resolved against
+// the current catalog, citations and the footer added. cache hits go through here too
+pub fn finish_plan(
+    state: &AppState,
+    request: &GeneratePlanRequest,
+    generated: GeneratedPlan,
+    owner: &PlanOwner,
+    started: Instant,
+) -> PlanReply {
+    let GeneratedPlan {
+        mut outcome,
+        conference,
+        usage,
+    } = generated;
+    let conference = match conference {
+        Some(conference) if outcome.next_action.is_none() && outcome.listing.is_none() => {
+            conference
+        }
+        // next actions, retrieval-only listings and simple test runs are not stored
+        _ => return plan_success(outcome, None, None, started).with_usage(usage),
+    };
+
+    // persisted for sharing, auditing, replays and explaining single steps,
+    // a store failure costs the plan id but not the plan
+    let plan_id = match state.plans.insert(
+        request,
+        &conference,
+        &outcome,
+        owner.request_id.as_deref(),
+        owner.api_key.as_deref(),
+    ) {
+        Ok(plan_id) => Some(plan_id),
+        Err(e) => {
+            tracing::error!("Failed to store plan: {}", e);
+            None
+        }
+    };
+    // stored with its placeholders, answered resolved
+    outcome.body = placeholders::resolve_plan(state, &conference, &outcome.body, &outcome.sources);
+    if plan_id.is_some() {
+        popularity::record_plan(state, &conference, &outcome);
+    }
+    let citations = request
+        .include_citations
+        .then(|| citations::cite_plan(&outcome.body, &outcome.sources));
+    // after storage and token accounting, the footer is ours and not the model's
+    let plan_footer = footer::render(
+        state,
+        &conference,
+        request.language.as_deref(),
+        chrono::Utc::now(),
+    );
+    outcome.body = footer::append(&outcome.body, plan_footer.as_deref());
+    info!(
+        "Planning task {} completed, response length: {} chars, {} sources",
+        plan_id.as_deref().unwrap_or("(unsaved)"),
+        outcome.body.len(),
+        outcome.sources.len()
+    );
+    plan_success(outcome, plan_id, citations, started).with_usage(usage)
+}
+
+// an agent run's result before it is stored and answered
+pub struct GeneratedPlan {
+    pub outcome: PlanOutcome,
+    // unset for the simple test mode, which has no conference
+    pub conference: Option<String>,
+    pub usage: TokenUsage,
+}
+
+// the agent run behind a plan request, Err is the reply to send instead,
+// e.g. a rejected option, the user's earlier plan or a failed run
+pub async fn generate_plan(
+    state: &AppState,
+    payload: GeneratePlanRequest,
+    started: Instant,
+) -> Result<GeneratedPlan, PlanReply> {
+    let (llm_client, choice) = initialize_llm(payload.model.as_deref(), started)?;
+
+    // simple test mode without tools
+    if payload.objective.contains("test simple") {
+        info!("Running simple agent test without tools");
+        let params = generation_params(&payload, &choice)
+            .map_err(|e| plan_error(AppError::Validation(e), started))?;
+        let simple_agent = build_text_agent(&llm_client, "You are a helpful assistant.", &params);
+
+        return match simple_agent.chat(&payload.objective, &[], 0).await {
+            Ok(response) => {
+                info!("Simple agent response successful");
+                let outcome = PlanOutcome {
+                    body: response,
+                    next_action: None,
+                    itinerary: None,
+                    sources: Vec::new(),
+                    model: choice.to_string(),
+                    fallback_from: None,
+                    listing: None,
+                };
+                Ok(GeneratedPlan {
+                    outcome,
+                    conference: None,
+                    usage: TokenUsage::default(),
+                })
+            }
+            Err(e) => {
+                tracing::error!("Simple agent failed: {}", e);
+                Err(plan_error(
+                    AppError::Llm(copy::error(
+                        "error.simple_agent_failed",
+                        &[("error", e.to_string().as_str())],
+                    )),
+                    started,
+                ))
+            }
+        };
+    }
+
+    let PreparedRun {
+        profile,
+        user_context,
+        params,
+        providers,
+        fallbacks,
+    } = prepare_run(state, &payload, &choice, started)?;
+    if degraded::llm_down(&state.health, &providers, &fallbacks) {
+        return retrieval_only_plan(
+            state,
+            &profile,
+            &payload.objective,
+            payload.language.as_deref(),
+            started,
+        )
+        .await;
+    }
+
+    let conference = profile.id.clone();
+    let health = state.health.clone();
+    let fallback = (
+        profile.clone(),
+        payload.objective.clone(),
+        payload.language.clone(),
+    );
+
+    // identical objectives in flight share one agent run, billed to the request that ran it
+    let attribution = usage::Attribution::new(state.usage.clone(), payload.cost_tags.clone());
+    let spent = attribution.clone();
+    let run = state
+        .plan_coalescer
+        .run(payload.coalescing_key(), move || async move {
+            let collector = SourceCollector::default();
+            match payload.mode {
+                OutputMode::Plan if payload.strategy == GenerationStrategy::TwoStage => {
+                    let (body, model) = execute_two_stage_plan(
+                        &profile,
+                        &user_context,
+                        &collector,
+                        &health,
+                        &payload.objective,
+                        &polish_model(&payload, &choice),
+                        &params,
+                    )
+                    .await?;
+                    Ok(PlanOutcome {
+                        body,
+                        next_action: None,
+                        itinerary: None,
+                        sources: collector.sources(),
+                        model,
+                        fallback_from: None,
+                        listing: None,
+                    })
+                }
+                OutputMode::Plan => {
+                    let answer = execute_planning_task(
+                        &llm_client,
+                        &choice,
+                        &payload.objective,
+                        &health,
+                        |client, model| {
+                            build_planning_agent(
+                                client,
+                                &profile,
+                                &user_context,
+                                &collector,
+                                &health,
+                                &params.for_model(model),
+                            )
+                        },
+                    )
+                    .await?;
+                    Ok(PlanOutcome {
+                        next_action: None,
+                        itinerary: None,
+                        sources: collector.sources(),
+                        model: answer.choice.to_string(),
+                        fallback_from: answer.fallback_from(&choice),
+                        body: answer.response,
+                        listing: None,
+                    })
+                }
+                // always the standard strategy, the prose plan is then structured
+                // by the model that wrote it
+                OutputMode::Itinerary => {
+                    let answer = execute_planning_task(
+                        &llm_client,
+                        &choice,
+                        &payload.objective,
+                        &health,
+                        |client, model| {
+                            build_planning_agent(
+                                client,
+                                &profile,
+                                &user_context,
+                                &collector,
+                                &health,
+                                &params.for_model(model),
+                            )
+                        },
+                    )
+                    .await?;
+                    let sources = collector.sources();
+                    let itinerary = execute_itinerary_task(
+                        &answer.client,
+                        &answer.choice.model,
+                        &answer.response,
+                        &sources,
+                    )
+                    .await?;
+                    Ok(PlanOutcome {
+                        next_action: None,
+                        itinerary: Some(itinerary),
+                        sources,
+                        model: answer.choice.to_string(),
+                        fallback_from: answer.fallback_from(&choice),
+                        body: answer.response,
+                        listing: None,
+                    })
+                }
+                OutputMode::NextAction => {
+                    let (next_action, answer) = execute_next_action_task(
+                        &llm_client,
+                        &choice,
+                        &payload.objective,
+                        &health,
+                        |client, model| {
+                            build_next_action_agent(
+                                client,
+                                &profile,
+                                &user_context,
+                                &collector,
+                                &health,
+                                &params.for_model(model),
+                            )
+                        },
+                    )
+                    .await?;
+                    info!("Next action task completed");
+                    Ok(PlanOutcome {
+                        body: next_action.action.clone(),
+                        next_action: Some(next_action),
+                        itinerary: None,
+                        sources: collector.sources(),
+                        model: answer.choice.to_string(),
+                        fallback_from: answer.fallback_from(&choice),
+                        listing: None,
+                    })
+                }
+            }
+        });
+    match usage::attributed(Some(attribution), run).await {
+        Ok(outcome) => Ok(GeneratedPlan {
+            outcome,
+            conference: Some(conference),
+            usage: spent.spent(),
+        }),
+        // the failed run may be what opened the circuit
+        Err(_) if degraded::llm_down(&state.health, &providers, &fallbacks) => {
+            let (profile, objective, language) = fallback;
+            retrieval_only_plan(state, &profile, &objective, language.as_deref(), started).await
+        }
+        Err(failure) => Err(plan_error(
+            AppError::llm(failure.message, failure.retry_after),
+            started,
+        )),
+    }
+}
+
+// what an agent run for an admitted request needs, shared by the streamed runs
+struct PreparedRun {
+    profile: ConferenceProfile,
+    user_context: String,
+    params: GenerationParams,
+    // the providers the run needs and its fallbacks, while all are down the search answers
+    providers: Vec<Provider>,
+    fallbacks: Vec<Provider>,
+}
+
+// the conference, the user's earlier plans and the generation settings with the
+// user's tone, live placeholders and the popularity and feedback priors.
+// Err is the reply to send instead, e.g. the user's earlier plan
+fn prepare_run(
+    state: &AppState,
+    payload: &GeneratePlanRequest,
+    choice: &ModelChoice,
+    started: Instant,
+) -> Result<PreparedRun, PlanReply> {
+    let profile = match state.conferences.resolve(payload.conference.as_deref()) {
+        Ok(profile) => profile.clone(),
+        Err(e) => {
+            tracing::warn!("Rejected planning request: {}", e);
+            return Err(plan_error(AppError::Validation(e), started));
+        }
+    };
+    if let Some(reply) = duplicate_reply(state, payload, &profile.id, started) {
+        return Err(reply);
+    }
+
+    let two_stage =
+        payload.strategy == GenerationStrategy::TwoStage && payload.mode == OutputMode::Plan;
+    let providers = if two_stage {
+        vec![
+            generation::get_draft_model().provider,
+            polish_model(payload, choice).provider,
+        ]
+    } else {
+        vec![choice.provider]
+    };
+    // two_stage keeps its stage models
+    let fallbacks: Vec<Provider> = if two_stage {
+        Vec::new()
+    } else {
+        llm::fallback_chain(choice)
+            .iter()
+            .map(|fallback| fallback.provider)
+            .collect()
+    };
+
+    let user_context = resolve_user_context(state, payload.user_id.as_deref(), &profile);
+    let tone = resolve_tone(state, payload.tone, payload.user_id.as_deref());
+    let live_placeholders = payload.live_placeholders && payload.mode == OutputMode::Plan;
+    let params = match generation_params(payload, choice) {
+        Ok(params) => params
+            .with_tone(tone)
+            .with_live_placeholders(live_placeholders)
+            .with_popular_sessions(popularity::prior(state, &profile))
+            .with_feedback_notes(feedback::prior(state, &profile)),
+        Err(e) => {
+            tracing::warn!("Rejected planning request: {}", e);
+            return Err(plan_error(AppError::Validation(e), started));
+        }
+    };
+    Ok(PreparedRun {
+        profile,
+        user_context,
+        params,
+        providers,
+        fallbacks,
+    })
+}
+
+// search hits listed by urgency instead of a plan, answered with degraded: true
+async fn retrieval_only_plan(
+    state: &AppState,
+    profile: &ConferenceProfile,
+    objective: &str,
+    language: Option<&str>,
+    started: Instant,
+) -> Result<GeneratedPlan, PlanReply> {
+    tracing::warn!(
+        "LLM provider unavailable, answering with a retrieval-only listing for: {}",
+        objective
+    );
+    match degraded::retrieval_only(profile, &state.health, objective, language).await {
+        Ok(outcome) => Ok(GeneratedPlan {
+            outcome,
+            conference: Some(profile.id.clone()),
+            usage: TokenUsage::default(),
+        }),
+        Err(message) => Err(plan_error(AppError::Llm(message), started)),
+    }
+}
+
+// the checks of a plan request, then the agent run in the background. its events end
+// with `done`, the plan stored and answered like run_plan_request does, or `error`.
+// also behind the graphql subscription
+pub async fn plan_events(
+    state: &AppState,
+    client: &str,
+    payload: GeneratePlanRequest,
+    started: Instant,
+) -> Result<impl futures::Stream<Item = streaming::StreamEvent> + Send + 'static, PlanReply> {
+    let payload = admit_request(state, client, payload, started).await?;
+    if payload.mode != OutputMode::Plan || payload.strategy != GenerationStrategy::Standard {
+        return Err(plan_error(
+            AppError::Validation(copy::error("error.streaming_unsupported", &[])),
+            started,
+        ));
+    }
+
+    let (llm_client, choice) = initialize_llm(payload.model.as_deref(), started)?;
+    let PreparedRun {
+        profile,
+        user_context,
+        params,
+        providers,
+        fallbacks,
+    } = prepare_run(state, &payload, &choice, started)?;
+
+    // tools are run by the stream loop itself so progress can be reported
+    let collector = SourceCollector::default();
+    let run = Arc::new(StreamedRun {
+        state: state.clone(),
+        attribution: usage::Attribution::new(state.usage.clone(), payload.cost_tags.clone()),
+        owner: PlanOwner::current(),
+        model: choice.to_string(),
+        collector: collector.clone(),
+        request: payload,
+        profile,
+        providers,
+        fallbacks,
+        started,
+    });
+    if degraded::llm_down(&state.health, &run.providers, &run.fallbacks) {
+        let done = run.retrieval_only().await;
+        return Ok(futures::stream::iter([done]).left_stream());
+    }
+
+    let planner_agent = build_planning_agent(
+        &llm_client,
+        &run.profile,
+        &user_context,
+        &collector,
+        &state.health,
+        &params,
+    );
+    let tools = streaming_tools(&run.profile, &collector, &state.health);
+    let (sender, receiver) = tokio::sync::mpsc::channel(64);
+    streaming::spawn_agent_run(
+        planner_agent,
+        tools,
+        run.request.objective.clone(),
+        Vec::new(),
+        state.config.agent_max_turns,
+        run.attribution.clone(),
+        sender,
+    );
+
+    Ok(ReceiverStream::new(receiver)
+        .then(move |event| run.clone().finish(event))
+        .right_stream())
+}
+
+// what the end of a streamed run needs to be stored and answered
+struct StreamedRun {
+    state: AppState,
+    request: GeneratePlanRequest,
+    profile: ConferenceProfile,
+    providers: Vec<Provider>,
+    fallbacks: Vec<Provider>,
+    owner: PlanOwner,
+    collector: SourceCollector,
+    attribution: usage::Attribution,
+    model: String,
+    started: Instant,
+}
+
+impl StreamedRun {
+    // the model's plan goes through finish_plan, a failure before any token was sent
+    // falls back to the search like a plan request does. other events pass through
+    async fn finish(self: Arc<Self>, event: streaming::StreamEvent) -> streaming::StreamEvent {
+        match event {
+            streaming::StreamEvent::Done { plan, .. } => {
+                let generated = GeneratedPlan {
+                    outcome: PlanOutcome {
+                        body: plan,
+                        next_action: None,
+                        itinerary: None,
+                        sources: self.collector.sources(),
+                        model: self.model.clone(),
+                        fallback_from: None,
+                        listing: None,
+                    },
+                    conference: Some(self.profile.id.clone()),
+                    usage: self.attribution.spent(),
+                };
+                self.answer(generated)
+            }
+            // the failed run may be what opened the circuit
+            streaming::StreamEvent::Error { partial: false, .. }
+                if degraded::llm_down(&self.state.health, &self.providers, &self.fallbacks) =>
+            {
+                self.retrieval_only().await
+            }
+            event => event,
+        }
+    }
+
+    async fn retrieval_only(&self) -> streaming::StreamEvent {
+        match retrieval_only_plan(
+            &self.state,
+            &self.profile,
+            &self.request.objective,
+            self.request.language.as_deref(),
+            self.started,
+        )
+        .await
+        {
+            Ok(generated) => self.answer(generated),
+            Err(reply) => reply.into_stream_error(),
+        }
+    }
+
+    fn answer(&self, generated: GeneratedPlan) -> streaming::StreamEvent {
+        let reply = finish_plan(
+            &self.state,
+            &self.request,
+            generated,
+            &self.owner,
+            self.started,
+        );
+        match reply.body {
+            GeneratePlanResponse::Success { plan, plan_id, .. } => {
+                streaming::StreamEvent::Done { plan, plan_id }
+            }
+            body => PlanReply { body, ..reply }.into_stream_error(),
+        }
+    }
+}
+
+// the planner's tools for a streamed run, which calls them itself between turns
+pub fn streaming_tools(
+    profile: &ConferenceProfile,
+    collector: &SourceCollector,
+    health: &Arc<CapabilityMatrix>,
+) -> ToolSet {
+    agents::planning_tools(ToolSet::builder(), profile, collector, health).build()
+}
+
+pub fn load_plan(
+    state: &AppState,
+    plan_id: &str,
+    started: Instant,
+) -> Result<StoredPlan, PlanReply> {
+    // another key's plan is as unknown as a missing one
+    match state
+        .plans
+        .get(plan_id, usage::current_api_key().as_deref())
+    {
+        // every read sees the current catalog
+        Ok(Some(mut stored)) => {
+            stored.plan = placeholders::resolve_plan(
+                state,
+                &stored.conference,
+                &stored.plan,
+                &stored.sources,
+            );
+            Ok(stored)
+        }
+        Ok(None) => Err(plan_error(
+            AppError::NotFound(copy::error("error.plan_not_found", &[])),
+            started,
+        )),
+        Err(e) => {
+            tracing::error!("Failed to load plan {}: {}", plan_id, e);
+            Err(plan_error(
+                AppError::Internal(copy::error("error.plan_load_failed", &[])),
+                started,
+            ))
+        }
+    }
+}
+
+// one search through the breaker and fallbacks, nothing found is an empty list and a
+// search that is down an error. also behind the graphql sessions query
+pub async fn search_sessions(
+    state: &AppState,
+    conference: Option<&str>,
+    args: QueryVivatechArgs,
+    started: Instant,
+) -> Result<Vec<models::VivatechSource>, PlanReply> {
+    let profile = state
+        .conferences
+        .resolve(conference)
+        .map_err(|e| plan_error(AppError::Validation(e), started))?;
+    let search = QueryVivatechAPI::for_conference(profile).with_health(state.health.clone());
+    match search.call(args).await {
+        Ok(SearchOutcome::Sources(sources)) => Ok(sources),
+        Ok(SearchOutcome::Empty(_)) => Ok(Vec::new()),
+        Ok(SearchOutcome::Degraded { message, .. }) => {
+            Err(plan_error(AppError::Unavailable(message), started))
+        }
+        Err(e) => Err(plan_error(
+            AppError::Upstream(copy::error(
+                "error.search_failed",
+                &[("error", e.to_string().as_str())],
+            )),
+            started,
+        )),
+    }
+}
+
+// personal context appended to the preamble
+pub fn resolve_user_context(
+    state: &AppState,
+    user_id: Option<&str>,
+    profile: &ConferenceProfile,
+) -> String {
+    let Some(user) = user_id.and_then(|user_id| state.profiles.get(user_id)) else {
+        return String::new();
+    };
+    let travel = user
+        .travel
+        .as_ref()
+        .map(|travel| profiles::travel_guidance(travel, profile))
+        .unwrap_or_default();
+    [profiles::preference_guidance(&user), travel]
+        .into_iter()
+        .filter(|block| !block.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// explicit request tone wins over the stored preference
+pub fn resolve_tone(
+    state: &AppState,
+    requested: Option<Tone>,
+    user_id: Option<&str>,
+) -> Option<Tone> {
+    requested.or_else(|| {
+        user_id
+            .and_then(|user_id| state.profiles.get(user_id))
+            .and_then(|user| user.preferred_tone)
+    })
+}
+
+// points the user at a recent plan for nearly the same objective instead of generating again.
+// next actions depend on the current time, so they are never deduplicated
+fn duplicate_reply(
+    state: &AppState,
+    payload: &GeneratePlanRequest,
+    conference: &str,
+    started: Instant,
+) -> Option<PlanReply> {
+    if payload.regenerate || payload.mode == OutputMode::NextAction {
+        return None;
+    }
+    let user_id = payload.user_id.as_deref()?;
+    let owner = usage::current_api_key();
+    let history = match state.plans.list_for_user(user_id, owner.as_deref(), None) {
+        Ok(history) => history,
+        Err(e) => {
+            tracing::warn!("Skipping duplicate check for {}: {}", user_id, e);
+            return None;
+        }
+    };
+
+    let earlier = dedupe::find_earlier_plan(&payload.objective, conference, history)?;
+    let age = dedupe::describe_age(earlier.plan.created_at);
+    info!(
+        "Objective from {} matches plan {} ({:.2})",
+        user_id, earlier.plan.id, earlier.similarity
+    );
+    Some(PlanReply {
+        status: StatusCode::OK,
+        retry_after: None,
+        cache: None,
+        body: GeneratePlanResponse::Duplicate {
+            hint: copy::error(
+                "duplicate.hint",
+                &[("age", age.as_str()), ("plan", earlier.plan.id.as_str())],
+            ),
+            age_seconds: (chrono::Utc::now() - earlier.plan.created_at).num_seconds(),
+            earlier_plan_id: earlier.plan.id,
+            earlier_objective: earlier.plan.objective,
+            similarity: earlier.similarity,
+            elapsed_ms: elapsed_ms(started),
+        },
+    })
+}
+
+// empty and overlong objectives, checked before anything else looks at them
+fn invalid_objective(objective: &str) -> Option<PlanReply> {
+    let e = objective::validate(objective).err()?;
+    tracing::warn!("Rejected planning request: {}", e);
+    Some(PlanReply {
+        status: StatusCode::UNPROCESSABLE_ENTITY,
+        retry_after: None,
+        cache: None,
+        body: GeneratePlanResponse::error(
+            error::envelope("invalid_objective", e.to_string()).with_reason(e.reason()),
+            0,
+        ),
+    })
+}
+
+// an objective the moderation api flags is a strike like any other abuse signal
+pub async fn moderation_rejection(
+    state: &AppState,
+    client: &str,
+    objective: &str,
+) -> Option<PlanReply> {
+    let categories = objective::moderate(objective).await?;
+    tracing::warn!(
+        client = %client,
+        categories = %categories.join(", "),
+        "Objective flagged by moderation"
+    );
+    abuse_rejection(state.abuse.flag(client, abuse::AbuseSignal::Moderation))
+}
+
+// map abuse verdicts to responses, None means serve the request
+pub fn abuse_rejection(verdict: abuse::Verdict) -> Option<PlanReply> {
+    let (status, code, message, retry_after) = match verdict {
+        abuse::Verdict::Allow => return None,
+        abuse::Verdict::Reject(signal) => {
+            return Some(PlanReply {
+                status: StatusCode::UNPROCESSABLE_ENTITY,
+                retry_after: None,
+                cache: None,
+                body: GeneratePlanResponse::error(
+                    error::envelope(
+                        "objective_rejected",
+                        copy::error("error.objective_rejected", &[("reason", signal.reason())]),
+                    )
+                    .with_reason(signal.reason()),
+                    0,
+                ),
+            })
+        }
+        abuse::Verdict::Throttled(delay) => (
+            StatusCode::TOO_MANY_REQUESTS,
+            "abuse_throttled",
+            copy::error("error.abuse_throttled", &[]),
+            Some(delay),
+        ),
+        abuse::Verdict::Suspended => (
+            StatusCode::FORBIDDEN,
+            "client_suspended",
+            copy::error("error.client_suspended", &[]),
+            None,
+        ),
+    };
+    Some(PlanReply {
+        status,
+        retry_after,
+        cache: None,
+        body: GeneratePlanResponse::error(error::envelope(code, message), 0),
+    })
+}
+
+// request overrides for the planning agent, validated against the configured limits
+pub fn generation_params(
+    payload: &GeneratePlanRequest,
+    choice: &ModelChoice,
+) -> Result<GenerationParams, String> {
+    GenerationParams::resolve(
+        &choice.model,
+        payload.temperature,
+        payload.max_tokens,
+        payload.language.as_deref(),
+    )
+}
+
+// requested or configured model, 400 on a bad spec and 503 when its provider isn't set up
+pub fn initialize_llm(
+    requested: Option<&str>,
+    started: Instant,
+) -> Result<(LlmClient, ModelChoice), PlanReply> {
+    let choice = ModelChoice::resolve(requested)
+        .map_err(|e| plan_error(AppError::Validation(e), started))?;
+    let client = initialize_llm_client(&choice).map_err(|e| {
+        plan_error(
+            AppError::Config(copy::error(
+                "error.ai_unavailable",
+                &[("error", e.to_string().as_str())],
+            )),
+            started,
+        )
+    })?;
+    Ok((client, choice))
+}
+
+// run the agent with user's request, on a fallback model when the chosen one is unavailable
+async fn execute_planning_task(
+    client: &LlmClient,
+    choice: &ModelChoice,
+    objective: &str,
+    health: &CapabilityMatrix,
+    build: impl Fn(&LlmClient, &str) -> AnyAgent,
+) -> Result<FallbackAnswer, AgentFailure> {
+    info!("Executing planning task for: {}", objective);
+
+    prompt_with_fallback(client, choice, objective, health, build)
+        .await
+        .map_err(|e| reworded(e, "error.plan_failed"))
+}
+
+// the failure under the copy of `key`, which quotes the original message
+fn reworded(failure: AgentFailure, key: &str) -> AgentFailure {
+    let message = copy::error(key, &[("error", failure.message.as_str())]);
+    failure.with_message(message)
+}
+
+// cheap model drafts with tools, large model polishes without them,
+// each stage may run on a different provider
+// the polish stage writes the answer, so a model named by the request runs it
+fn polish_model(payload: &GeneratePlanRequest, choice: &ModelChoice) -> ModelChoice {
+    match payload.model {
+        Some(_) => choice.clone(),
+        None => generation::get_polish_model(),
+    }
+}
+
+async fn execute_two_stage_plan(
+    profile: &ConferenceProfile,
+    user_context: &str,
+    collector: &SourceCollector,
+    health: &Arc<CapabilityMatrix>,
+    objective: &str,
+    polish_model: &ModelChoice,
+    params: &GenerationParams,
+) -> Result<(String, String), AgentFailure> {
+    info!("Executing two-stage planning task for: {}", objective);
+
+    // the draft is only read by the polish stage, so it keeps the defaults
+    let draft_model = generation::get_draft_model();
+    let draft_client = initialize_llm_client(&draft_model)?;
+    let draft_agent = build_planning_agent(
+        &draft_client,
+        profile,
+        user_context,
+        collector,
+        health,
+        &GenerationParams::new(&draft_model.model).with_live_placeholders(params.live_placeholders),
+    );
+    let draft = prompt_with_retry(&draft_agent, objective, health)
+        .await
+        .map_err(|e| reworded(e, "error.draft_failed"))?;
+    StageUsage::estimate("draft", &draft_model.to_string(), objective, &draft).log();
+
+    // language, tone and the request's sampling only matter for the final rendering
+    let polish_client = initialize_llm_client(polish_model)?;
+    let current_date = language::format_date(profile.current_date(), params.language.as_deref());
+    let mut polish_instructions = prompts::render(
+        &prompts::text(PromptKind::Critic),
+        &[
+            ("conference", &profile.name),
+            ("current_date", &current_date),
+        ],
+    );
+    agents::append_language(&mut polish_instructions, params.language.as_deref());
+    agents::append_placeholder_instructions(&mut polish_instructions, params.live_placeholders);
+    tone::append_tone(&mut polish_instructions, params.tone);
+    let polish_agent = build_text_agent(
+        &polish_client,
+        &polish_instructions,
+        &params.for_model(&polish_model.model),
+    );
+    let polish_prompt = generation::polish_prompt(objective, &draft);
+    let polished = prompt_with_retry(&polish_agent, &polish_prompt, health)
+        .await
+        .map_err(|e| reworded(e, "error.polish_failed"))?;
+    StageUsage::estimate(
+        "polish",
+        &polish_model.to_string(),
+        &polish_prompt,
+        &polished,
+    )
+    .log();
+
+    Ok((polished, polish_model.to_string()))
+}
+
+// structure a finished plan, one corrective retry when the slots don't validate
+async fn execute_itinerary_task(
+    client: &LlmClient,
+    model: &str,
+    plan: &str,
+    sources: &[models::VivatechSource],
+) -> Result<itinerary::Plan, String> {
+    info!("Extracting itinerary from plan of {} chars", plan.len());
+
+    let mut input = itinerary::extraction_input(plan, sources);
+    let extracted = extract_itinerary(client, model, &input).await?;
+    let Err(e) = extracted.validate(sources) else {
+        return Ok(extracted);
+    };
+
+    tracing::warn!("Extracted itinerary rejected, retrying: {}", e);
+    input.push_str(&format!(
+        "\nYour previous itinerary was rejected: {}. Fix it.",
+        e
+    ));
+    let extracted = extract_itinerary(client, model, &input).await?;
+    extracted.validate(sources).map_err(|e| {
+        tracing::error!("Extracted itinerary rejected again: {}", e);
+        format!("Agent returned an invalid itinerary - {}", e)
+    })?;
+    Ok(extracted)
+}
+
+async fn extract_itinerary(
+    client: &LlmClient,
+    model: &str,
+    input: &str,
+) -> Result<itinerary::Plan, String> {
+    client
+        .extract::<itinerary::Plan>(model, &prompts::text(PromptKind::Formatting), input)
+        .await
+        .map_err(|e| {
+            copy::error(
+                "error.itinerary_failed",
+                &[("error", e.to_string().as_str())],
+            )
+        })
+}
+
+// single prioritized action instead of a full plan
+async fn execute_next_action_task(
+    client: &LlmClient,
+    choice: &ModelChoice,
+    objective: &str,
+    health: &CapabilityMatrix,
+    build: impl Fn(&LlmClient, &str) -> AnyAgent,
+) -> Result<(NextAction, FallbackAnswer), AgentFailure> {
+    info!("Executing next action task for: {}", objective);
+
+    let answer = prompt_with_fallback(client, choice, objective, health, build)
+        .await
+        .map_err(|e| reworded(e, "error.next_action_failed"))?;
+
+    let next_action = NextAction::from_agent_output(&answer.response).map_err(|e| {
+        tracing::error!("Agent returned an unusable next action: {}", e);
+        e
+    })?;
+    Ok((next_action, answer))
+}
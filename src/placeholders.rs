@@ -0,0 +1,126 @@
+// live placeholders in stored plans, resolved against the current catalog on every read
+
+use std::collections::HashMap;
+
+use regex::{Captures, Regex};
+
+use crate::conference::ConferenceProfile;
+use crate::models::VivatechSource;
+use crate::state::AppState;
+use crate::tools::extract_dates_from_text;
+
+const MAX_SUMMARY_CHARS: usize = 160;
+
+// {{session:<id>}} is a pinned session, {{next:<terms>}} the next session matching all terms
+fn placeholder_pattern() -> Option<Regex> {
+    Regex::new(r"\{\{\s*(session|next)\s*:\s*([^{}]+?)\s*\}\}").ok()
+}
+
+fn has_placeholders(plan: &str) -> bool {
+    plan.contains("{{") && placeholder_pattern().is_some_and(|pattern| pattern.is_match(plan))
+}
+
+// the synced catalog only tracks the default conference. other conferences, and
+// the time before the first sync, fall back to the sources the plan was built from
+pub fn resolve_plan(
+    state: &AppState,
+    conference: &str,
+    plan: &str,
+    sources: &[VivatechSource],
+) -> String {
+    if !has_placeholders(plan) {
+        return plan.to_string();
+    }
+    let Ok(profile) = state.conferences.resolve(Some(conference)) else {
+        return plan.to_string();
+    };
+
+    let from_sources = || -> HashMap<String, VivatechSource> {
+        sources
+            .iter()
+            .map(|source| (source.id.clone(), source.clone()))
+            .collect()
+    };
+    if profile.id != state.conferences.default_profile().id {
+        return resolve(plan, &from_sources(), profile);
+    }
+    state.catalog_watcher.with_snapshot(|snapshot| {
+        if snapshot.is_empty() {
+            resolve(plan, &from_sources(), profile)
+        } else {
+            resolve(plan, snapshot, profile)
+        }
+    })
+}
+
+// replace every placeholder with what `catalog` says now. sessions missing from
+// the catalog are called out instead of silently keeping stale text
+fn resolve(
+    plan: &str,
+    catalog: &HashMap<String, VivatechSource>,
+    profile: &ConferenceProfile,
+) -> String {
+    let Some(pattern) = placeholder_pattern() else {
+        return plan.to_string();
+    };
+
+    pattern
+        .replace_all(plan, |captures: &Captures| {
+            let argument = &captures[2];
+            match &captures[1] {
+                "session" => match catalog.get(argument) {
+                    Some(source) => summary(source),
+                    None => format!("(session {} is no longer in the programme)", argument),
+                },
+                _ => match next_matching(catalog, argument, profile) {
+                    Some(source) => summary(source),
+                    None => format!("(nothing matching \"{}\" is scheduled)", argument),
+                },
+            }
+        })
+        .into_owned()
+}
+
+// earliest upcoming source containing every term, undated matches come last
+fn next_matching<'a>(
+    catalog: &'a HashMap<String, VivatechSource>,
+    terms: &str,
+    profile: &ConferenceProfile,
+) -> Option<&'a VivatechSource> {
+    let terms: Vec<String> = terms.split_whitespace().map(str::to_lowercase).collect();
+    let today = profile.current_date();
+
+    catalog
+        .values()
+        .filter(|source| {
+            let text = source.text_chunk.to_lowercase();
+            terms.iter().all(|term| text.contains(term.as_str()))
+        })
+        .filter_map(|source| {
+            let dates = extract_dates_from_text(&source.text_chunk, profile.year());
+            // undated sessions last, sessions whose every day has passed not at all
+            match dates.iter().find(|date| **date >= today) {
+                Some(date) => Some((false, Some(*date), source)),
+                None if dates.is_empty() => Some((true, None, source)),
+                None => None,
+            }
+        })
+        // ids break ties so the same catalog always resolves the same way
+        .min_by(|a, b| (a.0, a.1, &a.2.id).cmp(&(b.0, b.1, &b.2.id)))
+        .map(|(_, _, source)| source)
+}
+
+// first line of the chunk, which carries the title and usually the slot
+pub fn summary(source: &VivatechSource) -> String {
+    let line = source
+        .text_chunk
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or(source.id.as_str());
+    if line.chars().count() <= MAX_SUMMARY_CHARS {
+        return line.to_string();
+    }
+    let truncated: String = line.chars().take(MAX_SUMMARY_CHARS).collect();
+    format!("{}…", truncated.trim_end())
+}
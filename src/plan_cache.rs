@@ -0,0 +1,111 @@
+// in-process ttl cache for agent runs, so demo traffic and load tests asking the
+// same objective over and over don't spend tokens each time. the run is kept as the
+// model wrote it, every hit is stored, resolved and footed like a fresh plan
+
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+
+use moka::sync::Cache;
+use sha2::{Digest, Sha256};
+
+use crate::config;
+use crate::models::{GeneratePlanRequest, OutputMode, PlanOutcome};
+
+// rebuilt, empty, when the ttl is changed at runtime
+static PLAN_CACHE: RwLock<Option<Arc<PlanCache>>> = RwLock::new(None);
+
+// value of the X-Cache header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheStatus {
+    Hit,
+    Miss,
+}
+
+impl CacheStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CacheStatus::Hit => "HIT",
+            CacheStatus::Miss => "MISS",
+        }
+    }
+}
+
+// a plan before storage: placeholders unresolved, no footer, no plan id
+#[derive(Debug, Clone)]
+pub struct CachedPlan {
+    pub outcome: PlanOutcome,
+    // unset for the simple test mode, which has no conference
+    pub conference: Option<String>,
+}
+
+pub struct PlanCache {
+    entries: Cache<String, CachedPlan>,
+    ttl: Duration,
+}
+
+impl PlanCache {
+    pub fn new(capacity: u64, ttl: Duration) -> Self {
+        Self {
+            entries: Cache::builder()
+                .max_capacity(capacity)
+                .time_to_live(ttl)
+                .build(),
+            ttl,
+        }
+    }
+
+    pub fn get(&self, request: &GeneratePlanRequest) -> Option<CachedPlan> {
+        let cached = self.entries.get(&cache_key(request));
+        let result = if cached.is_some() { "hit" } else { "miss" };
+        metrics::counter!("plan_cache_lookups_total", "result" => result).increment(1);
+        cached
+    }
+
+    // only full plans, degraded listings are worth another try
+    pub fn insert(&self, request: &GeneratePlanRequest, plan: &CachedPlan) {
+        if plan.outcome.listing.is_none() {
+            self.entries.insert(cache_key(request), plan.clone());
+        }
+    }
+
+    // the catalog changed, cached plans may point at moved or cancelled sessions
+    pub fn clear(&self) {
+        self.entries.invalidate_all();
+    }
+}
+
+// requests this cache never answers: next actions depend on the current time,
+// and "bypass_cache" or "regenerate" ask for a fresh plan
+pub fn is_cacheable(request: &GeneratePlanRequest) -> bool {
+    !request.bypass_cache && !request.regenerate && request.mode != OutputMode::NextAction
+}
+
+// the objective is normalized like for coalescing, so "  AI  Sessions" and
+// "ai sessions" share an entry as long as every other setting matches
+fn cache_key(request: &GeneratePlanRequest) -> String {
+    let key = format!("{}:{}", request.coalescing_key(), request.include_citations);
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+// off unless PLAN_CACHE_TTL_SECONDS or /admin/config sets a ttl
+pub fn plan_cache() -> Option<Arc<PlanCache>> {
+    let ttl = Duration::from_secs(config::runtime().plan_cache_ttl_seconds);
+    if ttl.is_zero() {
+        return None;
+    }
+    let current = |cache: &Option<Arc<PlanCache>>| cache.as_ref().filter(|c| c.ttl == ttl).cloned();
+    if let Some(cache) = current(&PLAN_CACHE.read().unwrap_or_else(PoisonError::into_inner)) {
+        return Some(cache);
+    }
+    let mut slot = PLAN_CACHE.write().unwrap_or_else(PoisonError::into_inner);
+    // another request may have rebuilt it in the meantime
+    if let Some(cache) = current(&slot) {
+        return Some(cache);
+    }
+    let cache = Arc::new(PlanCache::new(config::get().plan_cache_capacity, ttl));
+    *slot = Some(cache.clone());
+    Some(cache)
+}
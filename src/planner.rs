@@ -0,0 +1,431 @@
+//! The planner as a library: the agent loop behind `/generate-plan`, with provider
+//! retries, circuit breakers and model fallbacks, for embedding in other Rust services
+//! and for driving the agent from tests.
+//!
+//! ```no_run
+//! use vivaagent::planner::{Planner, PlannerConfig};
+//!
+//! # async fn run() -> Result<(), vivaagent::planner::PlannerError> {
+//! let planner = Planner::new(PlannerConfig::default())?;
+//! let plan = planner.plan("AI keynotes and investor meetups on Thursday").await?;
+//! println!("{}", plan.body);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Settings not in [`PlannerConfig`] come from the environment, as for the api: the
+//! provider keys, `VIVATECH_API_URL`, `LLM_FALLBACK_MODELS`, `MOCK_MODE` and so on.
+
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use rig::completion::{Message, PromptError};
+use serde::Serialize;
+use tracing::{info, Instrument};
+
+use crate::agents::{build_planning_agent, GenerationParams};
+use crate::citations::SourceCollector;
+use crate::conference::{ConferenceProfile, ConferenceRegistry};
+use crate::config;
+use crate::health::{self, CapabilityMatrix};
+use crate::llm::{self, AnyAgent, LlmClient, ModelChoice};
+use crate::models::VivatechSource;
+use crate::retry;
+use crate::tone::Tone;
+
+// (provider, model, prompt, completion) for every successful agent run
+type UsageRecorder = fn(&'static str, &str, &str, &str);
+
+static USAGE_RECORDER: OnceLock<UsageRecorder> = OnceLock::new();
+
+// the api installs its metrics and cost accounting here, embedders may install their own
+pub fn set_usage_recorder(recorder: UsageRecorder) {
+    let _ = USAGE_RECORDER.set(recorder);
+}
+
+fn record_usage(provider: &'static str, model: &str, prompt: &str, completion: &str) {
+    if let Some(recorder) = USAGE_RECORDER.get() {
+        recorder(provider, model, prompt, completion);
+    }
+}
+
+/// Errors from [`Planner::new`] and [`Planner::plan`].
+#[derive(Debug, thiserror::Error)]
+pub enum PlannerError {
+    /// An option out of range or an unknown model.
+    #[error("invalid planner configuration: {0}")]
+    Config(String),
+    /// The provider client could not be set up, usually a missing api key.
+    #[error("model unavailable: {0}")]
+    Unavailable(String),
+    /// Every model tried failed to produce a plan.
+    #[error("plan generation failed: {0}")]
+    Failed(String),
+}
+
+/// How a [`Planner`] generates. Unset fields fall back to the service defaults.
+#[derive(Debug, Clone, Default)]
+pub struct PlannerConfig {
+    /// `gpt-4o` or `provider:model`, `LLM_MODEL` when unset.
+    pub model: Option<String>,
+    /// Conference to plan for, `DEFAULT_CONFERENCE` when unset.
+    pub conference: Option<ConferenceProfile>,
+    /// Between 0 and 2.
+    pub temperature: Option<f64>,
+    /// Up to `PLAN_MAX_TOKENS_LIMIT`.
+    pub max_tokens: Option<u64>,
+    /// Language of the plan, e.g. `"fr"`. The objective's language when unset.
+    pub language: Option<String>,
+    pub tone: Option<Tone>,
+    /// What the planner knows about the attendee, added to its instructions.
+    pub user_context: String,
+}
+
+/// A generated plan.
+#[derive(Debug, Clone, Serialize)]
+pub struct Plan {
+    /// The plan as markdown.
+    pub body: String,
+    /// Sessions and partners the agent found while planning.
+    pub sources: Vec<VivatechSource>,
+    /// The model that wrote it, as `provider:model`.
+    pub model: String,
+    /// The requested model when a fallback model answered instead.
+    pub fallback_from: Option<String>,
+}
+
+/// Plans a conference day with the same agent, tools and instructions as the api.
+///
+/// Create one per configuration and reuse it, plans don't share state.
+pub struct Planner {
+    client: LlmClient,
+    choice: ModelChoice,
+    profile: ConferenceProfile,
+    params: GenerationParams,
+    user_context: String,
+    health: Arc<CapabilityMatrix>,
+}
+
+impl Planner {
+    /// Checks the configuration and sets up the model's provider client.
+    pub fn new(config: PlannerConfig) -> Result<Self, PlannerError> {
+        let choice = ModelChoice::resolve(config.model.as_deref()).map_err(PlannerError::Config)?;
+        let params = GenerationParams::resolve(
+            &choice.model,
+            config.temperature,
+            config.max_tokens,
+            config.language.as_deref(),
+        )
+        .map_err(PlannerError::Config)?
+        .with_tone(config.tone);
+        let client = initialize_llm_client(&choice).map_err(PlannerError::Unavailable)?;
+        let profile = match config.conference {
+            Some(profile) => profile,
+            None => ConferenceRegistry::from_env().default_profile().clone(),
+        };
+        Ok(Self {
+            client,
+            choice,
+            profile,
+            params,
+            user_context: config.user_context,
+            health: Arc::new(CapabilityMatrix::from_env()),
+        })
+    }
+
+    /// Shares circuit breakers with other planners or the api, so a provider that is
+    /// down isn't called again by every planner.
+    pub fn with_health(mut self, health: Arc<CapabilityMatrix>) -> Self {
+        self.health = health;
+        self
+    }
+
+    /// The conference plans are made for.
+    pub fn conference(&self) -> &ConferenceProfile {
+        &self.profile
+    }
+
+    /// Runs the planning agent on `objective`, falling back to `LLM_FALLBACK_MODELS`
+    /// while the configured model is unavailable.
+    pub async fn plan(&self, objective: &str) -> Result<Plan, PlannerError> {
+        let collector = SourceCollector::default();
+        let answer = prompt_with_fallback(
+            &self.client,
+            &self.choice,
+            objective,
+            &self.health,
+            |client, model| {
+                build_planning_agent(
+                    client,
+                    &self.profile,
+                    &self.user_context,
+                    &collector,
+                    &self.health,
+                    &self.params.for_model(model),
+                )
+            },
+        )
+        .await
+        .map_err(|failure| PlannerError::Failed(failure.message))?;
+        Ok(Plan {
+            sources: collector.sources(),
+            model: answer.choice.to_string(),
+            fallback_from: answer.fallback_from(&self.choice),
+            body: answer.response,
+        })
+    }
+}
+
+// setup the provider client for a model from env
+pub fn initialize_llm_client(choice: &ModelChoice) -> Result<LlmClient, String> {
+    match LlmClient::from_env(choice.provider) {
+        Ok(client) => {
+            info!("Using {}", choice);
+            Ok(client)
+        }
+        Err(e) => {
+            tracing::error!(
+                "Failed to initialize {} client: {}",
+                choice.provider.name(),
+                e
+            );
+            Err(e)
+        }
+    }
+}
+
+// prompt the agent, retrying provider rate limits
+pub async fn prompt_with_retry(
+    agent: &AnyAgent,
+    prompt: &str,
+    health: &CapabilityMatrix,
+) -> Result<String, AgentFailure> {
+    chat_with_retry(agent, prompt, &[], health).await
+}
+
+// same with prior turns
+pub async fn chat_with_retry(
+    agent: &AnyAgent,
+    prompt: &str,
+    history: &[Message],
+    health: &CapabilityMatrix,
+) -> Result<String, AgentFailure> {
+    run_agent(agent, prompt, history, health).await
+}
+
+// a failed agent run. `unavailable` when another model may still answer: rate limits,
+// server errors and an open breaker, but not bad requests or tool errors.
+// `retry_after` is the provider's back-off hint when this run was rate limited
+#[derive(Debug, Clone)]
+pub struct AgentFailure {
+    pub message: String,
+    pub unavailable: bool,
+    pub retry_after: Option<Duration>,
+}
+
+impl AgentFailure {
+    fn unavailable(message: String) -> Self {
+        Self {
+            message,
+            unavailable: true,
+            retry_after: None,
+        }
+    }
+
+    fn rate_limited(message: String, retry_after: Duration) -> Self {
+        Self {
+            message,
+            unavailable: true,
+            retry_after: Some(retry_after),
+        }
+    }
+
+    fn failed(message: String) -> Self {
+        Self {
+            message,
+            unavailable: false,
+            retry_after: None,
+        }
+    }
+
+    // the same failure told in other words, the back-off hint is kept
+    pub fn with_message(self, message: String) -> Self {
+        Self { message, ..self }
+    }
+}
+
+impl fmt::Display for AgentFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+// e.g. an unparsable answer, nothing to fall back or wait for
+impl From<String> for AgentFailure {
+    fn from(message: String) -> Self {
+        Self::failed(message)
+    }
+}
+
+// the response and the model that gave it, with its client for follow-up calls
+pub struct FallbackAnswer {
+    pub response: String,
+    pub choice: ModelChoice,
+    pub client: LlmClient,
+}
+
+impl FallbackAnswer {
+    // the requested model when another one answered
+    pub fn fallback_from(&self, requested: &ModelChoice) -> Option<String> {
+        (&self.choice != requested).then(|| requested.to_string())
+    }
+}
+
+// the chosen model, then each of LLM_FALLBACK_MODELS while the previous one is
+// unavailable. the agent is built anew for every model
+pub async fn prompt_with_fallback(
+    client: &LlmClient,
+    choice: &ModelChoice,
+    prompt: &str,
+    health: &CapabilityMatrix,
+    build: impl Fn(&LlmClient, &str) -> AnyAgent,
+) -> Result<FallbackAnswer, AgentFailure> {
+    let mut failure = match run_agent(&build(client, &choice.model), prompt, &[], health).await {
+        Ok(response) => {
+            return Ok(FallbackAnswer {
+                response,
+                choice: choice.clone(),
+                client: client.clone(),
+            })
+        }
+        Err(failure) => failure,
+    };
+
+    let mut failed = choice.clone();
+    for fallback in llm::fallback_chain(choice) {
+        if !failure.unavailable {
+            break;
+        }
+        let Ok(fallback_client) = initialize_llm_client(&fallback) else {
+            continue;
+        };
+        tracing::warn!(
+            "{} unavailable, falling back to {}: {}",
+            failed,
+            fallback,
+            failure.message
+        );
+        metrics::counter!(
+            "llm_fallbacks_total",
+            "from" => failed.to_string(),
+            "to" => fallback.to_string()
+        )
+        .increment(1);
+        let agent = build(&fallback_client, &fallback.model);
+        match run_agent(&agent, prompt, &[], health).await {
+            Ok(response) => {
+                return Ok(FallbackAnswer {
+                    response,
+                    choice: fallback,
+                    client: fallback_client,
+                })
+            }
+            Err(next) => {
+                failure = next;
+                failed = fallback;
+            }
+        }
+    }
+    Err(failure)
+}
+
+// each attempt starts from the original history. rate limits and server errors are
+// retried with backoff, server errors that outlast the retries feed the provider's
+// breaker and calls fail fast while it is open
+pub async fn run_agent(
+    agent: &AnyAgent,
+    prompt: &str,
+    history: &[Message],
+    health: &CapabilityMatrix,
+) -> Result<String, AgentFailure> {
+    let policy = &config::get().retry;
+    let max_turns = config::get().agent_max_turns;
+    let circuit = agent.provider().circuit();
+    let mut attempt = 1;
+
+    if health.status(circuit) == health::ToolHealth::Down {
+        let retry_in = health.retry_in(circuit).unwrap_or_default();
+        tracing::warn!("Skipping agent run, {} circuit is open", circuit);
+        return Err(AgentFailure::unavailable(format!(
+            "{} is unavailable, retry in {}s",
+            agent.provider().name(),
+            retry_in.as_secs().max(1)
+        )));
+    }
+
+    loop {
+        let run = tracing::info_span!("agent_run", provider = agent.provider().name(), attempt);
+        match agent.chat(prompt, history, max_turns).instrument(run).await {
+            Ok(response) => {
+                info!("Agent successfully generated response");
+                health.record_success(circuit);
+                record_usage(agent.provider().name(), agent.model(), prompt, &response);
+                return Ok(response);
+            }
+            // tool errors already went through the vivatech retry policy
+            Err(PromptError::CompletionError(e))
+                if retry::is_rate_limit_message(&e.to_string()) =>
+            {
+                let hint = retry::retry_after_from_message(&e.to_string());
+                if let Some(delay) = policy.next_delay(attempt, hint) {
+                    tracing::warn!(
+                        "Provider rate limited (attempt {}/{}), retrying in {}ms",
+                        attempt,
+                        policy.max_attempts,
+                        delay.as_millis()
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                    continue;
+                }
+
+                tracing::error!("Agent execution rate limited: {}", e);
+                return Err(AgentFailure::rate_limited(
+                    e.to_string(),
+                    hint.unwrap_or(policy.max_delay),
+                ));
+            }
+            Err(PromptError::CompletionError(e))
+                if retry::is_server_error_message(&e.to_string()) =>
+            {
+                if let Some(delay) = policy.next_delay(attempt, None) {
+                    tracing::warn!(
+                        "Provider error (attempt {}/{}), retrying in {}ms: {}",
+                        attempt,
+                        policy.max_attempts,
+                        delay.as_millis(),
+                        e
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                    continue;
+                }
+
+                health.record_failure(circuit);
+                tracing::error!("Agent execution failed: {}", e);
+                return Err(AgentFailure::unavailable(e.to_string()));
+            }
+            // bad requests, e.g. a prompt over the context window, say nothing about the
+            // provider being up, only server errors count against its breaker
+            Err(PromptError::CompletionError(e)) => {
+                tracing::error!("Agent execution failed: {}", e);
+                return Err(AgentFailure::failed(e.to_string()));
+            }
+            Err(e) => {
+                tracing::error!("Agent execution failed: {}", e);
+                return Err(AgentFailure::failed(e.to_string()));
+            }
+        }
+    }
+}
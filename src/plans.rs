@@ -0,0 +1,786 @@
+// generated plans persisted in sqlite for sharing, auditing and follow-up questions,
+// next to what a known user said in chats and recaps, for their exports
+
+use std::sync::{Mutex, PoisonError};
+
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tracing::{error, info};
+
+use crate::citations::{self, Citation};
+use crate::config;
+use crate::models::{GeneratePlanRequest, PlanOutcome, VivatechSource};
+use crate::recap::SessionFeedback;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PlanStoreError {
+    #[error("Plan store error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("Plan store encoding error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+// everything recorded about one generated plan
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct StoredPlan {
+    pub id: String,
+    pub user_id: Option<String>,
+    pub conference: String,
+    pub objective: String,
+    // the request as received, for auditing
+    pub request: serde_json::Value,
+    pub plan: String,
+    pub sources: Vec<VivatechSource>,
+    pub model: String,
+    pub created_at: DateTime<Utc>,
+    // X-Request-Id of the request that generated it, unset for plans stored before it was kept
+    pub request_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PlanSummary {
+    pub id: String,
+    pub conference: String,
+    pub objective: String,
+    pub model: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// one chat exchange of a user, kept past the chat session's ttl
+#[derive(Debug, Clone, Serialize)]
+pub struct StoredExchange {
+    pub session_id: String,
+    pub message: String,
+    pub reply: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// feedback on a conference session, as sent with a recap request
+#[derive(Debug, Clone, Serialize)]
+pub struct StoredFeedback {
+    pub conference: String,
+    pub session_id: String,
+    pub rating: Option<u8>,
+    pub comment: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+// a rating of a whole generated plan, see feedback.rs
+#[derive(Debug, Clone, Serialize)]
+pub struct StoredPlanFeedback {
+    pub plan_id: String,
+    pub rating: u8,
+    pub comment: Option<String>,
+    // feedback issue names, picked by the user or read from the comment
+    pub issues: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+// how often a session was put in a plan or favorited, see popularity.rs
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct SessionPopularity {
+    pub session_id: String,
+    // first line of the session from the latest catalog sync
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub plans: u64,
+    pub favorites: u64,
+    // plans plus FAVORITE_WEIGHT per favorite, what the list is sorted by
+    pub score: u64,
+}
+
+// a favorite is a stronger intent to attend than a plan mention
+pub const FAVORITE_WEIGHT: u64 = 3;
+
+#[derive(Debug, Deserialize)]
+pub struct PlanListQuery {
+    pub user: String,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+impl StoredPlan {
+    // 1-based, same numbering as citations
+    pub fn step(&self, n: usize) -> Option<&str> {
+        let index = n.checked_sub(1)?;
+        citations::plan_steps(&self.plan).get(index).copied()
+    }
+
+    // transcript plus the sources the agent saw, nothing else to go on
+    pub fn explain_prompt(&self, n: usize, step: &str) -> String {
+        let mut prompt = format!(
+            "Attendee objective:\n{}\n\nFull plan:\n{}\n\nStep {} to explain:\n{}\n\nSources:\n",
+            self.objective, self.plan, n, step
+        );
+        for source in &self.sources {
+            prompt.push_str(&format!("[{}] {}\n", source.id, source.text_chunk));
+        }
+        prompt
+    }
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ExplainResponse {
+    pub plan_id: String,
+    pub step: usize,
+    pub step_text: String,
+    pub explanation: String,
+    // supporting snippets for this step only
+    pub citations: Vec<Citation>,
+    pub elapsed_ms: u64,
+}
+
+// plans are owned by the api key that generated them, keyless plans (widget, bots,
+// AUTH_DISABLED) by nobody. reads and writes on behalf of a caller pass its key as
+// `owner` and only see its own plans
+pub struct PlanStore {
+    conn: Mutex<Connection>,
+}
+
+// database file with fallback
+fn get_plan_db_path() -> String {
+    config::env_var("PLAN_DB_PATH").unwrap_or_else(|_| "data/plans.sqlite".to_string())
+}
+
+impl PlanStore {
+    // falls back to an in-memory database so planning keeps working without a disk
+    pub fn from_env() -> Self {
+        let path = get_plan_db_path();
+        match Self::open(&path) {
+            Ok(store) => {
+                info!("Plan store opened at {}", path);
+                store
+            }
+            Err(e) => {
+                error!(
+                    "Failed to open plan store at {}, plans won't survive restarts: {}",
+                    path, e
+                );
+                let conn =
+                    Connection::open_in_memory().expect("in-memory sqlite is always available");
+                Self::with_connection(conn).expect("in-memory schema creation cannot fail")
+            }
+        }
+    }
+
+    pub fn open(path: &str) -> Result<Self, PlanStoreError> {
+        if let Some(parent) = std::path::Path::new(path).parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self, PlanStoreError> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS plans (
+                id TEXT PRIMARY KEY,
+                user_id TEXT,
+                conference TEXT NOT NULL,
+                objective TEXT NOT NULL,
+                request TEXT NOT NULL,
+                plan TEXT NOT NULL,
+                sources TEXT NOT NULL,
+                model TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS plans_by_user ON plans (user_id, created_at);
+            CREATE TABLE IF NOT EXISTS plan_shares (
+                token TEXT PRIMARY KEY,
+                plan_id TEXT NOT NULL REFERENCES plans (id),
+                created_at TEXT NOT NULL,
+                expires_at TEXT
+            );
+            CREATE TABLE IF NOT EXISTS chat_exchanges (
+                user_id TEXT NOT NULL,
+                session_id TEXT NOT NULL,
+                message TEXT NOT NULL,
+                reply TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS chat_exchanges_by_user ON chat_exchanges (user_id, created_at);
+            CREATE TABLE IF NOT EXISTS session_feedback (
+                user_id TEXT NOT NULL,
+                conference TEXT NOT NULL,
+                session_id TEXT NOT NULL,
+                rating INTEGER,
+                comment TEXT,
+                created_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS session_feedback_by_user ON session_feedback (user_id, created_at);
+            CREATE TABLE IF NOT EXISTS user_favorites (
+                user_id TEXT NOT NULL,
+                conference TEXT NOT NULL,
+                session_id TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                PRIMARY KEY (user_id, conference, session_id)
+            );
+            CREATE TABLE IF NOT EXISTS session_picks (
+                conference TEXT NOT NULL,
+                session_id TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS session_picks_by_conference ON session_picks (conference, created_at);
+            CREATE TABLE IF NOT EXISTS plan_feedback (
+                plan_id TEXT NOT NULL REFERENCES plans (id),
+                user_id TEXT,
+                conference TEXT NOT NULL,
+                rating INTEGER NOT NULL,
+                comment TEXT,
+                issues TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS plan_feedback_by_conference ON plan_feedback (conference, created_at);
+            CREATE TABLE IF NOT EXISTS runtime_settings (
+                name TEXT PRIMARY KEY,
+                value TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );",
+        )?;
+        add_column_if_missing(&conn, "request_id", "TEXT")?;
+        add_column_if_missing(&conn, "owner", "TEXT")?;
+        conn.execute_batch("CREATE INDEX IF NOT EXISTS plans_by_request ON plans (request_id);")?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    // returns the new plan id
+    pub fn insert(
+        &self,
+        request: &GeneratePlanRequest,
+        conference: &str,
+        outcome: &PlanOutcome,
+        request_id: Option<&str>,
+        owner: Option<&str>,
+    ) -> Result<String, PlanStoreError> {
+        let id = new_plan_id();
+        let request_json = serde_json::to_string(request)?;
+        let sources = serde_json::to_string(&outcome.sources)?;
+
+        let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        conn.execute(
+            "INSERT INTO plans (id, user_id, conference, objective, request, plan, sources, model, created_at, request_id, owner)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                id,
+                request.user_id,
+                conference,
+                request.objective,
+                request_json,
+                outcome.body,
+                sources,
+                outcome.model,
+                Utc::now().to_rfc3339(),
+                request_id,
+                owner,
+            ],
+        )?;
+        Ok(id)
+    }
+
+    // None for unknown plans and for plans of another owner alike
+    pub fn get(&self, id: &str, owner: Option<&str>) -> Result<Option<StoredPlan>, PlanStoreError> {
+        let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        let row = conn
+            .query_row(
+                "SELECT id, user_id, conference, objective, request, plan, sources, model, created_at, request_id
+                 FROM plans WHERE id = ?1 AND owner IS ?2",
+                params![id, owner],
+                read_plan_row,
+            )
+            .optional()?;
+
+        row.map(PlanRow::decode).transpose()
+    }
+
+    // plans generated by one request, oldest first. batches store several under
+    // one request id, a plan id matches just that plan
+    pub fn logged(&self, id: &str) -> Result<Vec<StoredPlan>, PlanStoreError> {
+        let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut statement = conn.prepare(
+            "SELECT id, user_id, conference, objective, request, plan, sources, model, created_at, request_id
+             FROM plans WHERE request_id = ?1 OR id = ?1 ORDER BY created_at",
+        )?;
+        let rows = statement
+            .query_map(params![id], read_plan_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        rows.into_iter().map(PlanRow::decode).collect()
+    }
+
+    // records a public link token, false when the owner has no such plan
+    pub fn share(
+        &self,
+        plan_id: &str,
+        owner: Option<&str>,
+        token: &str,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<bool, PlanStoreError> {
+        let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        let inserted = conn.execute(
+            "INSERT INTO plan_shares (token, plan_id, created_at, expires_at)
+             SELECT ?1, id, ?2, ?3 FROM plans WHERE id = ?4 AND owner IS ?5",
+            params![
+                token,
+                Utc::now().to_rfc3339(),
+                expires_at.map(|t| t.to_rfc3339()),
+                plan_id,
+                owner
+            ],
+        )?;
+        Ok(inserted > 0)
+    }
+
+    // the plan behind a share token with the link expiry, None once expired
+    pub fn shared(
+        &self,
+        token: &str,
+    ) -> Result<Option<(StoredPlan, Option<DateTime<Utc>>)>, PlanStoreError> {
+        let (plan_id, expires_at) = {
+            let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+            let share: Option<(String, Option<String>)> = conn
+                .query_row(
+                    "SELECT plan_id, expires_at FROM plan_shares WHERE token = ?1",
+                    params![token],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()?;
+            match share {
+                Some(share) => share,
+                None => return Ok(None),
+            }
+        };
+
+        let expires_at = expires_at.map(parse_timestamp);
+        if expires_at.is_some_and(|t| t <= Utc::now()) {
+            return Ok(None);
+        }
+        // the token is the credential, whoever owns the plan
+        let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        let row = conn
+            .query_row(
+                "SELECT id, user_id, conference, objective, request, plan, sources, model, created_at, request_id
+                 FROM plans WHERE id = ?1",
+                params![plan_id],
+                read_plan_row,
+            )
+            .optional()?;
+        Ok(row
+            .map(PlanRow::decode)
+            .transpose()?
+            .map(|plan| (plan, expires_at)))
+    }
+
+    // newest first
+    pub fn list_for_user(
+        &self,
+        user_id: &str,
+        owner: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Vec<PlanSummary>, PlanStoreError> {
+        let limit = limit
+            .unwrap_or(usize::MAX)
+            .min(config::get().plan_list_limit);
+        let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut statement = conn.prepare(
+            "SELECT id, conference, objective, model, created_at FROM plans
+             WHERE user_id = ?1 AND owner IS ?2 ORDER BY created_at DESC LIMIT ?3",
+        )?;
+        let rows = statement.query_map(params![user_id, owner, limit as i64], |row| {
+            Ok(PlanSummary {
+                id: row.get(0)?,
+                conference: row.get(1)?,
+                objective: row.get(2)?,
+                model: row.get(3)?,
+                created_at: parse_timestamp(row.get(4)?),
+            })
+        })?;
+
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    // every plan of the user, oldest first and without the listing limit
+    pub fn all_for_user(
+        &self,
+        user_id: &str,
+        owner: Option<&str>,
+    ) -> Result<Vec<StoredPlan>, PlanStoreError> {
+        let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut statement = conn.prepare(
+            "SELECT id, user_id, conference, objective, request, plan, sources, model, created_at, request_id
+             FROM plans WHERE user_id = ?1 AND owner IS ?2 ORDER BY created_at",
+        )?;
+        let rows = statement
+            .query_map(params![user_id, owner], read_plan_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        rows.into_iter().map(PlanRow::decode).collect()
+    }
+
+    pub fn record_exchange(
+        &self,
+        user_id: &str,
+        session_id: &str,
+        message: &str,
+        reply: &str,
+    ) -> Result<(), PlanStoreError> {
+        let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        conn.execute(
+            "INSERT INTO chat_exchanges (user_id, session_id, message, reply, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![user_id, session_id, message, reply, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    // oldest first
+    pub fn exchanges_for_user(&self, user_id: &str) -> Result<Vec<StoredExchange>, PlanStoreError> {
+        let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut statement = conn.prepare(
+            "SELECT session_id, message, reply, created_at FROM chat_exchanges
+             WHERE user_id = ?1 ORDER BY created_at, rowid",
+        )?;
+        let rows = statement.query_map(params![user_id], |row| {
+            Ok(StoredExchange {
+                session_id: row.get(0)?,
+                message: row.get(1)?,
+                reply: row.get(2)?,
+                created_at: parse_timestamp(row.get(3)?),
+            })
+        })?;
+
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    pub fn record_feedback(
+        &self,
+        user_id: &str,
+        conference: &str,
+        feedback: &[SessionFeedback],
+    ) -> Result<(), PlanStoreError> {
+        let mut conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        let created_at = Utc::now().to_rfc3339();
+        let tx = conn.transaction()?;
+        for entry in feedback {
+            tx.execute(
+                "INSERT INTO session_feedback (user_id, conference, session_id, rating, comment, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    user_id,
+                    conference,
+                    entry.session_id,
+                    entry.rating,
+                    entry.comment,
+                    created_at
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    // oldest first
+    pub fn feedback_for_user(&self, user_id: &str) -> Result<Vec<StoredFeedback>, PlanStoreError> {
+        let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut statement = conn.prepare(
+            "SELECT conference, session_id, rating, comment, created_at FROM session_feedback
+             WHERE user_id = ?1 ORDER BY created_at, rowid",
+        )?;
+        let rows = statement.query_map(params![user_id], |row| {
+            Ok(StoredFeedback {
+                conference: row.get(0)?,
+                session_id: row.get(1)?,
+                rating: row.get(2)?,
+                comment: row.get(3)?,
+                created_at: parse_timestamp(row.get(4)?),
+            })
+        })?;
+
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    // sessions a user starred, sent again they are kept once
+    pub fn record_user_favorites(
+        &self,
+        user_id: &str,
+        conference: &str,
+        session_ids: &[String],
+    ) -> Result<(), PlanStoreError> {
+        let mut conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        let created_at = Utc::now().to_rfc3339();
+        let tx = conn.transaction()?;
+        for session_id in session_ids {
+            tx.execute(
+                "INSERT OR IGNORE INTO user_favorites (user_id, conference, session_id, created_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![user_id, conference, session_id, created_at],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    // oldest first
+    pub fn user_favorites(
+        &self,
+        user_id: &str,
+        conference: &str,
+    ) -> Result<Vec<String>, PlanStoreError> {
+        let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut statement = conn.prepare(
+            "SELECT session_id FROM user_favorites
+             WHERE user_id = ?1 AND conference = ?2 ORDER BY created_at, rowid",
+        )?;
+        let rows = statement.query_map(params![user_id, conference], |row| row.get(0))?;
+
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    pub fn record_plan_feedback(
+        &self,
+        plan: &StoredPlan,
+        rating: u8,
+        comment: Option<&str>,
+        issues: &[String],
+    ) -> Result<(), PlanStoreError> {
+        let issues = serde_json::to_string(issues)?;
+        let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        conn.execute(
+            "INSERT INTO plan_feedback (plan_id, user_id, conference, rating, comment, issues, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                plan.id,
+                plan.user_id,
+                plan.conference,
+                rating,
+                comment,
+                issues,
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    // newest first, feedback since `since` or all of it
+    pub fn plan_feedback(
+        &self,
+        conference: &str,
+        since: Option<DateTime<Utc>>,
+    ) -> Result<Vec<StoredPlanFeedback>, PlanStoreError> {
+        let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut statement = conn.prepare(
+            "SELECT plan_id, rating, comment, issues, created_at FROM plan_feedback
+             WHERE conference = ?1 AND (?2 IS NULL OR created_at >= ?2)
+             ORDER BY created_at DESC, rowid DESC",
+        )?;
+        let rows = statement
+            .query_map(params![conference, since.map(|t| t.to_rfc3339())], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, u8>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        rows.into_iter()
+            .map(|(plan_id, rating, comment, issues, created_at)| {
+                Ok(StoredPlanFeedback {
+                    plan_id,
+                    rating,
+                    comment,
+                    issues: serde_json::from_str(&issues)?,
+                    created_at: parse_timestamp(created_at),
+                })
+            })
+            .collect()
+    }
+
+    // kind is "plan" or "favorite"
+    pub fn record_picks(
+        &self,
+        conference: &str,
+        kind: &str,
+        session_ids: &[String],
+    ) -> Result<(), PlanStoreError> {
+        let mut conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        let created_at = Utc::now().to_rfc3339();
+        let tx = conn.transaction()?;
+        for session_id in session_ids {
+            tx.execute(
+                "INSERT INTO session_picks (conference, session_id, kind, created_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![conference, session_id, kind, created_at],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    // most picked first, counting picks since `since` or all of them
+    pub fn popular_sessions(
+        &self,
+        conference: &str,
+        since: Option<DateTime<Utc>>,
+        limit: usize,
+    ) -> Result<Vec<SessionPopularity>, PlanStoreError> {
+        let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut statement = conn.prepare(
+            "SELECT session_id,
+                    SUM(kind = 'plan') AS plans,
+                    SUM(kind = 'favorite') AS favorites
+             FROM session_picks
+             WHERE conference = ?1 AND (?2 IS NULL OR created_at >= ?2)
+             GROUP BY session_id
+             ORDER BY SUM(kind = 'plan') + ?3 * SUM(kind = 'favorite') DESC, session_id
+             LIMIT ?4",
+        )?;
+        let rows = statement.query_map(
+            params![
+                conference,
+                since.map(|t| t.to_rfc3339()),
+                FAVORITE_WEIGHT as i64,
+                limit as i64
+            ],
+            |row| {
+                let plans = row.get::<_, i64>(1)?.max(0) as u64;
+                let favorites = row.get::<_, i64>(2)?.max(0) as u64;
+                Ok(SessionPopularity {
+                    session_id: row.get(0)?,
+                    title: None,
+                    plans,
+                    favorites,
+                    score: plans + FAVORITE_WEIGHT * favorites,
+                })
+            },
+        )?;
+
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    // settings changed through /admin/config, json values by name
+    pub fn setting_overrides(&self) -> Result<Map<String, Value>, PlanStoreError> {
+        let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut statement = conn.prepare("SELECT name, value FROM runtime_settings")?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        rows.into_iter()
+            .map(|(name, value)| Ok((name, serde_json::from_str(&value)?)))
+            .collect()
+    }
+
+    pub fn save_setting_overrides(
+        &self,
+        overrides: &Map<String, Value>,
+    ) -> Result<(), PlanStoreError> {
+        let mut conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        let updated_at = Utc::now().to_rfc3339();
+        let tx = conn.transaction()?;
+        for (name, value) in overrides {
+            tx.execute(
+                "INSERT INTO runtime_settings (name, value, updated_at) VALUES (?1, ?2, ?3)
+                 ON CONFLICT (name) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+                params![name, serde_json::to_string(value)?, updated_at],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    // false when the setting wasn't overridden
+    pub fn delete_setting_override(&self, name: &str) -> Result<bool, PlanStoreError> {
+        let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        let deleted = conn.execute(
+            "DELETE FROM runtime_settings WHERE name = ?1",
+            params![name],
+        )?;
+        Ok(deleted > 0)
+    }
+}
+
+// 128 random bits, a plan id is not a hint at any other
+fn new_plan_id() -> String {
+    format!("plan-{:032x}", rand::random::<u128>())
+}
+
+// raw columns, json decoded outside the row callback
+struct PlanRow {
+    id: String,
+    user_id: Option<String>,
+    conference: String,
+    objective: String,
+    request: String,
+    plan: String,
+    sources: String,
+    model: String,
+    created_at: String,
+    request_id: Option<String>,
+}
+
+impl PlanRow {
+    fn decode(self) -> Result<StoredPlan, PlanStoreError> {
+        Ok(StoredPlan {
+            id: self.id,
+            user_id: self.user_id,
+            conference: self.conference,
+            objective: self.objective,
+            request: serde_json::from_str(&self.request)?,
+            plan: self.plan,
+            sources: serde_json::from_str(&self.sources)?,
+            model: self.model,
+            created_at: parse_timestamp(self.created_at),
+            request_id: self.request_id,
+        })
+    }
+}
+
+fn read_plan_row(row: &Row) -> rusqlite::Result<PlanRow> {
+    Ok(PlanRow {
+        id: row.get(0)?,
+        user_id: row.get(1)?,
+        conference: row.get(2)?,
+        objective: row.get(3)?,
+        request: row.get(4)?,
+        plan: row.get(5)?,
+        sources: row.get(6)?,
+        model: row.get(7)?,
+        created_at: row.get(8)?,
+        request_id: row.get(9)?,
+    })
+}
+
+// plan stores created before a column existed get it on open
+fn add_column_if_missing(
+    conn: &Connection,
+    column: &str,
+    definition: &str,
+) -> Result<(), PlanStoreError> {
+    let mut statement = conn.prepare("SELECT name FROM pragma_table_info('plans')")?;
+    let columns = statement
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    if !columns.iter().any(|name| name == column) {
+        conn.execute_batch(&format!(
+            "ALTER TABLE plans ADD COLUMN {} {}",
+            column, definition
+        ))?;
+    }
+    Ok(())
+}
+
+fn parse_timestamp(raw: String) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(&raw)
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or_default()
+}
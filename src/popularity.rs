@@ -0,0 +1,132 @@
+// how often each session ends up in generated plans and favorites, so the ops team
+// sees which rooms are likely to overflow, and optionally a prior for the planner
+
+use std::collections::HashSet;
+
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+
+use crate::citations;
+use crate::conference::ConferenceProfile;
+use crate::config;
+use crate::models::{PlanOutcome, VivatechSource};
+use crate::placeholders;
+use crate::plans::{PlanStoreError, SessionPopularity};
+use crate::state::AppState;
+
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 200;
+
+// sessions named in the planner prior
+const PRIOR_SESSIONS: usize = 5;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PopularityQuery {
+    // the default conference when unset
+    #[serde(default)]
+    pub conference: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    // only picks from the last `hours`, all of them when unset
+    #[serde(default)]
+    pub hours: Option<i64>,
+}
+
+// sessions the plan cites, partners and other sources are not rooms
+pub fn cited_sessions<'a>(plan: &str, sources: &'a [VivatechSource]) -> Vec<&'a VivatechSource> {
+    let cited: HashSet<String> = citations::cite_plan(plan, sources)
+        .into_iter()
+        .map(|citation| citation.source_id)
+        .collect();
+    sources
+        .iter()
+        .filter(|source| source.source_table == "sessions" && cited.contains(&source.id))
+        .collect()
+}
+
+fn planned_sessions(outcome: &PlanOutcome) -> Vec<String> {
+    cited_sessions(&outcome.body, &outcome.sources)
+        .into_iter()
+        .map(|source| source.id.clone())
+        .collect()
+}
+
+// a store failure only costs the count
+pub fn record_plan(state: &AppState, conference: &str, outcome: &PlanOutcome) {
+    let sessions = planned_sessions(outcome);
+    if sessions.is_empty() {
+        return;
+    }
+    if let Err(e) = state.plans.record_picks(conference, "plan", &sessions) {
+        tracing::error!("Failed to count planned sessions: {}", e);
+    }
+}
+
+pub fn record_favorites(state: &AppState, conference: &str, favorites: &[String]) {
+    let mut seen = HashSet::new();
+    let favorites: Vec<String> = favorites
+        .iter()
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty() && seen.insert(id.clone()))
+        .collect();
+    if favorites.is_empty() {
+        return;
+    }
+    if let Err(e) = state.plans.record_picks(conference, "favorite", &favorites) {
+        tracing::error!("Failed to count favorite sessions: {}", e);
+    }
+}
+
+pub fn popular(
+    state: &AppState,
+    profile: &ConferenceProfile,
+    query: &PopularityQuery,
+) -> Result<Vec<SessionPopularity>, PlanStoreError> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let since = query
+        .hours
+        .filter(|hours| *hours > 0)
+        .map(|hours| Utc::now() - Duration::hours(hours));
+    let mut sessions = state.plans.popular_sessions(&profile.id, since, limit)?;
+
+    // titles from the latest sync, which only covers the default conference
+    if profile.id == state.conferences.default_profile().id {
+        state.catalog_watcher.with_snapshot(|snapshot| {
+            for session in &mut sessions {
+                session.title = snapshot.get(&session.session_id).map(placeholders::summary);
+            }
+        });
+    }
+    Ok(sessions)
+}
+
+// one line per session for the planner preamble when POPULARITY_PRIOR_ENABLED is set,
+// empty otherwise
+pub fn prior(state: &AppState, profile: &ConferenceProfile) -> String {
+    if !config::runtime().popularity_prior_enabled {
+        return String::new();
+    }
+    let query = PopularityQuery {
+        limit: Some(PRIOR_SESSIONS),
+        ..PopularityQuery::default()
+    };
+    let sessions = match popular(state, profile, &query) {
+        Ok(sessions) => sessions,
+        Err(e) => {
+            tracing::warn!("Popularity prior unavailable: {}", e);
+            return String::new();
+        }
+    };
+    sessions
+        .iter()
+        .map(|session| {
+            format!(
+                "- {} ({} plans, {} favorites)",
+                session.title.as_deref().unwrap_or(&session.session_id),
+                session.plans,
+                session.favorites
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
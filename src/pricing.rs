@@ -0,0 +1,96 @@
+// estimated usd cost of agent runs from a per-model price table
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::config;
+
+static PRICES: OnceLock<HashMap<String, ModelPrice>> = OnceLock::new();
+
+// usd per million tokens
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ModelPrice {
+    pub prompt_per_million: f64,
+    pub completion_per_million: f64,
+}
+
+// list prices when this table was written, MODEL_PRICING overrides or adds models
+const DEFAULT_PRICES: &[(&str, f64, f64)] = &[
+    ("gpt-4o", 2.5, 10.0),
+    ("gpt-4o-mini", 0.15, 0.6),
+    ("gpt-4.1", 2.0, 8.0),
+    ("gpt-4.1-mini", 0.4, 1.6),
+    ("claude-3-5-sonnet", 3.0, 15.0),
+    ("claude-3-5-haiku", 0.8, 4.0),
+    ("claude-3-7-sonnet", 3.0, 15.0),
+    ("gemini-1.5-pro", 1.25, 5.0),
+    ("gemini-1.5-flash", 0.075, 0.3),
+    ("gemini-2.0-flash", 0.1, 0.4),
+];
+
+fn prices() -> &'static HashMap<String, ModelPrice> {
+    PRICES.get_or_init(|| {
+        let mut prices: HashMap<String, ModelPrice> = DEFAULT_PRICES
+            .iter()
+            .map(|(model, prompt, completion)| {
+                (
+                    model.to_string(),
+                    ModelPrice {
+                        prompt_per_million: *prompt,
+                        completion_per_million: *completion,
+                    },
+                )
+            })
+            .collect();
+        // e.g. {"gpt-4o": {"prompt_per_million": 2.5, "completion_per_million": 10.0}}
+        if let Ok(raw) = config::env_var("MODEL_PRICING") {
+            match serde_json::from_str::<HashMap<String, ModelPrice>>(&raw) {
+                Ok(overrides) => prices.extend(overrides),
+                Err(e) => warn!("Ignoring invalid MODEL_PRICING: {}", e),
+            }
+        }
+        prices
+    })
+}
+
+// exact name first, then the longest known prefix so dated or "-latest"
+// variants share their family's price. local and mock models are free
+pub fn price_for(provider: &str, model: &str) -> Option<ModelPrice> {
+    if provider == "ollama" || provider == "mock" {
+        return Some(ModelPrice {
+            prompt_per_million: 0.0,
+            completion_per_million: 0.0,
+        });
+    }
+    let prices = prices();
+    prices.get(model).copied().or_else(|| {
+        prices
+            .iter()
+            .filter(|(known, _)| model.starts_with(known.as_str()))
+            .max_by_key(|(known, _)| known.len())
+            .map(|(_, price)| *price)
+    })
+}
+
+// models missing from the table are logged and counted as free
+pub fn estimate_cost(
+    provider: &str,
+    model: &str,
+    prompt_tokens: u64,
+    completion_tokens: u64,
+) -> f64 {
+    match price_for(provider, model) {
+        Some(price) => {
+            (prompt_tokens as f64 * price.prompt_per_million
+                + completion_tokens as f64 * price.completion_per_million)
+                / 1_000_000.0
+        }
+        None => {
+            warn!("No price for {}:{}, counted as free", provider, model);
+            0.0
+        }
+    }
+}
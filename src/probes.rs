@@ -0,0 +1,165 @@
+// liveness, readiness and version probes for load balancers and shuttle
+
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use rig::tool::Tool;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::health;
+use crate::state::AppState;
+use crate::tools::{
+    get_vivatech_api_url, AssessTimeliness, DetectScheduleConflicts, ExportItineraryToIcal,
+    QueryVivatechAPI,
+};
+use crate::{config, http, llm, mock};
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ProbeCheck {
+    pub ok: bool,
+    pub detail: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ReadinessReport {
+    pub ready: bool,
+    pub checks: BTreeMap<&'static str, ProbeCheck>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct VersionInfo {
+    pub name: &'static str,
+    pub version: &'static str,
+    // set by CI through GIT_SHA at build time
+    pub git_sha: Option<&'static str>,
+    pub profile: &'static str,
+    pub features: Vec<&'static str>,
+}
+
+pub async fn check_readiness() -> ReadinessReport {
+    let mut checks = BTreeMap::new();
+    checks.insert("llm_key", check_llm_key());
+    checks.insert("vivatech_api", ping_vivatech_api().await);
+
+    ReadinessReport {
+        ready: checks.values().all(|check| check.ok),
+        checks,
+    }
+}
+
+// the configured provider has its key, no tokens are spent
+fn check_llm_key() -> ProbeCheck {
+    let provider = llm::default_provider();
+    match provider.api_key_var() {
+        Some(key) if !config::get().has_api_key(provider) => ProbeCheck {
+            ok: false,
+            detail: format!("{} not set for {}", key, provider.name()),
+        },
+        _ => ProbeCheck {
+            ok: true,
+            detail: format!("{} configured", provider.name()),
+        },
+    }
+}
+
+// any answer short of a server error means the api is up, no search is run
+async fn ping_vivatech_api() -> ProbeCheck {
+    if mock::mock_mode() {
+        return ProbeCheck {
+            ok: true,
+            detail: "MOCK_MODE, serving the fixture catalog".to_string(),
+        };
+    }
+    let url = match get_vivatech_api_url() {
+        Ok(url) => url,
+        Err(e) => {
+            return ProbeCheck {
+                ok: false,
+                detail: e.to_string(),
+            }
+        }
+    };
+
+    match http::client()
+        .head(&url)
+        .timeout(config::get().readiness_timeout)
+        .send()
+        .await
+    {
+        Ok(response) if !response.status().is_server_error() => ProbeCheck {
+            ok: true,
+            detail: format!("reachable ({})", response.status()),
+        },
+        Ok(response) => ProbeCheck {
+            ok: false,
+            detail: format!("returned {}", response.status()),
+        },
+        Err(e) => ProbeCheck {
+            ok: false,
+            detail: format!("unreachable: {}", e),
+        },
+    }
+}
+
+pub fn version_info() -> VersionInfo {
+    let mut features = Vec::new();
+    if cfg!(feature = "offline-catalog") {
+        features.push("offline-catalog");
+    }
+    if cfg!(feature = "semantic-fallback") {
+        features.push("semantic-fallback");
+    }
+
+    VersionInfo {
+        name: env!("CARGO_PKG_NAME"),
+        version: env!("CARGO_PKG_VERSION"),
+        git_sha: option_env!("GIT_SHA"),
+        profile: if cfg!(debug_assertions) {
+            "debug"
+        } else {
+            "release"
+        },
+        features,
+    }
+}
+
+// capability matrix, which tools the agents can currently rely on
+pub async fn tool_health_handler(
+    State(state): State<AppState>,
+) -> Json<BTreeMap<&'static str, health::ToolHealth>> {
+    let tools = [
+        llm::default_provider().circuit(),
+        QueryVivatechAPI::NAME,
+        AssessTimeliness::NAME,
+        DetectScheduleConflicts::NAME,
+        ExportItineraryToIcal::NAME,
+    ];
+    Json(state.health.snapshot(&tools))
+}
+
+// liveness, the process is up and serving
+pub async fn healthz_handler() -> &'static str {
+    "ok"
+}
+
+// readiness, the llm key is set and the vivatech api answers
+pub async fn readyz_handler() -> Response {
+    let report = check_readiness().await;
+    let status = if report.ready {
+        StatusCode::OK
+    } else {
+        tracing::warn!("Readiness check failed: {:?}", report.checks);
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report)).into_response()
+}
+
+pub async fn version_handler() -> Json<VersionInfo> {
+    Json(version_info())
+}
@@ -0,0 +1,290 @@
+// user profiles: preferences and travel context
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{NaiveDate, NaiveDateTime, Offset, TimeZone};
+use chrono_tz::Tz;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::conference::ConferenceProfile;
+use crate::copy;
+use crate::error::AppError;
+use crate::state::AppState;
+use crate::tone::Tone;
+
+// typical first session start when rested
+const DEFAULT_DAY_START_HOUR: i64 = 9;
+
+// keeps the preamble short, these go into every plan of the user
+const MAX_PREFERENCE_ITEMS: usize = 20;
+const MAX_PREFERENCE_CHARS: usize = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TravelContext {
+    // iana name, e.g. "America/New_York"
+    pub home_timezone: String,
+    #[serde(default)]
+    pub hotel: Option<String>,
+    #[serde(default)]
+    pub commute_minutes: Option<u32>,
+    // venue local time
+    #[serde(default)]
+    pub arrival: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct UserProfile {
+    pub user_id: String,
+    #[serde(default)]
+    pub travel: Option<TravelContext>,
+    // used when a request doesn't pick a tone
+    #[serde(default)]
+    pub preferred_tone: Option<Tone>,
+    // e.g. "generative AI", "climate tech"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub interests: Vec<String>,
+    // e.g. "CTO at a seed-stage startup"
+    #[serde(default)]
+    pub job_role: Option<String>,
+    // languages the attendee follows sessions in, e.g. "English", "French"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub languages: Vec<String>,
+    // e.g. "vegetarian", "halal", "nut allergy"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dietary_needs: Vec<String>,
+    // e.g. "wheelchair user", "hard of hearing"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accessibility_needs: Vec<String>,
+}
+
+impl UserProfile {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.user_id.trim().is_empty() {
+            return Err("user_id must not be empty".to_string());
+        }
+        if let Some(travel) = &self.travel {
+            Tz::from_str(&travel.home_timezone)
+                .map_err(|_| format!("Unknown timezone '{}'", travel.home_timezone))?;
+        }
+        for (field, values) in [
+            ("interests", &self.interests),
+            ("languages", &self.languages),
+            ("dietary_needs", &self.dietary_needs),
+            ("accessibility_needs", &self.accessibility_needs),
+        ] {
+            if values.len() > MAX_PREFERENCE_ITEMS {
+                return Err(format!(
+                    "{} takes at most {} entries",
+                    field, MAX_PREFERENCE_ITEMS
+                ));
+            }
+            if values
+                .iter()
+                .any(|value| value.chars().count() > MAX_PREFERENCE_CHARS)
+            {
+                return Err(format!(
+                    "{} entries must be at most {} characters",
+                    field, MAX_PREFERENCE_CHARS
+                ));
+            }
+        }
+        if self
+            .job_role
+            .as_ref()
+            .is_some_and(|role| role.chars().count() > MAX_PREFERENCE_CHARS)
+        {
+            return Err(format!(
+                "job_role must be at most {} characters",
+                MAX_PREFERENCE_CHARS
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct ProfileStore {
+    profiles: RwLock<HashMap<String, UserProfile>>,
+}
+
+impl ProfileStore {
+    pub fn upsert(&self, profile: UserProfile) {
+        let mut profiles = self.profiles.write().unwrap_or_else(|e| e.into_inner());
+        profiles.insert(profile.user_id.clone(), profile);
+    }
+
+    pub fn get(&self, user_id: &str) -> Option<UserProfile> {
+        let profiles = self.profiles.read().unwrap_or_else(|e| e.into_inner());
+        profiles.get(user_id).cloned()
+    }
+}
+
+fn listed(values: &[String]) -> Option<String> {
+    let values: Vec<&str> = values
+        .iter()
+        .map(|value| value.trim())
+        .filter(|value| !value.is_empty())
+        .collect();
+    (!values.is_empty()).then(|| values.join(", "))
+}
+
+// preamble block so plans fit the attendee without them repeating themselves
+pub fn preference_guidance(profile: &UserProfile) -> String {
+    let mut lines = Vec::new();
+    if let Some(role) = profile
+        .job_role
+        .as_deref()
+        .map(str::trim)
+        .filter(|role| !role.is_empty())
+    {
+        lines.push(format!(
+            "They work as {}: favour sessions, speakers and partners relevant to that role.",
+            role
+        ));
+    }
+    if let Some(interests) = listed(&profile.interests) {
+        lines.push(format!(
+            "Their interests are {}. Prefer sessions on these topics, even when the \
+            objective doesn't repeat them, unless it asks for something else.",
+            interests
+        ));
+    }
+    if let Some(languages) = listed(&profile.languages) {
+        lines.push(format!(
+            "They follow sessions in {}. Prefer sessions held in these languages and flag \
+            any session in another language.",
+            languages
+        ));
+    }
+    if let Some(dietary) = listed(&profile.dietary_needs) {
+        lines.push(format!(
+            "Dietary needs: {}. Any meal, lunch or food stop in the plan must suit them.",
+            dietary
+        ));
+    }
+    if let Some(accessibility) = listed(&profile.accessibility_needs) {
+        lines.push(format!(
+            "Accessibility needs: {}. Leave extra time between halls, prefer step-free \
+            routes and flag any session or activity that may not suit them.",
+            accessibility
+        ));
+    }
+    lines.join("\n")
+}
+
+// minutes the venue is ahead of home on a given day, dst aware.
+// minutes rather than hours so half-hour and 45-minute zones survive
+fn venue_offset_minutes(home: Tz, venue: Tz, date: NaiveDate) -> i64 {
+    let noon = date.and_hms_opt(12, 0, 0).unwrap_or_default();
+    let home_offset = home.offset_from_utc_datetime(&noon).fix().local_minus_utc();
+    let venue_offset = venue
+        .offset_from_utc_datetime(&noon)
+        .fix()
+        .local_minus_utc();
+    i64::from(venue_offset - home_offset) / 60
+}
+
+// "+5", "-3:30", "+5:45"
+fn format_offset(minutes: i64) -> String {
+    let sign = if minutes < 0 { '-' } else { '+' };
+    let (hours, rest) = (minutes.abs() / 60, minutes.abs() % 60);
+    if rest == 0 {
+        format!("{}{}", sign, hours)
+    } else {
+        format!("{}{}:{:02}", sign, hours, rest)
+    }
+}
+
+// preamble block telling the agent how to pace and display times
+pub fn travel_guidance(travel: &TravelContext, conference: &ConferenceProfile) -> String {
+    let (Ok(home), Ok(venue)) = (
+        Tz::from_str(&travel.home_timezone),
+        Tz::from_str(&conference.timezone),
+    ) else {
+        return String::new();
+    };
+
+    let today = conference.current_date();
+    let offset = venue_offset_minutes(home, venue, today);
+    let mut lines = vec![format!(
+        "The attendee lives in {} ({} hours relative to the venue's {}). \
+        Show every time as venue time followed by home time in parentheses.",
+        travel.home_timezone,
+        format_offset(-offset),
+        conference.timezone
+    )];
+
+    // flying east makes early mornings hard, flying west makes late evenings hard
+    let mut earliest_hour = DEFAULT_DAY_START_HOUR;
+    if offset >= 3 * 60 {
+        earliest_hour += (offset / (3 * 60)).min(2);
+        lines.push(format!(
+            "They are jet-lagged after travelling east: avoid sessions before {:02}:00, \
+            leave a break every two sessions and keep the evening light.",
+            earliest_hour
+        ));
+    } else if offset <= -3 * 60 {
+        lines.push(
+            "They are jet-lagged after travelling west: mornings are fine, \
+            but avoid stacking sessions after 17:00."
+                .to_string(),
+        );
+    }
+
+    if let Some(hotel) = &travel.hotel {
+        let commute = travel.commute_minutes.unwrap_or(30);
+        let first_start = earliest_hour * 60 + i64::from(commute);
+        lines.push(format!(
+            "They stay at {} with a ~{} minute commute, so the first session \
+            should start no earlier than {:02}:{:02} venue time.",
+            hotel,
+            commute,
+            first_start / 60,
+            first_start % 60
+        ));
+    }
+
+    if let Some(arrival) = travel.arrival {
+        if arrival.date() >= today {
+            lines.push(format!(
+                "They arrive at {} venue time: do not plan anything before then.",
+                arrival.format("%B %-d %H:%M")
+            ));
+        }
+    }
+
+    lines.join("\n")
+}
+
+// create or replace a user profile
+pub async fn upsert_profile_handler(
+    State(state): State<AppState>,
+    Json(profile): Json<UserProfile>,
+) -> Response {
+    if let Err(e) = profile.validate() {
+        return AppError::Validation(e).into_response();
+    }
+
+    info!("Stored profile for user {}", profile.user_id);
+    state.profiles.upsert(profile.clone());
+    Json(profile).into_response()
+}
+
+pub async fn get_profile_handler(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+) -> Response {
+    match state.profiles.get(&user_id) {
+        Some(profile) => Json(profile).into_response(),
+        None => AppError::NotFound(copy::error("error.profile_not_found", &[])).into_response(),
+    }
+}
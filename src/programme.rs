@@ -0,0 +1,234 @@
+// typed sessions and partners read from search sources, once, instead of every tool
+// matching its own regex against text_chunk. the upstream's structured fields win
+// when it sends them, the text fills whatever they leave out
+
+use std::sync::OnceLock;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::models::{Track, VivatechSource};
+use crate::tools::{extract_dates_from_text, extract_times_from_text};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Session {
+    #[serde(default)]
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub speakers: Vec<String>,
+    // e.g. "Stage 1, Hall 1"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stage: Option<String>,
+    // venue local time on the first day, unset without both a date and a time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<NaiveDateTime>,
+    // unset when only the start is known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<NaiveDateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub track: Option<Track>,
+    // every day it runs, a multi-day session is not over after its first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<NaiveDate>,
+}
+
+impl Session {
+    // dates without a year are in `year`
+    pub fn from_source(source: &VivatechSource, year: i32) -> Self {
+        let text = source.text_chunk.trim();
+        let days = extract_dates_from_text(text, year);
+        let times = extract_times_from_text(text);
+        let start = days
+            .first()
+            .zip(times.first())
+            .map(|(day, time)| day.and_time(*time));
+        // the second time of a "10:00-11:30" range, on the same day
+        let end = start.and_then(|start| {
+            times
+                .get(1)
+                .map(|time| start.date().and_time(*time))
+                .filter(|end| *end > start)
+        });
+        let parsed = Session {
+            title: title(text).unwrap_or_else(|| source.id.clone()),
+            speakers: speakers(text),
+            stage: stage(text),
+            start,
+            end,
+            track: Track::ALL.into_iter().find(|track| track.matches(source)),
+            days,
+        };
+        match &source.session {
+            Some(upstream) => upstream.clone().or(parsed),
+            None => parsed,
+        }
+    }
+
+    // the fields set here, the parsed ones for the rest
+    fn or(self, parsed: Session) -> Session {
+        let start = self.start.or(parsed.start);
+        let end = self
+            .end
+            .or(parsed.end)
+            .filter(|end| start.is_some_and(|start| *end > start));
+        let days = match (self.days.is_empty(), self.start) {
+            (false, _) => self.days,
+            (true, Some(start)) => {
+                let last = end.map_or(start.date(), |end| end.date());
+                start
+                    .date()
+                    .iter_days()
+                    .take_while(|day| *day <= last)
+                    .collect()
+            }
+            (true, None) => parsed.days,
+        };
+        Session {
+            title: if self.title.trim().is_empty() {
+                parsed.title
+            } else {
+                self.title
+            },
+            speakers: if self.speakers.is_empty() {
+                parsed.speakers
+            } else {
+                self.speakers
+            },
+            stage: self.stage.or(parsed.stage),
+            start,
+            end,
+            track: self.track.or(parsed.track),
+            days,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Partner {
+    #[serde(default)]
+    pub name: String,
+    // e.g. "Hall 1"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hall: Option<String>,
+    // e.g. "C12"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub booth: Option<String>,
+    // the upstream's sector, or the programme track the text matches
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sector: Option<String>,
+}
+
+impl Partner {
+    // partner texts are written as "<name> booth - <hall>, booth <number>"
+    pub fn from_source(source: &VivatechSource) -> Self {
+        let first_line = first_line(&source.text_chunk).unwrap_or_default();
+        let name = first_line.split(" - ").next().unwrap_or(first_line).trim();
+        let name = name
+            .strip_suffix(" booth")
+            .or_else(|| name.strip_suffix(" Booth"))
+            .unwrap_or(name);
+        let location = booth_location_pattern().and_then(|pattern| pattern.captures(first_line));
+        let capture = |index| {
+            location
+                .as_ref()
+                .and_then(|captures| captures.get(index))
+                .map(|m| m.as_str().to_string())
+        };
+        let parsed = Partner {
+            name: if name.is_empty() {
+                source.id.clone()
+            } else {
+                name.to_string()
+            },
+            hall: capture(1).map(|hall| format!("Hall {}", hall)),
+            booth: capture(2).map(|booth| booth.to_uppercase()),
+            sector: Track::ALL
+                .into_iter()
+                .find(|track| track.matches(source))
+                .map(|track| track.label().to_string()),
+        };
+        let Some(upstream) = &source.partner else {
+            return parsed;
+        };
+        Partner {
+            name: if upstream.name.trim().is_empty() {
+                parsed.name
+            } else {
+                upstream.name.clone()
+            },
+            hall: upstream.hall.clone().or(parsed.hall),
+            booth: upstream.booth.clone().or(parsed.booth),
+            sector: upstream.sector.clone().or(parsed.sector),
+        }
+    }
+}
+
+// structured fields that don't match the contract are dropped, not the whole response
+pub(crate) fn lenient<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: for<'a> Deserialize<'a>,
+{
+    Ok(Option::<serde_json::Value>::deserialize(deserializer)?
+        .and_then(|value| serde_json::from_value(value).ok()))
+}
+
+fn first_line(text: &str) -> Option<&str> {
+    text.lines().map(str::trim).find(|line| !line.is_empty())
+}
+
+// "Opening keynote: The Next Decade of AI - June 11 at 10:00, Stage 1, Hall 1." gives
+// the part before the date
+fn title(text: &str) -> Option<String> {
+    let line = first_line(text)?;
+    let title = line.split(" - ").next().unwrap_or(line).trim();
+    (!title.is_empty()).then(|| title.to_string())
+}
+
+// and the places after the date and time
+fn stage(text: &str) -> Option<String> {
+    let (_, slot) = first_line(text)?.split_once(" - ")?;
+    let (_, stage) = slot.split_once(',')?;
+    let stage = stage.trim().trim_end_matches('.').trim();
+    (!stage.is_empty()).then(|| stage.to_string())
+}
+
+// "Speakers: Jane Doe, John Smith and Ada Martin." up to the end of the sentence
+fn speakers(text: &str) -> Vec<String> {
+    let Some(names) = speakers_pattern()
+        .and_then(|pattern| pattern.captures(text))
+        .and_then(|captures| captures.get(1))
+    else {
+        return Vec::new();
+    };
+    names
+        .as_str()
+        .split(',')
+        .flat_map(|part| part.split(" and "))
+        .flat_map(|part| part.split(" et "))
+        .flat_map(|part| part.split(" & "))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+static SPEAKERS: OnceLock<Option<Regex>> = OnceLock::new();
+
+// english and french labels
+fn speakers_pattern() -> Option<&'static Regex> {
+    SPEAKERS
+        .get_or_init(|| Regex::new(r"(?i)\b(?:speakers?|intervenants?)\s*:\s*([^\n.;]+)").ok())
+        .as_ref()
+}
+
+static BOOTH_LOCATION: OnceLock<Option<Regex>> = OnceLock::new();
+
+// "Hall 1, booth C12", "Hall 2 - Booth F04", "Hall 3"
+fn booth_location_pattern() -> Option<&'static Regex> {
+    BOOTH_LOCATION
+        .get_or_init(|| Regex::new(r"(?i)\bhall\s+(\w+)(?:\W+booth\s+([a-z]?\d+[a-z]?))?").ok())
+        .as_ref()
+}
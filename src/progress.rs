@@ -0,0 +1,37 @@
+// what the tools are doing during a streamed run, e.g. "Querying the Vivatech API for
+// 'quantum'", so frontends can show a live activity feed while a long plan is written
+
+use std::future::Future;
+use std::sync::Arc;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+tokio::task_local! {
+    static SINK: ProgressSink;
+}
+
+// called for every report, must not block: the tool waits for it
+pub type ProgressSink = Arc<dyn Fn(ToolProgress) + Send + Sync>;
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ToolProgress {
+    pub tool: String,
+    // one sentence meant for people
+    pub message: String,
+}
+
+// reports made while `future` runs go to `sink`. tasks it spawns are not covered
+pub async fn reporting<F: Future>(sink: ProgressSink, future: F) -> F::Output {
+    SINK.scope(sink, future).await
+}
+
+// dropped outside reporting(), e.g. for plans that are not streamed
+pub fn report(tool: &str, message: impl Into<String>) {
+    let _ = SINK.try_with(|sink| {
+        sink(ToolProgress {
+            tool: tool.to_string(),
+            message: message.into(),
+        })
+    });
+}
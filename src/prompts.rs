@@ -0,0 +1,381 @@
+// prompt templates that change without a redeploy. the bundled defaults are
+// overridden by secrets (PROMPT_PREAMBLE, ...), then by a json file
+// (PROMPT_TEMPLATES_PATH), then by a prompt_templates table, then by /admin/config.
+// the file and the table are read again every PROMPT_TEMPLATES_RELOAD_SECONDS
+
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, PoisonError, RwLock};
+use std::time::Duration;
+
+use regex::{Captures, Regex};
+use serde::Serialize;
+use tokio_postgres::NoTls;
+use tracing::{info, warn};
+
+use crate::config;
+
+// replaced as a whole on every reload that changes something
+static TEMPLATES: RwLock<Option<Arc<PromptTemplates>>> = RwLock::new(None);
+
+const DEFAULT_PREAMBLE: &str = "\
+    You are a helpful assistant for {{conference}} conference planning. \
+    Current date: {{current_date}}.\n\n\
+    When asked about sessions or events:\n\
+    1. Use the query_vivatech_api tool to search for relevant information. When the \
+    request covers several topics, call query_vivatech_api_batch once with one query per \
+    topic instead\n\
+    2. Pass the sessions you found to the assess_event_timeliness tool to learn \
+    which happen today or tomorrow, and put those first\n\
+    3. Before finalizing, pass the sessions you picked to the detect_schedule_conflicts tool, \
+    with the other sessions you found as candidates. For every overlap, keep one session and \
+    swap the other for a suggested alternative, or tell the attendee they must choose\n\
+    4. Format the results in a clear, organized way for the user\n\
+    5. Mark the sessions that are happening soon\n\
+    6. If the attendee asks for a calendar file, call the export_itinerary_to_ical tool \
+    with the picked sessions and include the returned .ics content unchanged\n\
+    7. When the attendee asks about a speaker, e.g. when someone speaks, call the \
+    lookup_speaker tool with their name instead of a plain search\n\
+    8. When the attendee wants to meet exhibitors or visit partner booths, call the \
+    find_partners tool and add its route to the plan as a walk from booth to booth, \
+    in the order given, with the hall and booth of each stop\n\
+    9. When the attendee asks for a track or theme on specific days, e.g. \"only climate \
+    sessions on Friday\", call the filter_by_track tool with the tracks and days instead of \
+    a plain search, and only plan from the sessions it returns\n\
+    10. Before recommending rooftop, terrace or other outdoor side events, or when the \
+    attendee asks how to get to the venue, call the get_paris_weather tool for that day \
+    and follow its advice\n\
+    11. When consecutive picked sessions are in different halls, or the day starts from the \
+    attendee's hotel, call the estimate_travel_time tool with those legs and leave at least \
+    gap_minutes between the end of one session and the start of the next, dropping a \
+    session rather than keeping a transition that is too tight\n\
+    12. When the attendee wants to network, meet investors, partners, customers or \
+    startups, or you know their role and interests, call the suggest_networking_targets \
+    tool with them and add a separate \"Networking shortlist\" section after the schedule: \
+    one entry per target with its hall and booth, why it matches and its talking points, \
+    fitted into free slots between sessions";
+
+const DEFAULT_CRITIC: &str = "\
+    You are an editor reviewing a draft conference plan written by a junior assistant. \
+    Check it against the attendee's objective, fix ordering and formatting, remove duplicates \
+    and anything off-topic, and make the reasoning for each pick crisp. \
+    Never add sessions, times or locations that are not in the draft. \
+    Return only the final plan.";
+
+const DEFAULT_FORMATTING: &str = "\
+    You convert a conference plan into a structured itinerary. \
+    Add one slot per recommended session, in chronological order, and nothing that is not in the plan. \
+    Times are local venue times in ISO 8601 without offset, e.g. 2025-06-11T14:00:00. \
+    session_id must be the id of the source the session came from. \
+    urgency is Immediate for today, Soon for tomorrow and Normal otherwise.";
+
+const CREATE_TABLE: &str = "
+    CREATE TABLE IF NOT EXISTS prompt_templates (
+        name TEXT PRIMARY KEY,
+        template TEXT NOT NULL,
+        updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+    )";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PromptKind {
+    // the planning agent's instructions
+    Preamble,
+    // the editor that polishes two_stage drafts
+    Critic,
+    // turns a plan into the structured itinerary
+    Formatting,
+}
+
+impl PromptKind {
+    pub const ALL: [PromptKind; 3] = [
+        PromptKind::Preamble,
+        PromptKind::Critic,
+        PromptKind::Formatting,
+    ];
+
+    // key in the file and the table
+    pub fn name(self) -> &'static str {
+        match self {
+            PromptKind::Preamble => "preamble",
+            PromptKind::Critic => "critic",
+            PromptKind::Formatting => "formatting",
+        }
+    }
+
+    fn secret(self) -> &'static str {
+        match self {
+            PromptKind::Preamble => "PROMPT_PREAMBLE",
+            PromptKind::Critic => "PROMPT_CRITIC",
+            PromptKind::Formatting => "PROMPT_FORMATTING",
+        }
+    }
+
+    fn bundled(self) -> &'static str {
+        match self {
+            PromptKind::Preamble => DEFAULT_PREAMBLE,
+            PromptKind::Critic => DEFAULT_CRITIC,
+            PromptKind::Formatting => DEFAULT_FORMATTING,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name() == name.trim().to_lowercase())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PromptTemplateError {
+    #[error("failed to read {0}: {1}")]
+    File(String, String),
+    #[error("prompt template database error: {0}")]
+    Database(#[from] tokio_postgres::Error),
+}
+
+// one template and where it came from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PromptTemplate {
+    pub text: String,
+    // "bundled", "secrets", "file", "database" or "admin"
+    pub origin: &'static str,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptTemplates {
+    templates: HashMap<PromptKind, PromptTemplate>,
+}
+
+impl PromptTemplates {
+    fn bundled() -> Self {
+        Self {
+            templates: PromptKind::ALL
+                .into_iter()
+                .map(|kind| {
+                    let template = PromptTemplate {
+                        text: kind.bundled().to_string(),
+                        origin: "bundled",
+                    };
+                    (kind, template)
+                })
+                .collect(),
+        }
+    }
+
+    // empty templates are ignored, unknown names logged and ignored
+    fn apply(&mut self, overrides: HashMap<String, String>, origin: &'static str) {
+        for (name, text) in overrides {
+            match PromptKind::from_name(&name) {
+                Some(kind) if !text.trim().is_empty() => {
+                    self.templates.insert(kind, PromptTemplate { text, origin });
+                }
+                Some(_) => {}
+                None => warn!(
+                    "Ignoring unknown prompt template '{}' from {}",
+                    name, origin
+                ),
+            }
+        }
+    }
+
+    pub fn get(&self, kind: PromptKind) -> &PromptTemplate {
+        &self.templates[&kind]
+    }
+}
+
+fn get_templates_path() -> Option<String> {
+    config::env_var("PROMPT_TEMPLATES_PATH")
+        .ok()
+        .filter(|path| !path.is_empty())
+}
+
+fn get_database_url() -> Option<String> {
+    config::env_var("PROMPT_TEMPLATES_DATABASE_URL")
+        .ok()
+        .filter(|url| !url.is_empty())
+}
+
+// 0 turns hot reload off
+fn get_reload_interval() -> Option<Duration> {
+    let seconds = config::env_var("PROMPT_TEMPLATES_RELOAD_SECONDS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(60);
+    (seconds > 0).then(|| Duration::from_secs(seconds))
+}
+
+fn admin_templates() -> HashMap<String, String> {
+    config::runtime()
+        .prompt_templates
+        .clone()
+        .into_iter()
+        .collect()
+}
+
+fn from_secrets() -> HashMap<String, String> {
+    PromptKind::ALL
+        .into_iter()
+        .filter_map(|kind| {
+            let text = config::env_var(kind.secret()).ok()?;
+            Some((kind.name().to_string(), text))
+        })
+        .collect()
+}
+
+// {"preamble": "...", "critic": "..."}, only the templates to change are needed
+fn from_file(path: &str) -> Result<HashMap<String, String>, PromptTemplateError> {
+    let raw = std::fs::read_to_string(path)
+        .map_err(|e| PromptTemplateError::File(path.to_string(), e.to_string()))?;
+    serde_json::from_str(&raw)
+        .map_err(|e| PromptTemplateError::File(path.to_string(), e.to_string()))
+}
+
+// a short-lived connection, reloads are minutes apart
+async fn from_database(url: &str) -> Result<HashMap<String, String>, PromptTemplateError> {
+    let (client, connection) = tokio_postgres::connect(url, NoTls).await?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            warn!("Prompt template database connection closed: {}", e);
+        }
+    });
+    client.batch_execute(CREATE_TABLE).await?;
+    let rows = client
+        .query("SELECT name, template FROM prompt_templates", &[])
+        .await?;
+    Ok(rows
+        .iter()
+        .map(|row| (row.get::<_, String>(0), row.get::<_, String>(1)))
+        .collect())
+}
+
+// every source again, in order. a source that fails keeps its templates from
+// the last load that worked, so a database outage doesn't revert prompts
+async fn load(previous: Option<&PromptTemplates>) -> PromptTemplates {
+    let mut templates = PromptTemplates::bundled();
+    templates.apply(from_secrets(), "secrets");
+
+    let kept = |origin: &'static str| -> HashMap<String, String> {
+        previous
+            .map(|previous| {
+                previous
+                    .templates
+                    .iter()
+                    .filter(|(_, template)| template.origin == origin)
+                    .map(|(kind, template)| (kind.name().to_string(), template.text.clone()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    if let Some(path) = get_templates_path() {
+        match from_file(&path) {
+            Ok(overrides) => templates.apply(overrides, "file"),
+            Err(e) => {
+                warn!("Keeping the previous prompt templates: {}", e);
+                templates.apply(kept("file"), "file");
+            }
+        }
+    }
+    if let Some(url) = get_database_url() {
+        match from_database(&url).await {
+            Ok(overrides) => templates.apply(overrides, "database"),
+            Err(e) => {
+                warn!("Keeping the previous prompt templates: {}", e);
+                templates.apply(kept("database"), "database");
+            }
+        }
+    }
+    templates.apply(admin_templates(), "admin");
+    templates
+}
+
+pub fn current() -> Arc<PromptTemplates> {
+    if let Some(templates) = TEMPLATES
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+    {
+        return templates.clone();
+    }
+    // before init_prompts, e.g. in tests and tools
+    let mut templates = PromptTemplates::bundled();
+    templates.apply(from_secrets(), "secrets");
+    templates.apply(admin_templates(), "admin");
+    Arc::new(templates)
+}
+
+// the template as currently loaded, variables not filled in
+pub fn text(kind: PromptKind) -> String {
+    current().get(kind).text.clone()
+}
+
+// true when the templates changed
+pub async fn reload() -> bool {
+    let previous = TEMPLATES
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    let loaded = load(previous.as_deref()).await;
+    if previous.as_deref() == Some(&loaded) {
+        return false;
+    }
+    for kind in PromptKind::ALL {
+        let template = loaded.get(kind);
+        let changed = previous
+            .as_deref()
+            .map_or(true, |previous| previous.get(kind) != template);
+        if changed {
+            info!(
+                "Prompt template '{}' loaded from {}",
+                kind.name(),
+                template.origin
+            );
+        }
+    }
+    *TEMPLATES.write().unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(loaded));
+    true
+}
+
+// loads the templates at startup and keeps the file and table watched
+pub async fn init_prompts() {
+    reload().await;
+    if get_templates_path().is_none() && get_database_url().is_none() {
+        return;
+    }
+    let Some(interval) = get_reload_interval() else {
+        info!("Prompt template reload disabled via PROMPT_TEMPLATES_RELOAD_SECONDS");
+        return;
+    };
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        // the first tick fires at once, the templates were just loaded
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            reload().await;
+        }
+    });
+}
+
+fn variable_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\{\{\s*([a-z_]+)\s*\}\}").expect("valid variable pattern"))
+}
+
+// true when the template uses {{name}}, e.g. to not append the user profile twice
+pub fn uses(template: &str, name: &str) -> bool {
+    variable_pattern()
+        .captures_iter(template)
+        .any(|captures| &captures[1] == name)
+}
+
+// {{name}} and {{ name }} replaced by their value. unknown variables are kept as
+// written, they may be meant for the model
+pub fn render(template: &str, variables: &[(&str, &str)]) -> String {
+    variable_pattern()
+        .replace_all(template, |captures: &Captures| {
+            variables
+                .iter()
+                .find(|(name, _)| *name == &captures[1])
+                .map_or_else(|| captures[0].to_string(), |(_, value)| value.to_string())
+        })
+        .into_owned()
+}
@@ -0,0 +1,133 @@
+// in-process ttl cache for vivatech search results
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+
+use moka::sync::Cache;
+use serde::Serialize;
+
+use crate::config;
+use crate::models::VivatechSource;
+
+// rebuilt, empty, when the ttl is changed at runtime
+static QUERY_CACHE: RwLock<Option<Arc<QueryCache>>> = RwLock::new(None);
+
+#[derive(Debug, Serialize)]
+pub struct QueryCacheStats {
+    pub enabled: bool,
+    pub entries: u64,
+    pub capacity: u64,
+    pub ttl_seconds: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_rate: f64,
+}
+
+pub struct QueryCache {
+    entries: Cache<String, Vec<VivatechSource>>,
+    capacity: u64,
+    ttl: Duration,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl QueryCache {
+    pub fn new(capacity: u64, ttl: Duration) -> Self {
+        Self {
+            entries: Cache::builder()
+                .max_capacity(capacity)
+                .time_to_live(ttl)
+                .build(),
+            capacity,
+            ttl,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get(&self, api_url: Option<&str>, query: &str) -> Option<Vec<VivatechSource>> {
+        let cached = self.entries.get(&cache_key(api_url, query));
+        let counter = if cached.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        cached
+    }
+
+    pub fn insert(&self, api_url: Option<&str>, query: &str, sources: Vec<VivatechSource>) {
+        self.entries.insert(cache_key(api_url, query), sources);
+    }
+
+    // the catalog changed, cached answers may point at moved or cancelled sessions
+    pub fn clear(&self) {
+        self.entries.invalidate_all();
+    }
+
+    pub fn stats(&self) -> QueryCacheStats {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        let lookups = hits + misses;
+        QueryCacheStats {
+            enabled: true,
+            entries: self.entries.entry_count(),
+            capacity: self.capacity,
+            ttl_seconds: self.ttl.as_secs(),
+            hits,
+            misses,
+            hit_rate: if lookups == 0 {
+                0.0
+            } else {
+                hits as f64 / lookups as f64
+            },
+        }
+    }
+}
+
+// "  AI   Sessions " and "ai sessions" share an entry, per conference api
+fn cache_key(api_url: Option<&str>, query: &str) -> String {
+    let normalized = query
+        .split_whitespace()
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!("{}|{}", api_url.unwrap_or_default(), normalized)
+}
+
+// built on first use, QUERY_CACHE_TTL_SECONDS or /admin/config decide the ttl
+pub fn query_cache() -> Option<Arc<QueryCache>> {
+    let ttl = Duration::from_secs(config::runtime().query_cache_ttl_seconds);
+    if ttl.is_zero() {
+        return None;
+    }
+    let current =
+        |cache: &Option<Arc<QueryCache>>| cache.as_ref().filter(|c| c.ttl == ttl).cloned();
+    if let Some(cache) = current(&QUERY_CACHE.read().unwrap_or_else(PoisonError::into_inner)) {
+        return Some(cache);
+    }
+    let mut slot = QUERY_CACHE.write().unwrap_or_else(PoisonError::into_inner);
+    // another request may have rebuilt it in the meantime
+    if let Some(cache) = current(&slot) {
+        return Some(cache);
+    }
+    let cache = Arc::new(QueryCache::new(config::get().query_cache_capacity, ttl));
+    *slot = Some(cache.clone());
+    Some(cache)
+}
+
+pub fn query_cache_stats() -> QueryCacheStats {
+    match query_cache() {
+        Some(cache) => cache.stats(),
+        None => QueryCacheStats {
+            enabled: false,
+            entries: 0,
+            capacity: 0,
+            ttl_seconds: 0,
+            hits: 0,
+            misses: 0,
+            hit_rate: 0.0,
+        },
+    }
+}
@@ -0,0 +1,241 @@
+// in-memory vector index over a session dump, searched when the vivatech api is down
+
+use std::sync::{Arc, PoisonError, RwLock};
+
+use rig::embeddings::{EmbedError, EmbeddingError, EmbeddingsBuilder, TextEmbedder};
+use rig::providers::openai;
+use rig::vector_store::in_memory_store::{InMemoryVectorIndex, InMemoryVectorStore};
+use rig::vector_store::{VectorStoreError, VectorStoreIndex};
+use rig::Embed;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::config;
+use crate::models::VivatechSource;
+
+// replaced as a whole when /admin/ingest rebuilds it
+static INDEX: RwLock<Option<Arc<SemanticIndex>>> = RwLock::new(None);
+
+#[derive(Debug, thiserror::Error)]
+pub enum SemanticIndexError {
+    #[error("Semantic fallback needs OPENAI_API_KEY for embeddings")]
+    MissingApiKey,
+    #[error("Session dump unavailable: {0}")]
+    Dump(String),
+    #[error("Failed to prepare sessions for embedding: {0}")]
+    Embed(#[from] EmbedError),
+    #[error("Embedding failed: {0}")]
+    Embedding(#[from] EmbeddingError),
+    #[error("Semantic search failed: {0}")]
+    Search(#[from] VectorStoreError),
+}
+
+// json array of sources, same shape as the api returns
+fn get_dump_path() -> String {
+    config::env_var("RAG_DUMP_PATH").unwrap_or_else(|_| "data/sessions.json".to_string())
+}
+
+// fetched at startup and saved to RAG_DUMP_PATH, the saved copy is used if it fails
+fn get_dump_url() -> Option<String> {
+    config::env_var("RAG_DUMP_URL")
+        .ok()
+        .filter(|url| !url.is_empty())
+}
+
+fn get_embedding_model() -> String {
+    config::env_var("RAG_EMBEDDING_MODEL")
+        .unwrap_or_else(|_| openai::TEXT_EMBEDDING_3_SMALL.to_string())
+}
+
+// what gets embedded and stored, the store needs Eq so the f32 score stays out
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct SessionDoc {
+    id: String,
+    source_table: String,
+    text_chunk: String,
+}
+
+impl Embed for SessionDoc {
+    fn embed(&self, embedder: &mut TextEmbedder) -> Result<(), EmbedError> {
+        embedder.embed(self.text_chunk.clone());
+        Ok(())
+    }
+}
+
+pub struct SemanticIndex {
+    index: InMemoryVectorIndex<openai::EmbeddingModel, SessionDoc>,
+    size: usize,
+}
+
+impl SemanticIndex {
+    // one embedding call per batch of sessions, done once per process
+    pub async fn build(
+        model: openai::EmbeddingModel,
+        sources: Vec<VivatechSource>,
+    ) -> Result<Self, SemanticIndexError> {
+        let docs: Vec<SessionDoc> = sources
+            .into_iter()
+            .map(|source| SessionDoc {
+                id: source.id,
+                source_table: source.source_table,
+                text_chunk: source.text_chunk,
+            })
+            .collect();
+        let size = docs.len();
+
+        let embeddings = EmbeddingsBuilder::new(model.clone())
+            .documents(docs)?
+            .build()
+            .await?;
+        let store = InMemoryVectorStore::from_documents_with_id_f(embeddings, |doc| doc.id.clone());
+        Ok(Self {
+            index: store.index(model),
+            size,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    // closest sources first, scored by cosine similarity
+    pub async fn search(
+        &self,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<VivatechSource>, SemanticIndexError> {
+        let min_score = config::get().rag_min_score;
+        let hits = self.index.top_n::<SessionDoc>(query, limit).await?;
+        Ok(hits
+            .into_iter()
+            .filter(|(score, _, _)| *score >= min_score)
+            .map(|(score, _, doc)| VivatechSource {
+                id: doc.id,
+                source_table: doc.source_table,
+                score: score as f32,
+                text_chunk: doc.text_chunk,
+                facets: None,
+                session: None,
+                partner: None,
+            })
+            .collect())
+    }
+}
+
+async fn download_dump(url: &str, path: &str) -> Result<Vec<VivatechSource>, SemanticIndexError> {
+    let sources: Vec<VivatechSource> = reqwest::get(url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| SemanticIndexError::Dump(e.to_string()))?
+        .json()
+        .await
+        .map_err(|e| SemanticIndexError::Dump(e.to_string()))?;
+    save_dump(path, &sources);
+    Ok(sources)
+}
+
+// keep a copy for the next start, a write failure only costs that
+fn save_dump(path: &str, sources: &[VivatechSource]) {
+    if let Some(parent) = std::path::Path::new(path).parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    match serde_json::to_vec(sources) {
+        Ok(raw) => {
+            if let Err(e) = std::fs::write(path, raw) {
+                warn!("Could not save session dump to {}: {}", path, e);
+            }
+        }
+        Err(e) => warn!("Could not encode session dump: {}", e),
+    }
+}
+
+fn read_dump(path: &str) -> Result<Vec<VivatechSource>, SemanticIndexError> {
+    let raw = std::fs::read_to_string(path)
+        .map_err(|e| SemanticIndexError::Dump(format!("{}: {}", path, e)))?;
+    serde_json::from_str(&raw).map_err(|e| SemanticIndexError::Dump(format!("{}: {}", path, e)))
+}
+
+async fn load_dump() -> Result<Vec<VivatechSource>, SemanticIndexError> {
+    let path = get_dump_path();
+    if let Some(url) = get_dump_url() {
+        match download_dump(&url, &path).await {
+            Ok(sources) => return Ok(sources),
+            Err(e) => warn!("Session dump download failed, trying {}: {}", path, e),
+        }
+    }
+    read_dump(&path)
+}
+
+// embeds the dump in the background, searches fall through until it is ready.
+// failures only disable the fallback
+pub fn init_semantic_fallback() {
+    let Some(api_key) = get_openai_api_key() else {
+        warn!("Semantic fallback disabled, it needs OPENAI_API_KEY for embeddings");
+        return;
+    };
+
+    tokio::spawn(async move {
+        let model = embedding_model(&api_key);
+        let built = match load_dump().await {
+            Ok(sources) if sources.is_empty() => {
+                Err(SemanticIndexError::Dump("dump has no sources".to_string()))
+            }
+            Ok(sources) => SemanticIndex::build(model, sources).await,
+            Err(e) => Err(e),
+        };
+        match built {
+            Ok(index) => {
+                info!("Semantic fallback ready with {} sources", index.len());
+                install(index);
+            }
+            Err(e) => warn!("Semantic fallback unavailable: {}", e),
+        }
+    });
+}
+
+fn get_openai_api_key() -> Option<String> {
+    config::get().openai_api_key.clone()
+}
+
+fn embedding_model(api_key: &str) -> openai::EmbeddingModel {
+    openai::Client::new(api_key).embedding_model(&get_embedding_model())
+}
+
+// searches already running keep the index they started with
+fn install(index: SemanticIndex) {
+    *INDEX.write().unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(index));
+}
+
+// embeds a fresh pull, saves it as the dump for the next start and swaps it in.
+// the previous index keeps serving until the new one is ready
+pub async fn rebuild_semantic_index(
+    sources: Vec<VivatechSource>,
+) -> Result<usize, SemanticIndexError> {
+    let api_key = get_openai_api_key().ok_or(SemanticIndexError::MissingApiKey)?;
+    if sources.is_empty() {
+        return Err(SemanticIndexError::Dump("no sources to embed".to_string()));
+    }
+    save_dump(&get_dump_path(), &sources);
+    let index = SemanticIndex::build(embedding_model(&api_key), sources).await?;
+    let size = index.len();
+    install(index);
+    info!("Semantic fallback rebuilt with {} sources", size);
+    Ok(size)
+}
+
+pub fn semantic_index() -> Option<Arc<SemanticIndex>> {
+    INDEX.read().unwrap_or_else(PoisonError::into_inner).clone()
+}
+
+pub async fn search_semantic_index(
+    query: &str,
+) -> Option<Result<Vec<VivatechSource>, SemanticIndexError>> {
+    match semantic_index() {
+        Some(index) => Some(index.search(query, config::get().rag_search_limit).await),
+        None => None,
+    }
+}
@@ -0,0 +1,165 @@
+// token bucket per client in front of the routes that reach the llm: a burst allowance
+// refilled at a steady rate, keyed by api key name or else by client ip
+
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tracing::{info, warn};
+
+use crate::abuse;
+use crate::config;
+use crate::error;
+use crate::models::ErrorEnvelope;
+use crate::state::AppState;
+
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<String, Bucket>>,
+    // requests a client can make back to back
+    burst: f64,
+    // tokens added per second, 0 disables the limiter
+    refill_per_second: f64,
+}
+
+impl RateLimiter {
+    pub fn from_env() -> Self {
+        let config = config::get();
+        let (burst, per_minute) = (config.rate_limit_burst, config.rate_limit_per_minute);
+        if per_minute == 0 {
+            warn!("RATE_LIMIT_PER_MINUTE is 0, per-client rate limiting is disabled");
+        } else {
+            info!(
+                "Rate limiting clients to {} requests per minute, bursts of {}",
+                per_minute, burst
+            );
+        }
+        Self::new(burst, per_minute)
+    }
+
+    pub fn new(burst: u32, per_minute: u32) -> Self {
+        Self {
+            buckets: Mutex::new(HashMap::new()),
+            burst: f64::from(burst.max(1)),
+            refill_per_second: f64::from(per_minute) / 60.0,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.refill_per_second > 0.0
+    }
+
+    // takes a token, or says how long until the next one
+    pub fn check(&self, client: &str) -> Result<(), Duration> {
+        self.take(client, 1)
+    }
+
+    // all `tokens` or none, at most a full bucket so a large batch can still pass
+    pub fn take(&self, client: &str, tokens: u32) -> Result<(), Duration> {
+        let tokens = f64::from(tokens).min(self.burst);
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+        if buckets.len() > MAX_TRACKED_CLIENTS {
+            self.prune(&mut buckets, now);
+        }
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: self.burst,
+            refilled_at: now,
+        });
+
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.refill_per_second).min(self.burst);
+        bucket.refilled_at = now;
+        if bucket.tokens >= tokens {
+            bucket.tokens -= tokens;
+            return Ok(());
+        }
+        Err(Duration::from_secs_f64(
+            (tokens - bucket.tokens) / self.refill_per_second,
+        ))
+    }
+
+    // a bucket that has refilled completely is the same as no bucket
+    fn prune(&self, buckets: &mut HashMap<String, Bucket>, now: Instant) {
+        buckets.retain(|_, bucket| {
+            let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+            bucket.tokens + elapsed * self.refill_per_second < self.burst
+        });
+    }
+}
+
+// takes a token for `client`, counting and logging the rejection against `path`
+pub fn charge(state: &AppState, client: &str, path: &str) -> Result<(), Duration> {
+    charge_runs(state, client, path, 1)
+}
+
+// one token per agent run, for requests that plan several times
+pub fn charge_runs(state: &AppState, client: &str, path: &str, runs: u32) -> Result<(), Duration> {
+    if !state.rate_limiter.is_enabled() || runs == 0 {
+        return Ok(());
+    }
+    let Err(retry_after) = state.rate_limiter.take(client, runs) else {
+        return Ok(());
+    };
+
+    let kind = client.split(':').next().unwrap_or("anonymous").to_string();
+    metrics::counter!("rate_limited_requests_total", "path" => path.to_string(), "client" => kind)
+        .increment(1);
+    warn!(client, path, "Rate limited request");
+    Err(retry_after)
+}
+
+// whole seconds for Retry-After, never 0
+pub fn retry_after_seconds(retry_after: Duration) -> u64 {
+    retry_after.as_secs_f64().ceil().max(1.0) as u64
+}
+
+// body of a 429 from the limiter
+pub fn rejection_envelope(seconds: u64) -> ErrorEnvelope {
+    error::envelope(
+        "rate_limited",
+        format!("too many requests, retry in {} seconds", seconds),
+    )
+}
+
+// 429 with Retry-After and the usual envelope
+pub fn rejection(retry_after: Duration) -> Response {
+    let seconds = retry_after_seconds(retry_after);
+    (
+        [(header::RETRY_AFTER, seconds.to_string())],
+        error::respond(StatusCode::TOO_MANY_REQUESTS, rejection_envelope(seconds)),
+    )
+        .into_response()
+}
+
+// runs inside require_api_key, so the key that authenticated the request is known.
+// only on the routes that run an agent, and one token per request: handlers that
+// plan more than once charge the other runs themselves
+pub async fn limit_requests(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let client = abuse::client_identity(request.headers(), request.extensions());
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    match charge(&state, &client, &path) {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => rejection(retry_after),
+    }
+}
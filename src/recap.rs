@@ -0,0 +1,445 @@
+// post-conference recap generation
+
+use axum::{
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::agents::build_recap_agent;
+use crate::conference::ConferenceProfile;
+use crate::copy;
+use crate::error::AppError;
+use crate::llm::ModelChoice;
+use crate::models::VivatechSource;
+use crate::placeholders;
+use crate::planner::{initialize_llm_client, prompt_with_retry, AgentFailure};
+use crate::plans::PlanStoreError;
+use crate::popularity;
+use crate::render::{self, FormatQuery, PlanDocument};
+use crate::state::AppState;
+use crate::usage;
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RecapSession {
+    pub session_id: String,
+    pub title: String,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub favorite: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SessionFeedback {
+    pub session_id: String,
+    #[serde(default)]
+    pub rating: Option<u8>,
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct Contact {
+    pub name: String,
+    #[serde(default)]
+    pub company: Option<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+// what only the client knows, the contacts, and what the stores may not have yet.
+// favorites and feedback are kept for the next recap, contacts and the itinerary are not
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+pub struct RecapRequest {
+    #[serde(default)]
+    pub conference: Option<String>,
+    // sessions the stored plans don't cite, e.g. walk-ins, or notes on ones they do
+    #[serde(default)]
+    pub itinerary: Vec<RecapSession>,
+    #[serde(default)]
+    pub favorites: Vec<String>,
+    #[serde(default)]
+    pub feedback: Vec<SessionFeedback>,
+    #[serde(default)]
+    pub contacts: Vec<Contact>,
+    // json, markdown, html, text, ics or pdf. ?format= wins, the Accept header
+    // decides when neither is set
+    #[serde(default)]
+    pub format: Option<String>,
+    // for the headings
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+// everything a recap is built from: the sessions the user's stored plans cite, their
+// stored favorites and feedback for the conference, and the request's additions
+pub struct RecapInputs {
+    pub sessions: Vec<RecapSession>,
+    pub feedback: Vec<SessionFeedback>,
+    pub contacts: Vec<Contact>,
+    // the cited sessions as the plans saw them, for the calendar format
+    pub sources: Vec<VivatechSource>,
+}
+
+impl RecapInputs {
+    // expects the request's feedback and favorites to be stored already
+    pub fn collect(
+        state: &AppState,
+        user_id: &str,
+        profile: &ConferenceProfile,
+        request: &RecapRequest,
+    ) -> Result<Self, PlanStoreError> {
+        let mut sessions: Vec<RecapSession> = Vec::new();
+        let mut sources: Vec<VivatechSource> = Vec::new();
+        let owner = usage::current_api_key();
+        for plan in state.plans.all_for_user(user_id, owner.as_deref())? {
+            if plan.conference != profile.id {
+                continue;
+            }
+            for source in popularity::cited_sessions(&plan.plan, &plan.sources) {
+                if sources.iter().any(|known| known.id == source.id) {
+                    continue;
+                }
+                sessions.push(RecapSession {
+                    session_id: source.id.clone(),
+                    title: placeholders::summary(source),
+                    notes: None,
+                    favorite: false,
+                });
+                sources.push(source.clone());
+            }
+        }
+
+        for extra in &request.itinerary {
+            match sessions
+                .iter_mut()
+                .find(|session| session.session_id == extra.session_id)
+            {
+                Some(session) => {
+                    session.favorite |= extra.favorite;
+                    if extra.notes.is_some() {
+                        session.notes.clone_from(&extra.notes);
+                    }
+                }
+                None => sessions.push(extra.clone()),
+            }
+        }
+
+        let feedback: Vec<SessionFeedback> = state
+            .plans
+            .feedback_for_user(user_id)?
+            .into_iter()
+            .filter(|stored| stored.conference == profile.id)
+            .map(|stored| SessionFeedback {
+                session_id: stored.session_id,
+                rating: stored.rating,
+                comment: stored.comment,
+            })
+            .collect();
+        // rated sessions were attended even when no plan cites them
+        for entry in &feedback {
+            if !sessions
+                .iter()
+                .any(|session| session.session_id == entry.session_id)
+            {
+                sessions.push(RecapSession {
+                    session_id: entry.session_id.clone(),
+                    title: session_title(state, profile, &entry.session_id),
+                    notes: None,
+                    favorite: false,
+                });
+            }
+        }
+
+        let favorites = state.plans.user_favorites(user_id, &profile.id)?;
+        for session in &mut sessions {
+            session.favorite |= favorites.contains(&session.session_id);
+        }
+
+        Ok(Self {
+            sessions,
+            feedback,
+            contacts: request.contacts.clone(),
+            sources,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty() && self.feedback.is_empty() && self.contacts.is_empty()
+    }
+
+    // everything the model needs, as plain text
+    pub fn to_prompt(&self, conference_name: &str) -> String {
+        let mut prompt = format!(
+            "Attendee recap for {}.\n\nSessions attended:\n",
+            conference_name
+        );
+        for session in &self.sessions {
+            prompt.push_str(&format!(
+                "- {}{}{}\n",
+                session.title,
+                if session.favorite { " (favorite)" } else { "" },
+                session
+                    .notes
+                    .as_deref()
+                    .map(|n| format!(" - notes: {}", n))
+                    .unwrap_or_default()
+            ));
+        }
+
+        prompt.push_str("\nFeedback:\n");
+        for feedback in &self.feedback {
+            prompt.push_str(&format!(
+                "- {}: rating {} - {}\n",
+                feedback.session_id,
+                feedback
+                    .rating
+                    .map(|r| r.to_string())
+                    .unwrap_or_else(|| "n/a".to_string()),
+                feedback.comment.as_deref().unwrap_or("")
+            ));
+        }
+
+        prompt.push_str("\nContacts met:\n");
+        for contact in &self.contacts {
+            prompt.push_str(&format!(
+                "- {}{}{}\n",
+                contact.name,
+                contact
+                    .company
+                    .as_deref()
+                    .map(|c| format!(" ({})", c))
+                    .unwrap_or_default(),
+                contact
+                    .notes
+                    .as_deref()
+                    .map(|n| format!(" - {}", n))
+                    .unwrap_or_default()
+            ));
+        }
+
+        prompt
+    }
+}
+
+// first line from the latest catalog sync for the default conference, the id otherwise
+fn session_title(state: &AppState, profile: &ConferenceProfile, session_id: &str) -> String {
+    if profile.id != state.conferences.default_profile().id {
+        return session_id.to_string();
+    }
+    state
+        .catalog_watcher
+        .with_snapshot(|snapshot| snapshot.get(session_id).map(placeholders::summary))
+        .unwrap_or_else(|| session_id.to_string())
+}
+
+// part of the recap written by the model
+#[derive(Debug, Default, Deserialize)]
+pub struct RecapInsights {
+    #[serde(default)]
+    pub highlights: Vec<String>,
+    #[serde(default)]
+    pub follow_up_actions: Vec<String>,
+}
+
+impl RecapInsights {
+    pub fn from_agent_output(raw: &str) -> Result<Self, String> {
+        let start = raw.find('{');
+        let end = raw.rfind('}');
+        let json = match (start, end) {
+            (Some(start), Some(end)) if start < end => &raw[start..=end],
+            _ => return Err("Agent response did not contain a JSON object".to_string()),
+        };
+
+        serde_json::from_str(json).map_err(|e| format!("Invalid recap from agent: {}", e))
+    }
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Recap {
+    pub user_id: String,
+    pub conference: String,
+    pub sessions_attended: Vec<RecapSession>,
+    pub key_contacts: Vec<Contact>,
+    pub highlights: Vec<String>,
+    pub follow_up_actions: Vec<String>,
+}
+
+impl Recap {
+    pub fn new(
+        user_id: String,
+        conference: String,
+        inputs: &RecapInputs,
+        insights: RecapInsights,
+    ) -> Self {
+        Self {
+            user_id,
+            conference,
+            sessions_attended: inputs.sessions.clone(),
+            key_contacts: inputs.contacts.clone(),
+            highlights: insights.highlights,
+            follow_up_actions: insights.follow_up_actions,
+        }
+    }
+
+    pub fn title(&self, language: Option<&str>) -> String {
+        copy::render(
+            "recap.title",
+            language,
+            &[("conference", self.conference.as_str())],
+        )
+    }
+
+    // the sections under the title, render.rs adds the title and the footer
+    pub fn to_markdown(&self, language: Option<&str>) -> String {
+        let heading = |key: &str| copy::text(key, language);
+        let mut markdown = format!("## {}\n\n", heading("recap.sessions_attended"));
+        for session in &self.sessions_attended {
+            let star = if session.favorite { " ⭐" } else { "" };
+            markdown.push_str(&format!("- **{}**{}\n", session.title, star));
+            if let Some(notes) = &session.notes {
+                markdown.push_str(&format!("  - {}\n", notes));
+            }
+        }
+
+        if !self.highlights.is_empty() {
+            markdown.push_str(&format!("\n## {}\n\n", heading("recap.highlights")));
+            for highlight in &self.highlights {
+                markdown.push_str(&format!("- {}\n", highlight));
+            }
+        }
+
+        if !self.key_contacts.is_empty() {
+            markdown.push_str(&format!("\n## {}\n\n", heading("recap.key_contacts")));
+            for contact in &self.key_contacts {
+                markdown.push_str(&format!("- **{}**", contact.name));
+                if let Some(company) = &contact.company {
+                    markdown.push_str(&format!(", {}", company));
+                }
+                if let Some(notes) = &contact.notes {
+                    markdown.push_str(&format!(" - {}", notes));
+                }
+                markdown.push('\n');
+            }
+        }
+
+        if !self.follow_up_actions.is_empty() {
+            markdown.push_str(&format!("\n## {}\n\n", heading("recap.follow_up_actions")));
+            for action in &self.follow_up_actions {
+                markdown.push_str(&format!("- [ ] {}\n", action));
+            }
+        }
+
+        markdown
+    }
+}
+
+// post-conference recap of what the user planned, starred and rated at a conference,
+// in any format render.rs knows
+pub async fn recap_handler(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    Query(query): Query<FormatQuery>,
+    headers: HeaderMap,
+    request: Option<Json<RecapRequest>>,
+) -> Response {
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    let format = query.format.as_deref().or(request.format.as_deref());
+    let Some(renderer) = render::negotiate(format, &headers, "json") else {
+        return render::not_acceptable();
+    };
+
+    let profile = match state.conferences.resolve(request.conference.as_deref()) {
+        Ok(profile) => profile.clone(),
+        Err(e) => return AppError::Validation(e).into_response(),
+    };
+    // kept for the user's export and later recaps, even when this one fails
+    if let Err(e) = state
+        .plans
+        .record_feedback(&user_id, &profile.id, &request.feedback)
+    {
+        tracing::error!("Failed to keep session feedback for {}: {}", user_id, e);
+    }
+    if let Err(e) = state
+        .plans
+        .record_user_favorites(&user_id, &profile.id, &request.favorites)
+    {
+        tracing::error!("Failed to keep favorite sessions for {}: {}", user_id, e);
+    }
+    popularity::record_favorites(&state, &profile.id, &request.favorites);
+
+    let inputs = match RecapInputs::collect(&state, &user_id, &profile, &request) {
+        Ok(inputs) => inputs,
+        Err(e) => {
+            tracing::error!("Failed to load recap inputs for {}: {}", user_id, e);
+            return AppError::Internal(copy::error("error.recap_load_failed", &[])).into_response();
+        }
+    };
+    if inputs.is_empty() {
+        return AppError::NotFound(copy::error("error.nothing_to_recap", &[])).into_response();
+    }
+
+    let choice = ModelChoice::from_env();
+    let llm_client = match initialize_llm_client(&choice) {
+        Ok(client) => client,
+        Err(e) => {
+            return AppError::Config(copy::error(
+                "error.ai_unavailable",
+                &[("error", e.to_string().as_str())],
+            ))
+            .into_response();
+        }
+    };
+
+    info!(
+        "Generating recap for user {} from {} sessions",
+        user_id,
+        inputs.sessions.len()
+    );
+    let recap_agent = build_recap_agent(&llm_client, &choice.model);
+    let insights = match prompt_with_retry(
+        &recap_agent,
+        &inputs.to_prompt(&profile.name),
+        &state.health,
+    )
+    .await
+    .and_then(|raw| RecapInsights::from_agent_output(&raw).map_err(AgentFailure::from))
+    {
+        Ok(insights) => insights,
+        Err(e) => {
+            tracing::error!("Recap generation failed: {}", e);
+            return AppError::llm(
+                copy::error("error.recap_failed", &[("error", e.message.as_str())]),
+                e.retry_after,
+            )
+            .into_response();
+        }
+    };
+
+    let language = request.language.as_deref();
+    let recap = Recap::new(user_id, profile.name.clone(), &inputs, insights);
+    let document = PlanDocument {
+        conference: profile.name.clone(),
+        title: Some(recap.title(language)),
+        timezone: profile.timezone.clone(),
+        year: profile.year(),
+        objective: String::new(),
+        plan: recap.to_markdown(language),
+        sources: inputs.sources,
+        created_at: Utc::now(),
+        expires_at: None,
+        language: request.language.clone(),
+        data: serde_json::to_value(&recap).unwrap_or_default(),
+    };
+    if renderer.is_download() {
+        render::download(renderer, &document, "recap")
+    } else {
+        render::respond(renderer, &document)
+    }
+}
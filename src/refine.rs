@@ -0,0 +1,225 @@
+// revising a stored plan with a follow-up instruction ("move everything after 4pm,
+// add more fintech"), and what changed between the two versions
+
+use std::collections::HashSet;
+
+use chrono::NaiveDateTime;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::citations;
+use crate::models::{GeneratePlanRequest, VivatechSource};
+use crate::placeholders;
+use crate::plans::StoredPlan;
+use crate::tools::session_slot;
+
+const MAX_INSTRUCTION_CHARS: usize = 500;
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct RefinePlanRequest {
+    // e.g. "move everything after 4pm, add more fintech"
+    pub instruction: String,
+    // "provider:model" for this revision, the original plan's model setting when unset
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+impl RefinePlanRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.instruction.trim().is_empty() {
+            return Err("instruction must not be empty".to_string());
+        }
+        if self.instruction.chars().count() > MAX_INSTRUCTION_CHARS {
+            return Err(format!(
+                "instruction must be at most {} characters",
+                MAX_INSTRUCTION_CHARS
+            ));
+        }
+        Ok(())
+    }
+
+    // the original request with the instruction noted in the objective, so the stored
+    // revision says what it was asked. always generated anew
+    pub fn plan_request(&self, stored: &StoredPlan) -> GeneratePlanRequest {
+        let original = serde_json::from_value::<GeneratePlanRequest>(stored.request.clone())
+            .unwrap_or_else(|_| {
+                GeneratePlanRequest::simple(
+                    stored.objective.clone(),
+                    Some(stored.conference.clone()),
+                )
+            });
+        GeneratePlanRequest {
+            objective: format!(
+                "{} (refined: {})",
+                stored.objective,
+                self.instruction.trim()
+            ),
+            conference: Some(stored.conference.clone()),
+            user_id: stored.user_id.clone(),
+            model: self.model.clone().or(original.model),
+            // the stored plan is read resolved, the revision is written from it
+            live_placeholders: false,
+            regenerate: true,
+            bypass_cache: true,
+            ..original
+        }
+    }
+
+    // the current plan and its sessions, so the agent only searches for what's new
+    pub fn prompt(&self, stored: &StoredPlan) -> String {
+        let mut prompt = format!(
+            "Attendee objective:\n{}\n\nCurrent plan:\n{}\n\nRevise the plan as the attendee \
+            asks: {}\n\nKeep every part of the plan the request doesn't touch, search for new \
+            sessions when it asks for more, and answer with the whole revised plan.\n\n\
+            Sessions in the current plan:\n",
+            stored.objective,
+            stored.plan,
+            self.instruction.trim()
+        );
+        for source in &stored.sources {
+            prompt.push_str(&format!("[{}] {}\n", source.id, source.text_chunk));
+        }
+        prompt
+    }
+}
+
+// a session that came, went or changed place between the two versions
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SlotChange {
+    pub session_id: String,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub starts_at: Option<NaiveDateTime>,
+    // 1-based plan steps, unset on the side the session is missing from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_step: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_step: Option<usize>,
+}
+
+#[derive(Debug, Default, Serialize, JsonSchema)]
+pub struct PlanDiff {
+    pub added: Vec<SlotChange>,
+    pub removed: Vec<SlotChange>,
+    // kept, but in another order relative to the other kept sessions
+    pub moved: Vec<SlotChange>,
+    // kept in the same order
+    pub unchanged: usize,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RefinePlanResponse {
+    // the revision is stored as a new plan, the original stays as it was
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plan_id: Option<String>,
+    pub refined_from: String,
+    pub plan: String,
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback_from: Option<String>,
+    pub diff: PlanDiff,
+    pub elapsed_ms: u64,
+}
+
+// the sessions a plan cites, each at the first step citing it, in plan order
+fn cited_sessions(plan: &str, sources: &[VivatechSource]) -> Vec<(String, usize)> {
+    let mut seen = HashSet::new();
+    citations::cite_plan(plan, sources)
+        .into_iter()
+        .filter(|citation| seen.insert(citation.source_id.clone()))
+        .map(|citation| (citation.source_id, citation.step))
+        .collect()
+}
+
+// sessions are matched by the sources each version cites. the revision's sources
+// include the original's
+pub fn diff(before: &StoredPlan, plan: &str, sources: &[VivatechSource], year: i32) -> PlanDiff {
+    let old = cited_sessions(&before.plan, &before.sources);
+    let new = cited_sessions(plan, sources);
+    let change = |id: &str, from_step: Option<usize>, to_step: Option<usize>| {
+        let source = sources
+            .iter()
+            .chain(&before.sources)
+            .find(|source| source.id == id);
+        SlotChange {
+            session_id: id.to_string(),
+            title: source
+                .map(placeholders::summary)
+                .unwrap_or_else(|| id.to_string()),
+            starts_at: source
+                .and_then(|source| session_slot(source, year))
+                .map(|slot| slot.start),
+            from_step,
+            to_step,
+        }
+    };
+    let step_in = |sessions: &[(String, usize)], id: &str| {
+        sessions
+            .iter()
+            .find(|(session, _)| session == id)
+            .map(|(_, step)| *step)
+    };
+
+    let mut diff = PlanDiff::default();
+    for (id, step) in &new {
+        if step_in(&old, id).is_none() {
+            diff.added.push(change(id, None, Some(*step)));
+        }
+    }
+    for (id, step) in &old {
+        if step_in(&new, id).is_none() {
+            diff.removed.push(change(id, Some(*step), None));
+        }
+    }
+
+    // steps shift whenever something is added or removed before them, so only the
+    // fewest kept sessions that explain the new order count as moved
+    let kept_before: Vec<&str> = old
+        .iter()
+        .map(|(id, _)| id.as_str())
+        .filter(|id| step_in(&new, id).is_some())
+        .collect();
+    let kept_after: Vec<&str> = new
+        .iter()
+        .map(|(id, _)| id.as_str())
+        .filter(|id| step_in(&old, id).is_some())
+        .collect();
+    let in_order = longest_common_order(&kept_before, &kept_after);
+    for id in kept_after {
+        if in_order.contains(id) {
+            diff.unchanged += 1;
+        } else {
+            diff.moved
+                .push(change(id, step_in(&old, id), step_in(&new, id)));
+        }
+    }
+    diff
+}
+
+// the longest run of sessions both versions keep in the same relative order
+fn longest_common_order<'a>(before: &[&'a str], after: &[&str]) -> HashSet<&'a str> {
+    let mut lengths = vec![vec![0usize; after.len() + 1]; before.len() + 1];
+    for i in (0..before.len()).rev() {
+        for j in (0..after.len()).rev() {
+            lengths[i][j] = if before[i] == after[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut common = HashSet::new();
+    while i < before.len() && j < after.len() {
+        if before[i] == after[j] {
+            common.insert(before[i]);
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    common
+}
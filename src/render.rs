@@ -0,0 +1,658 @@
+// plan renderers picked by content negotiation. a new format is one more PlanRenderer
+// in RENDERERS, handlers only build a PlanDocument and call respond or download
+
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+use printpdf::{
+    BuiltinFont, IndirectFontRef, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference,
+};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::copy;
+use crate::error::{self, AppError};
+use crate::language;
+use crate::models::VivatechSource;
+use crate::placeholders;
+use crate::plans::StoredPlan;
+use crate::state::AppState;
+use crate::tools::{self, IcalExportError, ItinerarySession};
+
+#[derive(Debug, thiserror::Error)]
+pub enum RenderError {
+    #[error("JSON encoding error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Calendar error: {0}")]
+    Calendar(#[from] IcalExportError),
+    #[error("PDF error: {0}")]
+    Pdf(#[from] printpdf::Error),
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct FormatQuery {
+    // json, html, markdown, text, ics or pdf, the Accept header decides when unset
+    #[serde(default)]
+    pub format: Option<String>,
+}
+
+// what any format may need, built once per response
+pub struct PlanDocument {
+    // display name, e.g. "Vivatech 2025"
+    pub conference: String,
+    // heading in place of the plan's, e.g. for a recap
+    pub title: Option<String>,
+    pub timezone: String,
+    // assumed for session dates written without one
+    pub year: i32,
+    // left out when empty
+    pub objective: String,
+    pub plan: String,
+    pub sources: Vec<VivatechSource>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub language: Option<String>,
+    // the endpoint's json payload, so the json format keeps its shape per endpoint
+    pub data: Value,
+}
+
+impl PlanDocument {
+    pub fn new(state: &AppState, stored: &StoredPlan, data: Value) -> Self {
+        let (conference, timezone, year) = match state.conferences.resolve(Some(&stored.conference))
+        {
+            Ok(profile) => (
+                profile.name.clone(),
+                profile.timezone.clone(),
+                profile.year(),
+            ),
+            Err(_) => {
+                let profile = state.conferences.default_profile();
+                (
+                    stored.conference.clone(),
+                    profile.timezone.clone(),
+                    profile.year(),
+                )
+            }
+        };
+        Self {
+            conference,
+            title: None,
+            timezone,
+            year,
+            objective: stored.objective.clone(),
+            plan: stored.plan.clone(),
+            sources: stored.sources.clone(),
+            created_at: stored.created_at,
+            expires_at: None,
+            // the language the plan was asked in, for the page copy
+            language: stored
+                .request
+                .get("language")
+                .and_then(|v| v.as_str())
+                .map(str::to_string),
+            data,
+        }
+    }
+
+    pub fn with_expiry(mut self, expires_at: Option<DateTime<Utc>>) -> Self {
+        self.expires_at = expires_at;
+        self
+    }
+}
+
+pub trait PlanRenderer: Sync {
+    // value of ?format=
+    fn format(&self) -> &'static str;
+
+    // Content-Type of the response, its media type is what Accept is matched against
+    fn content_type(&self) -> &'static str;
+
+    fn render(&self, document: &PlanDocument) -> Result<Vec<u8>, RenderError>;
+
+    fn extension(&self) -> &'static str {
+        self.format()
+    }
+
+    // formats only meant to be saved are always sent as an attachment
+    fn is_download(&self) -> bool {
+        false
+    }
+
+    fn media_type(&self) -> &'static str {
+        self.content_type()
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+    }
+}
+
+static RENDERERS: &[&dyn PlanRenderer] = &[
+    &JsonRenderer,
+    &HtmlRenderer,
+    &MarkdownRenderer,
+    &PlainTextRenderer,
+    &IcsRenderer,
+    &PdfRenderer,
+];
+
+pub fn formats() -> Vec<&'static str> {
+    RENDERERS.iter().map(|renderer| renderer.format()).collect()
+}
+
+pub fn media_types() -> Vec<&'static str> {
+    RENDERERS
+        .iter()
+        .map(|renderer| renderer.media_type())
+        .collect()
+}
+
+pub fn by_format(format: &str) -> Option<&'static dyn PlanRenderer> {
+    RENDERERS
+        .iter()
+        .copied()
+        .find(|renderer| renderer.format().eq_ignore_ascii_case(format.trim()))
+}
+
+// ?format= wins, then the Accept header by quality. `default` answers */*, no Accept
+// header and browsers asking for several things at once. None is a 406
+pub fn negotiate(
+    format: Option<&str>,
+    headers: &HeaderMap,
+    default: &str,
+) -> Option<&'static dyn PlanRenderer> {
+    if let Some(format) = format.filter(|format| !format.trim().is_empty()) {
+        return by_format(format);
+    }
+    let Some(accept) = headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .filter(|accept| !accept.trim().is_empty())
+    else {
+        return by_format(default);
+    };
+
+    let mut ranges: Vec<(&str, f32)> = accept
+        .split(',')
+        .map(|range| {
+            let mut parts = range.split(';');
+            let media = parts.next().unwrap_or_default().trim();
+            let quality = parts
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (media, quality)
+        })
+        .filter(|(_, quality)| *quality > 0.0)
+        .collect();
+    // stable, so equal qualities keep the client's order
+    ranges.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+    ranges.into_iter().find_map(|(media, _)| match media {
+        "*/*" => by_format(default),
+        _ => match media.strip_suffix("/*") {
+            Some(kind) => RENDERERS
+                .iter()
+                .copied()
+                .find(|renderer| renderer.media_type().split('/').next() == Some(kind)),
+            None => RENDERERS
+                .iter()
+                .copied()
+                .find(|renderer| renderer.media_type().eq_ignore_ascii_case(media)),
+        },
+    })
+}
+
+pub fn respond(renderer: &dyn PlanRenderer, document: &PlanDocument) -> Response {
+    let attachment = renderer.is_download().then_some("plan");
+    rendered(renderer, document, attachment)
+}
+
+// always an attachment, named `<file_stem>.<extension>`
+pub fn download(renderer: &dyn PlanRenderer, document: &PlanDocument, file_stem: &str) -> Response {
+    rendered(renderer, document, Some(file_stem))
+}
+
+fn rendered(
+    renderer: &dyn PlanRenderer,
+    document: &PlanDocument,
+    attachment: Option<&str>,
+) -> Response {
+    let body = match renderer.render(document) {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::error!("Failed to render plan as {}: {}", renderer.format(), e);
+            return AppError::Internal(copy::error(
+                "error.render_failed",
+                &[("format", renderer.format())],
+            ))
+            .into_response();
+        }
+    };
+
+    let mut response = ([(header::CONTENT_TYPE, renderer.content_type())], body).into_response();
+    if let Some(file_stem) = attachment {
+        let disposition = format!(
+            "attachment; filename=\"{}.{}\"",
+            file_stem,
+            renderer.extension()
+        );
+        if let Ok(value) = HeaderValue::from_str(&disposition) {
+            response
+                .headers_mut()
+                .insert(header::CONTENT_DISPOSITION, value);
+        }
+    }
+    response
+}
+
+pub fn not_acceptable() -> Response {
+    error::respond(
+        StatusCode::NOT_ACCEPTABLE,
+        error::envelope(
+            "not_acceptable",
+            copy::error(
+                "error.format_not_acceptable",
+                &[("formats", formats().join(", ").as_str())],
+            ),
+        ),
+    )
+}
+
+pub struct JsonRenderer;
+
+impl PlanRenderer for JsonRenderer {
+    fn format(&self) -> &'static str {
+        "json"
+    }
+
+    fn content_type(&self) -> &'static str {
+        "application/json"
+    }
+
+    fn render(&self, document: &PlanDocument) -> Result<Vec<u8>, RenderError> {
+        Ok(serde_json::to_vec(&document.data)?)
+    }
+}
+
+pub struct MarkdownRenderer;
+
+impl PlanRenderer for MarkdownRenderer {
+    fn format(&self) -> &'static str {
+        "markdown"
+    }
+
+    fn content_type(&self) -> &'static str {
+        "text/markdown; charset=utf-8"
+    }
+
+    fn extension(&self) -> &'static str {
+        "md"
+    }
+
+    fn render(&self, document: &PlanDocument) -> Result<Vec<u8>, RenderError> {
+        let objective = objective(document)
+            .map(|(label, objective)| format!("**{}** {}\n\n", label, objective))
+            .unwrap_or_default();
+        Ok(format!(
+            "# {}\n\n{}{}\n\n_{}{}_\n",
+            heading(document),
+            objective,
+            document.plan.trim(),
+            generated(document),
+            expiry(document)
+        )
+        .into_bytes())
+    }
+}
+
+pub struct PlainTextRenderer;
+
+impl PlanRenderer for PlainTextRenderer {
+    fn format(&self) -> &'static str {
+        "text"
+    }
+
+    fn content_type(&self) -> &'static str {
+        "text/plain; charset=utf-8"
+    }
+
+    fn extension(&self) -> &'static str {
+        "txt"
+    }
+
+    // the markdown markers dropped, for sms gateways and terminals
+    fn render(&self, document: &PlanDocument) -> Result<Vec<u8>, RenderError> {
+        let objective = objective(document)
+            .map(|(label, objective)| format!("{} {}\n\n", label, objective))
+            .unwrap_or_default();
+        let plan = plain_lines(&document.plan);
+        Ok(format!(
+            "{}\n\n{}{}\n\n{}{}\n",
+            heading(document),
+            objective,
+            plan.join("\n"),
+            generated(document),
+            expiry(document)
+        )
+        .into_bytes())
+    }
+}
+
+pub struct HtmlRenderer;
+
+impl PlanRenderer for HtmlRenderer {
+    fn format(&self) -> &'static str {
+        "html"
+    }
+
+    fn content_type(&self) -> &'static str {
+        "text/html; charset=utf-8"
+    }
+
+    fn render(&self, document: &PlanDocument) -> Result<Vec<u8>, RenderError> {
+        let language = document.language.as_deref();
+        let title = document.title.clone().unwrap_or_else(|| {
+            copy::render(
+                "share.title",
+                language,
+                &[("conference", document.conference.as_str())],
+            )
+        });
+        let objective = objective(document)
+            .map(|(label, objective)| {
+                format!(
+                    "<p><strong>{}</strong> {}</p>\n",
+                    escape_html(&label),
+                    escape_html(objective)
+                )
+            })
+            .unwrap_or_default();
+        Ok(format!(
+            "<!DOCTYPE html>\n\
+            <html lang=\"{lang}\">\n\
+            <head>\n\
+            <meta charset=\"utf-8\">\n\
+            <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+            <meta name=\"robots\" content=\"noindex\">\n\
+            <title>{title}</title>\n\
+            <style>body{{font-family:system-ui,sans-serif;max-width:42rem;margin:2rem auto;padding:0 1rem;line-height:1.5}}\
+            pre{{white-space:pre-wrap;font-family:inherit}}small{{color:#666}}</style>\n\
+            </head>\n\
+            <body>\n\
+            <h1>{heading}</h1>\n\
+            {objective}\
+            <pre>{plan}</pre>\n\
+            <small>{generated}{expiry}</small>\n\
+            </body>\n\
+            </html>\n",
+            lang = escape_html(
+                &language
+                    .map(copy::language_code)
+                    .unwrap_or_else(copy::default_language)
+            ),
+            title = escape_html(&title),
+            heading = escape_html(&heading(document)),
+            plan = escape_html(&document.plan),
+            generated = escape_html(&generated(document)),
+            expiry = escape_html(&expiry(document)),
+        )
+        .into_bytes())
+    }
+}
+
+pub struct IcsRenderer;
+
+impl PlanRenderer for IcsRenderer {
+    fn format(&self) -> &'static str {
+        "ics"
+    }
+
+    fn content_type(&self) -> &'static str {
+        "text/calendar; charset=utf-8"
+    }
+
+    fn is_download(&self) -> bool {
+        true
+    }
+
+    // the plan's sources that have a date and a start time, the rest cannot be placed.
+    // titles only, shared links never show the source text
+    fn render(&self, document: &PlanDocument) -> Result<Vec<u8>, RenderError> {
+        let sessions: Vec<ItinerarySession> = document
+            .sources
+            .iter()
+            .filter_map(|source| {
+                let slot = tools::session_slot(source, document.year)?;
+                Some(ItinerarySession {
+                    title: placeholders::summary(source),
+                    start: slot.start,
+                    end: (!slot.end_estimated).then_some(slot.end),
+                    location: None,
+                    description: None,
+                    source_id: Some(source.id.clone()),
+                })
+            })
+            .collect();
+        let calendar = tools::render_ical(&sessions, &document.timezone, &heading(document))?;
+        Ok(calendar.into_bytes())
+    }
+}
+
+// A4 in portrait, printed with the builtin Helvetica so no font file has to ship
+const PAGE_WIDTH: Mm = Mm(210.0);
+const PAGE_HEIGHT: Mm = Mm(297.0);
+const MARGIN: f32 = 20.0;
+const LINE_HEIGHT: f32 = 5.5;
+const BODY_SIZE: f32 = 11.0;
+const HEADING_SIZE: f32 = 16.0;
+// what fits between the margins at BODY_SIZE
+const LINE_CHARS: usize = 88;
+
+pub struct PdfRenderer;
+
+impl PlanRenderer for PdfRenderer {
+    fn format(&self) -> &'static str {
+        "pdf"
+    }
+
+    fn content_type(&self) -> &'static str {
+        "application/pdf"
+    }
+
+    fn is_download(&self) -> bool {
+        true
+    }
+
+    fn render(&self, document: &PlanDocument) -> Result<Vec<u8>, RenderError> {
+        let title = heading(document);
+        let (pdf, page, layer) = PdfDocument::new(latin1(&title), PAGE_WIDTH, PAGE_HEIGHT, "plan");
+        let mut writer = PdfWriter {
+            regular: pdf.add_builtin_font(BuiltinFont::Helvetica)?,
+            bold: pdf.add_builtin_font(BuiltinFont::HelveticaBold)?,
+            layer: pdf.get_page(page).get_layer(layer),
+            y: PAGE_HEIGHT.0 - MARGIN,
+            pdf,
+        };
+
+        writer.line(&title, HEADING_SIZE, true);
+        writer.skip();
+        if let Some((label, objective)) = objective(document) {
+            writer.paragraph(&format!("{} {}", label, objective), true);
+            writer.skip();
+        }
+        for line in plain_lines(&document.plan) {
+            writer.paragraph(&line, false);
+        }
+        writer.skip();
+        writer.paragraph(
+            &format!("{}{}", generated(document), expiry(document)),
+            false,
+        );
+        Ok(writer.pdf.save_to_bytes()?)
+    }
+}
+
+// top to bottom, starting a new page when the bottom margin is reached
+struct PdfWriter {
+    pdf: PdfDocumentReference,
+    layer: PdfLayerReference,
+    regular: IndirectFontRef,
+    bold: IndirectFontRef,
+    // baseline of the next line, from the bottom of the page
+    y: f32,
+}
+
+impl PdfWriter {
+    fn line(&mut self, text: &str, size: f32, bold: bool) {
+        if self.y < MARGIN {
+            let (page, layer) = self.pdf.add_page(PAGE_WIDTH, PAGE_HEIGHT, "plan");
+            self.layer = self.pdf.get_page(page).get_layer(layer);
+            self.y = PAGE_HEIGHT.0 - MARGIN;
+        }
+        let font = if bold { &self.bold } else { &self.regular };
+        self.layer
+            .use_text(latin1(text), size, Mm(MARGIN), Mm(self.y), font);
+        self.y -= LINE_HEIGHT * size / BODY_SIZE;
+    }
+
+    fn paragraph(&mut self, text: &str, bold: bool) {
+        if text.trim().is_empty() {
+            self.skip();
+        }
+        for line in wrap(text, LINE_CHARS) {
+            self.line(&line, BODY_SIZE, bold);
+        }
+    }
+
+    fn skip(&mut self) {
+        self.y -= LINE_HEIGHT;
+    }
+}
+
+// on word boundaries, words longer than a line are cut
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        while word.len() > width {
+            if !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+            }
+            lines.push(word.drain(..width).collect());
+        }
+        let word: String = word.into_iter().collect();
+        if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(&word);
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+// the builtin fonts only cover latin-1, anything else prints as '?'
+fn latin1(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '–' | '—' => '-',
+            '‘' | '’' => '\'',
+            '“' | '”' => '"',
+            '…' => '.',
+            '⭐' | '★' => '*',
+            c if (c as u32) < 0x100 => c,
+            _ => '?',
+        })
+        .collect()
+}
+
+// plan lines without the markdown markers
+fn plain_lines(plan: &str) -> Vec<String> {
+    plan.trim()
+        .lines()
+        .map(|line| {
+            line.trim_start_matches('#')
+                .trim_start_matches(' ')
+                .replace("**", "")
+                .replace("__", "")
+                .replace('`', "")
+        })
+        .collect()
+}
+
+fn heading(document: &PlanDocument) -> String {
+    document.title.clone().unwrap_or_else(|| {
+        copy::render(
+            "share.heading",
+            document.language.as_deref(),
+            &[("conference", document.conference.as_str())],
+        )
+    })
+}
+
+// the label and the objective, None when there is none
+fn objective(document: &PlanDocument) -> Option<(String, &str)> {
+    let objective = document.objective.trim();
+    (!objective.is_empty()).then(|| {
+        (
+            copy::text("share.objective", document.language.as_deref()),
+            objective,
+        )
+    })
+}
+
+fn generated(document: &PlanDocument) -> String {
+    let created = format!(
+        "{} UTC",
+        language::format_datetime(
+            document.created_at.naive_utc(),
+            document.language.as_deref()
+        )
+    );
+    copy::render(
+        "share.generated",
+        document.language.as_deref(),
+        &[("created", created.as_str())],
+    )
+}
+
+fn expiry(document: &PlanDocument) -> String {
+    document
+        .expires_at
+        .map(|t| {
+            let expires = format!(
+                "{} UTC",
+                language::format_datetime(t.naive_utc(), document.language.as_deref())
+            );
+            format!(
+                " · {}",
+                copy::render(
+                    "share.valid_until",
+                    document.language.as_deref(),
+                    &[("expires", expires.as_str())]
+                )
+            )
+        })
+        .unwrap_or_default()
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,218 @@
+// admin replays of logged plan requests, diffed against what was answered at the time
+
+use std::collections::HashSet;
+
+use crate::models::{GeneratePlanRequest, PlanOutcome, TokenUsage};
+use serde::{Deserialize, Serialize};
+
+use crate::plans::StoredPlan;
+use crate::usage;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplayMode {
+    // nothing is stored, tokens are still spent and tagged as a replay
+    #[default]
+    DryRun,
+    // stored as a new plan for the same user, like a fresh request
+    Live,
+}
+
+impl ReplayMode {
+    fn tag(self) -> &'static str {
+        match self {
+            ReplayMode::DryRun => "dry_run",
+            ReplayMode::Live => "live",
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ReplayRequest {
+    #[serde(default)]
+    pub mode: ReplayMode,
+    // GeneratePlanRequest fields replacing the logged ones, e.g.
+    // {"model": "anthropic:claude-3-5-sonnet-latest", "temperature": 0.2}
+    #[serde(default)]
+    pub overrides: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ReplayRequest {
+    // the logged request with the overrides applied. never answered with the
+    // earlier plan, and its spend is tagged so replays stand out in usage reports
+    pub fn plan_request(&self, original: &StoredPlan) -> Result<GeneratePlanRequest, String> {
+        let mut fields = match &original.request {
+            serde_json::Value::Object(fields) => fields.clone(),
+            _ => return Err("the logged request is not a JSON object".to_string()),
+        };
+        fields.extend(self.overrides.clone());
+        let mut request: GeneratePlanRequest =
+            serde_json::from_value(serde_json::Value::Object(fields))
+                .map_err(|e| format!("Invalid replay overrides: {}", e))?;
+        usage::validate_cost_tags(&request.cost_tags)?;
+        request.regenerate = true;
+        request
+            .cost_tags
+            .insert("replay".to_string(), self.mode.tag().to_string());
+        Ok(request)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReplayedPlan {
+    // set for live replays
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plan_id: Option<String>,
+    pub plan: String,
+    pub model: String,
+    pub sources_used: Vec<String>,
+    pub usage: TokenUsage,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OriginalPlan {
+    pub plan_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub plan: String,
+    pub model: String,
+    pub sources_used: Vec<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlanDiff {
+    pub identical: bool,
+    pub model_changed: bool,
+    pub lines_added: usize,
+    pub lines_removed: usize,
+    pub sources_added: Vec<String>,
+    pub sources_removed: Vec<String>,
+    // every line of both plans prefixed with "+ ", "- " or two spaces
+    pub unified: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReplayReport {
+    pub mode: ReplayMode,
+    // what was run, overrides applied
+    pub request: GeneratePlanRequest,
+    pub original: OriginalPlan,
+    pub replay: ReplayedPlan,
+    pub diff: PlanDiff,
+}
+
+impl ReplayReport {
+    // both plans are compared as stored, before placeholders are resolved and footers added
+    pub fn new(
+        mode: ReplayMode,
+        request: GeneratePlanRequest,
+        original: StoredPlan,
+        outcome: PlanOutcome,
+        plan_id: Option<String>,
+        usage: TokenUsage,
+        elapsed_ms: u64,
+    ) -> Self {
+        let original = OriginalPlan {
+            sources_used: original
+                .sources
+                .into_iter()
+                .map(|source| source.id)
+                .collect(),
+            plan_id: original.id,
+            request_id: original.request_id,
+            plan: original.plan,
+            model: original.model,
+            created_at: original.created_at,
+        };
+        let replay = ReplayedPlan {
+            plan_id,
+            sources_used: outcome
+                .sources
+                .into_iter()
+                .map(|source| source.id)
+                .collect(),
+            plan: outcome.body,
+            model: outcome.model,
+            usage,
+            elapsed_ms,
+        };
+        let diff = diff_plans(&original, &replay);
+        Self {
+            mode,
+            request,
+            original,
+            replay,
+            diff,
+        }
+    }
+}
+
+fn diff_plans(original: &OriginalPlan, replay: &ReplayedPlan) -> PlanDiff {
+    let lines = diff_lines(&original.plan, &replay.plan);
+    let before: HashSet<&String> = original.sources_used.iter().collect();
+    let after: HashSet<&String> = replay.sources_used.iter().collect();
+
+    let mut unified = String::new();
+    for (marker, line) in &lines {
+        unified.push(*marker);
+        unified.push(' ');
+        unified.push_str(line);
+        unified.push('\n');
+    }
+    PlanDiff {
+        identical: lines.iter().all(|(marker, _)| *marker == ' '),
+        model_changed: original.model != replay.model,
+        lines_added: lines.iter().filter(|(marker, _)| *marker == '+').count(),
+        lines_removed: lines.iter().filter(|(marker, _)| *marker == '-').count(),
+        sources_added: replay
+            .sources_used
+            .iter()
+            .filter(|id| !before.contains(id))
+            .cloned()
+            .collect(),
+        sources_removed: original
+            .sources_used
+            .iter()
+            .filter(|id| !after.contains(id))
+            .cloned()
+            .collect(),
+        unified,
+    }
+}
+
+// line diff over the longest common subsequence, plans are a few hundred lines at most
+fn diff_lines<'a>(before: &'a str, after: &'a str) -> Vec<(char, &'a str)> {
+    let a: Vec<&str> = before.lines().collect();
+    let b: Vec<&str> = after.lines().collect();
+    let mut common = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            common[i][j] = if a[i] == b[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::with_capacity(a.len().max(b.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            lines.push((' ', a[i]));
+            i += 1;
+            j += 1;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            lines.push(('-', a[i]));
+            i += 1;
+        } else {
+            lines.push(('+', b[j]));
+            j += 1;
+        }
+    }
+    lines.extend(a[i..].iter().map(|line| ('-', *line)));
+    lines.extend(b[j..].iter().map(|line| ('+', *line)));
+    lines
+}
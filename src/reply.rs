@@ -0,0 +1,109 @@
+// what the plan routes answer: the response body with the status, Retry-After and
+// X-Cache it is served with, shared by the rest handlers, graphql and the bots
+
+use std::time::{Duration, Instant};
+
+use axum::{
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+
+use crate::citations;
+use crate::error::AppError;
+use crate::models::{GeneratePlanResponse, PlanOutcome, TokenUsage};
+use crate::plan_cache::CacheStatus;
+use crate::streaming;
+
+// typed plan result with the status it should be served with
+pub struct PlanReply {
+    pub status: StatusCode,
+    pub retry_after: Option<Duration>,
+    // answered as X-Cache while the plan cache is on
+    pub cache: Option<CacheStatus>,
+    pub body: GeneratePlanResponse,
+}
+
+impl IntoResponse for PlanReply {
+    fn into_response(self) -> Response {
+        let mut response = match self.retry_after {
+            Some(delay) => (
+                self.status,
+                [(header::RETRY_AFTER, delay.as_secs().max(1).to_string())],
+                Json(self.body),
+            )
+                .into_response(),
+            None => (self.status, Json(self.body)).into_response(),
+        };
+        if let Some(cache) = self.cache {
+            response
+                .headers_mut()
+                .insert("x-cache", HeaderValue::from_static(cache.as_str()));
+        }
+        response
+    }
+}
+
+pub fn elapsed_ms(started: Instant) -> u64 {
+    started.elapsed().as_millis() as u64
+}
+
+pub fn plan_success(
+    outcome: PlanOutcome,
+    plan_id: Option<String>,
+    citations: Option<Vec<citations::Citation>>,
+    started: Instant,
+) -> PlanReply {
+    PlanReply {
+        status: StatusCode::OK,
+        retry_after: None,
+        cache: None,
+        body: GeneratePlanResponse::success(outcome, plan_id, citations, elapsed_ms(started)),
+    }
+}
+
+impl PlanReply {
+    // websocket clients get rejections as error frames instead of a status
+    pub fn into_stream_error(self) -> streaming::StreamEvent {
+        let code = match self.status {
+            StatusCode::TOO_MANY_REQUESTS => streaming::StreamErrorCode::RateLimited,
+            StatusCode::SERVICE_UNAVAILABLE => streaming::StreamErrorCode::ProviderUnavailable,
+            status if status.is_client_error() => streaming::StreamErrorCode::InvalidRequest,
+            _ => streaming::StreamErrorCode::ProviderError,
+        };
+        let message = match self.body {
+            GeneratePlanResponse::Error { error, .. } => error.message,
+            _ => "Request rejected".to_string(),
+        };
+        streaming::StreamEvent::Error {
+            code,
+            message,
+            retryable: code.retryable(),
+            partial: false,
+            retry_after_seconds: self.retry_after.map(|delay| delay.as_secs().max(1)),
+        }
+    }
+
+    pub fn with_usage(self, spent: TokenUsage) -> Self {
+        PlanReply {
+            body: self.body.with_usage(spent),
+            ..self
+        }
+    }
+
+    pub fn with_cache(self, cache: CacheStatus) -> Self {
+        PlanReply {
+            cache: Some(cache),
+            ..self
+        }
+    }
+}
+
+pub fn plan_error(error: AppError, started: Instant) -> PlanReply {
+    PlanReply {
+        status: error.status(),
+        retry_after: error.retry_after(),
+        cache: None,
+        body: GeneratePlanResponse::error(error.envelope(), elapsed_ms(started)),
+    }
+}
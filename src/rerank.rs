@@ -0,0 +1,287 @@
+// re-scores search results against the query before the planner sees them, the
+// upstream score is noisy. only the top RERANK_TOP_K are kept, which also saves tokens
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+use rig::embeddings::EmbeddingModel as _;
+use rig::providers::openai;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::config;
+use crate::llm::{self, LlmClient, ModelChoice};
+use crate::models::VivatechSource;
+
+static RERANKER: OnceLock<Reranker> = OnceLock::new();
+
+// session embeddings kept across searches, cleared as a whole past this
+const MAX_CACHED_EMBEDDINGS: usize = 5_000;
+
+const RELEVANCE_INSTRUCTIONS: &str = "\
+    You judge how well conference sessions and partners match an attendee's search. \
+    Return one entry per item, using its id exactly as given. \
+    score: 0 when the item is unrelated to the query, 10 when it is exactly what was asked for. \
+    Judge the content only, not how the item is phrased.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RerankMode {
+    Off,
+    // cosine similarity between openai embeddings of the query and each chunk
+    Embedding,
+    // the model scores every chunk against the query, like a cross-encoder
+    Llm,
+}
+
+impl FromStr for RerankMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "off" | "none" => Ok(RerankMode::Off),
+            "embedding" | "embeddings" => Ok(RerankMode::Embedding),
+            "llm" | "prompt" => Ok(RerankMode::Llm),
+            other => Err(format!(
+                "unknown RERANK_MODE '{}', expected off, embedding or llm",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RerankError {
+    #[error("embedding failed: {0}")]
+    Embedding(#[from] rig::embeddings::EmbeddingError),
+    #[error("relevance scoring failed: {0}")]
+    Scoring(String),
+}
+
+// what the relevance prompt returns
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+struct RelevanceBatch {
+    items: Vec<RelevanceScore>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+struct RelevanceScore {
+    id: String,
+    score: f32,
+}
+
+// "provider:model" or a bare model for LLM_PROVIDER, defaults to a small model
+fn scoring_model() -> Result<ModelChoice, String> {
+    let provider = llm::default_provider();
+    match config::get().rerank_model.as_deref() {
+        Some(spec) => ModelChoice::parse(spec, provider),
+        None if provider == llm::Provider::OpenAI => ModelChoice::parse("gpt-4o-mini", provider),
+        None => Ok(ModelChoice::default_for(provider)),
+    }
+}
+
+enum Scorer {
+    Embedding {
+        model: openai::EmbeddingModel,
+        // chunk text hash to its embedding
+        cache: Mutex<HashMap<u64, Vec<f64>>>,
+    },
+    Llm {
+        client: LlmClient,
+        model: ModelChoice,
+    },
+}
+
+pub struct Reranker {
+    scorer: Scorer,
+    top_k: usize,
+}
+
+impl fmt::Display for Reranker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.scorer {
+            Scorer::Embedding { .. } => write!(f, "embeddings, top {}", self.top_k),
+            Scorer::Llm { model, .. } => write!(f, "{}, top {}", model, self.top_k),
+        }
+    }
+}
+
+impl Reranker {
+    // scores with a model through `client`, as RERANK_MODE=llm does with the env's provider
+    pub fn llm(client: LlmClient, model: ModelChoice, top_k: usize) -> Self {
+        Self {
+            scorer: Scorer::Llm { client, model },
+            top_k: top_k.max(1),
+        }
+    }
+
+    // best match first, the new score replaces the upstream one. when scoring fails
+    // the upstream order is kept, still cut to the top k
+    pub async fn rerank(
+        &self,
+        query: &str,
+        mut sources: Vec<VivatechSource>,
+    ) -> Vec<VivatechSource> {
+        if sources.len() > 1 {
+            match self.scores(query, &sources).await {
+                Ok(scores) => {
+                    for (source, score) in sources.iter_mut().zip(scores) {
+                        source.score = score;
+                    }
+                }
+                Err(e) => warn!("Reranking failed, keeping the upstream order: {}", e),
+            }
+            sources.sort_by(|a, b| b.score.total_cmp(&a.score));
+        }
+        sources.truncate(self.top_k);
+        sources
+    }
+
+    // one score per source, in the same order
+    async fn scores(
+        &self,
+        query: &str,
+        sources: &[VivatechSource],
+    ) -> Result<Vec<f32>, RerankError> {
+        match &self.scorer {
+            Scorer::Embedding { model, cache } => {
+                embedding_scores(model, cache, query, sources).await
+            }
+            Scorer::Llm { client, model } => relevance_scores(client, model, query, sources).await,
+        }
+    }
+}
+
+// only chunks not seen before are embedded, in the same call as the query
+async fn embedding_scores(
+    model: &openai::EmbeddingModel,
+    cache: &Mutex<HashMap<u64, Vec<f64>>>,
+    query: &str,
+    sources: &[VivatechSource],
+) -> Result<Vec<f32>, RerankError> {
+    let hashes: Vec<u64> = sources.iter().map(|s| text_hash(&s.text_chunk)).collect();
+    let mut pending: Vec<(u64, String)> = {
+        let cache = cache.lock().unwrap_or_else(PoisonError::into_inner);
+        sources
+            .iter()
+            .zip(&hashes)
+            .filter(|(_, hash)| !cache.contains_key(hash))
+            .map(|(source, hash)| (*hash, source.text_chunk.clone()))
+            .collect()
+    };
+    pending.sort_by_key(|(hash, _)| *hash);
+    pending.dedup_by_key(|(hash, _)| *hash);
+
+    let texts =
+        std::iter::once(query.to_string()).chain(pending.iter().map(|(_, text)| text.clone()));
+    let mut embeddings = model.embed_texts(texts).await?.into_iter();
+    let query_vec = embeddings.next().map(|e| e.vec).unwrap_or_default();
+
+    let mut cache = cache.lock().unwrap_or_else(PoisonError::into_inner);
+    if cache.len() + pending.len() > MAX_CACHED_EMBEDDINGS {
+        cache.clear();
+    }
+    for ((hash, _), embedding) in pending.iter().zip(embeddings) {
+        cache.insert(*hash, embedding.vec);
+    }
+    Ok(hashes
+        .iter()
+        .map(|hash| {
+            cache
+                .get(hash)
+                .map_or(0.0, |vec| cosine(&query_vec, vec) as f32)
+        })
+        .collect())
+}
+
+// 0-10 from the model, scaled to 0-1 like the other scores. ids it skipped score 0
+async fn relevance_scores(
+    client: &LlmClient,
+    model: &ModelChoice,
+    query: &str,
+    sources: &[VivatechSource],
+) -> Result<Vec<f32>, RerankError> {
+    let mut input = format!("Query: {}\n\nItems:\n", query);
+    for source in sources {
+        input.push_str(&format!("- {}: {}\n", source.id, source.text_chunk));
+    }
+
+    let batch = client
+        .extract::<RelevanceBatch>(&model.model, RELEVANCE_INSTRUCTIONS, &input)
+        .await
+        .map_err(|e| RerankError::Scoring(e.to_string()))?;
+    let scores: HashMap<String, f32> = batch
+        .items
+        .into_iter()
+        .map(|item| (item.id, (item.score / 10.0).clamp(0.0, 1.0)))
+        .collect();
+    Ok(sources
+        .iter()
+        .map(|source| scores.get(&source.id).copied().unwrap_or(0.0))
+        .collect())
+}
+
+fn cosine(a: &[f64], b: &[f64]) -> f64 {
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f64]| v.iter().map(|x| x * x).sum::<f64>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot / norms
+    }
+}
+
+fn text_hash(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+// set up the reranker at startup, a missing key leaves results in upstream order
+pub fn init_reranker() {
+    let config = config::get();
+    let scorer = match config.rerank_mode {
+        RerankMode::Off => {
+            info!("Search reranking disabled via RERANK_MODE");
+            return;
+        }
+        RerankMode::Embedding => match config.openai_api_key.as_deref() {
+            Some(api_key) => Ok(Scorer::Embedding {
+                model: openai::Client::new(api_key).embedding_model(&config.rerank_embedding_model),
+                cache: Mutex::new(HashMap::new()),
+            }),
+            None => Err("embedding reranking needs OPENAI_API_KEY".to_string()),
+        },
+        RerankMode::Llm => scoring_model().and_then(|model| {
+            LlmClient::from_env(model.provider).map(|client| Scorer::Llm { client, model })
+        }),
+    };
+    match scorer {
+        Ok(scorer) => {
+            let reranker = Reranker {
+                scorer,
+                top_k: config.rerank_top_k,
+            };
+            info!("Search reranking enabled with {}", reranker);
+            let _ = RERANKER.set(reranker);
+        }
+        Err(e) => warn!("Search reranking unavailable: {}", e),
+    }
+}
+
+pub fn reranker() -> Option<&'static Reranker> {
+    RERANKER.get()
+}
+
+// sources unchanged while reranking is off
+pub async fn rerank(query: &str, sources: Vec<VivatechSource>) -> Vec<VivatechSource> {
+    match reranker() {
+        Some(reranker) => reranker.rerank(query, sources).await,
+        None => sources,
+    }
+}
@@ -0,0 +1,188 @@
+// retry policy honoring upstream rate-limit hints
+
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use reqwest::header::HeaderMap;
+
+use crate::config;
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    // policy from env with fallbacks
+    pub fn from_env() -> Self {
+        let max_attempts = config::env_var("RETRY_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|s| s.parse::<u32>().ok())
+            .unwrap_or(3)
+            .max(1);
+        let base_delay_ms = config::env_var("RETRY_BASE_DELAY_MS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(500);
+        let max_delay_secs = config::env_var("RETRY_MAX_DELAY_SECONDS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(30);
+
+        Self {
+            max_attempts,
+            base_delay: Duration::from_millis(base_delay_ms),
+            max_delay: Duration::from_secs(max_delay_secs),
+        }
+    }
+
+    // delay before the next attempt, None means give up
+    // a hint longer than max_delay is not worth holding the request for
+    pub fn next_delay(&self, attempt: u32, hint: Option<Duration>) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+
+        match hint {
+            Some(delay) if delay > self.max_delay => None,
+            Some(delay) => Some(delay + jitter(self.base_delay / 4)),
+            None => {
+                let exponent = attempt.saturating_sub(1).min(16);
+                let backoff = self.base_delay.saturating_mul(1 << exponent);
+                Some(backoff.min(self.max_delay) + jitter(self.base_delay / 2))
+            }
+        }
+    }
+}
+
+// small jitter without pulling in a rng crate
+fn jitter(max: Duration) -> Duration {
+    let max_ms = max.as_millis() as u64;
+    if max_ms == 0 {
+        return Duration::ZERO;
+    }
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos() as u64)
+        .unwrap_or(0);
+    Duration::from_millis(nanos % max_ms)
+}
+
+// read Retry-After or ratelimit reset headers
+pub fn retry_after_from_headers(headers: &HeaderMap) -> Option<Duration> {
+    if let Some(value) = headers.get("retry-after").and_then(|v| v.to_str().ok()) {
+        if let Some(delay) = parse_retry_after_value(value) {
+            return Some(delay);
+        }
+    }
+
+    // openai style: "1s", "6m0s", "120ms"
+    for name in ["x-ratelimit-reset-requests", "x-ratelimit-reset-tokens"] {
+        if let Some(value) = headers.get(name).and_then(|v| v.to_str().ok()) {
+            if let Some(delay) = parse_compound_duration(value) {
+                return Some(delay);
+            }
+        }
+    }
+
+    // ietf draft style: seconds until reset
+    headers
+        .get("ratelimit-reset")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+// Retry-After is either delta-seconds or an http date
+fn parse_retry_after_value(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    let delta = date.with_timezone(&Utc) - Utc::now();
+    Some(delta.to_std().unwrap_or(Duration::ZERO))
+}
+
+static COMPOUND_DURATION: OnceLock<Option<Regex>> = OnceLock::new();
+
+fn compound_duration_pattern() -> Option<&'static Regex> {
+    COMPOUND_DURATION
+        .get_or_init(|| Regex::new(r"(\d+(?:\.\d+)?)(ms|h|m|s)").ok())
+        .as_ref()
+}
+
+fn parse_compound_duration(value: &str) -> Option<Duration> {
+    let pattern = compound_duration_pattern()?;
+    let mut total = 0.0_f64;
+    let mut matched = false;
+
+    for captures in pattern.captures_iter(value) {
+        let amount = captures.get(1)?.as_str().parse::<f64>().ok()?;
+        total += match captures.get(2)?.as_str() {
+            "ms" => amount / 1000.0,
+            "s" => amount,
+            "m" => amount * 60.0,
+            "h" => amount * 3600.0,
+            _ => return None,
+        };
+        matched = true;
+    }
+
+    matched.then(|| Duration::from_secs_f64(total))
+}
+
+static RETRY_HINT: OnceLock<Option<Regex>> = OnceLock::new();
+
+fn retry_hint_pattern() -> Option<&'static Regex> {
+    RETRY_HINT
+        .get_or_init(|| {
+            Regex::new(r"(?i)(?:try again in|retry after)\s+([\d.]+)\s*(seconds?|ms|s)\b").ok()
+        })
+        .as_ref()
+}
+
+// providers without header access put the hint in the error text,
+// e.g. "Please try again in 20s" or "retry after 3 seconds"
+pub fn retry_after_from_message(message: &str) -> Option<Duration> {
+    let captures = retry_hint_pattern()?.captures(message)?;
+    let amount = captures.get(1)?.as_str().parse::<f64>().ok()?;
+
+    if captures.get(2)?.as_str().eq_ignore_ascii_case("ms") {
+        Some(Duration::from_secs_f64(amount / 1000.0))
+    } else {
+        Some(Duration::from_secs_f64(amount))
+    }
+}
+
+pub fn is_rate_limit_message(message: &str) -> bool {
+    let lower = message.to_lowercase();
+    lower.contains("429") || lower.contains("rate limit") || lower.contains("too many requests")
+}
+
+static SERVER_ERROR_STATUS: OnceLock<Option<Regex>> = OnceLock::new();
+
+fn server_error_status_pattern() -> Option<&'static Regex> {
+    SERVER_ERROR_STATUS
+        .get_or_init(|| Regex::new(r"\b(500|502|503|504|529)\b").ok())
+        .as_ref()
+}
+
+// 5xx and overload errors, e.g. "503 Service Unavailable" or anthropic's 529
+pub fn is_server_error_message(message: &str) -> bool {
+    let lower = message.to_lowercase();
+    server_error_status_pattern().is_some_and(|pattern| pattern.is_match(&lower))
+        || [
+            "server error",
+            "bad gateway",
+            "service unavailable",
+            "overloaded",
+        ]
+        .iter()
+        .any(|phrase| lower.contains(phrase))
+}
@@ -0,0 +1,200 @@
+// the http api: the planner routes behind api keys and the rate limiter, probes, share
+// links and the routes that bring their own credentials
+
+use std::net::SocketAddr;
+
+use axum::{
+    http::{header, HeaderValue, Method},
+    middleware,
+    routing::{delete, get, post},
+    Router,
+};
+use tokio::net::TcpListener;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::trace::TraceLayer;
+
+use crate::state::AppState;
+use crate::{
+    admin, auth, chat, export, graphql, handlers, jobs, openapi, probes, profiles, ratelimit,
+    recap, share, slack, telegram, telemetry, widget,
+};
+
+// setup http routes
+pub fn build_router(state: AppState) -> Router {
+    // the routes that run an agent, one token each. the other api routes only read
+    // what was planned and stay outside the limiter
+    let llm = Router::new()
+        .route("/generate-plan", post(handlers::generate_plan_handler))
+        .route("/generate-plans", post(handlers::generate_plans_handler))
+        .route("/compare-plans", post(handlers::compare_plans_handler))
+        .route(
+            "/generate-plan/stream",
+            post(handlers::generate_plan_stream_handler),
+        )
+        .route(
+            "/generate-plan/from-template",
+            post(handlers::generate_plan_from_template_handler),
+        )
+        .route(
+            "/plans/{plan_id}/refine",
+            post(handlers::refine_plan_handler),
+        )
+        .route(
+            "/plans/{plan_id}/steps/{step}/explain",
+            get(handlers::explain_step_handler),
+        )
+        .route("/chat", post(chat::chat_handler))
+        .route("/ws", get(chat::ws_handler))
+        .route("/graphql", post(graphql::graphql_handler))
+        .route("/graphql/ws", get(graphql::graphql_ws_handler))
+        .route("/users/{user_id}/recap", post(recap::recap_handler))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            ratelimit::limit_requests,
+        ));
+
+    // planner routes need an API key once API_KEYS is configured
+    let api = Router::new()
+        .route("/templates", get(handlers::list_templates_handler))
+        .route("/branding", get(handlers::branding_handler))
+        .route("/export/ical", post(handlers::export_ical_handler))
+        .route("/catalog/changes", get(handlers::catalog_changes_handler))
+        .route("/catalog/facets", get(handlers::catalog_facets_handler))
+        .route("/sessions/popular", get(handlers::popular_sessions_handler))
+        .route(
+            "/plans",
+            get(handlers::list_plans_handler).post(jobs::submit_plan_job_handler),
+        )
+        .route("/plans/{plan_id}", get(handlers::get_plan_handler))
+        // job ids share the segment, the router wants one parameter name per position
+        .route(
+            "/plans/{plan_id}/status",
+            get(jobs::plan_job_status_handler),
+        )
+        .route(
+            "/plans/{plan_id}/result",
+            get(jobs::plan_job_result_handler),
+        )
+        .route(
+            "/plans/{plan_id}/cancel",
+            post(jobs::cancel_plan_job_handler),
+        )
+        .route("/plans/{plan_id}/share", post(share::share_plan_handler))
+        .route(
+            "/plans/{plan_id}/feedback",
+            post(handlers::plan_feedback_handler),
+        )
+        .route(
+            "/plans/{plan_id}/export",
+            get(handlers::export_plan_handler),
+        )
+        .route(
+            "/plans/{plan_id}/sync/google-calendar",
+            post(handlers::google_calendar_sync_handler),
+        )
+        .route(
+            "/chat/{session_id}",
+            get(chat::get_chat_handler).delete(chat::clear_chat_handler),
+        )
+        .route("/mcp", post(handlers::mcp_handler))
+        .route("/profiles", post(profiles::upsert_profile_handler))
+        .route("/profiles/{user_id}", get(profiles::get_profile_handler))
+        .route(
+            "/users/{user_id}/conversations/export",
+            get(export::export_conversations_handler),
+        )
+        // the limiter sits inside the key check, so known keys get their own bucket
+        .merge(llm)
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_api_key,
+        ));
+
+    // probes, public share links, and routes with their own credentials
+    let router = Router::new()
+        .route("/health/tools", get(probes::tool_health_handler))
+        .route("/cache/stats", get(handlers::cache_stats_handler))
+        .route("/healthz", get(probes::healthz_handler))
+        .route("/readyz", get(probes::readyz_handler))
+        .route("/version", get(probes::version_handler))
+        .route("/openapi.json", get(openapi::openapi_handler))
+        .route("/docs", get(openapi::docs_handler))
+        .route("/graphql/playground", get(graphql::playground_handler))
+        .route("/metrics", get(telemetry::metrics_handler))
+        .route("/shared/{token}", get(share::shared_plan_handler))
+        .route("/widget/tokens", post(widget::widget_token_handler))
+        .route("/slack/events", post(slack::slack_events_handler))
+        .route(
+            "/telegram/webhook",
+            post(telegram::telegram_webhook_handler),
+        )
+        .merge(widget_router(state.clone()))
+        .route("/admin/abuse", get(admin::list_abuse_handler))
+        .route(
+            "/admin/abuse/{client}/lift",
+            post(admin::lift_abuse_handler),
+        )
+        .route("/admin/usage", get(admin::usage_report_handler))
+        .route("/admin/feedback", get(admin::feedback_report_handler))
+        .route(
+            "/admin/config",
+            get(admin::get_config_handler).patch(admin::update_config_handler),
+        )
+        .route("/admin/config/{name}", delete(admin::reset_config_handler))
+        .route("/admin/ingest", post(admin::ingest_handler))
+        .route(
+            "/admin/replay/{request_id}",
+            post(admin::admin_replay_handler),
+        )
+        .merge(api);
+
+    #[cfg(feature = "offline-catalog")]
+    let router = router.route("/admin/catalog/sync", post(admin::sync_catalog_handler));
+
+    // after every route so the matched path is known. the request id is set
+    // outermost so the trace span and the X-Request-Id response header see it
+    router
+        .route_layer(middleware::from_fn(telemetry::track_requests))
+        .layer(PropagateRequestIdLayer::new(telemetry::REQUEST_ID_HEADER))
+        .layer(TraceLayer::new_for_http().make_span_with(telemetry::request_span))
+        .layer(SetRequestIdLayer::new(
+            telemetry::REQUEST_ID_HEADER,
+            MakeRequestUuid,
+        ))
+        .with_state(state)
+}
+
+// routes callable from the browser with a widget token, CORS limited to the allowed origins
+fn widget_router(state: AppState) -> Router<AppState> {
+    let origins: Vec<HeaderValue> = widget::get_allowed_origins()
+        .iter()
+        .filter_map(|origin| origin.parse().ok())
+        .collect();
+    let cors = CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods([Method::POST])
+        .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE]);
+
+    // search only reads the catalog, planning runs the agent
+    let plan = Router::new()
+        .route("/widget/plan", post(widget::widget_plan_handler))
+        .route_layer(middleware::from_fn_with_state(
+            state,
+            ratelimit::limit_requests,
+        ));
+    Router::new()
+        .route("/widget/search", post(widget::widget_search_handler))
+        .merge(plan)
+        .layer(cors)
+}
+
+// with the peer address of every connection, the client address of keyless requests
+// when there is no X-Forwarded-For to go by
+pub async fn serve(listener: TcpListener, router: Router) -> std::io::Result<()> {
+    axum::serve(
+        listener,
+        router.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+}
@@ -0,0 +1,136 @@
+// upstream text chunks arrive with html, markdown markup and runs of whitespace that
+// cost tokens and confuse the model. they are reduced to plain lines here, and very
+// long ones cut at a sentence end, before the agent or any parser sees them
+
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::config;
+
+// a cut shorter than this share of the limit falls back to a word boundary
+const MIN_SENTENCE_CUT: f64 = 0.5;
+
+struct Patterns {
+    // script and style blocks and comments, content and all
+    hidden: Regex,
+    // tags that end a line
+    line_break: Regex,
+    tag: Regex,
+    entity: Regex,
+    image: Regex,
+    link: Regex,
+    // heading, quote and bullet markers at the start of a line
+    line_marker: Regex,
+    // **bold**, __bold__ and `code`
+    emphasis: Regex,
+    rule: Regex,
+}
+
+static PATTERNS: OnceLock<Option<Patterns>> = OnceLock::new();
+
+fn patterns() -> Option<&'static Patterns> {
+    PATTERNS
+        .get_or_init(|| {
+            Some(Patterns {
+                hidden: Regex::new(
+                    r"(?is)<script\b.*?</script\s*>|<style\b.*?</style\s*>|<!--.*?-->",
+                )
+                .ok()?,
+                line_break: Regex::new(r"(?i)<br\s*/?>|</(?:p|div|li|tr|h[1-6])\s*>").ok()?,
+                tag: Regex::new(r"</?[a-zA-Z][a-zA-Z0-9-]*(?:\s[^<>]*)?/?>").ok()?,
+                entity: Regex::new(r"&(#[0-9]{1,7}|#[xX][0-9a-fA-F]{1,6}|[a-zA-Z]{2,8});").ok()?,
+                image: Regex::new(r"!\[([^\]]*)\]\([^)]*\)").ok()?,
+                link: Regex::new(r"\[([^\]]+)\]\([^)]*\)").ok()?,
+                line_marker: Regex::new(r"^(?:#{1,6}\s+|>\s*|[-*+•]\s+)").ok()?,
+                emphasis: Regex::new(r"\*\*|__|`").ok()?,
+                rule: Regex::new(r"^(?:-{3,}|\*{3,}|_{3,})$").ok()?,
+            })
+        })
+        .as_ref()
+}
+
+fn decode_entity(entity: &str) -> Option<String> {
+    let decoded = match entity {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        "ndash" => '–',
+        "mdash" => '—',
+        "hellip" => '…',
+        "rsquo" | "lsquo" => '\'',
+        "rdquo" | "ldquo" => '"',
+        "eacute" => 'é',
+        "egrave" => 'è',
+        "agrave" => 'à',
+        "ccedil" => 'ç',
+        "euro" => '€',
+        numeric => {
+            let code = numeric.strip_prefix('#')?;
+            let code = match code.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => code.parse().ok()?,
+            };
+            char::from_u32(code)?
+        }
+    };
+    Some(decoded.to_string())
+}
+
+// plain text, one trimmed line per paragraph or list item, at most SOURCE_MAX_CHARS
+pub fn text_chunk(raw: &str) -> String {
+    let Some(patterns) = patterns() else {
+        return raw.to_string();
+    };
+    let text = patterns.hidden.replace_all(raw, "");
+    let text = patterns.line_break.replace_all(&text, "\n");
+    let text = patterns.tag.replace_all(&text, "");
+    let text = patterns
+        .entity
+        .replace_all(&text, |captures: &regex::Captures| {
+            decode_entity(&captures[1]).unwrap_or_else(|| captures[0].to_string())
+        });
+    let text = patterns.image.replace_all(&text, "$1");
+    let text = patterns.link.replace_all(&text, "$1");
+    let text = patterns.emphasis.replace_all(&text, "");
+
+    let lines: Vec<String> = text
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty() && !patterns.rule.is_match(line))
+        .map(|line| patterns.line_marker.replace(&line, "").into_owned())
+        .filter(|line| !line.is_empty())
+        .collect();
+    truncate(&lines.join("\n"), config::get().source_max_chars)
+}
+
+// cut at the last sentence end within `max_chars`, or the last word when that would
+// drop too much. 0 keeps everything
+pub fn truncate(text: &str, max_chars: usize) -> String {
+    if max_chars == 0 || text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let head: String = text.chars().take(max_chars).collect();
+    let sentence_end = head
+        .char_indices()
+        .filter(|(i, c)| {
+            matches!(c, '.' | '!' | '?' | '…')
+                && text[i + c.len_utf8()..]
+                    .chars()
+                    .next()
+                    .map_or(true, char::is_whitespace)
+        })
+        .map(|(i, c)| i + c.len_utf8())
+        .last();
+    let min_cut = (head.len() as f64 * MIN_SENTENCE_CUT) as usize;
+    match sentence_end {
+        Some(end) if end >= min_cut => head[..end].to_string(),
+        _ => {
+            let end = head.rfind(char::is_whitespace).unwrap_or(head.len());
+            format!("{}…", head[..end].trim_end())
+        }
+    }
+}
@@ -0,0 +1,157 @@
+// startup self-test against live dependencies
+
+use std::time::{Duration, Instant};
+
+use crate::agents::{build_text_agent, GenerationParams};
+use crate::llm::{LlmClient, ModelChoice};
+use crate::tools::{get_vivatech_api_url, make_api_request, parse_query_response};
+use crate::{config, http};
+use serde_json::json;
+use tracing::{error, info, warn};
+
+// self-test is on by default, opt out with STARTUP_SELF_TEST=false
+fn is_self_test_enabled() -> bool {
+    config::env_var("STARTUP_SELF_TEST")
+        .map(|v| !matches!(v.to_lowercase().as_str(), "false" | "0" | "no" | "off"))
+        .unwrap_or(true)
+}
+
+// strict mode refuses to start when a check fails
+fn is_self_test_strict() -> bool {
+    config::env_var("STARTUP_SELF_TEST_STRICT")
+        .map(|v| matches!(v.to_lowercase().as_str(), "true" | "1" | "yes" | "on"))
+        .unwrap_or(false)
+}
+
+// per-check timeout with fallback
+fn get_self_test_timeout() -> Duration {
+    let seconds = config::env_var("STARTUP_SELF_TEST_TIMEOUT_SECONDS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(10);
+    Duration::from_secs(seconds)
+}
+
+// query sent to the vivatech api
+fn get_self_test_query() -> String {
+    config::env_var("STARTUP_SELF_TEST_QUERY").unwrap_or_else(|_| "AI".to_string())
+}
+
+#[derive(Debug)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub ok: bool,
+    pub elapsed_ms: u128,
+    pub detail: String,
+}
+
+// run all checks, returns Err if strict mode and something failed
+pub async fn run_startup_self_test() -> Result<(), String> {
+    if !is_self_test_enabled() {
+        info!("Startup self-test disabled via STARTUP_SELF_TEST");
+        return Ok(());
+    }
+
+    let timeout = get_self_test_timeout();
+    info!(
+        timeout_secs = timeout.as_secs(),
+        "Running startup self-test"
+    );
+
+    let results = vec![
+        run_check("llm_completion", timeout, check_llm_completion()).await,
+        run_check("vivatech_query", timeout, check_vivatech_query()).await,
+    ];
+
+    for result in &results {
+        if result.ok {
+            info!(
+                check = result.name,
+                ok = result.ok,
+                elapsed_ms = result.elapsed_ms as u64,
+                detail = %result.detail,
+                "Self-test check passed"
+            );
+        } else {
+            error!(
+                check = result.name,
+                ok = result.ok,
+                elapsed_ms = result.elapsed_ms as u64,
+                detail = %result.detail,
+                "Self-test check failed"
+            );
+        }
+    }
+
+    let failed: Vec<&str> = results.iter().filter(|r| !r.ok).map(|r| r.name).collect();
+    if failed.is_empty() {
+        info!("Startup self-test passed");
+        return Ok(());
+    }
+
+    if is_self_test_strict() {
+        return Err(format!("Startup self-test failed: {}", failed.join(", ")));
+    }
+
+    warn!(
+        failed = %failed.join(", "),
+        "Startup self-test failed - continuing because STARTUP_SELF_TEST_STRICT is off"
+    );
+    Ok(())
+}
+
+async fn run_check<F>(name: &'static str, timeout: Duration, check: F) -> CheckResult
+where
+    F: std::future::Future<Output = Result<String, String>>,
+{
+    let started = Instant::now();
+    let outcome = tokio::time::timeout(timeout, check).await;
+    let elapsed_ms = started.elapsed().as_millis();
+
+    let (ok, detail) = match outcome {
+        Ok(Ok(detail)) => (true, detail),
+        Ok(Err(e)) => (false, e),
+        Err(_) => (false, format!("timed out after {}s", timeout.as_secs())),
+    };
+
+    CheckResult {
+        name,
+        ok,
+        elapsed_ms,
+        detail,
+    }
+}
+
+// one-token completion to verify key and model access on the configured provider
+async fn check_llm_completion() -> Result<String, String> {
+    let choice = ModelChoice::from_env();
+    let client = LlmClient::from_env(choice.provider)?;
+    let params = GenerationParams {
+        max_tokens: 1,
+        temperature: 0.0,
+        ..GenerationParams::new(&choice.model)
+    };
+    let agent = build_text_agent(&client, "Reply with the single word OK.", &params);
+
+    agent
+        .chat("ping", &[], 0)
+        .await
+        .map(|response| format!("{} responded ({} chars)", choice, response.len()))
+        .map_err(|e| e.to_string())
+}
+
+// tiny search against the upstream api
+async fn check_vivatech_query() -> Result<String, String> {
+    let client = http::api_client();
+    let api_url = get_vivatech_api_url().map_err(|e| e.to_string())?;
+    let request_body = json!({ "query": get_self_test_query() });
+
+    let response = make_api_request(&client, &api_url, &request_body)
+        .await
+        .map_err(|e| e.to_string())?;
+    let api_response = parse_query_response(response)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(format!("{} sources returned", api_response.sources.len()))
+}
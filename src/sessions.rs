@@ -0,0 +1,387 @@
+// chat sessions keyed by a client supplied session_id within the calling api key, kept
+// in a ConversationStore
+
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use rig::completion::Message;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::config;
+use crate::conversation_store::{self, ConversationStore, Exchange, StoredConversation};
+use crate::copy;
+use crate::generation::estimate_tokens;
+use crate::models::{TokenUsage, VivatechSource};
+use crate::tone::Tone;
+use crate::usage;
+
+pub const SUMMARY_INSTRUCTIONS: &str = "\
+    You compress the earlier part of a conference planning conversation into a short memory \
+    for the assistant that continues it. Keep every constraint the attendee stated: the days \
+    and hours they attend, their interests, sessions, speakers or companies they asked for or \
+    rejected, budget, accessibility or language needs, and what the current plan looks like. \
+    Fold the earlier memory in, newer statements win over older ones. Drop greetings and \
+    anything repeated. Answer with at most 12 short bullet points and nothing else.";
+
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ChatRequest {
+    pub session_id: String,
+    pub message: String,
+    #[serde(default)]
+    pub conference: Option<String>,
+    #[serde(default)]
+    pub user_id: Option<String>,
+    #[serde(default)]
+    pub tone: Option<Tone>,
+    // same as on plan requests
+    #[serde(default)]
+    pub cost_tags: BTreeMap<String, String>,
+}
+
+impl ChatRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        let session_id = self.session_id.trim();
+        if session_id.is_empty() || session_id.len() > 128 {
+            return Err(copy::error("error.invalid_session_id", &[]));
+        }
+        if self.message.trim().is_empty() {
+            return Err("message must not be empty".to_string());
+        }
+        usage::validate_cost_tags(&self.cost_tags)
+    }
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ChatResponse {
+    pub session_id: String,
+    pub reply: String,
+    // user/assistant exchanges remembered for this session
+    pub turns: usize,
+    pub usage: TokenUsage,
+    pub elapsed_ms: u64,
+}
+
+fn tokens(conversation: &StoredConversation) -> usize {
+    conversation
+        .memory
+        .as_deref()
+        .map(estimate_tokens)
+        .unwrap_or(0)
+        + conversation
+            .exchanges
+            .iter()
+            .map(|exchange| estimate_tokens(&exchange.message) + estimate_tokens(&exchange.reply))
+            .sum::<usize>()
+}
+
+// what the agent gets from earlier in the session
+pub struct SessionHistory {
+    pub memory: Option<String>,
+    pub messages: Vec<Message>,
+}
+
+impl SessionHistory {
+    // appended to the attendee context so it sits in the preamble, not the chat
+    pub fn memory_block(&self) -> String {
+        match &self.memory {
+            Some(memory) => format!(
+                "\nEarlier in this conversation (summarized):\n{}",
+                memory.trim()
+            ),
+            None => String::new(),
+        }
+    }
+}
+
+// the oldest exchanges of a session, to be folded into its memory
+pub struct Compaction {
+    // the store key, the summary finishes outside the request's api key scope
+    key: String,
+    revision: u64,
+    exchanges: usize,
+    prompt: String,
+}
+
+impl Compaction {
+    pub fn prompt(&self) -> &str {
+        &self.prompt
+    }
+}
+
+// a stored session for GET /chat/{session_id}, e.g. to restore a chat after a reload
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ChatTranscript {
+    pub session_id: String,
+    // summary of the exchanges no longer kept word for word
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<String>,
+    pub exchanges: Vec<TranscriptExchange>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct TranscriptExchange {
+    pub message: String,
+    pub reply: String,
+    // ids of the vivatech sources the tools returned for the reply
+    pub sources_used: Vec<String>,
+    pub at: DateTime<Utc>,
+}
+
+// chat sessions on top of a ConversationStore: trimming, summaries and expiry
+pub struct Conversations {
+    store: Arc<dyn ConversationStore>,
+    // sessions with a summary in flight on this instance
+    compacting: Mutex<HashSet<String>>,
+    // load, change and save of one instance never interleave. instances sharing a
+    // store can still race, the later save wins
+    writes: tokio::sync::Mutex<()>,
+    ttl: Duration,
+    // messages kept per session, oldest exchanges are dropped first
+    history_limit: usize,
+    // estimated tokens of memory and history that trigger a summary, 0 disables it
+    summary_threshold: usize,
+    // most recent exchanges always kept verbatim
+    summary_keep_exchanges: usize,
+}
+
+impl Conversations {
+    pub fn from_env() -> Self {
+        let ttl_secs = config::env_var("CHAT_SESSION_TTL_SECONDS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(3600);
+        let history_limit = config::env_var("CHAT_HISTORY_LIMIT")
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .unwrap_or(20)
+            .max(2);
+        let summary_threshold = config::env_var("CHAT_SUMMARY_TOKEN_THRESHOLD")
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .unwrap_or(1500);
+        let summary_keep_exchanges = config::env_var("CHAT_SUMMARY_KEEP_EXCHANGES")
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .unwrap_or(2)
+            .max(1);
+
+        Self {
+            store: conversation_store::from_env(),
+            compacting: Mutex::new(HashSet::new()),
+            writes: tokio::sync::Mutex::new(()),
+            ttl: Duration::from_secs(ttl_secs),
+            history_limit,
+            summary_threshold,
+            summary_keep_exchanges,
+        }
+    }
+
+    // an unreachable store reads as a new session
+    async fn load(&self, key: &str) -> Option<StoredConversation> {
+        match self.store.load(key).await {
+            Ok(conversation) => conversation,
+            Err(e) => {
+                warn!(
+                    "Failed to load chat session {} from {}: {}",
+                    key,
+                    self.store.name(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    // a failed save costs the session its history, not the reply
+    async fn save(&self, key: &str, conversation: &StoredConversation) {
+        if let Err(e) = self.store.save(key, conversation, self.ttl).await {
+            tracing::error!(
+                "Failed to save chat session {} to {}: {}",
+                key,
+                self.store.name(),
+                e
+            );
+        }
+    }
+
+    // prior turns for the agent, expired sessions start over
+    pub async fn history(&self, session_id: &str) -> SessionHistory {
+        match self.load(&store_key(session_id)).await {
+            Some(conversation) => SessionHistory {
+                memory: conversation.memory,
+                messages: conversation
+                    .exchanges
+                    .iter()
+                    .flat_map(|exchange| {
+                        [
+                            Message::user(exchange.message.as_str()),
+                            Message::assistant(exchange.reply.as_str()),
+                        ]
+                    })
+                    .collect(),
+            },
+            None => SessionHistory {
+                memory: None,
+                messages: Vec::new(),
+            },
+        }
+    }
+
+    pub async fn transcript(&self, session_id: &str) -> Option<ChatTranscript> {
+        let conversation = self.load(&store_key(session_id)).await?;
+        Some(ChatTranscript {
+            session_id: session_id.to_string(),
+            memory: conversation.memory,
+            exchanges: conversation
+                .exchanges
+                .into_iter()
+                .map(|exchange| TranscriptExchange {
+                    message: exchange.message,
+                    reply: exchange.reply,
+                    sources_used: exchange
+                        .sources
+                        .into_iter()
+                        .map(|source| source.id)
+                        .collect(),
+                    at: exchange.at,
+                })
+                .collect(),
+        })
+    }
+
+    // store one exchange with the sources behind the reply, returns the number of
+    // exchanges in the session
+    pub async fn record(
+        &self,
+        session_id: &str,
+        message: &str,
+        reply: &str,
+        sources: Vec<VivatechSource>,
+    ) -> usize {
+        let key = store_key(session_id);
+        let _write = self.writes.lock().await;
+        let mut conversation = self.load(&key).await.unwrap_or_default();
+        conversation.exchanges.push(Exchange {
+            message: message.to_string(),
+            reply: reply.to_string(),
+            sources,
+            at: Utc::now(),
+        });
+
+        // whole exchanges so history never starts with an assistant reply
+        let excess = conversation
+            .exchanges
+            .len()
+            .saturating_sub(self.history_limit / 2);
+        if excess > 0 {
+            conversation.exchanges.drain(..excess);
+            conversation.revision += 1;
+        }
+
+        self.save(&key, &conversation).await;
+        conversation.exchanges.len()
+    }
+
+    // once a session passes the token threshold, everything but the latest exchanges
+    // is handed out for summarizing. one compaction per session at a time
+    pub async fn pending_compaction(&self, session_id: &str) -> Option<Compaction> {
+        if self.summary_threshold == 0 {
+            return None;
+        }
+        let key = store_key(session_id);
+        let conversation = self.load(&key).await?;
+        if conversation.exchanges.len() <= self.summary_keep_exchanges
+            || tokens(&conversation) <= self.summary_threshold
+        {
+            return None;
+        }
+        let mut compacting = self.compacting.lock().unwrap_or_else(|e| e.into_inner());
+        if !compacting.insert(key.clone()) {
+            return None;
+        }
+
+        let exchanges = conversation.exchanges.len() - self.summary_keep_exchanges;
+        let mut prompt = String::new();
+        if let Some(memory) = &conversation.memory {
+            prompt.push_str(&format!("Earlier memory:\n{}\n\n", memory.trim()));
+        }
+        prompt.push_str("Conversation:\n");
+        for exchange in &conversation.exchanges[..exchanges] {
+            prompt.push_str(&format!(
+                "Attendee: {}\nAssistant: {}\n",
+                exchange.message.trim(),
+                exchange.reply.trim()
+            ));
+        }
+
+        Some(Compaction {
+            key,
+            revision: conversation.revision,
+            exchanges,
+            prompt,
+        })
+    }
+
+    // swap the summarized exchanges for the new memory. None, or a session trimmed
+    // or cleared in the meantime, leaves the history as it is
+    pub async fn finish_compaction(&self, compaction: Compaction, memory: Option<String>) -> bool {
+        self.compacting
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&compaction.key);
+        let Some(memory) = memory.filter(|memory| !memory.trim().is_empty()) else {
+            return false;
+        };
+
+        let _write = self.writes.lock().await;
+        let Some(mut conversation) = self.load(&compaction.key).await else {
+            return false;
+        };
+        if conversation.revision != compaction.revision
+            || conversation.exchanges.len() < compaction.exchanges
+        {
+            return false;
+        }
+        conversation.exchanges.drain(..compaction.exchanges);
+        conversation.memory = Some(memory);
+        conversation.revision += 1;
+        self.save(&compaction.key, &conversation).await;
+        true
+    }
+
+    pub async fn clear(&self, session_id: &str) -> bool {
+        let _write = self.writes.lock().await;
+        match self.store.delete(&store_key(session_id)).await {
+            Ok(deleted) => deleted,
+            Err(e) => {
+                tracing::error!(
+                    "Failed to delete chat session {} from {}: {}",
+                    session_id,
+                    self.store.name(),
+                    e
+                );
+                false
+            }
+        }
+    }
+}
+
+// session ids are the client's, so two api keys sending the same one get separate
+// sessions. the key name is hashed, which keeps the separator out of the prefix
+fn store_key(session_id: &str) -> String {
+    match usage::current_api_key() {
+        Some(key) => {
+            let owner: String = Sha256::digest(key.as_bytes())
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            format!("{}:{}", owner, session_id)
+        }
+        None => format!("open:{}", session_id),
+    }
+}
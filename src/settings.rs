@@ -0,0 +1,178 @@
+// runtime settings changed through /admin/config without a redeploy. the overrides
+// are kept in the plan database and laid over the environment values at startup,
+// every change swaps the shared config::runtime() copy
+
+use std::collections::BTreeMap;
+
+use crate::config::{self, RuntimeSettings};
+use crate::copy;
+use crate::error::AppError;
+use crate::plans::{PlanStore, PlanStoreError};
+use crate::prompts::{self, PromptKind, PromptTemplate};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tracing::{info, warn};
+
+const PROMPT_TEMPLATES: &str = "prompt_templates";
+
+#[derive(Debug, thiserror::Error)]
+pub enum SettingsError {
+    #[error("unknown setting '{0}'")]
+    Unknown(String),
+    #[error("invalid settings: {0}")]
+    Invalid(String),
+    #[error("'{0}' is not overridden")]
+    NotOverridden(String),
+    #[error(transparent)]
+    Store(#[from] PlanStoreError),
+}
+
+impl From<SettingsError> for AppError {
+    fn from(e: SettingsError) -> Self {
+        match e {
+            SettingsError::Unknown(_) | SettingsError::Invalid(_) => {
+                AppError::Validation(e.to_string())
+            }
+            SettingsError::NotOverridden(_) => AppError::NotFound(e.to_string()),
+            SettingsError::Store(e) => {
+                tracing::error!("Failed to persist runtime settings: {}", e);
+                AppError::Internal(copy::error("error.settings_store_failed", &[]))
+            }
+        }
+    }
+}
+
+// what GET /admin/config answers
+#[derive(Debug, Serialize)]
+pub struct SettingsView {
+    pub settings: RuntimeSettings,
+    // settings that differ from the environment until reset
+    pub overridden: Vec<String>,
+    // every template in use, with where it came from
+    pub prompt_templates: BTreeMap<&'static str, PromptTemplate>,
+}
+
+pub fn view(store: &PlanStore) -> Result<SettingsView, SettingsError> {
+    let templates = prompts::current();
+    Ok(SettingsView {
+        settings: (*config::runtime()).clone(),
+        overridden: store.setting_overrides()?.keys().cloned().collect(),
+        prompt_templates: PromptKind::ALL
+            .into_iter()
+            .map(|kind| (kind.name(), templates.get(kind).clone()))
+            .collect(),
+    })
+}
+
+// the environment values with `overrides` on top, checked like request overrides
+fn with_overrides(overrides: &Map<String, Value>) -> Result<RuntimeSettings, SettingsError> {
+    let defaults = RuntimeSettings::from_config(config::get());
+    let Ok(Value::Object(mut settings)) = serde_json::to_value(defaults) else {
+        unreachable!("runtime settings serialize to an object");
+    };
+    for (name, value) in overrides {
+        if !settings.contains_key(name) {
+            return Err(SettingsError::Unknown(name.clone()));
+        }
+        settings.insert(name.clone(), value.clone());
+    }
+    let settings: RuntimeSettings = serde_json::from_value(Value::Object(settings))
+        .map_err(|e| SettingsError::Invalid(e.to_string()))?;
+    settings.validate().map_err(SettingsError::Invalid)?;
+    if let Some(name) = settings.prompt_templates.keys().find(|name| {
+        !PromptKind::ALL
+            .iter()
+            .any(|kind| kind.name() == name.as_str())
+    }) {
+        return Err(SettingsError::Invalid(format!(
+            "unknown prompt template '{}'",
+            name
+        )));
+    }
+    Ok(settings)
+}
+
+// stored overrides that no longer apply, e.g. a model whose key was removed, are
+// logged and the environment values are used instead
+pub fn load(store: &PlanStore) {
+    let overrides = match store.setting_overrides() {
+        Ok(overrides) if overrides.is_empty() => return,
+        Ok(overrides) => overrides,
+        Err(e) => {
+            warn!("Runtime settings unavailable, using the environment: {}", e);
+            return;
+        }
+    };
+    match with_overrides(&overrides) {
+        Ok(settings) => {
+            info!(
+                "Runtime settings overridden: {}",
+                overrides.keys().cloned().collect::<Vec<_>>().join(", ")
+            );
+            config::set_runtime(settings);
+        }
+        Err(e) => warn!("Ignoring stored runtime settings: {}", e),
+    }
+}
+
+// applies `changes` on top of the stored overrides. prompt templates are merged one
+// by one and an empty template drops its override. nothing is stored unless the
+// result is valid
+pub async fn update(
+    store: &PlanStore,
+    changes: Map<String, Value>,
+) -> Result<RuntimeSettings, SettingsError> {
+    let mut overrides = store.setting_overrides()?;
+    let mut changed = Map::new();
+    for (name, value) in changes {
+        let value = match (name.as_str(), value) {
+            (PROMPT_TEMPLATES, Value::Object(templates)) => {
+                let mut merged = match overrides.remove(PROMPT_TEMPLATES) {
+                    Some(Value::Object(current)) => current,
+                    _ => Map::new(),
+                };
+                for (template, text) in templates {
+                    let empty =
+                        text.is_null() || text.as_str().is_some_and(|t| t.trim().is_empty());
+                    if empty {
+                        merged.remove(&template);
+                    } else {
+                        merged.insert(template, text);
+                    }
+                }
+                Value::Object(merged)
+            }
+            (_, value) => value,
+        };
+        overrides.insert(name.clone(), value.clone());
+        changed.insert(name, value);
+    }
+
+    let settings = with_overrides(&overrides)?;
+    store.save_setting_overrides(&changed)?;
+    info!(
+        "Admin changed runtime settings: {}",
+        changed.keys().cloned().collect::<Vec<_>>().join(", ")
+    );
+    apply(settings.clone()).await;
+    Ok(settings)
+}
+
+// back to the environment value
+pub async fn reset(store: &PlanStore, name: &str) -> Result<RuntimeSettings, SettingsError> {
+    let mut overrides = store.setting_overrides()?;
+    if overrides.remove(name).is_none() {
+        return Err(SettingsError::NotOverridden(name.to_string()));
+    }
+    let settings = with_overrides(&overrides)?;
+    store.delete_setting_override(name)?;
+    info!("Admin reset runtime setting {}", name);
+    apply(settings.clone()).await;
+    Ok(settings)
+}
+
+async fn apply(settings: RuntimeSettings) {
+    config::set_runtime(settings);
+    // admin templates sit on top of the other sources
+    prompts::reload().await;
+}
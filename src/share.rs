@@ -0,0 +1,173 @@
+// public read-only links to stored plans
+
+use std::time::Instant;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use rand::RngCore;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::error::AppError;
+use crate::plans::StoredPlan;
+use crate::render::{self, FormatQuery, PlanDocument};
+use crate::reply::plan_error;
+use crate::state::AppState;
+use crate::{config, copy, footer, placeholders, usage};
+
+// keeps the expiry within what chrono can represent
+const MAX_SHARE_SECONDS: u64 = 10 * 365 * 24 * 60 * 60;
+
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+pub struct ShareRequest {
+    // link never expires when unset, capped at SHARE_MAX_TTL_SECONDS when that is set
+    #[serde(default)]
+    pub expires_in_seconds: Option<u64>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ShareResponse {
+    pub token: String,
+    pub url: String,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+// what a shared link shows, no user id, request or sources
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SharedPlan {
+    pub conference: String,
+    pub objective: String,
+    pub plan: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl SharedPlan {
+    pub fn new(stored: StoredPlan, expires_at: Option<DateTime<Utc>>) -> Self {
+        Self {
+            conference: stored.conference,
+            objective: stored.objective,
+            plan: stored.plan,
+            created_at: stored.created_at,
+            expires_at,
+        }
+    }
+}
+
+// 256 random bits, base64url
+pub fn new_share_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+// absolute when PUBLIC_BASE_URL is set, e.g. https://planner.example.com
+pub fn share_url(token: &str) -> String {
+    let base = &config::get().public_base_url;
+    format!("{}/shared/{}", base.trim_end_matches('/'), token)
+}
+
+pub fn expiry_for(request: &ShareRequest) -> Option<DateTime<Utc>> {
+    let seconds = match (
+        request.expires_in_seconds,
+        config::get().share_max_ttl_seconds,
+    ) {
+        (Some(requested), Some(max)) => requested.min(max),
+        (Some(requested), None) => requested,
+        (None, max) => max?,
+    };
+    Some(Utc::now() + Duration::seconds(seconds.min(MAX_SHARE_SECONDS) as i64))
+}
+
+// unguessable read-only link to a stored plan, optionally expiring
+pub async fn share_plan_handler(
+    State(state): State<AppState>,
+    Path(plan_id): Path<String>,
+    request: Option<Json<ShareRequest>>,
+) -> Response {
+    let started = Instant::now();
+    let request = request.map(|Json(request)| request).unwrap_or_default();
+    let token = new_share_token();
+    let expires_at = expiry_for(&request);
+
+    let owner = usage::current_api_key();
+    match state
+        .plans
+        .share(&plan_id, owner.as_deref(), &token, expires_at)
+    {
+        Ok(true) => {
+            info!("Shared plan {} until {:?}", plan_id, expires_at);
+            (
+                StatusCode::CREATED,
+                Json(ShareResponse {
+                    url: share_url(&token),
+                    token,
+                    expires_at,
+                }),
+            )
+                .into_response()
+        }
+        Ok(false) => plan_error(
+            AppError::NotFound(copy::error("error.plan_not_found", &[])),
+            started,
+        )
+        .into_response(),
+        Err(e) => {
+            tracing::error!("Failed to share plan {}: {}", plan_id, e);
+            plan_error(
+                AppError::Internal(copy::error("error.plan_share_failed", &[])),
+                started,
+            )
+            .into_response()
+        }
+    }
+}
+
+// public view of a shared plan, html unless another format is asked for
+pub async fn shared_plan_handler(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    Query(query): Query<FormatQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(renderer) = render::negotiate(query.format.as_deref(), &headers, "html") else {
+        return render::not_acceptable();
+    };
+    let document = match state.plans.shared(&token) {
+        Ok(Some((mut stored, expires_at))) => {
+            stored.plan = placeholders::resolve_plan(
+                &state,
+                &stored.conference,
+                &stored.plan,
+                &stored.sources,
+            );
+            footer::append_to_stored(&state, &mut stored);
+            // the json format is the SharedPlan, which leaves out the user, request and sources
+            let document =
+                PlanDocument::new(&state, &stored, serde_json::Value::Null).with_expiry(expires_at);
+            let shared = SharedPlan::new(stored, expires_at);
+            PlanDocument {
+                data: serde_json::to_value(&shared).unwrap_or_default(),
+                ..document
+            }
+        }
+        Ok(None) => {
+            return AppError::NotFound(copy::error("error.shared_plan_not_found", &[]))
+                .into_response()
+        }
+        Err(e) => {
+            tracing::error!("Failed to load shared plan: {}", e);
+            return AppError::Internal(copy::error("error.plan_load_failed", &[])).into_response();
+        }
+    };
+
+    render::respond(renderer, &document)
+}
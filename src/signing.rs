@@ -0,0 +1,155 @@
+// hmac request signing for upstreams that require it, with replay protection
+
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use reqwest::header::{HeaderMap, DATE};
+use serde::Deserialize;
+use sha2::Sha256;
+use tracing::{info, warn};
+
+use crate::config;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const KEY_ID_HEADER: &str = "X-Signature-Key-Id";
+pub const TIMESTAMP_HEADER: &str = "X-Signature-Timestamp";
+pub const NONCE_HEADER: &str = "X-Signature-Nonce";
+pub const SIGNATURE_HEADER: &str = "X-Signature";
+
+fn default_max_skew_seconds() -> i64 {
+    30
+}
+
+// one entry of UPSTREAM_SIGNING_KEYS, no Debug so the secret never ends up in logs
+#[derive(Clone, Deserialize)]
+pub struct UpstreamSigning {
+    // requests to urls starting with this are signed with this key
+    #[serde(default)]
+    pub url: String,
+    pub key_id: String,
+    pub secret: String,
+    // drift tolerated before we correct our timestamps
+    #[serde(default = "default_max_skew_seconds")]
+    pub max_skew_seconds: i64,
+    // seconds to add to our clock, learned from this upstream's Date headers.
+    // shared by the clones for_url hands out, so each entry keeps its own
+    #[serde(skip)]
+    clock_offset: Arc<AtomicI64>,
+}
+
+pub struct SignedHeaders {
+    pub key_id: String,
+    pub timestamp: String,
+    pub nonce: String,
+    pub signature: String,
+}
+
+impl UpstreamSigning {
+    // longest matching UPSTREAM_SIGNING_KEYS prefix, then VIVATECH_SIGNING_* for everything else
+    pub fn for_url(url: &str) -> Option<Self> {
+        let config = config::get();
+        config
+            .upstream_signing
+            .iter()
+            .filter(|signing| !signing.url.is_empty() && url.starts_with(&signing.url))
+            .max_by_key(|signing| signing.url.len())
+            .or(config.default_signing.as_ref())
+            .cloned()
+    }
+
+    // UPSTREAM_SIGNING_KEYS, read once into the app config
+    pub fn configured() -> Vec<Self> {
+        config::env_var("UPSTREAM_SIGNING_KEYS")
+            .ok()
+            .and_then(
+                |raw| match serde_json::from_str::<Vec<UpstreamSigning>>(&raw) {
+                    Ok(keys) => Some(keys),
+                    Err(e) => {
+                        warn!("Ignoring invalid UPSTREAM_SIGNING_KEYS: {}", e);
+                        None
+                    }
+                },
+            )
+            .unwrap_or_default()
+    }
+
+    pub fn from_default_env() -> Option<Self> {
+        let secret = config::env_var("VIVATECH_SIGNING_SECRET").ok()?;
+        if secret.is_empty() {
+            return None;
+        }
+        Some(Self {
+            url: String::new(),
+            key_id: config::env_var("VIVATECH_SIGNING_KEY_ID").unwrap_or_else(|_| "default".into()),
+            secret,
+            max_skew_seconds: config::env_var("VIVATECH_SIGNING_MAX_SKEW_SECONDS")
+                .ok()
+                .and_then(|s| s.parse::<i64>().ok())
+                .unwrap_or_else(default_max_skew_seconds),
+            clock_offset: Arc::default(),
+        })
+    }
+
+    // hex hmac-sha256 over "{timestamp}.{nonce}.{body}", fresh nonce on every attempt
+    pub fn sign(&self, body: &[u8]) -> SignedHeaders {
+        let timestamp =
+            (Utc::now().timestamp() + self.clock_offset.load(Ordering::Relaxed)).to_string();
+        let nonce = new_nonce();
+
+        let mut mac = HmacSha256::new_from_slice(self.secret.as_bytes())
+            .expect("hmac accepts keys of any length");
+        mac.update(timestamp.as_bytes());
+        mac.update(b".");
+        mac.update(nonce.as_bytes());
+        mac.update(b".");
+        mac.update(body);
+
+        SignedHeaders {
+            key_id: self.key_id.clone(),
+            timestamp,
+            nonce,
+            signature: to_hex(&mac.finalize().into_bytes()),
+        }
+    }
+
+    // compare our clock with the upstream's Date header, true when the offset changed
+    pub fn observe_server_clock(&self, headers: &HeaderMap) -> bool {
+        let Some(server_time) = headers
+            .get(DATE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| DateTime::parse_from_rfc2822(v).ok())
+        else {
+            return false;
+        };
+
+        let drift = server_time.timestamp() - Utc::now().timestamp();
+        let current = self.clock_offset.load(Ordering::Relaxed);
+        // Date only has second precision, small corrections are noise
+        if (drift - current).abs() <= self.max_skew_seconds {
+            return false;
+        }
+
+        self.clock_offset.store(drift, Ordering::Relaxed);
+        info!(
+            "Adjusted signing clock offset for key {} to {}s from upstream Date header",
+            self.key_id, drift
+        );
+        true
+    }
+}
+
+fn new_nonce() -> String {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
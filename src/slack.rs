@@ -0,0 +1,325 @@
+// slack slash commands, e.g. "/vivaplan find AI sessions". requests are verified with
+// the app's signing secret and acknowledged at once, the plan follows as block kit on
+// the command's response_url when it is ready
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::Sha256;
+
+use crate::config;
+use crate::copy;
+use crate::error;
+use crate::http;
+use crate::models::{GeneratePlanRequest, GeneratePlanResponse};
+use crate::pipeline::run_plan_request;
+use crate::state::AppState;
+use crate::telemetry;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// slack's own replay window
+const MAX_REQUEST_AGE_SECONDS: i64 = 300;
+
+// section text is capped at 3000 characters
+const MAX_SECTION_CHARS: usize = 2900;
+
+// header text is capped at 150 characters
+const MAX_HEADER_CHARS: usize = 150;
+
+// response urls are only ever on this host, anything else is not slack
+const RESPONSE_URL_PREFIX: &str = "https://hooks.slack.com/";
+
+#[derive(Debug, thiserror::Error)]
+pub enum SlackError {
+    #[error("the slack integration is disabled")]
+    Disabled,
+    #[error("missing slack signature")]
+    MissingSignature,
+    #[error("stale slack request")]
+    Stale,
+    #[error("invalid slack signature")]
+    BadSignature,
+    #[error("malformed slack request: {0}")]
+    Malformed(String),
+    #[error("response url is not a slack url")]
+    ForeignResponseUrl,
+    #[error("failed to post to slack: {0}")]
+    Post(#[from] reqwest::Error),
+}
+
+impl IntoResponse for SlackError {
+    fn into_response(self) -> Response {
+        let (status, code) = match self {
+            SlackError::Disabled => (StatusCode::NOT_FOUND, "slack_disabled"),
+            SlackError::MissingSignature | SlackError::Stale | SlackError::BadSignature => {
+                (StatusCode::UNAUTHORIZED, "invalid_signature")
+            }
+            SlackError::Malformed(_) | SlackError::ForeignResponseUrl => {
+                (StatusCode::BAD_REQUEST, "validation_error")
+            }
+            SlackError::Post(_) => (StatusCode::BAD_GATEWAY, "upstream_error"),
+        };
+        error::respond(status, error::envelope(code, self.to_string()))
+    }
+}
+
+// the form fields of a slash command we use
+#[derive(Debug, Deserialize)]
+pub struct SlashCommand {
+    // e.g. "/vivaplan"
+    pub command: String,
+    #[serde(default)]
+    pub text: String,
+    pub user_id: String,
+    #[serde(default)]
+    pub team_id: String,
+    pub response_url: String,
+}
+
+pub enum SlackEvent {
+    Command(SlashCommand),
+    // sent once when the events url is saved in the app settings
+    UrlVerification { challenge: String },
+    // other events api callbacks, acknowledged and ignored
+    Other,
+}
+
+// v0 signature: hex hmac-sha256 of "v0:{timestamp}:{body}" with the signing secret
+pub fn verify(headers: &HeaderMap, body: &[u8]) -> Result<(), SlackError> {
+    let secret = config::get()
+        .slack_signing_secret
+        .as_deref()
+        .ok_or(SlackError::Disabled)?;
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    let (Some(timestamp), Some(signature)) = (
+        header("x-slack-request-timestamp"),
+        header("x-slack-signature"),
+    ) else {
+        return Err(SlackError::MissingSignature);
+    };
+
+    let sent_at = timestamp
+        .parse::<i64>()
+        .map_err(|_| SlackError::MissingSignature)?;
+    if (Utc::now().timestamp() - sent_at).abs() > MAX_REQUEST_AGE_SECONDS {
+        return Err(SlackError::Stale);
+    }
+    let signature = signature
+        .strip_prefix("v0=")
+        .and_then(from_hex)
+        .ok_or(SlackError::BadSignature)?;
+
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("hmac accepts keys of any length");
+    mac.update(b"v0:");
+    mac.update(timestamp.as_bytes());
+    mac.update(b":");
+    mac.update(body);
+    mac.verify_slice(&signature)
+        .map_err(|_| SlackError::BadSignature)
+}
+
+// slash commands are form encoded, events api callbacks are json
+pub fn parse(headers: &HeaderMap, body: &[u8]) -> Result<SlackEvent, SlackError> {
+    let is_json = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("application/json"));
+    if !is_json {
+        return serde_urlencoded::from_bytes(body)
+            .map(SlackEvent::Command)
+            .map_err(|e| SlackError::Malformed(e.to_string()));
+    }
+
+    let event: Value =
+        serde_json::from_slice(body).map_err(|e| SlackError::Malformed(e.to_string()))?;
+    match event["type"].as_str() {
+        Some("url_verification") => Ok(SlackEvent::UrlVerification {
+            challenge: event["challenge"].as_str().unwrap_or_default().to_string(),
+        }),
+        _ => Ok(SlackEvent::Other),
+    }
+}
+
+// the immediate reply, only the caller sees it
+pub fn acknowledgement(objective: &str) -> Value {
+    ephemeral(&copy::render(
+        "slack.planning",
+        None,
+        &[("objective", objective)],
+    ))
+}
+
+pub fn usage(command: &str) -> Value {
+    ephemeral(&copy::render("slack.usage", None, &[("command", command)]))
+}
+
+// a finished plan goes to the whole channel, rejections and errors only to the caller
+pub fn plan_message(objective: &str, response: &GeneratePlanResponse) -> Value {
+    match response {
+        GeneratePlanResponse::Success {
+            plan,
+            plan_id,
+            model,
+            ..
+        } => {
+            let mut blocks = vec![json!({
+                "type": "header",
+                "text": { "type": "plain_text", "text": truncate(objective, MAX_HEADER_CHARS) },
+            })];
+            blocks.extend(sections(&to_mrkdwn(plan)).into_iter().map(
+                |text| json!({ "type": "section", "text": { "type": "mrkdwn", "text": text } }),
+            ));
+            let context = match plan_id {
+                Some(plan_id) => format!("{} · {}", plan_id, model),
+                None => model.clone(),
+            };
+            blocks.push(json!({
+                "type": "context",
+                "elements": [{ "type": "mrkdwn", "text": escape(&context) }],
+            }));
+            json!({
+                "response_type": "in_channel",
+                "replace_original": false,
+                // notifications and clients without block support
+                "text": truncate(plan, MAX_SECTION_CHARS),
+                "blocks": blocks,
+            })
+        }
+        GeneratePlanResponse::Duplicate { hint, .. } => ephemeral(hint),
+        GeneratePlanResponse::Error { error, .. } => ephemeral(&copy::render(
+            "slack.failed",
+            None,
+            &[("error", error.message.as_str())],
+        )),
+    }
+}
+
+pub async fn post_response(response_url: &str, message: &Value) -> Result<(), SlackError> {
+    if !response_url.starts_with(RESPONSE_URL_PREFIX) {
+        return Err(SlackError::ForeignResponseUrl);
+    }
+    http::client()
+        .post(response_url)
+        .json(message)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+fn ephemeral(text: &str) -> Value {
+    json!({ "response_type": "ephemeral", "text": text })
+}
+
+// slack mrkdwn: single asterisks for bold, no headings, &, < and > escaped
+fn to_mrkdwn(plan: &str) -> String {
+    plan.trim()
+        .lines()
+        .map(|line| {
+            let line = escape(line).replace("**", "*").replace("__", "_");
+            let heading = line.trim_start_matches('#');
+            if heading.len() < line.len() {
+                format!("*{}*", heading.trim().trim_matches('*'))
+            } else if let Some(item) = line.trim_start().strip_prefix("- ") {
+                format!("• {}", item)
+            } else {
+                line
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// whole lines per section while they fit
+fn sections(text: &str) -> Vec<String> {
+    let mut sections = Vec::new();
+    let mut current = String::new();
+    for line in text.lines() {
+        let line = truncate(line, MAX_SECTION_CHARS);
+        let full = current.chars().count() + line.chars().count() + 1 > MAX_SECTION_CHARS;
+        if !current.is_empty() && full {
+            sections.push(std::mem::take(&mut current).trim_end().to_string());
+        }
+        current.push_str(&line);
+        current.push('\n');
+    }
+    if !current.trim().is_empty() {
+        sections.push(current.trim_end().to_string());
+    }
+    sections
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let truncated: String = text.chars().take(max_chars - 1).collect();
+    format!("{}…", truncated.trim_end())
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// slack slash commands, verified with SLACK_SIGNING_SECRET instead of an api key. slack
+// wants an answer within three seconds, so the plan is posted to the response_url later
+pub async fn slack_events_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if let Err(e) = verify(&headers, &body) {
+        tracing::warn!("Rejected slack request: {}", e);
+        return e.into_response();
+    }
+    let command = match parse(&headers, &body) {
+        Ok(SlackEvent::Command(command)) => command,
+        Ok(SlackEvent::UrlVerification { challenge }) => {
+            return Json(json!({ "challenge": challenge })).into_response()
+        }
+        Ok(SlackEvent::Other) => return StatusCode::OK.into_response(),
+        Err(e) => return e.into_response(),
+    };
+
+    let objective = command.text.trim().to_string();
+    if objective.is_empty() || objective.eq_ignore_ascii_case("help") {
+        return Json(usage(&command.command)).into_response();
+    }
+
+    let client = format!("slack:{}:{}", command.team_id, command.user_id);
+    let request_id = telemetry::current_request_id();
+    let acknowledgement = acknowledgement(&objective);
+    let run = async move {
+        let payload = GeneratePlanRequest::simple(objective.clone(), None);
+        let reply = run_plan_request(&state, &client, payload).await;
+        let message = plan_message(&objective, &reply.body);
+        if let Err(e) = post_response(&command.response_url, &message).await {
+            tracing::error!("Failed to post plan to slack: {}", e);
+        }
+    };
+    tokio::spawn(telemetry::with_request_id(request_id, run));
+
+    Json(acknowledgement).into_response()
+}
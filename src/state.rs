@@ -0,0 +1,79 @@
+// shared application state
+
+use std::sync::Arc;
+
+use crate::abuse::AbuseMonitor;
+use crate::auth::ApiKeyAuth;
+use crate::catalog_sync::CatalogWatcher;
+use crate::coalesce::RequestCoalescer;
+use crate::conference::ConferenceRegistry;
+use crate::config::{self, AppConfig};
+use crate::health::CapabilityMatrix;
+use crate::http;
+use crate::jobs::PlanJobs;
+use crate::locks::JobLocks;
+use crate::models::PlanOutcome;
+use crate::planner::AgentFailure;
+use crate::plans::PlanStore;
+use crate::profiles::ProfileStore;
+use crate::ratelimit::RateLimiter;
+use crate::sessions::Conversations;
+use crate::usage::UsageLedger;
+
+#[derive(Clone)]
+pub struct AppState {
+    // settings for the request path, loaded once at startup
+    pub config: &'static AppConfig,
+    // pooled client for upstream calls made by the handlers
+    pub http: reqwest::Client,
+    pub plan_coalescer: Arc<RequestCoalescer<Result<PlanOutcome, AgentFailure>>>,
+    pub conferences: Arc<ConferenceRegistry>,
+    pub catalog_watcher: Arc<CatalogWatcher>,
+    pub profiles: Arc<ProfileStore>,
+    pub abuse: Arc<AbuseMonitor>,
+    pub api_keys: Arc<ApiKeyAuth>,
+    // per-client token buckets in front of the llm routes
+    pub rate_limiter: Arc<RateLimiter>,
+    // chat sessions, in memory, redis or postgres per CONVERSATION_STORE
+    pub conversations: Arc<Conversations>,
+    pub plans: Arc<PlanStore>,
+    // tool health from the circuit breakers, consulted by the agent builders
+    pub health: Arc<CapabilityMatrix>,
+    // leases so background jobs run on one instance at a time
+    pub job_locks: Arc<JobLocks>,
+    // token spend by cost tag, for chargeback
+    pub usage: Arc<UsageLedger>,
+    // plans generated in the background for POST /plans
+    pub plan_jobs: Arc<PlanJobs>,
+}
+
+impl AppState {
+    pub fn new() -> Self {
+        let conferences = ConferenceRegistry::from_env();
+        let catalog_year = conferences.default_profile().year();
+
+        Self {
+            config: config::get(),
+            http: http::client(),
+            plan_coalescer: Arc::new(RequestCoalescer::new()),
+            conferences: Arc::new(conferences),
+            catalog_watcher: Arc::new(CatalogWatcher::new(catalog_year)),
+            profiles: Arc::new(ProfileStore::default()),
+            abuse: Arc::new(AbuseMonitor::from_env()),
+            api_keys: Arc::new(ApiKeyAuth::from_env()),
+            rate_limiter: Arc::new(RateLimiter::from_env()),
+            conversations: Arc::new(Conversations::from_env()),
+            plans: Arc::new(PlanStore::from_env()),
+            health: Arc::new(CapabilityMatrix::from_env()),
+            job_locks: Arc::new(JobLocks::from_env()),
+            usage: Arc::new(UsageLedger::from_env()),
+            plan_jobs: Arc::new(PlanJobs::from_env()),
+        }
+    }
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,394 @@
+// server-sent events for incremental plan generation
+
+use std::future::Future;
+use std::sync::Arc;
+
+use crate::llm::AnyAgent;
+use crate::mock::MockAgent;
+use crate::progress::{self, ProgressSink};
+use crate::retry;
+use axum::response::sse::Event;
+use futures::StreamExt;
+use rig::agent::Agent;
+use rig::completion::{CompletionError, Message};
+use rig::message::{AssistantContent, ToolResultContent, UserContent};
+use rig::streaming::{StreamingChat, StreamingCompletionModel};
+use rig::tool::ToolSet;
+use rig::OneOrMany;
+use schemars::JsonSchema;
+use serde::Serialize;
+use tokio::sync::mpsc;
+use tracing::{error, info, Instrument, Span};
+
+use crate::telemetry;
+use crate::usage::{self, Attribution};
+
+// why a stream stopped early
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamErrorCode {
+    // the provider asked us to back off
+    RateLimited,
+    // network failure, timeout or 5xx from the provider
+    ProviderUnavailable,
+    // rejected request, bad credentials and other failures a retry will not fix
+    ProviderError,
+    TurnLimit,
+    Internal,
+    // the client sent something we cannot run, e.g. on the websocket
+    InvalidRequest,
+}
+
+impl StreamErrorCode {
+    pub fn retryable(self) -> bool {
+        matches!(
+            self,
+            StreamErrorCode::RateLimited
+                | StreamErrorCode::ProviderUnavailable
+                | StreamErrorCode::Internal
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamEvent {
+    Token {
+        text: String,
+    },
+    ToolCall {
+        name: String,
+        arguments: String,
+    },
+    ToolResult {
+        name: String,
+        ok: bool,
+        bytes: usize,
+    },
+    // what a running tool is doing, between its tool_call and tool_result
+    Progress {
+        tool: String,
+        message: String,
+    },
+    Done {
+        plan: String,
+        // the stored plan, for runs that are kept like a plan request
+        #[serde(skip_serializing_if = "Option::is_none")]
+        plan_id: Option<String>,
+    },
+    // always the last frame of a failed run, a stream without `done` or `error` was cut off
+    Error {
+        code: StreamErrorCode,
+        message: String,
+        retryable: bool,
+        // tokens were already sent, the text so far is incomplete
+        partial: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        retry_after_seconds: Option<u64>,
+    },
+}
+
+impl StreamEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            StreamEvent::Token { .. } => "token",
+            StreamEvent::ToolCall { .. } => "tool_call",
+            StreamEvent::ToolResult { .. } => "tool_result",
+            StreamEvent::Progress { .. } => "progress",
+            StreamEvent::Done { .. } => "done",
+            StreamEvent::Error { .. } => "error",
+        }
+    }
+
+    pub fn error(code: StreamErrorCode, message: impl Into<String>, partial: bool) -> Self {
+        StreamEvent::Error {
+            code,
+            message: message.into(),
+            retryable: code.retryable(),
+            partial,
+            retry_after_seconds: None,
+        }
+    }
+
+    // sort a provider failure into a code clients can act on
+    fn from_completion_error(e: &CompletionError, partial: bool) -> Self {
+        let message = e.to_string();
+        if retry::is_rate_limit_message(&message) {
+            let retry_after = retry::retry_after_from_message(&message);
+            return StreamEvent::Error {
+                code: StreamErrorCode::RateLimited,
+                message,
+                retryable: true,
+                partial,
+                retry_after_seconds: retry_after.map(|d| d.as_secs().max(1)),
+            };
+        }
+
+        let code = if matches!(e, CompletionError::HttpError(_)) || is_transient_message(&message) {
+            StreamErrorCode::ProviderUnavailable
+        } else {
+            StreamErrorCode::ProviderError
+        };
+        Self::error(code, message, partial)
+    }
+
+    pub fn to_sse(&self) -> Event {
+        Event::default()
+            .event(self.name())
+            .json_data(self)
+            .unwrap_or_else(|_| Event::default().event("error").data("serialization failed"))
+    }
+}
+
+fn is_transient_message(message: &str) -> bool {
+    let lower = message.to_lowercase();
+    [
+        "500",
+        "502",
+        "503",
+        "504",
+        "overloaded",
+        "timed out",
+        "timeout",
+    ]
+    .iter()
+    .any(|marker| lower.contains(marker))
+}
+
+// run the stream in the background for whichever provider built the agent,
+// `history` holds earlier chat turns
+pub fn spawn_agent_run(
+    agent: AnyAgent,
+    tools: ToolSet,
+    objective: String,
+    history: Vec<Message>,
+    max_turns: usize,
+    attribution: Attribution,
+    events: mpsc::Sender<StreamEvent>,
+) {
+    let guard = events.clone();
+    let provider = agent.provider().name();
+    let model = agent.model().to_string();
+    let input = RunInput {
+        objective,
+        history,
+        max_turns,
+    };
+    match agent {
+        AnyAgent::OpenAI(agent, _) => spawn_guarded(
+            stream_agent_run(agent, provider, model, tools, input, events),
+            attribution,
+            guard,
+        ),
+        AnyAgent::Anthropic(agent, _) => spawn_guarded(
+            stream_agent_run(agent, provider, model, tools, input, events),
+            attribution,
+            guard,
+        ),
+        AnyAgent::Gemini(agent, _) => spawn_guarded(
+            stream_agent_run(agent, provider, model, tools, input, events),
+            attribution,
+            guard,
+        ),
+        AnyAgent::Ollama(agent, _) => spawn_guarded(
+            stream_agent_run(agent, provider, model, tools, input, events),
+            attribution,
+            guard,
+        ),
+        // the mock has no streaming api, the whole plan arrives as one Done event
+        AnyAgent::Mock(agent, _) => spawn_guarded(
+            mock_agent_run(agent, provider, model, input, events),
+            attribution,
+            guard,
+        ),
+    }
+}
+
+// what one run answers, with the chat turns before it
+struct RunInput {
+    objective: String,
+    history: Vec<Message>,
+    max_turns: usize,
+}
+
+async fn mock_agent_run(
+    agent: MockAgent,
+    provider: &'static str,
+    model: String,
+    input: RunInput,
+    events: mpsc::Sender<StreamEvent>,
+) {
+    let RunInput {
+        objective,
+        history,
+        max_turns,
+    } = input;
+    match agent.chat(&objective, &history, max_turns).await {
+        Ok(plan) => {
+            telemetry::record_token_usage(provider, &model, &objective, &plan);
+            let _ = events
+                .send(StreamEvent::Done {
+                    plan,
+                    plan_id: None,
+                })
+                .await;
+        }
+        Err(e) => {
+            error!("Mock run failed: {}", e);
+            let _ = events
+                .send(StreamEvent::error(
+                    StreamErrorCode::Internal,
+                    e.to_string(),
+                    false,
+                ))
+                .await;
+        }
+    }
+}
+
+// a panicking run still ends the stream with an error frame
+fn spawn_guarded<F>(run: F, attribution: Attribution, events: mpsc::Sender<StreamEvent>)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    // keep the request span, the run outlives the handler
+    let span = Span::current();
+    tokio::spawn(async move {
+        let run = usage::attributed(Some(attribution), run.instrument(span));
+        if let Err(e) = tokio::spawn(run).await {
+            error!("Streaming run aborted: {}", e);
+            let _ = events
+                .send(StreamEvent::error(
+                    StreamErrorCode::Internal,
+                    "Plan generation stopped unexpectedly",
+                    false,
+                ))
+                .await;
+        }
+    });
+}
+
+// stream completions and run tool calls ourselves between turns,
+// stops early if the client went away
+async fn stream_agent_run<M>(
+    agent: Agent<M>,
+    provider: &'static str,
+    model: String,
+    tools: ToolSet,
+    input: RunInput,
+    events: mpsc::Sender<StreamEvent>,
+) where
+    M: StreamingCompletionModel + 'static,
+{
+    let RunInput {
+        objective,
+        mut history,
+        max_turns,
+    } = input;
+    let mut prompt = Message::user(objective.clone());
+    let mut plan = String::new();
+    // best effort, a report that doesn't fit in the channel is dropped
+    let progress_events = events.clone();
+    let sink: ProgressSink = Arc::new(move |report: progress::ToolProgress| {
+        let _ = progress_events.try_send(StreamEvent::Progress {
+            tool: report.tool,
+            message: report.message,
+        });
+    });
+
+    for turn in 0..=max_turns {
+        let mut stream = match agent.stream_chat(prompt.clone(), history.clone()).await {
+            Ok(stream) => stream,
+            Err(e) => {
+                error!("Streaming completion failed: {}", e);
+                let _ = events
+                    .send(StreamEvent::from_completion_error(&e, !plan.is_empty()))
+                    .await;
+                return;
+            }
+        };
+
+        let mut tool_calls = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            match chunk {
+                Ok(AssistantContent::Text(text)) => {
+                    plan.push_str(&text.text);
+                    let token = StreamEvent::Token { text: text.text };
+                    if events.send(token).await.is_err() {
+                        info!("Stream client disconnected");
+                        return;
+                    }
+                }
+                Ok(AssistantContent::ToolCall(call)) => tool_calls.push(call),
+                Err(e) => {
+                    error!("Streaming chunk failed: {}", e);
+                    let _ = events
+                        .send(StreamEvent::from_completion_error(&e, !plan.is_empty()))
+                        .await;
+                    return;
+                }
+            }
+        }
+
+        if tool_calls.is_empty() {
+            info!("Streaming plan completed after {} turns", turn + 1);
+            telemetry::record_token_usage(provider, &model, &objective, &plan);
+            let _ = events
+                .send(StreamEvent::Done {
+                    plan,
+                    plan_id: None,
+                })
+                .await;
+            return;
+        }
+
+        let mut results = Vec::new();
+        for call in &tool_calls {
+            let name = call.function.name.clone();
+            let arguments = call.function.arguments.to_string();
+            let _ = events
+                .send(StreamEvent::ToolCall {
+                    name: name.clone(),
+                    arguments: arguments.clone(),
+                })
+                .await;
+
+            let (ok, output) =
+                match progress::reporting(sink.clone(), tools.call(&name, arguments)).await {
+                    Ok(output) => (true, output),
+                    Err(e) => (false, format!("Tool error: {}", e)),
+                };
+            let _ = events
+                .send(StreamEvent::ToolResult {
+                    name,
+                    ok,
+                    bytes: output.len(),
+                })
+                .await;
+
+            results.push(UserContent::tool_result(
+                call.id.clone(),
+                OneOrMany::one(ToolResultContent::text(output)),
+            ));
+        }
+
+        // feed the calls and their results back for the next turn
+        history.push(prompt);
+        let calls: Vec<AssistantContent> = tool_calls
+            .into_iter()
+            .map(AssistantContent::ToolCall)
+            .collect();
+        if let (Ok(calls), Ok(results)) = (OneOrMany::many(calls), OneOrMany::many(results)) {
+            history.push(Message::Assistant { content: calls });
+            prompt = Message::User { content: results };
+        }
+    }
+
+    let _ = events
+        .send(StreamEvent::error(
+            StreamErrorCode::TurnLimit,
+            format!("Agent did not finish within {} tool turns", max_turns),
+            !plan.is_empty(),
+        ))
+        .await;
+}
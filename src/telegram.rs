@@ -0,0 +1,490 @@
+// telegram bot. messages become itinerary requests, the reply lists the slots with an
+// "add to plan" and a "skip" button each, and added sessions build the chat's own plan
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::auth;
+use crate::config;
+use crate::copy;
+use crate::error;
+use crate::http;
+use crate::itinerary::TimeSlot;
+use crate::models::{GeneratePlanRequest, GeneratePlanResponse, OutputMode};
+use crate::pipeline::run_plan_request;
+use crate::popularity;
+use crate::ratelimit;
+use crate::state::AppState;
+use crate::telemetry;
+
+const API_URL: &str = "https://api.telegram.org";
+
+// telegram rejects longer messages
+const MAX_MESSAGE_CHARS: usize = 4000;
+
+// callback data is capped at 64 bytes
+const MAX_CALLBACK_BYTES: usize = 64;
+
+// chats remembered for their offered and added sessions
+const MAX_CHATS: usize = 10_000;
+const CHAT_TTL_HOURS: i64 = 48;
+
+static CHATS: OnceLock<Mutex<HashMap<i64, ChatPlan>>> = OnceLock::new();
+
+#[derive(Debug, thiserror::Error)]
+pub enum TelegramError {
+    #[error("the telegram bot is disabled")]
+    Disabled,
+    #[error("invalid webhook secret")]
+    BadSecret,
+    #[error("failed to call the telegram api: {0}")]
+    Api(#[from] reqwest::Error),
+}
+
+impl IntoResponse for TelegramError {
+    fn into_response(self) -> Response {
+        let (status, code) = match self {
+            TelegramError::Disabled => (StatusCode::NOT_FOUND, "telegram_disabled"),
+            TelegramError::BadSecret => (StatusCode::UNAUTHORIZED, "invalid_secret"),
+            TelegramError::Api(_) => (StatusCode::BAD_GATEWAY, "upstream_error"),
+        };
+        error::respond(status, error::envelope(code, self.to_string()))
+    }
+}
+
+// the parts of an update the bot reads, everything else is ignored
+#[derive(Debug, Deserialize)]
+pub struct Update {
+    #[serde(default)]
+    pub message: Option<Message>,
+    #[serde(default)]
+    pub callback_query: Option<CallbackQuery>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Message {
+    pub message_id: i64,
+    pub chat: Chat,
+    #[serde(default)]
+    pub from: Option<User>,
+    #[serde(default)]
+    pub text: Option<String>,
+    // the inline keyboard, on messages the bot sent
+    #[serde(default)]
+    pub reply_markup: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Chat {
+    pub id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct User {
+    pub id: i64,
+    // e.g. "fr", the plan is written in it
+    #[serde(default)]
+    pub language_code: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CallbackQuery {
+    pub id: String,
+    pub from: User,
+    #[serde(default)]
+    pub message: Option<Message>,
+    #[serde(default)]
+    pub data: Option<String>,
+}
+
+pub enum Button {
+    Add(String),
+    Skip(String),
+}
+
+impl Button {
+    pub fn parse(data: &str) -> Option<Self> {
+        match data.split_once(':')? {
+            ("add", id) => Some(Button::Add(id.to_string())),
+            ("skip", id) => Some(Button::Skip(id.to_string())),
+            _ => None,
+        }
+    }
+}
+
+pub struct Pressed {
+    // shown to the user as a toast
+    pub toast: String,
+    // the session added to the chat's plan, if any
+    pub added: Option<TimeSlot>,
+    // the keyboard without the pressed row
+    pub keyboard: Value,
+}
+
+#[derive(Default)]
+struct ChatPlan {
+    // slots of the latest itinerary, so a button press knows the session
+    offered: Vec<TimeSlot>,
+    added: Vec<TimeSlot>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+fn chats() -> &'static Mutex<HashMap<i64, ChatPlan>> {
+    CHATS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn with_chat<T>(chat_id: i64, f: impl FnOnce(&mut ChatPlan) -> T) -> T {
+    let now = Utc::now();
+    let mut chats = chats().lock().unwrap_or_else(PoisonError::into_inner);
+    if chats.len() > MAX_CHATS {
+        let cutoff = now - Duration::hours(CHAT_TTL_HOURS);
+        chats.retain(|_, chat| chat.updated_at.is_some_and(|at| at > cutoff));
+    }
+    let chat = chats.entry(chat_id).or_default();
+    chat.updated_at = Some(now);
+    f(chat)
+}
+
+// telegram sends the secret given to setWebhook in every request
+pub fn verify(headers: &HeaderMap) -> Result<(), TelegramError> {
+    let config = config::get();
+    let (Some(_), Some(expected)) = (&config.telegram_bot_token, &config.telegram_webhook_secret)
+    else {
+        return Err(TelegramError::Disabled);
+    };
+    let provided = headers
+        .get("x-telegram-bot-api-secret-token")
+        .and_then(|v| v.to_str().ok());
+    if auth::secret_matches(provided, expected) {
+        Ok(())
+    } else {
+        Err(TelegramError::BadSecret)
+    }
+}
+
+pub fn usage(language: Option<&str>) -> Value {
+    json!({ "text": copy::text("telegram.usage", language) })
+}
+
+pub fn planning(language: Option<&str>) -> Value {
+    json!({ "text": copy::text("telegram.planning", language) })
+}
+
+pub fn slow_down(seconds: u64, language: Option<&str>) -> Value {
+    let seconds = seconds.to_string();
+    json!({ "text": copy::render("telegram.slow_down", language, &[("seconds", &seconds)]) })
+}
+
+// the itinerary with one button row per slot, or the plan text when there is none
+pub fn plan_message(
+    chat_id: i64,
+    objective: &str,
+    response: &GeneratePlanResponse,
+    language: Option<&str>,
+) -> Value {
+    let (plan, itinerary) = match response {
+        GeneratePlanResponse::Success {
+            plan, itinerary, ..
+        } => (plan, itinerary),
+        GeneratePlanResponse::Duplicate { hint, .. } => return json!({ "text": hint }),
+        GeneratePlanResponse::Error { error, .. } => {
+            return json!({
+                "text": copy::render("telegram.failed", language, &[("error", error.message.as_str())]),
+            })
+        }
+    };
+    let slots = itinerary
+        .as_ref()
+        .map(|itinerary| itinerary.slots.clone())
+        .unwrap_or_default();
+    with_chat(chat_id, |chat| chat.offered = slots.clone());
+    if slots.is_empty() {
+        return json!({ "text": truncate(plan.trim(), MAX_MESSAGE_CHARS) });
+    }
+
+    let keyboard: Vec<Value> = slots
+        .iter()
+        .filter(|slot| format!("skip:{}", slot.session_id).len() <= MAX_CALLBACK_BYTES)
+        .map(|slot| {
+            json!([
+                {
+                    "text": copy::render("telegram.add", language, &[("title", &truncate(&slot.title, 32))]),
+                    "callback_data": format!("add:{}", slot.session_id),
+                },
+                {
+                    "text": copy::text("telegram.skip", language),
+                    "callback_data": format!("skip:{}", slot.session_id),
+                },
+            ])
+        })
+        .collect();
+    json!({
+        "text": slot_list(objective, &slots),
+        "parse_mode": "HTML",
+        "reply_markup": { "inline_keyboard": keyboard },
+    })
+}
+
+// unknown ids are sessions of an older itinerary or a forgotten chat
+pub fn press(
+    chat_id: i64,
+    button: &Button,
+    reply_markup: Option<&Value>,
+    language: Option<&str>,
+) -> Pressed {
+    let (id, added) = match button {
+        Button::Add(id) => (id, true),
+        Button::Skip(id) => (id, false),
+    };
+    let slot = with_chat(chat_id, |chat| {
+        let slot = chat
+            .offered
+            .iter()
+            .find(|slot| &slot.session_id == id)
+            .cloned();
+        if let Some(slot) = slot.as_ref().filter(|_| added) {
+            if !chat.added.iter().any(|s| s.session_id == slot.session_id) {
+                chat.added.push(slot.clone());
+                chat.added.sort_by_key(|s| s.start);
+            }
+        }
+        slot
+    });
+
+    let toast = match (&slot, added) {
+        (Some(slot), true) => copy::render("telegram.added", language, &[("title", &slot.title)]),
+        (None, true) => copy::text("telegram.expired", language),
+        (_, false) => copy::text("telegram.skipped", language),
+    };
+    let rows: Vec<Value> = reply_markup
+        .and_then(|markup| markup["inline_keyboard"].as_array())
+        .map(|rows| {
+            rows.iter()
+                .filter(|row| {
+                    !row.as_array().is_some_and(|buttons| {
+                        buttons.iter().any(|b| {
+                            b["callback_data"]
+                                .as_str()
+                                .and_then(|data| data.split_once(':'))
+                                .is_some_and(|(_, pressed)| pressed == id.as_str())
+                        })
+                    })
+                })
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default();
+    Pressed {
+        toast,
+        added: slot.filter(|_| added),
+        keyboard: json!({ "inline_keyboard": rows }),
+    }
+}
+
+// the sessions added in this chat, earliest first
+pub fn added_plan(chat_id: i64, language: Option<&str>) -> Value {
+    let added = with_chat(chat_id, |chat| chat.added.clone());
+    if added.is_empty() {
+        return json!({ "text": copy::text("telegram.empty_plan", language) });
+    }
+    json!({
+        "text": slot_list(&copy::text("telegram.your_plan", language), &added),
+        "parse_mode": "HTML",
+    })
+}
+
+pub async fn send_message(chat_id: i64, mut message: Value) -> Result<(), TelegramError> {
+    message["chat_id"] = json!(chat_id);
+    call("sendMessage", &message).await
+}
+
+pub async fn answer_callback(callback_id: &str, text: &str) -> Result<(), TelegramError> {
+    call(
+        "answerCallbackQuery",
+        &json!({ "callback_query_id": callback_id, "text": text }),
+    )
+    .await
+}
+
+pub async fn edit_keyboard(
+    chat_id: i64,
+    message_id: i64,
+    reply_markup: Value,
+) -> Result<(), TelegramError> {
+    call(
+        "editMessageReplyMarkup",
+        &json!({ "chat_id": chat_id, "message_id": message_id, "reply_markup": reply_markup }),
+    )
+    .await
+}
+
+async fn call(method: &str, body: &Value) -> Result<(), TelegramError> {
+    let token = config::get()
+        .telegram_bot_token
+        .as_deref()
+        .ok_or(TelegramError::Disabled)?;
+    http::client()
+        .post(format!("{}/bot{}/{}", API_URL, token, method))
+        .json(body)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+// whole lines only, a cut tag would make telegram reject the message
+fn slot_list(heading: &str, slots: &[TimeSlot]) -> String {
+    let mut text = format!("<b>{}</b>\n", escape(heading));
+    for slot in slots {
+        let line = slot_line(slot);
+        if text.chars().count() + line.chars().count() + 1 > MAX_MESSAGE_CHARS {
+            text.push_str("\n…");
+            break;
+        }
+        text.push('\n');
+        text.push_str(&line);
+    }
+    text
+}
+
+// e.g. "<b>Wed 14:00–14:45</b> AI for a Greener Planet · Stage 1"
+fn slot_line(slot: &TimeSlot) -> String {
+    let mut line = format!(
+        "<b>{}–{}</b> {}",
+        slot.start.format("%a %H:%M"),
+        slot.end.format("%H:%M"),
+        escape(&slot.title)
+    );
+    if !slot.location.is_empty() {
+        line.push_str(&format!(" · {}", escape(&slot.location)));
+    }
+    line
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let truncated: String = text.chars().take(max_chars - 1).collect();
+    format!("{}…", truncated.trim_end())
+}
+
+// telegram bot updates, verified with TELEGRAM_WEBHOOK_SECRET. telegram resends updates
+// that aren't answered quickly, so the bot replies through the bot api in the background
+pub async fn telegram_webhook_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if let Err(e) = verify(&headers) {
+        tracing::warn!("Rejected telegram update: {}", e);
+        return e.into_response();
+    }
+    // acknowledged anyway, an update telegram keeps resending would not parse either
+    let update: Update = match serde_json::from_slice(&body) {
+        Ok(update) => update,
+        Err(e) => {
+            tracing::warn!("Ignoring unreadable telegram update: {}", e);
+            return StatusCode::OK.into_response();
+        }
+    };
+
+    let request_id = telemetry::current_request_id();
+    let run = async move {
+        if let Some(callback) = update.callback_query {
+            telegram_button(&state, callback).await;
+        } else if let Some(message) = update.message {
+            telegram_message(&state, message).await;
+        }
+    };
+    tokio::spawn(telemetry::with_request_id(request_id, run));
+    StatusCode::OK.into_response()
+}
+
+async fn telegram_message(state: &AppState, message: Message) {
+    let chat_id = message.chat.id;
+    let Some(text) = message.text.map(|text| text.trim().to_string()) else {
+        return;
+    };
+    let (user_id, language) = match message.from {
+        Some(user) => (user.id.to_string(), user.language_code),
+        None => (chat_id.to_string(), None),
+    };
+    let language = language.as_deref();
+
+    // in groups commands come as "/help@VivaPlanBot"
+    let command = text
+        .split_whitespace()
+        .next()
+        .and_then(|word| word.split('@').next())
+        .unwrap_or_default();
+    let reply = match command {
+        "/start" | "/help" => usage(language),
+        "/myplan" => added_plan(chat_id, language),
+        _ => {
+            // the webhook is public, every chat gets its own bucket in the api's limiter
+            let client = format!("telegram:{}", chat_id);
+            if let Err(retry_after) = ratelimit::charge(state, &client, "/telegram/webhook") {
+                let seconds = ratelimit::retry_after_seconds(retry_after);
+                if let Err(e) = send_message(chat_id, slow_down(seconds, language)).await {
+                    tracing::warn!("Failed to send telegram rate limit notice: {}", e);
+                }
+                return;
+            }
+            if let Err(e) = send_message(chat_id, planning(language)).await {
+                tracing::warn!("Failed to acknowledge telegram message: {}", e);
+            }
+            let mut payload = GeneratePlanRequest::simple(text.clone(), None);
+            payload.mode = OutputMode::Itinerary;
+            payload.language = language.map(str::to_string);
+            let reply = run_plan_request(state, &format!("telegram:{}", user_id), payload).await;
+            plan_message(chat_id, &text, &reply.body, language)
+        }
+    };
+    if let Err(e) = send_message(chat_id, reply).await {
+        tracing::error!("Failed to send telegram reply: {}", e);
+    }
+}
+
+async fn telegram_button(state: &AppState, callback: CallbackQuery) {
+    let Some(button) = callback.data.as_deref().and_then(Button::parse) else {
+        return;
+    };
+    let Some(message) = callback.message else {
+        return;
+    };
+    let language = callback.from.language_code.as_deref();
+    let pressed = press(
+        message.chat.id,
+        &button,
+        message.reply_markup.as_ref(),
+        language,
+    );
+    if let Some(slot) = &pressed.added {
+        let conference = &state.conferences.default_profile().id;
+        popularity::record_favorites(state, conference, std::slice::from_ref(&slot.session_id));
+    }
+
+    if let Err(e) = answer_callback(&callback.id, &pressed.toast).await {
+        tracing::warn!("Failed to answer telegram button: {}", e);
+    }
+    if let Err(e) = edit_keyboard(message.chat.id, message.message_id, pressed.keyboard).await {
+        tracing::warn!("Failed to update telegram keyboard: {}", e);
+    }
+}
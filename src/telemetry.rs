@@ -0,0 +1,157 @@
+// prometheus metrics: http traffic, agent token usage, tool calls and upstream errors
+
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{MatchedPath, Request},
+    http::{header, HeaderName, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use tracing::{warn, Span};
+
+use crate::config;
+use crate::generation::estimate_tokens;
+use crate::pricing;
+use crate::usage;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+static PROMETHEUS: OnceLock<PrometheusHandle> = OnceLock::new();
+
+tokio::task_local! {
+    // X-Request-Id of the request being handled, stored with its plans for replays
+    static REQUEST_ID: String;
+}
+
+// request latency buckets in seconds, plans take tens of seconds
+const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.025, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 40.0, 80.0,
+];
+
+// install the global recorder once, later calls are no-ops
+pub fn init_metrics() {
+    if PROMETHEUS.get().is_some() {
+        return;
+    }
+
+    let recorder = PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full("http_request_duration_seconds".to_string()),
+            LATENCY_BUCKETS,
+        )
+        .and_then(|builder| builder.install_recorder());
+    match recorder {
+        Ok(handle) => {
+            // histograms are compacted by upkeep, the recorder does not run it on its own
+            let upkeep = handle.clone();
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(Duration::from_secs(5));
+                loop {
+                    ticker.tick().await;
+                    upkeep.run_upkeep();
+                }
+            });
+            let _ = PROMETHEUS.set(handle);
+        }
+        Err(e) => warn!("Prometheus recorder unavailable: {}", e),
+    }
+}
+
+// covers the handler, the agent run and its tool calls. the id is set by
+// SetRequestIdLayer, or kept from the caller so ids survive across services
+pub fn request_span(request: &Request) -> Span {
+    let request_id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("-");
+    tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        method = %request.method(),
+        path = %request.uri().path(),
+        // filled in by the api key middleware
+        api_key = tracing::field::Empty
+    )
+}
+
+pub async fn with_request_id<F: Future>(request_id: Option<String>, run: F) -> F::Output {
+    match request_id {
+        Some(request_id) => REQUEST_ID.scope(request_id, run).await,
+        None => run.await,
+    }
+}
+
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+// counts and times every routed request, labelled with the route template
+pub async fn track_requests(request: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let method = request.method().to_string();
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let request_id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+
+    // env reads from here on are flagged in debug builds, see config::env_var
+    let response = with_request_id(request_id, config::serving_request(next.run(request))).await;
+
+    let status = response.status().as_u16().to_string();
+    metrics::counter!(
+        "http_requests_total",
+        "method" => method.clone(),
+        "path" => path.clone(),
+        "status" => status
+    )
+    .increment(1);
+    metrics::histogram!(
+        "http_request_duration_seconds",
+        "method" => method,
+        "path" => path
+    )
+    .record(started.elapsed().as_secs_f64());
+
+    response
+}
+
+// estimated like StageUsage, providers do not report usage through the agent api.
+// also charged to the request's cost tags and api key when it has an attribution
+pub fn record_token_usage(provider: &'static str, model: &str, prompt: &str, completion: &str) {
+    let prompt_tokens = estimate_tokens(prompt) as u64;
+    let completion_tokens = estimate_tokens(completion) as u64;
+    let cost = pricing::estimate_cost(provider, model, prompt_tokens, completion_tokens);
+    metrics::counter!("llm_tokens_total", "provider" => provider, "kind" => "prompt")
+        .increment(prompt_tokens);
+    metrics::counter!("llm_tokens_total", "provider" => provider, "kind" => "completion")
+        .increment(completion_tokens);
+    // a gauge because counters only take whole numbers, it only ever goes up
+    metrics::gauge!("llm_estimated_cost_usd", "provider" => provider).increment(cost);
+    usage::record(provider, model, prompt_tokens, completion_tokens, cost);
+}
+
+pub async fn metrics_handler() -> Response {
+    match PROMETHEUS.get() {
+        Some(handle) => (
+            [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+            handle.render(),
+        )
+            .into_response(),
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "metrics recorder not installed",
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,98 @@
+// curated objective templates
+
+use std::collections::HashMap;
+
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+const MAX_PARAMETER_LENGTH: usize = 100;
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ObjectiveTemplate {
+    pub id: &'static str,
+    pub title: &'static str,
+    pub template: &'static str,
+    pub parameters: Vec<String>,
+}
+
+impl ObjectiveTemplate {
+    fn new(id: &'static str, title: &'static str, template: &'static str) -> Self {
+        Self {
+            id,
+            title,
+            template,
+            parameters: placeholder_names(template),
+        }
+    }
+
+    // fill every {placeholder}, all of them are required
+    pub fn render(&self, values: &HashMap<String, String>) -> Result<String, String> {
+        let mut objective = self.template.to_string();
+
+        for name in &self.parameters {
+            let value = values
+                .get(name)
+                .map(|v| v.trim())
+                .filter(|v| !v.is_empty())
+                .ok_or_else(|| format!("Missing template parameter '{}'", name))?;
+
+            if value.len() > MAX_PARAMETER_LENGTH || value.contains(['{', '}']) {
+                return Err(format!("Invalid value for template parameter '{}'", name));
+            }
+
+            objective = objective.replace(&format!("{{{}}}", name), value);
+        }
+
+        Ok(objective)
+    }
+}
+
+fn placeholder_names(template: &str) -> Vec<String> {
+    let Ok(pattern) = Regex::new(r"\{(\w+)\}") else {
+        return Vec::new();
+    };
+
+    let mut names: Vec<String> = Vec::new();
+    for captures in pattern.captures_iter(template) {
+        let name = captures[1].to_string();
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+pub fn builtin_templates() -> Vec<ObjectiveTemplate> {
+    vec![
+        ObjectiveTemplate::new(
+            "ai-deep-dive",
+            "One-day AI deep dive",
+            "Plan a one-day AI deep dive for a {role}, focusing on the most relevant talks and demos.",
+        ),
+        ObjectiveTemplate::new(
+            "investor-deal-sourcing",
+            "Investor deal-sourcing day",
+            "Plan an investor deal-sourcing day in {sector}: startup pitches, partner booths and networking sessions.",
+        ),
+        ObjectiveTemplate::new(
+            "first-timer",
+            "First-time attendee tour",
+            "I'm attending for the first time as a {role}. Plan a balanced day mixing keynotes, booths and networking.",
+        ),
+        ObjectiveTemplate::new(
+            "topic-day",
+            "Topic-focused day",
+            "Find the best sessions and partners about {topic} on {day}.",
+        ),
+        ObjectiveTemplate::new(
+            "recruiting",
+            "Talent scouting",
+            "Plan a day to meet {sector} startups that are hiring {role} profiles.",
+        ),
+    ]
+}
+
+pub fn find_template(id: &str) -> Option<ObjectiveTemplate> {
+    builtin_templates().into_iter().find(|t| t.id == id)
+}
@@ -0,0 +1,40 @@
+// named output tones applied on top of the plan format
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Tone {
+    ConciseBullet,
+    ExecutiveBrief,
+    EnthusiasticGuide,
+}
+
+impl Tone {
+    pub fn instructions(self) -> &'static str {
+        match self {
+            Tone::ConciseBullet => {
+                "Use short bullet points only, one line per pick with time, place and a \
+                few words on why. No intro, no closing remarks."
+            }
+            Tone::ExecutiveBrief => {
+                "Write for a busy executive: open with a two-sentence summary of the day, \
+                then list the picks in order of business value with a one-line rationale each. \
+                Neutral, professional wording, no emojis."
+            }
+            Tone::EnthusiasticGuide => {
+                "Write like an upbeat local guide: warm and energetic, a short story for why \
+                each pick is worth it, with practical tips between sessions. Emojis are welcome."
+            }
+        }
+    }
+}
+
+// applied last so it overrides any formatting hints above it
+pub fn append_tone(instructions: &mut String, tone: Option<Tone>) {
+    if let Some(tone) = tone {
+        instructions.push_str("\n\nFormatting constraint for the final answer:\n");
+        instructions.push_str(tone.instructions());
+    }
+}
@@ -1,208 +1,2761 @@
 // agent tools for vivatech api integration
 
-use crate::models::{
-    get_current_conference_date, ActionUrgency, VivatechQueryResponse, VivatechSource,
-};
+use crate::citations::SourceCollector;
+use crate::conference::{ConferenceProfile, GeoPoint, VenueGraph};
+use crate::config;
+use crate::enrichment;
+use crate::health::{CapabilityMatrix, ToolHealth};
+use crate::http;
+use crate::mock;
+use crate::models::{ActionUrgency, Track, VivatechQueryResponse, VivatechSource};
+use crate::programme::{Partner, Session};
+use crate::progress;
+use crate::query_cache;
+use crate::rerank;
+use crate::retry::retry_after_from_headers;
+use crate::sanitize;
+use crate::signing::{self, UpstreamSigning};
 use anyhow::Result;
-use chrono::NaiveDate;
+use chrono::{
+    DateTime, Datelike, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike, Utc, Weekday,
+};
+use chrono_tz::Tz;
+use moka::sync::Cache;
 use regex::Regex;
-use reqwest::Client;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, StatusCode};
 use rig::completion::ToolDefinition;
 use rig::tool::Tool;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+use tracing::{info, warn};
+
+// api url from the app config
+pub fn get_vivatech_api_url() -> Result<String, VivatechApiError> {
+    config::get()
+        .vivatech_api_url
+        .clone()
+        .ok_or_else(|| VivatechApiError("VIVATECH_API_URL not found in environment".to_string()))
+}
+
+// tool 1: search vivatech database
+const MAX_TOP_K: usize = 50;
+
+#[derive(Debug, Deserialize)]
+pub struct QueryVivatechArgs {
+    pub query: String,
+    // at most this many sources, best first. everything that passes otherwise
+    #[serde(default)]
+    pub top_k: Option<usize>,
+    // 0 to 1, sources scoring lower are dropped
+    #[serde(default)]
+    pub min_score: Option<f32>,
+}
+
+impl QueryVivatechArgs {
+    pub fn new(query: impl Into<String>) -> Self {
+        Self {
+            query: query.into(),
+            top_k: None,
+            min_score: None,
+        }
+    }
+
+    // the agent asked for a narrower set than the search returned
+    fn narrow(&self, mut sources: Vec<VivatechSource>) -> Vec<VivatechSource> {
+        if let Some(min_score) = self.min_score {
+            sources.retain(|source| source.score >= min_score);
+        }
+        if let Some(top_k) = self.top_k {
+            sources.sort_by(|a, b| b.score.total_cmp(&a.score));
+            sources.truncate(top_k.clamp(1, MAX_TOP_K));
+        }
+        sources
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Vivatech API Error: {0}")]
+pub struct VivatechApiError(String);
+
+// what the agent gets back from a search. a failed search is reported as degraded
+// instead of a tool error, so the run goes on and the plan can say what is missing
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum SearchOutcome {
+    Sources(Vec<VivatechSource>),
+    Empty(EmptyResult),
+    Degraded {
+        status: &'static str,
+        message: String,
+    },
+}
+
+// nothing matched. an empty list left the model to guess, this tells it what to try next
+#[derive(Debug, Serialize)]
+pub struct EmptyResult {
+    pub status: &'static str,
+    pub query: String,
+    // rephrasings of the query, broadest last
+    pub suggestions: Vec<String>,
+    pub message: String,
+}
+
+impl SearchOutcome {
+    fn degraded(reason: &str) -> Self {
+        SearchOutcome::Degraded {
+            status: "degraded",
+            message: format!(
+                "Live Vivatech search is temporarily unavailable ({}). Tell the attendee the plan \
+                could not use live session data and may be incomplete; do not invent sessions.",
+                reason
+            ),
+        }
+    }
+
+    fn empty(query: &str) -> Self {
+        let suggestions = suggest_queries(query);
+        let message = if suggestions.is_empty() {
+            format!(
+                "No sessions or partners matched \"{}\". Tell the attendee nothing in the \
+                programme matches; do not invent sessions.",
+                query
+            )
+        } else {
+            format!(
+                "No sessions or partners matched \"{}\". Search again with one or two of the \
+                suggestions before answering. If they also find nothing, tell the attendee \
+                nothing in the programme matches; do not invent sessions.",
+                query
+            )
+        };
+        SearchOutcome::Empty(EmptyResult {
+            status: "no_results",
+            query: query.to_string(),
+            suggestions,
+            message,
+        })
+    }
+}
+
+const MAX_SUGGESTIONS: usize = 5;
+
+// words that narrow a search without naming a topic
+const FILLER_WORDS: &[&str] = &[
+    "a",
+    "about",
+    "all",
+    "an",
+    "and",
+    "any",
+    "at",
+    "best",
+    "event",
+    "events",
+    "find",
+    "for",
+    "in",
+    "interesting",
+    "me",
+    "of",
+    "on",
+    "related",
+    "session",
+    "sessions",
+    "show",
+    "talk",
+    "talks",
+    "the",
+    "to",
+    "top",
+    "vivatech",
+    "with",
+];
+
+// wider phrasings for common vivatech topics
+const RELATED_TOPICS: &[(&str, &str)] = &[
+    ("ai", "artificial intelligence"),
+    ("genai", "generative ai"),
+    ("llm", "generative ai"),
+    ("ml", "machine learning"),
+    ("climate", "sustainability"),
+    ("green", "sustainability"),
+    ("energy", "sustainability"),
+    ("fintech", "finance"),
+    ("banking", "finance"),
+    ("crypto", "web3"),
+    ("blockchain", "web3"),
+    ("health", "healthcare"),
+    ("medtech", "healthcare"),
+    ("security", "cybersecurity"),
+    ("cars", "mobility"),
+    ("transport", "mobility"),
+    ("vc", "investors"),
+    ("funding", "investors"),
+    ("founders", "startups"),
+];
+
+// alternate queries built locally, no model call: the topic words alone, each topic
+// word on its own, singulars, and the wider topic for known terms
+fn suggest_queries(query: &str) -> Vec<String> {
+    let words: Vec<String> = query
+        .split(|c: char| !c.is_alphanumeric() && c != '-')
+        .map(str::to_lowercase)
+        .filter(|word| !word.is_empty() && !FILLER_WORDS.contains(&word.as_str()))
+        .collect();
+
+    let mut candidates = Vec::new();
+    if words.len() > 1 {
+        candidates.push(words.join(" "));
+    }
+    let mut by_length = words.clone();
+    by_length.sort_by_key(|word| std::cmp::Reverse(word.len()));
+    for word in &by_length {
+        match word.strip_suffix('s') {
+            Some(singular) if singular.len() > 2 && !word.ends_with("ss") => {
+                candidates.push(singular.to_string())
+            }
+            _ => candidates.push(word.clone()),
+        }
+    }
+    for word in &words {
+        let singular = word.strip_suffix('s').unwrap_or(word);
+        if let Some((_, topic)) = RELATED_TOPICS
+            .iter()
+            .find(|(term, _)| *term == word.as_str() || *term == singular)
+        {
+            candidates.push(topic.to_string());
+        }
+    }
+
+    let original = query.trim().to_lowercase();
+    let mut suggestions: Vec<String> = Vec::new();
+    for candidate in candidates {
+        if candidate != original && !suggestions.contains(&candidate) {
+            suggestions.push(candidate);
+        }
+    }
+    suggestions.truncate(MAX_SUGGESTIONS);
+    suggestions
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct QueryVivatechAPI {
+    // None falls back to VIVATECH_API_URL
+    pub api_url: Option<String>,
+    // records returned sources for citations
+    #[serde(skip)]
+    pub collector: Option<SourceCollector>,
+    // circuit breaker for the upstream api
+    #[serde(skip)]
+    pub health: Option<Arc<CapabilityMatrix>>,
+}
+
+impl QueryVivatechAPI {
+    pub fn for_conference(profile: &ConferenceProfile) -> Self {
+        Self {
+            api_url: profile.api_url(),
+            collector: None,
+            health: None,
+        }
+    }
+
+    pub fn with_collector(mut self, collector: SourceCollector) -> Self {
+        self.collector = Some(collector);
+        self
+    }
+
+    pub fn with_health(mut self, health: Arc<CapabilityMatrix>) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    async fn fetch_live(&self, query: &str) -> Result<Vec<VivatechSource>, VivatechApiError> {
+        let Some(health) = &self.health else {
+            return fetch_vivatech_sources(query, self.api_url.as_deref()).await;
+        };
+
+        // fail fast while the breaker is open
+        if health.status(Self::NAME) == ToolHealth::Down {
+            let retry_in = health.retry_in(Self::NAME).unwrap_or_default();
+            return Err(VivatechApiError(format!(
+                "paused for {}s after repeated failures",
+                retry_in.as_secs().max(1)
+            )));
+        }
+
+        let result = fetch_vivatech_sources(query, self.api_url.as_deref()).await;
+        match &result {
+            Ok(_) => health.record_success(Self::NAME),
+            Err(_) => health.record_failure(Self::NAME),
+        }
+        result
+    }
+}
+
+impl Tool for QueryVivatechAPI {
+    const NAME: &'static str = "query_vivatech_api";
+    type Error = VivatechApiError;
+    type Args = QueryVivatechArgs;
+    type Output = SearchOutcome;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Searches the Vivatech conference database for sessions and partners related to a query.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "The search term to find relevant Vivatech sessions or partners"
+                    },
+                    "top_k": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": MAX_TOP_K,
+                        "description": "Return at most this many results, best first. Small for a precise question, omit for a broad overview"
+                    },
+                    "min_score": {
+                        "type": "number",
+                        "minimum": 0,
+                        "maximum": 1,
+                        "description": "Drop results whose relevance score (0 to 1) is lower, e.g. 0.5 to keep only close matches"
+                    }
+                },
+                "required": ["query"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        // keyed by the resolved url so conferences never share entries
+        let api_url = self.api_url.clone().or_else(|| get_vivatech_api_url().ok());
+        let cache = query_cache::query_cache();
+        let cached = cache
+            .as_ref()
+            .and_then(|cache| cache.get(api_url.as_deref(), &args.query));
+
+        let mut sources = match cached {
+            Some(sources) => sources,
+            None => {
+                progress::report(
+                    Self::NAME,
+                    format!("Querying the Vivatech API for '{}'", args.query),
+                );
+                match self.fetch_live(&args.query).await {
+                    Ok(sources) => {
+                        // offline and degraded answers are never cached
+                        if let Some(cache) = &cache {
+                            cache.insert(api_url.as_deref(), &args.query, sources.clone());
+                        }
+                        sources
+                    }
+                    Err(e) => {
+                        progress::report(
+                            Self::NAME,
+                            format!(
+                                "Vivatech API unavailable, searching the local catalog for '{}'",
+                                args.query
+                            ),
+                        );
+                        match local_fallback(&args.query, e).await {
+                            Ok(sources) => sources,
+                            Err(VivatechApiError(reason)) => {
+                                warn!("Search degraded: {}", reason);
+                                return Ok(SearchOutcome::degraded(&reason));
+                            }
+                        }
+                    }
+                }
+            }
+        };
+        if sources.is_empty() {
+            info!("No sources for query: {}", args.query);
+            return Ok(SearchOutcome::empty(&args.query));
+        }
+        enrichment::annotate(&mut sources);
+        let sources = args.narrow(rerank::rerank(&args.query, sources).await);
+        if sources.is_empty() {
+            info!(
+                "No sources above the requested score for query: {}",
+                args.query
+            );
+            return Ok(SearchOutcome::empty(&args.query));
+        }
+        progress::report(
+            Self::NAME,
+            format!("Found {} results for '{}'", sources.len(), args.query),
+        );
+
+        if let Some(collector) = &self.collector {
+            collector.extend(&sources);
+        }
+        Ok(SearchOutcome::Sources(sources))
+    }
+}
+
+async fn fetch_vivatech_sources(
+    query: &str,
+    api_url: Option<&str>,
+) -> Result<Vec<VivatechSource>, VivatechApiError> {
+    if let Some(sources) = mock::search_mock_catalog(query) {
+        return Ok(sources);
+    }
+    let client = http::api_client();
+    let request_body = json!({ "query": query });
+    let api_url = match api_url {
+        Some(url) => url.to_string(),
+        None => get_vivatech_api_url()?,
+    };
+    let response = make_api_request(&client, &api_url, &request_body).await?;
+    let api_response = parse_query_response(response).await?;
+    Ok(api_response.sources)
+}
+
+// answers from inside the process when the upstream is unreachable:
+// semantic search over the session dump first, then the sqlite snapshot
+async fn local_fallback(
+    query: &str,
+    error: VivatechApiError,
+) -> Result<Vec<VivatechSource>, VivatechApiError> {
+    match semantic_fallback(query, error).await {
+        Ok(sources) => Ok(sources),
+        Err(error) => offline_fallback(query, error).await,
+    }
+}
+
+#[cfg(feature = "semantic-fallback")]
+async fn semantic_fallback(
+    query: &str,
+    error: VivatechApiError,
+) -> Result<Vec<VivatechSource>, VivatechApiError> {
+    match crate::rag::search_semantic_index(query).await {
+        Some(Ok(sources)) if !sources.is_empty() => {
+            warn!(
+                "Vivatech API unavailable ({}), serving {} sources from semantic index",
+                error,
+                sources.len()
+            );
+            Ok(sources)
+        }
+        Some(Err(e)) => {
+            warn!("Semantic fallback search failed: {}", e);
+            Err(error)
+        }
+        // nothing close enough, the snapshot may still match on words
+        Some(Ok(_)) | None => Err(error),
+    }
+}
+
+#[cfg(not(feature = "semantic-fallback"))]
+async fn semantic_fallback(
+    _query: &str,
+    error: VivatechApiError,
+) -> Result<Vec<VivatechSource>, VivatechApiError> {
+    Err(error)
+}
+
+// serve from the local snapshot when the upstream is unreachable
+#[cfg(feature = "offline-catalog")]
+async fn offline_fallback(
+    query: &str,
+    error: VivatechApiError,
+) -> Result<Vec<VivatechSource>, VivatechApiError> {
+    match crate::catalog::search_offline_catalog(query).await {
+        Some(Ok(sources)) => {
+            warn!(
+                "Vivatech API unavailable ({}), serving {} sources from offline catalog",
+                error,
+                sources.len()
+            );
+            Ok(sources)
+        }
+        Some(Err(e)) => {
+            warn!("Offline catalog search failed: {}", e);
+            Err(error)
+        }
+        None => Err(error),
+    }
+}
+
+#[cfg(not(feature = "offline-catalog"))]
+async fn offline_fallback(
+    _query: &str,
+    error: VivatechApiError,
+) -> Result<Vec<VivatechSource>, VivatechApiError> {
+    Err(error)
+}
+
+// tool 1b: several searches at once, for objectives spanning topics
+// ("AI, quantum and healthtech") that would otherwise be searched one turn at a time
+
+// searches run per call, extra queries are dropped
+const MAX_BATCH_QUERIES: usize = 6;
+
+#[derive(Debug, Deserialize)]
+pub struct QueryVivatechBatchArgs {
+    pub queries: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchSearchOutcome {
+    // every query's sources, each source once with its best score, best first
+    pub sources: Vec<VivatechSource>,
+    // queries that found nothing or could not run, with what to do about them
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub misses: Vec<SearchOutcome>,
+}
+
+pub struct QueryVivatechBatch {
+    search: QueryVivatechAPI,
+}
+
+impl QueryVivatechBatch {
+    // runs each query through the single search, its cache, breaker and fallbacks
+    pub fn new(search: QueryVivatechAPI) -> Self {
+        Self { search }
+    }
+}
+
+impl Tool for QueryVivatechBatch {
+    const NAME: &'static str = "query_vivatech_api_batch";
+    type Error = VivatechApiError;
+    type Args = QueryVivatechBatchArgs;
+    type Output = BatchSearchOutcome;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: format!(
+                "Runs up to {} Vivatech searches concurrently and returns their merged, \
+                deduplicated sessions and partners. Use it instead of several \
+                query_vivatech_api calls when the request covers several topics.",
+                MAX_BATCH_QUERIES
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "queries": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "One search term per topic, e.g. [\"AI\", \"quantum\", \"healthtech\"]"
+                    }
+                },
+                "required": ["queries"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let mut seen = HashSet::new();
+        let queries: Vec<String> = args
+            .queries
+            .into_iter()
+            .map(|query| query.trim().to_string())
+            .filter(|query| !query.is_empty() && seen.insert(query.to_lowercase()))
+            .take(MAX_BATCH_QUERIES)
+            .collect();
+        info!("Running {} Vivatech searches concurrently", queries.len());
+        progress::report(
+            Self::NAME,
+            format!(
+                "Running {} Vivatech searches: {}",
+                queries.len(),
+                queries.join(", ")
+            ),
+        );
+
+        let outcomes = futures::future::join_all(
+            queries
+                .into_iter()
+                .map(|query| self.search.call(QueryVivatechArgs::new(query))),
+        )
+        .await;
+
+        let mut sources: Vec<VivatechSource> = Vec::new();
+        let mut misses = Vec::new();
+        for outcome in outcomes {
+            match outcome {
+                Ok(SearchOutcome::Sources(found)) => {
+                    for source in found {
+                        match sources.iter_mut().find(|known| known.id == source.id) {
+                            Some(known) => known.score = known.score.max(source.score),
+                            None => sources.push(source),
+                        }
+                    }
+                }
+                Ok(miss) => misses.push(miss),
+                Err(VivatechApiError(reason)) => misses.push(SearchOutcome::degraded(&reason)),
+            }
+        }
+        sources.sort_by(|a, b| b.score.total_cmp(&a.score));
+        Ok(BatchSearchOutcome { sources, misses })
+    }
+}
+
+// tool 1c: who is speaking when ("when does Jensen Huang speak?"). the api has no
+// speaker field, so the name is searched and the results that mention it are kept
+
+#[derive(Debug, Deserialize)]
+pub struct LookupSpeakerArgs {
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SpeakerSession {
+    pub id: String,
+    pub title: String,
+    // e.g. "Stage 1, Hall 1", from the session text
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stage: Option<String>,
+    // venue local time, unset when the text has no date or time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub starts_at: Option<NaiveDateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ends_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SpeakerLookup {
+    pub name: String,
+    // speaker records that mention the name
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub bios: Vec<String>,
+    // earliest first, undated sessions last
+    pub sessions: Vec<SpeakerSession>,
+    // why nothing was found, and what to do about it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub miss: Option<SearchOutcome>,
+}
+
+pub struct LookupSpeaker {
+    search: QueryVivatechAPI,
+    year: i32,
+}
+
+impl LookupSpeaker {
+    // goes through the regular search, its cache, breaker and fallbacks
+    pub fn new(search: QueryVivatechAPI, profile: &ConferenceProfile) -> Self {
+        Self {
+            search,
+            year: profile.year(),
+        }
+    }
+}
+
+impl Tool for LookupSpeaker {
+    const NAME: &'static str = "lookup_speaker";
+    type Error = VivatechApiError;
+    type Args = LookupSpeakerArgs;
+    type Output = SpeakerLookup;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Finds a Vivatech speaker by name and returns their sessions with \
+                stage and time, and their bio when the catalog has one."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "name": {
+                        "type": "string",
+                        "description": "The speaker's name, e.g. \"Jensen Huang\""
+                    }
+                },
+                "required": ["name"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let name = args.name.trim().to_string();
+        progress::report(Self::NAME, format!("Looking up sessions with {}", name));
+        let found = self
+            .search
+            .call(QueryVivatechArgs::new(name.clone()))
+            .await?;
+        let sources = match found {
+            SearchOutcome::Sources(sources) => sources,
+            miss => {
+                return Ok(SpeakerLookup {
+                    name,
+                    bios: Vec::new(),
+                    sessions: Vec::new(),
+                    miss: Some(miss),
+                })
+            }
+        };
+
+        let mut bios = Vec::new();
+        let mut sessions = Vec::new();
+        for source in sources
+            .iter()
+            .filter(|source| mentions_speaker(&source.text_chunk, &name))
+        {
+            if source.source_table == "speakers" {
+                bios.push(source.text_chunk.trim().to_string());
+                continue;
+            }
+            let session = Session::from_source(source, self.year);
+            sessions.push(SpeakerSession {
+                id: source.id.clone(),
+                title: session.title,
+                stage: session.stage,
+                starts_at: session.start,
+                ends_at: session.end,
+            });
+        }
+        sessions.sort_by_key(|session| (session.starts_at.is_none(), session.starts_at));
+        info!(
+            "Speaker lookup for {}: {} sessions, {} bios",
+            name,
+            sessions.len(),
+            bios.len()
+        );
+
+        let miss = (bios.is_empty() && sessions.is_empty()).then(|| SearchOutcome::empty(&name));
+        Ok(SpeakerLookup {
+            name,
+            bios,
+            sessions,
+            miss,
+        })
+    }
+}
+
+// every part of the name, so "Huang" alone does not match another Huang's session
+fn mentions_speaker(text: &str, name: &str) -> bool {
+    let text = text.to_lowercase();
+    let mut parts = name
+        .split_whitespace()
+        .map(|part| {
+            part.trim_matches(|c: char| !c.is_alphanumeric())
+                .to_lowercase()
+        })
+        .filter(|part| !part.is_empty())
+        .peekable();
+    parts.peek().is_some() && parts.all(|part| text.contains(&part))
+}
+
+// tool 1d: exhibitors rather than sessions, with where to find them on the floor and an
+// order to visit them in
+
+// booths per call, the rest of the matches are dropped
+const MAX_PARTNERS: usize = 10;
+
+#[derive(Debug, Deserialize)]
+pub struct FindPartnersArgs {
+    pub query: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PartnerBooth {
+    pub id: String,
+    pub name: String,
+    // e.g. "Hall 1" and "C12", unset when the partner text has no location
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hall: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub booth: Option<String>,
+    // e.g. "Climate Tech"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sector: Option<String>,
+    pub description: String,
+    pub score: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PartnerRoute {
+    // located booths, hall by hall in venue order, then by booth number
+    pub stops: Vec<PartnerBooth>,
+    // between halls, from the venue paths. unset when a leg has no known path
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub walking_minutes: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PartnerSearch {
+    // best match first
+    pub partners: Vec<PartnerBooth>,
+    // set when at least one partner has a hall
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub route: Option<PartnerRoute>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub miss: Option<SearchOutcome>,
+}
+
+pub struct FindPartners {
+    search: QueryVivatechAPI,
+    venue: VenueGraph,
+}
+
+impl FindPartners {
+    // goes through the regular search, its cache, breaker and fallbacks
+    pub fn new(search: QueryVivatechAPI, profile: &ConferenceProfile) -> Self {
+        Self {
+            search,
+            venue: profile.venue.clone(),
+        }
+    }
+}
+
+impl Tool for FindPartners {
+    const NAME: &'static str = "find_partners";
+    type Error = VivatechApiError;
+    type Args = FindPartnersArgs;
+    type Output = PartnerSearch;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Finds Vivatech partners and exhibitors (not sessions) related to a \
+                query, with their hall and booth, and a walking route through their booths \
+                in visiting order."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "What the exhibitors should be about, e.g. \"renewable energy\""
+                    }
+                },
+                "required": ["query"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        // partner texts are written as "<name> booth - <hall>, booth <number>"
+        let query = format!("{} booth", args.query.trim());
+        progress::report(
+            Self::NAME,
+            format!("Finding exhibitors for '{}'", args.query.trim()),
+        );
+        let sources = match self.search.call(QueryVivatechArgs::new(query)).await? {
+            SearchOutcome::Sources(sources) => sources,
+            miss => {
+                return Ok(PartnerSearch {
+                    partners: Vec::new(),
+                    route: None,
+                    miss: Some(miss),
+                })
+            }
+        };
+
+        let mut partners: Vec<PartnerBooth> = sources
+            .iter()
+            .filter(|source| source.source_table == "partners")
+            .map(partner_booth)
+            .collect();
+        partners.sort_by(|a, b| b.score.total_cmp(&a.score));
+        partners.truncate(MAX_PARTNERS);
+        info!(
+            "Found {} partners for query: {}",
+            partners.len(),
+            args.query
+        );
+
+        if partners.is_empty() {
+            return Ok(PartnerSearch {
+                partners,
+                route: None,
+                miss: Some(SearchOutcome::empty(&args.query)),
+            });
+        }
+        Ok(PartnerSearch {
+            route: walking_route(&partners, &self.venue),
+            partners,
+            miss: None,
+        })
+    }
+}
+
+fn partner_booth(source: &VivatechSource) -> PartnerBooth {
+    let partner = Partner::from_source(source);
+    let text = source.text_chunk.trim();
+    let (_, description) = text.split_once('\n').unwrap_or((text, ""));
+    PartnerBooth {
+        id: source.id.clone(),
+        name: partner.name,
+        hall: partner.hall,
+        booth: partner.booth,
+        sector: partner.sector,
+        description: description.trim().to_string(),
+        score: source.score,
+    }
+}
+
+// halls in the order the venue lists them, unknown halls after those by name
+fn walking_route(partners: &[PartnerBooth], venue: &VenueGraph) -> Option<PartnerRoute> {
+    let mut stops: Vec<PartnerBooth> = partners
+        .iter()
+        .filter(|partner| partner.hall.is_some())
+        .cloned()
+        .collect();
+    if stops.is_empty() {
+        return None;
+    }
+    let hall_rank = |hall: &str| {
+        venue
+            .locations
+            .iter()
+            .position(|location| location.eq_ignore_ascii_case(hall))
+            .unwrap_or(venue.locations.len())
+    };
+    stops.sort_by(|a, b| {
+        let (a_hall, b_hall) = (
+            a.hall.as_deref().unwrap_or_default(),
+            b.hall.as_deref().unwrap_or_default(),
+        );
+        hall_rank(a_hall)
+            .cmp(&hall_rank(b_hall))
+            .then_with(|| a_hall.cmp(b_hall))
+            .then_with(|| a.booth.cmp(&b.booth))
+    });
+
+    let mut walking_minutes = Some(0);
+    for leg in stops.windows(2) {
+        let (Some(from), Some(to)) = (leg[0].hall.as_deref(), leg[1].hall.as_deref()) else {
+            continue;
+        };
+        let minutes = venue.walking_minutes(from, to);
+        walking_minutes = walking_minutes.zip(minutes).map(|(total, leg)| total + leg);
+    }
+    Some(PartnerRoute {
+        stops,
+        walking_minutes,
+    })
+}
+
+// tool 1e: sessions of given tracks on given days ("only climate sessions on Friday").
+// each track is searched by its label, then the results are kept by their classified
+// topics or track keywords and by the date in their text
+
+#[derive(Debug, Deserialize)]
+pub struct FilterByTrackArgs {
+    pub tracks: Vec<Track>,
+    // weekdays, "today", "tomorrow" or dates, every day when empty
+    #[serde(default)]
+    pub days: Vec<String>,
+    // narrows the search within the tracks, e.g. "robotics"
+    #[serde(default)]
+    pub query: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrackSearch {
+    // the days the sources were kept for, empty when every day was asked for
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<NaiveDate>,
+    // days that are not conference days or could not be read
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_days: Vec<String>,
+    // earliest first, undated sources last
+    pub sources: Vec<VivatechSource>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub miss: Option<SearchOutcome>,
+}
+
+pub struct FilterByTrack {
+    search: QueryVivatechAPI,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    today: NaiveDate,
+    year: i32,
+}
+
+impl FilterByTrack {
+    // goes through the regular search, its cache, breaker and fallbacks
+    pub fn new(search: QueryVivatechAPI, profile: &ConferenceProfile) -> Self {
+        Self {
+            search,
+            start_date: profile.start_date,
+            end_date: profile.end_date,
+            today: profile.current_date(),
+            year: profile.year(),
+        }
+    }
+
+    fn resolve_day(&self, day: &str) -> Option<NaiveDate> {
+        resolve_conference_day(day, self.start_date, self.end_date, self.today, self.year)
+    }
+}
+
+// "friday" or "vendredi" is the conference's friday, dates must fall in the conference
+fn resolve_conference_day(
+    day: &str,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    today: NaiveDate,
+    year: i32,
+) -> Option<NaiveDate> {
+    let day = day.trim().to_lowercase();
+    let date = match day.as_str() {
+        "today" | "aujourd'hui" => Some(today),
+        "tomorrow" | "demain" => today.succ_opt(),
+        _ => match weekday_from_name(&day) {
+            Some(weekday) => start_date
+                .iter_days()
+                .take_while(|date| *date <= end_date)
+                .find(|date| date.weekday() == weekday),
+            None => extract_date_from_text(&day, year),
+        },
+    };
+    date.filter(|date| (start_date..=end_date).contains(date))
+}
+
+impl Tool for FilterByTrack {
+    const NAME: &'static str = "filter_by_track";
+    type Error = VivatechApiError;
+    type Args = FilterByTrackArgs;
+    type Output = TrackSearch;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        let tracks: Vec<String> = Track::ALL
+            .iter()
+            .map(|track| {
+                format!(
+                    "{} ({})",
+                    json!(track).as_str().unwrap_or_default(),
+                    track.label()
+                )
+            })
+            .collect();
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: format!(
+                "Finds Vivatech sessions of specific tracks, optionally only on specific days \
+                of the conference ({} to {}). Use it instead of a plain search when the \
+                attendee names a track or theme and a day, e.g. \"only climate sessions on \
+                Friday\". Tracks: {}.",
+                self.start_date.format("%A %B %-d"),
+                self.end_date.format("%A %B %-d"),
+                tracks.join(", ")
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "tracks": {
+                        "type": "array",
+                        "items": {
+                            "type": "string",
+                            "enum": Track::ALL
+                        },
+                        "description": "The tracks to keep, at least one"
+                    },
+                    "days": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Weekdays (\"friday\"), \"today\", \"tomorrow\" or dates (\"2025-06-13\"). Leave empty for every day"
+                    },
+                    "query": {
+                        "type": "string",
+                        "description": "Optional subject within the tracks, e.g. \"robotics\""
+                    }
+                },
+                "required": ["tracks"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let mut seen = HashSet::new();
+        let tracks: Vec<Track> = args
+            .tracks
+            .into_iter()
+            .filter(|track| seen.insert(*track))
+            .collect();
+        let mut days = Vec::new();
+        let mut unknown_days = Vec::new();
+        for day in args.days.iter().filter(|day| !day.trim().is_empty()) {
+            match self.resolve_day(day) {
+                Some(date) if !days.contains(&date) => days.push(date),
+                Some(_) => {}
+                None => unknown_days.push(day.trim().to_string()),
+            }
+        }
+        days.sort();
+        let query = args.query.as_deref().map(str::trim).unwrap_or_default();
+        let describe = || {
+            let labels: Vec<&str> = tracks.iter().map(|track| track.label()).collect();
+            format!("{} {}", labels.join(", "), query)
+                .trim()
+                .to_string()
+        };
+
+        // every day asked for was unreadable, searching would ignore them
+        if tracks.is_empty() || (days.is_empty() && !unknown_days.is_empty()) {
+            return Ok(TrackSearch {
+                days,
+                unknown_days,
+                sources: Vec::new(),
+                miss: Some(SearchOutcome::empty(&describe())),
+            });
+        }
+
+        progress::report(
+            Self::NAME,
+            format!("Filtering the programme by track: {}", describe()),
+        );
+        let outcomes = futures::future::join_all(tracks.iter().map(|track| {
+            self.search.call(QueryVivatechArgs::new(
+                format!("{} {}", track.label(), query).trim(),
+            ))
+        }))
+        .await;
+
+        let mut sources: Vec<VivatechSource> = Vec::new();
+        let mut miss = None;
+        for outcome in outcomes {
+            match outcome {
+                Ok(SearchOutcome::Sources(found)) => {
+                    for source in found {
+                        if !sources.iter().any(|known| known.id == source.id) {
+                            sources.push(source);
+                        }
+                    }
+                }
+                Ok(other) => miss = miss.or(Some(other)),
+                Err(VivatechApiError(reason)) => {
+                    miss = miss.or(Some(SearchOutcome::degraded(&reason)))
+                }
+            }
+        }
+
+        let mut kept: Vec<(Option<NaiveDateTime>, VivatechSource)> = sources
+            .into_iter()
+            .filter(|source| tracks.iter().any(|track| track.matches(source)))
+            .filter_map(|source| {
+                let starts_at = session_slot(&source, self.year).map(|slot| slot.start);
+                let on_day =
+                    days.is_empty() || starts_at.is_some_and(|start| days.contains(&start.date()));
+                on_day.then_some((starts_at, source))
+            })
+            .collect();
+        kept.sort_by(|(a_start, a), (b_start, b)| {
+            (a_start.is_none(), a_start)
+                .cmp(&(b_start.is_none(), b_start))
+                .then_with(|| b.score.total_cmp(&a.score))
+        });
+        let sources: Vec<VivatechSource> = kept.into_iter().map(|(_, source)| source).collect();
+        info!(
+            "Track filter for {} kept {} sources on {} days",
+            describe(),
+            sources.len(),
+            days.len()
+        );
+
+        let miss = match (sources.is_empty(), miss) {
+            (false, _) => None,
+            (true, Some(degraded @ SearchOutcome::Degraded { .. })) => Some(degraded),
+            (true, _) => Some(SearchOutcome::empty(&describe())),
+        };
+        Ok(TrackSearch {
+            days,
+            unknown_days,
+            sources,
+            miss,
+        })
+    }
+}
+
+// english and french names and their three letter forms
+fn weekday_from_name(name: &str) -> Option<Weekday> {
+    let weekday = match name {
+        "lundi" => Weekday::Mon,
+        "mardi" => Weekday::Tue,
+        "mercredi" => Weekday::Wed,
+        "jeudi" => Weekday::Thu,
+        "vendredi" => Weekday::Fri,
+        "samedi" => Weekday::Sat,
+        "dimanche" => Weekday::Sun,
+        other => return Weekday::from_str(other).ok(),
+    };
+    Some(weekday)
+}
+
+// tool 2: assess event timeliness
+#[derive(Debug, Deserialize)]
+pub struct AssessTimelinessArgs {
+    pub events: Vec<VivatechSource>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TimelinessResult {
+    pub source_id: String,
+    pub urgency: ActionUrgency,
+    pub description: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to parse date from event text")]
+pub struct DateParseError;
+
+pub struct AssessTimeliness {
+    // in the venue timezone, event times in the text are read in it too
+    pub now: DateTime<Tz>,
+    // year assumed for dates without one
+    pub year: i32,
+}
+
+impl AssessTimeliness {
+    pub fn for_conference(profile: &ConferenceProfile) -> Self {
+        Self {
+            now: profile.now(),
+            year: profile.year(),
+        }
+    }
+}
+
+impl Tool for AssessTimeliness {
+    const NAME: &'static str = "assess_event_timeliness";
+    type Error = DateParseError;
+    type Args = AssessTimelinessArgs;
+    type Output = Vec<TimelinessResult>;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: format!(
+                "Analyzes a list of conference events to determine their urgency based on the current time at the venue ({}). Use this to prioritize actions.",
+                self.now.format("%B %-d, %Y %H:%M %Z")
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "events": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "id": { 
+                                    "type": "string",
+                                    "description": "Unique identifier of the event"
+                                },
+                                "text_chunk": { 
+                                    "type": "string",
+                                    "description": "Text content describing the event"
+                                },
+                                "source_table": {
+                                    "type": "string",
+                                    "description": "Type of source (e.g., sessions, partners)"
+                                },
+                                "score": {
+                                    "type": "number",
+                                    "description": "Relevance score"
+                                }
+                            },
+                            "required": ["id", "text_chunk"]
+                        },
+                        "description": "List of events to assess for timeliness"
+                    }
+                },
+                "required": ["events"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        progress::report(
+            Self::NAME,
+            format!("Assessing timeliness of {} events", args.events.len()),
+        );
+        let mut results = Vec::new();
+
+        for event in args.events {
+            let session = Session::from_source(&event, self.year);
+            let (urgency, description) = analyze_event_urgency(&session, self.now);
+            results.push(TimelinessResult {
+                source_id: event.id,
+                urgency,
+                description,
+            });
+        }
+
+        Ok(results)
+    }
+}
+
+// tool 3: export selected sessions as an icalendar file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItinerarySession {
+    pub title: String,
+    // venue local time
+    pub start: NaiveDateTime,
+    #[serde(default)]
+    pub end: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub location: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub source_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportItineraryArgs {
+    pub sessions: Vec<ItinerarySession>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum IcalExportError {
+    #[error("Unknown timezone '{0}'")]
+    UnknownTimezone(String),
+    #[error("'{0}' has no valid local start time (daylight saving gap?)")]
+    InvalidTime(String),
+    #[error("'{0}' ends before it starts")]
+    EndBeforeStart(String),
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExportItineraryToIcal {
+    pub calendar_name: String,
+    // iana name the session times are given in
+    pub timezone: String,
+}
+
+impl ExportItineraryToIcal {
+    pub fn for_conference(profile: &ConferenceProfile) -> Self {
+        Self {
+            calendar_name: profile.name.clone(),
+            timezone: profile.timezone.clone(),
+        }
+    }
+}
+
+impl Tool for ExportItineraryToIcal {
+    const NAME: &'static str = "export_itinerary_to_ical";
+    type Error = IcalExportError;
+    type Args = ExportItineraryArgs;
+    type Output = String;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: format!(
+                "Turns selected sessions into an iCalendar (.ics) file the attendee can import into Google Calendar or Outlook. Times are local {} time.",
+                self.timezone
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "sessions": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "title": { "type": "string", "description": "Session title" },
+                                "start": {
+                                    "type": "string",
+                                    "description": "Local start time, e.g. 2025-06-11T14:00:00"
+                                },
+                                "end": {
+                                    "type": "string",
+                                    "description": "Local end time, defaults to one hour after start"
+                                },
+                                "location": { "type": "string", "description": "Stage, hall or booth" },
+                                "description": { "type": "string", "description": "Why the session was picked" },
+                                "source_id": { "type": "string", "description": "Id of the Vivatech source" }
+                            },
+                            "required": ["title", "start"]
+                        },
+                        "description": "Sessions selected for the itinerary"
+                    }
+                },
+                "required": ["sessions"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        progress::report(
+            Self::NAME,
+            format!(
+                "Exporting {} sessions to a calendar file",
+                args.sessions.len()
+            ),
+        );
+        render_ical(&args.sessions, &self.timezone, &self.calendar_name)
+    }
+}
+
+// rfc 5545 calendar, times converted to utc so no VTIMEZONE block is needed
+pub fn render_ical(
+    sessions: &[ItinerarySession],
+    timezone: &str,
+    calendar_name: &str,
+) -> Result<String, IcalExportError> {
+    let tz = Tz::from_str(timezone)
+        .map_err(|_| IcalExportError::UnknownTimezone(timezone.to_string()))?;
+    let stamp = Utc::now().format(ICAL_UTC_FORMAT).to_string();
+
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//vivaagent//Vivatech Strategic Planner//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        format!("X-WR-CALNAME:{}", escape_ical_text(calendar_name)),
+    ];
+
+    for (index, session) in sessions.iter().enumerate() {
+        let end = session
+            .end
+            .unwrap_or(session.start + chrono::Duration::hours(1));
+        if end < session.start {
+            return Err(IcalExportError::EndBeforeStart(session.title.clone()));
+        }
+        let to_utc = |local: NaiveDateTime| {
+            tz.from_local_datetime(&local)
+                .earliest()
+                .map(|time| time.with_timezone(&Utc).format(ICAL_UTC_FORMAT).to_string())
+                .ok_or_else(|| IcalExportError::InvalidTime(session.title.clone()))
+        };
+        let uid = match &session.source_id {
+            Some(id) => format!("{}-{}@vivaagent", id, session.start.format("%Y%m%dT%H%M")),
+            None => format!(
+                "item-{}-{}@vivaagent",
+                index,
+                session.start.format("%Y%m%dT%H%M")
+            ),
+        };
+
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}", uid));
+        lines.push(format!("DTSTAMP:{}", stamp));
+        lines.push(format!("DTSTART:{}", to_utc(session.start)?));
+        lines.push(format!("DTEND:{}", to_utc(end)?));
+        lines.push(format!("SUMMARY:{}", escape_ical_text(&session.title)));
+        if let Some(location) = &session.location {
+            lines.push(format!("LOCATION:{}", escape_ical_text(location)));
+        }
+        if let Some(description) = &session.description {
+            lines.push(format!("DESCRIPTION:{}", escape_ical_text(description)));
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut ics = String::new();
+    for line in &lines {
+        ics.push_str(&fold_ical_line(line));
+        ics.push_str("\r\n");
+    }
+    Ok(ics)
+}
+
+const ICAL_UTC_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+fn escape_ical_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+// content lines are limited to 75 octets, continuations start with a space
+fn fold_ical_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + line.len() / 74 * 3);
+    let mut width = 0;
+    for c in line.chars() {
+        let len = c.len_utf8();
+        if width + len > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += len;
+    }
+    folded
+}
+
+// tool 4: find overlapping sessions in a selection and suggest replacements
+const DEFAULT_SESSION_MINUTES: i64 = 60;
+const MAX_ALTERNATIVES: usize = 3;
+
+#[derive(Debug, Deserialize)]
+pub struct DetectConflictsArgs {
+    pub sessions: Vec<VivatechSource>,
+    // other sessions found while planning, used for replacements
+    #[serde(default)]
+    pub candidates: Vec<VivatechSource>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionSlot {
+    pub source_id: String,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    // no end time in the text, assumed to last an hour
+    pub end_estimated: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScheduleConflict {
+    pub first: String,
+    // the later session, the one the alternatives would replace
+    pub second: String,
+    pub overlap_minutes: i64,
+    pub alternatives: Vec<SessionSlot>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConflictReport {
+    pub summary: String,
+    pub conflicts: Vec<ScheduleConflict>,
+    // sessions without a readable date and time, not checked
+    pub unscheduled: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DetectScheduleConflicts {
+    // year assumed for dates without one
+    pub year: i32,
+}
+
+impl DetectScheduleConflicts {
+    pub fn for_conference(profile: &ConferenceProfile) -> Self {
+        Self {
+            year: profile.year(),
+        }
+    }
+}
+
+impl Tool for DetectScheduleConflicts {
+    const NAME: &'static str = "detect_schedule_conflicts";
+    type Error = std::convert::Infallible;
+    type Args = DetectConflictsArgs;
+    type Output = ConflictReport;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        let source = json!({
+            "type": "object",
+            "properties": {
+                "id": { "type": "string", "description": "Unique identifier of the session" },
+                "text_chunk": { "type": "string", "description": "Text describing the session, with its date and time" }
+            },
+            "required": ["id", "text_chunk"]
+        });
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: "Checks the sessions picked for a plan for time overlaps and suggests non-overlapping replacements from the candidates. Call it before finalizing a plan.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "sessions": {
+                        "type": "array",
+                        "items": source,
+                        "description": "Sessions picked for the plan"
+                    },
+                    "candidates": {
+                        "type": "array",
+                        "items": source,
+                        "description": "Other sessions found that could replace a conflicting one"
+                    }
+                },
+                "required": ["sessions"]
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        progress::report(
+            Self::NAME,
+            format!(
+                "Checking {} sessions for schedule conflicts",
+                args.sessions.len()
+            ),
+        );
+        Ok(detect_conflicts(
+            &args.sessions,
+            &args.candidates,
+            self.year,
+        ))
+    }
+}
+
+pub fn detect_conflicts(
+    sessions: &[VivatechSource],
+    candidates: &[VivatechSource],
+    year: i32,
+) -> ConflictReport {
+    let mut slots = Vec::new();
+    let mut unscheduled = Vec::new();
+    for session in sessions {
+        match session_slot(session, year) {
+            Some(slot) => slots.push((slot, session)),
+            None => unscheduled.push(session.id.clone()),
+        }
+    }
+    slots.sort_by_key(|(slot, _)| slot.start);
+
+    let mut conflicts = Vec::new();
+    for (i, (first, _)) in slots.iter().enumerate() {
+        for (second, second_source) in &slots[i + 1..] {
+            if second.start >= first.end {
+                continue;
+            }
+            let overlap = first.end.min(second.end) - second.start;
+            conflicts.push(ScheduleConflict {
+                first: first.source_id.clone(),
+                second: second.source_id.clone(),
+                overlap_minutes: overlap.num_minutes(),
+                alternatives: alternatives_for(second_source, &slots, sessions, candidates, year),
+            });
+        }
+    }
+
+    let summary = match conflicts.len() {
+        0 => format!("No overlaps among {} timed sessions.", slots.len()),
+        n => format!(
+            "{} overlapping pair(s). Keep one session of each pair and replace or drop the other.",
+            n
+        ),
+    };
+    ConflictReport {
+        summary,
+        conflicts,
+        unscheduled,
+    }
+}
+
+// candidates that fit around every other pick, most similar to the replaced session first
+fn alternatives_for(
+    replaced: &VivatechSource,
+    slots: &[(SessionSlot, &VivatechSource)],
+    sessions: &[VivatechSource],
+    candidates: &[VivatechSource],
+    year: i32,
+) -> Vec<SessionSlot> {
+    let replaced_words = topic_words(&replaced.text_chunk);
+    let mut fitting: Vec<(usize, SessionSlot)> = candidates
+        .iter()
+        .filter(|candidate| !sessions.iter().any(|s| s.id == candidate.id))
+        .filter_map(|candidate| {
+            let slot = session_slot(candidate, year)?;
+            let fits = slots.iter().all(|(other, _)| {
+                other.source_id == replaced.id || slot.end <= other.start || slot.start >= other.end
+            });
+            let shared = topic_words(&candidate.text_chunk)
+                .intersection(&replaced_words)
+                .count();
+            fits.then_some((shared, slot))
+        })
+        .collect();
+    fitting.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.start.cmp(&b.1.start)));
+    fitting
+        .into_iter()
+        .take(MAX_ALTERNATIVES)
+        .map(|(_, slot)| slot)
+        .collect()
+}
+
+fn topic_words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.len() > 3)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+// the session's first day from its start, an hour long when it has no end
+pub fn session_slot(source: &VivatechSource, year: i32) -> Option<SessionSlot> {
+    let session = Session::from_source(source, year);
+    let start = session.start?;
+    let end = session.end;
+
+    Some(SessionSlot {
+        source_id: source.id.clone(),
+        start,
+        end: end.unwrap_or(start + chrono::Duration::minutes(DEFAULT_SESSION_MINUTES)),
+        end_estimated: end.is_none(),
+    })
+}
+
+// tool 5: the venue's weather on a conference day, for rooftop and outdoor side events
+// and for getting there. forecasts are cached for an hour
+const WEATHER_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+const WEATHER_CACHE_SECONDS: u64 = 3600;
+// the hours the advice looks at, from the morning commute to the last side events
+const DAY_HOURS: std::ops::RangeInclusive<u32> = 7..=22;
+const EVENING_FROM: u32 = 17;
+
+static WEATHER_CACHE: OnceLock<Cache<String, Vec<HourlyWeather>>> = OnceLock::new();
+
+#[derive(Debug, Deserialize)]
+pub struct WeatherArgs {
+    // "today", "tomorrow", a weekday or a date, today when absent
+    #[serde(default)]
+    pub day: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HourlyWeather {
+    pub time: NaiveTime,
+    pub temperature_c: f64,
+    // percent, unknown for some past days
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rain_chance: Option<u32>,
+    pub wind_kmh: f64,
+    pub conditions: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WeatherReport {
+    pub date: NaiveDate,
+    pub location: String,
+    pub temperature_min_c: f64,
+    pub temperature_max_c: f64,
+    // from 07:00 to 22:00 venue time
+    pub hours: Vec<HourlyWeather>,
+    // what it means for outdoor events and the commute
+    pub advice: Vec<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct WeatherError(String);
+
+#[derive(Debug, Deserialize)]
+struct ForecastResponse {
+    hourly: HourlyForecast,
+}
+
+#[derive(Debug, Deserialize)]
+struct HourlyForecast {
+    // venue local time, e.g. "2025-06-12T14:00"
+    time: Vec<String>,
+    temperature_2m: Vec<Option<f64>>,
+    #[serde(default)]
+    precipitation_probability: Vec<Option<f64>>,
+    weather_code: Vec<Option<u32>>,
+    wind_speed_10m: Vec<Option<f64>>,
+}
+
+pub struct GetParisWeather {
+    location: String,
+    coordinates: GeoPoint,
+    tz: Tz,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    today: NaiveDate,
+    year: i32,
+}
+
+impl GetParisWeather {
+    // None when the venue has no coordinates or WEATHER_ENABLED is off
+    pub fn for_conference(profile: &ConferenceProfile) -> Option<Self> {
+        if !config::get().weather_enabled {
+            return None;
+        }
+        Some(Self {
+            location: profile.venue.name.clone(),
+            coordinates: profile.venue.coordinates?,
+            tz: profile.tz(),
+            start_date: profile.start_date,
+            end_date: profile.end_date,
+            today: profile.current_date(),
+            year: profile.year(),
+        })
+    }
+
+    async fn hourly(&self, date: NaiveDate) -> Result<Vec<HourlyWeather>, WeatherError> {
+        if mock::mock_mode() {
+            return Ok(mock_weather());
+        }
+        let cache = WEATHER_CACHE.get_or_init(|| {
+            Cache::builder()
+                .max_capacity(64)
+                .time_to_live(std::time::Duration::from_secs(WEATHER_CACHE_SECONDS))
+                .build()
+        });
+        let key = format!(
+            "{},{},{}",
+            self.coordinates.latitude, self.coordinates.longitude, date
+        );
+        if let Some(hours) = cache.get(&key) {
+            return Ok(hours);
+        }
+        let hours = self.fetch(date).await?;
+        cache.insert(key, hours.clone());
+        Ok(hours)
+    }
+
+    async fn fetch(&self, date: NaiveDate) -> Result<Vec<HourlyWeather>, WeatherError> {
+        let date = date.format("%Y-%m-%d").to_string();
+        let forecast = http::client()
+            .get(&config::get().weather_api_url)
+            .timeout(WEATHER_TIMEOUT)
+            .query(&[
+                ("latitude", self.coordinates.latitude.to_string()),
+                ("longitude", self.coordinates.longitude.to_string()),
+                (
+                    "hourly",
+                    "temperature_2m,precipitation_probability,weather_code,wind_speed_10m"
+                        .to_string(),
+                ),
+                ("timezone", self.tz.name().to_string()),
+                ("start_date", date.clone()),
+                ("end_date", date),
+            ])
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| WeatherError(format!("Weather forecast unavailable: {}", e)))?
+            .json::<ForecastResponse>()
+            .await
+            .map_err(|e| WeatherError(format!("Unreadable weather forecast: {}", e)))?;
+
+        let hourly = forecast.hourly;
+        let hours = hourly
+            .time
+            .iter()
+            .enumerate()
+            .filter_map(|(i, time)| {
+                Some(HourlyWeather {
+                    time: NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M")
+                        .ok()?
+                        .time(),
+                    temperature_c: (*hourly.temperature_2m.get(i)?)?,
+                    rain_chance: hourly
+                        .precipitation_probability
+                        .get(i)
+                        .copied()
+                        .flatten()
+                        .map(|chance| chance.round() as u32),
+                    wind_kmh: hourly
+                        .wind_speed_10m
+                        .get(i)
+                        .copied()
+                        .flatten()
+                        .unwrap_or(0.0),
+                    conditions: weather_conditions(
+                        hourly.weather_code.get(i).copied().flatten().unwrap_or(0),
+                    ),
+                })
+            })
+            .collect::<Vec<_>>();
+        if hours.is_empty() {
+            return Err(WeatherError(
+                "No weather forecast for that day yet".to_string(),
+            ));
+        }
+        Ok(hours)
+    }
+}
+
+impl Tool for GetParisWeather {
+    const NAME: &'static str = "get_paris_weather";
+    type Error = WeatherError;
+    type Args = WeatherArgs;
+    type Output = WeatherReport;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        ToolDefinition {
+            name: Self::NAME.to_string(),
+            description: format!(
+                "Hourly weather at {} on a conference day, with advice for rooftop and outdoor networking events and for the commute. Use it before recommending outdoor side events.",
+                self.location
+            ),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "day": {
+                        "type": "string",
+                        "description": "Conference day: \"today\", \"tomorrow\", a weekday such as \"friday\", or a date. Today when omitted"
+                    }
+                }
+            }),
+        }
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let date = match args.day.as_deref() {
+            None => self.today,
+            Some(day) => {
+                resolve_conference_day(day, self.start_date, self.end_date, self.today, self.year)
+                    .ok_or_else(|| WeatherError(format!("'{}' is not a conference day", day)))?
+            }
+        };
+        progress::report(Self::NAME, format!("Checking the weather for {}", date));
+
+        let hours: Vec<HourlyWeather> = self
+            .hourly(date)
+            .await?
+            .into_iter()
+            .filter(|hour| DAY_HOURS.contains(&hour.time.hour()))
+            .collect();
+        if hours.is_empty() {
+            return Err(WeatherError(format!(
+                "No weather forecast for {} yet",
+                date
+            )));
+        }
+        let temperatures = hours.iter().map(|hour| hour.temperature_c);
+        Ok(WeatherReport {
+            date,
+            location: self.location.clone(),
+            temperature_min_c: temperatures.clone().fold(f64::INFINITY, f64::min),
+            temperature_max_c: temperatures.fold(f64::NEG_INFINITY, f64::max),
+            advice: weather_advice(&hours),
+            hours,
+        })
+    }
+}
 
-// get api url from env
-fn get_vivatech_api_url() -> Result<String, VivatechApiError> {
-    std::env::var("VIVATECH_API_URL")
-        .map_err(|_| VivatechApiError("VIVATECH_API_URL not found in environment".to_string()))
+// wmo weather interpretation codes
+fn weather_conditions(code: u32) -> &'static str {
+    match code {
+        0 => "clear",
+        1 | 2 => "partly cloudy",
+        3 => "overcast",
+        45 | 48 => "fog",
+        51..=57 => "drizzle",
+        61..=67 | 80..=82 => "rain",
+        71..=77 | 85 | 86 => "snow",
+        95..=99 => "thunderstorm",
+        _ => "unknown",
+    }
 }
 
-// api timeout with fallback
-fn get_api_timeout_seconds() -> u64 {
-    std::env::var("API_TIMEOUT_SECONDS")
-        .ok()
-        .and_then(|s| s.parse::<u64>().ok())
-        .unwrap_or(30)
+fn is_wet(hour: &HourlyWeather) -> bool {
+    hour.rain_chance.is_some_and(|chance| chance >= 50)
+        || matches!(
+            hour.conditions,
+            "drizzle" | "rain" | "snow" | "thunderstorm"
+        )
+}
+
+fn weather_advice(hours: &[HourlyWeather]) -> Vec<String> {
+    let mut advice = Vec::new();
+    let morning: Vec<&HourlyWeather> = hours.iter().filter(|h| h.time.hour() <= 9).collect();
+    let evening: Vec<&HourlyWeather> = hours
+        .iter()
+        .filter(|h| h.time.hour() >= EVENING_FROM)
+        .collect();
+
+    if morning.iter().any(|hour| is_wet(hour)) {
+        advice.push(
+            "Rain likely during the morning commute: allow extra time and prefer the metro or tram to walking.".to_string(),
+        );
+    }
+    match evening.iter().find(|hour| is_wet(hour)) {
+        Some(hour) => advice.push(format!(
+            "Rain likely from {}: keep an indoor alternative for rooftop and outdoor side events.",
+            hour.time.format("%H:%M")
+        )),
+        None if !evening.is_empty() => {
+            let coolest = evening
+                .iter()
+                .map(|hour| hour.temperature_c)
+                .fold(f64::INFINITY, f64::min);
+            if coolest < 14.0 {
+                advice.push(format!(
+                    "Dry but cool evening, down to {:.0}°C: outdoor events work with a jacket.",
+                    coolest
+                ));
+            } else {
+                advice.push(
+                    "Dry, mild evening: good for rooftop and outdoor networking.".to_string(),
+                );
+            }
+        }
+        None => {}
+    }
+    if let Some(windiest) = hours
+        .iter()
+        .max_by(|a, b| a.wind_kmh.total_cmp(&b.wind_kmh))
+    {
+        if windiest.wind_kmh >= 40.0 {
+            advice.push(format!(
+                "Strong wind around {} ({:.0} km/h): rooftop venues may close.",
+                windiest.time.format("%H:%M"),
+                windiest.wind_kmh
+            ));
+        }
+    }
+    if let Some(hottest) = hours
+        .iter()
+        .max_by(|a, b| a.temperature_c.total_cmp(&b.temperature_c))
+    {
+        if hottest.temperature_c >= 30.0 {
+            advice.push(format!(
+                "Hot afternoon, up to {:.0}°C: outdoor queues and terraces will be tiring, plan water and shade.",
+                hottest.temperature_c
+            ));
+        }
+    }
+    advice
+}
+
+// MOCK_MODE: a mild, dry june day without calling the weather api
+fn mock_weather() -> Vec<HourlyWeather> {
+    (0..24)
+        .filter_map(|hour| {
+            Some(HourlyWeather {
+                time: NaiveTime::from_hms_opt(hour, 0, 0)?,
+                temperature_c: 14.0 + 8.0 * (1.0 - ((hour as f64 - 15.0) / 9.0).powi(2)).max(0.0),
+                rain_chance: Some(10),
+                wind_kmh: 12.0,
+                conditions: "partly cloudy",
+            })
+        })
+        .collect()
+}
+
+// tool 6: time to get from one place to the next, so back-to-back sessions in different
+// halls or a morning start from the hotel leave a realistic gap. halls come from the
+// venue's walking matrix, other places from the routing api when ROUTING_API_KEY is set
+const ROUTING_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+const MAX_TRAVEL_LEGS: usize = 12;
+// queues at hall doors and finding a seat
+const HALL_BUFFER_MINUTES: u32 = 5;
+// security at the venue entrance and getting to the first hall
+const ARRIVAL_BUFFER_MINUTES: u32 = 15;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TravelMode {
+    #[default]
+    Transit,
+    Walking,
+    Driving,
+    Bicycling,
+}
+
+impl TravelMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            TravelMode::Transit => "transit",
+            TravelMode::Walking => "walking",
+            TravelMode::Driving => "driving",
+            TravelMode::Bicycling => "bicycling",
+        }
+    }
 }
 
-// tool 1: search vivatech database
 #[derive(Debug, Deserialize)]
-pub struct QueryVivatechArgs {
-    pub query: String,
+pub struct TravelLeg {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TravelArgs {
+    pub legs: Vec<TravelLeg>,
+    // for legs outside the venue, transit when absent
+    #[serde(default)]
+    pub mode: TravelMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TravelSource {
+    SameLocation,
+    VenueMatrix,
+    RoutingApi,
+    Unknown,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TravelEstimate {
+    pub from: String,
+    pub to: String,
+    // door to door, none when it can't be estimated
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minutes: Option<u32>,
+    // what to leave between the end of one session and the start of the next
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gap_minutes: Option<u32>,
+    pub source: TravelSource,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TravelReport {
+    pub legs: Vec<TravelEstimate>,
 }
 
 #[derive(Debug, thiserror::Error)]
-#[error("Vivatech API Error: {0}")]
-pub struct VivatechApiError(String);
+#[error("{0}")]
+pub struct TravelError(String);
 
-#[derive(Serialize, Deserialize)]
-pub struct QueryVivatechAPI;
+#[derive(Debug, Deserialize)]
+struct DistanceMatrixResponse {
+    status: String,
+    #[serde(default)]
+    rows: Vec<DistanceMatrixRow>,
+}
 
-impl Tool for QueryVivatechAPI {
-    const NAME: &'static str = "query_vivatech_api";
-    type Error = VivatechApiError;
-    type Args = QueryVivatechArgs;
-    type Output = Vec<VivatechSource>;
+#[derive(Debug, Deserialize)]
+struct DistanceMatrixRow {
+    elements: Vec<DistanceMatrixElement>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DistanceMatrixElement {
+    status: String,
+    #[serde(default)]
+    duration: Option<DistanceMatrixDuration>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DistanceMatrixDuration {
+    // seconds
+    value: u64,
+}
+
+pub struct EstimateTravelTime {
+    venue: VenueGraph,
+}
+
+impl EstimateTravelTime {
+    pub fn for_conference(profile: &ConferenceProfile) -> Self {
+        Self {
+            venue: profile.venue.clone(),
+        }
+    }
+
+    // the venue's name stands for its entrance
+    fn is_venue(&self, place: &str) -> bool {
+        place.eq_ignore_ascii_case(&self.venue.name)
+            || place.to_lowercase().contains("porte de versailles")
+    }
+
+    // the routing api gets coordinates for the venue, it doesn't know "Hall 1"
+    fn routing_place(&self, place: &str) -> String {
+        match self.venue.coordinates {
+            Some(point) if self.is_venue(place) || self.venue.location(place).is_some() => {
+                format!("{},{}", point.latitude, point.longitude)
+            }
+            _ => place.to_string(),
+        }
+    }
+
+    async fn estimate(&self, leg: TravelLeg, mode: TravelMode) -> TravelEstimate {
+        let (from, to) = (leg.from.trim(), leg.to.trim());
+        let halls = (self.venue.location(from), self.venue.location(to));
+        if let (Some(from_hall), Some(to_hall)) = halls {
+            return match self.venue.walking_minutes(from_hall, to_hall) {
+                Some(0) => TravelEstimate {
+                    from: leg.from,
+                    to: leg.to,
+                    minutes: Some(0),
+                    gap_minutes: Some(HALL_BUFFER_MINUTES),
+                    source: TravelSource::SameLocation,
+                    note: None,
+                },
+                Some(minutes) => TravelEstimate {
+                    from: leg.from,
+                    to: leg.to,
+                    minutes: Some(minutes),
+                    gap_minutes: Some(minutes + HALL_BUFFER_MINUTES),
+                    source: TravelSource::VenueMatrix,
+                    note: None,
+                },
+                None => TravelEstimate {
+                    from: leg.from,
+                    to: leg.to,
+                    minutes: None,
+                    gap_minutes: None,
+                    source: TravelSource::Unknown,
+                    note: Some("No walking time between these halls".to_string()),
+                },
+            };
+        }
+
+        let arriving = halls.1.is_some() || self.is_venue(to);
+        match self.route(from, to, mode).await {
+            Ok(minutes) => TravelEstimate {
+                from: leg.from,
+                to: leg.to,
+                minutes: Some(minutes),
+                gap_minutes: Some(if arriving {
+                    minutes + ARRIVAL_BUFFER_MINUTES
+                } else {
+                    minutes
+                }),
+                source: TravelSource::RoutingApi,
+                note: arriving.then(|| {
+                    format!(
+                        "Includes {} minutes for security at the entrance",
+                        ARRIVAL_BUFFER_MINUTES
+                    )
+                }),
+            },
+            Err(e) => TravelEstimate {
+                from: leg.from,
+                to: leg.to,
+                minutes: None,
+                gap_minutes: None,
+                source: TravelSource::Unknown,
+                note: Some(e.to_string()),
+            },
+        }
+    }
+
+    async fn route(&self, from: &str, to: &str, mode: TravelMode) -> Result<u32, TravelError> {
+        let config = config::get();
+        let Some(key) = config.routing_api_key.as_deref() else {
+            return Err(TravelError(
+                "No routing configured for places outside the venue".to_string(),
+            ));
+        };
+        if mock::mock_mode() {
+            return Err(TravelError(
+                "Routing is not called in mock mode".to_string(),
+            ));
+        }
+        let matrix = http::client()
+            .get(&config.routing_api_url)
+            .timeout(ROUTING_TIMEOUT)
+            .query(&[
+                ("origins", self.routing_place(from)),
+                ("destinations", self.routing_place(to)),
+                ("mode", mode.as_str().to_string()),
+                ("key", key.to_string()),
+            ])
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| TravelError(format!("Routing unavailable: {}", e.without_url())))?
+            .json::<DistanceMatrixResponse>()
+            .await
+            .map_err(|e| TravelError(format!("Unreadable routing response: {}", e)))?;
+        if matrix.status != "OK" {
+            return Err(TravelError(format!("Routing failed: {}", matrix.status)));
+        }
+        let element = matrix
+            .rows
+            .into_iter()
+            .next()
+            .and_then(|row| row.elements.into_iter().next())
+            .ok_or_else(|| TravelError("No route found".to_string()))?;
+        match (element.status.as_str(), element.duration) {
+            ("OK", Some(duration)) => Ok(duration.value.div_ceil(60) as u32),
+            (status, _) => Err(TravelError(format!("No route found: {}", status))),
+        }
+    }
+}
+
+impl Tool for EstimateTravelTime {
+    const NAME: &'static str = "estimate_travel_time";
+    type Error = TravelError;
+    type Args = TravelArgs;
+    type Output = TravelReport;
 
     async fn definition(&self, _prompt: String) -> ToolDefinition {
         ToolDefinition {
             name: Self::NAME.to_string(),
-            description: "Searches the Vivatech conference database for sessions and partners related to a query.".to_string(),
+            description: format!(
+                "Travel time between places, for leaving realistic gaps between back-to-back sessions. Halls of {} ({}) use walking times at the venue; a hotel or another address is routed to the venue. Returns the minutes to leave between the end of one session and the start of the next.",
+                self.venue.name,
+                self.venue.locations.join(", ")
+            ),
             parameters: json!({
                 "type": "object",
                 "properties": {
-                    "query": {
+                    "legs": {
+                        "type": "array",
+                        "minItems": 1,
+                        "maxItems": MAX_TRAVEL_LEGS,
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "from": {
+                                    "type": "string",
+                                    "description": "A hall such as \"Hall 1\", the venue, or an address or hotel name"
+                                },
+                                "to": {
+                                    "type": "string",
+                                    "description": "A hall such as \"Hall 2\", the venue, or an address or hotel name"
+                                }
+                            },
+                            "required": ["from", "to"]
+                        }
+                    },
+                    "mode": {
                         "type": "string",
-                        "description": "The search term to find relevant Vivatech sessions or partners"
+                        "enum": ["transit", "walking", "driving", "bicycling"],
+                        "description": "How the attendee travels outside the venue, transit when omitted"
                     }
                 },
-                "required": ["query"]
+                "required": ["legs"]
             }),
         }
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        let client = create_http_client()?;
-        let request_body = json!({ "query": args.query });
-        let api_url = get_vivatech_api_url()?;
-        let response = make_api_request(&client, &api_url, &request_body).await?;
-        let api_response = parse_api_response::<VivatechQueryResponse>(response).await?;
-        Ok(api_response.sources)
+        if args.legs.is_empty() {
+            return Err(TravelError("Give at least one leg".to_string()));
+        }
+        if args.legs.len() > MAX_TRAVEL_LEGS {
+            return Err(TravelError(format!(
+                "At most {} legs per call",
+                MAX_TRAVEL_LEGS
+            )));
+        }
+        progress::report(
+            Self::NAME,
+            format!("Estimating {} transitions", args.legs.len()),
+        );
+        let legs = futures::future::join_all(
+            args.legs
+                .into_iter()
+                .map(|leg| self.estimate(leg, args.mode)),
+        )
+        .await;
+        Ok(TravelReport { legs })
     }
 }
 
-// tool 2: assess event timeliness
+// tool 7: who to meet. searches exhibitors and startups for each of the attendee's
+// interests and turns the best matches into an outreach shortlist with talking points
+
+// interests searched per call, the rest are dropped
+const MAX_NETWORKING_INTERESTS: usize = 5;
+const MAX_NETWORKING_TARGETS: usize = 8;
+// a partner matching several interests beats a slightly better single match
+const EXTRA_INTEREST_BONUS: f32 = 0.1;
+
 #[derive(Debug, Deserialize)]
-pub struct AssessTimelinessArgs {
-    pub events: Vec<VivatechSource>,
+pub struct NetworkingArgs {
+    // e.g. "CTO at a seed-stage startup"
+    pub role: String,
+    pub interests: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NetworkingTarget {
+    #[serde(flatten)]
+    pub partner: PartnerBooth,
+    // the attendee's interests the partner came up for
+    pub matched_interests: Vec<String>,
+    pub talking_points: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
-pub struct TimelinessResult {
-    pub source_id: String,
-    pub urgency: ActionUrgency,
-    pub description: String,
+pub struct NetworkingShortlist {
+    // best match first
+    pub targets: Vec<NetworkingTarget>,
+    // set when at least one target has a hall
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub route: Option<PartnerRoute>,
+    // interests no partner came up for, or whose search could not run
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub misses: Vec<SearchOutcome>,
 }
 
-#[derive(Debug, thiserror::Error)]
-#[error("Failed to parse date from event text")]
-pub struct DateParseError;
+pub struct SuggestNetworkingTargets {
+    search: QueryVivatechAPI,
+    venue: VenueGraph,
+}
 
-#[derive(Serialize, Deserialize)]
-pub struct AssessTimeliness;
+impl SuggestNetworkingTargets {
+    // goes through the regular search, its cache, breaker and fallbacks
+    pub fn new(search: QueryVivatechAPI, profile: &ConferenceProfile) -> Self {
+        Self {
+            search,
+            venue: profile.venue.clone(),
+        }
+    }
+}
 
-impl Tool for AssessTimeliness {
-    const NAME: &'static str = "assess_event_timeliness";
-    type Error = DateParseError;
-    type Args = AssessTimelinessArgs;
-    type Output = Vec<TimelinessResult>;
+impl Tool for SuggestNetworkingTargets {
+    const NAME: &'static str = "suggest_networking_targets";
+    type Error = VivatechApiError;
+    type Args = NetworkingArgs;
+    type Output = NetworkingShortlist;
 
     async fn definition(&self, _prompt: String) -> ToolDefinition {
         ToolDefinition {
             name: Self::NAME.to_string(),
-            description: "Analyzes a list of Vivatech events to determine their urgency based on the current date (June 11, 2025). Use this to prioritize actions.".to_string(),
+            description: format!(
+                "Builds an outreach shortlist of up to {} partners and startups worth meeting \
+                for the attendee's role and interests, with their hall and booth, the \
+                interests they match, talking points to open with, and a walking route \
+                through their booths.",
+                MAX_NETWORKING_TARGETS
+            ),
             parameters: json!({
                 "type": "object",
                 "properties": {
-                    "events": {
+                    "role": {
+                        "type": "string",
+                        "description": "What the attendee does, e.g. \"CTO at a seed-stage startup\" or \"climate VC\""
+                    },
+                    "interests": {
                         "type": "array",
-                        "items": {
-                            "type": "object",
-                            "properties": {
-                                "id": { 
-                                    "type": "string",
-                                    "description": "Unique identifier of the event"
-                                },
-                                "text_chunk": { 
-                                    "type": "string",
-                                    "description": "Text content describing the event"
-                                },
-                                "source_table": {
-                                    "type": "string",
-                                    "description": "Type of source (e.g., sessions, partners)"
-                                },
-                                "score": {
-                                    "type": "number",
-                                    "description": "Relevance score"
-                                }
-                            },
-                            "required": ["id", "text_chunk"]
-                        },
-                        "description": "List of events to assess for timeliness"
+                        "items": { "type": "string" },
+                        "minItems": 1,
+                        "description": "Topics the attendee wants to meet people about, e.g. [\"generative AI\", \"retail\"]"
                     }
                 },
-                "required": ["events"]
+                "required": ["role", "interests"]
             }),
         }
     }
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
-        let current_date = get_current_conference_date();
-        let mut results = Vec::new();
+        let role = args.role.trim();
+        let mut seen = HashSet::new();
+        let interests: Vec<String> = args
+            .interests
+            .into_iter()
+            .map(|interest| interest.trim().to_string())
+            .filter(|interest| !interest.is_empty() && seen.insert(interest.to_lowercase()))
+            .take(MAX_NETWORKING_INTERESTS)
+            .collect();
+        if interests.is_empty() {
+            return Err(VivatechApiError("Give at least one interest".to_string()));
+        }
+        progress::report(
+            Self::NAME,
+            format!("Finding people to meet about {}", interests.join(", ")),
+        );
 
-        for event in args.events {
-            let (urgency, description) = analyze_event_urgency(&event.text_chunk, current_date);
-            results.push(TimelinessResult {
-                source_id: event.id,
-                urgency,
-                description,
-            });
+        // partner texts are written as "<name> booth - <hall>, booth <number>"
+        let outcomes = futures::future::join_all(interests.iter().map(|interest| {
+            self.search
+                .call(QueryVivatechArgs::new(format!("{} booth", interest)))
+        }))
+        .await;
+
+        let mut targets: Vec<NetworkingTarget> = Vec::new();
+        let mut misses = Vec::new();
+        for (interest, outcome) in interests.iter().zip(outcomes) {
+            let sources = match outcome {
+                Ok(SearchOutcome::Sources(sources)) => sources,
+                Ok(miss) => {
+                    misses.push(miss);
+                    continue;
+                }
+                Err(VivatechApiError(reason)) => {
+                    misses.push(SearchOutcome::degraded(&reason));
+                    continue;
+                }
+            };
+            let partners: Vec<PartnerBooth> = sources
+                .iter()
+                .filter(|source| source.source_table == "partners")
+                .map(partner_booth)
+                .collect();
+            if partners.is_empty() {
+                misses.push(SearchOutcome::empty(interest));
+            }
+            for partner in partners {
+                match targets
+                    .iter_mut()
+                    .find(|target| target.partner.id == partner.id)
+                {
+                    Some(target) => {
+                        target.partner.score =
+                            target.partner.score.max(partner.score) + EXTRA_INTEREST_BONUS;
+                        target.matched_interests.push(interest.clone());
+                    }
+                    None => targets.push(NetworkingTarget {
+                        partner,
+                        matched_interests: vec![interest.clone()],
+                        talking_points: Vec::new(),
+                    }),
+                }
+            }
+        }
+        targets.sort_by(|a, b| b.partner.score.total_cmp(&a.partner.score));
+        targets.truncate(MAX_NETWORKING_TARGETS);
+        for target in &mut targets {
+            target.talking_points = talking_points(role, target);
         }
+        info!(
+            "Shortlisted {} networking targets for {}",
+            targets.len(),
+            role
+        );
 
-        Ok(results)
+        let booths: Vec<PartnerBooth> = targets
+            .iter()
+            .map(|target| target.partner.clone())
+            .collect();
+        Ok(NetworkingShortlist {
+            route: walking_route(&booths, &self.venue),
+            targets,
+            misses,
+        })
     }
 }
 
-// helper functions
-fn create_http_client() -> Result<Client, VivatechApiError> {
-    Client::builder()
-        .timeout(std::time::Duration::from_secs(get_api_timeout_seconds()))
-        .build()
-        .map_err(|e| VivatechApiError(format!("Failed to create HTTP client: {}", e)))
+// what the attendee's role is after, by keywords in how they describe it
+fn role_angle(role: &str) -> &'static str {
+    let role = role.to_lowercase();
+    let has = |words: &[&str]| words.iter().any(|word| role.contains(word));
+    if has(&["investor", "vc", "venture", "angel", "fund"]) {
+        "Ask about their stage, traction and whether they are raising"
+    } else if has(&[
+        "cto",
+        "engineer",
+        "developer",
+        "architect",
+        "tech lead",
+        "data",
+    ]) {
+        "Ask about their technical stack and how their product integrates with yours"
+    } else if has(&["founder", "ceo", "co-founder", "owner"]) {
+        "Explore a partnership, pilot or co-marketing opportunity"
+    } else if has(&["sales", "business development", "bizdev", "partnership"]) {
+        "Find out who owns purchasing decisions and what they are looking to buy"
+    } else if has(&["recruit", "talent", "hr", "people"]) {
+        "Ask which roles they are hiring for and how they find talent"
+    } else if has(&["student", "job", "career"]) {
+        "Ask about internships, open positions and what their teams work on"
+    } else {
+        "Ask what they are launching or showing at the conference this year"
+    }
+}
+
+fn talking_points(role: &str, target: &NetworkingTarget) -> Vec<String> {
+    let mut points = Vec::new();
+    let summary = target
+        .partner
+        .description
+        .split_inclusive(['.', '!', '?'])
+        .next()
+        .map(str::trim)
+        .filter(|sentence| !sentence.is_empty());
+    if let Some(summary) = summary {
+        points.push(format!("Open with what they do: {}", summary));
+    }
+    points.push(format!(
+        "Ask how {} approaches {}",
+        target.partner.name,
+        target.matched_interests.join(" and ")
+    ));
+    points.push(role_angle(role).to_string());
+    points
 }
 
-async fn make_api_request(
+// helper functions
+pub async fn make_api_request(
     client: &Client,
     url: &str,
     body: &serde_json::Value,
 ) -> Result<reqwest::Response, VivatechApiError> {
-    let response = client
-        .post(url)
-        .json(body)
-        .send()
-        .await
-        .map_err(|e| VivatechApiError(format!("HTTP request failed: {}", e)))?;
+    let policy = &config::get().retry;
+    let mut attempt = 1;
+
+    // serialized once so the signature covers the exact bytes sent
+    let payload = serde_json::to_vec(body)
+        .map_err(|e| VivatechApiError(format!("Failed to serialize request: {}", e)))?;
+    let signing = UpstreamSigning::for_url(url);
+    let mut skew_corrected = false;
+
+    loop {
+        metrics::counter!("vivatech_api_requests_total").increment(1);
+        let mut request = client
+            .post(url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(payload.clone());
+        if let Some(signing) = &signing {
+            let signed = signing.sign(&payload);
+            request = request
+                .header(signing::KEY_ID_HEADER, signed.key_id)
+                .header(signing::TIMESTAMP_HEADER, signed.timestamp)
+                .header(signing::NONCE_HEADER, signed.nonce)
+                .header(signing::SIGNATURE_HEADER, signed.signature);
+        }
+
+        let response = match request.send().await {
+            Ok(response) => response,
+            // timeouts and refused connections are usually over in a moment
+            Err(e) if e.is_timeout() || e.is_connect() => {
+                let kind = if e.is_timeout() { "timeout" } else { "connect" };
+                metrics::counter!("vivatech_api_errors_total", "kind" => kind).increment(1);
+                if let Some(delay) = policy.next_delay(attempt, None) {
+                    warn!(
+                        "Vivatech API request failed: {} (attempt {}/{}), retrying in {}ms",
+                        e,
+                        attempt,
+                        policy.max_attempts,
+                        delay.as_millis()
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                    continue;
+                }
+                return Err(VivatechApiError(format!("HTTP request failed: {}", e)));
+            }
+            Err(e) => {
+                metrics::counter!("vivatech_api_errors_total", "kind" => "http").increment(1);
+                return Err(VivatechApiError(format!("HTTP request failed: {}", e)));
+            }
+        };
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        // a signature rejected because our clock drifted is re-signed once, right away
+        if let Some(signing) = &signing {
+            if status == StatusCode::UNAUTHORIZED
+                && !skew_corrected
+                && signing.observe_server_clock(response.headers())
+            {
+                warn!("Vivatech API rejected the signature, retrying with corrected clock");
+                skew_corrected = true;
+                continue;
+            }
+        }
+        metrics::counter!("vivatech_api_errors_total", "kind" => status.as_u16().to_string())
+            .increment(1);
+
+        // rate limits, hinted unavailability and gateway hiccups are worth waiting for
+        let hint = retry_after_from_headers(response.headers());
+        let rate_limited = status == StatusCode::TOO_MANY_REQUESTS
+            || (status == StatusCode::SERVICE_UNAVAILABLE && hint.is_some());
+        let transient = matches!(
+            status,
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+        );
 
-    if !response.status().is_success() {
+        if rate_limited || transient {
+            if let Some(delay) = policy.next_delay(attempt, hint) {
+                warn!(
+                    "Vivatech API returned {} (attempt {}/{}), retrying in {}ms",
+                    status,
+                    attempt,
+                    policy.max_attempts,
+                    delay.as_millis()
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+                continue;
+            }
+        }
         return Err(VivatechApiError(format!(
             "API returned error status: {}",
-            response.status()
+            status
         )));
     }
-
-    Ok(response)
 }
 
-async fn parse_api_response<T: for<'de> Deserialize<'de>>(
+// read at most VIVATECH_MAX_RESPONSE_BYTES, a declared or streamed body over the cap aborts early
+pub async fn parse_api_response<T: for<'de> Deserialize<'de>>(
     response: reqwest::Response,
 ) -> Result<T, VivatechApiError> {
-    response
-        .json()
-        .await
+    let body = read_capped_body(response).await?;
+    serde_json::from_slice(&body)
         .map_err(|e| VivatechApiError(format!("Failed to parse JSON response: {}", e)))
 }
 
-// check event urgency based on date
-fn analyze_event_urgency(text: &str, current_date: NaiveDate) -> (ActionUrgency, String) {
-    match extract_date_from_text(text) {
+// a search response, checked against the fields we expect first in strict schema mode.
+// text chunks come back as plain text, see sanitize::text_chunk
+pub async fn parse_query_response(
+    response: reqwest::Response,
+) -> Result<VivatechQueryResponse, VivatechApiError> {
+    let body = read_capped_body(response).await?;
+    if config::get().strict_schema {
+        match serde_json::from_slice::<serde_json::Value>(&body) {
+            Ok(value) => report_schema_drift(&value),
+            Err(e) => warn!("Strict schema: Vivatech API response is not JSON: {}", e),
+        }
+    }
+    let mut api_response: VivatechQueryResponse = serde_json::from_slice(&body)
+        .map_err(|e| VivatechApiError(format!("Failed to parse JSON response: {}", e)))?;
+    for source in &mut api_response.sources {
+        source.text_chunk = sanitize::text_chunk(&source.text_chunk);
+    }
+    Ok(api_response)
+}
+
+// one log line per difference, so staging shows where the contract moved
+fn report_schema_drift(value: &serde_json::Value) {
+    for drift in VivatechQueryResponse::schema_drift(value) {
+        warn!(
+            kind = drift.kind,
+            path = drift.path.as_str(),
+            expected = drift.expected,
+            found = drift.found.as_str(),
+            "Vivatech API response drifted from the expected schema"
+        );
+        metrics::counter!(
+            "vivatech_schema_drift_total",
+            "kind" => drift.kind,
+            "field" => drift.field()
+        )
+        .increment(1);
+    }
+}
+
+async fn read_capped_body(mut response: reqwest::Response) -> Result<Vec<u8>, VivatechApiError> {
+    let max_bytes = config::get().max_response_bytes;
+    let too_large = |size: String| {
+        metrics::counter!("vivatech_api_errors_total", "kind" => "too_large").increment(1);
+        warn!(
+            "Vivatech API response of {} bytes exceeds the {} byte cap",
+            size, max_bytes
+        );
+        VivatechApiError(format!("Response exceeds the {} byte limit", max_bytes))
+    };
+
+    if let Some(declared) = response.content_length() {
+        if declared > max_bytes as u64 {
+            return Err(too_large(declared.to_string()));
+        }
+    }
+
+    let mut body = Vec::with_capacity(response.content_length().unwrap_or(0) as usize);
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| VivatechApiError(format!("Failed to read response: {}", e)))?
+    {
+        if body.len() + chunk.len() > max_bytes {
+            return Err(too_large(format!("more than {}", max_bytes)));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+// same-day events starting within this many hours need action now
+const IMMEDIATE_WITHIN_HOURS: i64 = 3;
+
+// evening starts from this hour, "tonight" rather than "later today"
+const EVENING_HOUR: u32 = 18;
+
+// check event urgency based on date, and on the start time for same-day events
+fn analyze_event_urgency(session: &Session, now: DateTime<Tz>) -> (ActionUrgency, String) {
+    // the next day the event runs, a multi-day event is not over after its first day
+    let next_date = session
+        .days
+        .iter()
+        .find(|date| **date >= now.date_naive())
+        .or(session.days.last());
+    match next_date.copied() {
         Some(event_date) => {
-            let days_until_event = (event_date - current_date).num_days();
+            let days_until_event = (event_date - now.date_naive()).num_days();
+            // it starts at the same time every day it runs
+            let starts_at = session.start.and_then(|start| {
+                now.timezone()
+                    .from_local_datetime(&event_date.and_time(start.time()))
+                    .earliest()
+            });
             match days_until_event {
-                0 => (
-                    ActionUrgency::Immediate,
-                    "This event is happening TODAY - immediate action required!".to_string(),
-                ),
+                0 => match starts_at {
+                    Some(starts_at) => same_day_urgency(starts_at, now),
+                    None => (
+                        ActionUrgency::Immediate,
+                        "This event is happening TODAY - immediate action required!".to_string(),
+                    ),
+                },
                 1 => (
                     ActionUrgency::Soon,
                     "This event is happening TOMORROW - plan accordingly.".to_string(),
@@ -224,66 +2777,286 @@ fn analyze_event_urgency(text: &str, current_date: NaiveDate) -> (ActionUrgency,
     }
 }
 
-// extract dates from text
-fn extract_date_from_text(text: &str) -> Option<NaiveDate> {
-    // try "June 12" format
-    let month_day_pattern = r"(January|February|March|April|May|June|July|August|September|October|November|December)\s+(\d{1,2})";
-    if let Ok(regex) = Regex::new(month_day_pattern) {
-        if let Some(captures) = regex.captures(text) {
-            if let Some(date) = extract_month_day_date(&captures) {
-                return Some(date);
-            }
+fn same_day_urgency(starts_at: DateTime<Tz>, now: DateTime<Tz>) -> (ActionUrgency, String) {
+    let at = starts_at.format("%H:%M");
+    let minutes = (starts_at - now).num_minutes();
+    match minutes {
+        m if m < 0 => (
+            ActionUrgency::Normal,
+            format!(
+                "This event started at {} today and may already be over.",
+                at
+            ),
+        ),
+        m if m < 60 => (
+            ActionUrgency::Immediate,
+            format!(
+                "This event starts in {} minutes, at {} - immediate action required!",
+                m, at
+            ),
+        ),
+        m if m < IMMEDIATE_WITHIN_HOURS * 60 => (
+            ActionUrgency::Immediate,
+            format!(
+                "This event is happening in {} - starts at {}, immediate action required!",
+                hours(m),
+                at
+            ),
+        ),
+        _ if starts_at.hour() >= EVENING_HOUR => (
+            ActionUrgency::Soon,
+            format!(
+                "This event is TONIGHT at {} - plan your evening around it.",
+                at
+            ),
+        ),
+        m => (
+            ActionUrgency::Soon,
+            format!(
+                "This event is later TODAY, in {} at {} - plan accordingly.",
+                hours(m),
+                at
+            ),
+        ),
+    }
+}
+
+// whole hours until a start, rounded down
+fn hours(minutes: i64) -> String {
+    match minutes / 60 {
+        1 => "1 hour".to_string(),
+        h => format!("{} hours", h),
+    }
+}
+
+// month names and abbreviations in english and french, longest first so "march"
+// wins over "mar"
+const MONTH_NAMES: &str = "january|february|march|april|may|june|july|august|september|october|\
+    november|december|janvier|février|fevrier|mars|avril|mai|juin|juillet|août|aout|septembre|\
+    octobre|novembre|décembre|decembre|sept|jan|feb|mar|apr|jun|jul|aug|sep|oct|nov|dec";
+
+// longest range expanded day by day, "June 1–30" is a whole month
+const MAX_RANGE_DAYS: i64 = 31;
+
+struct DatePatterns {
+    // "June 12", "June 12th, 2025", "June 11–14"
+    month_day: Regex,
+    // "12 June", "12th of June 2025", "1er juin", "11–14 June", "du 11 au 14 juin"
+    day_month: Regex,
+    // "2025-06-12", also the date part of "2025-06-12T14:00"
+    iso: Regex,
+    // "12/06/2025", "12.06.2025", read day first as written at the venue
+    numeric: Regex,
+}
+
+static DATE_PATTERNS: OnceLock<Option<DatePatterns>> = OnceLock::new();
+
+fn date_patterns() -> Option<&'static DatePatterns> {
+    DATE_PATTERNS
+        .get_or_init(|| {
+            let month = format!(r"({})\.?", MONTH_NAMES);
+            let day = r"(\d{1,2})(?:st|nd|rd|th|er)?";
+            let year = r"(?:,?\s+(\d{4}))?";
+            // the month anchors the first day, so spaced dashes are safe here
+            let range = r"(?:\s*[-–—]\s*|\s+(?:to|au)\s+)";
+            // while "Hall 3 - 12 June" must not read as the 3rd to the 12th
+            let tight_range = r"(?:[-–—]|\s+(?:to|au)\s+)";
+            Some(DatePatterns {
+                month_day: Regex::new(&format!(r"(?i)\b{month}\s+{day}(?:{range}{day})?{year}\b"))
+                    .ok()?,
+                day_month: Regex::new(&format!(
+                    r"(?i)\b{day}(?:{tight_range}{day})?\s+(?:of\s+)?{month}{year}\b"
+                ))
+                .ok()?,
+                iso: Regex::new(r"\b(\d{4})-(\d{2})-(\d{2})(?:\b|T)").ok()?,
+                numeric: Regex::new(r"\b(\d{1,2})[/.](\d{1,2})[/.](\d{4})\b").ok()?,
+            })
+        })
+        .as_ref()
+}
+
+// every date mentioned in the text, ranges expanded to each day, sorted and without
+// duplicates. dates without a year are in `year`, impossible ones are skipped
+pub fn extract_dates_from_text(text: &str, year: i32) -> Vec<NaiveDate> {
+    let Some(patterns) = date_patterns() else {
+        return Vec::new();
+    };
+    let mut dates = Vec::new();
+
+    // a month word starting a "June 12" match is not also the end of "Hall 2 June"
+    let mut claimed_months = HashSet::new();
+    for captures in patterns.month_day.captures_iter(text) {
+        let Some(month) = captures
+            .get(1)
+            .and_then(|m| month_name_to_number(m.as_str()))
+        else {
+            continue;
+        };
+        let Some((start, end)) = day_span(text, &captures, 2, 3, 4) else {
+            continue;
+        };
+        let year = captured_year(&captures, 4).unwrap_or(year);
+        if push_range(&mut dates, year, month, start, end) {
+            claimed_months.insert(captures.get(1).map(|m| m.start()));
+        }
+    }
+    for captures in patterns.day_month.captures_iter(text) {
+        let month_match = captures.get(3);
+        if claimed_months.contains(&month_match.map(|m| m.start())) {
+            continue;
         }
+        let Some(month) = month_match.and_then(|m| month_name_to_number(m.as_str())) else {
+            continue;
+        };
+        let Some((start, end)) = day_span(text, &captures, 1, 2, 4) else {
+            continue;
+        };
+        let year = captured_year(&captures, 4).unwrap_or(year);
+        push_range(&mut dates, year, month, start, end);
     }
 
-    // try "12th June" format
-    let day_month_pattern = r"(\d{1,2})(?:st|nd|rd|th)?\s+(January|February|March|April|May|June|July|August|September|October|November|December)";
-    if let Ok(regex) = Regex::new(day_month_pattern) {
-        if let Some(captures) = regex.captures(text) {
-            if let Some(date) = extract_day_month_date(&captures) {
-                return Some(date);
-            }
+    for captures in patterns.iso.captures_iter(text) {
+        let parts = (
+            captured_year(&captures, 1),
+            captured_number(&captures, 2),
+            captured_number(&captures, 3),
+        );
+        if let (Some(year), Some(month), Some(day)) = parts {
+            dates.extend(NaiveDate::from_ymd_opt(year, month, day));
+        }
+    }
+    for captures in patterns.numeric.captures_iter(text) {
+        let parts = (
+            captured_year(&captures, 3),
+            captured_number(&captures, 2),
+            captured_number(&captures, 1),
+        );
+        if let (Some(year), Some(month), Some(day)) = parts {
+            dates.extend(NaiveDate::from_ymd_opt(year, month, day));
         }
     }
 
-    None
+    dates.sort();
+    dates.dedup();
+    dates
 }
 
-fn extract_month_day_date(captures: &regex::Captures) -> Option<NaiveDate> {
-    let month_str = captures.get(1)?.as_str();
-    let day_str = captures.get(2)?.as_str();
+// the earliest date in the text
+pub fn extract_date_from_text(text: &str, year: i32) -> Option<NaiveDate> {
+    extract_dates_from_text(text, year).into_iter().next()
+}
 
-    let month_num = month_name_to_number(month_str)?;
-    let day = day_str.parse::<u32>().ok()?;
+// first and last day of a match. a day right before a clock time is an hour,
+// "June 12 - 14:00" is the 12th only and "June 14:00" no date at all
+fn day_span(
+    text: &str,
+    captures: &regex::Captures,
+    start: usize,
+    end: usize,
+    year: usize,
+) -> Option<(u32, u32)> {
+    let first = captured_number(captures, start)?;
+    let Some(last) = captures.get(end) else {
+        if captures.get(year).is_none() && followed_by_time(text, captures.get(start)?.end()) {
+            return None;
+        }
+        return Some((first, first));
+    };
+    if captures.get(year).is_none() && followed_by_time(text, last.end()) {
+        return Some((first, first));
+    }
+    let last = last.as_str().parse::<u32>().ok()?;
+    Some((first, last))
+}
 
-    NaiveDate::from_ymd_opt(2025, month_num, day)
+fn followed_by_time(text: &str, at: usize) -> bool {
+    let rest = text.get(at..).unwrap_or_default();
+    let mut chars = rest.chars();
+    match chars.next() {
+        Some(':') => true,
+        Some('h' | 'H') => chars.next().is_some_and(|c| c.is_ascii_digit()),
+        _ => {
+            let rest = rest.trim_start().to_lowercase();
+            (rest.starts_with("am") || rest.starts_with("pm"))
+                && !rest[2..].starts_with(|c: char| c.is_alphabetic())
+        }
+    }
 }
 
-fn extract_day_month_date(captures: &regex::Captures) -> Option<NaiveDate> {
-    let day_str = captures.get(1)?.as_str();
-    let month_str = captures.get(2)?.as_str();
+// every day of the range, false when not even the first day exists
+fn push_range(dates: &mut Vec<NaiveDate>, year: i32, month: u32, start: u32, end: u32) -> bool {
+    let Some(first) = NaiveDate::from_ymd_opt(year, month, start) else {
+        return false;
+    };
+    // a backwards or oversized range is taken as its first day
+    let days = match NaiveDate::from_ymd_opt(year, month, end) {
+        Some(last) if last >= first && (last - first).num_days() < MAX_RANGE_DAYS => {
+            (last - first).num_days()
+        }
+        _ => 0,
+    };
+    dates.extend((0..=days).map(|offset| first + chrono::Duration::days(offset)));
+    true
+}
 
-    let day = day_str.parse::<u32>().ok()?;
-    let month_num = month_name_to_number(month_str)?;
+fn captured_number(captures: &regex::Captures, group: usize) -> Option<u32> {
+    captures.get(group)?.as_str().parse::<u32>().ok()
+}
 
-    NaiveDate::from_ymd_opt(2025, month_num, day)
+fn captured_year(captures: &regex::Captures, group: usize) -> Option<i32> {
+    captures.get(group)?.as_str().parse::<i32>().ok()
 }
 
 // convert month names to numbers
 fn month_name_to_number(month: &str) -> Option<u32> {
-    match month.to_lowercase().as_str() {
-        "january" => Some(1),
-        "february" => Some(2),
-        "march" => Some(3),
-        "april" => Some(4),
-        "may" => Some(5),
-        "june" => Some(6),
-        "july" => Some(7),
-        "august" => Some(8),
-        "september" => Some(9),
-        "october" => Some(10),
-        "november" => Some(11),
-        "december" => Some(12),
+    match month.trim_end_matches('.').to_lowercase().as_str() {
+        "january" | "jan" | "janvier" => Some(1),
+        "february" | "feb" | "février" | "fevrier" => Some(2),
+        "march" | "mar" | "mars" => Some(3),
+        "april" | "apr" | "avril" => Some(4),
+        "may" | "mai" => Some(5),
+        "june" | "jun" | "juin" => Some(6),
+        "july" | "jul" | "juillet" => Some(7),
+        "august" | "aug" | "août" | "aout" => Some(8),
+        "september" | "sept" | "sep" | "septembre" => Some(9),
+        "october" | "oct" | "octobre" => Some(10),
+        "november" | "nov" | "novembre" => Some(11),
+        "december" | "dec" | "décembre" | "decembre" => Some(12),
         _ => None,
     }
 }
+
+static TIME_PATTERN: OnceLock<Option<Regex>> = OnceLock::new();
+
+fn time_pattern() -> Option<&'static Regex> {
+    TIME_PATTERN
+        .get_or_init(|| Regex::new(r"(?i)\b(\d{1,2})(?:[:h](\d{2}))?\s*(am|pm)?\b").ok())
+        .as_ref()
+}
+
+// clock times in order of appearance: "14:00", "9h30", "2:30 pm", "2pm"
+pub fn extract_times_from_text(text: &str) -> Vec<NaiveTime> {
+    let Some(regex) = time_pattern() else {
+        return Vec::new();
+    };
+
+    regex
+        .captures_iter(text)
+        .filter_map(|captures| {
+            let minutes = captures.get(2);
+            let meridiem = captures.get(3).map(|m| m.as_str().to_lowercase());
+            // a bare number is a day or a hall, not a time
+            if minutes.is_none() && meridiem.is_none() {
+                return None;
+            }
+            let mut hour = captures[1].parse::<u32>().ok()?;
+            let minute = minutes.map_or(Some(0), |m| m.as_str().parse::<u32>().ok())?;
+            match meridiem.as_deref() {
+                Some("pm") if hour < 12 => hour += 12,
+                Some("am") if hour == 12 => hour = 0,
+                _ => {}
+            }
+            NaiveTime::from_hms_opt(hour, minute, 0)
+        })
+        .collect()
+}
@@ -0,0 +1,366 @@
+// token spend and estimated cost per request, recorded with the caller's api key and
+// cost tags for internal chargeback
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use crate::config;
+use crate::models::TokenUsage;
+
+const MAX_COST_TAGS: usize = 10;
+const MAX_TAG_KEY_CHARS: usize = 40;
+const MAX_TAG_VALUE_CHARS: usize = 100;
+const UNTAGGED: &str = "(untagged)";
+const NO_API_KEY: &str = "(no key)";
+
+tokio::task_local! {
+    static ATTRIBUTION: Attribution;
+    // name of the key that authenticated the request, set by the auth middleware
+    static API_KEY: String;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum UsageLedgerError {
+    #[error("Usage ledger error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("Usage ledger encoding error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+// keys like "team" or "kiosk-id", short values, nothing that could carry a payload
+pub fn validate_cost_tags(tags: &BTreeMap<String, String>) -> Result<(), String> {
+    if tags.len() > MAX_COST_TAGS {
+        return Err(format!("at most {} cost_tags are allowed", MAX_COST_TAGS));
+    }
+    for (key, value) in tags {
+        let valid_key = !key.is_empty()
+            && key.len() <= MAX_TAG_KEY_CHARS
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid_key {
+            return Err(format!("invalid cost tag name '{}'", key));
+        }
+        if value.trim().is_empty() || value.chars().count() > MAX_TAG_VALUE_CHARS {
+            return Err(format!("invalid value for cost tag '{}'", key));
+        }
+    }
+    Ok(())
+}
+
+// who pays for the agent runs of one request, and what they came to so far
+#[derive(Clone)]
+pub struct Attribution {
+    ledger: Arc<UsageLedger>,
+    tags: BTreeMap<String, String>,
+    api_key: Option<String>,
+    spent: Arc<Mutex<TokenUsage>>,
+}
+
+impl Attribution {
+    // picks up the api key of the request being handled, if any
+    pub fn new(ledger: Arc<UsageLedger>, tags: BTreeMap<String, String>) -> Self {
+        Self {
+            ledger,
+            tags,
+            api_key: current_api_key(),
+            spent: Arc::new(Mutex::new(TokenUsage::default())),
+        }
+    }
+
+    // runs recorded under this attribution, for the response metadata
+    pub fn spent(&self) -> TokenUsage {
+        self.spent
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+pub async fn for_api_key<F: Future>(name: String, run: F) -> F::Output {
+    API_KEY.scope(name, run).await
+}
+
+pub fn current_api_key() -> Option<String> {
+    API_KEY.try_with(|name| name.clone()).ok()
+}
+
+// token usage recorded while `run` executes is charged to `attribution`
+pub async fn attributed<F: Future>(attribution: Option<Attribution>, run: F) -> F::Output {
+    match attribution {
+        Some(attribution) => ATTRIBUTION.scope(attribution, run).await,
+        None => run.await,
+    }
+}
+
+// called next to the token metrics. the ledger only sees attributed requests,
+// the per key metrics also runs of an authenticated request outside one
+pub fn record(
+    provider: &'static str,
+    model: &str,
+    prompt_tokens: u64,
+    completion_tokens: u64,
+    cost_usd: f64,
+) {
+    let attributed = ATTRIBUTION.try_with(|attribution| {
+        let row = UsageRow {
+            provider,
+            model,
+            prompt_tokens,
+            completion_tokens,
+            cost_usd,
+            api_key: attribution.api_key.as_deref(),
+            tags: &attribution.tags,
+        };
+        if let Err(e) = attribution.ledger.insert(&row) {
+            warn!("Failed to record token usage: {}", e);
+        }
+        attribution
+            .spent
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .add(prompt_tokens, completion_tokens, cost_usd);
+        attribution.api_key.clone()
+    });
+
+    if let Some(api_key) = attributed.ok().flatten().or_else(current_api_key) {
+        metrics::counter!("api_key_tokens_total", "api_key" => api_key.clone())
+            .increment(prompt_tokens + completion_tokens);
+        metrics::gauge!("api_key_estimated_cost_usd", "api_key" => api_key).increment(cost_usd);
+    }
+}
+
+// one agent run as stored in the ledger
+pub struct UsageRow<'a> {
+    pub provider: &'a str,
+    pub model: &'a str,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost_usd: f64,
+    pub api_key: Option<&'a str>,
+    pub tags: &'a BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageDimension {
+    #[default]
+    ApiKey,
+    Model,
+    Provider,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UsageReportQuery {
+    // tag to group by, e.g. "team". takes precedence over `by`
+    #[serde(default)]
+    pub tag: Option<String>,
+    // api_key when neither is given
+    #[serde(default)]
+    pub by: Option<UsageDimension>,
+    #[serde(default)]
+    pub since: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct UsageGroup {
+    pub value: String,
+    pub agent_runs: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+    pub estimated_cost_usd: f64,
+    pub by_provider: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UsageReport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub by: Option<UsageDimension>,
+    pub since: Option<DateTime<Utc>>,
+    pub total_tokens: u64,
+    pub estimated_cost_usd: f64,
+    // largest spend first. requests without the tag are grouped as "(untagged)",
+    // unauthenticated ones as "(no key)"
+    pub groups: Vec<UsageGroup>,
+}
+
+pub struct UsageLedger {
+    conn: Mutex<Connection>,
+}
+
+// database file with fallback
+fn get_usage_db_path() -> String {
+    config::env_var("USAGE_DB_PATH").unwrap_or_else(|_| "data/usage.sqlite".to_string())
+}
+
+impl UsageLedger {
+    // falls back to an in-memory database, spend is then lost on restart
+    pub fn from_env() -> Self {
+        let path = get_usage_db_path();
+        match Self::open(&path) {
+            Ok(ledger) => {
+                info!("Usage ledger opened at {}", path);
+                ledger
+            }
+            Err(e) => {
+                error!(
+                    "Failed to open usage ledger at {}, spend won't survive restarts: {}",
+                    path, e
+                );
+                let conn =
+                    Connection::open_in_memory().expect("in-memory sqlite is always available");
+                Self::with_connection(conn).expect("in-memory schema creation cannot fail")
+            }
+        }
+    }
+
+    pub fn open(path: &str) -> Result<Self, UsageLedgerError> {
+        if let Some(parent) = std::path::Path::new(path).parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self, UsageLedgerError> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS usage_events (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                recorded_at TEXT NOT NULL,
+                provider TEXT NOT NULL,
+                prompt_tokens INTEGER NOT NULL,
+                completion_tokens INTEGER NOT NULL,
+                tags TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS usage_by_time ON usage_events (recorded_at);",
+        )?;
+        // ledgers written before costs were tracked lack these columns
+        add_column_if_missing(&conn, "model", "TEXT NOT NULL DEFAULT ''")?;
+        add_column_if_missing(&conn, "api_key", "TEXT")?;
+        add_column_if_missing(&conn, "cost_usd", "REAL NOT NULL DEFAULT 0")?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    // one row per agent run
+    pub fn insert(&self, row: &UsageRow<'_>) -> Result<(), UsageLedgerError> {
+        let tags = serde_json::to_string(row.tags)?;
+        let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+        conn.execute(
+            "INSERT INTO usage_events
+                (recorded_at, provider, prompt_tokens, completion_tokens, tags, model, api_key, cost_usd)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                Utc::now().to_rfc3339(),
+                row.provider,
+                row.prompt_tokens as i64,
+                row.completion_tokens as i64,
+                tags,
+                row.model,
+                row.api_key,
+                row.cost_usd,
+            ],
+        )?;
+        Ok(())
+    }
+
+    // token spend grouped by the value of one tag, or by key, model or provider
+    pub fn report(&self, query: &UsageReportQuery) -> Result<UsageReport, UsageLedgerError> {
+        // rfc3339 in utc sorts chronologically as text
+        let cutoff = query.since.map(|t| t.to_rfc3339()).unwrap_or_default();
+        let rows = {
+            let conn = self.conn.lock().unwrap_or_else(PoisonError::into_inner);
+            let mut statement = conn.prepare(
+                "SELECT provider, prompt_tokens, completion_tokens, tags, model, api_key, cost_usd
+                 FROM usage_events WHERE recorded_at >= ?1",
+            )?;
+            let rows = statement.query_map(params![cutoff], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                    row.get::<_, f64>(6)?,
+                ))
+            })?;
+            rows.collect::<Result<Vec<_>, _>>()?
+        };
+
+        let by = match query.tag {
+            Some(_) => None,
+            None => Some(query.by.unwrap_or_default()),
+        };
+        let mut groups: HashMap<String, UsageGroup> = HashMap::new();
+        for (provider, prompt_tokens, completion_tokens, tags, model, api_key, cost_usd) in rows {
+            let value = match (&query.tag, by) {
+                (Some(tag), _) => {
+                    let tags: BTreeMap<String, String> = serde_json::from_str(&tags)?;
+                    tags.get(tag)
+                        .cloned()
+                        .unwrap_or_else(|| UNTAGGED.to_string())
+                }
+                (None, Some(UsageDimension::Model)) => model,
+                (None, Some(UsageDimension::Provider)) => provider.clone(),
+                (None, _) => api_key.unwrap_or_else(|| NO_API_KEY.to_string()),
+            };
+            let (prompt_tokens, completion_tokens) =
+                (prompt_tokens.max(0) as u64, completion_tokens.max(0) as u64);
+
+            let group = groups.entry(value.clone()).or_insert_with(|| UsageGroup {
+                value,
+                ..UsageGroup::default()
+            });
+            group.agent_runs += 1;
+            group.prompt_tokens += prompt_tokens;
+            group.completion_tokens += completion_tokens;
+            group.total_tokens += prompt_tokens + completion_tokens;
+            group.estimated_cost_usd += cost_usd;
+            *group.by_provider.entry(provider).or_default() += prompt_tokens + completion_tokens;
+        }
+
+        let mut groups: Vec<UsageGroup> = groups.into_values().collect();
+        groups.sort_by(|a, b| {
+            b.total_tokens
+                .cmp(&a.total_tokens)
+                .then_with(|| a.value.cmp(&b.value))
+        });
+        Ok(UsageReport {
+            tag: query.tag.clone(),
+            by,
+            since: query.since,
+            total_tokens: groups.iter().map(|group| group.total_tokens).sum(),
+            estimated_cost_usd: groups.iter().map(|group| group.estimated_cost_usd).sum(),
+            groups,
+        })
+    }
+}
+
+fn add_column_if_missing(
+    conn: &Connection,
+    column: &str,
+    definition: &str,
+) -> Result<(), UsageLedgerError> {
+    let mut statement = conn.prepare("SELECT name FROM pragma_table_info('usage_events')")?;
+    let columns = statement
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    if !columns.iter().any(|name| name == column) {
+        conn.execute_batch(&format!(
+            "ALTER TABLE usage_events ADD COLUMN {} {}",
+            column, definition
+        ))?;
+    }
+    Ok(())
+}
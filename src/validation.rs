@@ -0,0 +1,209 @@
+// tool argument validation against the advertised json schema
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use rig::completion::ToolDefinition;
+use rig::tool::Tool;
+use serde::Serialize;
+use serde_json::Value;
+use tracing::{info, info_span, warn, Instrument};
+
+// invalid calls answered with feedback before the tool call fails for real
+const MAX_CORRECTIONS: u32 = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ToolCallError<E: std::error::Error + 'static> {
+    #[error("Invalid arguments for {tool}: {problems}")]
+    InvalidArguments {
+        tool: &'static str,
+        problems: String,
+    },
+    #[error(transparent)]
+    Tool(E),
+}
+
+// what the model sees when its arguments don't match the schema
+#[derive(Debug, Serialize)]
+pub struct ValidationFeedback {
+    pub error: &'static str,
+    pub tool: &'static str,
+    pub problems: Vec<String>,
+    pub hint: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum ToolReply<O> {
+    Output(O),
+    Invalid(ValidationFeedback),
+}
+
+// wraps a tool so arguments are logged and checked before deserialization
+pub struct Validated<T> {
+    inner: T,
+    corrections: AtomicU32,
+}
+
+impl<T> Validated<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            corrections: AtomicU32::new(0),
+        }
+    }
+}
+
+impl<T: Tool> Tool for Validated<T> {
+    const NAME: &'static str = T::NAME;
+    type Error = ToolCallError<T::Error>;
+    type Args = Value;
+    type Output = ToolReply<T::Output>;
+
+    async fn definition(&self, prompt: String) -> ToolDefinition {
+        self.inner.definition(prompt).await
+    }
+
+    // one span per call so tool logs carry the request id
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let span = info_span!("tool_call", tool = T::NAME);
+        self.validate_and_call(args).instrument(span).await
+    }
+}
+
+impl<T: Tool> Validated<T> {
+    async fn validate_and_call(
+        &self,
+        args: Value,
+    ) -> Result<ToolReply<T::Output>, ToolCallError<T::Error>> {
+        info!(tool = T::NAME, arguments = %args, "Tool call requested");
+
+        let schema = self.inner.definition(String::new()).await.parameters;
+        let mut problems = Vec::new();
+        validate_value(&schema, &args, "arguments", &mut problems);
+
+        // the schema is hand written, deserialization is the final word
+        let parsed = if problems.is_empty() {
+            serde_json::from_value::<T::Args>(args).map_err(|e| problems.push(e.to_string()))
+        } else {
+            Err(())
+        };
+
+        match parsed {
+            Ok(args) => {
+                self.corrections.store(0, Ordering::Relaxed);
+                let result = self.inner.call(args).await;
+                let outcome = if result.is_ok() { "ok" } else { "error" };
+                metrics::counter!("tool_calls_total", "tool" => T::NAME, "outcome" => outcome)
+                    .increment(1);
+                result.map(ToolReply::Output).map_err(ToolCallError::Tool)
+            }
+            Err(()) => {
+                metrics::counter!(
+                    "tool_calls_total",
+                    "tool" => T::NAME,
+                    "outcome" => "invalid_arguments"
+                )
+                .increment(1);
+                let attempt = self.corrections.fetch_add(1, Ordering::Relaxed) + 1;
+                warn!(
+                    tool = T::NAME,
+                    attempt,
+                    problems = %problems.join("; "),
+                    "Tool arguments failed validation"
+                );
+
+                if attempt > MAX_CORRECTIONS {
+                    return Err(ToolCallError::InvalidArguments {
+                        tool: T::NAME,
+                        problems: problems.join("; "),
+                    });
+                }
+
+                Ok(ToolReply::Invalid(ValidationFeedback {
+                    error: "invalid_arguments",
+                    tool: T::NAME,
+                    problems,
+                    hint: "Fix the arguments to match the tool's parameter schema and call the tool again.",
+                }))
+            }
+        }
+    }
+}
+
+// subset of json schema used by our tool definitions:
+// type, enum, required, properties and items
+fn validate_value(schema: &Value, value: &Value, path: &str, problems: &mut Vec<String>) {
+    if let Some(expected) = schema.get("type").and_then(Value::as_str) {
+        if !matches_type(expected, value) {
+            problems.push(format!(
+                "`{}`: expected {}, got {}",
+                path,
+                expected,
+                type_name(value)
+            ));
+            return;
+        }
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            problems.push(format!(
+                "`{}`: must be one of {}",
+                path,
+                Value::from(allowed.clone())
+            ));
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        for name in schema
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+        {
+            if !object.contains_key(name) {
+                problems.push(format!("`{}.{}`: missing required field", path, name));
+            }
+        }
+
+        if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+            for (name, property) in properties {
+                if let Some(field) = object.get(name) {
+                    validate_value(property, field, &format!("{}.{}", path, name), problems);
+                }
+            }
+        }
+    }
+
+    if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+        for (index, item) in array.iter().enumerate() {
+            validate_value(items, item, &format!("{}[{}]", path, index), problems);
+        }
+    }
+}
+
+fn matches_type(expected: &str, value: &Value) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
@@ -0,0 +1,341 @@
+// short-lived signed tokens for the public web widget
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tracing::{info, warn};
+
+use crate::auth;
+use crate::config;
+use crate::copy;
+use crate::error::{self, AppError};
+use crate::models::GeneratePlanRequest;
+use crate::pipeline::{abuse_rejection, run_plan_request, search_sessions};
+use crate::reply::plan_error;
+use crate::state::AppState;
+use crate::tools::QueryVivatechArgs;
+
+type HmacSha256 = Hmac<Sha256>;
+
+static TOKEN_SEQ: AtomicU64 = AtomicU64::new(0);
+
+// what a widget token may call
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WidgetScope {
+    Search,
+    Plan,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WidgetClaims {
+    // unique per token, used as the abuse identity
+    pub id: String,
+    pub scopes: Vec<WidgetScope>,
+    // conference profile the widget is locked to
+    #[serde(default)]
+    pub conference: Option<String>,
+    // unix seconds
+    pub exp: i64,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum WidgetTokenError {
+    #[error("widget tokens are disabled")]
+    Disabled,
+    #[error("missing widget token")]
+    Missing,
+    #[error("malformed widget token")]
+    Malformed,
+    #[error("invalid widget token signature")]
+    BadSignature,
+    #[error("widget token expired")]
+    Expired,
+    #[error("widget token lacks the {0:?} scope")]
+    MissingScope(WidgetScope),
+}
+
+impl WidgetTokenError {
+    fn status(&self) -> StatusCode {
+        match self {
+            WidgetTokenError::Disabled => StatusCode::FORBIDDEN,
+            WidgetTokenError::MissingScope(_) => StatusCode::FORBIDDEN,
+            _ => StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            WidgetTokenError::Disabled => "widget_disabled",
+            WidgetTokenError::Missing => "missing_widget_token",
+            WidgetTokenError::MissingScope(_) => "missing_scope",
+            _ => "invalid_widget_token",
+        }
+    }
+}
+
+impl IntoResponse for WidgetTokenError {
+    fn into_response(self) -> Response {
+        error::respond(
+            self.status(),
+            error::envelope(self.code(), self.to_string()),
+        )
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WidgetTokenRequest {
+    #[serde(default = "default_scopes")]
+    pub scopes: Vec<WidgetScope>,
+    #[serde(default)]
+    pub conference: Option<String>,
+    // capped at WIDGET_TOKEN_TTL_SECONDS
+    #[serde(default)]
+    pub ttl_seconds: Option<u64>,
+}
+
+fn default_scopes() -> Vec<WidgetScope> {
+    vec![WidgetScope::Search, WidgetScope::Plan]
+}
+
+#[derive(Debug, Serialize)]
+pub struct WidgetTokenResponse {
+    pub token: String,
+    pub scopes: Vec<WidgetScope>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WidgetSearchRequest {
+    pub query: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WidgetPlanRequest {
+    pub objective: String,
+}
+
+pub struct WidgetSigner {
+    secret: Vec<u8>,
+}
+
+impl WidgetSigner {
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            secret: secret.to_vec(),
+        }
+    }
+
+    // None while WIDGET_SIGNING_SECRET is unset, which disables the widget routes
+    pub fn from_env() -> Option<Self> {
+        config::get()
+            .widget_signing_secret
+            .as_ref()
+            .map(|secret| Self::new(secret.as_bytes()))
+    }
+
+    pub fn mint(&self, request: WidgetTokenRequest) -> WidgetTokenResponse {
+        let max_ttl = config::get().widget_token_ttl_seconds;
+        let ttl = request.ttl_seconds.unwrap_or(max_ttl).clamp(1, max_ttl);
+        let now = Utc::now();
+        let expires_at = now + Duration::seconds(ttl as i64);
+
+        let claims = WidgetClaims {
+            id: format!(
+                "{:x}-{}",
+                now.timestamp_nanos_opt().unwrap_or_default(),
+                TOKEN_SEQ.fetch_add(1, Ordering::Relaxed)
+            ),
+            scopes: request.scopes,
+            conference: request.conference,
+            exp: expires_at.timestamp(),
+        };
+
+        // payload.signature, both base64url
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).unwrap_or_default());
+        let signature = URL_SAFE_NO_PAD.encode(self.sign(payload.as_bytes()));
+        WidgetTokenResponse {
+            token: format!("{}.{}", payload, signature),
+            scopes: claims.scopes,
+            expires_at,
+        }
+    }
+
+    pub fn verify(&self, token: &str) -> Result<WidgetClaims, WidgetTokenError> {
+        let (payload, signature) = token.split_once('.').ok_or(WidgetTokenError::Malformed)?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| WidgetTokenError::Malformed)?;
+
+        // constant time comparison
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&signature)
+            .map_err(|_| WidgetTokenError::BadSignature)?;
+
+        let claims: WidgetClaims = URL_SAFE_NO_PAD
+            .decode(payload)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .ok_or(WidgetTokenError::Malformed)?;
+        if claims.exp <= Utc::now().timestamp() {
+            return Err(WidgetTokenError::Expired);
+        }
+        Ok(claims)
+    }
+
+    // the bearer widget token of a request, verified and carrying `scope`
+    pub fn authorize(
+        &self,
+        headers: &HeaderMap,
+        scope: WidgetScope,
+    ) -> Result<WidgetClaims, WidgetTokenError> {
+        let token = bearer_token(headers).ok_or(WidgetTokenError::Missing)?;
+        let claims = self.verify(token)?;
+        if !claims.scopes.contains(&scope) {
+            return Err(WidgetTokenError::MissingScope(scope));
+        }
+        Ok(claims)
+    }
+
+    fn sign(&self, payload: &[u8]) -> Vec<u8> {
+        let mut mac = self.mac();
+        mac.update(payload);
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        HmacSha256::new_from_slice(&self.secret).expect("hmac accepts keys of any length")
+    }
+}
+
+// the widget's backend mints tokens with WIDGET_ISSUER_KEY, never the browser
+pub fn require_issuer(headers: &HeaderMap) -> Result<WidgetSigner, Response> {
+    let Some(signer) = WidgetSigner::from_env() else {
+        warn!("Widget token request rejected: WIDGET_SIGNING_SECRET not configured");
+        return Err(WidgetTokenError::Disabled.into_response());
+    };
+
+    let expected = config::get().widget_issuer_key.as_deref();
+    if expected.is_some_and(|expected| auth::secret_matches(bearer_token(headers), expected)) {
+        Ok(signer)
+    } else {
+        Err(error::respond(
+            StatusCode::UNAUTHORIZED,
+            error::envelope("invalid_issuer_key", "invalid issuer key"),
+        ))
+    }
+}
+
+// checks the bearer widget token carries `scope`
+pub fn require_scope(
+    headers: &HeaderMap,
+    scope: WidgetScope,
+) -> Result<WidgetClaims, WidgetTokenError> {
+    WidgetSigner::from_env()
+        .ok_or(WidgetTokenError::Disabled)?
+        .authorize(headers, scope)
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+}
+
+// browser origins allowed to call the widget routes
+pub fn get_allowed_origins() -> Vec<String> {
+    config::env_var("WIDGET_ALLOWED_ORIGINS")
+        .map(|s| {
+            s.split(',')
+                .map(|origin| origin.trim().to_string())
+                .filter(|origin| !origin.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// mint a short-lived token for the public widget, called by the Vivatech site's backend
+pub async fn widget_token_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<WidgetTokenRequest>,
+) -> Response {
+    let started = Instant::now();
+    let signer = match require_issuer(&headers) {
+        Ok(signer) => signer,
+        Err(rejection) => return rejection,
+    };
+
+    if request.scopes.is_empty() {
+        return plan_error(
+            AppError::Validation(copy::error("error.scope_required", &[])),
+            started,
+        )
+        .into_response();
+    }
+    if let Err(e) = state.conferences.resolve(request.conference.as_deref()) {
+        return plan_error(AppError::Validation(e), started).into_response();
+    }
+
+    let minted = signer.mint(request);
+    info!(
+        "Minted widget token with scopes {:?}, valid until {}",
+        minted.scopes, minted.expires_at
+    );
+    Json(minted).into_response()
+}
+
+// session and partner search straight from the browser widget
+pub async fn widget_search_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<WidgetSearchRequest>,
+) -> Response {
+    let started = Instant::now();
+    let claims = match require_scope(&headers, WidgetScope::Search) {
+        Ok(claims) => claims,
+        Err(e) => return e.into_response(),
+    };
+
+    let client = format!("widget:{}", claims.id);
+    if let Some(rejection) = abuse_rejection(state.abuse.check(&client, &request.query)) {
+        return rejection.into_response();
+    }
+
+    let args = QueryVivatechArgs::new(request.query);
+    match search_sessions(&state, claims.conference.as_deref(), args, started).await {
+        Ok(sources) => Json(sources).into_response(),
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+// standard plan for the widget, no profile and the conference comes from the token
+pub async fn widget_plan_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<WidgetPlanRequest>,
+) -> Response {
+    let claims = match require_scope(&headers, WidgetScope::Plan) {
+        Ok(claims) => claims,
+        Err(e) => return e.into_response(),
+    };
+
+    let payload = GeneratePlanRequest::simple(request.objective, claims.conference);
+    run_plan_request(&state, &format!("widget:{}", claims.id), payload)
+        .await
+        .into_response()
+}
@@ -0,0 +1,90 @@
+// websocket chat protocol: json text frames both ways, one agent run per user message
+
+use std::collections::BTreeMap;
+
+use axum::extract::ws::{Message, WebSocket};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::sessions::ChatRequest;
+use crate::tone::Tone;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct WsQuery {
+    // resumes an existing chat session, a new one is started when unset
+    #[serde(default)]
+    pub session_id: Option<String>,
+}
+
+// a user message, with the same options as POST /chat
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ChatMessage {
+    pub message: String,
+    #[serde(default)]
+    pub conference: Option<String>,
+    #[serde(default)]
+    pub user_id: Option<String>,
+    #[serde(default)]
+    pub tone: Option<Tone>,
+    #[serde(default)]
+    pub cost_tags: BTreeMap<String, String>,
+}
+
+impl ChatMessage {
+    pub fn into_request(self, session_id: &str) -> ChatRequest {
+        ChatRequest {
+            session_id: session_id.to_string(),
+            message: self.message,
+            conference: self.conference,
+            user_id: self.user_id,
+            tone: self.tone,
+            cost_tags: self.cost_tags,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientFrame {
+    Message(ChatMessage),
+    // forget the conversation so far, the session id stays
+    Reset,
+}
+
+// frames about the session itself, the agent's frames are streaming::StreamEvent
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SessionFrame {
+    Ready { session_id: String },
+    Reset { session_id: String },
+}
+
+pub fn new_session_id() -> String {
+    format!("ws-{:016x}", rand::random::<u64>())
+}
+
+pub async fn send<T: Serialize>(socket: &mut WebSocket, frame: &T) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(frame).map_err(axum::Error::new)?;
+    socket.send(Message::Text(text.into())).await
+}
+
+// the next client frame, an Err for frames that are not valid json frames,
+// None once the client closed the socket
+pub async fn next_frame(socket: &mut WebSocket) -> Option<Result<ClientFrame, String>> {
+    loop {
+        match socket.recv().await? {
+            Ok(Message::Text(text)) => {
+                return Some(
+                    serde_json::from_str(text.as_str())
+                        .map_err(|e| format!("Invalid frame: {}", e)),
+                )
+            }
+            Ok(Message::Binary(_)) => {
+                return Some(Err("Binary frames are not supported".to_string()))
+            }
+            // pings are answered by axum
+            Ok(Message::Ping(_) | Message::Pong(_)) => continue,
+            Ok(Message::Close(_)) | Err(_) => return None,
+        }
+    }
+}
@@ -0,0 +1,1316 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::extract::ConnectInfo;
+use axum::http::{header, Extensions, HeaderMap, HeaderValue, StatusCode};
+use axum::response::IntoResponse;
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Utc};
+use mockito::{Matcher, Server, ServerGuard};
+use proptest::prelude::*;
+use rig::providers::openai;
+use rig::tool::Tool;
+use serde_json::json;
+use vivaagent::abuse;
+use vivaagent::agents::{build_planning_agent, GenerationParams};
+use vivaagent::auth::{ApiKeyAuth, ApiKeyName, AuthError};
+use vivaagent::catalog_sync::{CatalogWatcher, ChangeKind};
+use vivaagent::citations::SourceCollector;
+use vivaagent::coalesce::RequestCoalescer;
+use vivaagent::conference::ConferenceProfile;
+use vivaagent::error::AppError;
+use vivaagent::health::CapabilityMatrix;
+use vivaagent::llm::{LlmClient, ModelChoice, Provider};
+use vivaagent::locks::JobLocks;
+use vivaagent::mock::{
+    self, FixtureCatalog, MockClient, MockContext, MockResponder, MockTurn, SessionCatalog,
+};
+use vivaagent::models::{GeneratePlanRequest, PlanOutcome, VivatechSource};
+use vivaagent::plan_cache::{CachedPlan, PlanCache};
+use vivaagent::planner::{Planner, PlannerConfig};
+use vivaagent::plans::PlanStore;
+use vivaagent::profiles::{travel_guidance, TravelContext};
+use vivaagent::ratelimit::RateLimiter;
+use vivaagent::refine::{self, SlotChange};
+use vivaagent::rerank::{RerankMode, Reranker};
+use vivaagent::retry;
+use vivaagent::sanitize;
+use vivaagent::signing::UpstreamSigning;
+use vivaagent::tools::{
+    detect_conflicts, extract_dates_from_text, render_ical, IcalExportError, ItinerarySession,
+    NetworkingArgs, QueryVivatechAPI, QueryVivatechArgs, SearchOutcome, SuggestNetworkingTargets,
+};
+use vivaagent::widget::{WidgetScope, WidgetSigner, WidgetTokenError, WidgetTokenRequest};
+
+const FINAL_PLAN: &str = "1. AI keynote, Stage 1 (today)\n2. Fintech panel (in 2 days)";
+
+fn conference_on(date: NaiveDate) -> ConferenceProfile {
+    let mut profile = ConferenceProfile::vivatech_2025();
+    profile.reference_date = Some(date);
+    profile
+}
+
+fn completion(message: serde_json::Value, finish_reason: &str) -> String {
+    json!({
+        "id": "chatcmpl-test",
+        "object": "chat.completion",
+        "created": 0,
+        "model": "gpt-4o",
+        "choices": [{ "index": 0, "message": message, "finish_reason": finish_reason }],
+        "usage": { "prompt_tokens": 1, "completion_tokens": 1, "total_tokens": 2 }
+    })
+    .to_string()
+}
+
+fn tool_call(name: &str, arguments: serde_json::Value) -> String {
+    completion(
+        json!({
+            "role": "assistant",
+            "content": null,
+            "tool_calls": [{
+                "id": "call_1",
+                "type": "function",
+                "function": { "name": name, "arguments": arguments.to_string() }
+            }]
+        }),
+        "tool_calls",
+    )
+}
+
+fn final_answer(text: &str) -> String {
+    completion(json!({ "role": "assistant", "content": text }), "stop")
+}
+
+// fake openai: answers with `first` until a tool result comes back, then with the plan.
+// returns every request body so tests can look at what the agent sent
+async fn mock_openai(first: String) -> (ServerGuard, Arc<Mutex<Vec<String>>>) {
+    let mut server = Server::new_async().await;
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let captured = requests.clone();
+
+    server
+        .mock("POST", Matcher::Regex("/chat/completions$".to_string()))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body_from_request(move |request| {
+            let body = String::from_utf8_lossy(request.body().unwrap()).to_string();
+            let reply = if body.contains("\"role\":\"tool\"") {
+                final_answer(FINAL_PLAN)
+            } else {
+                first.clone()
+            };
+            captured.lock().unwrap().push(body);
+            reply.into_bytes()
+        })
+        .create_async()
+        .await;
+
+    (server, requests)
+}
+
+#[tokio::test]
+async fn planning_agent_ranks_events_with_assess_timeliness() {
+    let arguments = json!({
+        "events": [
+            { "id": "s1", "text_chunk": "AI keynote on June 11 at Stage 1" },
+            { "id": "s2", "text_chunk": "Fintech panel on June 13 in Hall 2" }
+        ]
+    });
+    let (server, requests) = mock_openai(tool_call("assess_event_timeliness", arguments)).await;
+
+    let client = LlmClient::OpenAI(openai::Client::from_url("test-key", &server.url()));
+    let profile = conference_on(NaiveDate::from_ymd_opt(2025, 6, 11).unwrap());
+    let agent = build_planning_agent(
+        &client,
+        &profile,
+        "",
+        &SourceCollector::default(),
+        &Arc::new(CapabilityMatrix::default()),
+        &GenerationParams::new(openai::GPT_4O),
+    );
+
+    let plan = agent
+        .chat("What should I see first?", &[], 3)
+        .await
+        .expect("agent loop should finish");
+    assert_eq!(plan, FINAL_PLAN);
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert!(requests[0].contains("assess_event_timeliness"));
+    assert!(requests[0].contains("query_vivatech_api"));
+
+    // the tool result handed back to the model carries the ranking
+    assert!(requests[1].contains("Immediate"));
+    assert!(requests[1].contains("TODAY"));
+    assert!(requests[1].contains("in 2 days"));
+}
+
+#[tokio::test]
+async fn planning_agent_gets_feedback_for_invalid_timeliness_arguments() {
+    let arguments = json!({ "sessions": ["AI keynote on June 11"] });
+    let (server, requests) = mock_openai(tool_call("assess_event_timeliness", arguments)).await;
+
+    let client = LlmClient::OpenAI(openai::Client::from_url("test-key", &server.url()));
+    let profile = conference_on(NaiveDate::from_ymd_opt(2025, 6, 11).unwrap());
+    let agent = build_planning_agent(
+        &client,
+        &profile,
+        "",
+        &SourceCollector::default(),
+        &Arc::new(CapabilityMatrix::default()),
+        &GenerationParams::new(openai::GPT_4O),
+    );
+
+    let plan = agent
+        .chat("What should I see first?", &[], 3)
+        .await
+        .expect("validation feedback should not abort the loop");
+    assert_eq!(plan, FINAL_PLAN);
+
+    let requests = requests.lock().unwrap();
+    assert!(requests[1].contains("invalid_arguments"));
+    assert!(requests[1].contains("missing required field"));
+}
+
+#[tokio::test]
+async fn planning_agent_preamble_explains_when_to_assess_timeliness() {
+    let (server, requests) = mock_openai(final_answer(FINAL_PLAN)).await;
+
+    let client = LlmClient::OpenAI(openai::Client::from_url("test-key", &server.url()));
+    let profile = conference_on(NaiveDate::from_ymd_opt(2025, 6, 12).unwrap());
+    let agent = build_planning_agent(
+        &client,
+        &profile,
+        "",
+        &SourceCollector::default(),
+        &Arc::new(CapabilityMatrix::default()),
+        &GenerationParams::new(openai::GPT_4O),
+    );
+
+    agent
+        .chat("Plan my day", &[], 0)
+        .await
+        .expect("plain answer should pass through");
+
+    let requests = requests.lock().unwrap();
+    assert!(requests[0].contains("Pass the sessions you found to the assess_event_timeliness tool"));
+    assert!(requests[0].contains("June 12, 2025"));
+}
+
+#[test]
+fn fixture_catalog_ranks_sessions_by_matching_words() {
+    let catalog = FixtureCatalog::bundled();
+
+    let results = catalog.search("keynote in Hall 1", 3);
+    assert_eq!(results[0].id, "session-101");
+    assert_eq!(results[1].id, "session-105");
+    assert_eq!(results[0].score, 1.0);
+    assert!(catalog.search("quantum knitting", 3).is_empty());
+}
+
+#[test]
+fn text_chunks_lose_markup_and_long_ones_end_on_a_sentence() {
+    let raw = "<div><h2>**AI &amp; Robotics** keynote</h2>\n<p>Hall   1,\t<a href=\"/s/1\">Stage 3</a></p>\
+        <script>track()</script><ul><li>- [Speaker page](https://vivatech.com/x)</li></ul></div>";
+    assert_eq!(
+        sanitize::text_chunk(raw),
+        "AI & Robotics keynote\nHall 1, Stage 3\nSpeaker page"
+    );
+
+    let long = "First sentence here. Second one is longer than the rest of them.";
+    assert_eq!(sanitize::truncate(long, 40), "First sentence here.");
+    assert_eq!(sanitize::truncate(long, 15), "First sentence…");
+    assert_eq!(sanitize::truncate(long, 0), long);
+}
+
+#[tokio::test]
+async fn mock_planner_searches_the_fixture_without_api_keys() {
+    // a no-op when another test installed it first, both use the bundled fixture
+    mock::install_mock_catalog(Arc::new(FixtureCatalog::bundled()));
+
+    let client = LlmClient::Mock(MockClient::canned());
+    let collector = SourceCollector::default();
+    let profile = conference_on(NaiveDate::from_ymd_opt(2025, 6, 11).unwrap());
+    let agent = build_planning_agent(
+        &client,
+        &profile,
+        "",
+        &collector,
+        &Arc::new(CapabilityMatrix::default()),
+        &GenerationParams::new("mock-planner"),
+    );
+
+    let plan = agent
+        .chat("climate startups pitch", &[], 3)
+        .await
+        .expect("mock run should finish");
+    assert!(plan.contains("[session-104]"));
+    assert!(collector.sources().iter().any(|s| s.id == "session-104"));
+}
+
+#[tokio::test]
+async fn planner_plans_through_the_library_api() {
+    mock::install_mock_catalog(Arc::new(FixtureCatalog::bundled()));
+
+    let planner = Planner::new(PlannerConfig {
+        model: Some("mock:mock-planner".to_string()),
+        conference: Some(conference_on(NaiveDate::from_ymd_opt(2025, 6, 11).unwrap())),
+        ..PlannerConfig::default()
+    })
+    .expect("the mock provider needs no key");
+
+    let plan = planner
+        .plan("climate startups pitch")
+        .await
+        .expect("mock run should finish");
+    assert!(plan.body.contains("[session-104]"));
+    assert!(plan.sources.iter().any(|s| s.id == "session-104"));
+    assert_eq!(plan.model, "mock:mock-planner");
+    assert_eq!(plan.fallback_from, None);
+}
+
+#[tokio::test]
+async fn networking_shortlist_matches_partners_to_interests() {
+    mock::install_mock_catalog(Arc::new(FixtureCatalog::bundled()));
+    let profile = conference_on(NaiveDate::from_ymd_opt(2025, 6, 11).unwrap());
+    let tool = SuggestNetworkingTargets::new(QueryVivatechAPI::for_conference(&profile), &profile);
+
+    let shortlist = tool
+        .call(NetworkingArgs {
+            role: "climate VC".to_string(),
+            interests: vec!["renewable energy".to_string(), "security".to_string()],
+        })
+        .await
+        .expect("the fixture catalog answers");
+
+    let green = shortlist
+        .targets
+        .iter()
+        .find(|target| target.partner.id == "partner-201")
+        .expect("GreenGrid matches renewable energy");
+    assert_eq!(green.partner.hall.as_deref(), Some("Hall 1"));
+    assert_eq!(green.partner.booth.as_deref(), Some("C12"));
+    assert!(green
+        .matched_interests
+        .contains(&"renewable energy".to_string()));
+    assert!(green.talking_points[0].starts_with("Open with what they do: Live demo"));
+    assert!(green
+        .talking_points
+        .iter()
+        .any(|point| point.contains("raising")));
+    assert!(shortlist
+        .targets
+        .iter()
+        .all(|target| target.partner.id.starts_with("partner-")));
+
+    let route = shortlist.route.expect("both booths have a hall");
+    assert_eq!(route.stops[0].hall.as_deref(), Some("Hall 1"));
+    assert_eq!(route.walking_minutes, Some(6));
+}
+
+// scripted model: calls the timeliness tool once, then answers with its output
+struct ScriptedResponder {
+    preambles: Mutex<Vec<String>>,
+}
+
+impl MockResponder for ScriptedResponder {
+    fn next_turn(&self, context: &MockContext<'_>) -> MockTurn {
+        self.preambles
+            .lock()
+            .unwrap()
+            .push(context.preamble.to_string());
+        match context.tool_results.first() {
+            Some(output) => MockTurn::Answer(output.clone()),
+            None => MockTurn::CallTool {
+                name: "assess_event_timeliness".to_string(),
+                arguments: json!({
+                    "events": [{ "id": "s1", "text_chunk": "AI keynote on June 11 at Stage 1" }]
+                }),
+            },
+        }
+    }
+}
+
+#[tokio::test]
+async fn mock_client_runs_real_tools_for_a_scripted_model() {
+    let responder = Arc::new(ScriptedResponder {
+        preambles: Mutex::new(Vec::new()),
+    });
+    let client = LlmClient::Mock(MockClient::new(responder.clone()));
+    let profile = conference_on(NaiveDate::from_ymd_opt(2025, 6, 11).unwrap());
+    let agent = build_planning_agent(
+        &client,
+        &profile,
+        "",
+        &SourceCollector::default(),
+        &Arc::new(CapabilityMatrix::default()),
+        &GenerationParams::new("mock-planner"),
+    );
+
+    let answer = agent
+        .chat("What should I see first?", &[], 3)
+        .await
+        .expect("scripted run should finish");
+    assert!(answer.contains("Immediate"));
+
+    let preambles = responder.preambles.lock().unwrap();
+    assert_eq!(preambles.len(), 2);
+    assert!(preambles[0].contains("June 11, 2025"));
+}
+
+const ENGLISH_MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+const FRENCH_MONTHS: [&str; 12] = [
+    "janvier",
+    "février",
+    "mars",
+    "avril",
+    "mai",
+    "juin",
+    "juillet",
+    "août",
+    "septembre",
+    "octobre",
+    "novembre",
+    "décembre",
+];
+
+// session text around the date, no digits, month names or clock words
+const FILLER_WORDS: [&str; 9] = [
+    "the", "keynote", "on", "Stage", "Hall", "with", "panel", "at", "Paris",
+];
+
+fn client_of(peer: &str, authorization: Option<&str>, api_key: Option<ApiKeyName>) -> String {
+    let mut headers = HeaderMap::new();
+    if let Some(authorization) = authorization {
+        headers.insert(header::AUTHORIZATION, authorization.parse().unwrap());
+    }
+    let mut extensions = Extensions::new();
+    extensions.insert(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+    if let Some(api_key) = api_key {
+        extensions.insert(api_key);
+    }
+    abuse::client_identity(&headers, &extensions)
+}
+
+#[test]
+fn api_keys_sharing_a_prefix_are_separate_clients() {
+    let keys = ApiKeyAuth::from_json(
+        r#"[{"name": "alpha", "key": "sk-proj-aaaa1111"}, {"name": "beta", "key": "sk-proj-aaaa2222"}]"#,
+    );
+    let authenticate = |key: &str| {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", key.parse().unwrap());
+        keys.authenticate(&headers).expect("configured key")
+    };
+
+    let alpha = client_of(
+        "10.0.0.1:5000",
+        None,
+        Some(authenticate("sk-proj-aaaa1111")),
+    );
+    let beta = client_of(
+        "10.0.0.1:5000",
+        None,
+        Some(authenticate("sk-proj-aaaa2222")),
+    );
+    assert_eq!(alpha, "key:alpha");
+    assert_eq!(beta, "key:beta");
+}
+
+#[test]
+fn unchecked_tokens_and_forwarded_for_do_not_change_the_client() {
+    // widget tokens all start with the same encoded claims prefix
+    let first = client_of("10.0.0.1:5000", Some("Bearer eyJpZCI6IjE4-first"), None);
+    let second = client_of("10.0.0.1:5000", Some("Bearer eyJpZCI6IjE4-second"), None);
+    let elsewhere = client_of("10.0.0.2:5000", Some("Bearer eyJpZCI6IjE4-first"), None);
+    assert_eq!(first, "ip:10.0.0.1");
+    assert_eq!(first, second);
+    assert_ne!(first, elsewhere);
+
+    // without TRUSTED_PROXY_HOPS the forwarded-for chain is the client's own say
+    let mut headers = HeaderMap::new();
+    headers.insert("x-forwarded-for", "203.0.113.7".parse().unwrap());
+    let mut extensions = Extensions::new();
+    extensions.insert(ConnectInfo("10.0.0.1:5000".parse::<SocketAddr>().unwrap()));
+    assert_eq!(abuse::client_identity(&headers, &extensions), "ip:10.0.0.1");
+}
+
+fn widget_request(scopes: Vec<WidgetScope>, ttl_seconds: Option<u64>) -> WidgetTokenRequest {
+    WidgetTokenRequest {
+        scopes,
+        conference: Some("vivatech-2025".to_string()),
+        ttl_seconds,
+    }
+}
+
+fn bearer(token: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        format!("Bearer {}", token).parse().unwrap(),
+    );
+    headers
+}
+
+#[test]
+fn widget_tokens_verify_with_their_own_secret_only() {
+    let signer = WidgetSigner::new(b"widget-secret");
+    let first = signer.mint(widget_request(vec![WidgetScope::Search], None));
+    let second = signer.mint(widget_request(vec![WidgetScope::Search], None));
+
+    let claims = signer.verify(&first.token).expect("freshly minted");
+    assert_eq!(claims.scopes, vec![WidgetScope::Search]);
+    assert_eq!(claims.conference.as_deref(), Some("vivatech-2025"));
+    assert_ne!(claims.id, signer.verify(&second.token).unwrap().id);
+
+    let other = WidgetSigner::new(b"another-secret");
+    assert!(matches!(
+        other.verify(&first.token),
+        Err(WidgetTokenError::BadSignature)
+    ));
+    let (payload, signature) = first.token.split_once('.').unwrap();
+    let tampered = format!("{}x.{}", payload, signature);
+    assert!(signer.verify(&tampered).is_err());
+    assert!(matches!(
+        signer.verify("no-signature"),
+        Err(WidgetTokenError::Malformed)
+    ));
+}
+
+#[test]
+fn widget_tokens_expire() {
+    let signer = WidgetSigner::new(b"widget-secret");
+    let minted = signer.mint(widget_request(vec![WidgetScope::Plan], Some(1)));
+    assert!(signer.verify(&minted.token).is_ok());
+
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    assert!(matches!(
+        signer.verify(&minted.token),
+        Err(WidgetTokenError::Expired)
+    ));
+}
+
+#[test]
+fn widget_tokens_only_open_their_scopes() {
+    let signer = WidgetSigner::new(b"widget-secret");
+    let minted = signer.mint(widget_request(vec![WidgetScope::Search], None));
+
+    let headers = bearer(&minted.token);
+    assert!(signer.authorize(&headers, WidgetScope::Search).is_ok());
+    assert!(matches!(
+        signer.authorize(&headers, WidgetScope::Plan),
+        Err(WidgetTokenError::MissingScope(WidgetScope::Plan))
+    ));
+    assert!(matches!(
+        signer.authorize(&HeaderMap::new(), WidgetScope::Search),
+        Err(WidgetTokenError::Missing)
+    ));
+}
+
+fn upstream_headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        headers.insert(*name, HeaderValue::from_str(value).unwrap());
+    }
+    headers
+}
+
+#[test]
+fn retry_after_reads_seconds_and_http_dates() {
+    let headers = upstream_headers(&[("retry-after", "7")]);
+    assert_eq!(
+        retry::retry_after_from_headers(&headers),
+        Some(Duration::from_secs(7))
+    );
+
+    let soon = chrono::Utc::now() + chrono::Duration::seconds(90);
+    let date = soon.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+    let delay =
+        retry::retry_after_from_headers(&upstream_headers(&[("retry-after", &date)])).unwrap();
+    assert!(delay > Duration::from_secs(80) && delay <= Duration::from_secs(90));
+
+    // a date already past means retry now
+    let past = chrono::Utc::now() - chrono::Duration::seconds(60);
+    let date = past.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+    assert_eq!(
+        retry::retry_after_from_headers(&upstream_headers(&[("retry-after", &date)])),
+        Some(Duration::ZERO)
+    );
+    assert_eq!(retry::retry_after_from_headers(&HeaderMap::new()), None);
+}
+
+#[test]
+fn retry_after_falls_back_to_ratelimit_reset_headers() {
+    let headers = upstream_headers(&[("x-ratelimit-reset-requests", "6m0s")]);
+    assert_eq!(
+        retry::retry_after_from_headers(&headers),
+        Some(Duration::from_secs(360))
+    );
+    let headers = upstream_headers(&[("x-ratelimit-reset-tokens", "120ms")]);
+    assert_eq!(
+        retry::retry_after_from_headers(&headers),
+        Some(Duration::from_millis(120))
+    );
+    let headers = upstream_headers(&[("ratelimit-reset", "12")]);
+    assert_eq!(
+        retry::retry_after_from_headers(&headers),
+        Some(Duration::from_secs(12))
+    );
+
+    // Retry-After wins, and an unreadable one gives way to the reset headers
+    let headers = upstream_headers(&[("retry-after", "3"), ("ratelimit-reset", "12")]);
+    assert_eq!(
+        retry::retry_after_from_headers(&headers),
+        Some(Duration::from_secs(3))
+    );
+    let headers = upstream_headers(&[("retry-after", "soon"), ("ratelimit-reset", "12")]);
+    assert_eq!(
+        retry::retry_after_from_headers(&headers),
+        Some(Duration::from_secs(12))
+    );
+}
+
+#[test]
+fn retry_after_reads_provider_error_text() {
+    assert_eq!(
+        retry::retry_after_from_message("Rate limit reached. Please try again in 20s."),
+        Some(Duration::from_secs(20))
+    );
+    assert_eq!(
+        retry::retry_after_from_message("429 Too Many Requests, retry after 3 seconds"),
+        Some(Duration::from_secs(3))
+    );
+    assert_eq!(
+        retry::retry_after_from_message("Please try again in 500ms"),
+        Some(Duration::from_millis(500))
+    );
+    assert_eq!(retry::retry_after_from_message("invalid api key"), None);
+    assert!(retry::is_rate_limit_message("429 Too Many Requests"));
+    assert!(!retry::is_rate_limit_message("400 Bad Request"));
+}
+
+#[test]
+fn llm_errors_are_429_only_with_their_own_hint() {
+    let failed = AppError::llm("context too long".to_string(), None);
+    assert_eq!(failed.status(), StatusCode::BAD_GATEWAY);
+    assert!(failed
+        .into_response()
+        .headers()
+        .get(header::RETRY_AFTER)
+        .is_none());
+
+    let limited = AppError::llm("rate limited".to_string(), Some(Duration::from_secs(5)));
+    assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(limited.code(), "llm_error");
+    let response = limited.into_response();
+    assert_eq!(response.headers()[header::RETRY_AFTER], "5");
+
+    // a rate limited call does not turn the next, unrelated failure into a 429
+    let next = AppError::llm("context too long".to_string(), None);
+    assert_eq!(next.status(), StatusCode::BAD_GATEWAY);
+}
+
+fn catalog(ids: &[&str]) -> Vec<VivatechSource> {
+    ids.iter()
+        .map(|id| {
+            serde_json::from_value(json!({
+                "id": id,
+                "text_chunk": format!("Session {} on June 12 at 10:00", id),
+            }))
+            .unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn sessions_absent_from_a_pull_are_missing_until_confirmed() {
+    let watcher = CatalogWatcher::new(2025);
+    assert!(watcher.apply(catalog(&["a", "b", "c"])).is_empty());
+
+    let changes = watcher.apply(catalog(&["a", "b"]));
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].kind, ChangeKind::Missing);
+    assert_eq!(changes[0].source_id, "c");
+    // the last version stays in the snapshot meanwhile
+    assert!(watcher.with_snapshot(|snapshot| snapshot.contains_key("c")));
+
+    assert!(watcher.apply(catalog(&["a", "b"])).is_empty());
+    let changes = watcher.apply(catalog(&["a", "b"]));
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].kind, ChangeKind::Removed);
+    assert!(!watcher.with_snapshot(|snapshot| snapshot.contains_key("c")));
+
+    // a session that comes back unchanged was never gone
+    watcher.apply(catalog(&["a"]));
+    assert!(watcher.apply(catalog(&["a", "b"])).is_empty());
+}
+
+#[test]
+fn repeated_small_pulls_become_the_new_baseline() {
+    let watcher = CatalogWatcher::new(2025);
+    watcher.apply(catalog(&["a", "b", "c", "d", "e"]));
+
+    assert!(watcher.apply(catalog(&["a"])).is_empty());
+    assert!(watcher.apply(catalog(&["a"])).is_empty());
+    assert_eq!(watcher.with_snapshot(|snapshot| snapshot.len()), 5);
+    assert!(watcher.apply(catalog(&["a"])).is_empty());
+    assert_eq!(watcher.with_snapshot(|snapshot| snapshot.len()), 1);
+
+    // diffs resume against it
+    let changes = watcher.apply(catalog(&["a", "f"]));
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].kind, ChangeKind::Added);
+}
+
+#[test]
+fn travel_guidance_keeps_half_hour_offsets() {
+    let travel = TravelContext {
+        home_timezone: "Asia/Kolkata".to_string(),
+        hotel: None,
+        commute_minutes: None,
+        arrival: None,
+    };
+    let conference = conference_on(NaiveDate::from_ymd_opt(2025, 6, 11).unwrap());
+    let guidance = travel_guidance(&travel, &conference);
+    assert!(guidance.contains("(+3:30 hours relative"), "{}", guidance);
+    assert!(guidance.contains("travelling west"), "{}", guidance);
+}
+
+#[test]
+fn objectives_in_other_scripts_are_not_nonsense() {
+    let monitor = abuse::AbuseMonitor::from_env();
+    for objective in [
+        "人工智能和机器人技术的主题演讲",
+        "मैं कृत्रिम बुद्धिमत्ता सत्र देखना चाहता हूँ",
+        "أريد حضور جلسات الذكاء الاصطناعي",
+        "Rynek fintech w Europie środkowej",
+    ] {
+        assert!(
+            matches!(
+                monitor.check("ip:10.0.0.7", objective),
+                abuse::Verdict::Allow
+            ),
+            "{}",
+            objective
+        );
+    }
+    assert!(matches!(
+        monitor.check("ip:10.0.0.8", "asdfghjkl qwrtzp xcvbnm"),
+        abuse::Verdict::Reject(abuse::AbuseSignal::Nonsense)
+    ));
+}
+
+#[tokio::test]
+async fn local_job_lock_excludes_a_second_holder() {
+    let locks = JobLocks::from_env();
+    let ttl = Duration::from_secs(60);
+
+    let lease = locks
+        .try_acquire("lock-test", ttl)
+        .await
+        .expect("lock is free");
+    assert!(locks.try_acquire("lock-test", ttl).await.is_none());
+    drop(lease);
+
+    // held on after the job, like after an ingestion
+    let lease = locks
+        .try_acquire("lock-test", ttl)
+        .await
+        .expect("released on drop");
+    lease.hold_for(ttl).await;
+    assert!(locks.try_acquire("lock-test", ttl).await.is_none());
+}
+
+#[tokio::test]
+async fn concurrent_identical_requests_share_one_run() {
+    let coalescer = RequestCoalescer::<String>::new();
+    let counter = AtomicUsize::new(0);
+    let runs = &counter;
+    let task = move || async move {
+        runs.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        "plan".to_string()
+    };
+
+    let (first, second) = tokio::join!(
+        coalescer.run("ai sessions".to_string(), task),
+        coalescer.run("ai sessions".to_string(), task),
+    );
+    assert_eq!(first, "plan");
+    assert_eq!(second, "plan");
+    assert_eq!(counter.load(Ordering::SeqCst), 1);
+
+    // nothing is kept once the run finished
+    coalescer.run("ai sessions".to_string(), task).await;
+    assert_eq!(counter.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn rate_limiter_buckets_are_per_client_and_all_or_nothing() {
+    let limiter = RateLimiter::new(3, 60);
+
+    assert!(limiter.check("key:alpha").is_ok());
+    assert!(limiter.take("key:alpha", 3).is_err());
+    assert!(limiter.take("key:alpha", 2).is_ok());
+    let retry_after = limiter.check("key:alpha").unwrap_err();
+    assert!(retry_after > Duration::ZERO && retry_after <= Duration::from_secs(1));
+
+    // a batch larger than the bucket takes all of it
+    assert!(limiter.take("key:beta", 10).is_ok());
+    assert!(limiter.check("key:beta").is_err());
+    assert!(limiter.check("key:gamma").is_ok());
+
+    assert!(!RateLimiter::new(3, 0).is_enabled());
+}
+
+fn api_key_headers(key: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert("x-api-key", key.parse().unwrap());
+    headers
+}
+
+#[test]
+fn api_keys_fail_closed_and_have_their_own_limits() {
+    let keys = ApiKeyAuth::from_json(
+        r#"[{"name": "alpha", "key": "sk-alpha", "rate_limit_per_minute": 2},
+            {"name": "off", "key": "sk-off", "disabled": true}]"#,
+    );
+    assert!(keys.is_enforced());
+    assert!(matches!(
+        keys.authenticate(&HeaderMap::new()),
+        Err(AuthError::Missing)
+    ));
+    assert!(matches!(
+        keys.authenticate(&api_key_headers("sk-unknown")),
+        Err(AuthError::Invalid)
+    ));
+    assert!(matches!(
+        keys.authenticate(&api_key_headers("sk-off")),
+        Err(AuthError::Disabled(name)) if name == "off"
+    ));
+
+    for _ in 0..2 {
+        assert_eq!(
+            keys.authenticate(&api_key_headers("sk-alpha")).unwrap().0,
+            "alpha"
+        );
+    }
+    assert!(matches!(
+        keys.authenticate(&api_key_headers("sk-alpha")),
+        Err(AuthError::RateLimited { limit: 2, .. })
+    ));
+
+    // a typo in API_KEYS must not open the api
+    let broken = ApiKeyAuth::from_json(r#"[{"name": "alpha", "key": "sk-alpha""#);
+    assert!(broken.is_enforced());
+    assert!(matches!(
+        broken.authenticate(&api_key_headers("sk-alpha")),
+        Err(AuthError::Invalid)
+    ));
+}
+
+fn source(id: &str, text: &str, score: f32) -> VivatechSource {
+    serde_json::from_value(json!({ "id": id, "text_chunk": text, "score": score })).unwrap()
+}
+
+fn outcome(body: &str, sources: Vec<VivatechSource>) -> PlanOutcome {
+    PlanOutcome {
+        body: body.to_string(),
+        next_action: None,
+        itinerary: None,
+        sources,
+        model: "gpt-4o".to_string(),
+        fallback_from: None,
+        listing: None,
+    }
+}
+
+fn plan_request(objective: &str) -> GeneratePlanRequest {
+    GeneratePlanRequest::simple(objective.to_string(), Some("vivatech-2025".to_string()))
+}
+
+#[test]
+fn plan_cache_matches_normalized_objectives_and_skips_listings() {
+    let cache = PlanCache::new(10, Duration::from_secs(60));
+    let plan = CachedPlan {
+        outcome: outcome("1. {{live:session-101}} keynote", Vec::new()),
+        conference: Some("vivatech-2025".to_string()),
+    };
+    cache.insert(&plan_request("AI Sessions"), &plan);
+
+    let hit = cache
+        .get(&plan_request("  ai   sessions "))
+        .expect("same objective once normalized");
+    // stored as the model wrote it, placeholders are resolved per hit
+    assert_eq!(hit.outcome.body, "1. {{live:session-101}} keynote");
+
+    let mut cited = plan_request("AI Sessions");
+    cited.include_citations = !cited.include_citations;
+    assert!(cache.get(&cited).is_none());
+
+    let listing = CachedPlan {
+        outcome: PlanOutcome {
+            listing: Some(Vec::new()),
+            ..outcome("AI keynote, Hall 1", Vec::new())
+        },
+        conference: None,
+    };
+    cache.insert(&plan_request("fintech panels"), &listing);
+    assert!(cache.get(&plan_request("fintech panels")).is_none());
+}
+
+#[test]
+fn share_links_expire_and_only_the_owner_can_share() {
+    let store = PlanStore::open(":memory:").unwrap();
+    let plan_id = store
+        .insert(
+            &plan_request("AI Sessions"),
+            "vivatech-2025",
+            &outcome("1. AI keynote", Vec::new()),
+            None,
+            Some("alpha"),
+        )
+        .unwrap();
+
+    assert!(!store.share(&plan_id, Some("beta"), "stolen", None).unwrap());
+    assert!(store.shared("stolen").unwrap().is_none());
+
+    let expires_at = Utc::now() + chrono::Duration::hours(1);
+    assert!(store
+        .share(&plan_id, Some("alpha"), "open", Some(expires_at))
+        .unwrap());
+    let (plan, expiry) = store.shared("open").unwrap().expect("link is live");
+    assert_eq!(plan.id, plan_id);
+    assert_eq!(expiry.map(|t| t.timestamp()), Some(expires_at.timestamp()));
+
+    let expired = Utc::now() - chrono::Duration::seconds(1);
+    assert!(store
+        .share(&plan_id, Some("alpha"), "stale", Some(expired))
+        .unwrap());
+    assert!(store.shared("stale").unwrap().is_none());
+}
+
+fn signing_key(key_id: &str) -> UpstreamSigning {
+    serde_json::from_value(json!({
+        "url": "https://partner.example",
+        "key_id": key_id,
+        "secret": "shh",
+    }))
+    .unwrap()
+}
+
+#[test]
+fn clock_offsets_are_learned_per_signing_key() {
+    let key = signing_key("partner");
+    let handed_out = key.clone();
+    let other = signing_key("other");
+
+    let mut headers = HeaderMap::new();
+    let ahead = Utc::now() + chrono::Duration::hours(2);
+    headers.insert(header::DATE, ahead.to_rfc2822().parse().unwrap());
+    assert!(handed_out.observe_server_clock(&headers));
+    assert!(!handed_out.observe_server_clock(&headers));
+
+    let timestamp =
+        |signing: &UpstreamSigning| -> i64 { signing.sign(b"{}").timestamp.parse().unwrap() };
+    assert!((timestamp(&key) - ahead.timestamp()).abs() <= 5);
+    assert!((timestamp(&other) - Utc::now().timestamp()).abs() <= 5);
+}
+
+#[test]
+fn overlapping_sessions_get_alternatives_that_fit() {
+    let sessions = vec![
+        source("keynote", "AI keynote on June 12 at 10:00-11:00", 1.0),
+        source(
+            "robotics-panel",
+            "Robotics panel on June 12 at 10:30-11:30",
+            1.0,
+        ),
+        source("fintech", "Fintech talk on June 12 at 14:00", 1.0),
+        source("drinks", "Networking drinks with founders", 1.0),
+    ];
+    let candidates = vec![
+        source(
+            "robotics-demo",
+            "Robotics demo on June 12 at 11:00-12:00",
+            1.0,
+        ),
+        source(
+            "robotics-workshop",
+            "Robotics workshop on June 12 at 14:30",
+            1.0,
+        ),
+    ];
+
+    let report = detect_conflicts(&sessions, &candidates, 2025);
+    assert_eq!(report.conflicts.len(), 1, "{}", report.summary);
+    let conflict = &report.conflicts[0];
+    assert_eq!(conflict.first, "keynote");
+    assert_eq!(conflict.second, "robotics-panel");
+    assert_eq!(conflict.overlap_minutes, 30);
+    let alternatives: Vec<&str> = conflict
+        .alternatives
+        .iter()
+        .map(|slot| slot.source_id.as_str())
+        .collect();
+    assert_eq!(alternatives, ["robotics-demo"]);
+    assert_eq!(report.unscheduled, ["drinks"]);
+}
+
+fn itinerary_session(title: &str, start: &str, end: Option<&str>) -> ItinerarySession {
+    let at = |time: &str| NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap();
+    ItinerarySession {
+        title: title.to_string(),
+        start: at(start),
+        end: end.map(at),
+        location: Some("Hall 1, Stage 2".to_string()),
+        description: None,
+        source_id: Some("session-101".to_string()),
+    }
+}
+
+#[test]
+fn ical_export_is_in_utc_with_folded_lines() {
+    let title = "Opening keynote on artificial intelligence, robotics and the future of work";
+    let ics = render_ical(
+        &[itinerary_session(title, "2025-06-12 10:00", None)],
+        "Europe/Paris",
+        "Vivatech 2025",
+    )
+    .unwrap();
+
+    assert!(ics.contains("DTSTART:20250612T080000Z\r\n"));
+    assert!(ics.contains("DTEND:20250612T090000Z\r\n"));
+    assert!(ics.contains("LOCATION:Hall 1\\, Stage 2\r\n"));
+    assert!(ics.contains("\r\n "), "the summary is folded");
+    assert!(ics.split("\r\n").all(|line| line.len() <= 75));
+    assert!(ics.ends_with("END:VCALENDAR\r\n"));
+
+    assert!(matches!(
+        render_ical(&[], "Mars/Olympus", "Vivatech 2025"),
+        Err(IcalExportError::UnknownTimezone(_))
+    ));
+    assert!(matches!(
+        render_ical(
+            &[itinerary_session(
+                "Late",
+                "2025-06-12 10:00",
+                Some("2025-06-12 09:00")
+            )],
+            "Europe/Paris",
+            "Vivatech 2025",
+        ),
+        Err(IcalExportError::EndBeforeStart(_))
+    ));
+}
+
+#[test]
+fn refined_plans_report_added_removed_and_moved_sessions() {
+    let sources = vec![
+        source(
+            "quantum",
+            "Quantum computing keynote with Alice Martin.",
+            0.9,
+        ),
+        source("fintech", "Fintech regulation panel with Bob Durand.", 0.8),
+        source("robotics", "Robotics startup demos by Carol Petit.", 0.7),
+        source(
+            "climate",
+            "Climate investors breakfast with Dave Moreau.",
+            0.6,
+        ),
+    ];
+    let store = PlanStore::open(":memory:").unwrap();
+    let plan_id = store
+        .insert(
+            &plan_request("deep tech"),
+            "vivatech-2025",
+            &outcome(
+                "1. Quantum computing keynote\n2. Fintech regulation panel\n3. Robotics startup demos",
+                sources[..3].to_vec(),
+            ),
+            None,
+            None,
+        )
+        .unwrap();
+    let before = store.get(&plan_id, None).unwrap().expect("stored plan");
+
+    let diff = refine::diff(
+        &before,
+        "1. Robotics startup demos\n2. Quantum computing keynote\n3. Climate investors breakfast",
+        &sources,
+        2025,
+    );
+    let ids = |changes: &[SlotChange]| -> Vec<String> {
+        changes.iter().map(|c| c.session_id.clone()).collect()
+    };
+    assert_eq!(ids(&diff.added), ["climate"]);
+    assert_eq!(diff.added[0].to_step, Some(3));
+    assert_eq!(ids(&diff.removed), ["fintech"]);
+    assert_eq!(diff.removed[0].from_step, Some(2));
+    // of two kept sessions that swapped places, one explains the new order
+    assert_eq!(diff.moved.len(), 1);
+    assert_eq!(diff.unchanged, 1);
+}
+
+#[test]
+fn repeated_sources_merge_with_their_best_score() {
+    let collector = SourceCollector::default();
+    collector.extend(&[
+        source("keynote", "AI keynote", 0.4),
+        source("panel", "Fintech panel", 0.6),
+        source("demo", "Robotics demo", 0.5),
+    ]);
+    collector.extend(&[
+        source("keynote", "AI keynote", 0.9),
+        source("demo", "Robotics demo", 0.6),
+    ]);
+
+    let sources = collector.sources();
+    let ids: Vec<&str> = sources.iter().map(|s| s.id.as_str()).collect();
+    // the demo ties with the panel, two searches returned it
+    assert_eq!(ids, ["keynote", "demo", "panel"]);
+    assert_eq!(sources[0].score, 0.9);
+}
+
+#[tokio::test]
+async fn search_keeps_the_top_k_best_sources() {
+    mock::install_mock_catalog(Arc::new(FixtureCatalog::bundled()));
+    let profile = conference_on(NaiveDate::from_ymd_opt(2025, 6, 11).unwrap());
+    let search = QueryVivatechAPI::for_conference(&profile);
+
+    let SearchOutcome::Sources(all) = search
+        .call(QueryVivatechArgs::new("keynote in Hall 1"))
+        .await
+        .unwrap()
+    else {
+        panic!("the fixture has keynotes");
+    };
+    assert!(all.len() > 1);
+
+    let SearchOutcome::Sources(top) = search
+        .call(QueryVivatechArgs {
+            top_k: Some(1),
+            ..QueryVivatechArgs::new("keynote in Hall 1")
+        })
+        .await
+        .unwrap()
+    else {
+        panic!("the fixture has keynotes");
+    };
+    assert_eq!(top.len(), 1);
+    assert_eq!(top[0].id, "session-101");
+}
+
+// scores sessions like the relevance prompt would, skipping the ones it finds unrelated
+struct RelevanceJudge;
+
+impl MockResponder for RelevanceJudge {
+    fn next_turn(&self, _context: &MockContext<'_>) -> MockTurn {
+        MockTurn::Answer(String::new())
+    }
+
+    fn extract(&self, _instructions: &str, input: &str) -> Option<serde_json::Value> {
+        assert!(input.starts_with("Query: robotics"));
+        Some(json!({ "items": [{ "id": "demo", "score": 9 }, { "id": "keynote", "score": 2 }] }))
+    }
+}
+
+#[tokio::test]
+async fn llm_reranking_reorders_and_keeps_the_top_k() {
+    let reranker = Reranker::llm(
+        LlmClient::Mock(MockClient::new(Arc::new(RelevanceJudge))),
+        ModelChoice {
+            provider: Provider::Mock,
+            model: "mock-judge".to_string(),
+        },
+        2,
+    );
+    let ranked = reranker
+        .rerank(
+            "robotics",
+            vec![
+                source("keynote", "AI keynote", 0.9),
+                source("panel", "Fintech panel", 0.8),
+                source("demo", "Robotics demo", 0.1),
+            ],
+        )
+        .await;
+
+    let ids: Vec<&str> = ranked.iter().map(|s| s.id.as_str()).collect();
+    assert_eq!(ids, ["demo", "keynote"]);
+    assert!((ranked[0].score - 0.9).abs() < 1e-6);
+
+    assert_eq!("llm".parse::<RerankMode>(), Ok(RerankMode::Llm));
+    assert_eq!("".parse::<RerankMode>(), Ok(RerankMode::Off));
+    assert!("cross-encoder".parse::<RerankMode>().is_err());
+}
+
+fn filler() -> impl Strategy<Value = String> {
+    prop::collection::vec(prop::sample::select(FILLER_WORDS.to_vec()), 0..6)
+        .prop_map(|words| words.join(" "))
+}
+
+fn date_in(year: i32) -> impl Strategy<Value = NaiveDate> {
+    (1u32..=12, 1u32..=31).prop_filter_map("no such day", move |(month, day)| {
+        NaiveDate::from_ymd_opt(year, month, day)
+    })
+}
+
+fn ordinal(day: u32) -> &'static str {
+    match day {
+        1 | 21 | 31 => "st",
+        2 | 22 => "nd",
+        3 | 23 => "rd",
+        _ => "th",
+    }
+}
+
+fn english(date: NaiveDate) -> &'static str {
+    ENGLISH_MONTHS[date.month0() as usize]
+}
+
+fn french(date: NaiveDate) -> &'static str {
+    FRENCH_MONTHS[date.month0() as usize]
+}
+
+// date extraction over generated session text, every supported format
+proptest! {
+    #[test]
+    fn extracts_english_month_day(date in date_in(2025), before in filler(), after in filler()) {
+        let text = format!("{} {} {} {}", before, english(date), date.day(), after);
+        prop_assert!(extract_dates_from_text(&text, 2025).contains(&date), "{}", text);
+    }
+
+    #[test]
+    fn extracts_ordinal_day_month(date in date_in(2025), before in filler(), after in filler()) {
+        let text = format!(
+            "{} {}{} {} {}",
+            before,
+            date.day(),
+            ordinal(date.day()),
+            english(date),
+            after
+        );
+        prop_assert!(extract_dates_from_text(&text, 2025).contains(&date), "{}", text);
+    }
+
+    #[test]
+    fn extracts_french_month_names(date in date_in(2025), before in filler(), after in filler()) {
+        let text = format!("{} le {} {} {}", before, date.day(), french(date), after);
+        prop_assert!(extract_dates_from_text(&text, 2025).contains(&date), "{}", text);
+    }
+
+    #[test]
+    fn explicit_years_win_over_the_default(date in date_in(2026), before in filler()) {
+        let text = format!("{} {} {}, 2026", before, english(date), date.day());
+        prop_assert!(extract_dates_from_text(&text, 2025).contains(&date), "{}", text);
+    }
+
+    #[test]
+    fn extracts_iso_dates(
+        year in 1970i32..2100,
+        date in date_in(2025),
+        before in filler(),
+        after in filler(),
+    ) {
+        let Some(date) = date.with_year(year) else {
+            return Ok(());
+        };
+        let text = format!("{} {} {}", before, date.format("%Y-%m-%d"), after);
+        prop_assert!(extract_dates_from_text(&text, 2025).contains(&date), "{}", text);
+    }
+
+    #[test]
+    fn extracts_numeric_dates_day_first(
+        date in date_in(2025),
+        before in filler(),
+        after in filler(),
+    ) {
+        for text in [
+            format!("{} {} {}", before, date.format("%d/%m/%Y"), after),
+            format!("{} {} {}", before, date.format("%-d.%-m.%Y"), after),
+        ] {
+            prop_assert!(extract_dates_from_text(&text, 2025).contains(&date), "{}", text);
+        }
+    }
+
+    #[test]
+    fn ranges_expand_to_every_day(
+        month in 1u32..=12,
+        start in 1u32..=25,
+        length in 0u32..=3,
+        dash in prop::sample::select(vec!["-", "–", " – ", " to "]),
+    ) {
+        let end = start + length;
+        let text = format!("{} {}{}{}", ENGLISH_MONTHS[month as usize - 1], start, dash, end);
+        let expected: Vec<NaiveDate> = (start..=end)
+            .filter_map(|day| NaiveDate::from_ymd_opt(2025, month, day))
+            .collect();
+        prop_assert_eq!(extract_dates_from_text(&text, 2025), expected, "{}", text);
+    }
+
+    #[test]
+    fn french_ranges_expand_to_every_day(
+        month in 1u32..=12,
+        start in 1u32..=25,
+        length in 0u32..=3,
+    ) {
+        let end = start + length;
+        let text = format!("du {} au {} {}", start, end, FRENCH_MONTHS[month as usize - 1]);
+        let expected: Vec<NaiveDate> = (start..=end)
+            .filter_map(|day| NaiveDate::from_ymd_opt(2025, month, day))
+            .collect();
+        prop_assert_eq!(extract_dates_from_text(&text, 2025), expected, "{}", text);
+    }
+
+    #[test]
+    fn returns_every_date_not_just_the_first(
+        first in date_in(2025),
+        second in date_in(2025),
+        between in filler(),
+    ) {
+        let text = format!(
+            "{} {} {} {}",
+            english(first),
+            first.day(),
+            between,
+            second.format("%Y-%m-%d")
+        );
+        let dates = extract_dates_from_text(&text, 2025);
+        prop_assert!(dates.contains(&first) && dates.contains(&second), "{}", text);
+    }
+
+    #[test]
+    fn results_are_sorted_and_unique(text in "\\PC{0,80}") {
+        let dates = extract_dates_from_text(&text, 2025);
+        prop_assert!(dates.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", dates);
+    }
+
+    #[test]
+    fn text_without_digits_has_no_dates(text in "[^0-9]{0,80}") {
+        prop_assert!(extract_dates_from_text(&text, 2025).is_empty(), "{}", text);
+    }
+
+    #[test]
+    fn clock_times_are_not_days(date in date_in(2025), hour in 0u32..24, minute in 0u32..60) {
+        let text = format!("{} {} {:02}:{:02}", date.day(), english(date), hour, minute);
+        prop_assert_eq!(extract_dates_from_text(&text, 2025), vec![date], "{}", text);
+    }
+}
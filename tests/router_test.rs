@@ -0,0 +1,235 @@
+// the real router in MOCK_MODE, in its own binary since the configuration is read once per
+// process and the mock would break the scripted providers of the integration tests
+
+use std::sync::Once;
+
+use axum::body::{to_bytes, Body};
+use axum::http::{header, Request, StatusCode};
+use axum::Router;
+use serde_json::{json, Value};
+use tower::ServiceExt;
+use vivaagent::router::{self, build_router};
+use vivaagent::state::AppState;
+
+const KEY: &str = "router-test-key";
+
+fn setup() {
+    static ENV: Once = Once::new();
+    ENV.call_once(|| {
+        std::env::set_var("MOCK_MODE", "true");
+        std::env::set_var(
+            "API_KEYS",
+            json!([{ "name": "router-test", "key": KEY }]).to_string(),
+        );
+        std::env::set_var("PLAN_DB_PATH", ":memory:");
+        std::env::set_var("USAGE_DB_PATH", ":memory:");
+        std::env::set_var("RATE_LIMIT_BURST", "2");
+        std::env::set_var("RATE_LIMIT_PER_MINUTE", "1");
+    });
+}
+
+fn router() -> Router {
+    setup();
+    build_router(AppState::new())
+}
+
+fn generate(objective: &str, key: Option<&str>) -> Request<Body> {
+    let mut request =
+        Request::post("/generate-plan").header(header::CONTENT_TYPE, "application/json");
+    if let Some(key) = key {
+        request = request.header(header::AUTHORIZATION, format!("Bearer {}", key));
+    }
+    request
+        .body(Body::from(json!({ "objective": objective }).to_string()))
+        .unwrap()
+}
+
+async fn send(router: &Router, request: Request<Body>) -> (StatusCode, Option<String>, Value) {
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let retry_after = response
+        .headers()
+        .get(header::RETRY_AFTER)
+        .map(|v| v.to_str().unwrap().to_string());
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, retry_after, serde_json::from_slice(&bytes).unwrap())
+}
+
+#[tokio::test]
+async fn generate_plan_needs_an_api_key() {
+    let router = router();
+
+    let (status, _, body) = send(&router, generate("AI startups to meet", None)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["code"], "missing_api_key");
+
+    let (status, _, body) = send(&router, generate("AI startups to meet", Some("wrong"))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["code"], "invalid_api_key");
+}
+
+#[tokio::test]
+async fn generate_plan_answers_from_the_mock_planner() {
+    let router = router();
+
+    let (status, _, body) = send(&router, generate("AI keynotes on day one", Some(KEY))).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["status"], "success");
+    assert!(body["plan"].as_str().unwrap().contains("Plan for"));
+}
+
+#[tokio::test]
+async fn generate_plan_failures_use_the_error_envelope() {
+    let router = router();
+
+    let (status, _, body) = send(&router, generate("   ", Some(KEY))).await;
+    assert!(status.is_client_error(), "{}", status);
+    assert_eq!(body["status"], "error");
+    assert!(body["code"].is_string());
+    assert!(body["message"].is_string());
+}
+
+#[tokio::test]
+async fn generate_plan_is_rate_limited_per_key() {
+    let router = router();
+
+    for objective in ["fintech panels", "climate tech founders"] {
+        let (status, _, body) = send(&router, generate(objective, Some(KEY))).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+
+    let (status, retry_after, body) = send(&router, generate("robotics demos", Some(KEY))).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body["code"], "rate_limited");
+    assert!(retry_after.unwrap().parse::<u64>().unwrap() >= 1);
+}
+
+#[tokio::test]
+async fn keyless_clients_are_limited_by_their_address() {
+    setup();
+    let state = AppState::new();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(router::serve(listener, build_router(state.clone())));
+
+    // no widget token, the limiter still charges the caller before the handler refuses it
+    let client = reqwest::Client::new();
+    let mut statuses = Vec::new();
+    for _ in 0..3 {
+        let response = client
+            .post(format!("http://{}/widget/plan", addr))
+            .json(&json!({ "objective": "AI keynotes" }))
+            .send()
+            .await
+            .unwrap();
+        statuses.push(response.status().as_u16());
+    }
+    assert_eq!(statuses[2], 429, "{:?}", statuses);
+
+    assert!(state.rate_limiter.check("ip:127.0.0.1").is_err());
+    assert!(state.rate_limiter.check("anonymous").is_ok());
+}
+
+#[tokio::test]
+async fn batches_take_a_token_per_plan_and_reads_take_none() {
+    let router = router();
+
+    let batch = Request::post("/generate-plans")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, format!("Bearer {}", KEY))
+        .body(Body::from(
+            json!({ "requests": [
+                { "objective": "fintech panels" },
+                { "objective": "robotics demos" },
+            ] })
+            .to_string(),
+        ))
+        .unwrap();
+    let response = router.clone().oneshot(batch).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+    let (status, _, body) = send(&router, generate("AI keynotes", Some(KEY))).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body["code"], "rate_limited");
+
+    let templates = Request::get("/templates")
+        .header(header::AUTHORIZATION, format!("Bearer {}", KEY))
+        .body(Body::empty())
+        .unwrap();
+    let (status, _, body) = send(&router, templates).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+fn graphql(query: &str) -> Request<Body> {
+    Request::post("/graphql")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, format!("Bearer {}", KEY))
+        .body(Body::from(json!({ "query": query }).to_string()))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn graphql_aliases_each_take_a_token() {
+    let router = router();
+
+    // the request's token pays for the first search, the second token of the burst for
+    // another, the third alias finds the bucket empty
+    let (status, _, body) = send(
+        &router,
+        graphql(
+            r#"{
+                a: sessions(query: "AI keynotes") { id }
+                b: sessions(query: "fintech panels") { id }
+                c: sessions(query: "robotics demos") { id }
+            }"#,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let errors = body["errors"].as_array().expect("one alias is refused");
+    assert_eq!(errors.len(), 1, "{}", body);
+    assert_eq!(errors[0]["extensions"]["code"], "rate_limited");
+    assert!(
+        errors[0]["extensions"]["retryAfterSeconds"]
+            .as_u64()
+            .unwrap()
+            >= 1
+    );
+}
+
+#[tokio::test]
+async fn graphql_documents_are_capped_in_size() {
+    let router = router();
+
+    let fields: Vec<String> = (0..200)
+        .map(|i| format!("p{}: plan(id: \"missing\") {{ id }}", i))
+        .collect();
+    let (status, _, body) = send(&router, graphql(&format!("{{ {} }}", fields.join(" ")))).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert!(body["data"].is_null(), "{}", body);
+    assert!(body["errors"][0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("too complex"));
+}
+
+#[tokio::test]
+async fn graphql_plan_input_is_validated_like_rest() {
+    let router = router();
+
+    let (status, _, body) = send(
+        &router,
+        graphql(
+            r#"mutation {
+                generatePlan(input: { objective: "AI keynotes", strategy: TWO_STAGE, temperature: 5.0 }) {
+                    ... on GeneratedPlan { planId }
+                }
+            }"#,
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["errors"][0]["extensions"]["code"], "validation_error");
+    assert_eq!(body["errors"][0]["extensions"]["status"], 400);
+}